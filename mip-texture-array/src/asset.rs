@@ -229,7 +229,7 @@ impl RenderAsset for MippedArrayTexture {
 
             // Get precomputed size
             let size = view_sizes[mip_level as usize];
-            let workgroup_count: u32 = (size + WORKGROUP_SIZE_PER_DIM - 1) / WORKGROUP_SIZE_PER_DIM;
+            let workgroup_count: u32 = size.div_ceil(WORKGROUP_SIZE_PER_DIM);
 
             pass.dispatch_workgroups(workgroup_count, workgroup_count, self.array_layers);
         }
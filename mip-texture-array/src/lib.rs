@@ -118,7 +118,7 @@ impl MipArrayTextureBuilder {
     ) -> Result<usize, TextureArrayBuilderError> {
         let image = images
             .get(asset_id)
-            .ok_or_else(|| TextureArrayBuilderError::ImageNotFound(asset_id.clone()))?;
+            .ok_or(TextureArrayBuilderError::ImageNotFound(asset_id))?;
         let extent = image.texture_descriptor.size;
 
         if extent.width != self.dims || extent.height != self.dims {
@@ -159,7 +159,7 @@ impl MipArrayTextureBuilder {
             // The image might have been removed from the assets by the time that finish() is run, so we handle the error again here so we avoid panicking in a library.
             let source = images
                 .get(*asset_id)
-                .ok_or_else(|| TextureArrayBuilderError::ImageNotFound(asset_id.clone()))?;
+                .ok_or(TextureArrayBuilderError::ImageNotFound(*asset_id))?;
 
             // Finally we perform the actual copy.
             self.copy_to_arr_tex(&mut arr_texture, source, idx as _)?;
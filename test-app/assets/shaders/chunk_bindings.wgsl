@@ -1,4 +1,13 @@
-#import "shaders/vxl_types.wgsl"::ChunkQuad
+#import "shaders/vxl_types.wgsl"::{ChunkQuad, ChunkFade}
 
 @group(2) @binding(0) var<uniform> chunk_position: vec3f;
-@group(2) @binding(1) var<storage> quads: array<ChunkQuad>;
\ No newline at end of file
+@group(2) @binding(1) var<storage> quads: array<ChunkQuad>;
+@group(2) @binding(2) var<uniform> chunk_fade: ChunkFade;
+@group(2) @binding(3) var<storage> quad_attributes: array<u32>;
+
+#ifdef QUAD_ATTRIBUTE_CHANNELS
+// read a channel registered in `QuadAttributes`, `channel` is one of the `QUAD_ATTR_*` shader defs
+fn quad_attribute(quad_index: u32, channel: u32) -> u32 {
+    return quad_attributes[quad_index * #{QUAD_ATTRIBUTE_CHANNELS}u + channel];
+}
+#endif
@@ -12,6 +12,18 @@ const FACE_SHIFT: u32 = #{FACE_SHIFT}u;
 const FLIP_UV_X_BIT: u32 = #{FLIP_UV_X_BIT}u;
 const FLIP_UV_Y_BIT: u32 = #{FLIP_UV_Y_BIT}u;
 
+const CORNER_DEPTH_SHIFT: u32 = #{CORNER_DEPTH_SHIFT}u;
+const CORNER_DEPTH_BITS: u32 = #{CORNER_DEPTH_BITS}u;
+const CORNER_DEPTH_MASK: u32 = #{CORNER_DEPTH_MASK}u;
+const CORNER_DEPTH_STEPS: u32 = #{CORNER_DEPTH_STEPS}u;
+
+const LIGHT_CHANNEL_MASK: u32 = #{LIGHT_CHANNEL_MASK}u;
+const LIGHT_SKY_SHIFT: u32 = #{LIGHT_SKY_SHIFT}u;
+const LIGHT_MAX: u32 = #{LIGHT_MAX}u;
+
 const HAS_NORMAL_MAP_BIT: u32 = #{HAS_NORMAL_MAP_BIT}u;
+const MATERIAL_CHANNEL_MASK: u32 = #{MATERIAL_CHANNEL_MASK}u;
+const ROUGHNESS_SHIFT: u32 = #{ROUGHNESS_SHIFT}u;
+const METALLIC_SHIFT: u32 = #{METALLIC_SHIFT}u;
 
 const DEFAULT_PBR_INPUT_FLAGS: u32 = #{DEFAULT_PBR_INPUT_FLAGS}u;
@@ -1,4 +1,4 @@
-#import "shaders/vxl_types.wgsl"::FaceTexture
+#import "shaders/vxl_types.wgsl"::{FaceTexture, VoxelFog}
 
 @group(1) @binding(0) var<storage> faces: array<FaceTexture>;
 // the base texture
@@ -6,4 +6,6 @@
 @group(1) @binding(2) var color_sampler: sampler;
 // the normal map
 @group(1) @binding(3) var normal_texture: texture_2d_array<f32>;
-@group(1) @binding(4) var normal_sampler: sampler;
\ No newline at end of file
+@group(1) @binding(4) var normal_sampler: sampler;
+// distance fog, only read when VOXEL_FOG is defined
+@group(1) @binding(5) var<uniform> fog: VoxelFog;
@@ -1,4 +1,5 @@
 #import "shaders/vxl_types.wgsl"::ChunkQuad
+#import "shaders/vxl_types.wgsl"::ChunkFade
 
 #import "shaders/constants.wgsl"::ROTATION_MASK
 #import "shaders/constants.wgsl"::ROTATION_SHIFT
@@ -6,6 +7,13 @@
 #import "shaders/constants.wgsl"::FACE_SHIFT
 #import "shaders/constants.wgsl"::FLIP_UV_X_BIT
 #import "shaders/constants.wgsl"::FLIP_UV_Y_BIT
+#import "shaders/constants.wgsl"::CORNER_DEPTH_SHIFT
+#import "shaders/constants.wgsl"::CORNER_DEPTH_BITS
+#import "shaders/constants.wgsl"::CORNER_DEPTH_MASK
+#import "shaders/constants.wgsl"::CORNER_DEPTH_STEPS
+#import "shaders/constants.wgsl"::LIGHT_CHANNEL_MASK
+#import "shaders/constants.wgsl"::LIGHT_SKY_SHIFT
+#import "shaders/constants.wgsl"::LIGHT_MAX
 
 // from https://community.khronos.org/t/mipmap-level-calculation-using-dfdx-dfdy/67480/2
 fn calculate_mip_level(uv: vec2f) -> f32 {
@@ -111,6 +119,27 @@ fn extract_texture_rot(quad: ChunkQuad) -> u32 {
     return (quad.bitfields.value & ROTATION_MASK) >> ROTATION_SHIFT;
 }
 
+// get the block light and skylight (normalized to 0..1) at the corner of the quad that pos_2d is on.
+// corners are indexed by which side of the quad they're on, the same way they're indexed on the CPU
+fn extract_corner_light(quad: ChunkQuad, pos_2d: vec2<f32>) -> vec2<f32> {
+    let corner = u32(pos_2d.x > quad.min.x) | (u32(pos_2d.y > quad.min.y) << 1u);
+    let level = (quad.light >> (corner * 8u)) & 0xFFu;
+
+    let block = level & LIGHT_CHANNEL_MASK;
+    let sky = (level >> LIGHT_SKY_SHIFT) & LIGHT_CHANNEL_MASK;
+
+    return vec2(f32(block), f32(sky)) / f32(LIGHT_MAX);
+}
+
+// get how far the corner of the quad that pos_2d is on is pushed back along the face normal, in blocks.
+// this is only nonzero for the sloped surfaces of fluids
+fn extract_corner_depth(quad: ChunkQuad, pos_2d: vec2<f32>) -> f32 {
+    let corner = u32(pos_2d.x > quad.min.x) | (u32(pos_2d.y > quad.min.y) << 1u);
+    let steps = (quad.bitfields.value >> (CORNER_DEPTH_SHIFT + corner * CORNER_DEPTH_BITS)) & CORNER_DEPTH_MASK;
+
+    return f32(steps) / f32(CORNER_DEPTH_STEPS);
+}
+
 fn extract_position(quad: ChunkQuad, quad_vertex_index: u32) -> vec3<f32> {
     var pos_2d: vec2<f32>;
     let face = extract_face(quad);
@@ -182,7 +211,8 @@ fn extract_position(quad: ChunkQuad, quad_vertex_index: u32) -> vec3<f32> {
         }
     }
 
-    return project_to_3d(pos_2d, axis_from_face(face), f32(quad.magnitude) * 0.25);
+    let position = project_to_3d(pos_2d, axis_from_face(face), f32(quad.magnitude) * 0.25);
+    return position - normal_from_face(face) * extract_corner_depth(quad, pos_2d);
 }
 
 fn ivec_project_to_3d(pos: vec2<i32>, axis: u32, mag: i32) -> vec3<i32> {
@@ -409,4 +439,27 @@ fn opposite_face(face: u32) -> u32 {
 
 fn index_from_3d_pos(pos: vec3<u32>, max: u32) -> u32 {
     return (pos.z * max * max) + (pos.y * max) + pos.x;
-}
\ No newline at end of file
+}
+
+// interleaved gradient noise, a threshold between 0 and 1 that varies per pixel for dithering
+// from http://www.iryoku.com/next-generation-post-processing-in-call-of-duty-advanced-warfare
+fn dither_threshold(frag_coord: vec2<f32>) -> f32 {
+    return fract(52.9829189 * fract(dot(frag_coord, vec2<f32>(0.06711056, 0.00583715))));
+}
+
+// how visible a chunk fragment `dist` away from the camera is at `time`, between 0 and 1
+fn chunk_fade_factor(fade: ChunkFade, time: f32, dist: f32) -> f32 {
+    var factor = 1.0;
+    if fade.fade_in > 0.0 {
+        // the time wraps around, so the chunk became ready before the last wrap if this is negative
+        var age = time - fade.ready_time;
+        if age < 0.0 {
+            age += fade.wrap_period;
+        }
+        factor = clamp(age / fade.fade_in, 0.0, 1.0);
+    }
+    if fade.far_end > fade.far_start {
+        factor = min(factor, 1.0 - smoothstep(fade.far_start, fade.far_end, dist));
+    }
+    return factor;
+}
@@ -16,6 +16,23 @@
 
 #import "shaders/chunk_bindings.wgsl"::quads
 
+#ifdef CHUNK_FRAGMENT_EXTENSION
+#import vxl::fragment_extension::extend_fragment
+#endif
+
+#import bevy_pbr::mesh_view_bindings::view
+
+#ifdef CHUNK_FADE
+#import "shaders/chunk_bindings.wgsl"::chunk_fade
+#import "shaders/utils.wgsl"::dither_threshold
+#import "shaders/utils.wgsl"::chunk_fade_factor
+#import bevy_pbr::mesh_view_bindings::globals
+#endif
+
+#ifdef VOXEL_FOG
+#import "shaders/registry_bindings.wgsl"::fog
+#endif
+
 const TEXTURE_SCALING: f32 = 16.0;
 
 @fragment
@@ -23,6 +40,13 @@ fn fragment(
     in: VertexOutput,
     @builtin(front_facing) is_front: bool,
 ) -> FragmentOutput {
+#ifdef CHUNK_FADE
+    let dist = distance(view.world_position, in.world_position.xyz);
+    if chunk_fade_factor(chunk_fade, globals.time, dist) < dither_threshold(in.position.xy) {
+        discard;
+    }
+#endif
+
     let quad = quads[in.quad_idx];
     let face = extract_face(quad);
 
@@ -34,6 +58,15 @@ fn fragment(
     out.color = apply_pbr_lighting(pbr_input);
     out.color = main_pass_post_lighting_processing(pbr_input, out.color);
 
+#ifdef VOXEL_FOG
+    let fog_factor = smoothstep(fog.start, fog.end, distance(view.world_position, in.world_position.xyz));
+    out.color = vec4(mix(out.color.rgb, fog.color.rgb, fog_factor * fog.color.a), out.color.a);
+#endif
+
+#ifdef CHUNK_FRAGMENT_EXTENSION
+    out.color = extend_fragment(in, out.color);
+#endif
+
     // out.color = pbr_input.material.base_color;
 
     return out;
@@ -7,21 +7,39 @@
 
 #import "shaders/chunk_bindings.wgsl"::quads
 
-#import "shaders/utils.wgsl"::normal_from_face
-#import "shaders/utils.wgsl"::extract_face
+#import "shaders/vxl_normals.wgsl"::quad_world_normal
+
+#ifdef CHUNK_FADE
+#import "shaders/chunk_bindings.wgsl"::chunk_fade
+#import "shaders/utils.wgsl"::dither_threshold
+#import "shaders/utils.wgsl"::chunk_fade_factor
+#import bevy_render::globals::Globals
+
+// the globals are at binding 1 of the prepass view bind group, `mesh_view_bindings::globals` is at binding 9 of the
+// main pass view bind group
+@group(0) @binding(1) var<uniform> globals: Globals;
+#endif
 
 @fragment
 fn fragment(
     in: PrepassOutput,
     @builtin(front_facing) is_front: bool,
 ) -> FragmentOutput {
+#ifdef CHUNK_FADE
+    // discard the same fragments as the main pass, so the depth buffer doesn't have chunks that aren't drawn
+    let dist = distance(view.world_position, in.world_position.xyz);
+    if chunk_fade_factor(chunk_fade, globals.time, dist) < dither_threshold(in.position.xy) {
+        discard;
+    }
+#endif
+
     let quad = quads[in.quad_idx];
 
     var out: FragmentOutput;
 
-    let face = extract_face(quad);
-
-    let world_normal = normal_from_face(face);
+    // transparent quads (like water) don't have anything behind them in the normal prepass, so we need to
+    // write their normal mapped normal here for lighting and screen space effects to be correct
+    let world_normal = quad_world_normal(quad, in.local_position);
 
 #ifdef NORMAL_PREPASS
     // not sure why this happens but we need to do this little funny operation on the normal otherwise rendering is all
@@ -5,6 +5,8 @@ struct VertexOutput {
     @location(2) uv: vec2<f32>,
     @location(3) @interpolate(flat) quad_idx: u32,
     @location(4) @interpolate(flat) instance_index: u32,
+    // block light and skylight, between 0 and 1
+    @location(5) light: vec2<f32>,
 }
 
 struct PrepassOutput {
@@ -6,6 +6,7 @@
 #import "shaders/utils.wgsl"::project_to_2d
 #import "shaders/utils.wgsl"::axis_from_face
 #import "shaders/utils.wgsl"::extract_face
+#import "shaders/utils.wgsl"::extract_corner_light
 
 #import bevy_pbr::{ 
     view_transformations::position_world_to_clip
@@ -24,7 +25,9 @@ fn vertex(
     var out: VertexOutput;
     out.quad_idx = vertex / 4u;
 
-    out.uv = project_to_2d(position, axis_from_face(face)) - quad.min;
+    let pos_2d = project_to_2d(position, axis_from_face(face));
+    out.uv = pos_2d - quad.min;
+    out.light = extract_corner_light(quad, pos_2d);
 
     out.local_position = position;
     out.world_position = vec4f(position + (chunk_position * 16.0), 1.0);
@@ -0,0 +1,104 @@
+#import "shaders/registry_bindings.wgsl"::faces
+#import "shaders/registry_bindings.wgsl"::normal_texture
+#import "shaders/registry_bindings.wgsl"::normal_sampler
+
+#import "shaders/utils.wgsl"::project_to_2d
+#import "shaders/utils.wgsl"::axis_from_face
+#import "shaders/utils.wgsl"::normal_from_face
+#import "shaders/utils.wgsl"::tangent_from_face
+#import "shaders/utils.wgsl"::tex_rotation_matrix_around_axis
+#import "shaders/utils.wgsl"::extract_face
+#import "shaders/utils.wgsl"::extract_texture_rot
+#import "shaders/utils.wgsl"::create_rotation_matrix
+#import "shaders/utils.wgsl"::flipped_uv_x
+#import "shaders/utils.wgsl"::flipped_uv_y
+#import "shaders/utils.wgsl"::uv_coords_from_fs_pos_and_params
+
+#import "shaders/vxl_types.wgsl"::ChunkQuad
+
+#import "shaders/constants.wgsl"::HAS_NORMAL_MAP_BIT
+
+#import bevy_pbr::pbr_types
+
+// The world space normal of a quad at the given local position, with the quad's normal map applied if it has one.
+// The tangent basis is derived from the quad's face and texture rotation, this must match `TangentBasis` on the CPU.
+fn quad_world_normal(quad: ChunkQuad, local_position: vec3<f32>) -> vec3<f32> {
+    let face = extract_face(quad);
+    let axis = axis_from_face(face);
+    let world_normal = normal_from_face(face);
+
+    let face_texture = faces[quad.texture_id];
+    if (face_texture.flags & HAS_NORMAL_MAP_BIT) == 0u {
+        return world_normal;
+    }
+
+    let texture_rot = extract_texture_rot(quad);
+    let fs_pos = fract(project_to_2d(local_position, axis));
+
+    let uv = uv_coords_from_fs_pos_and_params(
+        fs_pos,
+        create_rotation_matrix(texture_rot),
+        face,
+        flipped_uv_x(quad),
+        flipped_uv_y(quad),
+    );
+
+    let tangent = tex_rotation_matrix_around_axis(texture_rot, axis) * tangent_from_face(face);
+
+    return apply_normal_mapping(
+        0u,
+        world_normal,
+        vec4f(tangent, 0.0),
+        uv,
+        face_texture.normal_tex_idx,
+        0.0,
+    );
+}
+
+fn apply_normal_mapping(
+    standard_material_flags: u32,
+    world_normal: vec3<f32>,
+    world_tangent: vec4<f32>,
+    uv: vec2<f32>,
+    texture_array_idx: u32,
+    mip_level: f32,
+) -> vec3<f32> {
+    // NOTE: The mikktspace method of normal mapping explicitly requires that the world normal NOT
+    // be re-normalized in the fragment shader. This is primarily to match the way mikktspace
+    // bakes vertex tangents and normal maps so that this is the exact inverse. Blender, Unity,
+    // Unreal Engine, Godot, and more all use the mikktspace method. Do not change this code
+    // unless you really know what you are doing.
+    // http://www.mikktspace.com/
+    var N: vec3<f32> = world_normal;
+
+    // NOTE: The mikktspace method of normal mapping explicitly requires that these NOT be
+    // normalized nor any Gram-Schmidt applied to ensure the vertex normal is orthogonal to the
+    // vertex tangent! Do not change this code unless you really know what you are doing.
+    // http://www.mikktspace.com/
+    var T: vec3<f32> = world_tangent.xyz;
+    var B: vec3<f32> = 1.0 * cross(N, T);
+
+    // Nt is the tangent-space normal.
+    var Nt = textureSampleLevel(
+        normal_texture,
+        normal_sampler,
+        uv,
+        texture_array_idx,
+        mip_level
+    ).rgb;
+    Nt = Nt * 2.0 - 1.0;
+    // TODO: do we need this?
+    // Normal maps authored for DirectX require flipping the y component
+    if (standard_material_flags & pbr_types::STANDARD_MATERIAL_FLAGS_FLIP_NORMAL_MAP_Y) != 0u {
+        Nt.y = -Nt.y;
+    }
+
+    // NOTE: The mikktspace method of normal mapping applies maps the tangent-space normal from
+    // the normal map texture in this way to be an EXACT inverse of how the normal map baker
+    // calculates the normal maps so there is no error introduced. Do not change this code
+    // unless you really know what you are doing.
+    // http://www.mikktspace.com/
+    N = Nt.x * T + Nt.y * B + Nt.z * N;
+
+    return normalize(N);
+}
\ No newline at end of file
@@ -23,11 +23,16 @@
 #import "shaders/utils.wgsl"::uv_coords_from_fs_pos_and_params
 #import "shaders/utils.wgsl"::calculate_mip_level
 
+#import "shaders/vxl_normals.wgsl"::apply_normal_mapping
+
 #import "shaders/vxl_chunk_io.wgsl"::VertexOutput
 #import "shaders/vxl_types.wgsl"::FaceTexture
 #import "shaders/vxl_types.wgsl"::ChunkQuad
 
 #import "shaders/constants.wgsl"::HAS_NORMAL_MAP_BIT
+#import "shaders/constants.wgsl"::MATERIAL_CHANNEL_MASK
+#import "shaders/constants.wgsl"::ROUGHNESS_SHIFT
+#import "shaders/constants.wgsl"::METALLIC_SHIFT
 #import "shaders/constants.wgsl"::CHUNK_OCCLUSION_BUFFER_DIMENSIONS
 #import "shaders/constants.wgsl"::FLIP_UV_X_BIT
 #import "shaders/constants.wgsl"::FLIP_UV_Y_BIT
@@ -66,6 +71,11 @@ fn standard_material_new() -> pbr_types::StandardMaterial {
     return material;
 }
 
+// unpack a channel of the material of a face texture, see GpuFaceTexture::with_material
+fn material_channel(material: u32, shift: u32) -> f32 {
+    return f32((material >> shift) & MATERIAL_CHANNEL_MASK) / f32(MATERIAL_CHANNEL_MASK);
+}
+
 fn calculate_view(
     world_position: vec4<f32>,
     is_orthographic: bool,
@@ -134,7 +144,11 @@ fn create_pbr_input(
         mip_level
     );
 
-    pbr_input.diffuse_occlusion = vec3(1.0);
+    pbr_input.material.perceptual_roughness = material_channel(face_texture.material, ROUGHNESS_SHIFT);
+    pbr_input.material.metallic = material_channel(face_texture.material, METALLIC_SHIFT);
+
+    // voxel light only affects ambient light, like ambient occlusion
+    pbr_input.diffuse_occlusion = vec3(max(in.light.x, in.light.y));
 
     if (face_texture.flags & HAS_NORMAL_MAP_BIT) != 0u {
         pbr_input.N = apply_normal_mapping(
@@ -153,51 +167,3 @@ fn create_pbr_input(
 
     return pbr_input;
 }
-
-fn apply_normal_mapping(
-    standard_material_flags: u32,
-    world_normal: vec3<f32>,
-    world_tangent: vec4<f32>,
-    uv: vec2<f32>,
-    texture_array_idx: u32,
-    mip_level: f32,
-) -> vec3<f32> {
-    // NOTE: The mikktspace method of normal mapping explicitly requires that the world normal NOT
-    // be re-normalized in the fragment shader. This is primarily to match the way mikktspace
-    // bakes vertex tangents and normal maps so that this is the exact inverse. Blender, Unity,
-    // Unreal Engine, Godot, and more all use the mikktspace method. Do not change this code
-    // unless you really know what you are doing.
-    // http://www.mikktspace.com/
-    var N: vec3<f32> = world_normal;
-
-    // NOTE: The mikktspace method of normal mapping explicitly requires that these NOT be
-    // normalized nor any Gram-Schmidt applied to ensure the vertex normal is orthogonal to the
-    // vertex tangent! Do not change this code unless you really know what you are doing.
-    // http://www.mikktspace.com/
-    var T: vec3<f32> = world_tangent.xyz;
-    var B: vec3<f32> = 1.0 * cross(N, T);
-
-    // Nt is the tangent-space normal.
-    var Nt = textureSampleLevel(
-        normal_texture,
-        normal_sampler,
-        uv,
-        texture_array_idx,
-        mip_level
-    ).rgb;
-    Nt = Nt * 2.0 - 1.0;
-    // TODO: do we need this?
-    // Normal maps authored for DirectX require flipping the y component
-    if (standard_material_flags & pbr_types::STANDARD_MATERIAL_FLAGS_FLIP_NORMAL_MAP_Y) != 0u {
-        Nt.y = -Nt.y;
-    }
-
-    // NOTE: The mikktspace method of normal mapping applies maps the tangent-space normal from
-    // the normal map texture in this way to be an EXACT inverse of how the normal map baker
-    // calculates the normal maps so there is no error introduced. Do not change this code
-    // unless you really know what you are doing.
-    // http://www.mikktspace.com/
-    N = Nt.x * T + Nt.y * B + Nt.z * N;
-
-    return normalize(N);
-}
\ No newline at end of file
@@ -0,0 +1,10 @@
+// An example fragment extension that tints chunks, see `ChunkShaders::fragment_extension`
+#define_import_path vxl::fragment_extension
+
+#import "shaders/vxl_chunk_io.wgsl"::VertexOutput
+
+const TINT: vec4<f32> = vec4<f32>(1.0, 0.9, 0.8, 1.0);
+
+fn extend_fragment(in: VertexOutput, color: vec4<f32>) -> vec4<f32> {
+    return color * TINT;
+}
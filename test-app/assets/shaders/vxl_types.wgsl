@@ -2,6 +2,7 @@ struct FaceTexture {
     flags: u32,
     color_tex_idx: u32,
     normal_tex_idx: u32,
+    material: u32,
 }
 
 struct ChunkQuad {
@@ -10,8 +11,23 @@ struct ChunkQuad {
     min: vec2<f32>,
     max: vec2<f32>,
     magnitude: i32,
+    light: u32,
 }
 
 struct ChunkQuadBitfields {
     value: u32
-}
\ No newline at end of file
+}
+
+struct ChunkFade {
+    ready_time: f32,
+    wrap_period: f32,
+    fade_in: f32,
+    far_start: f32,
+    far_end: f32,
+}
+
+struct VoxelFog {
+    color: vec4<f32>,
+    start: f32,
+    end: f32,
+}
@@ -11,10 +11,7 @@ use ve::{
     },
     util::ws_to_chunk_pos,
 };
-use voxel_engine::{
-    data::tile::Face,
-    topo::world::{Chunk, ChunkEntity},
-};
+use voxel_engine::{data::tile::Face, topo::world::Chunk};
 
 use crate::camera::PlayerCamController;
 
@@ -74,7 +71,7 @@ pub fn update_spatial_debug_text(
             format!("load reasons: {load_reasons:?}\n"),
             format!("chunk flags: {chunk_flags:?}\n"),
             format!("permit flags: {permit_flags:?}\n"),
-            format!("\n"),
+            "\n".to_string(),
             format!("mesh: {mesh:?}"),
         ]
         .map(text_section)
@@ -18,9 +18,12 @@ use bevy::render::settings::{WgpuFeatures, WgpuSettings};
 use bevy::render::RenderPlugin;
 use bevy::window::PresentMode;
 use debug_info::{DirectionText, FpsText, SpatialDebugText};
+use ve::render::core::VoxelFog;
 use ve::topo::ChunkObserver;
 use ve::EngineState;
 
+const SKY_COLOR: Color = Color::rgb(0.4, 0.75, 0.9);
+
 fn main() {
     println!(
         "RUNNING IN WORKING DIRECTORY: {}",
@@ -28,7 +31,12 @@ fn main() {
     );
 
     App::new()
-        .insert_resource(ClearColor(Color::rgb(0.4, 0.75, 0.9)))
+        .insert_resource(ClearColor(SKY_COLOR))
+        .insert_resource(VoxelFog {
+            enabled: true,
+            color: SKY_COLOR,
+            ..default()
+        })
         .add_plugins((
             DefaultPlugins
                 .set(RenderPlugin {
@@ -0,0 +1,47 @@
+use std::hash::BuildHasher;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use voxel_engine::{topo::world::ChunkPos, util::ChunkPosBuildHasher};
+
+const RADIUS: i32 = 16;
+
+fn positions() -> Vec<ChunkPos> {
+    let mut positions = Vec::new();
+
+    for x in -RADIUS..RADIUS {
+        for y in -RADIUS / 4..RADIUS / 4 {
+            for z in -RADIUS..RADIUS {
+                positions.push(ChunkPos::new(x, y, z));
+            }
+        }
+    }
+
+    positions
+}
+
+fn lookups<S: BuildHasher + Default>(c: &mut Criterion, name: &str) {
+    let positions = positions();
+    let map = positions
+        .iter()
+        .map(|&pos| (pos, pos.as_ivec3().x))
+        .collect::<hashbrown::HashMap<ChunkPos, i32, S>>();
+
+    c.bench_function(name, |bencher| {
+        bencher.iter(|| {
+            let mut total = 0;
+            for pos in positions.iter() {
+                total += map.get(black_box(pos)).unwrap();
+            }
+            total
+        });
+    });
+}
+
+fn chunk_pos_hash_lookups(c: &mut Criterion) {
+    lookups::<ChunkPosBuildHasher>(c, "chunk-map-lookup-morton");
+    lookups::<wyhash2::WyHash>(c, "chunk-map-lookup-wyhash");
+    lookups::<ahash::RandomState>(c, "chunk-map-lookup-ahash");
+}
+
+criterion_group!(benches, chunk_pos_hash_lookups);
+criterion_main!(benches);
@@ -1,24 +1,25 @@
 use std::{fs::File, io::Read, path::Path};
 
 use indexmap::IndexMap;
+use itertools::Itertools;
 
 use crate::data::{
     error::BlockVariantFileLoaderError,
-    resourcepath::ResourcePath,
+    resourcepath::{rpath, ResourcePath},
     tile::Transparency,
-    voxel::{descriptor::BlockVariantDescriptor, BlockModel},
+    voxel::{descriptor::BlockVariantDescriptor, BlockModel, VoxelModel},
 };
 
 #[cfg(test)]
 use crate::{
-    data::{resourcepath::rpath, texture::FaceTexture, voxel::rotations::BlockModelFaceMap},
+    data::{texture::FaceTexture, voxel::rotations::BlockModelFaceMap},
     util::FaceMap,
 };
 
 use super::{error::BlockVariantRegistryLoadError, texture::TextureRegistry, Registry};
 
 pub const MAX_RECURSION_DEPTH: usize = 8;
-pub static BLOCK_VARIANT_FILE_EXTENSION: &'static str = "block";
+pub static BLOCK_VARIANT_FILE_EXTENSION: &str = "block";
 
 #[derive(Debug, Clone)]
 pub struct BlockVariantRegistryEntry<'a> {
@@ -26,11 +27,28 @@ pub struct BlockVariantRegistryEntry<'a> {
     pub model: Option<&'a BlockModel>,
 }
 
+impl BlockVariantRegistryEntry<'_> {
+    /// The model this variant is meshed with. Fluids are meshed as fluids even if they have a block model,
+    /// the block model only gives them their textures.
+    pub fn voxel_model(&self) -> Option<VoxelModel> {
+        match self.options.fluid_level {
+            Some(level) => Some(VoxelModel::Fluid { level }),
+            None => self.model.map(|model| VoxelModel::Block(*model)),
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct BlockVariantFileLoader {
     raw_descriptors: hb::HashMap<ResourcePath, Vec<u8>>,
 }
 
+impl Default for BlockVariantFileLoader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl BlockVariantFileLoader {
     pub fn new() -> Self {
         Self {
@@ -84,7 +102,7 @@ impl BlockVariantFileLoader {
     ) -> Result<(), BlockVariantFileLoaderError> {
         let path = path.as_ref();
 
-        let mut file = File::open(&path)?;
+        let mut file = File::open(path)?;
 
         let mut buffer = Vec::<u8>::with_capacity(file.metadata()?.len() as _);
         file.read_to_end(&mut buffer)?;
@@ -102,6 +120,13 @@ impl BlockVariantFileLoader {
 pub struct BlockVariantRegistryLoader {
     file_loader: BlockVariantFileLoader,
     manual_descriptors: hb::HashMap<ResourcePath, BlockVariantDescriptor>,
+    air: ResourcePath,
+}
+
+impl Default for BlockVariantRegistryLoader {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl BlockVariantRegistryLoader {
@@ -109,9 +134,16 @@ impl BlockVariantRegistryLoader {
         Self {
             file_loader: BlockVariantFileLoader::new(),
             manual_descriptors: hb::HashMap::new(),
+            air: rpath(BlockVariantRegistry::RPATH_VOID),
         }
     }
 
+    /// Designate the variant with the given label as air, see [`BlockVariantRegistry::air`].
+    /// Defaults to [`BlockVariantRegistry::RPATH_VOID`].
+    pub fn set_air(&mut self, label: ResourcePath) {
+        self.air = label;
+    }
+
     pub fn register_from_directory(
         &mut self,
         path: impl AsRef<Path>,
@@ -123,7 +155,7 @@ impl BlockVariantRegistryLoader {
     }
 
     pub fn register(&mut self, label: ResourcePath, descriptor: BlockVariantDescriptor) {
-        self.manual_descriptors.insert(label.into(), descriptor);
+        self.manual_descriptors.insert(label, descriptor);
     }
 
     pub fn build_registry(
@@ -136,7 +168,14 @@ impl BlockVariantRegistryLoader {
                 ahash::RandomState::new(),
             );
 
-        for (rpath, descriptor) in self.manual_descriptors.into_iter() {
+        // the descriptors are stored in hash maps with a random iteration order, so we sort them by label
+        // to give every variant the same ID every time the registry is built
+        let manual_descriptors = self
+            .manual_descriptors
+            .into_iter()
+            .sorted_unstable_by(|(a, _), (b, _)| a.cmp(b));
+
+        for (rpath, descriptor) in manual_descriptors {
             let model = if let Some(model_desc) = descriptor.model {
                 Some(model_desc.create_block_model(texture_registry)?)
             } else {
@@ -151,7 +190,12 @@ impl BlockVariantRegistryLoader {
             map.insert(rpath, variant);
         }
 
-        for (rpath, buffer) in self.file_loader.entries() {
+        let file_descriptors = self
+            .file_loader
+            .entries()
+            .sorted_unstable_by(|(a, _), (b, _)| a.cmp(b));
+
+        for (rpath, buffer) in file_descriptors {
             let descriptor =
                 toml::from_str::<BlockVariantDescriptor>(String::from_utf8_lossy(buffer).as_ref())?;
 
@@ -169,7 +213,12 @@ impl BlockVariantRegistryLoader {
             map.insert(rpath.clone(), variant);
         }
 
-        Ok(BlockVariantRegistry { map })
+        let air = map
+            .get_index_of(&self.air)
+            .map(|i| BlockVariantId(i as _))
+            .ok_or(BlockVariantRegistryLoadError::AirNotFound(self.air))?;
+
+        Ok(BlockVariantRegistry { map, air })
     }
 }
 
@@ -184,6 +233,12 @@ pub struct BlockOptions {
     pub transparency: Transparency,
     #[serde(default)]
     pub subdividable: bool,
+    /// The block light level emitted by this block, between 0 and 15.
+    #[serde(default)]
+    pub emission: u8,
+    /// If set, this block is a fluid filled up to this level out of [`VoxelModel::MAX_FLUID_LEVEL`].
+    #[serde(default)]
+    pub fluid_level: Option<u8>,
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, dm::Display)]
@@ -196,7 +251,7 @@ impl BlockVariantId {
     }
 
     pub fn as_u32(self) -> u32 {
-        self.0 as u32
+        self.0
     }
 
     #[cfg(test)]
@@ -207,10 +262,28 @@ impl BlockVariantId {
 
 pub struct BlockVariantRegistry {
     map: IndexMap<ResourcePath, BlockVariant, ahash::RandomState>,
+    air: BlockVariantId,
 }
 
 impl BlockVariantRegistry {
     pub const RPATH_VOID: &'static str = "void";
+
+    /// The variant that represents empty space. Air never has any geometry and never hides the faces
+    /// of the blocks next to it, regardless of its model and transparency. It's also what the world
+    /// is filled with where there are no chunks.
+    pub fn air(&self) -> BlockVariantId {
+        self.air
+    }
+
+    pub fn is_air(&self, id: BlockVariantId) -> bool {
+        id == self.air
+    }
+
+    /// The label of the variant with the given ID. Inverse of [`Registry::get_id`].
+    pub fn label(&self, id: BlockVariantId) -> &ResourcePath {
+        let (label, _) = self.map.get_index(id.index()).unwrap();
+        label
+    }
 }
 
 #[cfg(test)]
@@ -221,6 +294,14 @@ impl BlockVariantRegistry {
     pub const FULL: BlockVariantId = BlockVariantId::new(1);
     pub const RPATH_SUBDIV: &'static str = "subdiv";
     pub const SUBDIV: BlockVariantId = BlockVariantId::new(2);
+    pub const RPATH_LAMP: &'static str = "lamp";
+    pub const LAMP: BlockVariantId = BlockVariantId::new(3);
+    pub const RPATH_GLASS: &'static str = "glass";
+    pub const GLASS: BlockVariantId = BlockVariantId::new(4);
+    pub const RPATH_WATER: &'static str = "water";
+    pub const WATER: BlockVariantId = BlockVariantId::new(5);
+    pub const RPATH_SHALLOW_WATER: &'static str = "shallow_water";
+    pub const SHALLOW_WATER: BlockVariantId = BlockVariantId::new(6);
 
     pub fn new_mock(_registry: &TextureRegistry) -> Self {
         let mut map = IndexMap::with_hasher(ahash::RandomState::default());
@@ -231,6 +312,8 @@ impl BlockVariantRegistry {
                 options: BlockOptions {
                     transparency: Transparency::Transparent,
                     subdividable: true,
+                    emission: 0,
+                    fluid_level: None,
                 },
                 model: None,
             },
@@ -242,6 +325,8 @@ impl BlockVariantRegistry {
                 options: BlockOptions {
                     transparency: Transparency::Opaque,
                     subdividable: false,
+                    emission: 0,
+                    fluid_level: None,
                 },
                 model: Some(BlockModel {
                     directions: FaceMap::new(),
@@ -256,6 +341,40 @@ impl BlockVariantRegistry {
                 options: BlockOptions {
                     transparency: Transparency::Opaque,
                     subdividable: true,
+                    emission: 0,
+                    fluid_level: None,
+                },
+                model: Some(BlockModel {
+                    directions: FaceMap::new(),
+                    model: BlockModelFaceMap::filled(FaceTexture::new(TextureRegistry::TEX2)),
+                }),
+            },
+        );
+
+        map.insert(
+            rpath(Self::RPATH_LAMP),
+            BlockVariant {
+                options: BlockOptions {
+                    transparency: Transparency::Opaque,
+                    subdividable: false,
+                    emission: 15,
+                    fluid_level: None,
+                },
+                model: Some(BlockModel {
+                    directions: FaceMap::new(),
+                    model: BlockModelFaceMap::filled(FaceTexture::new(TextureRegistry::TEX1)),
+                }),
+            },
+        );
+
+        map.insert(
+            rpath(Self::RPATH_GLASS),
+            BlockVariant {
+                options: BlockOptions {
+                    transparency: Transparency::SelfCulling,
+                    subdividable: true,
+                    emission: 0,
+                    fluid_level: None,
                 },
                 model: Some(BlockModel {
                     directions: FaceMap::new(),
@@ -264,7 +383,60 @@ impl BlockVariantRegistry {
             },
         );
 
-        Self { map }
+        // water is also part of the terrain generator's palette
+        for (label, level) in [
+            (Self::RPATH_WATER, VoxelModel::MAX_FLUID_LEVEL),
+            (Self::RPATH_SHALLOW_WATER, VoxelModel::MAX_FLUID_LEVEL / 2),
+        ] {
+            map.insert(
+                rpath(label),
+                BlockVariant {
+                    options: BlockOptions {
+                        transparency: Transparency::SelfCulling,
+                        subdividable: false,
+                        emission: 0,
+                        fluid_level: Some(level),
+                    },
+                    model: Some(BlockModel {
+                        directions: FaceMap::new(),
+                        model: BlockModelFaceMap::filled(FaceTexture::new(TextureRegistry::TEX1)),
+                    }),
+                },
+            );
+        }
+
+        // the rest of the terrain generator's palette
+        for (label, transparency) in [
+            ("debug", Transparency::Opaque),
+            ("stone", Transparency::Opaque),
+        ] {
+            map.insert(
+                rpath(label),
+                BlockVariant {
+                    options: BlockOptions {
+                        transparency,
+                        subdividable: true,
+                        emission: 0,
+                        fluid_level: None,
+                    },
+                    model: Some(BlockModel {
+                        directions: FaceMap::new(),
+                        model: BlockModelFaceMap::filled(FaceTexture::new(TextureRegistry::TEX2)),
+                    }),
+                },
+            );
+        }
+
+        Self {
+            map,
+            air: Self::VOID,
+        }
+    }
+
+    /// Designate another variant as air, see [`BlockVariantRegistry::air`].
+    pub fn with_air(mut self, air: BlockVariantId) -> Self {
+        self.air = air;
+        self
     }
 }
 
@@ -298,5 +470,48 @@ impl Registry for BlockVariantRegistry {
 
 #[cfg(test)]
 mod tests {
-    // TODO: tests!
+    use super::*;
+
+    fn descriptor() -> BlockVariantDescriptor {
+        BlockVariantDescriptor {
+            options: BlockOptions {
+                transparency: Transparency::Opaque,
+                subdividable: false,
+                emission: 0,
+                fluid_level: None,
+            },
+            model: None,
+        }
+    }
+
+    #[test]
+    fn variant_ids_are_stable() {
+        let texreg = TextureRegistry::new_mock();
+        let labels = [
+            "void", "stone", "dirt", "grass", "sand", "water", "glass", "lamp",
+        ];
+
+        let build = |labels: &[&'static str]| {
+            let mut loader = BlockVariantRegistryLoader::new();
+            for &label in labels {
+                loader.register(rpath(label), descriptor());
+            }
+
+            let registry = loader.build_registry(&texreg).unwrap();
+            labels
+                .iter()
+                .map(|&label| (label, registry.get_id(&rpath(label)).unwrap()))
+                .sorted_unstable_by_key(|&(label, _)| label)
+                .collect_vec()
+        };
+
+        let expected = build(&labels);
+        let mut reversed = labels;
+        reversed.reverse();
+
+        for _ in 0..8 {
+            assert_eq!(expected, build(&labels));
+            assert_eq!(expected, build(&reversed));
+        }
+    }
 }
@@ -26,6 +26,11 @@ pub enum TextureRegistryError {
     VoxelNormalMapFolderNotFound,
     #[error("Voxel normal map folder asset is not loaded")]
     VoxelNormalMapFolderNotLoaded,
+    #[error(
+        "No textures were found, add some to the '{}' folder in the assets",
+        crate::data::systems::TEXTURE_FOLDER_NAME
+    )]
+    NoTextures,
     #[error("Atlas builder error: {0}")]
     BuilderError(#[from] TextureArrayBuilderError),
     #[error("Unexpected asset ID type: {0}")]
@@ -52,4 +57,6 @@ pub enum BlockVariantRegistryLoadError {
     FileLoadError(#[from] BlockVariantFileLoaderError),
     #[error("Error parsing block variant TOML file: {0}")]
     TomlParseError(#[from] toml::de::Error),
+    #[error("Block variant with label '{0}' was designated as air but not found")]
+    AirNotFound(ResourcePath),
 }
@@ -42,6 +42,12 @@ pub struct Registries {
 
 pub type RegistryRef<'a, R> = MappedRwLockReadGuard<'a, R>;
 
+impl Default for Registries {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Registries {
     pub fn new() -> Self {
         Self {
@@ -59,7 +65,7 @@ impl Registries {
         // The call to anymap::Map::get here returns an option but due to the closure signature in RwLockReadGuard we have to return a reference
         // to a type. Therefore we unwrap on the get call and test if the type exists in the map before we get there.
         if !guard.contains::<R>() {
-            return None;
+            None
         } else {
             Some(RwLockReadGuard::map(guard, |g| g.get::<R>().unwrap()))
         }
@@ -3,13 +3,17 @@ use std::marker::PhantomData;
 use bevy::{
     asset::{AssetId, Assets, Handle},
     ecs::system::Resource,
+    log::warn,
     render::texture::Image,
 };
 use indexmap::IndexMap;
 use mip_texture_array::asset::MippedArrayTexture;
 use mip_texture_array::MipArrayTextureBuilder;
 
-use crate::data::{resourcepath::ResourcePath, texture::GpuFaceTexture};
+use crate::data::{
+    resourcepath::ResourcePath,
+    texture::{GpuFaceTexture, TextureMaterial},
+};
 
 #[cfg(test)]
 use crate::data::resourcepath::rpath;
@@ -28,6 +32,13 @@ pub struct TextureRegistryLoader {
 pub(crate) struct TexIdBundle {
     pub color: TexId,
     pub normal: Option<TexId>,
+    pub material: TextureMaterial,
+}
+
+impl Default for TextureRegistryLoader {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl TextureRegistryLoader {
@@ -39,19 +50,38 @@ impl TextureRegistryLoader {
 
     pub fn register(&mut self, label: ResourcePath, texture: TexId, normal: Option<TexId>) {
         self.textures.insert(
-            label.into(),
+            label,
             TexIdBundle {
                 color: texture,
                 normal,
+                material: TextureMaterial::default(),
             },
         );
     }
 
+    /// Set the PBR material of a registered texture, textures use [`TextureMaterial::default`] otherwise.
+    /// Returns false if there's no texture with the label.
+    pub fn set_material(&mut self, label: &ResourcePath, material: TextureMaterial) -> bool {
+        match self.textures.get_mut(label) {
+            Some(bundle) => {
+                bundle.material = material;
+                true
+            }
+            None => false,
+        }
+    }
+
     pub fn build_registry(
         self,
         textures: &Assets<Image>,
         array_textures: &mut Assets<MippedArrayTexture>,
     ) -> Result<TextureRegistry, TextureRegistryError> {
+        // the array texture builder can't build an empty array texture, and an empty registry would only
+        // cause confusing errors later when looking up face textures
+        if self.textures.is_empty() {
+            return Err(TextureRegistryError::NoTextures);
+        }
+
         // we map the asset id to the array texture index
         let mut color_id_to_idx = hb::HashMap::<AssetId<Image>, u32>::new();
 
@@ -75,11 +105,17 @@ impl TextureRegistryLoader {
             let mut builder = MipArrayTextureBuilder::new(TEXTURE_DIMENSIONS, false);
             builder.set_label(Some("normal_array_texture"));
 
-            for id in self.textures.values().cloned() {
-                let Some(id) = id.normal else {
+            for (label, ids) in self.textures.iter() {
+                let Some(id) = ids.normal else {
                     continue;
                 };
 
+                // Textures without a loaded normal map fall back to a flat normal
+                if !textures.contains(id) {
+                    warn!("Normal map for texture '{label}' is not loaded, it will not be normal mapped");
+                    continue;
+                }
+
                 let texarr_idx = builder.add_image(id, textures)? as u32;
                 normal_id_to_idx.insert(id, texarr_idx);
             }
@@ -98,7 +134,8 @@ impl TextureRegistryLoader {
             for (label, ids) in self.textures.into_iter() {
                 let indices = AtlasIdxBundle {
                     color: *color_id_to_idx.get(&ids.color).unwrap(),
-                    normal: ids.normal.map(|id| *normal_id_to_idx.get(&id).unwrap()),
+                    normal: ids.normal.and_then(|id| normal_id_to_idx.get(&id).copied()),
+                    material: ids.material,
                 };
 
                 map.insert(label, indices);
@@ -109,6 +146,7 @@ impl TextureRegistryLoader {
 
         Ok(TextureRegistry {
             map: registry_map,
+            normal_layers: normal_id_to_idx.len() as u32,
             color_atlas: color_arr_tex,
             normal_atlas: normal_arr_tex,
         })
@@ -120,6 +158,7 @@ pub struct TexregFaces(pub Vec<GpuFaceTexture>);
 
 pub struct TextureRegistry {
     map: IndexMap<ResourcePath, AtlasIdxBundle, ahash::RandomState>,
+    normal_layers: u32,
 
     color_atlas: Handle<MippedArrayTexture>,
     normal_atlas: Handle<MippedArrayTexture>,
@@ -128,6 +167,7 @@ pub struct TextureRegistry {
 pub(crate) struct AtlasIdxBundle {
     pub color: u32,
     pub normal: Option<u32>,
+    pub material: TextureMaterial,
 }
 
 #[cfg(test)]
@@ -147,6 +187,7 @@ impl TextureRegistry {
             AtlasIdxBundle {
                 color: 0,
                 normal: None,
+                material: TextureMaterial::default(),
             },
         );
 
@@ -155,6 +196,7 @@ impl TextureRegistry {
             AtlasIdxBundle {
                 color: 1,
                 normal: Some(0),
+                material: TextureMaterial::default(),
             },
         );
 
@@ -163,11 +205,13 @@ impl TextureRegistry {
             AtlasIdxBundle {
                 color: 2,
                 normal: Some(1),
+                material: TextureMaterial::default(),
             },
         );
 
         Self {
             map,
+            normal_layers: 2,
             color_atlas: Handle::default(),
             normal_atlas: Handle::default(),
         }
@@ -192,7 +236,10 @@ impl TextureRegistry {
         self.map
             .values()
             .map(|indices| {
-                GpuFaceTexture::new(indices.color as u32, indices.normal.map(|v| v as u32))
+                let mut face = GpuFaceTexture::new(indices.color, indices.normal)
+                    .with_material(indices.material);
+                face.validate_normal_map(self.normal_layers);
+                face
             })
             .collect::<Vec<_>>()
     }
@@ -202,6 +249,7 @@ impl TextureRegistry {
 pub struct TextureRegistryEntry<'a> {
     pub texture_idx: u32,
     pub normal_idx: Option<u32>,
+    pub material: TextureMaterial,
 
     // Placeholder in case we wanna store some other funny stuff in here
     _data: PhantomData<&'a ()>,
@@ -209,7 +257,7 @@ pub struct TextureRegistryEntry<'a> {
 
 impl<'a> TextureRegistryEntry<'a> {
     pub fn gpu_representation(&self) -> GpuFaceTexture {
-        GpuFaceTexture::new(self.texture_idx, self.normal_idx)
+        GpuFaceTexture::new(self.texture_idx, self.normal_idx).with_material(self.material)
     }
 }
 
@@ -245,8 +293,9 @@ impl Registry for TextureRegistry {
         let indices = self.map.get_index(map_idx).unwrap().1;
 
         TextureRegistryEntry {
-            texture_idx: indices.color as u32,
-            normal_idx: indices.normal.map(|v| v as u32),
+            texture_idx: indices.color,
+            normal_idx: indices.normal,
+            material: indices.material,
             _data: PhantomData,
         }
     }
@@ -260,6 +309,51 @@ impl Registry for TextureRegistry {
 
 #[cfg(test)]
 mod tests {
+    use crate::data::{resourcepath::rpath, texture::GpuFaceTexture};
+
+    use super::*;
+
+    #[test]
+    fn missing_normal_map_fallback() {
+        let mut registry = TextureRegistry::new_mock();
+
+        let buffer = registry.face_texture_buffer();
+        assert!(!buffer[TextureRegistry::TEX1.index()].has_normal_map());
+        assert!(buffer[TextureRegistry::TEX2.index()].has_normal_map());
+        assert!(buffer[TextureRegistry::TEX3.index()].has_normal_map());
+
+        // a texture claiming a normal map that doesn't exist in the normal array texture
+        registry.map.insert(
+            rpath("broken"),
+            AtlasIdxBundle {
+                color: 3,
+                normal: Some(2),
+                material: TextureMaterial::default(),
+            },
+        );
+
+        let buffer = registry.face_texture_buffer();
+        let broken = buffer[3];
+        assert!(!broken.has_normal_map());
+        assert_eq!(0, broken.flags & GpuFaceTexture::HAS_NORMAL_MAP_BIT);
+        assert_eq!(3, broken.color_tex_idx);
+
+        let mut face = GpuFaceTexture::new(0, Some(1));
+        assert!(face.validate_normal_map(2));
+        assert!(face.has_normal_map());
+        assert!(!face.validate_normal_map(1));
+        assert!(!face.has_normal_map());
+    }
+
+    #[test]
+    fn no_textures_is_an_error() {
+        let images = Assets::<Image>::default();
+        let mut array_textures = Assets::<MippedArrayTexture>::default();
+
+        let result = TextureRegistryLoader::new().build_registry(&images, &mut array_textures);
+        assert!(matches!(result, Err(TextureRegistryError::NoTextures)));
+        assert_eq!(0, array_textures.len());
+    }
 
     #[test]
     #[ignore]
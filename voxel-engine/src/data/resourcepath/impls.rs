@@ -17,9 +17,7 @@ impl<'a> TryFrom<&'a Path> for ResourcePath {
             }
         }
 
-        Ok(Self::parse(
-            &buffer.replace(|c| c == '\\' || c == '/', "."),
-        )?)
+        Ok(Self::parse(&buffer.replace(['\\', '/'], "."))?)
     }
 }
 
@@ -10,7 +10,7 @@ pub fn rpath(s: &str) -> ResourcePath {
     ResourcePath::parse(s).unwrap()
 }
 
-#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+#[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub struct ResourcePath {
     parts: Vec<ResourcePathPart>,
 }
@@ -39,6 +39,10 @@ impl ResourcePath {
         len
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
     pub fn num_parts(&self) -> usize {
         self.parts.len()
     }
@@ -51,6 +55,17 @@ impl ResourcePath {
         self.parts.get(idx).map(String::as_str)
     }
 
+    /// This path with `suffix` removed from the end of its last part. `None` if the last part doesn't end
+    /// with the suffix, or if nothing would be left of it.
+    pub fn strip_suffix(&self, suffix: &str) -> Option<Self> {
+        let (last, rest) = self.parts.split_last()?;
+        let stripped = last.strip_suffix(suffix).filter(|part| !part.is_empty())?;
+
+        let mut parts = rest.to_vec();
+        parts.push(stripped.to_string());
+        Some(Self::from_parts(parts))
+    }
+
     pub fn string(&self) -> String {
         let mut string = String::with_capacity(self.len());
         let mut parts = self.parts().peekable();
@@ -75,4 +90,14 @@ mod tests {
 
         assert_eq!("[should.format.correctly]", format!("{rpath}"));
     }
+
+    #[test]
+    fn strip_suffix() {
+        assert_eq!(
+            Some(rpath("blocks.stone")),
+            rpath("blocks.stone_n").strip_suffix("_n")
+        );
+        assert_eq!(None, rpath("blocks_n.stone").strip_suffix("_n"));
+        assert_eq!(None, rpath("blocks._n").strip_suffix("_n"));
+    }
 }
@@ -1,7 +1,10 @@
-use std::{path::PathBuf, sync::Arc};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 use bevy::{
-    asset::LoadedFolder,
+    asset::{io::AssetReaderError, AssetLoadError, AssetLoadFailedEvent, LoadedFolder},
     ecs::system::SystemParam,
     prelude::*,
     render::{render_asset::RenderAssets, texture::GpuImage},
@@ -22,25 +25,248 @@ use super::{
         Registries,
     },
     resourcepath::rpath,
+    texture::TextureMaterial,
     tile::Transparency,
     voxel::descriptor::BlockVariantDescriptor,
 };
 
-pub static TEXTURE_FOLDER_NAME: &'static str = "textures";
-pub static NORMALMAPS_FOLDER_NAME: &'static str = "normalmaps";
+pub static TEXTURE_FOLDER_NAME: &str = "textures";
+pub static NORMALMAPS_FOLDER_NAME: &str = "normalmaps";
+
+/// The texture packs to load textures from, in order of precedence. A pack is a folder in the assets
+/// with a [`TEXTURE_FOLDER_NAME`] and a [`NORMALMAPS_FOLDER_NAME`] folder in it, either of which can be
+/// left out if the pack has nothing to put in it. Textures (and normal maps)
+/// in later packs override the ones at the same resource path in earlier packs, so resource packs
+/// and mods can replace some textures and keep the rest.
+///
+/// Normal maps can also be put next to their texture in the texture folder, named like the texture
+/// with the [normal map suffix](Self::with_normal_map_suffix) at the end.
+///
+/// The stack starts with the base pack at the root of the assets folder. Insert this resource before
+/// adding the [`VoxelPlugin`](crate::VoxelPlugin) to load more packs.
+#[derive(Resource, Clone, Debug)]
+pub struct TexturePackStack {
+    packs: Vec<PathBuf>,
+    normal_map_suffix: String,
+    materials: hb::HashMap<ResourcePath, TextureMaterial>,
+}
+
+impl Default for TexturePackStack {
+    fn default() -> Self {
+        Self {
+            packs: vec![PathBuf::new()],
+            normal_map_suffix: Self::DEFAULT_NORMAL_MAP_SUFFIX.to_string(),
+            materials: hb::HashMap::new(),
+        }
+    }
+}
+
+impl TexturePackStack {
+    pub const DEFAULT_NORMAL_MAP_SUFFIX: &'static str = "_n";
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Textures named like another texture with this suffix at the end are used as the normal map of
+    /// that texture, instead of as textures of their own. `_n` by default.
+    pub fn with_normal_map_suffix(mut self, suffix: impl Into<String>) -> Self {
+        self.normal_map_suffix = suffix.into();
+        self
+    }
+
+    pub fn normal_map_suffix(&self) -> &str {
+        &self.normal_map_suffix
+    }
+
+    /// Give the texture at `label` PBR properties other than the [default](TextureMaterial::default),
+    /// like metal blocks or wet surfaces. Applies to the texture from whichever pack it ends up coming from.
+    pub fn with_material(mut self, label: ResourcePath, material: TextureMaterial) -> Self {
+        self.materials.insert(label, material);
+        self
+    }
+
+    pub fn materials(&self) -> &hb::HashMap<ResourcePath, TextureMaterial> {
+        &self.materials
+    }
+
+    /// Add a pack on top of the stack, overriding all the packs before it.
+    pub fn with_pack(mut self, root: impl Into<PathBuf>) -> Self {
+        self.packs.push(root.into());
+        self
+    }
+
+    /// The root folders of the packs, lowest precedence first.
+    pub fn packs(&self) -> &[PathBuf] {
+        &self.packs
+    }
+
+    /// The folder called `name` in every pack, lowest precedence first.
+    pub fn folders(&self, name: &str) -> Vec<PathBuf> {
+        self.packs.iter().map(|root| root.join(name)).collect()
+    }
+
+    /// Merge the textures of every pack, given lowest precedence first. A texture at the same resource
+    /// path as one in an earlier pack replaces it.
+    pub fn merge<P, T>(packs: P) -> hb::HashMap<ResourcePath, T>
+    where
+        P: IntoIterator,
+        P::Item: IntoIterator<Item = (ResourcePath, T)>,
+    {
+        let mut merged = hb::HashMap::new();
+        for pack in packs {
+            merged.extend(pack);
+        }
+
+        merged
+    }
+
+    /// Resolve the textures and normal maps of every pack, given lowest precedence first, into the ones
+    /// of the whole stack.
+    ///
+    /// Textures named with the [normal map suffix](Self::with_normal_map_suffix) are moved to the normal maps
+    /// of their pack first, under the path of the texture they belong to, and normal maps in the pack's normal
+    /// map folder take precedence over them. The packs are then [merged](Self::merge), so a normal map from a
+    /// later pack overrides one from an earlier pack no matter how either of them was named. Suffixed textures
+    /// without a texture in the stack to belong to are kept as textures.
+    pub fn resolve<T>(
+        &self,
+        packs: Vec<PackImages<T>>,
+    ) -> (hb::HashMap<ResourcePath, T>, hb::HashMap<ResourcePath, T>) {
+        let labels = packs
+            .iter()
+            .flat_map(|pack| pack.textures.iter().map(|(rpath, _)| rpath.clone()))
+            .collect::<hb::HashSet<_>>();
+
+        let (textures, normalmaps): (Vec<_>, Vec<_>) = packs
+            .into_iter()
+            .map(|pack| {
+                let mut normalmaps = pack.normalmaps.into_iter().collect::<hb::HashMap<_, _>>();
+                let mut textures = Vec::with_capacity(pack.textures.len());
+
+                for (rpath, image) in pack.textures {
+                    match rpath
+                        .strip_suffix(&self.normal_map_suffix)
+                        .filter(|texture| labels.contains(texture))
+                    {
+                        Some(texture) => {
+                            normalmaps.entry(texture).or_insert(image);
+                        }
+                        None => textures.push((rpath, image)),
+                    }
+                }
+
+                (textures, normalmaps)
+            })
+            .unzip();
+
+        (Self::merge(textures), Self::merge(normalmaps))
+    }
+}
+
+/// The images of a pack in a [`TexturePackStack`], by their resource path.
+#[derive(Clone, Debug)]
+pub struct PackImages<T> {
+    pub textures: Vec<(ResourcePath, T)>,
+    pub normalmaps: Vec<(ResourcePath, T)>,
+}
 
-#[derive(Resource, Default)]
-pub struct VoxelTextureFolder {
-    pub handle: Handle<LoadedFolder>,
-    pub loaded: bool,
+/// The loading state of the folder of a pack in a [`PackFolders`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PackFolderState {
+    Loading,
+    Loaded,
+    /// The pack doesn't have this folder, it's treated as if it was empty.
+    Missing,
 }
 
-#[derive(Resource, Default)]
-pub struct VoxelNormalMapFolder {
-    pub handle: Handle<LoadedFolder>,
-    pub loaded: bool,
+/// Folders loaded for every pack in the [`TexturePackStack`], in the same order as the stack.
+/// Packs don't need to have every folder, missing folders are skipped.
+#[derive(Default)]
+pub struct PackFolders {
+    pub folders: Vec<(PathBuf, Handle<LoadedFolder>)>,
+    pub states: Vec<PackFolderState>,
 }
 
+impl PackFolders {
+    fn load(server: &AssetServer, stack: &TexturePackStack, name: &str) -> Self {
+        let folders = stack
+            .folders(name)
+            .into_iter()
+            .map(|path| (path.clone(), server.load_folder(path)))
+            .collect::<Vec<_>>();
+
+        Self {
+            states: vec![PackFolderState::Loading; folders.len()],
+            folders,
+        }
+    }
+
+    fn mark_loaded(&mut self, event: &AssetEvent<LoadedFolder>) {
+        for ((_, handle), state) in self.folders.iter().zip(self.states.iter_mut()) {
+            if event.is_loaded_with_dependencies(handle) {
+                *state = PackFolderState::Loaded;
+            }
+        }
+    }
+
+    fn mark_failed(&mut self, event: &AssetLoadFailedEvent<LoadedFolder>) {
+        for ((path, handle), state) in self.folders.iter().zip(self.states.iter_mut()) {
+            if event.id != handle.id() {
+                continue;
+            }
+
+            if !matches!(
+                event.error,
+                AssetLoadError::AssetReaderError(AssetReaderError::NotFound(_))
+            ) {
+                let path = path.to_string_lossy();
+                error!(
+                    "Error loading texture pack folder '{path}', skipping it: {}",
+                    event.error
+                );
+            }
+
+            *state = PackFolderState::Missing;
+        }
+    }
+
+    pub fn all_loaded(&self) -> bool {
+        self.states
+            .iter()
+            .all(|&state| state != PackFolderState::Loading)
+    }
+
+    /// The images in the folder of every pack, in the same order as the stack. Folders missing from their
+    /// pack have no images.
+    #[allow(clippy::type_complexity)]
+    fn images(
+        &self,
+        folders: &Assets<LoadedFolder>,
+        not_loaded: impl Fn() -> TextureRegistryError,
+    ) -> Result<Vec<Vec<(ResourcePath, AssetId<Image>)>>, TextureRegistryError> {
+        self.folders
+            .iter()
+            .zip(self.states.iter())
+            .map(|((path, handle), &state)| {
+                if state == PackFolderState::Missing {
+                    return Ok(Vec::new());
+                }
+
+                // rust-analyzer can't infer this type for some reason so we have to explicitly state it
+                let folder: &LoadedFolder = folders.get(handle).ok_or_else(&not_loaded)?;
+                folder_images(folder, path)
+            })
+            .collect()
+    }
+}
+
+#[derive(Resource, Default, Deref, DerefMut)]
+pub struct VoxelTextureFolder(pub PackFolders);
+
+#[derive(Resource, Default, Deref, DerefMut)]
+pub struct VoxelNormalMapFolder(pub PackFolders);
+
 #[derive(Resource, Default, Clone)]
 pub struct VoxelColorArrayTexture(pub Handle<MippedArrayTexture>);
 
@@ -114,15 +340,21 @@ impl<'w> ArrayTextureHandles<'w> {
 #[derive(Resource, Deref, dm::Constructor)]
 pub struct VariantFolders(Arc<Vec<PathBuf>>);
 
-pub(crate) fn load_textures(mut cmds: Commands, server: Res<AssetServer>) {
-    cmds.insert_resource(VoxelTextureFolder {
-        handle: server.load_folder(TEXTURE_FOLDER_NAME),
-        loaded: false,
-    });
-    cmds.insert_resource(VoxelNormalMapFolder {
-        handle: server.load_folder(NORMALMAPS_FOLDER_NAME),
-        loaded: false,
-    });
+pub(crate) fn load_textures(
+    mut cmds: Commands,
+    server: Res<AssetServer>,
+    stack: Res<TexturePackStack>,
+) {
+    cmds.insert_resource(VoxelTextureFolder(PackFolders::load(
+        &server,
+        &stack,
+        TEXTURE_FOLDER_NAME,
+    )));
+    cmds.insert_resource(VoxelNormalMapFolder(PackFolders::load(
+        &server,
+        &stack,
+        NORMALMAPS_FOLDER_NAME,
+    )));
 }
 
 pub(crate) fn check_textures(
@@ -130,74 +362,69 @@ pub(crate) fn check_textures(
     mut texture_folder: ResMut<VoxelTextureFolder>,
     mut normalmap_folder: ResMut<VoxelNormalMapFolder>,
     mut events: EventReader<AssetEvent<LoadedFolder>>,
+    mut failed: EventReader<AssetLoadFailedEvent<LoadedFolder>>,
 ) {
     for event in events.read() {
-        if event.is_loaded_with_dependencies(&texture_folder.handle) {
-            texture_folder.loaded = true;
-        }
+        texture_folder.mark_loaded(event);
+        normalmap_folder.mark_loaded(event);
+    }
 
-        if event.is_loaded_with_dependencies(&normalmap_folder.handle) {
-            normalmap_folder.loaded = true;
-        }
+    for event in failed.read() {
+        texture_folder.mark_failed(event);
+        normalmap_folder.mark_failed(event);
     }
 
-    if texture_folder.loaded && normalmap_folder.loaded {
+    if texture_folder.all_loaded() && normalmap_folder.all_loaded() {
         next_state.set(EngineState::Finished);
     }
 }
 
+/// The images in a loaded folder of a pack, by their resource path relative to the folder at `path`.
+fn folder_images(
+    folder: &LoadedFolder,
+    path: &Path,
+) -> Result<Vec<(ResourcePath, AssetId<Image>)>, TextureRegistryError> {
+    let mut images = Vec::with_capacity(folder.handles.len());
+
+    for handle in folder.handles.iter() {
+        let Some(asset_path) = handle.path() else {
+            return Err(TextureRegistryError::CannotMakePath(handle.clone()));
+        };
+
+        let id = handle.id().try_typed::<Image>()?;
+
+        let rpath = asset_path.path().strip_prefix(path).unwrap();
+        images.push((ResourcePath::try_from(rpath)?, id));
+    }
+
+    Ok(images)
+}
+
 fn create_texture_registry(
     folders: Res<Assets<LoadedFolder>>,
     images: ResMut<Assets<Image>>,
     mut array_textures: ResMut<Assets<MippedArrayTexture>>,
     texture_folder: Res<VoxelTextureFolder>,
     normalmap_folder: Res<VoxelNormalMapFolder>,
+    stack: Res<TexturePackStack>,
 ) -> Result<TextureRegistry, TextureRegistryError> {
-    // rust-analyzer can't infer this type for some reason so we have to explicitly state it
-    let texture_folder: &LoadedFolder = folders
-        .get(&texture_folder.handle)
-        .ok_or(TextureRegistryError::VoxelTextureFolderNotLoaded)?;
-
-    let normalmap_folder: &LoadedFolder = folders
-        .get(&normalmap_folder.handle)
-        .ok_or(TextureRegistryError::VoxelNormalMapFolderNotLoaded)?;
-
-    let textures = {
-        let mut map = hb::HashMap::<ResourcePath, AssetId<Image>>::new();
-
-        for handle in texture_folder.handles.iter() {
-            let Some(asset_path) = handle.path() else {
-                return Err(TextureRegistryError::CannotMakePath(handle.clone()));
-            };
-
-            let id = handle.id().try_typed::<Image>()?;
-
-            let path = asset_path.path().strip_prefix(TEXTURE_FOLDER_NAME).unwrap();
-            map.insert(ResourcePath::try_from(path)?, id);
-        }
-
-        map
-    };
-
-    let normalmaps = {
-        let mut map = hb::HashMap::<ResourcePath, AssetId<Image>>::new();
-
-        for handle in normalmap_folder.handles.iter() {
-            let Some(asset_path) = handle.path() else {
-                return Err(TextureRegistryError::CannotMakePath(handle.clone()));
-            };
-
-            let id = handle.id().try_typed::<Image>()?;
-
-            let path = asset_path
-                .path()
-                .strip_prefix(NORMALMAPS_FOLDER_NAME)
-                .unwrap();
-            map.insert(ResourcePath::try_from(path)?, id);
-        }
-
-        map
-    };
+    let textures = texture_folder.images(&folders, || {
+        TextureRegistryError::VoxelTextureFolderNotLoaded
+    })?;
+    let normalmaps = normalmap_folder.images(&folders, || {
+        TextureRegistryError::VoxelNormalMapFolderNotLoaded
+    })?;
+
+    let (textures, normalmaps) = stack.resolve(
+        textures
+            .into_iter()
+            .zip(normalmaps)
+            .map(|(textures, normalmaps)| PackImages {
+                textures,
+                normalmaps,
+            })
+            .collect(),
+    );
 
     let mut registry_loader = TextureRegistryLoader::new();
 
@@ -207,7 +434,13 @@ fn create_texture_registry(
         registry_loader.register(rpath.clone(), texture, normalmap)
     }
 
-    Ok(registry_loader.build_registry(images.as_ref(), &mut array_textures)?)
+    for (rpath, &material) in stack.materials() {
+        if !registry_loader.set_material(rpath, material) {
+            warn!("Material was given for texture '{rpath}', but there is no such texture");
+        }
+    }
+
+    registry_loader.build_registry(images.as_ref(), &mut array_textures)
 }
 
 fn create_block_variant_registry(
@@ -223,6 +456,8 @@ fn create_block_variant_registry(
             options: BlockOptions {
                 transparency: Transparency::Transparent,
                 subdividable: true,
+                emission: 0,
+                fluid_level: None,
             },
             model: None,
         },
@@ -261,7 +496,6 @@ pub fn build_registries(world: &mut World) {
     let registries = world.resource_mut::<Registries>();
 
     registries.add_registry(texreg);
-    drop(registries);
 
     let blockreg = match world
         .run_system::<Result<BlockVariantRegistry, BlockVariantRegistryLoadError>>(
@@ -279,3 +513,255 @@ pub fn build_registries(world: &mut World) {
     let registries = world.resource_mut::<Registries>();
     registries.add_registry(blockreg);
 }
+
+#[cfg(test)]
+mod tests {
+    use bevy::{
+        asset::io::{
+            memory::{Dir, MemoryAssetReader},
+            AssetReader, AssetReaderError, AssetSource, AssetSourceId, PathStream, Reader,
+        },
+        render::{
+            render_asset::RenderAssetUsages,
+            render_resource::{Extent3d, TextureDimension, TextureFormat},
+        },
+        utils::BoxedFuture,
+    };
+
+    use crate::data::registries::{texture::TEXTURE_DIMENSIONS, Registry};
+
+    use super::*;
+
+    fn solid_image(color: [u8; 4]) -> Image {
+        let size = Extent3d {
+            width: TEXTURE_DIMENSIONS,
+            height: TEXTURE_DIMENSIONS,
+            depth_or_array_layers: 1,
+        };
+
+        Image::new_fill(
+            size,
+            TextureDimension::D2,
+            &color,
+            TextureFormat::Rgba8UnormSrgb,
+            RenderAssetUsages::all(),
+        )
+    }
+
+    #[test]
+    fn override_pack_texture_wins() {
+        let stack = TexturePackStack::new().with_pack("packs/override");
+        assert_eq!(
+            vec![
+                PathBuf::from("textures"),
+                PathBuf::from("packs/override/textures")
+            ],
+            stack.folders(TEXTURE_FOLDER_NAME)
+        );
+
+        let mut images = Assets::<Image>::default();
+        let base_stone = images.add(solid_image([255, 0, 0, 255])).id();
+        let base_dirt = images.add(solid_image([0, 255, 0, 255])).id();
+        let override_stone = images.add(solid_image([0, 0, 255, 255])).id();
+
+        let textures = TexturePackStack::merge([
+            vec![(rpath("stone"), base_stone), (rpath("dirt"), base_dirt)],
+            vec![(rpath("stone"), override_stone)],
+        ]);
+        assert_eq!(2, textures.len());
+
+        // the array texture builder can't build an empty normal map array texture
+        let normal = images.add(solid_image([127, 127, 255, 255])).id();
+
+        let mut loader = TextureRegistryLoader::new();
+        for (label, &texture) in textures.iter() {
+            loader.register(label.clone(), texture, Some(normal));
+        }
+
+        let mut array_textures = Assets::<MippedArrayTexture>::default();
+        let registry = loader.build_registry(&images, &mut array_textures).unwrap();
+        let atlas = array_textures.get(registry.color_texture()).unwrap();
+
+        let layer_color = |label: &str| {
+            let layer = registry.get_by_label(&rpath(label)).unwrap().texture_idx as usize;
+            let layer_size = (TEXTURE_DIMENSIONS * TEXTURE_DIMENSIONS * 4) as usize;
+            atlas.image.data[layer * layer_size..layer * layer_size + 4].to_vec()
+        };
+
+        // the override pack replaced the stone texture, and the base pack's dirt texture is kept
+        assert_eq!(vec![0, 0, 255, 255], layer_color("stone"));
+        assert_eq!(vec![0, 255, 0, 255], layer_color("dirt"));
+    }
+
+    /// An in-memory asset reader that reports missing folders as not found like the filesystem does,
+    /// instead of treating them as empty.
+    struct PackReader(MemoryAssetReader);
+
+    impl AssetReader for PackReader {
+        fn read<'a>(
+            &'a self,
+            path: &'a Path,
+        ) -> BoxedFuture<'a, Result<Box<Reader<'a>>, AssetReaderError>> {
+            self.0.read(path)
+        }
+
+        fn read_meta<'a>(
+            &'a self,
+            path: &'a Path,
+        ) -> BoxedFuture<'a, Result<Box<Reader<'a>>, AssetReaderError>> {
+            self.0.read_meta(path)
+        }
+
+        fn read_directory<'a>(
+            &'a self,
+            path: &'a Path,
+        ) -> BoxedFuture<'a, Result<Box<PathStream>, AssetReaderError>> {
+            self.0.read_directory(path)
+        }
+
+        fn is_directory<'a>(
+            &'a self,
+            path: &'a Path,
+        ) -> BoxedFuture<'a, Result<bool, AssetReaderError>> {
+            let root = &self.0.root;
+            Box::pin(async move {
+                if root.get_dir(path).is_some() {
+                    Ok(true)
+                } else if root.get_asset(path).is_some() {
+                    Ok(false)
+                } else {
+                    Err(AssetReaderError::NotFound(path.to_path_buf()))
+                }
+            })
+        }
+    }
+
+    #[test]
+    fn packs_without_normal_maps_finish_loading() {
+        // the base pack only has textures, the override pack has no folders at all
+        let assets = Dir::default();
+        assets.get_or_insert_dir(Path::new(TEXTURE_FOLDER_NAME));
+
+        let mut app = App::new();
+        app.register_asset_source(
+            AssetSourceId::Default,
+            AssetSource::build().with_reader(move || {
+                Box::new(PackReader(MemoryAssetReader {
+                    root: assets.clone(),
+                }))
+            }),
+        );
+        app.add_plugins((MinimalPlugins, AssetPlugin::default()));
+        app.init_state::<EngineState>();
+        app.insert_resource(TexturePackStack::new().with_pack("override"));
+        app.add_systems(OnEnter(EngineState::Setup), load_textures);
+        app.add_systems(Update, check_textures.run_if(in_state(EngineState::Setup)));
+
+        // the folders are read by the asset server's tasks, keep updating until they've all settled
+        for _ in 0..10_000 {
+            app.update();
+            if *app.world.resource::<State<EngineState>>() == EngineState::Finished {
+                break;
+            }
+        }
+
+        assert_eq!(
+            EngineState::Finished,
+            *app.world.resource::<State<EngineState>>().get()
+        );
+        assert_eq!(
+            vec![PackFolderState::Loaded, PackFolderState::Missing],
+            app.world.resource::<VoxelTextureFolder>().states
+        );
+        assert_eq!(
+            vec![PackFolderState::Missing; 2],
+            app.world.resource::<VoxelNormalMapFolder>().states
+        );
+    }
+
+    #[test]
+    fn suffixed_normal_maps_are_discovered() {
+        let mut images = Assets::<Image>::default();
+        let stone = images.add(solid_image([255, 0, 0, 255])).id();
+        let stone_n = images.add(solid_image([127, 127, 255, 255])).id();
+        let dirt = images.add(solid_image([0, 255, 0, 255])).id();
+        let lonely_n = images.add(solid_image([0, 0, 0, 255])).id();
+
+        let (textures, normalmaps) = TexturePackStack::new().resolve(vec![PackImages {
+            textures: vec![
+                (rpath("stone"), stone),
+                (rpath("stone_n"), stone_n),
+                (rpath("dirt"), dirt),
+                (rpath("lonely_n"), lonely_n),
+            ],
+            normalmaps: vec![],
+        }]);
+
+        // a suffixed texture without a texture to belong to is a texture of its own
+        assert_eq!(3, textures.len());
+        assert!(textures.contains_key(&rpath("lonely_n")));
+        assert_eq!(Some(&stone_n), normalmaps.get(&rpath("stone")));
+        assert_eq!(1, normalmaps.len());
+
+        let mut loader = TextureRegistryLoader::new();
+        for (label, &texture) in textures.iter() {
+            loader.register(label.clone(), texture, normalmaps.get(label).copied());
+        }
+
+        let mut array_textures = Assets::<MippedArrayTexture>::default();
+        let registry = loader.build_registry(&images, &mut array_textures).unwrap();
+        let faces = registry.face_texture_buffer();
+
+        let stone_id = registry.get_id(&rpath("stone")).unwrap();
+        let dirt_id = registry.get_id(&rpath("dirt")).unwrap();
+        assert!(faces[stone_id.index()].has_normal_map());
+        assert!(!faces[dirt_id.index()].has_normal_map());
+
+        // the normal map of the stone texture is in the normal array texture where the face points to
+        let normal_idx = registry.get_by_id(stone_id).normal_idx.unwrap() as usize;
+        let normal_atlas = array_textures.get(registry.normal_texture()).unwrap();
+        let layer_size = (TEXTURE_DIMENSIONS * TEXTURE_DIMENSIONS * 4) as usize;
+        assert_eq!(
+            &[127, 127, 255, 255],
+            &normal_atlas.image.data[normal_idx * layer_size..normal_idx * layer_size + 4]
+        );
+    }
+
+    #[test]
+    fn later_packs_override_normal_maps() {
+        let (base, base_n, base_stone_n) = (0, 1, 2);
+        let (top_stone_n, top_dirt_n) = (3, 4);
+
+        // the base pack has stone's normal map in its normal map folder and a suffixed one, the top pack
+        // only has a suffixed one for stone and for the base pack's dirt texture
+        let packs = vec![
+            PackImages {
+                textures: vec![
+                    (rpath("stone"), base),
+                    (rpath("dirt"), base),
+                    (rpath("stone_n"), base_stone_n),
+                ],
+                normalmaps: vec![(rpath("stone"), base_n)],
+            },
+            PackImages {
+                textures: vec![
+                    (rpath("stone_n"), top_stone_n),
+                    (rpath("dirt_n"), top_dirt_n),
+                ],
+                normalmaps: vec![],
+            },
+        ];
+
+        let (textures, normalmaps) = TexturePackStack::new().resolve(packs);
+        assert_eq!(2, textures.len());
+        assert_eq!(Some(&top_stone_n), normalmaps.get(&rpath("stone")));
+        assert_eq!(Some(&top_dirt_n), normalmaps.get(&rpath("dirt")));
+
+        // within a pack, the normal map folder wins over suffixed textures
+        let (_, normalmaps) = TexturePackStack::new().resolve(vec![PackImages {
+            textures: vec![(rpath("stone"), base), (rpath("stone_n"), base_stone_n)],
+            normalmaps: vec![(rpath("stone"), base_n)],
+        }]);
+        assert_eq!(Some(&base_n), normalmaps.get(&rpath("stone")));
+    }
+}
@@ -1,6 +1,11 @@
 use std::ops;
 
-use bevy::{log::info, render::render_resource::ShaderType};
+use bevy::{
+    log::{info, warn},
+    render::render_resource::{ShaderDefVal, ShaderType},
+};
+
+use crate::render::core::u32_shader_def;
 
 use super::{
     error::FaceTextureRotationParseError,
@@ -38,7 +43,7 @@ impl std::str::FromStr for FaceTextureRotation {
 impl FaceTextureRotation {
     pub const TOTAL_ROTATIONS: i32 = 4;
     pub const ONE_TURN_DEG: i32 = 90;
-    pub const ONE_TURN_RAD: f32 = 1.57079633;
+    pub const ONE_TURN_RAD: f32 = std::f32::consts::FRAC_PI_2;
 
     pub fn new(value: i32) -> Self {
         let value: u32 = value.rem_euclid(Self::TOTAL_ROTATIONS) as _;
@@ -94,16 +99,80 @@ impl FaceTexture {
     }
 }
 
-#[derive(Copy, Clone, Debug, Default, ShaderType)]
-pub struct GpuFaceTexture {
-    pub flags: u32,
-    pub color_tex_idx: u32,
-    pub normal_tex_idx: u32,
+/// The PBR properties of a texture, the same for every pixel of it.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TextureMaterial {
+    /// Perceptual roughness, between 0 (glossy) and 1 (rough).
+    pub roughness: f32,
+    /// Between 0 (dielectric) and 1 (metal).
+    pub metallic: f32,
+}
+
+impl Default for TextureMaterial {
+    /// The same properties as the default material in the chunk shaders.
+    fn default() -> Self {
+        Self {
+            roughness: 0.5,
+            metallic: 0.0,
+        }
+    }
+}
+
+pub use self::shader_types::GpuFaceTexture;
+
+// unused `ShaderType` field checks, see `render::quad::shader_types`
+#[allow(dead_code)]
+mod shader_types {
+    use super::*;
+
+    #[derive(Copy, Clone, Debug, Default, ShaderType)]
+    pub struct GpuFaceTexture {
+        pub flags: u32,
+        pub color_tex_idx: u32,
+        pub normal_tex_idx: u32,
+        /// The [`TextureMaterial`] of the face, quantized to 8 bits per channel.
+        pub material: u32,
+    }
 }
 
 impl GpuFaceTexture {
     pub const HAS_NORMAL_MAP_BIT: u32 = 0b1;
 
+    pub const MATERIAL_CHANNEL_MASK: u32 = 0xff;
+    pub const ROUGHNESS_SHIFT: u32 = 0;
+    pub const METALLIC_SHIFT: u32 = 8;
+
+    pub fn shader_defs() -> Vec<ShaderDefVal> {
+        vec![
+            u32_shader_def("HAS_NORMAL_MAP_BIT", Self::HAS_NORMAL_MAP_BIT),
+            u32_shader_def("MATERIAL_CHANNEL_MASK", Self::MATERIAL_CHANNEL_MASK),
+            u32_shader_def("ROUGHNESS_SHIFT", Self::ROUGHNESS_SHIFT),
+            u32_shader_def("METALLIC_SHIFT", Self::METALLIC_SHIFT),
+        ]
+    }
+
+    pub fn with_material(mut self, material: TextureMaterial) -> Self {
+        let quantize = |value: f32| {
+            (value.clamp(0.0, 1.0) * Self::MATERIAL_CHANNEL_MASK as f32).round() as u32
+        };
+
+        self.material = (quantize(material.roughness) << Self::ROUGHNESS_SHIFT)
+            | (quantize(material.metallic) << Self::METALLIC_SHIFT);
+        self
+    }
+
+    pub fn material(&self) -> TextureMaterial {
+        let channel = |shift: u32| {
+            ((self.material >> shift) & Self::MATERIAL_CHANNEL_MASK) as f32
+                / Self::MATERIAL_CHANNEL_MASK as f32
+        };
+
+        TextureMaterial {
+            roughness: channel(Self::ROUGHNESS_SHIFT),
+            metallic: channel(Self::METALLIC_SHIFT),
+        }
+    }
+
     pub fn new(color_idx: u32, normal_idx: Option<u32>) -> Self {
         let mut flags = 0u32;
 
@@ -116,6 +185,65 @@ impl GpuFaceTexture {
             flags,
             color_tex_idx: color_idx,
             normal_tex_idx: normal_idx.unwrap_or(0),
+            material: 0,
         }
+        .with_material(TextureMaterial::default())
+    }
+
+    pub fn has_normal_map(&self) -> bool {
+        self.flags & Self::HAS_NORMAL_MAP_BIT != 0
+    }
+
+    /// Make sure that this face's normal map exists in a normal map array texture with `normal_layers` layers.
+    /// If the face claims to have a normal map that doesn't exist, [`GpuFaceTexture::HAS_NORMAL_MAP_BIT`] is cleared
+    /// so the face falls back to a flat normal. Returns false if the normal map was missing.
+    pub fn validate_normal_map(&mut self, normal_layers: u32) -> bool {
+        if self.has_normal_map() && self.normal_tex_idx >= normal_layers {
+            warn!(
+                "Face texture claims to have normal map at index {}, but there are only {} normal maps. Falling back to a flat normal",
+                self.normal_tex_idx, normal_layers
+            );
+
+            self.flags &= !Self::HAS_NORMAL_MAP_BIT;
+            self.normal_tex_idx = 0;
+            return false;
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn material_round_trip() {
+        let default = GpuFaceTexture::new(0, None);
+        let material = default.material();
+        assert!((material.roughness - 0.5).abs() < 1.0 / 255.0);
+        assert_eq!(0.0, material.metallic);
+
+        let metal = TextureMaterial {
+            roughness: 0.2,
+            metallic: 1.0,
+        };
+        let face = GpuFaceTexture::new(3, Some(1)).with_material(metal);
+        assert!((face.material().roughness - metal.roughness).abs() < 1.0 / 255.0);
+        assert_eq!(1.0, face.material().metallic);
+        assert!(face.has_normal_map());
+
+        // out of range values are clamped instead of bleeding into the other channel
+        let face = GpuFaceTexture::new(0, None).with_material(TextureMaterial {
+            roughness: 2.0,
+            metallic: -1.0,
+        });
+        assert_eq!(
+            TextureMaterial {
+                roughness: 1.0,
+                metallic: 0.0
+            },
+            face.material()
+        );
     }
 }
@@ -22,6 +22,10 @@ pub enum Transparency {
     Opaque,
     #[serde(rename = "trans")]
     Transparent,
+    /// Rendered like an opaque block, but doesn't hide the faces of the blocks next to it, and hides its own faces
+    /// only when they're next to the same block variant (like glass).
+    #[serde(rename = "self_culling")]
+    SelfCulling,
 }
 
 impl Transparency {
@@ -32,6 +36,16 @@ impl Transparency {
     pub fn is_transparent(self) -> bool {
         matches!(self, Self::Transparent)
     }
+
+    /// Whether the face of a block with this transparency is visible when the face is covered by a block with the
+    /// `covering` transparency. `same_variant` is whether the two blocks are the same variant.
+    pub fn face_visible(self, covering: Self, same_variant: bool) -> bool {
+        match (self, covering) {
+            (Self::Transparent, _) | (_, Self::Opaque) => false,
+            (Self::SelfCulling, Self::SelfCulling) => !same_variant,
+            _ => true,
+        }
+    }
 }
 
 /// Faces of a cube
@@ -91,7 +105,7 @@ impl std::str::FromStr for Face {
             "s" | "south" => Self::South,
             "w" | "west" => Self::West,
 
-            _ => return Err(Self::Err::default()),
+            _ => return Err(FaceParseError),
         })
     }
 }
@@ -157,6 +171,17 @@ impl Face {
         .into()
     }
 
+    /// The tangent of this face before any texture rotation is applied.
+    /// This must match `tangent_from_face` in the shaders.
+    #[inline]
+    pub fn tangent(self) -> IVec3 {
+        match self {
+            Face::Top | Face::Bottom | Face::East | Face::West => [1, 0, 0],
+            Face::North | Face::South => [0, 0, 1],
+        }
+        .into()
+    }
+
     #[inline]
     pub fn axis_direction(self) -> i32 {
         match self {
@@ -188,6 +213,37 @@ impl Face {
         }
     }
 
+    /// Project a position on the plane of this face into 3D, placing the plane `mag` units along the axis of
+    /// the face. Opposite faces share a plane. The 2D coordinates map to 3D like this:
+    /// - North and South (X axis): `(x, y)` becomes `(mag, y, x)`
+    /// - Top and Bottom (Y axis): `(x, y)` becomes `(x, mag, y)`
+    /// - East and West (Z axis): `(x, y)` becomes `(x, y, mag)`
+    ///
+    /// Same as [`ivec_project_to_3d`](crate::topo::ivec_project_to_3d). Inverse of [`Face::to_plane_coords`]
+    /// and [`Face::plane_magnitude`].
+    #[inline]
+    pub fn project_to_3d(self, pos: IVec2, mag: i32) -> IVec3 {
+        crate::topo::ivec_project_to_3d(pos, self, mag)
+    }
+
+    /// The 2D coordinates of a 3D position on the plane of this face, see [`Face::project_to_3d`] for how
+    /// the axes map. The component along the axis of the face is dropped, use [`Face::plane_magnitude`] to get it.
+    /// For any `pos` and `mag`, `face.to_plane_coords(face.project_to_3d(pos, mag)) == pos`.
+    #[inline]
+    pub fn to_plane_coords(self, pos: IVec3) -> IVec2 {
+        crate::topo::ivec_project_to_2d(pos, self)
+    }
+
+    /// The component of a 3D position along the axis of this face, the magnitude passed to [`Face::project_to_3d`].
+    #[inline]
+    pub fn plane_magnitude(self, pos: IVec3) -> i32 {
+        match self.axis() {
+            Axis3D::X => pos.x,
+            Axis3D::Y => pos.y,
+            Axis3D::Z => pos.z,
+        }
+    }
+
     #[inline]
     pub const fn from_normal(normal: IVec3) -> Option<Self> {
         match normal.to_array() {
@@ -221,3 +277,57 @@ impl Face {
         self.normal().dot(other.normal()) == 0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::{ivec2, ivec3};
+
+    use crate::{
+        render::quad::isometric::{project_to_2d, project_to_3d},
+        topo::world::Chunk,
+    };
+
+    use super::*;
+
+    #[test]
+    fn plane_projection_round_trips() {
+        for face in Face::FACES {
+            for x in 0..Chunk::SIZE {
+                for y in 0..Chunk::SIZE {
+                    for mag in 0..Chunk::SIZE {
+                        let pos_2d = ivec2(x, y);
+                        let pos_3d = face.project_to_3d(pos_2d, mag);
+
+                        assert_eq!(pos_2d, face.to_plane_coords(pos_3d), "{face:?}");
+                        assert_eq!(mag, face.plane_magnitude(pos_3d), "{face:?}");
+                        assert_eq!(
+                            pos_3d,
+                            face.project_to_3d(
+                                face.to_plane_coords(pos_3d),
+                                face.plane_magnitude(pos_3d)
+                            ),
+                            "{face:?}"
+                        );
+
+                        // the float projection used for isometric quads follows the same convention
+                        let vec_3d = project_to_3d(pos_2d.as_vec2(), face, mag as f32);
+                        assert_eq!(pos_3d.as_vec3(), vec_3d, "{face:?}");
+                        assert_eq!(pos_2d.as_vec2(), project_to_2d(vec_3d, face), "{face:?}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn plane_projection_conventions() {
+        let pos = ivec2(1, 2);
+
+        assert_eq!(ivec3(3, 2, 1), Face::North.project_to_3d(pos, 3));
+        assert_eq!(ivec3(3, 2, 1), Face::South.project_to_3d(pos, 3));
+        assert_eq!(ivec3(1, 3, 2), Face::Top.project_to_3d(pos, 3));
+        assert_eq!(ivec3(1, 3, 2), Face::Bottom.project_to_3d(pos, 3));
+        assert_eq!(ivec3(1, 2, 3), Face::East.project_to_3d(pos, 3));
+        assert_eq!(ivec3(1, 2, 3), Face::West.project_to_3d(pos, 3));
+    }
+}
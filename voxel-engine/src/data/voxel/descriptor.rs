@@ -192,7 +192,7 @@ mod tests {
 
     use super::*;
 
-    fn test<'a, T>(desc: T, s: &'a str)
+    fn test<T>(desc: T, s: &str)
     where
         T::Error: PartialEq + std::fmt::Debug,
         T: std::fmt::Debug + PartialEq + TryFrom<String>,
@@ -1,4 +1,7 @@
-use crate::{render::occlusion::BlockOcclusion, util::FaceMap};
+use crate::{
+    render::{occlusion::BlockOcclusion, quad::tangent::TangentBasis},
+    util::FaceMap,
+};
 
 use self::{
     descriptor::BlockVariantDescriptor,
@@ -27,6 +30,7 @@ pub struct BlockModel {
 }
 
 impl BlockModel {
+    #[allow(clippy::result_unit_err)]
     pub fn from_descriptor(
         _descriptor: &BlockVariantDescriptor,
         _registry: &TextureRegistry,
@@ -65,6 +69,19 @@ impl BlockSubmodel {
         BlockSubmodel::from_map(map).unwrap()
     }
 
+    /// Like [`BlockSubmodel::selfref_no_tex_rot_submodel`], but the textures are also rotated so that
+    /// they keep their orientation relative to the rotated block.
+    fn selfref_submodel(model_rotation: BlockModelRotation) -> BlockSubmodel {
+        BlockSubmodel(FaceMap::filled_with(|world_face| {
+            let model_face = model_rotation.get_model_face(world_face);
+
+            SubmodelFaceTexture::SelfFace {
+                face: model_face,
+                rotation: texture_rotation(model_rotation, model_face, world_face),
+            }
+        }))
+    }
+
     pub fn from_map(map: FaceMap<SubmodelFaceTexture>) -> Option<Self> {
         if map.is_filled() {
             Some(Self(map))
@@ -74,6 +91,28 @@ impl BlockSubmodel {
     }
 }
 
+/// The texture rotation needed for the texture of `model_face` to keep its orientation on `world_face`
+/// when the block is rotated by `rotation`.
+fn texture_rotation(
+    rotation: BlockModelRotation,
+    model_face: BlockModelFace,
+    world_face: Face,
+) -> FaceTextureRotation {
+    let default_face = BlockModelRotation::DEFAULT.get_cardinal_face(model_face);
+    let tangent = rotation.rotate(default_face.tangent());
+
+    (0..FaceTextureRotation::TOTAL_ROTATIONS)
+        .map(FaceTextureRotation::new)
+        .find(|&rot| {
+            TangentBasis::new(world_face, rot)
+                .tangent
+                .round()
+                .as_ivec3()
+                == tangent
+        })
+        .unwrap_or_default()
+}
+
 impl BlockModel {
     pub fn submodel(&self, direction: Face) -> SubmodelRef<'_> {
         let submodel =
@@ -85,14 +124,28 @@ impl BlockModel {
                 ));
 
         SubmodelRef {
-            parent: &self,
+            parent: self,
+            model: submodel,
+        }
+    }
+
+    /// The submodel for a block with the given rotation. Submodels from the descriptor are only used for upright
+    /// blocks, for other rotations the faces of the model are remapped through the rotation.
+    pub fn rotated_submodel(&self, rotation: BlockModelRotation) -> SubmodelRef<'_> {
+        let submodel = (rotation.up() == Face::Top)
+            .then(|| self.directions.get(rotation.front()).copied())
+            .flatten()
+            .unwrap_or_else(|| BlockSubmodel::selfref_submodel(rotation));
+
+        SubmodelRef {
+            parent: self,
             model: submodel,
         }
     }
 
     pub fn default_submodel(&self) -> SubmodelRef<'_> {
         SubmodelRef {
-            parent: &self,
+            parent: self,
             model: BlockSubmodel::selfref_no_tex_rot_submodel(BlockModelRotation::DEFAULT),
         }
     }
@@ -120,11 +173,27 @@ impl<'a> SubmodelRef<'a> {
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 #[non_exhaustive]
+// block models are much bigger than fluids, but voxel models are only ever short lived values
+#[allow(clippy::large_enum_variant)]
 pub enum VoxelModel {
     Block(BlockModel),
+    /// A fluid filled up to `level` out of [`VoxelModel::MAX_FLUID_LEVEL`]. Fluids are meshed with sloped
+    /// top surfaces, see [`crate::render::meshing::fluid`].
+    Fluid {
+        level: u8,
+    },
 }
 
 impl VoxelModel {
+    pub const MAX_FLUID_LEVEL: u8 = 8;
+
+    pub fn fluid_level(&self) -> Option<u8> {
+        match self {
+            Self::Fluid { level } => Some(*level),
+            _ => None,
+        }
+    }
+
     pub fn into_block_model(self) -> Option<BlockModel> {
         match self {
             Self::Block(model) => Some(model),
@@ -136,3 +205,59 @@ impl VoxelModel {
         todo!()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::data::registries::texture::TextureId;
+
+    use super::*;
+
+    fn texture(idx: u32) -> FaceTexture {
+        FaceTexture::new(TextureId::new(idx))
+    }
+
+    /// A model with a different texture on every face.
+    fn model() -> BlockModel {
+        BlockModel {
+            directions: FaceMap::new(),
+            model: BlockModelFaceMap::from_fn(|face| Some(texture(face.to_usize() as u32))),
+        }
+    }
+
+    #[test]
+    fn rotated_submodel_remaps_faces() {
+        let model = model();
+
+        let default = model.default_submodel();
+        assert_eq!(
+            default.texture(Face::North),
+            model
+                .rotated_submodel(BlockModelRotation::DEFAULT)
+                .texture(Face::North)
+        );
+
+        // rotated 90 degrees around the Y axis
+        let rotated =
+            model.rotated_submodel(BlockModelRotation::new(Face::East, Face::Top).unwrap());
+
+        assert_eq!(
+            default.texture(Face::North).id,
+            rotated.texture(Face::East).id
+        );
+        assert_eq!(
+            default.texture(Face::East).id,
+            rotated.texture(Face::South).id
+        );
+        assert_eq!(default.texture(Face::Top).id, rotated.texture(Face::Top).id);
+        assert_eq!(
+            default.texture(Face::Bottom).id,
+            rotated.texture(Face::Bottom).id
+        );
+
+        // the top texture turns with the block
+        assert_ne!(
+            default.texture(Face::Top).rotation,
+            rotated.texture(Face::Top).rotation
+        );
+    }
+}
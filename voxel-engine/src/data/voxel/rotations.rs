@@ -95,6 +95,12 @@ impl<'a, T> Iterator for BlockModelFaceMapIterator<'a, T> {
     }
 }
 
+impl<T> Default for BlockModelFaceMap<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<T> BlockModelFaceMap<T> {
     pub fn new() -> Self {
         Self(array::from_fn(|_| None))
@@ -134,6 +140,10 @@ impl<T> BlockModelFaceMap<T> {
         self.0.iter().filter(|&v| v.is_some()).count()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.0.iter().all(|v| v.is_none())
+    }
+
     pub fn map<U, F: FnMut(BlockModelFace, &T) -> U>(&self, mut f: F) -> BlockModelFaceMap<U> {
         let mut mapped = BlockModelFaceMap::<U>::new();
 
@@ -206,17 +216,33 @@ impl BlockModelRotation {
         }
     }
 
-    pub fn get_model_face(self, face: Face) -> BlockModelFace {
-        match face {
-            Face::Top => todo!(),
-            Face::Bottom => todo!(),
-            Face::North => todo!(),
-            Face::East => todo!(),
-            Face::South => todo!(),
-            Face::West => todo!(),
+    /// Rotate a vector from the default orientation of a block model into this rotation.
+    pub fn rotate(self, v: IVec3) -> IVec3 {
+        let default = Self::DEFAULT;
+
+        v.dot(default.right().normal()) * self.right().normal()
+            + v.dot(default.up().normal()) * self.up().normal()
+            + v.dot(default.front().normal()) * self.front().normal()
+    }
+
+    /// This rotation followed by `rotation`, i.e. a model rotated by this rotation and then rotated again
+    /// by `rotation` as if `rotation` was applied to the already rotated model.
+    pub fn rotated_by(self, rotation: Self) -> Self {
+        Self {
+            fwd: Face::from_normal(rotation.rotate(self.front().normal())).unwrap(),
+            up: Face::from_normal(rotation.rotate(self.up().normal())).unwrap(),
         }
     }
 
+    /// The face of the model that ends up facing `face` with this rotation.
+    /// Inverse of [`BlockModelRotation::get_cardinal_face`].
+    pub fn get_model_face(self, face: Face) -> BlockModelFace {
+        BlockModelFace::FACES
+            .into_iter()
+            .find(|&model_face| self.get_cardinal_face(model_face) == face)
+            .unwrap()
+    }
+
     pub fn pitch(self) -> i32 {
         let front_normal = self.front().normal().as_vec3();
         let pitch = f32::asin(front_normal.y);
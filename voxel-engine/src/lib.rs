@@ -11,19 +11,16 @@ extern crate num_derive;
 
 use std::{path::PathBuf, sync::Arc, time::Duration};
 
-use bevy::{math::ivec3, prelude::*};
-use data::{
-    registries::{block::BlockVariantRegistry, Registries, Registry},
-    resourcepath::rpath,
-};
+use bevy::prelude::*;
+use data::registries::{block::BlockVariantRegistry, Registries};
 use mip_texture_array::MippedArrayTexturePlugin;
-use render::meshing::controller::MeshGeneration;
+use render::meshing::controller::MeshBackend;
 use topo::{
     block::FullBlock,
     controller::{
         ChunkEcsPermits, WorldController, WorldControllerSettings, WorldControllerSystems,
     },
-    world::{realm::ChunkManagerResource, ChunkManager, VoxelRealm},
+    world::{realm::ChunkManagerResource, ChunkManager, ContainerHasher},
 };
 
 pub mod data;
@@ -35,27 +32,41 @@ pub mod util;
 pub mod testing_utils;
 
 use crate::{
-    data::systems::{build_registries, check_textures, load_textures, VariantFolders},
+    data::systems::{
+        build_registries, check_textures, load_textures, TexturePackStack, VariantFolders,
+    },
     render::{core::RenderCore, meshing::controller::MeshController},
     topo::{
-        world::{Chunk, ChunkEntity, ChunkPos},
+        store::RealmTeardownSystems,
         worldgen::{
-            ecs::{generate_chunks_from_events, setup_terrain_generator_workers, GeneratorSeed},
+            ecs::{
+                generate_chunks_from_events, send_generated_chunk_events,
+                setup_terrain_generator_workers, shutdown_terrain_generator_workers, GeneratorSeed,
+            },
             generator::GenerateChunk,
+            GeneratedChunk,
         },
     },
 };
 
 pub struct VoxelPlugin {
     variant_folders: Arc<Vec<PathBuf>>,
+    mesh_backend: MeshBackend,
 }
 
 impl VoxelPlugin {
     pub fn new(variant_folders: Vec<PathBuf>) -> Self {
         VoxelPlugin {
             variant_folders: Arc::new(variant_folders),
+            mesh_backend: MeshBackend::default(),
         }
     }
+
+    /// Build chunk meshes with the given backend instead of the default one for the platform.
+    pub fn with_mesh_backend(mut self, backend: MeshBackend) -> Self {
+        self.mesh_backend = backend;
+        self
+    }
 }
 
 #[derive(SystemSet, Hash, Debug, PartialEq, Eq, Clone)]
@@ -82,15 +93,20 @@ impl Plugin for VoxelPlugin {
                 chunk_loading_max_stalling: Duration::from_millis(200),
             },
         });
-        app.add_plugins(MeshController);
+        app.add_plugins(MeshController {
+            backend: self.mesh_backend,
+        });
         app.add_plugins(RenderCore);
         app.add_plugins(MippedArrayTexturePlugin::default());
 
         app.add_event::<GenerateChunk>();
+        app.add_event::<GeneratedChunk>();
         app.init_state::<EngineState>();
 
         app.insert_resource(VariantFolders::new(self.variant_folders.clone()));
+        app.init_resource::<TexturePackStack>();
         app.insert_resource(GeneratorSeed(140));
+        app.init_resource::<ContainerHasher>();
 
         app.add_systems(OnEnter(EngineState::Setup), load_textures);
         app.add_systems(Update, check_textures.run_if(in_state(EngineState::Setup)));
@@ -103,23 +119,26 @@ impl Plugin for VoxelPlugin {
 
         app.add_systems(
             FixedPostUpdate,
-            generate_chunks_from_events
+            (generate_chunks_from_events, send_generated_chunk_events)
                 .run_if(in_state(EngineState::Finished))
                 .after(WorldControllerSystems::CoreEvents),
         );
+
+        app.add_systems(
+            Last,
+            shutdown_terrain_generator_workers.in_set(RealmTeardownSystems::StopWorkers),
+        );
     }
 }
 
-fn setup(mut cmds: Commands, registries: Res<Registries>) {
+fn setup(mut cmds: Commands, registries: Res<Registries>, hasher: Res<ContainerHasher>) {
     let varreg = registries.get_registry::<BlockVariantRegistry>().unwrap();
-    let void = FullBlock {
+    let air = FullBlock {
         rotation: None,
-        id: varreg
-            .get_id(&rpath(BlockVariantRegistry::RPATH_VOID))
-            .unwrap(),
+        id: varreg.air(),
     };
 
-    let chunk_manager = ChunkManager::new(void);
+    let chunk_manager = ChunkManager::new(air).with_container_hasher(*hasher);
 
     cmds.init_resource::<ChunkEcsPermits>();
     cmds.insert_resource(ChunkManagerResource(Arc::new(chunk_manager)));
@@ -12,10 +12,7 @@ use bevy::{
     },
 };
 
-use crate::{
-    render::core::gpu_chunk::ChunkRenderData,
-    topo::world::{ChunkEntity, ChunkPos},
-};
+use crate::topo::world::{ChunkEntity, ChunkPos};
 
 use super::gpu_chunk::ChunkRenderDataStore;
 
@@ -41,7 +38,7 @@ impl<P: PhaseItem> RenderCommand<P> for DrawChunk {
             return RenderCommandResult::Failure;
         };
 
-        let Some(ChunkRenderData::Gpu(data)) = store.map.get(chunk_pos).map(|d| &d.data) else {
+        let Some(data) = store.map.get(chunk_pos).and_then(|d| d.data.gpu()) else {
             return RenderCommandResult::Failure;
         };
 
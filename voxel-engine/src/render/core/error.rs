@@ -0,0 +1,39 @@
+use bevy::ecs::entity::Entity;
+
+use super::RenderCore;
+
+#[derive(te::Error, Debug)]
+pub enum ChunkPipelineError {
+    #[error(
+        "Mesh for chunk entity {entity:?} has {index_count} indices, which isn't a whole number of triangles. Chunk meshes must be {:?} (RenderCore::CHUNK_TOPOLOGY)",
+        RenderCore::CHUNK_TOPOLOGY
+    )]
+    IncompleteTriangles {
+        entity: Option<Entity>,
+        index_count: u32,
+    },
+}
+
+#[derive(te::Error, Debug, Clone, PartialEq, Eq)]
+pub enum ChunkUploadError {
+    #[error("Chunk render data is missing quads or indices")]
+    MissingData,
+    #[error("Chunk {buffer} buffer is {size} bytes, but the device only allows {limit} bytes")]
+    BufferTooLarge {
+        buffer: &'static str,
+        size: u64,
+        limit: u64,
+    },
+    #[error("Ran out of GPU memory: {0}")]
+    OutOfMemory(String),
+}
+
+impl ChunkUploadError {
+    /// Whether uploading the same data again would fail the same way, so there's no point in retrying.
+    pub fn is_permanent(&self) -> bool {
+        match self {
+            Self::MissingData | Self::BufferTooLarge { .. } => true,
+            Self::OutOfMemory(_) => false,
+        }
+    }
+}
@@ -0,0 +1,166 @@
+use bevy::{
+    ecs::{
+        change_detection::{DetectChanges, DetectChangesMut},
+        query::Has,
+        system::{Query, Res, ResMut, Resource},
+    },
+    math::Vec4,
+    prelude::{Color, Deref, DerefMut},
+    render::{
+        extract_resource::ExtractResource,
+        render_resource::{ShaderType, UniformBuffer},
+        renderer::{RenderDevice, RenderQueue},
+    },
+};
+
+use crate::topo::{
+    controller::{ChunkObserver, PrimaryObserver},
+    world::Chunk,
+};
+
+/// Distance fog for chunks, so the world fades into the fog at the edge of the load radius instead of
+/// ending abruptly. The fog range follows the horizontal load radius of the primary observer (see
+/// [`PrimaryObserver`]), so only the color and falloff need to be configured.
+/// Disabled by default, the color should usually match the [`ClearColor`](bevy::render::camera::ClearColor).
+#[derive(Resource, ExtractResource, Clone, Debug, PartialEq)]
+pub struct VoxelFog {
+    pub enabled: bool,
+    /// The alpha of the color is how opaque the fog is at `end`.
+    pub color: Color,
+    /// How much of the load radius the fog thickens over, from 0 (no fog) to 1 (fog starts at the observer).
+    pub falloff: f32,
+    /// Distance from the camera in world space where the fog starts. Set from the observer's load radius.
+    pub start: f32,
+    /// Distance from the camera in world space where the fog is thickest. Set from the observer's load radius.
+    pub end: f32,
+}
+
+impl Default for VoxelFog {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            color: Color::WHITE,
+            falloff: 0.25,
+            start: 0.0,
+            end: 0.0,
+        }
+    }
+}
+
+impl VoxelFog {
+    pub const SHADER_DEF: &'static str = "VOXEL_FOG";
+
+    /// The horizontal load radius of the observer in world space.
+    pub fn load_radius(observer: &ChunkObserver) -> f32 {
+        observer.horizontal_range * Chunk::SIZE as f32
+    }
+
+    /// Fit the fog range to the load radius of the observer, so the fog is thickest where chunks stop loading.
+    pub fn fit_to_observer(&mut self, observer: &ChunkObserver) {
+        self.end = Self::load_radius(observer);
+        self.start = self.end * (1.0 - self.falloff.clamp(0.0, 1.0));
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.enabled && self.end > self.start
+    }
+}
+
+pub use self::shader_types::GpuVoxelFog;
+
+// unused `ShaderType` field checks, see `render::quad::shader_types`
+#[allow(dead_code)]
+mod shader_types {
+    use super::*;
+
+    /// The fog uniform in the registry bind group, see [`VoxelFog`].
+    #[derive(Copy, Clone, Debug, Default, PartialEq, ShaderType)]
+    pub struct GpuVoxelFog {
+        pub color: Vec4,
+        pub start: f32,
+        pub end: f32,
+    }
+}
+
+impl From<&VoxelFog> for GpuVoxelFog {
+    fn from(fog: &VoxelFog) -> Self {
+        Self {
+            color: Vec4::from_array(fog.color.as_linear_rgba_f32()),
+            start: fog.start,
+            end: fog.end,
+        }
+    }
+}
+
+#[derive(Resource, Default, Deref, DerefMut)]
+pub struct VoxelFogBuffer(UniformBuffer<GpuVoxelFog>);
+
+/// Keeps the fog range in sync with the load radius of the primary observer.
+/// If no observer is marked, an arbitrary observer is used instead.
+pub fn update_voxel_fog(
+    mut fog: ResMut<VoxelFog>,
+    observers: Query<(&ChunkObserver, Has<PrimaryObserver>)>,
+) {
+    let observer = observers
+        .iter()
+        .find(|(_, primary)| *primary)
+        .or_else(|| observers.iter().next());
+
+    let Some((observer, _)) = observer else {
+        return;
+    };
+
+    let mut fitted = fog.clone();
+    fitted.fit_to_observer(observer);
+    fog.set_if_neq(fitted);
+}
+
+pub fn prepare_voxel_fog(
+    fog: Res<VoxelFog>,
+    mut buffer: ResMut<VoxelFogBuffer>,
+    gpu: Res<RenderDevice>,
+    queue: Res<RenderQueue>,
+) {
+    if !fog.is_changed() && buffer.buffer().is_some() {
+        return;
+    }
+
+    buffer.set(GpuVoxelFog::from(fog.as_ref()));
+    buffer.write_buffer(&gpu, &queue);
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::*;
+
+    use super::*;
+
+    fn observer(horizontal_range: f32) -> ChunkObserver {
+        ChunkObserver {
+            horizontal_range,
+            view_distance_above: 2.0,
+            view_distance_below: 2.0,
+        }
+    }
+
+    #[test]
+    fn fog_range_matches_load_radius() {
+        let mut app = App::new();
+        app.init_resource::<VoxelFog>()
+            .add_systems(Update, update_voxel_fog);
+
+        app.world.spawn(observer(2.0));
+        app.world.spawn((observer(4.0), PrimaryObserver));
+        app.update();
+
+        let radius = 4.0 * Chunk::SIZE as f32;
+        let fog = app.world.resource::<VoxelFog>();
+        assert_eq!(radius, fog.end);
+        assert_eq!(radius * 0.75, fog.start);
+
+        let gpu = GpuVoxelFog::from(fog);
+        assert_eq!(fog.start, gpu.start);
+        assert_eq!(fog.end, gpu.end);
+        assert_eq!(Vec4::ONE, gpu.color);
+    }
+}
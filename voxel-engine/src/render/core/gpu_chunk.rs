@@ -1,8 +1,9 @@
-use std::mem;
+use std::{mem, time::Duration};
 
 use bevy::{
     ecs::{
         entity::Entity,
+        event::Event,
         query::{ROQueryItem, With},
         system::{
             lifetimeless::{Read, SRes},
@@ -10,16 +11,20 @@ use bevy::{
         },
         world::Mut,
     },
-    log::{debug, warn},
+    log::{debug, error, warn},
+    pbr::MeshPipelineKey,
     render::{
         render_phase::{PhaseItem, RenderCommand, RenderCommandResult, TrackedRenderPass},
         render_resource::{
-            BindGroup, BindGroupEntries, Buffer, BufferUsages, BufferVec, StorageBuffer,
-            UniformBuffer,
+            BindGroup, BindGroupEntries, Buffer, BufferUsages, BufferVec, ShaderType,
+            StorageBuffer, UniformBuffer, WgpuLimits,
         },
         renderer::{RenderDevice, RenderQueue},
+        view::VisibleEntities,
         Extract, MainWorld,
     },
+    time::Time,
+    utils::Instant,
 };
 use hashbrown::hash_map::Entry;
 use itertools::Itertools;
@@ -34,7 +39,10 @@ use crate::{
     util::ChunkMap,
 };
 
-use super::DefaultBindGroupLayouts;
+use super::{
+    error::ChunkUploadError, ChunkFadeSettings, ChunkUploadRetrySettings, DefaultBindGroupLayouts,
+    QuadAttributes, RenderCore,
+};
 
 pub fn extract_chunk_entities(
     mut cmds: Commands,
@@ -57,106 +65,241 @@ pub fn extract_chunk_mesh_data(
     mut main_world: ResMut<MainWorld>,
 ) {
     main_world.resource_scope(
-        |_world, mut extractable_meshes: Mut<ExtractableChunkMeshData>| {
-            let mut extracted = 0;
-
-            extractable_meshes
-                .active
-                .for_each_entry_mut(|pos, new_mesh| {
-                    // Skip unfulfilled and extracted chunks
-                    if matches!(
-                        new_mesh.data,
-                        ChunkMeshStatus::Unfulfilled | ChunkMeshStatus::Extracted
-                    ) {
-                        return;
-                    }
+        |world, mut extractable_meshes: Mut<ExtractableChunkMeshData>| {
+            let ready = extract_meshes(&mut render_meshes, &mut extractable_meshes);
+            if !ready.is_empty() {
+                world.send_event_batch(ready);
+            }
+
+            if !render_meshes.failed.is_empty() {
+                world.send_event_batch(mem::take(&mut render_meshes.failed));
+            }
+        },
+    );
+}
 
-                    let status = mem::replace(&mut new_mesh.data, ChunkMeshStatus::Extracted);
+/// Move new meshes into the render world. Chunks that were uploaded last frame are marked as ready first,
+/// and an event is returned for each of them.
+fn extract_meshes<G: Send + Sync + 'static>(
+    render_meshes: &mut ChunkRenderDataStore<G>,
+    extractable_meshes: &mut ExtractableChunkMeshData,
+) -> Vec<ChunkRenderReady> {
+    // The render graph has run since these were uploaded, so the GPU has the data by now
+    let ready = mark_uploaded_ready(render_meshes);
 
-                    match status {
-                        // If the new chunk has an empty mesh, remove it from rendering
-                        ChunkMeshStatus::Empty => {
-                            let Some(existing) = render_meshes.map.get(pos) else {
-                                return;
-                            };
+    let mut extracted = 0;
 
-                            if existing.generation > new_mesh.generation {
-                                return;
-                            }
+    extractable_meshes
+        .active
+        .for_each_entry_mut(|pos, new_mesh| {
+            // Skip unfulfilled and extracted chunks
+            if matches!(
+                new_mesh.data,
+                ChunkMeshStatus::Unfulfilled | ChunkMeshStatus::Extracted
+            ) {
+                return;
+            }
 
-                            render_meshes.map.remove(pos);
-                            new_mesh.data = ChunkMeshStatus::Extracted;
-                        }
-                        // Insert the chunk render data if it doesn't exist, and update it
-                        // if this is a newer version
-                        ChunkMeshStatus::Filled(data) => match render_meshes.map.entry(pos) {
-                            Entry::Occupied(mut entry) => {
-                                let tcrd = entry.get_mut();
-                                if tcrd.generation > new_mesh.generation {
-                                    return;
-                                }
-                                tcrd.generation = new_mesh.generation;
-                                tcrd.data = ChunkRenderData::Cpu(data);
-
-                                extracted += 1;
-                            }
-                            Entry::Vacant(entry) => {
-                                entry.insert(TimedChunkRenderData {
-                                    data: ChunkRenderData::Cpu(data),
-                                    generation: new_mesh.generation,
-                                });
-
-                                extracted += 1;
-                            }
-                        },
-                        _ => unreachable!(),
+            let status = mem::replace(&mut new_mesh.data, ChunkMeshStatus::Extracted);
+
+            match status {
+                // If the new chunk has an empty mesh, remove it from rendering
+                ChunkMeshStatus::Empty => {
+                    let Some(existing) = render_meshes.map.get(pos) else {
+                        return;
+                    };
+
+                    if existing.generation > new_mesh.generation {
+                        return;
                     }
-                });
 
-            let mut removed = 0;
+                    render_meshes.map.remove(pos);
+                    new_mesh.data = ChunkMeshStatus::Extracted;
+                }
+                // Insert the chunk render data if it doesn't exist, and update it
+                // if this is a newer version
+                ChunkMeshStatus::Filled(data) => match render_meshes.map.entry(pos) {
+                    Entry::Occupied(mut entry) => {
+                        let tcrd = entry.get_mut();
+                        if tcrd.generation > new_mesh.generation {
+                            return;
+                        }
+                        tcrd.generation = new_mesh.generation;
+                        tcrd.data = ChunkRenderData::Cpu(data);
+
+                        extracted += 1;
+                    }
+                    Entry::Vacant(entry) => {
+                        entry.insert(TimedChunkRenderData {
+                            data: ChunkRenderData::Cpu(data),
+                            generation: new_mesh.generation,
+                            ready_time: None,
+                        });
 
-            // Remove meshes from the render world
-            for &chunk_pos in &extractable_meshes.removed {
-                render_meshes.map.remove(chunk_pos);
-                removed += 1;
+                        extracted += 1;
+                    }
+                },
+                _ => unreachable!(),
             }
+        });
+
+    let mut removed = 0;
+
+    // Remove meshes from the render world
+    for &chunk_pos in &extractable_meshes.removed {
+        render_meshes.map.remove(chunk_pos);
+        removed += 1;
+    }
+
+    // Clear the removed mesh buffer
+    extractable_meshes.removed.clear();
+
+    if extracted > 0 {
+        debug!("Extracted {} chunk meshes to render world", extracted);
+    }
+
+    if removed > 0 {
+        debug!("Removed {} chunk meshes from render world", removed);
+    }
+
+    ready
+}
 
-            // Clear the removed mesh buffer
-            extractable_meshes.removed.clear();
+/// Move chunks in the [`ChunkUploadState::Uploading`] state to [`ChunkUploadState::Ready`].
+fn mark_uploaded_ready<G: Send + Sync + 'static>(
+    render_meshes: &mut ChunkRenderDataStore<G>,
+) -> Vec<ChunkRenderReady> {
+    let mut ready = Vec::new();
 
-            if extracted > 0 {
-                debug!("Extracted {} chunk meshes to render world", extracted);
+    render_meshes.map.for_each_entry_mut(|pos, timed_data| {
+        if !matches!(timed_data.data, ChunkRenderData::Uploading(_)) {
+            return;
+        }
+
+        let ChunkRenderData::Uploading(gpu_data) = timed_data.data.take() else {
+            unreachable!();
+        };
+
+        timed_data.data = ChunkRenderData::Gpu(gpu_data);
+        ready.push(ChunkRenderReady {
+            pos,
+            generation: timed_data.generation,
+        });
+    });
+
+    ready
+}
+
+/// Upload all chunks in the [`ChunkUploadState::Pending`] state with `upload`, moving them to
+/// [`ChunkUploadState::Uploading`] or [`ChunkUploadState::Failed`]. Failed chunks are retried once their
+/// backoff (see [`ChunkUploadRetrySettings`]) has passed at `now`, until they run out of attempts.
+/// Chunks that fail with a [permanent](ChunkUploadError::is_permanent) error aren't retried.
+///
+/// `time` is the current time in seconds, wrapped like `globals.time` in shaders. A chunk's
+/// [ready time](TimedChunkRenderData::ready_time) is set to it the first time the chunk is uploaded, and
+/// `upload` is called with the ready time. Returns the number of uploaded chunks.
+fn upload_chunks<G: Send + Sync + 'static, F>(
+    render_meshes: &mut ChunkRenderDataStore<G>,
+    settings: &ChunkUploadRetrySettings,
+    now: Instant,
+    time: f32,
+    mut upload: F,
+) -> usize
+where
+    F: FnMut(ChunkPos, &ChunkMeshData, f32) -> Result<G, ChunkUploadError>,
+{
+    let mut total = 0;
+    let mut failed = Vec::new();
+
+    render_meshes.map.for_each_entry_mut(|pos, timed_data| {
+        let previous_attempts = match &timed_data.data {
+            ChunkRenderData::Cpu(_) => 0,
+            ChunkRenderData::Failed(failed) if failed.should_retry(now) => failed.attempts,
+            _ => return,
+        };
+
+        let Some(data) = timed_data.data.cpu() else {
+            unreachable!();
+        };
+
+        let ready_time = timed_data.ready_time.unwrap_or(time);
+
+        match upload(pos, data, ready_time) {
+            Ok(gpu_data) => {
+                if previous_attempts > 0 {
+                    debug!("Uploaded render data for chunk at position {pos} after {previous_attempts} failed attempts");
+                }
+
+                timed_data.ready_time = Some(ready_time);
+                timed_data.data = ChunkRenderData::Uploading(gpu_data);
+                total += 1;
             }
+            Err(error) => {
+                let attempts = previous_attempts + 1;
+                let retry_at = (!error.is_permanent() && attempts < settings.max_attempts)
+                    .then(|| now + settings.backoff(attempts));
+
+                if retry_at.is_some() {
+                    warn!("Failed to upload render data for chunk at position {pos} (attempt {attempts}), retrying: {error}");
+                } else {
+                    error!("Failed to upload render data for chunk at position {pos}, giving up after {attempts} attempts: {error}");
+                    failed.push(ChunkUploadFailed {
+                        pos,
+                        generation: timed_data.generation,
+                        attempts,
+                        error,
+                    });
+                }
 
-            if removed > 0 {
-                debug!("Removed {} chunk meshes from render world", removed);
+                let data = match timed_data.data.take() {
+                    ChunkRenderData::Cpu(data) => data,
+                    ChunkRenderData::Failed(failed) => failed.data,
+                    _ => unreachable!(),
+                };
+
+                timed_data.data = ChunkRenderData::Failed(FailedChunkUpload {
+                    data,
+                    attempts,
+                    retry_at,
+                });
             }
-        },
-    );
+        }
+    });
+
+    render_meshes.failed.extend(failed);
+
+    total
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn prepare_chunk_mesh_data(
     mut chunk_data_store: ResMut<ChunkRenderDataStore>,
     default_layouts: Res<DefaultBindGroupLayouts>,
+    fade_settings: Res<ChunkFadeSettings>,
+    retry_settings: Res<ChunkUploadRetrySettings>,
+    quad_attributes: Res<QuadAttributes>,
+    time: Res<Time>,
     gpu: Res<RenderDevice>,
     queue: Res<RenderQueue>,
 ) {
     let gpu = gpu.as_ref();
     let queue = queue.as_ref();
 
-    let mut total = 0;
-
-    chunk_data_store.map.for_each_entry_mut(|pos, timed_data| {
-        if matches!(timed_data.data, ChunkRenderData::Cpu(_)) {
-            let ChunkRenderData::Cpu(ref data) = timed_data.data else {
-                unreachable!();
-            };
-
+    let now = Instant::now();
+    let total = upload_chunks(
+        &mut chunk_data_store,
+        &retry_settings,
+        now,
+        // the same clock as the `globals.time` the shaders get
+        time.elapsed_seconds_wrapped(),
+        |pos, data, ready_time| {
             if data.quad_buffer.is_empty() || data.index_buffer.is_empty() {
-                warn!("Tried to prepare render data for chunk at position {pos}, but it was missing data!");
-                return;
+                return Err(ChunkUploadError::MissingData);
             }
 
+            let attribute_data = quad_attributes.encode(&data.quad_buffer);
+            check_buffer_sizes(data, &attribute_data, &gpu.limits())?;
+
             let quads = {
                 let mut buffer = StorageBuffer::from(data.quad_buffer.clone());
                 buffer.set_label(Some("chunk_quad_buffer"));
@@ -181,50 +324,420 @@ pub fn prepare_chunk_mesh_data(
                 buffer
             };
 
+            let fade = {
+                let mut buffer = UniformBuffer::from(GpuChunkFade::new(
+                    ready_time,
+                    time.wrap_period(),
+                    &fade_settings,
+                ));
+                buffer.set_label(Some("chunk_fade_buffer"));
+                buffer.write_buffer(gpu, queue);
+                buffer
+            };
+
+            let attributes = {
+                let mut buffer = StorageBuffer::from(attribute_data);
+                buffer.set_label(Some("chunk_quad_attribute_buffer"));
+                buffer.write_buffer(gpu, queue);
+                buffer
+            };
+
+            // all the buffers were written above, so they exist
+            let position = position.buffer().unwrap().clone();
+            let quads = quads.buffer().unwrap().clone();
+            let fade = fade.buffer().unwrap().clone();
+            let attributes = attributes.buffer().unwrap().clone();
+            let indices = indices.buffer().unwrap().clone();
+
             let bind_group = gpu.create_bind_group(
                 Some("chunk_bind_group"),
                 &default_layouts.chunk_bg_layout,
                 &BindGroupEntries::sequential((
-                    position.binding().unwrap(),
-                    quads.binding().unwrap(),
+                    position.as_entire_binding(),
+                    quads.as_entire_binding(),
+                    fade.as_entire_binding(),
+                    attributes.as_entire_binding(),
                 )),
             );
 
-            timed_data.data = ChunkRenderData::Gpu(GpuChunkMeshData {
+            Ok(GpuChunkMeshData {
                 bind_group,
                 index_count,
-                position: position.buffer().unwrap().clone(),
-                index_buffer: indices.buffer().unwrap().clone(),
-                quad_buffer: quads.buffer().unwrap().clone(),
+                position,
+                fade,
+                index_buffer: indices,
+                quad_buffer: quads,
+            })
+        },
+    );
+
+    if total > 0 {
+        debug!("Uploaded {total} chunks to the GPU");
+    }
+}
+
+/// Check that the buffers for a chunk fit within the device limits before creating them, since the device
+/// doesn't report failed allocations to us.
+fn check_buffer_sizes(
+    data: &ChunkMeshData,
+    attributes: &[u32],
+    limits: &WgpuLimits,
+) -> Result<(), ChunkUploadError> {
+    let buffers = [
+        (
+            "quad",
+            mem::size_of_val(data.quad_buffer.as_slice()),
+            limits.max_storage_buffer_binding_size as u64,
+        ),
+        (
+            "quad attribute",
+            mem::size_of_val(attributes),
+            limits.max_storage_buffer_binding_size as u64,
+        ),
+        (
+            "index",
+            mem::size_of_val(data.index_buffer.as_slice()),
+            limits.max_buffer_size,
+        ),
+    ];
+
+    for (buffer, size, limit) in buffers {
+        let size = size as u64;
+        if size > limit {
+            return Err(ChunkUploadError::BufferTooLarge {
+                buffer,
+                size,
+                limit,
             });
+        }
+    }
+
+    Ok(())
+}
+
+/// Collect the chunk entities that have their render data on the GPU, so that the queue systems
+/// only have to look at chunks that can actually be drawn.
+pub fn prepare_gpu_chunk_entities(
+    mut gpu_chunks: ResMut<GpuChunkEntities>,
+    mut skips: ResMut<ChunkQueueSkips>,
+    chunk_data_store: Res<ChunkRenderDataStore>,
+    chunks: Query<(Entity, &ChunkPos), With<ChunkEntity>>,
+) {
+    gpu_chunks.entities.clear();
+
+    let mut skipped = 0;
 
-            total += 1;
+    for (entity, &chunk_pos) in &chunks {
+        match chunk_data_store.state(chunk_pos) {
+            Some(ChunkUploadState::Ready) => {
+                let Some(gpu) = chunk_data_store
+                    .map
+                    .get(chunk_pos)
+                    .and_then(|data| data.data.gpu())
+                else {
+                    continue;
+                };
+
+                if let Some(mesh_key) =
+                    RenderCore::queueable_chunk_mesh_key(gpu.index_count, Some(entity))
+                {
+                    gpu_chunks.entities.insert(
+                        entity,
+                        GpuChunk {
+                            pos: chunk_pos,
+                            mesh_key,
+                        },
+                    );
+                }
+            }
+            // The chunk has render data, but it hasn't made it to the GPU yet, so it won't be drawn
+            Some(_) => skipped += 1,
+            None => (),
         }
-    });
+    }
 
-    if total > 0 {
-        debug!("Uploaded {total} chunks to the GPU");
+    if skips.record(skipped, Instant::now()) {
+        debug!(
+            "Skipped queuing {skipped} chunks with render data that was not uploaded to the GPU ({} skipped in total)",
+            skips.total
+        );
+    }
+}
+
+/// Counts chunks that had render data but couldn't be queued for rendering because the data was
+/// missing from the GPU. A chunk that's persistently skipped will appear as a missing chunk in the world.
+#[derive(Resource, Default, Debug)]
+pub struct ChunkQueueSkips {
+    /// Chunks skipped in the most recent frame.
+    pub last_frame: u32,
+    /// Chunks skipped since the render app started.
+    pub total: u64,
+    last_logged: Option<Instant>,
+}
+
+impl ChunkQueueSkips {
+    /// Minimum time between logging skipped chunks, so we don't flood the log every frame.
+    pub const LOG_INTERVAL: Duration = Duration::from_secs(1);
+
+    /// Record the number of chunks skipped this frame. Returns true if the skips should be logged.
+    pub fn record(&mut self, skipped: u32, now: Instant) -> bool {
+        self.last_frame = skipped;
+        self.total += skipped as u64;
+
+        if skipped == 0 {
+            return false;
+        }
+
+        let should_log = self
+            .last_logged
+            .is_none_or(|last| now.duration_since(last) >= Self::LOG_INTERVAL);
+
+        if should_log {
+            self.last_logged = Some(now);
+        }
+
+        should_log
     }
 }
 
+/// A chunk entity in the render world with render data ready on the GPU.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct GpuChunk {
+    pub pos: ChunkPos,
+    /// The pipeline key for the mesh of the chunk, see [`RenderCore::chunk_mesh_key`].
+    pub mesh_key: MeshPipelineKey,
+}
+
+/// Chunk entities in the render world with render data ready on the GPU.
 #[derive(Resource, Default)]
-pub struct ChunkRenderDataStore {
-    pub map: ChunkMap<TimedChunkRenderData>,
+pub struct GpuChunkEntities {
+    pub entities: hb::HashMap<Entity, GpuChunk>,
+}
+
+/// The chunks with render data on the GPU that are visible from each view, in the order of the visible entities
+/// of the view. They're collected once per frame, so the queue systems of the view iterate the visible chunks
+/// directly instead of each looking through all the entities the view can see.
+#[derive(Resource, Default)]
+pub struct VisibleChunks {
+    views: hb::HashMap<Entity, Vec<(Entity, GpuChunk)>>,
+}
+
+impl VisibleChunks {
+    /// The chunks visible from `view`, empty if the view wasn't collected.
+    pub fn get(&self, view: Entity) -> &[(Entity, GpuChunk)] {
+        self.views.get(&view).map_or(&[], Vec::as_slice)
+    }
+}
+
+/// Collect the chunks visible from every view, see [`VisibleChunks`].
+pub fn prepare_visible_chunks(
+    mut visible_chunks: ResMut<VisibleChunks>,
+    gpu_chunks: Res<GpuChunkEntities>,
+    views: Query<(Entity, &VisibleEntities)>,
+) {
+    visible_chunks.views.clear();
+
+    for (view, visible) in &views {
+        let mut chunks = Vec::new();
+        gpu_chunks.for_each_visible(&visible.entities, |entity, chunk| {
+            chunks.push((entity, chunk))
+        });
+        visible_chunks.views.insert(view, chunks);
+    }
+}
+
+impl GpuChunkEntities {
+    /// Call `f` for every entity in `visible` that is a chunk with render data on the GPU.
+    /// Does nothing if there are no chunks ready for rendering, regardless of how many entities are visible.
+    pub fn for_each_visible<F>(&self, visible: &[Entity], mut f: F)
+    where
+        F: FnMut(Entity, GpuChunk),
+    {
+        if self.entities.is_empty() {
+            return;
+        }
+
+        for entity in visible {
+            if let Some(&chunk) = self.entities.get(entity) {
+                f(*entity, chunk);
+            }
+        }
+    }
+}
+
+/// Render data for all chunks in the render world. Generic over the GPU data so the upload lifecycle
+/// can be driven without a render device.
+#[derive(Resource)]
+pub struct ChunkRenderDataStore<G: Send + Sync + 'static = GpuChunkMeshData> {
+    pub map: ChunkMap<TimedChunkRenderData<G>>,
+    /// Chunks that ran out of upload attempts since the last extract, sent to the main world during extraction.
+    pub failed: Vec<ChunkUploadFailed>,
+}
+
+impl<G: Send + Sync + 'static> Default for ChunkRenderDataStore<G> {
+    fn default() -> Self {
+        Self {
+            map: ChunkMap::default(),
+            failed: Vec::new(),
+        }
+    }
+}
+
+impl<G: Send + Sync + 'static> ChunkRenderDataStore<G> {
+    /// The upload state of the chunk at `pos`, or `None` if the chunk has no render data.
+    pub fn state(&self, pos: ChunkPos) -> Option<ChunkUploadState> {
+        self.map.get(pos).map(|data| data.data.state())
+    }
+}
+
+/// Where a chunk's render data is in the process of being uploaded to the GPU.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ChunkUploadState {
+    /// The render data is in CPU memory and will be uploaded during the next prepare phase.
+    Pending,
+    /// The buffers were written this frame, and will be submitted to the GPU along with this frame's commands.
+    /// The chunk can already be drawn.
+    Uploading,
+    /// The render data is on the GPU.
+    Ready,
+    /// Uploading the render data failed. The upload is retried a few times (see [`ChunkUploadRetrySettings`]),
+    /// and if none of the attempts succeed the chunk won't be drawn until it's remeshed.
+    Failed,
 }
 
 #[derive(Clone)]
-pub enum ChunkRenderData {
+pub enum ChunkRenderData<G = GpuChunkMeshData> {
     /// Raw chunk data in CPU memory, should be uploaded to GPU memory
     Cpu(ChunkMeshData),
+    /// Render data that was uploaded this frame
+    Uploading(G),
     /// Handle to a bind group with the render data for this chunk
-    Gpu(GpuChunkMeshData),
+    Gpu(G),
+    /// Uploading failed, holds the CPU data that couldn't be uploaded
+    Failed(FailedChunkUpload),
+}
+
+#[derive(Clone)]
+pub struct FailedChunkUpload {
+    pub data: ChunkMeshData,
+    /// How many times uploading this data has failed.
+    pub attempts: u32,
+    /// When to try uploading again, `None` if we ran out of attempts.
+    pub retry_at: Option<Instant>,
+}
+
+impl FailedChunkUpload {
+    pub fn should_retry(&self, now: Instant) -> bool {
+        self.retry_at.is_some_and(|retry_at| retry_at <= now)
+    }
+}
+
+impl<G> ChunkRenderData<G> {
+    pub fn state(&self) -> ChunkUploadState {
+        match self {
+            Self::Cpu(_) => ChunkUploadState::Pending,
+            Self::Uploading(_) => ChunkUploadState::Uploading,
+            Self::Gpu(_) => ChunkUploadState::Ready,
+            Self::Failed(_) => ChunkUploadState::Failed,
+        }
+    }
+
+    /// The render data in CPU memory, if the chunk hasn't been uploaded yet or failed to upload.
+    pub fn cpu(&self) -> Option<&ChunkMeshData> {
+        match self {
+            Self::Cpu(data) => Some(data),
+            Self::Failed(failed) => Some(&failed.data),
+            _ => None,
+        }
+    }
+
+    /// Take the data out of `self`, leaving empty CPU data behind.
+    fn take(&mut self) -> Self {
+        let empty = Self::Cpu(ChunkMeshData {
+            index_buffer: Vec::new(),
+            quad_buffer: Vec::new(),
+        });
+
+        mem::replace(self, empty)
+    }
+
+    /// The GPU render data, if the chunk can be drawn.
+    pub fn gpu(&self) -> Option<&G> {
+        match self {
+            Self::Uploading(data) | Self::Gpu(data) => Some(data),
+            _ => None,
+        }
+    }
+}
+
+/// Sent in the main world when a chunk's render data has been uploaded to the GPU, i.e. when the chunk
+/// reaches [`ChunkUploadState::Ready`]. Sent once for every uploaded mesh, so remeshing a chunk sends this again
+/// with a newer generation.
+#[derive(Copy, Clone, Event, Debug, PartialEq, Eq)]
+pub struct ChunkRenderReady {
+    pub pos: ChunkPos,
+    pub generation: u64,
 }
 
-pub struct TimedChunkRenderData {
-    pub data: ChunkRenderData,
+/// Sent in the main world when uploading a chunk's render data failed too many times, see
+/// [`ChunkUploadRetrySettings`]. The chunk won't be drawn until it's remeshed.
+#[derive(Clone, Event, Debug, PartialEq, Eq)]
+pub struct ChunkUploadFailed {
+    pub pos: ChunkPos,
     pub generation: u64,
+    pub attempts: u32,
+    /// The error from the last attempt.
+    pub error: ChunkUploadError,
 }
 
+pub struct TimedChunkRenderData<G = GpuChunkMeshData> {
+    pub data: ChunkRenderData<G>,
+    pub generation: u64,
+    /// The time (in seconds, wrapped like `globals.time` in shaders) when this chunk was first uploaded, `None`
+    /// until then. Kept when the chunk is remeshed so chunks don't fade in again every time they change.
+    pub ready_time: Option<f32>,
+}
+
+pub use self::shader_types::GpuChunkFade;
+
+// unused `ShaderType` field checks, see `render::quad::shader_types`
+#[allow(dead_code)]
+mod shader_types {
+    use super::*;
+
+    /// The per-chunk uniform used to fade chunks in and out, see [`ChunkFadeSettings`].
+    #[derive(Copy, Clone, Debug, PartialEq, ShaderType)]
+    pub struct GpuChunkFade {
+        pub ready_time: f32,
+        /// The period `globals.time` wraps around at, so shaders can tell how long ago `ready_time` was
+        /// after the time has wrapped.
+        pub wrap_period: f32,
+        /// Fade in duration in seconds, 0 if chunks shouldn't fade in.
+        pub fade_in: f32,
+        pub far_start: f32,
+        pub far_end: f32,
+    }
+}
+
+impl GpuChunkFade {
+    pub fn new(ready_time: f32, wrap_period: Duration, settings: &ChunkFadeSettings) -> Self {
+        let (far_start, far_end) = if settings.fades_out() {
+            (settings.far_start, settings.far_end)
+        } else {
+            (0.0, 0.0)
+        };
+
+        Self {
+            ready_time,
+            wrap_period: wrap_period.as_secs_f32(),
+            fade_in: settings.fade_in.as_secs_f32(),
+            far_start,
+            far_end,
+        }
+    }
+}
+
+#[allow(dead_code)]
 #[derive(Clone)]
 pub struct CpuChunkRenderData {
     pub quads: Vec<GpuQuad>,
@@ -236,7 +749,12 @@ pub struct GpuChunkMeshData {
     pub bind_group: BindGroup,
     pub index_buffer: Buffer,
     pub index_count: u32,
+    // the buffers bound in `bind_group`, only kept so they can be inspected
+    #[allow(dead_code)]
     pub position: Buffer,
+    #[allow(dead_code)]
+    pub fade: Buffer,
+    #[allow(dead_code)]
     pub quad_buffer: Buffer,
 }
 
@@ -257,7 +775,7 @@ impl<P: PhaseItem, const I: usize> RenderCommand<P> for SetChunkBindGroup<I> {
         let store = param.into_inner();
 
         if let Some((&chunk_pos, _)) = entity {
-            if let Some(ChunkRenderData::Gpu(data)) = store.map.get(chunk_pos).map(|d| &d.data) {
+            if let Some(data) = store.map.get(chunk_pos).and_then(|d| d.data.gpu()) {
                 pass.set_bind_group(I, &data.bind_group, &[]);
                 RenderCommandResult::Success
             } else {
@@ -268,3 +786,416 @@ impl<P: PhaseItem, const I: usize> RenderCommand<P> for SetChunkBindGroup<I> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use bevy::{ecs::world::World, math::Vec2, utils::default};
+
+    use crate::render::{meshing::controller::TimedChunkMeshData, quad::GpuQuadBitfields};
+
+    use super::*;
+
+    #[test]
+    fn iterate_only_visible_gpu_chunks() {
+        let mut world = World::new();
+        world.init_resource::<VisibleChunks>();
+
+        let chunk = |x| GpuChunk {
+            pos: ChunkPos::new(x, 0, 0),
+            mesh_key: MeshPipelineKey::NONE,
+        };
+
+        let mut gpu_chunks = GpuChunkEntities::default();
+        let chunk_entities = (0..4).map(Entity::from_raw).collect_vec();
+        for (i, &entity) in chunk_entities.iter().enumerate() {
+            gpu_chunks.entities.insert(entity, chunk(i as i32));
+        }
+        world.insert_resource(gpu_chunks);
+
+        // lots of visible entities that aren't chunks, and chunks that aren't visible from every view
+        let mut entities = (100..10_100).map(Entity::from_raw).collect_vec();
+        entities.extend([chunk_entities[2], chunk_entities[0], chunk_entities[3]]);
+        let view = world.spawn(VisibleEntities { entities }).id();
+
+        let entities = vec![Entity::from_raw(100), chunk_entities[1]];
+        let other_view = world.spawn(VisibleEntities { entities }).id();
+
+        let system = world.register_system(prepare_visible_chunks);
+        world.run_system(system).unwrap();
+
+        let visible_chunks = world.resource::<VisibleChunks>();
+        assert_eq!(
+            &[
+                (chunk_entities[2], chunk(2)),
+                (chunk_entities[0], chunk(0)),
+                (chunk_entities[3], chunk(3)),
+            ],
+            visible_chunks.get(view)
+        );
+        assert_eq!(
+            &[(chunk_entities[1], chunk(1))],
+            visible_chunks.get(other_view)
+        );
+        assert!(visible_chunks.get(Entity::from_raw(5)).is_empty());
+
+        // nothing is visited if no chunks are on the GPU, regardless of how many entities are visible
+        world.insert_resource(GpuChunkEntities::default());
+        world.run_system(system).unwrap();
+        assert!(world.resource::<VisibleChunks>().get(view).is_empty());
+    }
+
+    #[test]
+    fn count_chunks_missing_gpu_data() {
+        let mut world = World::new();
+        world.init_resource::<GpuChunkEntities>();
+        world.init_resource::<ChunkQueueSkips>();
+
+        let mut store = ChunkRenderDataStore::<GpuChunkMeshData>::default();
+        store.map.set(
+            ChunkPos::new(0, 0, 0),
+            TimedChunkRenderData {
+                data: ChunkRenderData::Cpu(ChunkMeshData {
+                    index_buffer: vec![0, 1, 2],
+                    quad_buffer: vec![GpuQuad {
+                        texture_id: 0,
+                        bitfields: GpuQuadBitfields::new(),
+                        min: Vec2::ZERO,
+                        max: Vec2::ONE,
+                        magnitude: 0,
+                        light: 0,
+                    }],
+                }),
+                generation: 0,
+                ready_time: None,
+            },
+        );
+        world.insert_resource(store);
+
+        world.spawn((ChunkPos::new(0, 0, 0), ChunkEntity));
+        // chunk without any render data at all shouldn't count as skipped
+        world.spawn((ChunkPos::new(1, 0, 0), ChunkEntity));
+
+        let system = world.register_system(prepare_gpu_chunk_entities);
+        world.run_system(system).unwrap();
+        world.run_system(system).unwrap();
+
+        let skips = world.resource::<ChunkQueueSkips>();
+        assert_eq!(1, skips.last_frame);
+        assert_eq!(2, skips.total);
+        assert!(world.resource::<GpuChunkEntities>().entities.is_empty());
+    }
+
+    #[test]
+    fn chunk_ready_time_set_on_first_upload() {
+        fn mesh(generation: u64) -> TimedChunkMeshData {
+            TimedChunkMeshData {
+                generation,
+                data: ChunkMeshStatus::Filled(ChunkMeshData {
+                    index_buffer: vec![0, 1, 2],
+                    quad_buffer: vec![GpuQuad {
+                        texture_id: 0,
+                        bitfields: GpuQuadBitfields::new(),
+                        min: Vec2::ZERO,
+                        max: Vec2::ONE,
+                        magnitude: 0,
+                        light: 0,
+                    }],
+                }),
+            }
+        }
+
+        let pos = ChunkPos::new(0, 0, 0);
+        let settings = ChunkUploadRetrySettings::default();
+        let now = Instant::now();
+
+        let mut extractable = ExtractableChunkMeshData::default();
+        let mut store = ChunkRenderDataStore::<f32>::default();
+
+        extractable.active.set(pos, mesh(0));
+        extract_meshes(&mut store, &mut extractable);
+        assert_eq!(None, store.map.get(pos).unwrap().ready_time);
+
+        // the chunk only starts fading in once it can be drawn
+        upload_chunks(&mut store, &settings, now, 3.0, |_, _, ready_time| {
+            Ok(ready_time)
+        });
+        let chunk = store.map.get(pos).unwrap();
+        assert_eq!(Some(3.0), chunk.ready_time);
+        assert_eq!(Some(&3.0), chunk.data.gpu());
+
+        // remeshing the chunk later doesn't make it fade in again
+        extractable.active.set(pos, mesh(1));
+        extract_meshes(&mut store, &mut extractable);
+        upload_chunks(&mut store, &settings, now, 8.0, |_, _, ready_time| {
+            Ok(ready_time)
+        });
+
+        let chunk = store.map.get(pos).unwrap();
+        assert_eq!(1, chunk.generation);
+        assert_eq!(Some(3.0), chunk.ready_time);
+        assert_eq!(Some(&3.0), chunk.data.gpu());
+
+        let fade_settings = ChunkFadeSettings::default();
+        let fade = GpuChunkFade::new(3.0, Duration::from_secs(3600), &fade_settings);
+        assert_eq!(3.0, fade.ready_time);
+        assert_eq!(3600.0, fade.wrap_period);
+        assert_eq!(fade_settings.fade_in.as_secs_f32(), fade.fade_in);
+    }
+
+    #[test]
+    fn chunk_becomes_ready_once() {
+        let pos = ChunkPos::new(0, 0, 0);
+
+        let mut extractable = ExtractableChunkMeshData::default();
+        let mut store = ChunkRenderDataStore::<()>::default();
+
+        extractable.active.set(
+            pos,
+            TimedChunkMeshData {
+                generation: 0,
+                data: ChunkMeshStatus::Filled(ChunkMeshData {
+                    index_buffer: vec![0, 1, 2],
+                    quad_buffer: vec![GpuQuad {
+                        texture_id: 0,
+                        bitfields: GpuQuadBitfields::new(),
+                        min: Vec2::ZERO,
+                        max: Vec2::ONE,
+                        magnitude: 0,
+                        light: 0,
+                    }],
+                }),
+            },
+        );
+
+        assert!(extract_meshes(&mut store, &mut extractable).is_empty());
+        assert_eq!(Some(ChunkUploadState::Pending), store.state(pos));
+
+        let settings = ChunkUploadRetrySettings::default();
+        let now = Instant::now();
+
+        assert_eq!(
+            1,
+            upload_chunks(&mut store, &settings, now, 0.0, |_, _, _| Ok(()))
+        );
+        assert_eq!(Some(ChunkUploadState::Uploading), store.state(pos));
+
+        let ready = extract_meshes(&mut store, &mut extractable);
+        assert_eq!(vec![ChunkRenderReady { pos, generation: 0 }], ready);
+        assert_eq!(Some(ChunkUploadState::Ready), store.state(pos));
+
+        // nothing else happens to the chunk, so it isn't uploaded or reported again
+        assert_eq!(
+            0,
+            upload_chunks(&mut store, &settings, now, 0.0, |_, _, _| Ok(()))
+        );
+        assert!(extract_meshes(&mut store, &mut extractable).is_empty());
+        assert_eq!(Some(ChunkUploadState::Ready), store.state(pos));
+    }
+
+    fn pending_chunk() -> TimedChunkRenderData<()> {
+        TimedChunkRenderData {
+            data: ChunkRenderData::Cpu(ChunkMeshData {
+                index_buffer: vec![0, 1, 2],
+                quad_buffer: vec![],
+            }),
+            generation: 0,
+            ready_time: None,
+        }
+    }
+
+    #[test]
+    fn retry_failed_upload() {
+        let pos = ChunkPos::new(0, 0, 0);
+        let settings = ChunkUploadRetrySettings::default();
+        let now = Instant::now();
+
+        let mut store = ChunkRenderDataStore::<()>::default();
+        store.map.set(pos, pending_chunk());
+
+        // a device that fails the first upload and then works fine
+        let mut calls = 0;
+        let mut upload = |_: ChunkPos, _: &ChunkMeshData, _: f32| {
+            calls += 1;
+            if calls == 1 {
+                Err(ChunkUploadError::OutOfMemory("out of memory".to_string()))
+            } else {
+                Ok(())
+            }
+        };
+
+        assert_eq!(
+            0,
+            upload_chunks(&mut store, &settings, now, 0.0, &mut upload)
+        );
+        assert_eq!(Some(ChunkUploadState::Failed), store.state(pos));
+
+        // not retried before the backoff has passed
+        assert_eq!(
+            0,
+            upload_chunks(&mut store, &settings, now, 0.0, &mut upload)
+        );
+
+        let later = now + settings.backoff(1);
+        assert_eq!(
+            1,
+            upload_chunks(&mut store, &settings, later, 0.0, &mut upload)
+        );
+        assert_eq!(Some(ChunkUploadState::Uploading), store.state(pos));
+
+        let mut extractable = ExtractableChunkMeshData::default();
+        let ready = extract_meshes(&mut store, &mut extractable);
+        assert_eq!(vec![ChunkRenderReady { pos, generation: 0 }], ready);
+        assert_eq!(Some(ChunkUploadState::Ready), store.state(pos));
+
+        assert_eq!(2, calls);
+        assert!(store.failed.is_empty());
+    }
+
+    #[test]
+    fn give_up_after_max_attempts() {
+        let pos = ChunkPos::new(0, 0, 0);
+        let settings = ChunkUploadRetrySettings {
+            max_attempts: 3,
+            ..default()
+        };
+
+        let mut store = ChunkRenderDataStore::<()>::default();
+        store.map.set(pos, pending_chunk());
+
+        let mut now = Instant::now();
+        let mut calls = 0;
+        for _ in 0..10 {
+            upload_chunks(&mut store, &settings, now, 0.0, |_, _, _| {
+                calls += 1;
+                Err(ChunkUploadError::OutOfMemory("out of memory".to_string()))
+            });
+            now += settings.max_backoff;
+        }
+
+        assert_eq!(3, calls);
+        assert_eq!(Some(ChunkUploadState::Failed), store.state(pos));
+        assert_eq!(
+            vec![ChunkUploadFailed {
+                pos,
+                generation: 0,
+                attempts: 3,
+                error: ChunkUploadError::OutOfMemory("out of memory".to_string()),
+            }],
+            store.failed
+        );
+    }
+
+    #[test]
+    fn oversized_buffers_are_rejected() {
+        let data = ChunkMeshData {
+            index_buffer: vec![0; 6],
+            quad_buffer: vec![
+                GpuQuad {
+                    texture_id: 0,
+                    bitfields: GpuQuadBitfields::new(),
+                    min: Vec2::ZERO,
+                    max: Vec2::ONE,
+                    magnitude: 0,
+                    light: 0,
+                };
+                2
+            ],
+        };
+        let attributes = vec![0u32; 8];
+        let limits = WgpuLimits::default();
+
+        assert_eq!(Ok(()), check_buffer_sizes(&data, &attributes, &limits));
+
+        let small_storage = WgpuLimits {
+            max_storage_buffer_binding_size: 48,
+            ..limits.clone()
+        };
+        let error = check_buffer_sizes(&data, &attributes, &small_storage).unwrap_err();
+        assert_eq!(
+            ChunkUploadError::BufferTooLarge {
+                buffer: "quad",
+                size: 64,
+                limit: 48,
+            },
+            error
+        );
+        assert!(error.is_permanent());
+
+        let small_buffers = WgpuLimits {
+            max_buffer_size: 8,
+            ..limits
+        };
+        assert_eq!(
+            Err(ChunkUploadError::BufferTooLarge {
+                buffer: "index",
+                size: 24,
+                limit: 8,
+            }),
+            check_buffer_sizes(&data, &attributes, &small_buffers)
+        );
+    }
+
+    #[test]
+    fn failed_upload_is_not_ready() {
+        let pos = ChunkPos::new(0, 0, 0);
+        let settings = ChunkUploadRetrySettings::default();
+        let now = Instant::now();
+
+        let mut store = ChunkRenderDataStore::<()>::default();
+        store.map.set(pos, pending_chunk());
+
+        let mut calls = 0;
+        let mut upload = |_: ChunkPos, _: &ChunkMeshData, _: f32| {
+            calls += 1;
+            Err::<(), _>(ChunkUploadError::MissingData)
+        };
+
+        assert_eq!(
+            0,
+            upload_chunks(&mut store, &settings, now, 0.0, &mut upload)
+        );
+        assert_eq!(Some(ChunkUploadState::Failed), store.state(pos));
+
+        // missing data won't show up by trying again, so the chunk fails right away
+        let later = now + settings.max_backoff;
+        assert_eq!(
+            0,
+            upload_chunks(&mut store, &settings, later, 0.0, &mut upload)
+        );
+        assert_eq!(1, calls);
+        assert_eq!(
+            vec![ChunkUploadFailed {
+                pos,
+                generation: 0,
+                attempts: 1,
+                error: ChunkUploadError::MissingData,
+            }],
+            store.failed
+        );
+
+        let mut extractable = ExtractableChunkMeshData::default();
+        assert!(extract_meshes(&mut store, &mut extractable).is_empty());
+        assert_eq!(Some(ChunkUploadState::Failed), store.state(pos));
+    }
+
+    #[test]
+    fn backoff_is_capped() {
+        let settings = ChunkUploadRetrySettings::default();
+
+        assert_eq!(settings.backoff, settings.backoff(1));
+        assert_eq!(settings.backoff * 2, settings.backoff(2));
+        assert_eq!(settings.max_backoff, settings.backoff(100));
+    }
+
+    #[test]
+    fn rate_limit_skip_logging() {
+        let mut skips = ChunkQueueSkips::default();
+        let now = Instant::now();
+
+        assert!(!skips.record(0, now));
+        assert!(skips.record(3, now));
+        assert!(!skips.record(3, now + Duration::from_millis(10)));
+        assert!(skips.record(1, now + ChunkQueueSkips::LOG_INTERVAL));
+        assert_eq!(7, skips.total);
+    }
+}
@@ -20,7 +20,7 @@ use crate::data::{
     registries::texture::TexregFaces, systems::ArrayTextureHandles, texture::GpuFaceTexture,
 };
 
-use super::DefaultBindGroupLayouts;
+use super::{fog::VoxelFogBuffer, DefaultBindGroupLayouts};
 
 #[derive(Clone, Resource)]
 pub struct RegistryBindGroup {
@@ -41,12 +41,14 @@ pub fn extract_texreg_faces(mut cmds: Commands, texreg_faces: Extract<Option<Res
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn prepare_gpu_registry_data(
     mut cmds: Commands,
     extracted_faces: Option<Res<ExtractedTexregFaces>>,
     gpu: Res<RenderDevice>,
     queue: Res<RenderQueue>,
     layouts: Res<DefaultBindGroupLayouts>,
+    fog: Res<VoxelFogBuffer>,
     array_textures: Res<RenderAssets<MippedArrayTexture>>,
     handles: ArrayTextureHandles,
 ) {
@@ -59,6 +61,10 @@ pub fn prepare_gpu_registry_data(
         return;
     };
 
+    let Some(fog) = fog.binding() else {
+        return;
+    };
+
     let mut buffer = StorageBuffer::<Vec<GpuFaceTexture>>::from(extracted_faces.faces.clone());
     buffer.set_label(Some("face_texture_buffer"));
     buffer.write_buffer(&gpu, &queue);
@@ -70,7 +76,7 @@ pub fn prepare_gpu_registry_data(
         &layouts.registry_bg_layout,
         &BindGroupEntries::sequential((
             BindingResource::Buffer(BufferBinding {
-                buffer: &gpu_buffer,
+                buffer: gpu_buffer,
                 offset: 0,
                 size: None,
             }),
@@ -78,6 +84,7 @@ pub fn prepare_gpu_registry_data(
             &gpu_array_textures.color.sampler,
             &gpu_array_textures.normal.texture_view,
             &gpu_array_textures.normal.sampler,
+            fog,
         )),
     );
 
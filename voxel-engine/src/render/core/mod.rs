@@ -1,26 +1,31 @@
 mod draw;
+pub mod error;
+mod fog;
 mod gpu_chunk;
 mod gpu_registries;
 mod impls;
 mod prepass;
+mod quad_attributes;
 mod render;
 mod shadows;
 mod utils;
 
+use std::time::Duration;
+
 use bevy::{
     app::{App, Plugin},
     core_pipeline::{core_3d::Opaque3d, prepass::Opaque3dPrepass},
     ecs::system::Resource,
-    pbr::Shadow,
+    pbr::{MeshPipelineKey, Shadow},
     prelude::*,
     render::{
-        extract_resource::ExtractResourcePlugin,
-        mesh::MeshVertexAttribute,
+        extract_resource::{ExtractResource, ExtractResourcePlugin},
+        mesh::{MeshVertexAttribute, PrimitiveTopology},
         render_phase::AddRenderCommand,
         render_resource::{
             binding_types::{self},
-            BindGroupLayout, BindGroupLayoutEntries, SamplerBindingType, ShaderStages, ShaderType,
-            SpecializedRenderPipelines, TextureSampleType, VertexFormat,
+            BindGroupLayout, BindGroupLayoutEntries, SamplerBindingType, ShaderDefVal,
+            ShaderStages, ShaderType, SpecializedRenderPipelines, TextureSampleType, VertexFormat,
         },
         renderer::RenderDevice,
         Render, RenderApp, RenderSet,
@@ -33,9 +38,12 @@ use crate::data::{
 };
 
 use self::{
+    error::ChunkPipelineError,
+    fog::{prepare_voxel_fog, update_voxel_fog, GpuVoxelFog, VoxelFogBuffer},
     gpu_chunk::{
         extract_chunk_entities, extract_chunk_mesh_data, prepare_chunk_mesh_data,
-        ChunkRenderDataStore,
+        prepare_gpu_chunk_entities, prepare_visible_chunks, ChunkQueueSkips, ChunkRenderDataStore,
+        GpuChunkEntities, GpuChunkFade, VisibleChunks,
     },
     gpu_registries::{
         extract_texreg_faces, prepare_gpu_registry_data, ExtractedTexregFaces, RegistryBindGroup,
@@ -46,19 +54,207 @@ use self::{
     utils::main_world_res_exists,
 };
 
-use super::{meshing::controller::ExtractableChunkMeshData, quad::GpuQuad};
+use super::{
+    meshing::{controller::ExtractableChunkMeshData, MeshSidedness},
+    quad::GpuQuad,
+};
+
+pub use fog::VoxelFog;
+pub use gpu_chunk::{ChunkRenderReady, ChunkUploadFailed, ChunkUploadState};
+pub use quad_attributes::{QuadAttributeChannel, QuadAttributeFn, QuadAttributes};
+pub(crate) use utils::u32_shader_def;
 
 pub struct RenderCore;
 
+/// Settings for fading chunks in when they're first shown and out at the edge of the view distance, to hide
+/// chunks popping in. Fading is done by dithering, so it's only enabled for views with
+/// [`DebandDither::Enabled`](bevy::core_pipeline::tonemapping::DebandDither).
+/// Changes only apply to chunks that are uploaded to the GPU after the change.
+#[derive(Resource, ExtractResource, Clone, Debug, PartialEq)]
+pub struct ChunkFadeSettings {
+    /// How long it takes a newly shown chunk to fade in. Zero disables fading in.
+    pub fade_in: Duration,
+    /// Fragments further away from the camera than `far_start` fade out, until they're completely invisible
+    /// at `far_end`. Disabled if `far_end` is not greater than `far_start`.
+    pub far_start: f32,
+    pub far_end: f32,
+}
+
+impl Default for ChunkFadeSettings {
+    fn default() -> Self {
+        Self {
+            fade_in: Duration::from_millis(500),
+            far_start: 0.0,
+            far_end: 0.0,
+        }
+    }
+}
+
+impl ChunkFadeSettings {
+    pub fn fades_in(&self) -> bool {
+        !self.fade_in.is_zero()
+    }
+
+    pub fn fades_out(&self) -> bool {
+        self.far_end > self.far_start
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.fades_in() || self.fades_out()
+    }
+}
+
+/// How failed chunk render data uploads are retried. Every failed attempt doubles the time until the next one,
+/// starting at `backoff` and capped at `max_backoff`. After `max_attempts` a
+/// [`ChunkUploadFailed`] event is sent and the chunk isn't drawn until it's remeshed.
+#[derive(Resource, ExtractResource, Clone, Debug, PartialEq)]
+pub struct ChunkUploadRetrySettings {
+    pub max_attempts: u32,
+    pub backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for ChunkUploadRetrySettings {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(2),
+        }
+    }
+}
+
+impl ChunkUploadRetrySettings {
+    /// How long to wait before trying again after `attempts` failed attempts.
+    pub fn backoff(&self, attempts: u32) -> Duration {
+        let factor = 1u32
+            .checked_shl(attempts.saturating_sub(1))
+            .unwrap_or(u32::MAX);
+        self.backoff.saturating_mul(factor).min(self.max_backoff)
+    }
+}
+
+/// The shaders used by the chunk pipelines. Insert this resource before the [`RenderCore`] plugin is finished
+/// to render chunks with custom shaders, otherwise the built-in shaders are loaded. The custom shaders must
+/// use the same bind group layouts as the built-in ones.
+#[derive(Resource, Clone, Debug, PartialEq)]
+pub struct ChunkShaders {
+    pub vert: Handle<Shader>,
+    pub frag: Handle<Shader>,
+    pub prepass_vert: Handle<Shader>,
+    pub prepass_frag: Handle<Shader>,
+    /// Code run by the built-in fragment shader after shading, for things like fog or tinting, without
+    /// having to replace the whole fragment shader. The shader must have the import path
+    /// [`ChunkShaders::FRAGMENT_EXTENSION_IMPORT`] and define
+    /// `fn extend_fragment(in: VertexOutput, color: vec4<f32>) -> vec4<f32>`, which returns the final color
+    /// of the fragment. `VertexOutput` is imported from `shaders/vxl_chunk_io.wgsl`.
+    pub fragment_extension: Option<Handle<Shader>>,
+}
+
+impl ChunkShaders {
+    pub const VERT: &'static str = "shaders/vxl_chunk_vert.wgsl";
+    pub const FRAG: &'static str = "shaders/vxl_chunk_frag.wgsl";
+    pub const PREPASS_VERT: &'static str = "shaders/vxl_chunk_vert_prepass.wgsl";
+    pub const PREPASS_FRAG: &'static str = "shaders/vxl_chunk_frag_prepass.wgsl";
+
+    pub const FRAGMENT_EXTENSION_IMPORT: &'static str = "vxl::fragment_extension";
+    pub const FRAGMENT_EXTENSION_DEF: &'static str = "CHUNK_FRAGMENT_EXTENSION";
+
+    pub fn with_fragment_extension(mut self, extension: Handle<Shader>) -> Self {
+        self.fragment_extension = Some(extension);
+        self
+    }
+
+    /// Shader defs for the main chunk pipeline.
+    pub fn shader_defs(&self) -> Vec<ShaderDefVal> {
+        let mut defs = Vec::new();
+
+        if self.fragment_extension.is_some() {
+            defs.push(Self::FRAGMENT_EXTENSION_DEF.into());
+        }
+
+        defs
+    }
+}
+
+impl FromWorld for ChunkShaders {
+    fn from_world(world: &mut World) -> Self {
+        let server = world.resource::<AssetServer>();
+
+        Self {
+            vert: server.load(Self::VERT),
+            frag: server.load(Self::FRAG),
+            prepass_vert: server.load(Self::PREPASS_VERT),
+            prepass_frag: server.load(Self::PREPASS_FRAG),
+            fragment_extension: None,
+        }
+    }
+}
+
 impl RenderCore {
     pub const QUAD_INDEX_ATTR: MeshVertexAttribute =
-        MeshVertexAttribute::new("quad_index_attr", 5099_0, VertexFormat::Uint32);
+        MeshVertexAttribute::new("quad_index_attr", 50_990, VertexFormat::Uint32);
+
+    /// The primitive topology of all chunk meshes. The chunk shaders expand every quad into two triangles
+    /// from its index, so the chunk pipelines are only ever specialized for this topology.
+    pub const CHUNK_TOPOLOGY: PrimitiveTopology = PrimitiveTopology::TriangleList;
+
+    /// The chunk shaders inserted by the user, or the built-in shaders if there are none.
+    fn chunk_shaders(app: &mut App) -> ChunkShaders {
+        app.init_resource::<ChunkShaders>();
+        app.world.resource::<ChunkShaders>().clone()
+    }
+
+    /// Check that the mesh of a chunk can be rendered by the chunk pipelines, and get the pipeline key for it.
+    /// Chunk meshes are drawn as [`RenderCore::CHUNK_TOPOLOGY`], so an index buffer that doesn't hold a whole
+    /// number of triangles means the mesher built something else, which would be drawn as garbage.
+    pub fn chunk_mesh_key(
+        index_count: u32,
+        entity: Option<Entity>,
+    ) -> Result<MeshPipelineKey, ChunkPipelineError> {
+        if !index_count.is_multiple_of(3) {
+            return Err(ChunkPipelineError::IncompleteTriangles {
+                entity,
+                index_count,
+            });
+        }
+
+        Ok(MeshPipelineKey::from_primitive_topology(
+            Self::CHUNK_TOPOLOGY,
+        ))
+    }
+
+    /// Like [`RenderCore::chunk_mesh_key`], but logs the error so the caller can just skip queueing the mesh.
+    pub fn queueable_chunk_mesh_key(
+        index_count: u32,
+        entity: Option<Entity>,
+    ) -> Option<MeshPipelineKey> {
+        match Self::chunk_mesh_key(index_count, entity) {
+            Ok(key) => Some(key),
+            Err(error) => {
+                error!("Skipping chunk mesh: {error}");
+                None
+            }
+        }
+    }
 }
 
 impl Plugin for RenderCore {
     fn build(&self, app: &mut App) {
         app.add_plugins(ExtractResourcePlugin::<VoxelColorArrayTexture>::default());
         app.add_plugins(ExtractResourcePlugin::<VoxelNormalArrayTexture>::default());
+        app.add_plugins(ExtractResourcePlugin::<ChunkFadeSettings>::default());
+        app.add_plugins(ExtractResourcePlugin::<ChunkUploadRetrySettings>::default());
+        app.add_plugins(ExtractResourcePlugin::<VoxelFog>::default());
+        app.add_plugins(ExtractResourcePlugin::<MeshSidedness>::default());
+        app.init_resource::<ChunkFadeSettings>();
+        app.init_resource::<ChunkUploadRetrySettings>();
+        app.init_resource::<VoxelFog>();
+        app.init_resource::<MeshSidedness>();
+        app.init_resource::<QuadAttributes>();
+        app.add_event::<ChunkRenderReady>();
+        app.add_event::<ChunkUploadFailed>();
+        app.add_systems(Update, update_voxel_fog);
 
         // Render app logic
         let render_app = app.sub_app_mut(RenderApp);
@@ -71,7 +267,11 @@ impl Plugin for RenderCore {
         render_app
             .init_resource::<SpecializedRenderPipelines<ChunkPipeline>>()
             .init_resource::<SpecializedRenderPipelines<ChunkPrepassPipeline>>()
-            .init_resource::<ChunkRenderDataStore>();
+            .init_resource::<ChunkRenderDataStore>()
+            .init_resource::<GpuChunkEntities>()
+            .init_resource::<VisibleChunks>()
+            .init_resource::<ChunkQueueSkips>()
+            .init_resource::<VoxelFogBuffer>();
 
         render_app.add_systems(
             ExtractSchedule,
@@ -89,18 +289,29 @@ impl Plugin for RenderCore {
             Render,
             (
                 (
-                    prepare_gpu_registry_data.run_if(not(resource_exists::<RegistryBindGroup>)),
-                    prepare_chunk_mesh_data,
+                    (
+                        prepare_voxel_fog,
+                        prepare_gpu_registry_data.run_if(not(resource_exists::<RegistryBindGroup>)),
+                    )
+                        .chain(),
+                    (prepare_chunk_mesh_data, prepare_gpu_chunk_entities).chain(),
                 )
                     .in_set(RenderSet::PrepareResources),
+                prepare_visible_chunks
+                    .in_set(RenderSet::Queue)
+                    .before(RenderSet::QueueMeshes),
                 (queue_chunks, queue_prepass_chunks, queue_shadows).in_set(RenderSet::QueueMeshes),
             ),
         );
     }
 
     fn finish(&self, app: &mut App) {
+        let quad_attributes = app.world.resource::<QuadAttributes>().clone();
+        let shaders = Self::chunk_shaders(app);
         let render_app = app.sub_app_mut(RenderApp);
 
+        render_app.insert_resource(quad_attributes);
+        render_app.insert_resource(shaders);
         render_app.init_resource::<DefaultBindGroupLayouts>();
 
         render_app.init_resource::<ChunkPipeline>();
@@ -129,6 +340,7 @@ impl FromWorld for DefaultBindGroupLayouts {
                         binding_types::sampler(SamplerBindingType::NonFiltering),
                         binding_types::texture_2d_array(TextureSampleType::default()),
                         binding_types::sampler(SamplerBindingType::NonFiltering),
+                        binding_types::uniform_buffer::<GpuVoxelFog>(false),
                     ),
                 ),
             ),
@@ -142,9 +354,90 @@ impl FromWorld for DefaultBindGroupLayouts {
                             Some(<Vec3 as ShaderType>::min_size()),
                         ),
                         binding_types::storage_buffer_read_only::<GpuQuad>(false),
+                        binding_types::uniform_buffer::<GpuChunkFade>(false),
+                        binding_types::storage_buffer_read_only::<u32>(false),
                     ),
                 ),
             ),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn custom_chunk_shaders() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, AssetPlugin::default()))
+            .init_asset::<Shader>();
+
+        let defaults = RenderCore::chunk_shaders(&mut app);
+        assert_eq!(
+            Some(ChunkShaders::VERT),
+            defaults.vert.path().and_then(|p| p.path().to_str())
+        );
+
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, AssetPlugin::default()))
+            .init_asset::<Shader>();
+
+        let server = app.world.resource::<AssetServer>();
+        let custom = ChunkShaders {
+            vert: server.load("shaders/custom_vert.wgsl"),
+            frag: server.load("shaders/custom_frag.wgsl"),
+            prepass_vert: defaults.prepass_vert.clone(),
+            prepass_frag: defaults.prepass_frag.clone(),
+            fragment_extension: None,
+        };
+        app.insert_resource(custom.clone());
+
+        let shaders = RenderCore::chunk_shaders(&mut app);
+        assert_eq!(custom, shaders);
+        assert_eq!(
+            Some("shaders/custom_vert.wgsl"),
+            shaders.vert.path().and_then(|p| p.path().to_str())
+        );
+    }
+
+    #[test]
+    fn fragment_extension_shader_def() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, AssetPlugin::default()))
+            .init_asset::<Shader>();
+
+        let shaders = RenderCore::chunk_shaders(&mut app);
+        assert!(shaders.shader_defs().is_empty());
+
+        let server = app.world.resource::<AssetServer>();
+        let tint = server.load("shaders/vxl_tint_extension.wgsl");
+        let shaders = shaders.with_fragment_extension(tint.clone());
+
+        assert_eq!(Some(tint), shaders.fragment_extension);
+        assert!(matches!(
+            &shaders.shader_defs()[..],
+            [ShaderDefVal::Bool(def, true)] if def == ChunkShaders::FRAGMENT_EXTENSION_DEF
+        ));
+    }
+
+    #[test]
+    fn incomplete_triangles_are_rejected() {
+        let entity = Entity::from_raw(3);
+
+        let error = RenderCore::chunk_mesh_key(4, Some(entity)).unwrap_err();
+        assert!(matches!(
+            error,
+            ChunkPipelineError::IncompleteTriangles {
+                entity: Some(e),
+                index_count: 4
+            } if e == entity
+        ));
+        assert!(RenderCore::queueable_chunk_mesh_key(4, Some(entity)).is_none());
+
+        assert_eq!(
+            MeshPipelineKey::from_primitive_topology(RenderCore::CHUNK_TOPOLOGY),
+            RenderCore::queueable_chunk_mesh_key(6, Some(entity)).unwrap()
+        );
+    }
+}
@@ -1,45 +1,44 @@
 use bevy::{
-    asset::{AssetId, AssetServer, Handle},
+    asset::{AssetId, Handle},
     core_pipeline::{
         core_3d::CORE_3D_DEPTH_FORMAT,
         prepass::{
             DepthPrepass, MotionVectorPrepass, NormalPrepass, Opaque3dPrepass,
             MOTION_VECTOR_PREPASS_FORMAT, NORMAL_PREPASS_FORMAT,
         },
+        tonemapping::DebandDither,
     },
     ecs::{
+        entity::Entity,
         query::Has,
         system::{Query, Res, ResMut, Resource},
         world::{FromWorld, World},
     },
-    log::debug,
     pbr::{MeshPipelineKey, PreviousViewProjection, SetPrepassViewBindGroup},
     render::{
         globals::GlobalsUniform,
-        mesh::PrimitiveTopology,
         render_phase::{DrawFunctions, RenderPhase, SetItemPipeline},
         render_resource::{
             binding_types::uniform_buffer, BindGroupLayout, BindGroupLayoutEntries,
             ColorTargetState, ColorWrites, CompareFunction, DepthBiasState, DepthStencilState,
-            Face, FragmentState, FrontFace, MultisampleState, PipelineCache, PolygonMode,
-            PrimitiveState, RenderPipelineDescriptor, Shader, ShaderDefVal, ShaderStages,
-            SpecializedMeshPipeline, SpecializedRenderPipeline, SpecializedRenderPipelines,
+            FragmentState, MultisampleState, PipelineCache, RenderPipelineDescriptor, Shader,
+            ShaderDefVal, ShaderStages, SpecializedRenderPipeline, SpecializedRenderPipelines,
             StencilFaceState, StencilState, VertexState,
         },
         renderer::RenderDevice,
-        view::{ExtractedView, ViewUniform, VisibleEntities},
+        view::{ExtractedView, ViewUniform},
     },
 };
 
-use crate::render::core::render::ChunkPipeline;
+use crate::render::{core::render::ChunkPipeline, meshing::MeshSidedness};
 
 use super::{
     draw::DrawChunk,
     gpu_chunk::SetChunkBindGroup,
     gpu_registries::SetRegistryBindGroup,
     render::ChunkPipelineKey,
-    utils::{add_shader_constants, iter_visible_chunks, ChunkDataParams},
-    DefaultBindGroupLayouts,
+    utils::{add_shader_constants, ChunkDataParams},
+    ChunkFadeSettings, ChunkShaders, DefaultBindGroupLayouts,
 };
 
 #[derive(Clone, Resource)]
@@ -53,7 +52,7 @@ pub struct ChunkPrepassPipeline {
 
 impl FromWorld for ChunkPrepassPipeline {
     fn from_world(world: &mut World) -> Self {
-        let server = world.resource::<AssetServer>();
+        let shaders = world.resource::<ChunkShaders>();
         let gpu = world.resource::<RenderDevice>();
 
         let _mesh_pipeline = world.resource::<ChunkPipeline>();
@@ -90,30 +89,14 @@ impl FromWorld for ChunkPrepassPipeline {
             view_layout_motion_vectors,
             view_layout_no_motion_vectors,
             layouts: world.resource::<DefaultBindGroupLayouts>().clone(),
-            vert: server.load("shaders/vxl_chunk_vert_prepass.wgsl"),
-            frag: server.load("shaders/vxl_chunk_frag_prepass.wgsl"),
+            vert: shaders.prepass_vert.clone(),
+            frag: shaders.prepass_frag.clone(),
         }
     }
 }
 
-// most of this code is taken verbatim from
-// https://github.com/bevyengine/bevy/blob/d4132f661a8a567fd3f9c3b329c2b4032bb1e05e/crates/bevy_pbr/src/prepass/mod.rs#L297C1-L582C2
-impl SpecializedRenderPipeline for ChunkPrepassPipeline {
-    type Key = ChunkPipelineKey;
-
-    fn specialize(&self, key: Self::Key) -> RenderPipelineDescriptor {
-        let mut bind_group_layouts =
-            vec![if key.contains(MeshPipelineKey::MOTION_VECTOR_PREPASS) {
-                self.view_layout_motion_vectors.clone()
-            } else {
-                self.view_layout_no_motion_vectors.clone()
-            }];
-
-        bind_group_layouts.extend_from_slice(&[
-            self.layouts.registry_bg_layout.clone(),
-            self.layouts.chunk_bg_layout.clone(),
-        ]);
-
+impl ChunkPrepassPipeline {
+    pub fn shader_defs(key: ChunkPipelineKey) -> Vec<ShaderDefVal> {
         let mut shader_defs: Vec<ShaderDefVal> = vec![
             "PREPASS_PIPELINE".into(),
             "VERTEX_UVS".into(),
@@ -153,6 +136,10 @@ impl SpecializedRenderPipeline for ChunkPrepassPipeline {
             shader_defs.push("PREPASS_FRAGMENT".into());
         }
 
+        if key.fade {
+            shader_defs.push("CHUNK_FADE".into());
+        }
+
         if key.contains(MeshPipelineKey::DEPTH_CLAMP_ORTHO) {
             shader_defs.push("DEPTH_CLAMP_ORTHO".into());
             // PERF: This line forces the "prepass fragment shader" to always run in
@@ -164,6 +151,30 @@ impl SpecializedRenderPipeline for ChunkPrepassPipeline {
             shader_defs.push("PREPASS_FRAGMENT".into());
         }
 
+        shader_defs
+    }
+}
+
+// most of this code is taken verbatim from
+// https://github.com/bevyengine/bevy/blob/d4132f661a8a567fd3f9c3b329c2b4032bb1e05e/crates/bevy_pbr/src/prepass/mod.rs#L297C1-L582C2
+impl SpecializedRenderPipeline for ChunkPrepassPipeline {
+    type Key = ChunkPipelineKey;
+
+    fn specialize(&self, key: Self::Key) -> RenderPipelineDescriptor {
+        let mut bind_group_layouts =
+            vec![if key.contains(MeshPipelineKey::MOTION_VECTOR_PREPASS) {
+                self.view_layout_motion_vectors.clone()
+            } else {
+                self.view_layout_no_motion_vectors.clone()
+            }];
+
+        bind_group_layouts.extend_from_slice(&[
+            self.layouts.registry_bg_layout.clone(),
+            self.layouts.chunk_bg_layout.clone(),
+        ]);
+
+        let shader_defs = Self::shader_defs(key);
+
         let mut targets = vec![
             key.contains(MeshPipelineKey::NORMAL_PREPASS)
                 .then_some(ColorTargetState {
@@ -193,7 +204,8 @@ impl SpecializedRenderPipeline for ChunkPrepassPipeline {
 
         let fragment_required = !targets.is_empty()
             || key.contains(MeshPipelineKey::DEPTH_CLAMP_ORTHO)
-            || key.contains(MeshPipelineKey::MAY_DISCARD);
+            || key.contains(MeshPipelineKey::MAY_DISCARD)
+            || key.fade;
 
         let fragment = fragment_required.then(|| {
             // Use the fragment shader from the material
@@ -216,15 +228,7 @@ impl SpecializedRenderPipeline for ChunkPrepassPipeline {
                 shader_defs: shader_defs.clone(),
                 buffers: vec![],
             },
-            primitive: PrimitiveState {
-                topology: key.primitive_topology(),
-                strip_index_format: None,
-                front_face: FrontFace::Ccw,
-                cull_mode: Some(Face::Back),
-                unclipped_depth: false,
-                polygon_mode: PolygonMode::Fill,
-                conservative: false,
-            },
+            primitive: key.primitive_state(),
             depth_stencil: Some(DepthStencilState {
                 format: CORE_3D_DEPTH_FORMAT,
                 depth_write_enabled: true,
@@ -251,16 +255,20 @@ impl SpecializedRenderPipeline for ChunkPrepassPipeline {
     }
 }
 
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 pub fn queue_prepass_chunks(
     functions: Res<DrawFunctions<Opaque3dPrepass>>,
     mut pipelines: ResMut<SpecializedRenderPipelines<ChunkPrepassPipeline>>,
     pipeline_cache: Res<PipelineCache>,
     prepass_pipeline: Res<ChunkPrepassPipeline>,
     chunks: ChunkDataParams,
+    sidedness: Res<MeshSidedness>,
+    fade_settings: Res<ChunkFadeSettings>,
     mut views: Query<(
+        Entity,
         &ExtractedView,
-        &VisibleEntities,
         &mut RenderPhase<Opaque3dPrepass>,
+        Option<&DebandDither>,
         Has<DepthPrepass>,
         Has<NormalPrepass>,
         Has<MotionVectorPrepass>,
@@ -269,9 +277,10 @@ pub fn queue_prepass_chunks(
     let draw_function = functions.read().get_id::<DrawVoxelChunkPrepass>().unwrap();
 
     for (
+        view_entity,
         _view,
-        visible_entities,
         mut phase,
+        dither,
         depth_prepass,
         normal_prepass,
         motion_vector_prepass,
@@ -289,20 +298,19 @@ pub fn queue_prepass_chunks(
             view_key |= MeshPipelineKey::MOTION_VECTOR_PREPASS;
         }
 
-        iter_visible_chunks(visible_entities, &chunks, |entity, _chunk_pos| {
+        // discard the same fragments as the main pass does when fading, see `queue_chunks`
+        let fade = fade_settings.is_enabled() && matches!(dither, Some(DebandDither::Enabled));
+
+        for &(entity, chunk) in chunks.visible_chunks.get(view_entity) {
             let pipeline_id = pipelines.specialize(
                 &pipeline_cache,
                 &prepass_pipeline,
-                ChunkPipelineKey {
-                    inner: MeshPipelineKey::from_primitive_topology(
-                        PrimitiveTopology::TriangleList,
-                    ) | view_key,
-                },
+                ChunkPipelineKey::new(view_key, chunk.mesh_key, fade).with_sidedness(*sidedness),
             );
 
             phase.add(Opaque3dPrepass {
-                entity: entity,
-                draw_function: draw_function,
+                entity,
+                draw_function,
                 pipeline_id,
                 // this asset ID is seemingly just for some sorting stuff bevy does, but we have our own
                 // logic so we don't care about what bevy would use this field for, so we set it to the default asset ID
@@ -310,7 +318,7 @@ pub fn queue_prepass_chunks(
                 batch_range: 0..1,
                 dynamic_offset: None,
             });
-        });
+        }
     }
 }
 
@@ -0,0 +1,150 @@
+use std::{fmt, sync::Arc};
+
+use bevy::{ecs::system::Resource, render::render_resource::ShaderDefVal};
+
+use crate::render::quad::GpuQuad;
+
+use super::u32_shader_def;
+
+/// Computes the value of an extra attribute for a quad.
+pub type QuadAttributeFn = Arc<dyn Fn(&GpuQuad) -> u32 + Send + Sync>;
+
+/// The index of a channel registered in [`QuadAttributes`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct QuadAttributeChannel(u32);
+
+impl QuadAttributeChannel {
+    pub fn index(self) -> u32 {
+        self.0
+    }
+}
+
+/// Extra per-quad data that's uploaded in a buffer next to the quads of a chunk, so shaders can read data
+/// like the wind-sway strength of foliage without it being stored in every [`GpuQuad`].
+///
+/// Every channel holds one `u32` per quad, computed when the chunk is uploaded. In the shader, the value of
+/// a channel named `sway` is read with `quad_attribute(quad_index, #{QUAD_ATTR_SWAY}u)`.
+/// Channels must be registered while building the app, they're copied to the render world when
+/// [`RenderCore`](super::RenderCore) is finished.
+#[derive(Resource, Clone, Default)]
+pub struct QuadAttributes {
+    channels: Vec<(String, QuadAttributeFn)>,
+}
+
+impl fmt::Debug for QuadAttributes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.channels.iter().map(|(name, _)| name))
+            .finish()
+    }
+}
+
+impl QuadAttributes {
+    /// Register a channel. The name is used for the shader def with the index of the channel,
+    /// so it should be a valid identifier.
+    pub fn register<F>(&mut self, name: &str, attribute: F) -> QuadAttributeChannel
+    where
+        F: Fn(&GpuQuad) -> u32 + Send + Sync + 'static,
+    {
+        let channel = QuadAttributeChannel(self.channels.len() as u32);
+        self.channels
+            .push((name.to_ascii_uppercase(), Arc::new(attribute)));
+        channel
+    }
+
+    pub fn len(&self) -> usize {
+        self.channels.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.channels.is_empty()
+    }
+
+    /// Shader defs for the number of channels and the index of each channel.
+    /// There are no defs if no channels are registered.
+    pub fn shader_defs(&self) -> Vec<ShaderDefVal> {
+        if self.is_empty() {
+            return Vec::new();
+        }
+
+        let mut defs = vec![u32_shader_def(
+            "QUAD_ATTRIBUTE_CHANNELS",
+            self.channels.len() as u32,
+        )];
+
+        for (i, (name, _)) in self.channels.iter().enumerate() {
+            defs.push(u32_shader_def(&format!("QUAD_ATTR_{name}"), i as u32));
+        }
+
+        defs
+    }
+
+    /// Compute the attributes of the quads. The attributes of each quad are next to each other, in the order
+    /// the channels were registered. Storage buffers can't be empty, so if there are no channels the buffer
+    /// has a single unused value.
+    pub fn encode(&self, quads: &[GpuQuad]) -> Vec<u32> {
+        if self.is_empty() {
+            return vec![0];
+        }
+
+        let mut buffer = Vec::with_capacity(quads.len() * self.channels.len());
+
+        for quad in quads {
+            buffer.extend(self.channels.iter().map(|(_, attribute)| attribute(quad)));
+        }
+
+        buffer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::Vec2;
+
+    use crate::render::quad::GpuQuadBitfields;
+
+    use super::*;
+
+    fn quad(texture_id: u32, magnitude: i32) -> GpuQuad {
+        GpuQuad {
+            texture_id,
+            bitfields: GpuQuadBitfields::new(),
+            min: Vec2::ZERO,
+            max: Vec2::ONE,
+            magnitude,
+            light: 0,
+        }
+    }
+
+    #[test]
+    fn channels_are_encoded_per_quad() {
+        let quads = [quad(1, 0), quad(7, 12)];
+
+        // chunks without channels still get a valid buffer
+        let empty = QuadAttributes::default();
+        assert_eq!(vec![0], empty.encode(&quads));
+        assert!(empty.shader_defs().is_empty());
+
+        let mut attributes = QuadAttributes::default();
+        let sway = attributes.register("sway", |quad| (quad.texture_id == 7) as u32 * 100);
+        let mag = attributes.register("magnitude", |quad| quad.magnitude as u32);
+
+        assert_eq!(0, sway.index());
+        assert_eq!(1, mag.index());
+
+        let encoded = attributes.encode(&quads);
+        let read = |quad: usize, channel: QuadAttributeChannel| {
+            encoded[quad * attributes.len() + channel.index() as usize]
+        };
+
+        assert_eq!(4, encoded.len());
+        assert_eq!(0, read(0, sway));
+        assert_eq!(100, read(1, sway));
+        assert_eq!(12, read(1, mag));
+
+        let defs = attributes.shader_defs();
+        assert!(defs.contains(&u32_shader_def("QUAD_ATTRIBUTE_CHANNELS", 2)));
+        assert!(defs.contains(&u32_shader_def("QUAD_ATTR_SWAY", 0)));
+        assert!(defs.contains(&u32_shader_def("QUAD_ATTR_MAGNITUDE", 1)));
+    }
+}
@@ -1,16 +1,16 @@
 use bevy::{
-    asset::{AssetId, AssetServer, Handle},
+    asset::{AssetId, Handle},
     core_pipeline::{
         core_3d::{Opaque3d, CORE_3D_DEPTH_FORMAT},
         prepass::{DeferredPrepass, DepthPrepass, MotionVectorPrepass, NormalPrepass},
         tonemapping::{DebandDither, Tonemapping},
     },
     ecs::{
+        entity::Entity,
         query::Has,
         system::{Query, Res, ResMut, Resource},
         world::{FromWorld, World},
     },
-    log::debug,
     pbr::{
         generate_view_layouts, MeshPipelineKey, MeshPipelineViewLayout, MeshPipelineViewLayoutKey,
         ScreenSpaceAmbientOcclusionSettings, SetMeshViewBindGroup, ShadowFilteringMethod,
@@ -19,29 +19,28 @@ use bevy::{
     prelude::Deref,
     render::{
         camera::{Projection, TemporalJitter},
-        mesh::PrimitiveTopology,
         render_phase::{DrawFunctions, RenderPhase, SetItemPipeline},
         render_resource::{
             BindGroupLayout, ColorTargetState, ColorWrites, CompareFunction, DepthBiasState,
-            DepthStencilState, Face, FragmentState, FrontFace, MultisampleState, PipelineCache,
+            DepthStencilState, FragmentState, FrontFace, MultisampleState, PipelineCache,
             PolygonMode, PrimitiveState, PushConstantRange, RenderPipelineDescriptor, Shader,
-            ShaderDefVal, ShaderStages, SpecializedMeshPipeline, SpecializedRenderPipeline,
-            SpecializedRenderPipelines, StencilFaceState, StencilState, TextureFormat, VertexState,
+            ShaderDefVal, ShaderStages, SpecializedRenderPipeline, SpecializedRenderPipelines,
+            StencilFaceState, StencilState, TextureFormat, VertexState,
         },
         renderer::RenderDevice,
         texture::BevyDefault,
-        view::{ExtractedView, ViewTarget, VisibleEntities},
+        view::{ExtractedView, ViewTarget},
     },
 };
 
-use crate::render::core::utils::add_mesh_pipeline_shader_defs;
+use crate::render::{core::utils::add_mesh_pipeline_shader_defs, meshing::MeshSidedness};
 
 use super::{
     draw::DrawChunk,
     gpu_chunk::SetChunkBindGroup,
     gpu_registries::SetRegistryBindGroup,
-    utils::{add_shader_constants, iter_visible_chunks, ChunkDataParams},
-    DefaultBindGroupLayouts,
+    utils::{add_shader_constants, ChunkDataParams},
+    ChunkFadeSettings, ChunkShaders, DefaultBindGroupLayouts, QuadAttributes, RenderCore, VoxelFog,
 };
 
 #[derive(Resource, Clone)]
@@ -51,19 +50,66 @@ pub struct ChunkPipeline {
     pub chunk_layout: BindGroupLayout,
     pub vert: Handle<Shader>,
     pub frag: Handle<Shader>,
+    /// Shader defs for the registered [`QuadAttributes`](super::QuadAttributes) channels.
+    pub quad_attribute_defs: Vec<ShaderDefVal>,
+    /// Shader defs for the [`ChunkShaders`] fragment extension.
+    pub extension_defs: Vec<ShaderDefVal>,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deref)]
 pub struct ChunkPipelineKey {
+    #[deref]
     pub inner: MeshPipelineKey,
+    /// Fade chunks in and out with dithering, see [`ChunkFadeSettings`].
+    pub fade: bool,
+    /// Blend chunks into the distance fog, see [`VoxelFog`].
+    pub fog: bool,
+    /// Which faces of chunk meshes are culled, see [`MeshSidedness`].
+    pub sidedness: MeshSidedness,
+}
+
+impl ChunkPipelineKey {
+    /// Create a key for the given view and chunk mesh, see [`RenderCore::chunk_mesh_key`].
+    pub fn new(view_key: MeshPipelineKey, mesh_key: MeshPipelineKey, fade: bool) -> Self {
+        Self {
+            inner: view_key | mesh_key,
+            fade,
+            fog: false,
+            sidedness: MeshSidedness::Single,
+        }
+    }
+
+    pub fn with_fog(mut self, fog: bool) -> Self {
+        self.fog = fog;
+        self
+    }
+
+    pub fn with_sidedness(mut self, sidedness: MeshSidedness) -> Self {
+        self.sidedness = sidedness;
+        self
+    }
+
+    /// The primitive state shared by the chunk pipelines.
+    pub fn primitive_state(&self) -> PrimitiveState {
+        PrimitiveState {
+            topology: RenderCore::CHUNK_TOPOLOGY,
+            strip_index_format: None,
+            front_face: FrontFace::Ccw,
+            cull_mode: self.sidedness.cull_mode(),
+            unclipped_depth: false,
+            polygon_mode: PolygonMode::Fill,
+            conservative: false,
+        }
+    }
 }
 
 impl FromWorld for ChunkPipeline {
     fn from_world(world: &mut World) -> Self {
-        let server = world.resource::<AssetServer>();
+        let shaders = world.resource::<ChunkShaders>();
         let gpu = world.resource::<RenderDevice>();
 
         let layouts = world.resource::<DefaultBindGroupLayouts>();
+        let quad_attributes = world.resource::<QuadAttributes>();
 
         let clustered_forward_buffer_binding_type =
             gpu.get_supported_read_only_binding_type(CLUSTERED_FORWARD_STORAGE_BUFFER_COUNT);
@@ -75,16 +121,16 @@ impl FromWorld for ChunkPipeline {
             ),
             registry_layout: layouts.registry_bg_layout.clone(),
             chunk_layout: layouts.chunk_bg_layout.clone(),
-            vert: server.load("shaders/vxl_chunk_vert.wgsl"),
-            frag: server.load("shaders/vxl_chunk_frag.wgsl"),
+            vert: shaders.vert.clone(),
+            frag: shaders.frag.clone(),
+            quad_attribute_defs: quad_attributes.shader_defs(),
+            extension_defs: shaders.shader_defs(),
         }
     }
 }
 
-impl SpecializedRenderPipeline for ChunkPipeline {
-    type Key = ChunkPipelineKey;
-
-    fn specialize(&self, key: Self::Key) -> RenderPipelineDescriptor {
+impl ChunkPipeline {
+    pub fn shader_defs(key: ChunkPipelineKey) -> Vec<ShaderDefVal> {
         let mut shader_defs: Vec<ShaderDefVal> = vec![
             "MESH_PIPELINE".into(),
             "VERTEX_OUTPUT_INSTANCE_INDEX".into(),
@@ -93,6 +139,26 @@ impl SpecializedRenderPipeline for ChunkPipeline {
         add_shader_constants(&mut shader_defs);
         add_mesh_pipeline_shader_defs(key.inner, &mut shader_defs);
 
+        if key.fade {
+            shader_defs.push("CHUNK_FADE".into());
+        }
+
+        if key.fog {
+            shader_defs.push(VoxelFog::SHADER_DEF.into());
+        }
+
+        shader_defs
+    }
+}
+
+impl SpecializedRenderPipeline for ChunkPipeline {
+    type Key = ChunkPipelineKey;
+
+    fn specialize(&self, key: Self::Key) -> RenderPipelineDescriptor {
+        let mut shader_defs = Self::shader_defs(key);
+        shader_defs.extend(self.quad_attribute_defs.iter().cloned());
+        shader_defs.extend(self.extension_defs.iter().cloned());
+
         let mesh_view_layout = {
             let idx = MeshPipelineViewLayoutKey::from(key.inner).bits() as usize;
             self.mesh_pipeline_view_layouts[idx]
@@ -135,15 +201,7 @@ impl SpecializedRenderPipeline for ChunkPipeline {
                 stages: ShaderStages::VERTEX,
                 range: 0..4,
             }],
-            primitive: PrimitiveState {
-                topology: PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: FrontFace::Ccw,
-                cull_mode: Some(Face::Back),
-                unclipped_depth: false,
-                polygon_mode: PolygonMode::Fill,
-                conservative: false,
-            },
+            primitive: key.primitive_state(),
             depth_stencil: Some(DepthStencilState {
                 format: CORE_3D_DEPTH_FORMAT,
                 depth_write_enabled: true,
@@ -184,15 +242,19 @@ pub const fn tonemapping_pipeline_key(tonemapping: Tonemapping) -> MeshPipelineK
     }
 }
 
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 pub fn queue_chunks(
     functions: Res<DrawFunctions<Opaque3d>>,
     pipeline: Res<ChunkPipeline>,
     mut pipelines: ResMut<SpecializedRenderPipelines<ChunkPipeline>>,
     pipeline_cache: Res<PipelineCache>,
     chunks: ChunkDataParams,
+    fade_settings: Res<ChunkFadeSettings>,
+    fog: Res<VoxelFog>,
+    sidedness: Res<MeshSidedness>,
     mut views: Query<(
+        Entity,
         &ExtractedView,
-        &VisibleEntities,
         &mut RenderPhase<Opaque3d>,
         Option<&Tonemapping>,
         Option<&DebandDither>,
@@ -211,8 +273,8 @@ pub fn queue_chunks(
     let draw_chunk = functions.read().id::<DrawVoxelChunk>();
 
     for (
+        view_entity,
         view,
-        visible_entities,
         mut phase,
         tonemapping,
         dither,
@@ -278,26 +340,28 @@ pub fn queue_chunks(
             }
         }
 
-        iter_visible_chunks(visible_entities, &chunks, |entity, _chunk_pos| {
+        // fading is done by dithering, so views that don't want dithering don't get faded chunks either
+        let fade = fade_settings.is_enabled() && matches!(dither, Some(DebandDither::Enabled));
+
+        for &(entity, chunk) in chunks.visible_chunks.get(view_entity) {
             let pipeline_id = pipelines.specialize(
                 pipeline_cache.as_ref(),
                 pipeline.as_ref(),
-                ChunkPipelineKey {
-                    inner: view_key
-                        | MeshPipelineKey::from_primitive_topology(PrimitiveTopology::TriangleList),
-                },
+                ChunkPipelineKey::new(view_key, chunk.mesh_key, fade)
+                    .with_fog(fog.is_active())
+                    .with_sidedness(*sidedness),
             );
 
             // queue this entity for rendering
             phase.add(Opaque3d {
-                entity: entity,
+                entity,
                 draw_function: draw_chunk,
                 pipeline: pipeline_id,
                 asset_id: AssetId::default(),
                 batch_range: 0..1,
                 dynamic_offset: None,
             });
-        });
+        }
     }
 }
 
@@ -308,3 +372,52 @@ pub type DrawVoxelChunk = (
     SetChunkBindGroup<2>,
     DrawChunk,
 );
+
+#[cfg(test)]
+mod tests {
+    use bevy::render::render_resource::Face;
+
+    use super::*;
+
+    #[test]
+    fn fade_shader_def() {
+        let key = |fade| {
+            ChunkPipelineKey::new(MeshPipelineKey::DEBAND_DITHER, MeshPipelineKey::NONE, fade)
+        };
+
+        let has_fade = |defs: Vec<ShaderDefVal>| {
+            defs.iter()
+                .any(|def| matches!(def, ShaderDefVal::Bool(name, true) if name == "CHUNK_FADE"))
+        };
+
+        assert!(has_fade(ChunkPipeline::shader_defs(key(true))));
+        assert!(!has_fade(ChunkPipeline::shader_defs(key(false))));
+    }
+
+    #[test]
+    fn fog_shader_def() {
+        let key = ChunkPipelineKey::new(MeshPipelineKey::NONE, MeshPipelineKey::NONE, false);
+
+        let has_fog = |defs: Vec<ShaderDefVal>| {
+            defs.iter().any(
+                |def| matches!(def, ShaderDefVal::Bool(name, true) if name == VoxelFog::SHADER_DEF),
+            )
+        };
+
+        assert!(has_fog(ChunkPipeline::shader_defs(key.with_fog(true))));
+        assert!(!has_fog(ChunkPipeline::shader_defs(key)));
+    }
+
+    #[test]
+    fn double_sided_disables_culling() {
+        let key = ChunkPipelineKey::new(MeshPipelineKey::NONE, MeshPipelineKey::NONE, false);
+
+        assert_eq!(Some(Face::Back), key.primitive_state().cull_mode);
+        assert_eq!(
+            None,
+            key.with_sidedness(MeshSidedness::Double)
+                .primitive_state()
+                .cull_mode
+        );
+    }
+}
@@ -5,13 +5,14 @@ use bevy::{
     },
     prelude::*,
     render::{
-        mesh::PrimitiveTopology,
         render_phase::{DrawFunctions, RenderPhase},
-        render_resource::{PipelineCache, SpecializedRenderPipeline, SpecializedRenderPipelines},
+        render_resource::{PipelineCache, SpecializedRenderPipelines},
         view::VisibleEntities,
     },
 };
 
+use crate::render::meshing::MeshSidedness;
+
 use super::{
     prepass::{ChunkPrepassPipeline, DrawVoxelChunkPrepass},
     render::ChunkPipelineKey,
@@ -20,6 +21,7 @@ use super::{
 
 // largely taken from
 // https://github.com/bevyengine/bevy/blob/main/crates/bevy_pbr/src/render/light.rs#L1590
+#[allow(clippy::too_many_arguments)]
 pub fn queue_shadows(
     shadow_draw_functions: Res<DrawFunctions<Shadow>>,
     prepass_pipeline: Res<ChunkPrepassPipeline>,
@@ -31,6 +33,7 @@ pub fn queue_shadows(
     directional_light_entities: Query<&CascadesVisibleEntities, With<ExtractedDirectionalLight>>,
     spot_light_entities: Query<&VisibleEntities, With<ExtractedPointLight>>,
     chunks: ChunkDataParams,
+    sidedness: Res<MeshSidedness>,
 ) {
     for (entity, view_lights) in &view_lights {
         let shadow_function = shadow_draw_functions.read().id::<DrawVoxelChunkPrepass>();
@@ -63,10 +66,8 @@ pub fn queue_shadows(
                     .expect("Failed to get spot light visible entities"),
             };
 
-            iter_visible_chunks(visible_entities, &chunks, |entity, _chunk_pos| {
-                let mut key =
-                    MeshPipelineKey::from_primitive_topology(PrimitiveTopology::TriangleList)
-                        | MeshPipelineKey::DEPTH_PREPASS;
+            iter_visible_chunks(visible_entities, &chunks, |entity, chunk| {
+                let mut key = MeshPipelineKey::DEPTH_PREPASS;
 
                 if is_directional_light {
                     key |= MeshPipelineKey::DEPTH_CLAMP_ORTHO;
@@ -75,13 +76,13 @@ pub fn queue_shadows(
                 let pipeline_id = pipelines.specialize(
                     &pipeline_cache,
                     &prepass_pipeline,
-                    ChunkPipelineKey { inner: key },
+                    ChunkPipelineKey::new(key, chunk.mesh_key, false).with_sidedness(*sidedness),
                 );
 
                 phase.add(Shadow {
                     draw_function: shadow_function,
                     pipeline: pipeline_id,
-                    entity: entity,
+                    entity,
                     distance: 0.0, // TODO: (bevy todo) sort front-to-back
                     batch_range: 0..1,
                     dynamic_offset: None,
@@ -1,5 +1,3 @@
-use bevy::ecs::query::QueryEntityError;
-use bevy::ecs::system::lifetimeless::Read;
 use bevy::pbr::{MeshFlags, MeshPipelineKey};
 use bevy::prelude::*;
 use bevy::render::render_resource::ShaderDefVal;
@@ -9,45 +7,26 @@ use bevy::{ecs::system::SystemParam, render::view::VisibleEntities};
 use crate::data::texture::GpuFaceTexture;
 use crate::render::occlusion::ChunkOcclusionMap;
 use crate::render::quad::GpuQuadBitfields;
-use crate::topo::world::{ChunkEntity, ChunkPos};
+use crate::topo::light::LightLevel;
 
-use super::gpu_chunk::{ChunkRenderData, ChunkRenderDataStore};
+use super::gpu_chunk::{GpuChunk, GpuChunkEntities, VisibleChunks};
 
 #[derive(SystemParam)]
-pub struct ChunkDataParams<'w, 's> {
-    pub chunk_entities: Query<'w, 's, Read<ChunkPos>, With<ChunkEntity>>,
-    pub chunk_data_store: Res<'w, ChunkRenderDataStore>,
+pub struct ChunkDataParams<'w> {
+    pub gpu_chunks: Res<'w, GpuChunkEntities>,
+    pub visible_chunks: Res<'w, VisibleChunks>,
 }
 
-pub fn iter_visible_chunks<'w, 's, F>(
+pub fn iter_visible_chunks<'w, F>(
     visible: &VisibleEntities,
-    chunk_data_params: &ChunkDataParams<'w, 's>,
-    mut f: F,
+    chunk_data_params: &ChunkDataParams<'w>,
+    f: F,
 ) where
-    F: FnMut(Entity, ChunkPos),
+    F: FnMut(Entity, GpuChunk),
 {
-    for entity in &visible.entities {
-        // Extract chunk position for this entity, and skip all entities that don't match the query.
-        let chunk_pos = match chunk_data_params.chunk_entities.get(*entity) {
-            Ok(chunk_pos) => *chunk_pos,
-            Err(QueryEntityError::QueryDoesNotMatch(_)) => continue,
-            Err(QueryEntityError::NoSuchEntity(_)) => continue,
-
-            _ => panic!("Unexpected result when getting chunk position from entity"),
-        };
-
-        // Skip chunks that don't have chunk render data on the GPU
-        if !chunk_data_params
-            .chunk_data_store
-            .map
-            .get(chunk_pos)
-            .is_some_and(|data| matches!(data.data, ChunkRenderData::Gpu(_)))
-        {
-            continue;
-        }
-
-        f(*entity, chunk_pos);
-    }
+    chunk_data_params
+        .gpu_chunks
+        .for_each_visible(&visible.entities, f);
 }
 
 pub fn main_world_res_exists<T: Resource>(res: Extract<Option<Res<T>>>) -> bool {
@@ -58,33 +37,21 @@ pub fn u32_shader_def(name: &str, value: u32) -> ShaderDefVal {
     ShaderDefVal::UInt(name.into(), value)
 }
 
+/// Add the constants shared between the chunk pipelines and their shaders. Every pipeline must get
+/// its constants from here so that they can't drift apart.
 pub fn add_shader_constants(shader_defs: &mut Vec<ShaderDefVal>) {
-    let shader_constants = [
-        u32_shader_def("ROTATION_MASK", GpuQuadBitfields::ROTATION_MASK),
-        u32_shader_def("ROTATION_SHIFT", GpuQuadBitfields::ROTATION_SHIFT),
-        u32_shader_def("FACE_MASK", GpuQuadBitfields::FACE_MASK),
-        u32_shader_def("FACE_SHIFT", GpuQuadBitfields::FACE_SHIFT),
-        u32_shader_def("FLIP_UV_X_BIT", GpuQuadBitfields::FLIP_UV_X_BIT),
-        u32_shader_def("FLIP_UV_Y_BIT", GpuQuadBitfields::FLIP_UV_Y_BIT),
-        u32_shader_def("HAS_NORMAL_MAP_BIT", GpuFaceTexture::HAS_NORMAL_MAP_BIT),
-        u32_shader_def(
-            "CHUNK_OCCLUSION_BUFFER_SIZE",
-            ChunkOcclusionMap::GPU_BUFFER_SIZE,
-        ),
-        u32_shader_def(
-            "CHUNK_OCCLUSION_BUFFER_DIMENSIONS",
-            ChunkOcclusionMap::GPU_BUFFER_DIMENSIONS,
-        ),
-        u32_shader_def("HAS_NORMAL_MAP_BIT", GpuFaceTexture::HAS_NORMAL_MAP_BIT),
-        u32_shader_def(
-            "DEFAULT_PBR_INPUT_FLAGS",
-            (MeshFlags::SHADOW_RECEIVER | MeshFlags::TRANSMITTED_SHADOW_RECEIVER).bits(),
-        ),
-    ];
-
-    shader_defs.extend_from_slice(&shader_constants);
+    shader_defs.extend(GpuQuadBitfields::shader_defs());
+    shader_defs.extend(GpuFaceTexture::shader_defs());
+    shader_defs.extend(ChunkOcclusionMap::shader_defs());
+    shader_defs.extend(LightLevel::shader_defs());
+    shader_defs.push(u32_shader_def(
+        "DEFAULT_PBR_INPUT_FLAGS",
+        (MeshFlags::SHADOW_RECEIVER | MeshFlags::TRANSMITTED_SHADOW_RECEIVER).bits(),
+    ));
 }
 
+// copied from bevy's mesh pipeline, this crate doesn't have the feature so the define is never added
+#[allow(unexpected_cfgs)]
 pub fn add_mesh_pipeline_shader_defs(key: MeshPipelineKey, shader_defs: &mut Vec<ShaderDefVal>) {
     if cfg!(feature = "pbr_transmission_textures") {
         shader_defs.push("PBR_TRANSMISSION_TEXTURES_SUPPORTED".into());
@@ -203,3 +170,65 @@ pub fn add_mesh_pipeline_shader_defs(key: MeshPipelineKey, shader_defs: &mut Vec
     shader_defs.push("MULTIPLE_LIGHT_PROBES_IN_ARRAY".into());
     shader_defs.push("IRRADIANCE_VOLUMES_ARE_USABLE".into());
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::render::core::{
+        prepass::ChunkPrepassPipeline,
+        render::{ChunkPipeline, ChunkPipelineKey},
+    };
+
+    use super::*;
+
+    fn shader_def_name(def: &ShaderDefVal) -> &str {
+        match def {
+            ShaderDefVal::Bool(name, _)
+            | ShaderDefVal::Int(name, _)
+            | ShaderDefVal::UInt(name, _) => name,
+        }
+    }
+
+    #[test]
+    fn shader_constants_are_unique() {
+        let mut shader_defs = vec![];
+        add_shader_constants(&mut shader_defs);
+
+        let names = shader_defs.iter().map(shader_def_name).collect::<Vec<_>>();
+        let unique = names.iter().collect::<hb::HashSet<_>>();
+
+        assert_eq!(names.len(), unique.len());
+    }
+
+    #[test]
+    fn pipelines_share_bitfield_constants() {
+        let key =
+            ChunkPipelineKey::new(MeshPipelineKey::DEPTH_PREPASS, MeshPipelineKey::NONE, true);
+
+        // the constants are the only unsigned shader defs, the rest are flags
+        let constants = |defs: Vec<ShaderDefVal>| {
+            defs.into_iter()
+                .filter(|def| matches!(def, ShaderDefVal::UInt(..)))
+                .collect::<Vec<_>>()
+        };
+
+        let main = constants(ChunkPipeline::shader_defs(key));
+        let prepass = constants(ChunkPrepassPipeline::shader_defs(key));
+
+        for def in GpuQuadBitfields::shader_defs() {
+            assert!(main.contains(&def), "main pass is missing {def:?}");
+        }
+
+        assert_eq!(main, prepass);
+    }
+
+    #[test]
+    fn face_material_constants_are_shader_constants() {
+        let mut shader_defs = vec![];
+        add_shader_constants(&mut shader_defs);
+
+        let names = shader_defs.iter().map(shader_def_name).collect::<Vec<_>>();
+        for name in ["MATERIAL_CHANNEL_MASK", "ROUGHNESS_SHIFT", "METALLIC_SHIFT"] {
+            assert!(names.contains(&name), "missing shader constant {name}");
+        }
+    }
+}
@@ -0,0 +1,376 @@
+use bevy::{prelude::*, render::primitives::Aabb};
+
+use crate::{
+    render::quad::GpuQuad,
+    topo::{
+        block::SubdividedBlock,
+        bounding_box::BoundingBox,
+        controller::ChunkEcsPermits,
+        ivec_project_to_2d,
+        world::{Chunk, ChunkEntity, ChunkPos},
+        ChunkObserver,
+    },
+    util::{ChunkMap, ChunkSet},
+};
+
+use super::{ChunkMeshData, ChunkMeshStatus, ExtractableChunkMeshData, TimedChunkMeshData};
+
+#[derive(Copy, Clone, Resource, Debug)]
+pub struct MeshBatchSettings {
+    /// Chunks further than this many chunks away from every observer can be batched.
+    pub min_distance: f32,
+    /// Only chunk meshes with at most this many quads are batched.
+    pub max_quads: usize,
+    /// The side length (in chunks) of the cubic regions that chunk meshes are batched in.
+    pub region_size: i32,
+}
+
+impl Default for MeshBatchSettings {
+    fn default() -> Self {
+        Self {
+            min_distance: 12.0,
+            max_quads: 256,
+            region_size: 2,
+        }
+    }
+}
+
+impl MeshBatchSettings {
+    /// The position of the chunk at the minimum corner of the region that `pos` is in.
+    /// The super-chunk mesh of a region is drawn in place of this chunk's mesh.
+    pub fn region_origin(&self, pos: ChunkPos) -> ChunkPos {
+        let size = IVec3::splat(self.region_size);
+        ChunkPos::from(pos.as_ivec3().div_euclid(size) * size)
+    }
+
+    pub fn region(&self, origin: ChunkPos) -> BoundingBox {
+        BoundingBox::from_min_max(
+            origin.as_ivec3(),
+            origin.as_ivec3() + IVec3::splat(self.region_size),
+        )
+    }
+
+    /// Whether the region with the given origin is far enough away from every observer to be batched.
+    pub fn is_distant(&self, origin: ChunkPos, observers: &[Vec3]) -> bool {
+        const CHUNK_SIZE_F32: f32 = Chunk::SIZE as f32;
+
+        let center =
+            (origin.as_vec3() + Vec3::splat(self.region_size as f32 / 2.0)) * CHUNK_SIZE_F32;
+        let distance = self.min_distance * CHUNK_SIZE_F32;
+
+        observers
+            .iter()
+            .all(|observer| observer.distance_squared(center) > distance * distance)
+    }
+}
+
+/// Move a quad by the given offset (in blocks).
+fn offset_quad(quad: GpuQuad, offset: IVec3) -> GpuQuad {
+    let face = quad.bitfields.get_face();
+    let offset_2d = ivec_project_to_2d(offset, face).as_vec2();
+    let offset_mag = face.axis().choose(offset.as_vec3()) as i32 * SubdividedBlock::SUBDIVISIONS;
+
+    GpuQuad {
+        min: quad.min + offset_2d,
+        max: quad.max + offset_2d,
+        magnitude: quad.magnitude + offset_mag,
+        ..quad
+    }
+}
+
+/// Merge the meshes of several chunks into one mesh in the localspace of the chunk at `origin`.
+pub fn merge_chunk_meshes<'a>(
+    origin: ChunkPos,
+    meshes: impl IntoIterator<Item = (ChunkPos, &'a ChunkMeshData)>,
+) -> ChunkMeshData {
+    let mut merged = ChunkMeshData {
+        index_buffer: Vec::new(),
+        quad_buffer: Vec::new(),
+    };
+
+    for (pos, mesh) in meshes {
+        let offset = (pos - origin) * Chunk::SIZE;
+        // every quad has 4 vertices
+        let index_offset = merged.quad_buffer.len() as u32 * 4;
+
+        merged
+            .index_buffer
+            .extend(mesh.index_buffer.iter().map(|&idx| idx + index_offset));
+        merged.quad_buffer.extend(
+            mesh.quad_buffer
+                .iter()
+                .map(|&quad| offset_quad(quad, offset)),
+        );
+    }
+
+    merged
+}
+
+/// The chunks that were merged into a super-chunk mesh.
+#[derive(Clone, Debug)]
+struct MeshBatch {
+    members: Vec<ChunkPos>,
+}
+
+/// Bookkeeping for super-chunk meshes. Batched regions are keyed by their origin, see [`MeshBatchSettings::region_origin`].
+#[derive(Resource, Default)]
+pub struct MeshBatches {
+    /// The most recent meshes of chunks that are small enough to be batched (including empty ones).
+    /// Used to build super-chunk meshes, and to restore the original meshes when a super-chunk mesh is split.
+    small: ChunkMap<ChunkMeshData>,
+    batches: ChunkMap<MeshBatch>,
+}
+
+impl MeshBatches {
+    pub fn is_batched(&self, origin: ChunkPos) -> bool {
+        self.batches.get(origin).is_some()
+    }
+
+    /// Split the super-chunk mesh of a region back into the meshes of its members.
+    /// Members that got a new mesh since they were batched keep that mesh.
+    fn split(
+        &mut self,
+        origin: ChunkPos,
+        meshes: &mut ExtractableChunkMeshData,
+        permits: &ChunkEcsPermits,
+        chunks: &mut Query<&mut Aabb, With<ChunkEntity>>,
+    ) {
+        let Some(batch) = self.batches.remove(origin) else {
+            return;
+        };
+
+        for pos in batch.members {
+            if meshes.removed.contains(&pos) {
+                continue;
+            }
+
+            let Some(entry) = meshes.active.get_mut(pos) else {
+                continue;
+            };
+
+            if !matches!(entry.data, ChunkMeshStatus::Extracted) {
+                continue;
+            }
+
+            if let Some(mesh) = self.small.get(pos) {
+                entry.data = ChunkMeshStatus::from_mesh_data(mesh);
+            }
+        }
+
+        set_chunk_aabb(permits, chunks, origin, Chunk::BOUNDING_BOX.to_aabb());
+    }
+}
+
+fn set_chunk_aabb(
+    permits: &ChunkEcsPermits,
+    chunks: &mut Query<&mut Aabb, With<ChunkEntity>>,
+    pos: ChunkPos,
+    aabb: Aabb,
+) {
+    let Some(entity) = permits.get_entity(pos) else {
+        return;
+    };
+
+    if let Ok(mut chunk_aabb) = chunks.get_mut(entity) {
+        *chunk_aabb = aabb;
+    }
+}
+
+/// This system merges the meshes of small, distant chunks into super-chunk meshes to reduce the number of
+/// draw calls. The super-chunk mesh of a region is drawn in place of the mesh of the chunk at the region's origin,
+/// and the meshes of the other chunks in the region are hidden. Super-chunk meshes are split back into the
+/// original meshes when an observer comes close, or when any of the chunks in them are remeshed.
+pub fn batch_distant_chunk_meshes(
+    settings: Res<MeshBatchSettings>,
+    observers: Query<&Transform, With<ChunkObserver>>,
+    mut meshes: ResMut<ExtractableChunkMeshData>,
+    mut batches: ResMut<MeshBatches>,
+    permits: Res<ChunkEcsPermits>,
+    mut chunks: Query<&mut Aabb, With<ChunkEntity>>,
+) {
+    let observers = observers
+        .iter()
+        .map(|trans| trans.translation)
+        .collect::<Vec<_>>();
+
+    let mut split = ChunkSet::default();
+    let mut candidates = ChunkSet::default();
+
+    // chunks that were just (re)meshed or removed invalidate the batch they're in
+    let removed = meshes.removed.clone();
+    for pos in removed {
+        batches.small.remove(pos);
+        split.set(settings.region_origin(pos));
+    }
+
+    meshes.active.for_each_entry(|pos, entry| {
+        let mesh = match &entry.data {
+            ChunkMeshStatus::Filled(data) if data.quad_buffer.len() <= settings.max_quads => {
+                Some(data.clone())
+            }
+            ChunkMeshStatus::Empty => Some(ChunkMeshData {
+                index_buffer: Vec::new(),
+                quad_buffer: Vec::new(),
+            }),
+            ChunkMeshStatus::Filled(_) => None,
+            ChunkMeshStatus::Unfulfilled | ChunkMeshStatus::Extracted => return,
+        };
+
+        match mesh {
+            Some(mesh) => batches.small.set(pos, mesh),
+            None => batches.small.remove(pos),
+        };
+
+        let origin = settings.region_origin(pos);
+        split.set(origin);
+        candidates.set(origin);
+    });
+
+    // split batches that were invalidated or that an observer came close to
+    let mut near = Vec::new();
+    batches.batches.for_each_entry(|origin, _| {
+        if !settings.is_distant(origin, &observers) {
+            near.push(origin);
+        }
+    });
+
+    for origin in split.iter().chain(near) {
+        batches.split(origin, &mut meshes, &permits, &mut chunks);
+    }
+
+    // regions with small chunks that were remeshed or moved out of range are candidates for batching
+    batches.small.for_each_entry(|pos, _| {
+        candidates.set(settings.region_origin(pos));
+    });
+
+    for origin in candidates.iter() {
+        if batches.is_batched(origin) || !settings.is_distant(origin, &observers) {
+            continue;
+        }
+
+        // the super-chunk mesh is drawn in place of the origin chunk's mesh, so the origin chunk needs to be meshed
+        if meshes.active.get(origin).is_none() {
+            continue;
+        }
+
+        let region = settings.region(origin);
+
+        // every chunk with a mesh in the region needs to be small enough for the region to be batched
+        if !region.cartesian_iter().all(|pos| {
+            let pos = ChunkPos::from(pos);
+            meshes.active.get(pos).is_none() || batches.small.get(pos).is_some()
+        }) {
+            continue;
+        }
+
+        let members = region
+            .cartesian_iter()
+            .map(ChunkPos::from)
+            .filter(|&pos| meshes.active.get(pos).is_some())
+            .collect::<Vec<_>>();
+
+        let non_empty = members
+            .iter()
+            .filter(|&&pos| batches.small.get(pos).is_some_and(|mesh| !mesh.is_empty()))
+            .count();
+
+        if non_empty < 2 {
+            continue;
+        }
+
+        let merged = merge_chunk_meshes(
+            origin,
+            members
+                .iter()
+                .filter_map(|&pos| batches.small.get(pos).map(|mesh| (pos, mesh))),
+        );
+
+        // the super-chunk mesh replaces the mesh of the origin chunk, so it needs to be at least as new
+        // as all the meshes it was built from
+        let generation = members
+            .iter()
+            .filter_map(|&pos| meshes.active.get(pos).map(|entry| entry.generation))
+            .max()
+            .unwrap_or_default();
+
+        for &pos in &members {
+            if pos == origin {
+                continue;
+            }
+
+            if let Some(entry) = meshes.active.get_mut(pos) {
+                entry.data = ChunkMeshStatus::Extracted;
+            }
+            meshes.removed.push(pos);
+        }
+
+        if let Some(aabb) = merged.aabb() {
+            set_chunk_aabb(&permits, &mut chunks, origin, aabb);
+        }
+
+        meshes.active.set(
+            origin,
+            TimedChunkMeshData {
+                generation,
+                data: ChunkMeshStatus::Filled(merged),
+            },
+        );
+
+        batches.batches.set(origin, MeshBatch { members });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::ivec2;
+
+    use crate::{data::tile::Face, render::quad::GpuQuadBitfields};
+
+    use super::*;
+
+    /// A mesh with a single quad on top of the block at `pos`.
+    fn top_quad_mesh(pos: IVec2, magnitude: i32) -> ChunkMeshData {
+        ChunkMeshData {
+            index_buffer: vec![0, 1, 2, 2, 1, 3],
+            quad_buffer: vec![GpuQuad {
+                texture_id: 0,
+                bitfields: GpuQuadBitfields::new().with_face(Face::Top),
+                min: pos.as_vec2(),
+                max: pos.as_vec2() + Vec2::ONE,
+                magnitude,
+                light: 0,
+            }],
+        }
+    }
+
+    #[test]
+    fn merge_four_chunk_meshes() {
+        let meshes = [
+            (ChunkPos::new(0, 0, 0), top_quad_mesh(ivec2(0, 0), 4)),
+            (ChunkPos::new(1, 0, 0), top_quad_mesh(ivec2(15, 0), 4)),
+            (ChunkPos::new(0, 0, 1), top_quad_mesh(ivec2(0, 15), 64)),
+            (ChunkPos::new(1, 0, 1), top_quad_mesh(ivec2(15, 15), 64)),
+        ];
+
+        let merged = merge_chunk_meshes(
+            ChunkPos::ZERO,
+            meshes.iter().map(|(pos, mesh)| (*pos, mesh)),
+        );
+
+        assert_eq!(
+            meshes
+                .iter()
+                .map(|(_, mesh)| mesh.quad_buffer.len())
+                .sum::<usize>(),
+            merged.quad_buffer.len()
+        );
+        assert_eq!(
+            vec![0, 1, 2, 2, 1, 3, 4, 5, 6, 6, 5, 7],
+            merged.index_buffer[..12]
+        );
+
+        let aabb = merged.aabb().unwrap();
+        assert_eq!(Vec3::new(0.0, 1.0, 0.0), Vec3::from(aabb.min()));
+        assert_eq!(Vec3::new(32.0, 16.0, 32.0), Vec3::from(aabb.max()));
+    }
+}
@@ -0,0 +1,224 @@
+use std::time::Duration;
+
+use bevy::{
+    diagnostic::{Diagnostic, DiagnosticPath, Diagnostics, RegisterDiagnostic},
+    prelude::*,
+};
+
+use crate::topo::worldgen::GeneratedChunk;
+
+use super::workers::{FinishedChunkData, MeshBuilder};
+
+/// The paths of the meshing diagnostics in Bevy's [`DiagnosticsStore`](bevy::diagnostic::DiagnosticsStore),
+/// so meshing can be profiled with the usual diagnostic tools (like the
+/// [`LogDiagnosticsPlugin`](bevy::diagnostic::LogDiagnosticsPlugin)).
+pub struct MeshingDiagnostics;
+
+impl MeshingDiagnostics {
+    /// The total number of chunk meshes finished since startup.
+    pub const MESHES_COMPLETED: DiagnosticPath =
+        DiagnosticPath::const_new("meshing/meshes_completed");
+    pub const MESHES_PER_SECOND: DiagnosticPath =
+        DiagnosticPath::const_new("meshing/meshes_per_second");
+    /// The average time it took to build the meshes finished this frame, in milliseconds.
+    pub const MESH_TIME: DiagnosticPath = DiagnosticPath::const_new("meshing/mesh_time");
+    /// The number of chunks that are queued for meshing or being meshed, see [`MeshBuilder::pending_count`].
+    pub const PENDING: DiagnosticPath = DiagnosticPath::const_new("meshing/pending");
+    pub const QUADS_PER_SECOND: DiagnosticPath =
+        DiagnosticPath::const_new("meshing/quads_per_second");
+    /// The total number of chunks generated since startup.
+    pub const CHUNKS_GENERATED: DiagnosticPath =
+        DiagnosticPath::const_new("worldgen/chunks_generated");
+    pub const CHUNKS_GENERATED_PER_SECOND: DiagnosticPath =
+        DiagnosticPath::const_new("worldgen/chunks_generated_per_second");
+
+    pub(crate) fn register(app: &mut App) {
+        app.init_resource::<MeshingStats>()
+            .add_event::<GeneratedChunk>()
+            .register_diagnostic(Diagnostic::new(Self::MESHES_COMPLETED).with_smoothing_factor(0.0))
+            .register_diagnostic(Diagnostic::new(Self::MESHES_PER_SECOND))
+            .register_diagnostic(Diagnostic::new(Self::MESH_TIME).with_suffix("ms"))
+            .register_diagnostic(Diagnostic::new(Self::PENDING).with_smoothing_factor(0.0))
+            .register_diagnostic(Diagnostic::new(Self::QUADS_PER_SECOND))
+            .register_diagnostic(Diagnostic::new(Self::CHUNKS_GENERATED).with_smoothing_factor(0.0))
+            .register_diagnostic(Diagnostic::new(Self::CHUNKS_GENERATED_PER_SECOND));
+    }
+}
+
+/// The meshes finished (and chunks generated) since the meshing diagnostics were last measured.
+#[derive(Resource, Default, Debug)]
+pub struct MeshingStats {
+    /// The total number of meshes finished since startup.
+    pub total: u64,
+    /// The total number of chunks generated since startup.
+    pub generated_total: u64,
+    meshes: u64,
+    quads: u64,
+    mesh_time: Duration,
+    generated: u64,
+}
+
+impl MeshingStats {
+    pub(crate) fn record(&mut self, mesh: &FinishedChunkData) {
+        self.total += 1;
+        self.meshes += 1;
+        self.quads += mesh.data.quad_buffer.len() as u64;
+        self.mesh_time += mesh.duration;
+    }
+}
+
+/// Measure the meshing diagnostics, see [`MeshingDiagnostics`].
+pub fn measure_meshing_diagnostics(
+    mut diagnostics: Diagnostics,
+    mut stats: ResMut<MeshingStats>,
+    mut generated: EventReader<GeneratedChunk>,
+    builder: Option<Res<MeshBuilder>>,
+    time: Res<Time<Real>>,
+) {
+    let count = generated.read().count() as u64;
+    stats.generated += count;
+    stats.generated_total += count;
+
+    let total = stats.total;
+    diagnostics.add_measurement(&MeshingDiagnostics::MESHES_COMPLETED, || total as f64);
+    let generated_total = stats.generated_total;
+    diagnostics.add_measurement(&MeshingDiagnostics::CHUNKS_GENERATED, || {
+        generated_total as f64
+    });
+
+    // nothing is pending once the builder is shut down
+    let pending = builder.map_or(0, |builder| builder.pending_count());
+    diagnostics.add_measurement(&MeshingDiagnostics::PENDING, || pending as f64);
+
+    let meshes = std::mem::take(&mut stats.meshes);
+    let quads = std::mem::take(&mut stats.quads);
+    let mesh_time = std::mem::take(&mut stats.mesh_time);
+    let generated = std::mem::take(&mut stats.generated);
+
+    if meshes > 0 {
+        diagnostics.add_measurement(&MeshingDiagnostics::MESH_TIME, || {
+            mesh_time.as_secs_f64() * 1000.0 / meshes as f64
+        });
+    }
+
+    let delta_seconds = time.delta_seconds_f64();
+    if delta_seconds == 0.0 {
+        return;
+    }
+
+    diagnostics.add_measurement(&MeshingDiagnostics::MESHES_PER_SECOND, || {
+        meshes as f64 / delta_seconds
+    });
+    diagnostics.add_measurement(&MeshingDiagnostics::QUADS_PER_SECOND, || {
+        quads as f64 / delta_seconds
+    });
+    diagnostics.add_measurement(&MeshingDiagnostics::CHUNKS_GENERATED_PER_SECOND, || {
+        generated as f64 / delta_seconds
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use bevy::{diagnostic::DiagnosticsStore, tasks::TaskPoolBuilder};
+
+    use crate::{
+        render::meshing::controller::{
+            ecs::{insert_chunks, FinishedMeshBacklog, MeshApplySettings},
+            workers::{MeshBackend, MeshBuilderSettings, MeshCommand},
+            ExtractableChunkMeshData, MeshQuality, RemeshPriority,
+        },
+        testing_utils::mock_registries,
+        topo::{
+            neighbors::NeighborSet,
+            world::{ChunkManager, ChunkPos},
+            worldgen::GenerationPriority,
+        },
+    };
+
+    use super::*;
+
+    #[test]
+    fn meshes_completed_diagnostic() {
+        let positions = [
+            ChunkPos::new(0, 0, 0),
+            ChunkPos::new(1, 0, 0),
+            ChunkPos::new(0, -1, 0),
+            ChunkPos::new(-1, 0, 2),
+        ];
+
+        let cm = ChunkManager::new_test();
+        for pos in positions {
+            cm.insert_test_chunk(pos, |_| ());
+        }
+
+        let registries = mock_registries();
+
+        let task_pool = TaskPoolBuilder::new().num_threads(1).build();
+        let mut builder = MeshBuilder::new(
+            MeshBuilderSettings {
+                backend: MeshBackend::MainThread {
+                    budget: Duration::from_secs(60),
+                },
+                ..MeshBuilderSettings::new_test()
+            },
+            &task_pool,
+            registries,
+            Arc::new(cm),
+        );
+
+        // the last chunk isn't loaded, so meshing it fails. It shouldn't be pending forever
+        let unloaded = ChunkPos::new(50, 0, 0);
+        builder.queue_jobs(
+            positions
+                .into_iter()
+                .chain([unloaded])
+                .map(|pos| MeshCommand {
+                    pos,
+                    priority: RemeshPriority::HIGHEST,
+                    generation: 0,
+                    quality: MeshQuality::High,
+                    stitched: NeighborSet::EMPTY,
+                }),
+        );
+
+        let mut app = App::new();
+        app.init_resource::<Time<Real>>()
+            .init_resource::<ExtractableChunkMeshData>()
+            .init_resource::<FinishedMeshBacklog>()
+            .init_resource::<MeshApplySettings>()
+            .insert_resource(builder)
+            .add_systems(Update, (insert_chunks, measure_meshing_diagnostics).chain());
+        MeshingDiagnostics::register(&mut app);
+
+        app.world
+            .send_event_batch(positions.map(|pos| GeneratedChunk {
+                pos,
+                priority: GenerationPriority::HIGHEST,
+            }));
+
+        // the jobs run on the main thread, so they're all done after one update
+        app.update();
+        assert_eq!(0, app.world.resource::<MeshBuilder>().pending_count());
+
+        let store = app.world.resource::<DiagnosticsStore>();
+        let value = |path: &DiagnosticPath| store.get(path).and_then(Diagnostic::value);
+
+        assert_eq!(
+            Some(positions.len() as f64),
+            value(&MeshingDiagnostics::MESHES_COMPLETED)
+        );
+        assert_eq!(Some(0.0), value(&MeshingDiagnostics::PENDING));
+        assert_eq!(
+            Some(positions.len() as f64),
+            value(&MeshingDiagnostics::CHUNKS_GENERATED)
+        );
+        assert!(value(&MeshingDiagnostics::MESH_TIME).is_some_and(|time| time >= 0.0));
+
+        app.world
+            .remove_resource::<MeshBuilder>()
+            .unwrap()
+            .shutdown();
+    }
+}
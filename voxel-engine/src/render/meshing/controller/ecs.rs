@@ -1,15 +1,20 @@
-use std::{cmp::max, time::Duration};
+use std::{cmp::max, collections::VecDeque, time::Duration};
 
 use bevy::{
+    math::{Affine3A, Vec3A},
     prelude::*,
+    render::primitives::{Aabb, Frustum},
     tasks::{available_parallelism, TaskPool, TaskPoolBuilder},
 };
 
-use itertools::Itertools;
-
 use crate::{
-    data::{registries::Registries, tile::Face},
-    render::meshing::controller::workers::MeshBuilderSettings,
+    data::registries::Registries,
+    render::meshing::{
+        controller::workers::{MeshBackend, MeshBuilderSettings},
+        greedy::algorithm::{MergeAxisPolicy, QuadTriangulation},
+        lighting::LightingMode,
+        MeshSidedness,
+    },
     topo::{
         controller::{PermitFlags, UpdatePermitEvent},
         world::{chunk::ChunkFlags, Chunk, ChunkPos, VoxelRealm},
@@ -19,9 +24,10 @@ use crate::{
 };
 
 use super::{
-    workers::{MeshBuilder, MeshCommand},
-    ChunkMeshStatus, ChunkRenderPermit, ExtractableChunkMeshData, RemeshPriority, RemeshType,
-    TimedChunkMeshData,
+    diagnostics::MeshingStats,
+    lod::{lod_observer_positions, ForceLowQuality, MeshLodSettings, MeshQualities, MeshQuality},
+    workers::{FinishedChunkData, MeshBuilder, MeshCommand},
+    ChunkMeshStatus, ExtractableChunkMeshData, RemeshPriority, RemeshType, TimedChunkMeshData,
 };
 
 #[derive(Resource, Deref)]
@@ -36,6 +42,7 @@ pub struct RemeshChunk {
     pub remesh_type: RemeshType,
     pub priority: RemeshPriority,
     pub generation: u64,
+    pub quality: MeshQuality,
 }
 
 /// This system queues meshing jobs in the mesh builder from `RemeshChunk` events.
@@ -43,20 +50,26 @@ pub fn queue_chunk_mesh_jobs(
     mut builder: ResMut<MeshBuilder>,
     mut events: EventReader<RemeshChunk>,
     mut current_generation: ResMut<MeshGeneration>,
+    lod_settings: Res<MeshLodSettings>,
+    qualities: Res<MeshQualities>,
+    observers: Query<(&Transform, Has<ForceLowQuality>), With<ChunkObserver>>,
 ) {
-    if events.len() > 0 {
+    if !events.is_empty() {
         current_generation.0 += 1;
         debug!("Queuing {} chunks for remeshing from events", events.len());
     }
 
     let mut commands = Vec::<MeshCommand>::with_capacity(events.len());
     let mut immediate = Vec::<MeshCommand>::new();
+    let positions = lod_observer_positions(&observers);
 
     for event in events.read() {
         let cmd = MeshCommand {
             pos: event.pos,
             priority: event.priority,
             generation: event.generation,
+            quality: event.quality,
+            stitched: qualities.stitched(event.pos, event.quality, &lod_settings, &positions),
         };
 
         match event.remesh_type {
@@ -72,19 +85,137 @@ pub fn queue_chunk_mesh_jobs(
     }
 }
 
+/// Queue the meshing tasks that were lost again, so their chunks don't go missing forever.
+pub fn requeue_stale_mesh_jobs(mut builder: ResMut<MeshBuilder>) {
+    let timeout = builder.stale_timeout();
+    let stale = builder.reap_stale_tasks(timeout);
+
+    if !stale.is_empty() {
+        builder.queue_jobs(stale.into_iter());
+    }
+}
+
+#[derive(Copy, Clone, Resource, Debug)]
+pub struct MeshApplySettings {
+    /// At most this many finished meshes are made available to the renderer per frame. Uploading and drawing a
+    /// big batch of new meshes in one frame (like after teleporting) causes a hitch, so the rest are kept in the
+    /// [`FinishedMeshBacklog`] for the following frames.
+    pub max_meshes_per_frame: usize,
+}
+
+impl Default for MeshApplySettings {
+    fn default() -> Self {
+        Self {
+            max_meshes_per_frame: 64,
+        }
+    }
+}
+
+/// Finished meshes that haven't been made available to the renderer yet, see [`MeshApplySettings`].
+/// There's at most one mesh per chunk, the one with the newest generation.
+#[derive(Resource, Default)]
+pub struct FinishedMeshBacklog {
+    /// The chunks in the order their meshes finished. Can contain chunks that were removed from the backlog.
+    order: VecDeque<ChunkPos>,
+    meshes: ChunkMap<FinishedChunkData>,
+}
+
+impl FinishedMeshBacklog {
+    pub fn len(&self) -> usize {
+        self.meshes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.meshes.len() == 0
+    }
+
+    /// Add a finished mesh to the backlog. If the chunk already has a mesh in the backlog, the mesh with the
+    /// newer generation is kept, in the place of the mesh that finished first.
+    pub fn push(&mut self, mesh: FinishedChunkData) {
+        match self.meshes.get_mut(mesh.pos) {
+            Some(existing) => {
+                if existing.generation <= mesh.generation {
+                    *existing = mesh;
+                }
+            }
+            None => {
+                self.order.push_back(mesh.pos);
+                self.meshes.set(mesh.pos, mesh);
+            }
+        }
+    }
+
+    /// Remove the mesh of the chunk at `pos` from the backlog, for chunks that aren't rendered anymore.
+    pub fn remove(&mut self, pos: ChunkPos) -> Option<FinishedChunkData> {
+        self.meshes.remove(pos)
+    }
+
+    /// Take the first `count` meshes out of the backlog, in the order they finished.
+    fn take(&mut self, count: usize) -> Vec<FinishedChunkData> {
+        let mut taken = Vec::with_capacity(count);
+
+        while taken.len() < count {
+            let Some(pos) = self.order.pop_front() else {
+                break;
+            };
+
+            // removed chunks are skipped
+            if let Some(mesh) = self.meshes.remove(pos) {
+                taken.push(mesh);
+            }
+        }
+
+        taken
+    }
+}
+
+impl Extend<FinishedChunkData> for FinishedMeshBacklog {
+    fn extend<T: IntoIterator<Item = FinishedChunkData>>(&mut self, iter: T) {
+        for mesh in iter {
+            self.push(mesh);
+        }
+    }
+}
+
 /// This system makes finished chunk meshes available for extraction by the renderer.
-pub fn insert_chunks(workers: Res<MeshBuilder>, mut meshes: ResMut<ExtractableChunkMeshData>) {
-    let mut total = 0;
+pub fn insert_chunks(
+    mut workers: ResMut<MeshBuilder>,
+    mut backlog: ResMut<FinishedMeshBacklog>,
+    settings: Res<MeshApplySettings>,
+    mut meshes: ResMut<ExtractableChunkMeshData>,
+    mut stats: ResMut<MeshingStats>,
+) {
+    backlog.extend(workers.get_finished_meshes());
 
-    let finished = workers.get_finished_meshes();
+    let total = apply_finished_meshes(
+        &mut backlog,
+        settings.max_meshes_per_frame,
+        &mut meshes,
+        &mut stats,
+    );
 
-    if finished.len() > 0 {
-        debug!("Inserting finished chunk meshes");
+    if total > 0 {
+        debug!(
+            "Inserted {} chunks, {} left in backlog",
+            total,
+            backlog.len()
+        );
     }
+}
+
+/// Insert at most `budget` meshes from the backlog, in the order they finished. Returns how many were inserted.
+fn apply_finished_meshes(
+    backlog: &mut FinishedMeshBacklog,
+    budget: usize,
+    meshes: &mut ExtractableChunkMeshData,
+    stats: &mut MeshingStats,
+) -> usize {
+    let taken = backlog.take(budget);
+    let count = taken.len();
 
     let mut insert = ChunkMap::<TimedChunkMeshData>::new();
-    for mesh in finished.into_iter() {
-        total += 1;
+    for mesh in taken {
+        stats.record(&mesh);
 
         let Some(existing) = meshes.active.get(mesh.pos) else {
             insert.set(
@@ -114,19 +245,24 @@ pub fn insert_chunks(workers: Res<MeshBuilder>, mut meshes: ResMut<ExtractableCh
         meshes.active.set(pos, chunk_data.clone());
     });
 
-    if total > 0 {
-        debug!("Inserted {} chunks", total);
-    }
+    count
 }
 
 /// Remove the extracted chunks from the render world when their render permits are revoked
 pub fn remove_chunks(
     mut meshes: ResMut<ExtractableChunkMeshData>,
+    mut qualities: ResMut<MeshQualities>,
+    mut builder: ResMut<MeshBuilder>,
+    mut backlog: ResMut<FinishedMeshBacklog>,
     mut events: EventReader<UpdatePermitEvent>,
 ) {
     for event in events.read() {
         if event.remove_flags.contains(PermitFlags::RENDER) {
             meshes.removed.push(event.chunk_pos);
+            qualities.remove(event.chunk_pos);
+            // the chunk won't be meshed anymore, so it shouldn't count as pending
+            builder.cancel(event.chunk_pos);
+            backlog.remove(event.chunk_pos);
         }
     }
 }
@@ -188,9 +324,10 @@ pub fn voxel_realm_remesh_updated_chunks(
         remeshings_issued += 1;
 
         // This chunk was updated in such a way that we need to remesh its neighbors too!
+        // Only the neighbors touching the voxels that were edited are remeshed.
         if cref.flags().contains(ChunkFlags::REMESH_NEIGHBORS) {
-            for face in Face::FACES {
-                let neighbor_pos = ChunkPos::from(face.normal() + IVec3::from(cref.pos()));
+            for offset in cref.neighbors_to_remesh().iter() {
+                let neighbor_pos = cref.pos() + offset;
 
                 if !realm.has_render_permit(neighbor_pos)
                     || queued_primary.contains(&neighbor_pos)
@@ -255,7 +392,17 @@ pub fn voxel_realm_remesh_updated_chunks(
     }
 }
 
-fn calculate_priority(trans: &Transform, chunk_pos: ChunkPos) -> RemeshPriority {
+/// Chunks outside of an observer's view are meshed as if they were this many times further away (squared),
+/// so the chunks the observer can see are meshed first.
+const OUT_OF_VIEW_PRIORITY_PENALTY: u32 = 4;
+
+/// The priority to mesh a chunk with for an observer, based on the distance between them. If the observer
+/// has a [`Frustum`] (like a camera), chunks outside of it get a lower priority.
+pub(super) fn calculate_priority(
+    trans: &Transform,
+    frustum: Option<&Frustum>,
+    chunk_pos: ChunkPos,
+) -> RemeshPriority {
     const CHUNK_SIZE_F32: f32 = Chunk::SIZE as f32;
     const CHUNK_SIZE_DIV2: f32 = CHUNK_SIZE_F32 / 2.0;
 
@@ -264,34 +411,59 @@ fn calculate_priority(trans: &Transform, chunk_pos: ChunkPos) -> RemeshPriority
     let distance_sq = chunk_center.distance_squared(trans.translation);
     let distance_sq_int = distance_sq.clamp(0.0, u32::MAX as _) as u32;
 
-    RemeshPriority::new(distance_sq_int)
+    let in_view = frustum.is_none_or(|frustum| {
+        let aabb = Aabb {
+            center: chunk_center.into(),
+            half_extents: Vec3A::splat(CHUNK_SIZE_DIV2),
+        };
+
+        frustum.intersects_obb(&aabb, &Affine3A::IDENTITY, true, false)
+    });
+
+    match in_view {
+        true => RemeshPriority::new(distance_sq_int),
+        false => RemeshPriority::new(distance_sq_int.saturating_mul(OUT_OF_VIEW_PRIORITY_PENALTY)),
+    }
 }
 
 /// This system dispatches remesh jobs for chunks discovered by `voxel_realm_remesh_updated_chunks`
+#[allow(clippy::type_complexity)]
 pub fn dispatch_updated_chunk_remeshings(
     In(detected): In<UpdateDetectionRemeshResults>,
     current_generation: Res<MeshGeneration>,
-    observers: Query<&Transform, With<ChunkObserver>>,
+    lod_settings: Res<MeshLodSettings>,
+    observers: Query<(&Transform, Option<&Frustum>, Has<ForceLowQuality>), With<ChunkObserver>>,
+    mut qualities: ResMut<MeshQualities>,
     mut writer: EventWriter<RemeshChunk>,
 ) {
+    let observer_positions = lod_observer_positions(
+        observers
+            .iter()
+            .map(|(trans, _, force_low_quality)| (trans, force_low_quality)),
+    );
+
     writer.send_batch(
         detected
             .primary
             .into_iter()
-            .chain(detected.neighbors.into_iter())
+            .chain(detected.neighbors)
             .map(|chunk_pos| {
                 // Calculate remesh priority based on distance to nearest "observer"
                 let priority = observers
                     .iter()
-                    .map(|trans| calculate_priority(trans, chunk_pos))
+                    .map(|(trans, frustum, _)| calculate_priority(trans, frustum, chunk_pos))
                     .max()
                     .unwrap_or(RemeshPriority::LOWEST);
 
+                let quality = lod_settings.quality(chunk_pos, &observer_positions);
+                qualities.record(chunk_pos, quality);
+
                 RemeshChunk {
                     pos: chunk_pos,
                     remesh_type: RemeshType::Delayed,
                     priority,
                     generation: current_generation.0,
+                    quality,
                 }
             }),
     );
@@ -301,6 +473,7 @@ pub fn dispatch_updated_chunk_remeshings(
 pub fn setup_chunk_meshing_workers(
     mut cmds: Commands,
     registries: Res<Registries>,
+    backend: Res<MeshBackend>,
     realm: VoxelRealm,
 ) {
     info!("Setting up chunk meshing workers");
@@ -314,10 +487,454 @@ pub fn setup_chunk_meshing_workers(
         workers: task_pool.thread_num(),
         job_channel_capacity: task_pool.thread_num() * 4,
         worker_mesh_backlog_capacity: 3,
+        lighting: LightingMode::Smooth,
+        merge_policy: MergeAxisPolicy::PreferWidth,
+        triangulation: QuadTriangulation::default(),
+        merge_borders: false,
+        sidedness: MeshSidedness::Single,
+        low_quality_downsample: 2,
+        weld_t_junctions: false,
+        backend: *backend,
+        stale_timeout: Duration::from_secs(30),
     };
 
     let worker_pool = MeshBuilder::new(settings, &task_pool, registries.clone(), realm.clone_cm());
 
+    cmds.insert_resource(worker_pool.sidedness());
     cmds.insert_resource(worker_pool);
     cmds.insert_resource(MeshWorkerTaskPool(task_pool));
 }
+
+/// Stop the background mesh builder pool, then drop its task pool. Meshes that were being built are discarded.
+pub fn shutdown_chunk_meshing_workers(world: &mut World) {
+    if let Some(builder) = world.remove_resource::<MeshBuilder>() {
+        info!("Shutting down chunk meshing workers");
+        builder.shutdown();
+    }
+
+    world.remove_resource::<MeshWorkerTaskPool>();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use bevy::{
+        app::AppExit,
+        math::ivec3,
+        render::camera::{CameraProjection, PerspectiveProjection},
+    };
+
+    use crate::{
+        data::registries::block::BlockVariantRegistry,
+        render::meshing::controller::ChunkMeshData,
+        testing_utils::mock_registries,
+        topo::{
+            access::ReadAccess,
+            block::{BlockVoxel, FullBlock},
+            controller::{ChunkEcsPermits, Permit, PermitFlags},
+            neighbors::NeighborSet,
+            store::{save_dirty_chunks, MemoryChunkStore, RealmTeardownSystems, VoxelStore},
+            world::{
+                realm::ChunkManagerResource, CaoBlock, ChunkAccessInput, ChunkManager,
+                ChunkManagerError,
+            },
+        },
+    };
+
+    use super::*;
+
+    fn observer() -> ChunkObserver {
+        ChunkObserver {
+            horizontal_range: 4.0,
+            view_distance_above: 2.0,
+            view_distance_below: 2.0,
+        }
+    }
+
+    #[test]
+    fn chunks_in_view_are_meshed_first() {
+        let trans = Transform::from_xyz(8.0, 8.0, 8.0);
+        let view_projection = PerspectiveProjection::default().get_projection_matrix()
+            * trans.compute_matrix().inverse();
+        let frustum = Frustum::from_view_projection(&view_projection);
+
+        // cameras look down -Z
+        let ahead = ChunkPos::new(0, 0, -3);
+        let behind = ChunkPos::new(0, 0, 3);
+
+        assert_eq!(
+            calculate_priority(&trans, None, ahead),
+            calculate_priority(&trans, None, behind)
+        );
+        assert!(
+            calculate_priority(&trans, Some(&frustum), ahead)
+                > calculate_priority(&trans, Some(&frustum), behind)
+        );
+        assert_eq!(
+            calculate_priority(&trans, None, ahead),
+            calculate_priority(&trans, Some(&frustum), ahead)
+        );
+    }
+
+    #[test]
+    fn force_low_quality_observer() {
+        let mut app = App::new();
+        app.init_resource::<MeshGeneration>()
+            .init_resource::<MeshLodSettings>()
+            .init_resource::<MeshQualities>()
+            .add_event::<RemeshChunk>()
+            .add_systems(
+                Update,
+                (|| {
+                    let mut primary = hb::HashSet::default();
+                    primary.insert(ChunkPos::new(0, 0, 0));
+
+                    UpdateDetectionRemeshResults {
+                        primary,
+                        neighbors: Default::default(),
+                    }
+                })
+                .pipe(dispatch_updated_chunk_remeshings),
+            );
+
+        let minimap = app
+            .world
+            .spawn((
+                observer(),
+                ForceLowQuality,
+                Transform::from_xyz(8.0, 8.0, 8.0),
+            ))
+            .id();
+
+        let qualities = |app: &mut App| {
+            let events = app
+                .world
+                .resource_mut::<Events<RemeshChunk>>()
+                .drain()
+                .collect::<Vec<_>>();
+            events
+                .into_iter()
+                .map(|event| event.quality)
+                .collect::<Vec<_>>()
+        };
+
+        // the chunk is right next to the minimap observer, but it's meshed in low quality anyway
+        app.update();
+        assert_eq!(vec![MeshQuality::Low], qualities(&mut app));
+
+        // unless a regular observer comes close
+        app.world
+            .spawn((observer(), Transform::from_xyz(8.0, 8.0, 8.0)));
+        app.update();
+        assert_eq!(vec![MeshQuality::High], qualities(&mut app));
+
+        app.world.despawn(minimap);
+        app.update();
+        assert_eq!(vec![MeshQuality::High], qualities(&mut app));
+    }
+
+    #[test]
+    fn nearest_observer_sets_remesh_priority() {
+        let mut app = App::new();
+        app.init_resource::<MeshGeneration>()
+            .init_resource::<MeshLodSettings>()
+            .init_resource::<MeshQualities>()
+            .add_event::<RemeshChunk>()
+            .add_systems(
+                Update,
+                (|| {
+                    let mut primary = hb::HashSet::default();
+                    primary.insert(ChunkPos::new(0, 0, 0));
+
+                    UpdateDetectionRemeshResults {
+                        primary,
+                        neighbors: Default::default(),
+                    }
+                })
+                .pipe(dispatch_updated_chunk_remeshings),
+            );
+
+        let near = Transform::from_xyz(8.0, 8.0, 8.0);
+        let far = Transform::from_xyz(8.0, 8.0, 200.0);
+        app.world.spawn((observer(), far));
+        app.world.spawn((observer(), near));
+
+        app.update();
+
+        let events = app
+            .world
+            .resource_mut::<Events<RemeshChunk>>()
+            .drain()
+            .collect::<Vec<_>>();
+
+        assert_eq!(1, events.len());
+        assert_eq!(
+            calculate_priority(&near, None, ChunkPos::new(0, 0, 0)),
+            events[0].priority
+        );
+        assert!(events[0].priority > calculate_priority(&far, None, ChunkPos::new(0, 0, 0)));
+    }
+
+    #[test]
+    fn mesh_apply_budget() {
+        let mut backlog = FinishedMeshBacklog::default();
+        backlog.extend((0..100).map(|x| FinishedChunkData {
+            pos: ChunkPos::new(x, 0, 0),
+            data: ChunkMeshData {
+                index_buffer: Vec::new(),
+                quad_buffer: Vec::new(),
+            },
+            generation: 0,
+            duration: Duration::ZERO,
+        }));
+
+        let mut meshes = ExtractableChunkMeshData::default();
+        let mut stats = MeshingStats::default();
+
+        for frame in 1..=10 {
+            assert_eq!(
+                10,
+                apply_finished_meshes(&mut backlog, 10, &mut meshes, &mut stats)
+            );
+            assert_eq!(frame * 10, meshes.active.len());
+            assert_eq!(100 - frame * 10, backlog.len());
+        }
+
+        assert!(meshes.active.contains(ChunkPos::new(99, 0, 0)));
+        assert_eq!(
+            0,
+            apply_finished_meshes(&mut backlog, 10, &mut meshes, &mut stats)
+        );
+    }
+
+    #[test]
+    fn backlog_keeps_the_newest_mesh_per_chunk() {
+        // the index buffer tells the meshes of a chunk apart
+        let finished = |x: i32, generation: u64, indices: usize| FinishedChunkData {
+            pos: ChunkPos::new(x, 0, 0),
+            data: ChunkMeshData {
+                index_buffer: vec![0; indices],
+                quad_buffer: Vec::new(),
+            },
+            generation,
+            duration: Duration::ZERO,
+        };
+
+        let mut backlog = FinishedMeshBacklog::default();
+        backlog.extend([
+            finished(0, 1, 1),
+            finished(1, 1, 1),
+            finished(2, 1, 1),
+            finished(0, 3, 3),
+            // older than the mesh already in the backlog
+            finished(0, 2, 2),
+        ]);
+        assert_eq!(3, backlog.len());
+
+        assert!(backlog.remove(ChunkPos::new(1, 0, 0)).is_some());
+        assert_eq!(2, backlog.len());
+
+        let taken = backlog.take(10);
+        assert!(backlog.is_empty());
+        assert_eq!(
+            vec![
+                (ChunkPos::new(0, 0, 0), 3, 3),
+                (ChunkPos::new(2, 0, 0), 1, 1)
+            ],
+            taken
+                .iter()
+                .map(|mesh| (mesh.pos, mesh.generation, mesh.data.index_buffer.len()))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn removed_chunks_are_not_pending() {
+        let registries = mock_registries();
+
+        // there are no workers, so the commands are never finished
+        let task_pool = TaskPoolBuilder::new().num_threads(1).build();
+        let mut builder = MeshBuilder::new(
+            MeshBuilderSettings {
+                workers: 0,
+                ..MeshBuilderSettings::new_test()
+            },
+            &task_pool,
+            registries,
+            Arc::new(ChunkManager::new_test()),
+        );
+
+        let removed = ChunkPos::new(0, 0, 0);
+        let kept = ChunkPos::new(1, 0, 0);
+        builder.queue_jobs([removed, kept].into_iter().map(|pos| MeshCommand {
+            pos,
+            priority: RemeshPriority::HIGHEST,
+            generation: 0,
+            quality: MeshQuality::High,
+            stitched: NeighborSet::EMPTY,
+        }));
+
+        let mut backlog = FinishedMeshBacklog::default();
+        backlog.push(FinishedChunkData {
+            pos: removed,
+            data: ChunkMeshData {
+                index_buffer: Vec::new(),
+                quad_buffer: Vec::new(),
+            },
+            generation: 0,
+            duration: Duration::ZERO,
+        });
+
+        let mut app = App::new();
+        app.init_resource::<ExtractableChunkMeshData>()
+            .init_resource::<MeshQualities>()
+            .insert_resource(backlog)
+            .insert_resource(builder)
+            .add_event::<UpdatePermitEvent>()
+            .add_systems(Update, remove_chunks);
+
+        app.world.send_event(UpdatePermitEvent {
+            chunk_pos: removed,
+            insert_flags: PermitFlags::empty(),
+            remove_flags: PermitFlags::RENDER,
+        });
+        app.update();
+
+        let builder = app.world.resource::<MeshBuilder>();
+        assert_eq!(vec![kept], builder.pending_positions());
+        assert_eq!(
+            vec![removed],
+            app.world.resource::<ExtractableChunkMeshData>().removed
+        );
+    }
+
+    #[test]
+    fn teardown_saves_dirty_chunks_and_joins_workers() {
+        let cm = Arc::new(ChunkManager::new_test());
+        let void = BlockVoxel::new_full(BlockVariantRegistry::VOID);
+        cm.insert_filled_test_chunk(ChunkPos::new(0, 0, 0), void.clone());
+        cm.insert_filled_test_chunk(ChunkPos::new(1, 0, 0), void);
+
+        let registries = mock_registries();
+
+        let task_pool = TaskPoolBuilder::new().num_threads(1).build();
+        let builder = MeshBuilder::new(
+            MeshBuilderSettings::new_test(),
+            &task_pool,
+            registries,
+            cm.clone(),
+        );
+
+        let store = MemoryChunkStore::default();
+        let mut app = App::new();
+        app.insert_resource(ChunkManagerResource(cm.clone()))
+            .init_resource::<ChunkEcsPermits>()
+            .insert_resource(VoxelStore::new(store.clone()))
+            .insert_resource(builder)
+            .insert_resource(MeshWorkerTaskPool(task_pool))
+            .add_systems(
+                Last,
+                (
+                    shutdown_chunk_meshing_workers.in_set(RealmTeardownSystems::StopWorkers),
+                    save_dirty_chunks.in_set(RealmTeardownSystems::Save),
+                ),
+            );
+        RealmTeardownSystems::configure(&mut app);
+
+        // nothing is torn down until the app exits
+        app.update();
+        assert!(app.world.contains_resource::<MeshBuilder>());
+        assert!(store.is_empty());
+
+        cm.set_voxel(
+            ivec3(1, 2, 3),
+            ChunkAccessInput::new(BlockVoxel::new_full(BlockVariantRegistry::FULL)),
+        )
+        .unwrap();
+
+        app.world.send_event(AppExit);
+        app.update();
+
+        assert!(!app.world.contains_resource::<MeshBuilder>());
+        assert!(!app.world.contains_resource::<MeshWorkerTaskPool>());
+
+        let saved = store.get(ChunkPos::new(0, 0, 0)).unwrap();
+        assert_eq!(
+            CaoBlock::Full(FullBlock::new(BlockVariantRegistry::FULL)),
+            saved.read_access().get(ivec3(1, 2, 3)).unwrap().block
+        );
+    }
+
+    #[test]
+    fn requested_remesh_builds_a_new_mesh() {
+        let cm = Arc::new(ChunkManager::new_test());
+        let pos = ChunkPos::new(0, 0, 0);
+        cm.insert_filled_test_chunk(pos, BlockVoxel::new_full(BlockVariantRegistry::FULL));
+        cm.get_loaded_chunk(pos, false)
+            .unwrap()
+            .update_flags(|flags| flags.remove(ChunkFlags::REMESH));
+
+        let registries = mock_registries();
+
+        let task_pool = TaskPoolBuilder::new().num_threads(1).build();
+        let builder = MeshBuilder::new(
+            MeshBuilderSettings {
+                backend: MeshBackend::MainThread {
+                    budget: Duration::from_secs(1),
+                },
+                ..MeshBuilderSettings::new_test()
+            },
+            &task_pool,
+            registries,
+            cm.clone(),
+        );
+
+        let mut permits = ChunkEcsPermits::default();
+        permits.insert(Entity::PLACEHOLDER, pos, Permit::new(PermitFlags::RENDER));
+
+        let mut app = App::new();
+        app.insert_resource(ChunkManagerResource(cm.clone()))
+            .insert_resource(permits)
+            .insert_resource(builder)
+            .init_resource::<Time>()
+            .init_resource::<MeshGeneration>()
+            .init_resource::<MeshLodSettings>()
+            .init_resource::<MeshQualities>()
+            .add_event::<RemeshChunk>()
+            .add_systems(
+                Update,
+                (
+                    voxel_realm_remesh_updated_chunks.pipe(dispatch_updated_chunk_remeshings),
+                    queue_chunk_mesh_jobs,
+                )
+                    .chain(),
+            );
+
+        let finished = |app: &mut App| {
+            app.world
+                .resource_mut::<MeshBuilder>()
+                .get_finished_meshes()
+                .into_iter()
+                .map(|finished| finished.pos)
+                .collect::<Vec<_>>()
+        };
+
+        // nothing changed in the chunk, so it isn't remeshed
+        app.update();
+        assert!(finished(&mut app).is_empty());
+
+        cm.request_remesh(pos).unwrap();
+        app.update();
+        assert_eq!(vec![pos], finished(&mut app));
+
+        // the request is handled once
+        app.update();
+        assert!(finished(&mut app).is_empty());
+
+        assert!(matches!(
+            cm.request_remesh(ChunkPos::new(5, 0, 0)),
+            Err(ChunkManagerError::Unloaded)
+        ));
+    }
+}
@@ -0,0 +1,237 @@
+use bevy::{prelude::*, render::primitives::Frustum};
+
+use crate::{
+    topo::{
+        neighbors::NeighborSet,
+        world::{Chunk, ChunkPos, VoxelRealm},
+        ChunkObserver,
+    },
+    util::ChunkMap,
+};
+
+use super::{ecs::calculate_priority, MeshGeneration, RemeshChunk, RemeshPriority, RemeshType};
+
+/// The quality a chunk is meshed at. Low quality meshes use flat lighting, which is cheaper to build
+/// but doesn't look as good up close.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Default)]
+pub enum MeshQuality {
+    #[default]
+    High,
+    Low,
+}
+
+/// Marks a chunk observer that never needs high quality meshes, like the observer of a minimap or overview
+/// camera. Chunks near this observer are meshed in low quality, unless they're near another observer.
+#[derive(Copy, Clone, Component, Debug, Default)]
+pub struct ForceLowQuality;
+
+/// The positions of the observers that chunks can be meshed in high quality for.
+pub(super) fn lod_observer_positions<'a>(
+    observers: impl IntoIterator<Item = (&'a Transform, bool)>,
+) -> Vec<Vec3> {
+    observers
+        .into_iter()
+        .filter(|&(_, force_low_quality)| !force_low_quality)
+        .map(|(trans, _)| trans.translation)
+        .collect()
+}
+
+#[derive(Copy, Clone, Resource, Debug)]
+pub struct MeshLodSettings {
+    /// Chunks within this many chunks of an observer are meshed in high quality, chunks further away
+    /// are meshed in low quality.
+    pub high_quality_range: f32,
+}
+
+impl Default for MeshLodSettings {
+    fn default() -> Self {
+        Self {
+            high_quality_range: 6.0,
+        }
+    }
+}
+
+impl MeshLodSettings {
+    /// The quality a chunk should be meshed at given the positions of the observers.
+    /// If there are no observers the chunk is meshed in low quality.
+    ///
+    /// The quality only depends on the distance to the observers, not on whether the chunk is in their view.
+    /// Otherwise every chunk entering or leaving the view would be remeshed whenever an observer turns around.
+    /// The view only decides the order chunks are meshed in, see [`calculate_priority`].
+    pub fn quality(&self, chunk_pos: ChunkPos, observers: &[Vec3]) -> MeshQuality {
+        const CHUNK_SIZE_F32: f32 = Chunk::SIZE as f32;
+
+        let chunk_center = (chunk_pos.as_vec3() + Vec3::splat(0.5)) * CHUNK_SIZE_F32;
+        let range = self.high_quality_range * CHUNK_SIZE_F32;
+
+        if observers
+            .iter()
+            .any(|observer| observer.distance_squared(chunk_center) <= range * range)
+        {
+            MeshQuality::High
+        } else {
+            MeshQuality::Low
+        }
+    }
+}
+
+/// The quality that every chunk with a mesh was last meshed at.
+#[derive(Resource, Default)]
+pub struct MeshQualities(pub ChunkMap<MeshQuality>);
+
+impl MeshQualities {
+    /// Record the quality a chunk is meshed at.
+    pub fn record(&mut self, pos: ChunkPos, quality: MeshQuality) {
+        self.0.set(pos, quality);
+    }
+
+    pub fn remove(&mut self, pos: ChunkPos) {
+        self.0.remove(pos);
+    }
+
+    /// The quality a chunk is meshed at. Chunks that haven't been meshed yet are assumed to get the quality
+    /// they should be meshed at.
+    pub fn quality(
+        &self,
+        pos: ChunkPos,
+        settings: &MeshLodSettings,
+        observers: &[Vec3],
+    ) -> MeshQuality {
+        self.0
+            .get(pos)
+            .copied()
+            .unwrap_or_else(|| settings.quality(pos, observers))
+    }
+
+    /// The face neighbors of a chunk meshed at the given quality that are meshed at a higher quality,
+    /// so the chunk has to stitch its border to them.
+    pub fn stitched(
+        &self,
+        pos: ChunkPos,
+        quality: MeshQuality,
+        settings: &MeshLodSettings,
+        observers: &[Vec3],
+    ) -> NeighborSet {
+        if quality == MeshQuality::High {
+            return NeighborSet::EMPTY;
+        }
+
+        NeighborSet::faces()
+            .iter()
+            .filter(|&offset| self.quality(pos + offset, settings, observers) == MeshQuality::High)
+            .collect()
+    }
+
+    /// Find the recorded chunks that should be meshed at a different quality than they were last meshed at,
+    /// and record the new quality for them. Chunks that haven't been meshed yet are ignored, they'll get the
+    /// right quality when they're meshed the first time.
+    pub fn transitions(
+        &mut self,
+        settings: &MeshLodSettings,
+        observers: &[Vec3],
+    ) -> Vec<(ChunkPos, MeshQuality)> {
+        let mut transitions = Vec::new();
+
+        self.0.for_each_entry_mut(|pos, quality| {
+            let desired = settings.quality(pos, observers);
+
+            if *quality != desired {
+                *quality = desired;
+                transitions.push((pos, desired));
+            }
+        });
+
+        transitions
+    }
+}
+
+/// This system remeshes chunks at a new quality level when observers move close enough or far enough
+/// away from them.
+#[allow(clippy::type_complexity)]
+pub fn remesh_lod_transitions(
+    realm: VoxelRealm,
+    settings: Res<MeshLodSettings>,
+    current_generation: Res<MeshGeneration>,
+    observers: Query<(&Transform, Option<&Frustum>, Has<ForceLowQuality>), With<ChunkObserver>>,
+    mut qualities: ResMut<MeshQualities>,
+    mut writer: EventWriter<RemeshChunk>,
+) {
+    let positions = lod_observer_positions(
+        observers
+            .iter()
+            .map(|(trans, _, force_low_quality)| (trans, force_low_quality)),
+    );
+
+    let transitions = qualities.transitions(&settings, &positions);
+
+    // the low quality neighbors of the transitioned chunks have to stitch their borders differently
+    let mut remeshed = transitions.clone();
+    for &(pos, _) in &transitions {
+        for offset in NeighborSet::faces().iter() {
+            let neighbor = pos + offset;
+            if remeshed.iter().any(|&(remeshed, _)| remeshed == neighbor) {
+                continue;
+            }
+
+            if let Some(&MeshQuality::Low) = qualities.0.get(neighbor) {
+                remeshed.push((neighbor, MeshQuality::Low));
+            }
+        }
+    }
+
+    writer.send_batch(
+        remeshed
+            .into_iter()
+            .filter(|&(pos, _)| realm.has_render_permit(pos))
+            .map(|(pos, quality)| RemeshChunk {
+                pos,
+                remesh_type: RemeshType::Delayed,
+                priority: observers
+                    .iter()
+                    .map(|(trans, frustum, _)| calculate_priority(trans, frustum, pos))
+                    .max()
+                    .unwrap_or(RemeshPriority::LOWEST),
+                generation: current_generation.0,
+                quality,
+            }),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lod_transition_remeshes_once() {
+        let settings = MeshLodSettings {
+            high_quality_range: 2.0,
+        };
+
+        let pos = ChunkPos::new(0, 0, 0);
+        let mut qualities = MeshQualities::default();
+
+        let near = [Vec3::splat(8.0)];
+        let far = [Vec3::new(200.0, 8.0, 8.0)];
+
+        qualities.record(pos, settings.quality(pos, &near));
+        assert!(qualities.transitions(&settings, &near).is_empty());
+
+        // the observer moves away, so the chunk should be remeshed in low quality exactly once
+        assert_eq!(
+            vec![(pos, MeshQuality::Low)],
+            qualities.transitions(&settings, &far)
+        );
+        assert!(qualities.transitions(&settings, &far).is_empty());
+
+        // and back in high quality once the observer comes close again
+        assert_eq!(
+            vec![(pos, MeshQuality::High)],
+            qualities.transitions(&settings, &near)
+        );
+        assert!(qualities.transitions(&settings, &near).is_empty());
+
+        // chunks that haven't been meshed yet don't transition
+        qualities.remove(pos);
+        assert!(qualities.transitions(&settings, &far).is_empty());
+    }
+}
@@ -1,24 +1,39 @@
+mod batching;
+mod diagnostics;
 mod ecs;
+mod lod;
+mod readiness;
 mod workers;
 
 use std::{cmp, fmt};
 
-use bevy::prelude::*;
+use batching::batch_distant_chunk_meshes;
+use bevy::{prelude::*, render::primitives::Aabb};
+use diagnostics::measure_meshing_diagnostics;
 use ecs::remove_chunks;
+use lod::remesh_lod_transitions;
+use readiness::update_realm_state;
 
 use crate::{
     render::{meshing::controller::ecs::dispatch_updated_chunk_remeshings, quad::GpuQuad},
-    topo::world::ChunkPos,
+    topo::{
+        block::SubdividedBlock, store::RealmTeardownSystems, vec_project_to_3d, world::ChunkPos,
+    },
     util::ChunkMap,
     CoreEngineSetup, EngineState,
 };
 
 use self::ecs::{
-    insert_chunks, queue_chunk_mesh_jobs, setup_chunk_meshing_workers,
-    voxel_realm_remesh_updated_chunks,
+    insert_chunks, queue_chunk_mesh_jobs, requeue_stale_mesh_jobs, setup_chunk_meshing_workers,
+    shutdown_chunk_meshing_workers, voxel_realm_remesh_updated_chunks,
 };
 
-pub use self::ecs::{MeshGeneration, RemeshChunk};
+pub use self::batching::{merge_chunk_meshes, MeshBatchSettings, MeshBatches};
+pub use self::diagnostics::{MeshingDiagnostics, MeshingStats};
+pub use self::ecs::{FinishedMeshBacklog, MeshApplySettings, MeshGeneration, RemeshChunk};
+pub use self::lod::{ForceLowQuality, MeshLodSettings, MeshQualities, MeshQuality};
+pub use self::readiness::{observer_chunks_ready, RealmState};
+pub use self::workers::MeshBackend;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum RemeshType {
@@ -26,7 +41,7 @@ pub enum RemeshType {
     Delayed,
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Ord)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub struct RemeshPriority(u32);
 
 impl RemeshPriority {
@@ -38,9 +53,16 @@ impl RemeshPriority {
     }
 }
 
+// lower raw values are higher priorities
+impl Ord for RemeshPriority {
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        other.0.cmp(&self.0)
+    }
+}
+
 impl PartialOrd for RemeshPriority {
     fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
-        other.0.partial_cmp(&self.0)
+        Some(self.cmp(other))
     }
 }
 
@@ -54,6 +76,24 @@ impl ChunkMeshData {
     pub fn is_empty(&self) -> bool {
         self.index_buffer.is_empty() || self.quad_buffer.is_empty()
     }
+
+    /// The bounding box of the quads in this mesh, in the localspace of the chunk.
+    /// Returns `None` if there are no quads.
+    pub fn aabb(&self) -> Option<Aabb> {
+        self.quad_buffer
+            .iter()
+            .map(|quad| {
+                let face = quad.bitfields.get_face();
+                let mag = quad.magnitude as f32 / SubdividedBlock::SUBDIVISIONS as f32;
+
+                (
+                    vec_project_to_3d(quad.min, face, mag),
+                    vec_project_to_3d(quad.max, face, mag),
+                )
+            })
+            .reduce(|(min_a, max_a), (min_b, max_b)| (min_a.min(min_b), max_a.max(max_b)))
+            .map(|(min, max)| Aabb::from_min_max(min, max))
+    }
 }
 
 impl fmt::Debug for ChunkMeshData {
@@ -102,16 +142,30 @@ pub struct ChunkRenderPermit {
     pub granted: u64,
 }
 
-pub struct MeshController;
+#[derive(Default)]
+pub struct MeshController {
+    /// How chunk meshes are built, see [`MeshBackend`].
+    pub backend: MeshBackend,
+}
 
 impl Plugin for MeshController {
     fn build(&self, app: &mut App) {
         info!("Setting up mesh controller");
 
-        app.init_resource::<ExtractableChunkMeshData>()
+        app.insert_resource(self.backend)
+            .init_resource::<ExtractableChunkMeshData>()
             .init_resource::<MeshGeneration>()
+            .init_resource::<MeshLodSettings>()
+            .init_resource::<MeshQualities>()
+            .init_resource::<MeshBatchSettings>()
+            .init_resource::<MeshBatches>()
+            .init_resource::<MeshApplySettings>()
+            .init_resource::<FinishedMeshBacklog>()
+            .init_state::<RealmState>()
             .add_event::<RemeshChunk>();
 
+        MeshingDiagnostics::register(app);
+
         app.add_systems(
             OnEnter(EngineState::Finished),
             setup_chunk_meshing_workers.after(CoreEngineSetup),
@@ -119,17 +173,36 @@ impl Plugin for MeshController {
 
         app.add_systems(
             PreUpdate,
-            (remove_chunks, insert_chunks).run_if(in_state(EngineState::Finished)),
+            (
+                remove_chunks,
+                insert_chunks,
+                measure_meshing_diagnostics,
+                batch_distant_chunk_meshes,
+            )
+                .chain()
+                .run_if(in_state(EngineState::Finished)),
+        );
+
+        app.add_systems(
+            Update,
+            update_realm_state.run_if(in_state(EngineState::Finished)),
         );
 
         app.add_systems(
             FixedPostUpdate,
             (
                 voxel_realm_remesh_updated_chunks.pipe(dispatch_updated_chunk_remeshings),
+                remesh_lod_transitions,
+                requeue_stale_mesh_jobs,
                 queue_chunk_mesh_jobs,
             )
                 .chain()
                 .run_if(in_state(EngineState::Finished)),
         );
+
+        app.add_systems(
+            Last,
+            shutdown_chunk_meshing_workers.in_set(RealmTeardownSystems::StopWorkers),
+        );
     }
 }
@@ -0,0 +1,155 @@
+use bevy::prelude::*;
+
+use crate::topo::{
+    controller::{chunks_in_range, ChunkObserver, LastPosition, PrimaryObserver},
+    world::{ChunkManager, ChunkPos, VoxelRealm},
+};
+
+use super::{ChunkMeshStatus, ExtractableChunkMeshData};
+
+/// Whether the chunks around the primary observer (see [`PrimaryObserver`]) are ready to be shown.
+/// Apps can use this to show a loading screen until the world around the player has finished loading.
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq, Hash, States)]
+pub enum RealmState {
+    /// Some chunks in range of the observer haven't been generated or meshed yet.
+    #[default]
+    Loading,
+    /// All chunks in range of the observer are generated and have a mesh available.
+    Ready,
+}
+
+/// Whether all the chunks in range of an observer in `observer_pos` are generated and have a mesh available.
+pub fn observer_chunks_ready(
+    cm: &ChunkManager,
+    meshes: &ExtractableChunkMeshData,
+    observer_pos: ChunkPos,
+    observer: &ChunkObserver,
+) -> bool {
+    chunks_in_range(observer_pos, observer).all(|pos| {
+        cm.get_loaded_chunk(pos, false).is_ok()
+            && meshes
+                .active
+                .get(pos)
+                .is_some_and(|mesh| !matches!(mesh.data, ChunkMeshStatus::Unfulfilled))
+    })
+}
+
+/// Updates the [`RealmState`] according to the chunks around the primary observer.
+pub fn update_realm_state(
+    realm: VoxelRealm,
+    meshes: Res<ExtractableChunkMeshData>,
+    observers: Query<(&ChunkObserver, &LastPosition, Has<PrimaryObserver>)>,
+    state: Res<State<RealmState>>,
+    mut next_state: ResMut<NextState<RealmState>>,
+) {
+    let primary = observers
+        .iter()
+        .find(|(_, _, primary)| *primary)
+        .or_else(|| observers.iter().next());
+
+    let ready = primary.is_some_and(|(observer, last_pos, _)| {
+        observer_chunks_ready(realm.cm(), &meshes, last_pos.chunk_pos, observer)
+    });
+
+    let new_state = if ready {
+        RealmState::Ready
+    } else {
+        RealmState::Loading
+    };
+
+    if *state.get() != new_state {
+        next_state.set(new_state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use bevy::math::Vec3;
+
+    use crate::{
+        render::meshing::controller::{ChunkMeshData, TimedChunkMeshData},
+        topo::{controller::ChunkEcsPermits, world::realm::ChunkManagerResource},
+    };
+
+    use super::*;
+
+    #[test]
+    fn state_follows_observer_chunks() {
+        let cm = Arc::new(ChunkManager::new_test());
+        let observer = ChunkObserver {
+            horizontal_range: 1.0,
+            view_distance_above: 0.0,
+            view_distance_below: 0.0,
+        };
+
+        let origin = ChunkPos::new(0, 0, 0);
+        let in_range = chunks_in_range(origin, &observer).collect::<Vec<_>>();
+        assert_eq!(9, in_range.len());
+
+        for &pos in &in_range {
+            cm.insert_test_chunk(pos, |_| ());
+        }
+
+        let mut app = App::new();
+        app.init_state::<RealmState>()
+            .init_resource::<ChunkEcsPermits>()
+            .init_resource::<ExtractableChunkMeshData>()
+            .insert_resource(ChunkManagerResource(cm))
+            .add_systems(Update, update_realm_state);
+
+        let observer_entity = app
+            .world
+            .spawn((
+                observer,
+                PrimaryObserver,
+                LastPosition {
+                    ws_pos: Vec3::ZERO,
+                    chunk_pos: origin,
+                },
+            ))
+            .id();
+
+        let state = |app: &App| *app.world.resource::<State<RealmState>>().get();
+        let mesh = TimedChunkMeshData {
+            generation: 0,
+            data: ChunkMeshStatus::from_mesh_data(&ChunkMeshData {
+                index_buffer: vec![],
+                quad_buffer: vec![],
+            }),
+        };
+
+        let (last, rest) = in_range.split_last().unwrap();
+        for &pos in rest {
+            app.world
+                .resource_mut::<ExtractableChunkMeshData>()
+                .active
+                .set(pos, mesh.clone());
+        }
+
+        app.update();
+        app.update();
+        assert_eq!(RealmState::Loading, state(&app));
+
+        app.world
+            .resource_mut::<ExtractableChunkMeshData>()
+            .active
+            .set(*last, mesh.clone());
+
+        app.update();
+        app.update();
+        assert_eq!(RealmState::Ready, state(&app));
+
+        // teleporting far away leaves all the chunks around the observer unloaded
+        app.world
+            .entity_mut(observer_entity)
+            .get_mut::<LastPosition>()
+            .unwrap()
+            .chunk_pos = ChunkPos::new(1000, 0, 1000);
+
+        app.update();
+        app.update();
+        assert_eq!(RealmState::Loading, state(&app));
+    }
+}
@@ -1,4 +1,5 @@
 use std::{
+    cmp::max,
     collections::BinaryHeap,
     sync::{
         atomic::{AtomicBool, Ordering},
@@ -10,33 +11,52 @@ use std::{
 
 use bevy::{
     ecs::system::Resource,
-    log::{error, info, warn},
-    tasks::{block_on, futures_lite::future, Task, TaskPool},
+    log::{error, warn},
+    tasks::{block_on, futures_lite::future, AsyncComputeTaskPool, Task, TaskPool},
+    utils::Instant,
 };
 use crossbeam::channel::{self, Receiver, RecvTimeoutError, Sender, TrySendError};
 
 use crate::{
-    data::registries::Registries,
-    render::meshing::{error::ChunkMeshingError, greedy::algorithm::GreedyMesher, Context},
-    topo::world::{ChunkManager, ChunkPos},
-    util::{result::ResultFlattening, Keyed, KeyedOrd},
+    data::registries::{block::BlockVariantRegistry, Registries},
+    render::meshing::{
+        error::{ChunkMeshingError, MesherError},
+        greedy::algorithm::{GreedyMesher, MergeAxisPolicy, QuadTriangulation},
+        lighting::LightingMode,
+        selector::MesherSelector,
+        weld::weld_t_junctions,
+        Context, MeshSidedness, Mesher,
+    },
+    topo::{
+        neighbors::NeighborSet,
+        world::{
+            downsample::{downsample, valid_factor},
+            ChunkManager, ChunkPos,
+        },
+    },
+    util::{result::ResultFlattening, ChunkMap, Keyed, KeyedOrd},
 };
 
-use super::{ChunkMeshData, RemeshPriority};
+use super::{lod::MeshQuality, ChunkMeshData, RemeshPriority};
 
 pub struct Worker {
     task: Task<()>,
     interrupt: Arc<AtomicBool>,
-    label: String,
 }
 
 #[derive(Clone)]
 pub struct WorkerParams {
     pub registries: Registries,
     pub chunk_manager: Arc<ChunkManager>,
-    pub mesher: GreedyMesher,
+    pub mesher: MesherSelector<GreedyMesher>,
+    /// Used for low quality meshes, this mesher always uses flat lighting.
+    pub low_quality_mesher: MesherSelector<GreedyMesher>,
+    /// The factor that chunks are downsampled by for low quality meshes.
+    pub low_quality_downsample: i32,
+    /// Whether to weld the T-junctions of finished meshes, see [`weld_t_junctions`].
+    pub weld_t_junctions: bool,
 
-    pub finished: Sender<FinishedChunkData>,
+    pub finished: Sender<MeshJobOutcome>,
     pub cmds: Receiver<MeshCommand>,
 }
 
@@ -45,6 +65,9 @@ pub struct MeshCommand {
     pub pos: ChunkPos,
     pub priority: RemeshPriority,
     pub generation: u64,
+    pub quality: MeshQuality,
+    /// The face neighbors that are meshed at a higher quality than this chunk, see [`mesh_chunk_downsampled`].
+    pub stitched: NeighborSet,
 }
 
 impl Keyed<RemeshPriority> for MeshCommand {
@@ -55,6 +78,199 @@ impl Keyed<RemeshPriority> for MeshCommand {
     }
 }
 
+/// Build the mesh for the chunk at the given position. Only the neighbors within the mesher's
+/// [neighbor radius](Mesher::neighbor_radius) are gathered. Returns [`ChunkMeshingError::NeighborsGenerating`]
+/// without meshing if any of those neighbors are still being generated, see [`ChunkManager::neighbors_ready`].
+// workers always go through `mesh_chunk_downsampled` since they mesh at different qualities
+#[allow(dead_code)]
+pub fn mesh_chunk<M: Mesher>(
+    cm: &ChunkManager,
+    registries: &Registries,
+    pos: ChunkPos,
+    mesher: &mut M,
+) -> Result<ChunkMeshData, ChunkMeshingError> {
+    mesh_chunk_downsampled(cm, registries, pos, mesher, 1, NeighborSet::EMPTY)
+}
+
+/// Like [`mesh_chunk`], but the voxels of the chunk are [downsampled](downsample) by `factor` first, for
+/// distant chunks that don't need all their detail. A factor of 1 meshes the chunk at full resolution.
+///
+/// The `stitched` face neighbors are meshed at a higher quality, so the border facing them is kept at full
+/// resolution and there are no cracks between the levels of detail, see
+/// [`DownsampledChunk::expand_stitched`](crate::topo::world::downsample::DownsampledChunk::expand_stitched).
+/// The other face neighbors are meshed with the same factor as this chunk, so this chunk is meshed against
+/// their downsampled voxels, like they're meshed against the downsampled voxels of this chunk.
+pub fn mesh_chunk_downsampled<M: Mesher>(
+    cm: &ChunkManager,
+    registries: &Registries,
+    pos: ChunkPos,
+    mesher: &mut M,
+    factor: i32,
+    stitched: NeighborSet,
+) -> Result<ChunkMeshData, ChunkMeshingError> {
+    let radius = mesher.neighbor_radius();
+
+    // meshing against the placeholder voxels of generating neighbors would build wrong borders
+    if !cm.neighbors_ready(pos, radius) {
+        return Err(ChunkMeshingError::NeighborsGenerating);
+    }
+
+    // the face neighbors meshed at the same quality, as they'll be meshed
+    let mut downsampled_neighbors = Vec::new();
+    if factor > 1 {
+        let varreg = registries.get_registry::<BlockVariantRegistry>().unwrap();
+        for offset in NeighborSet::faces().iter() {
+            if stitched.contains(offset) {
+                continue;
+            }
+
+            let Ok(neighbor) = cm.get_loaded_chunk(pos + offset, false) else {
+                continue;
+            };
+
+            let expanded = downsample(&neighbor.read_snapshot().read_access(), factor, &varreg)
+                .map_err(MesherError::custom)?
+                .expand();
+            downsampled_neighbors.push((offset, expanded));
+        }
+    }
+
+    cm.with_neighbors_in_radius::<_, Result<ChunkMeshData, ChunkMeshingError>>(
+        pos,
+        radius,
+        |neighbors| {
+            let mut neighbors = neighbors;
+            for (offset, expanded) in &downsampled_neighbors {
+                neighbors
+                    .set_neighbor(*offset, expanded.read_access())
+                    .unwrap();
+            }
+
+            let chunk = cm.get_loaded_chunk(pos, false)?;
+            // mesh a snapshot of the chunk so it can be written to while the mesh is built
+            let mut snapshot = chunk.read_snapshot();
+            let light = chunk.light();
+            let mut downsampled_light = None;
+
+            if factor > 1 {
+                let varreg = registries.get_registry::<BlockVariantRegistry>().unwrap();
+                let original = snapshot.read_access();
+                let downsampled =
+                    downsample(&original, factor, &varreg).map_err(MesherError::custom)?;
+                let expanded = downsampled
+                    .expand_stitched(&original, stitched)
+                    .map_err(MesherError::custom)?;
+
+                drop(original);
+                snapshot = expanded;
+                downsampled_light = Some(downsampled.light(&light));
+            }
+
+            let context = Context {
+                neighbors,
+                registries,
+                light: downsampled_light.as_ref().unwrap_or(&light),
+            };
+
+            Ok(mesher.build(snapshot.read_access(), context)?)
+        },
+    )
+    .map_err(ChunkMeshingError::from)
+    .custom_flatten()
+}
+
+/// What to do with a command after trying to build the mesh for it.
+enum CommandOutcome {
+    /// A [`MeshJobOutcome`] was sent for the command.
+    Done,
+    /// The chunk manager was globally locked, so the command should be tried again later.
+    Retry,
+}
+
+/// Relight and mesh the chunk for the command. Unless the command has to be retried, its outcome is sent to
+/// [`WorkerParams::finished`], whether the mesh was built or not.
+fn run_command(params: &mut WorkerParams, cmd: &MeshCommand, label: &str) -> CommandOutcome {
+    let cm = params.chunk_manager.clone();
+    let start = Instant::now();
+
+    let (mesher, factor) = match cmd.quality {
+        MeshQuality::High => (&mut params.mesher, 1),
+        MeshQuality::Low => (
+            &mut params.low_quality_mesher,
+            params.low_quality_downsample,
+        ),
+    };
+
+    // the command is queued again right away, so bail before the (much more expensive) relighting
+    if !cm.neighbors_ready(cmd.pos, mesher.neighbor_radius()) {
+        let _ = params
+            .finished
+            .send(MeshJobOutcome::NeighborsGenerating(cmd.clone()));
+        return CommandOutcome::Done;
+    }
+
+    // the light of the chunk is recalculated before every remesh, so that the mesh uses
+    // the most recent light of the chunk and its neighbors
+    let relight = {
+        let varreg = params
+            .registries
+            .get_registry::<BlockVariantRegistry>()
+            .unwrap();
+        cm.relight_chunk(cmd.pos, &varreg)
+    };
+
+    let result = relight.map_err(ChunkMeshingError::from).and_then(|_| {
+        mesh_chunk_downsampled(
+            &cm,
+            &params.registries,
+            cmd.pos,
+            mesher,
+            factor,
+            cmd.stitched,
+        )
+    });
+
+    let outcome = match result {
+        Ok(mut output) => {
+            if params.weld_t_junctions {
+                weld_t_junctions(&mut output);
+            }
+
+            MeshJobOutcome::Finished(FinishedChunkData {
+                data: output,
+                pos: cmd.pos,
+                generation: cmd.generation,
+                duration: start.elapsed(),
+            })
+        }
+        // backlog if globally locked
+        Err(ChunkMeshingError::ChunkManagerError(error)) if error.is_globally_locked() => {
+            return CommandOutcome::Retry;
+        }
+        Err(ChunkMeshingError::NeighborsGenerating) => {
+            MeshJobOutcome::NeighborsGenerating(cmd.clone())
+        }
+        Err(ChunkMeshingError::ChunkManagerError(error)) => {
+            warn!(
+                "Error in '{label}' building chunk mesh for {}: {error}",
+                cmd.pos
+            );
+            MeshJobOutcome::Failed(cmd.clone())
+        }
+        Err(ChunkMeshingError::MesherError(error)) => {
+            error!(
+                "Error in '{label}' building chunk mesh for {}: {error}",
+                cmd.pos
+            );
+            MeshJobOutcome::Failed(cmd.clone())
+        }
+    };
+
+    // the receiver is only dropped when the mesh builder shuts down
+    let _ = params.finished.send(outcome);
+    CommandOutcome::Done
+}
+
 impl Worker {
     pub fn new(
         pool: &TaskPool,
@@ -64,7 +280,6 @@ impl Worker {
     ) -> Self {
         let atomic_interrupt = Arc::new(AtomicBool::new(false));
 
-        let task_label = label.clone();
         let task_interrupt = atomic_interrupt.clone();
         let task = pool.spawn(async move {
             let mut backlog_cmd = None::<MeshCommand>;
@@ -76,7 +291,7 @@ impl Worker {
                         Ok(cmd) => Some(cmd),
                         Err(RecvTimeoutError::Timeout) => None,
                         Err(RecvTimeoutError::Disconnected) => {
-                            warn!("Channel disconnected for meshing worker '{task_label}', worker is shutting down.");
+                            warn!("Channel disconnected for meshing worker '{label}', worker is shutting down.");
                             return;
                         }
                     }
@@ -84,41 +299,10 @@ impl Worker {
 
                 let Some(cmd) = cmd else { continue };
 
-                let cm = params.chunk_manager.clone();
-
-                let result = cm.with_neighbors::<_, Result<ChunkMeshData, ChunkMeshingError>>(cmd.pos, |neighbors| {
-                    let context = Context {
-                        neighbors,
-                        registries: &params.registries,
-                    };
-
-                    let chunk = cm.get_loaded_chunk(cmd.pos, false)?;
-                    Ok(chunk.with_read_access(|access| {
-                        params.mesher.build(access, context)
-                    })??)
-                }).map_err(ChunkMeshingError::from).custom_flatten();
-
-                match result {
-                    Ok(output) => {
-                        params.finished.send(FinishedChunkData {
-                            data: output,
-                            pos: cmd.pos,
-                            generation: cmd.generation
-                        }).unwrap();
-                    }
-                    Err(ChunkMeshingError::ChunkManagerError(error)) => {
-                        // backlog if globally locked
-                        if error.is_globally_locked() {
-                            backlog_cmd = Some(cmd);
-                            // sleep here to avoid busy looping
-                            thread::sleep(channel_timeout);
-                        }
-
-                        continue;
-                    },
-                    Err(ChunkMeshingError::MesherError(error)) => {
-                        error!("Error in worker '{task_label}' building chunk mesh for {}: {error}", cmd.pos);
-                    }
+                if let CommandOutcome::Retry = run_command(&mut params, &cmd, &label) {
+                    backlog_cmd = Some(cmd);
+                    // sleep here to avoid busy looping
+                    thread::sleep(channel_timeout);
                 }
             }
         });
@@ -126,7 +310,6 @@ impl Worker {
         Self {
             interrupt: atomic_interrupt,
             task,
-            label: label.clone(),
         }
     }
 
@@ -136,26 +319,132 @@ impl Worker {
     }
 }
 
+/// What came of a [`MeshCommand`], sent back to the [`MeshBuilder`] so it can stop tracking the chunk.
+pub enum MeshJobOutcome {
+    Finished(FinishedChunkData),
+    /// Some neighbors of the chunk were still being generated, so it couldn't be meshed yet.
+    NeighborsGenerating(MeshCommand),
+    /// Meshing the chunk failed (for example because it was unloaded), the error was logged by the worker.
+    Failed(MeshCommand),
+}
+
 pub struct FinishedChunkData {
     pub pos: ChunkPos,
     pub data: ChunkMeshData,
     pub generation: u64,
+    /// How long it took to light and mesh the chunk.
+    pub duration: Duration,
+}
+
+/// How a [`MeshBuilder`] runs its meshing jobs, configured with [`MeshController::backend`](super::MeshController::backend).
+#[derive(Resource, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MeshBackend {
+    /// Long-lived workers that receive commands over a channel, running on the task pool given to
+    /// [`MeshBuilder::new`]. Usually a dedicated pool, so meshing never competes with Bevy's own tasks for a thread.
+    Workers,
+    /// One task per command on Bevy's [`AsyncComputeTaskPool`], so meshing shares threads with the rest of
+    /// the engine instead of oversubscribing the CPU. At most [`MeshBuilderSettings::workers`] tasks run at once.
+    /// Commands that have to be retried are spawned again later, so tasks never block a thread of the pool.
+    AsyncCompute,
+    /// Meshes are built on the thread calling [`MeshBuilder::get_finished_meshes`], for platforms without
+    /// threads (like WASM). Every call builds meshes until `budget` has passed, so the queue is worked
+    /// through over several frames. At least one mesh is built per call so meshing always makes progress.
+    MainThread { budget: Duration },
+}
+
+impl MeshBackend {
+    /// The default budget per frame of [`MeshBackend::MainThread`].
+    pub const DEFAULT_MAIN_THREAD_BUDGET: Duration = Duration::from_millis(4);
+}
+
+impl Default for MeshBackend {
+    /// Workers, or meshing on the main thread when targeting WASM since it can't spawn threads.
+    fn default() -> Self {
+        if cfg!(target_arch = "wasm32") {
+            Self::MainThread {
+                budget: Self::DEFAULT_MAIN_THREAD_BUDGET,
+            }
+        } else {
+            Self::Workers
+        }
+    }
 }
 
 #[derive(Copy, Clone)]
 pub struct MeshBuilderSettings {
+    /// The number of workers, or the number of concurrent tasks for [`MeshBackend::AsyncCompute`].
     pub workers: usize,
+    pub backend: MeshBackend,
     pub job_channel_capacity: usize,
     // TODO: if a worker cant send its finished mesh immediately, then let it build another while waiting
+    #[allow(dead_code)]
     pub worker_mesh_backlog_capacity: usize,
+    /// Flat lighting is cheaper to mesh than smooth lighting. Only used for high quality meshes,
+    /// low quality meshes always use flat lighting.
+    pub lighting: LightingMode,
+    /// The order quads are extended in when merging them.
+    pub merge_policy: MergeAxisPolicy,
+    /// Which diagonal quads are split into triangles along.
+    pub triangulation: QuadTriangulation,
+    /// Merge quads across chunk borders to avoid tiny quads on chunk edges.
+    pub merge_borders: bool,
+    /// Whether the faces of chunk meshes are visible from both sides, see [`Mesher::sidedness`].
+    pub sidedness: MeshSidedness,
+    /// The factor that chunks are [downsampled](downsample) by before building low quality meshes.
+    /// Has to evenly divide the chunk size, a factor of 1 doesn't downsample.
+    pub low_quality_downsample: i32,
+    /// Split quads at T-junctions after meshing, see [`weld_t_junctions`]. This prevents cracks between
+    /// quads of different sizes, at the cost of more quads.
+    pub weld_t_junctions: bool,
+    /// Meshing tasks that were sent to the workers longer than this ago without finishing are assumed to be lost,
+    /// see [`MeshBuilder::reap_stale_tasks`].
+    pub stale_timeout: Duration,
+}
+
+#[cfg(test)]
+impl MeshBuilderSettings {
+    /// Settings for a single worker with tiny channels, meshing with flat lighting and without any extra passes.
+    pub fn new_test() -> Self {
+        Self {
+            workers: 1,
+            backend: MeshBackend::Workers,
+            job_channel_capacity: 2,
+            worker_mesh_backlog_capacity: 2,
+            lighting: LightingMode::Flat,
+            merge_policy: MergeAxisPolicy::default(),
+            triangulation: QuadTriangulation::default(),
+            merge_borders: false,
+            sidedness: MeshSidedness::Single,
+            low_quality_downsample: 1,
+            weld_t_junctions: false,
+            stale_timeout: Duration::from_secs(60),
+        }
+    }
+}
+
+/// A chunk that was queued for meshing but hasn't had its finished mesh drained yet.
+struct InFlightMesh {
+    /// The latest command queued for the chunk.
+    cmd: MeshCommand,
+    /// When a command for the chunk was last sent to the workers, `None` if it's still waiting to be sent.
+    sent: Option<Instant>,
 }
 
 #[derive(Resource)]
 pub struct MeshBuilder {
+    backend: MeshBackend,
     workers: Vec<Worker>,
+    /// Parameters for the tasks spawned by [`MeshBackend::AsyncCompute`].
+    task_params: WorkerParams,
+    /// Tasks of [`MeshBackend::AsyncCompute`], which return their command if it has to be retried.
+    tasks: Vec<Task<Option<MeshCommand>>>,
+    max_tasks: usize,
     cmds: Sender<MeshCommand>,
     pending: BinaryHeap<KeyedOrd<MeshCommand, RemeshPriority>>,
-    finished: Receiver<FinishedChunkData>,
+    /// The chunks that were queued but haven't had a finished mesh drained yet.
+    in_flight: ChunkMap<InFlightMesh>,
+    finished: Receiver<MeshJobOutcome>,
+    stale_timeout: Duration,
 }
 
 impl MeshBuilder {
@@ -165,22 +454,47 @@ impl MeshBuilder {
         registries: Registries,
         cm: Arc<ChunkManager>,
     ) -> Self {
+        assert!(
+            valid_factor(settings.low_quality_downsample),
+            "invalid low quality downsampling factor {}",
+            settings.low_quality_downsample
+        );
+
         let (cmd_sender, cmd_recver) =
             channel::bounded::<MeshCommand>(settings.job_channel_capacity);
-        let (mesh_sender, mesh_recver) = channel::unbounded::<FinishedChunkData>();
+        let (mesh_sender, mesh_recver) = channel::unbounded::<MeshJobOutcome>();
         let mut workers = Vec::<Worker>::with_capacity(settings.workers);
 
         let default_channel_timeout_duration = Duration::from_millis(50);
 
+        let mesher = GreedyMesher::new()
+            .with_merge_policy(settings.merge_policy)
+            .with_triangulation(settings.triangulation)
+            .with_border_merging(settings.merge_borders)
+            .with_sidedness(settings.sidedness);
+
+        // chunks with only full blocks are meshed with the faster cube-only mesher
+        let selector = |mesher: GreedyMesher| {
+            MesherSelector::new(vec![mesher.clone().with_cubes_only(true), mesher])
+        };
+
         let worker_params = WorkerParams {
             registries,
             chunk_manager: cm,
-            mesher: GreedyMesher::new(),
+            mesher: selector(mesher.clone().with_lighting(settings.lighting)),
+            low_quality_mesher: selector(mesher.with_lighting(LightingMode::Flat)),
+            low_quality_downsample: settings.low_quality_downsample,
+            weld_t_junctions: settings.weld_t_junctions,
             finished: mesh_sender,
             cmds: cmd_recver,
         };
 
-        for i in 0..settings.workers {
+        let worker_count = match settings.backend {
+            MeshBackend::Workers => settings.workers,
+            MeshBackend::AsyncCompute | MeshBackend::MainThread { .. } => 0,
+        };
+
+        for i in 0..worker_count {
             let worker = Worker::new(
                 pool,
                 worker_params.clone(),
@@ -192,21 +506,65 @@ impl MeshBuilder {
         }
 
         Self {
+            backend: settings.backend,
             workers,
+            task_params: worker_params,
+            tasks: Vec::new(),
+            max_tasks: max(1, settings.workers),
             pending: BinaryHeap::default(),
+            in_flight: ChunkMap::default(),
             cmds: cmd_sender,
             finished: mesh_recver,
+            stale_timeout: settings.stale_timeout,
         }
     }
 
     pub fn queue_jobs<I: Iterator<Item = MeshCommand>>(&mut self, cmds: I) {
-        self.pending.extend(cmds.map(KeyedOrd::new));
+        for cmd in cmds {
+            match self.in_flight.get_mut(cmd.pos) {
+                Some(in_flight) if in_flight.cmd.generation > cmd.generation => (),
+                Some(in_flight) => in_flight.cmd = cmd.clone(),
+                None => {
+                    self.in_flight.set(
+                        cmd.pos,
+                        InFlightMesh {
+                            cmd: cmd.clone(),
+                            sent: None,
+                        },
+                    );
+                }
+            }
+
+            self.pending.push(KeyedOrd::new(cmd));
+        }
+
+        self.dispatch();
+    }
+
+    /// Hand pending commands to the backend until it can't take any more.
+    fn dispatch(&mut self) {
+        match self.backend {
+            MeshBackend::Workers => self.send_to_workers(),
+            MeshBackend::AsyncCompute => self.spawn_tasks(),
+            // commands are only run when finished meshes are requested, to stay within the frame budget
+            MeshBackend::MainThread { .. } => (),
+        }
+    }
 
+    fn mark_sent(&mut self, pos: ChunkPos) {
+        if let Some(in_flight) = self.in_flight.get_mut(pos) {
+            in_flight.sent = Some(Instant::now());
+        }
+    }
+
+    fn send_to_workers(&mut self) {
         while let Some(next) = self.pending.pop() {
             let next = next.into_inner();
+            let pos = next.pos;
 
-            if let Err(error) = self.cmds.try_send(next) {
-                match error {
+            match self.cmds.try_send(next) {
+                Ok(()) => self.mark_sent(pos),
+                Err(error) => match error {
                     TrySendError::Disconnected(msg) => {
                         self.pending.push(KeyedOrd::new(msg));
                         error!("Could not send remesh command to workers because the channel is disconnected.");
@@ -216,24 +574,989 @@ impl MeshBuilder {
                         self.pending.push(KeyedOrd::new(msg));
                         break;
                     }
+                },
+            }
+        }
+    }
+
+    fn spawn_tasks(&mut self) {
+        // commands that have to be retried are spawned again instead of blocking a thread of the shared pool
+        let mut i = 0;
+        while i < self.tasks.len() {
+            if !self.tasks[i].is_finished() {
+                i += 1;
+                continue;
+            }
+
+            let task = self.tasks.swap_remove(i);
+            if let Some(cmd) = block_on(future::poll_once(task)).flatten() {
+                self.pending.push(KeyedOrd::new(cmd));
+            }
+        }
+
+        while self.tasks.len() < self.max_tasks {
+            let Some(next) = self.pending.pop() else {
+                break;
+            };
+
+            let cmd = next.into_inner();
+            let pos = cmd.pos;
+            let mut params = self.task_params.clone();
+
+            let task = AsyncComputeTaskPool::get().spawn(async move {
+                match run_command(&mut params, &cmd, "mesh_task") {
+                    CommandOutcome::Done => None,
+                    CommandOutcome::Retry => Some(cmd),
                 }
+            });
+
+            self.tasks.push(task);
+            self.mark_sent(pos);
+        }
+    }
+
+    /// Run pending commands on this thread until `budget` has passed, see [`MeshBackend::MainThread`].
+    fn mesh_on_this_thread(&mut self, budget: Duration) {
+        let start = Instant::now();
+
+        while let Some(next) = self.pending.pop() {
+            let cmd = next.into_inner();
+            self.mark_sent(cmd.pos);
+
+            if let CommandOutcome::Retry = run_command(&mut self.task_params, &cmd, "main_thread") {
+                // try again next frame instead of blocking this one
+                self.pending.push(KeyedOrd::new(cmd));
+                break;
+            }
+
+            if start.elapsed() >= budget {
+                break;
             }
         }
     }
 
+    /// Stop tracking the chunk at `pos`, for chunks that shouldn't be meshed anymore (like chunks that
+    /// were unloaded). Commands for it that were already sent still run, but their outcome is ignored.
+    pub fn cancel(&mut self, pos: ChunkPos) {
+        self.in_flight.remove(pos);
+    }
+
+    /// How long meshing tasks can take before they're considered lost, see [`MeshBuilderSettings::stale_timeout`].
+    pub fn stale_timeout(&self) -> Duration {
+        self.stale_timeout
+    }
+
+    /// The sidedness of the meshes built by this builder's meshers.
+    pub fn sidedness(&self) -> MeshSidedness {
+        self.task_params.mesher.sidedness()
+    }
+
+    /// Find the chunks that were sent to the workers longer than `timeout` ago and haven't finished meshing.
+    /// Their tasks were probably lost (for example because a worker panicked), so they're removed from the
+    /// pending chunks and their latest commands are returned so they can be queued again.
+    pub fn reap_stale_tasks(&mut self, timeout: Duration) -> Vec<MeshCommand> {
+        let now = Instant::now();
+        let mut stale = Vec::new();
+
+        self.in_flight.for_each_entry(|pos, in_flight| {
+            let Some(sent) = in_flight.sent else {
+                return;
+            };
+
+            let elapsed = now.duration_since(sent);
+            if elapsed >= timeout {
+                warn!(
+                    "Meshing task for chunk {pos} (generation {}) has been pending for {elapsed:?}, assuming it was lost",
+                    in_flight.cmd.generation
+                );
+                stale.push(pos);
+            }
+        });
+
+        stale
+            .into_iter()
+            .filter_map(|pos| self.in_flight.remove(pos))
+            .map(|in_flight| in_flight.cmd)
+            .collect()
+    }
+
     pub fn shutdown(self) {
         for worker in self.workers.into_iter() {
             block_on(worker.stop());
         }
+
+        for task in self.tasks.into_iter() {
+            block_on(task.cancel());
+        }
     }
 
-    pub fn get_finished_meshes(&self) -> Vec<FinishedChunkData> {
+    pub fn get_finished_meshes(&mut self) -> Vec<FinishedChunkData> {
+        match self.backend {
+            // make room for the commands that couldn't be spawned when they were queued
+            MeshBackend::AsyncCompute => self.spawn_tasks(),
+            MeshBackend::MainThread { budget } => self.mesh_on_this_thread(budget),
+            MeshBackend::Workers => (),
+        }
+
         let mut vec = Vec::with_capacity(self.finished.len());
 
-        while let Ok(finished) = self.finished.try_recv() {
-            vec.push(finished);
+        let mut waiting = Vec::new();
+
+        while let Ok(outcome) = self.finished.try_recv() {
+            let (pos, generation) = match &outcome {
+                MeshJobOutcome::Finished(finished) => (finished.pos, finished.generation),
+                MeshJobOutcome::NeighborsGenerating(cmd) | MeshJobOutcome::Failed(cmd) => {
+                    (cmd.pos, cmd.generation)
+                }
+            };
+
+            // a chunk is only done once its latest queued generation is finished (or failed)
+            let latest = self
+                .in_flight
+                .get(pos)
+                .is_some_and(|in_flight| in_flight.cmd.generation <= generation);
+
+            if latest {
+                self.in_flight.remove(pos);
+            }
+
+            match outcome {
+                MeshJobOutcome::Finished(finished) => vec.push(finished),
+                // the chunk still needs a mesh, so it goes back in the queue until its neighbors are generated
+                MeshJobOutcome::NeighborsGenerating(cmd) if latest => waiting.push(cmd),
+                MeshJobOutcome::NeighborsGenerating(_) | MeshJobOutcome::Failed(_) => (),
+            }
+        }
+
+        if !waiting.is_empty() {
+            self.queue_jobs(waiting.into_iter());
         }
 
         vec
     }
+
+    /// The number of chunks that are queued for meshing or being meshed, including chunks with finished meshes
+    /// that haven't been drained with [`MeshBuilder::get_finished_meshes`] yet.
+    pub fn pending_count(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    /// The positions of the chunks that are queued for meshing or being meshed, see [`MeshBuilder::pending_count`].
+    #[cfg(test)]
+    pub fn pending_positions(&self) -> Vec<ChunkPos> {
+        self.in_flight.iter().map(|(pos, _)| pos).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{
+        math::{ivec3, IVec2, IVec3},
+        tasks::TaskPoolBuilder,
+    };
+
+    use crate::{
+        data::tile::Face,
+        render::meshing::error::MesherResult,
+        testing_utils::mock_registries,
+        topo::{
+            access::{ReadAccess, WriteAccess},
+            block::BlockVoxel,
+            world::{
+                chunk::ChunkFlags, chunk_manager::GlobalLockState, CaoBlock, Chunk,
+                ChunkAccessInput, ChunkLoadState, Crra,
+            },
+        },
+    };
+
+    use super::*;
+
+    /// Records how many neighbors were gathered for it, and reads the given neighbor position if any.
+    struct NeighborCountingMesher {
+        radius: u8,
+        gathered: Option<usize>,
+        read: Option<IVec3>,
+    }
+
+    impl Mesher for NeighborCountingMesher {
+        fn build<'reg, 'chunk>(
+            &mut self,
+            _access: Crra<'chunk>,
+            cx: Context<'reg, 'chunk>,
+        ) -> MesherResult {
+            self.gathered = Some(cx.neighbors.gathered());
+
+            if let Some(pos) = self.read {
+                cx.neighbors.get_3d(pos).unwrap();
+            }
+
+            Ok(ChunkMeshData {
+                index_buffer: Vec::new(),
+                quad_buffer: Vec::new(),
+            })
+        }
+
+        fn neighbor_radius(&self) -> u8 {
+            self.radius
+        }
+    }
+
+    fn loaded_chunk_manager() -> ChunkManager {
+        let cm = ChunkManager::new_test();
+
+        for x in -1..=1 {
+            for y in -1..=1 {
+                for z in -1..=1 {
+                    cm.insert_test_chunk(ChunkPos::new(x, y, z), |_| ());
+                }
+            }
+        }
+
+        cm
+    }
+
+    #[test]
+    fn pending_commands_pop_in_priority_order() {
+        let mut pending = BinaryHeap::new();
+        pending.extend([5, 0, u32::MAX, 2].map(|priority| {
+            KeyedOrd::new(MeshCommand {
+                pos: ChunkPos::ZERO,
+                priority: RemeshPriority::new(priority),
+                generation: 0,
+                quality: MeshQuality::High,
+                stitched: NeighborSet::EMPTY,
+            })
+        }));
+
+        let popped = std::iter::from_fn(|| pending.pop())
+            .map(|cmd| cmd.priority)
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            vec![
+                RemeshPriority::HIGHEST,
+                RemeshPriority::new(2),
+                RemeshPriority::new(5),
+                RemeshPriority::LOWEST,
+            ],
+            popped
+        );
+    }
+
+    #[test]
+    fn mesh_chunk_border() {
+        let registries = mock_registries();
+
+        let block = || ChunkAccessInput::new(BlockVoxel::new_full(BlockVariantRegistry::FULL));
+
+        // a block on the border of the chunk, right next to the block on the border of the neighboring chunk
+        let cm = ChunkManager::new_test();
+        cm.insert_test_chunk(ChunkPos::new(0, 0, 0), |access| {
+            access.set(ivec3(Chunk::SIZE - 1, 4, 4), block()).unwrap();
+        });
+
+        let mut mesher = GreedyMesher::new();
+        let quads = |cm: &ChunkManager, mesher: &mut GreedyMesher| {
+            mesh_chunk(cm, &registries, ChunkPos::new(0, 0, 0), mesher)
+                .unwrap()
+                .quad_buffer
+                .len()
+        };
+
+        // the neighbor isn't loaded so the block is surrounded by air
+        assert_eq!(6, quads(&cm, &mut mesher));
+
+        cm.insert_test_chunk(ChunkPos::new(1, 0, 0), |access| {
+            access.set(ivec3(0, 4, 4), block()).unwrap();
+        });
+
+        // the face between the two blocks is hidden
+        assert_eq!(5, quads(&cm, &mut mesher));
+    }
+
+    #[test]
+    fn mesh_chunk_waits_for_generating_neighbors() {
+        let registries = mock_registries();
+
+        let cm = ChunkManager::new_test();
+        let center = ChunkPos::new(0, 0, 0);
+        let neighbor = ChunkPos::new(1, 0, 0);
+        let corner = ChunkPos::new(1, 1, 1);
+        for pos in [center, neighbor, corner] {
+            cm.insert_test_chunk(pos, |_| ());
+        }
+
+        let set_generating = |pos, generating| {
+            cm.get_loaded_chunk(pos, false)
+                .unwrap()
+                .update_flags(|flags| flags.set(ChunkFlags::GENERATING, generating));
+        };
+
+        let mut mesher = GreedyMesher::new();
+        let mut mesh = || mesh_chunk(&cm, &registries, center, &mut mesher);
+
+        assert_eq!(
+            ChunkLoadState::Unloaded,
+            cm.load_state(ChunkPos::new(-1, 0, 0))
+        );
+        assert_eq!(ChunkLoadState::Ready, cm.load_state(neighbor));
+
+        // the corner neighbor is outside the radius of the mesher, so it doesn't hold meshing back
+        set_generating(corner, true);
+        assert_eq!(ChunkLoadState::Generating, cm.load_state(corner));
+        assert!(mesh().is_ok());
+
+        set_generating(neighbor, true);
+        assert!(matches!(
+            mesh(),
+            Err(ChunkMeshingError::NeighborsGenerating)
+        ));
+
+        set_generating(neighbor, false);
+        assert!(mesh().is_ok());
+    }
+
+    #[test]
+    fn downsampled_meshes_have_fewer_quads() {
+        let registries = mock_registries();
+
+        let cm = ChunkManager::new_test();
+        let pos = ChunkPos::new(0, 0, 0);
+        cm.insert_test_chunk(pos, |access| {
+            for ls in Chunk::BOUNDING_BOX.cartesian_iter() {
+                // a floor with scattered holes in it, and scattered blocks above it
+                let floor = ls.y < Chunk::SIZE / 2;
+                let speck = (ls.x * 7 + ls.y * 13 + ls.z * 5) % 11 == 0;
+                if floor != speck {
+                    let block = BlockVoxel::new_full(BlockVariantRegistry::FULL);
+                    access.set(ls, ChunkAccessInput::new(block)).unwrap();
+                }
+            }
+        });
+
+        let mut mesher = GreedyMesher::new();
+        let full = mesh_chunk(&cm, &registries, pos, &mut mesher).unwrap();
+        let downsampled =
+            mesh_chunk_downsampled(&cm, &registries, pos, &mut mesher, 2, NeighborSet::EMPTY)
+                .unwrap();
+
+        assert!(!downsampled.quad_buffer.is_empty());
+        assert!(downsampled.quad_buffer.len() < full.quad_buffer.len());
+
+        assert!(matches!(
+            mesh_chunk_downsampled(&cm, &registries, pos, &mut mesher, 3, NeighborSet::EMPTY),
+            Err(ChunkMeshingError::MesherError(_))
+        ));
+    }
+
+    #[test]
+    fn lod_border_has_no_holes() {
+        let registries = mock_registries();
+
+        let cm = ChunkManager::new_test();
+        let fine = ChunkPos::new(0, 0, 0);
+        let coarse = ChunkPos::new(1, 0, 0);
+        let solid = BlockVoxel::new_full(BlockVariantRegistry::FULL);
+
+        cm.insert_filled_test_chunk(fine, solid.clone());
+        // specks on the border that are too sparse to survive downsampling
+        cm.insert_test_chunk(coarse, |access| {
+            for ls in Chunk::BOUNDING_BOX.cartesian_iter() {
+                if ls.x == 0 && (ls.y + ls.z) % 3 == 0 {
+                    access
+                        .set(ls, ChunkAccessInput::new(solid.clone()))
+                        .unwrap();
+                }
+            }
+        });
+
+        let mut mesher = GreedyMesher::new();
+        let fine_mesh = mesh_chunk(&cm, &registries, fine, &mut mesher).unwrap();
+        // the fine chunk is to the south of the coarse chunk
+        let stitched = [Face::South.normal()].into_iter().collect();
+        let coarse_mesh =
+            mesh_chunk_downsampled(&cm, &registries, coarse, &mut mesher, 2, stitched).unwrap();
+
+        // the fine chunk is solid, so wherever the coarse chunk is air on the border there has to be a face
+        // of the fine chunk. The faces of the fine chunk are culled against the original voxels of the coarse
+        // chunk, so the coarse chunk has to keep the solid specks on its border
+        let coarse_solid = cm.get_loaded_chunk(coarse, false).unwrap().read_snapshot();
+
+        let border = |face: Face, magnitude: i32, mesh: &ChunkMeshData, pos: IVec2| {
+            mesh.quad_buffer.iter().any(|quad| {
+                quad.bitfields.get_face() == face
+                    && quad.magnitude == magnitude
+                    && quad.min.cmple(pos.as_vec2()).all()
+                    && quad.max.cmpge(pos.as_vec2() + 1.0).all()
+            })
+        };
+
+        for y in 0..Chunk::SIZE {
+            for z in 0..Chunk::SIZE {
+                let pos = ivec3(0, y, z);
+                let plane = Face::North.to_plane_coords(pos);
+                let speck = matches!(
+                    coarse_solid.get(pos).unwrap().block,
+                    CaoBlock::Full(block) if block.id == BlockVariantRegistry::FULL
+                );
+
+                let fine_face =
+                    border(Face::North, Chunk::SUBDIVIDED_CHUNK_SIZE, &fine_mesh, plane);
+                assert_eq!(!speck, fine_face, "at {pos}");
+
+                // there's nothing to cover between the specks and the solid chunk
+                assert!(!border(Face::South, 0, &coarse_mesh, plane), "at {pos}");
+                // but the specks are still there, facing away from the fine chunk
+                if speck {
+                    assert!(border(Face::North, 4, &coarse_mesh, plane), "at {pos}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn low_quality_neighbors_mesh_against_downsampled_voxels() {
+        let registries = mock_registries();
+
+        let cm = ChunkManager::new_test();
+        let solid_pos = ChunkPos::new(0, 0, 0);
+        let specks_pos = ChunkPos::new(1, 0, 0);
+        let solid = BlockVoxel::new_full(BlockVariantRegistry::FULL);
+
+        cm.insert_filled_test_chunk(solid_pos, solid.clone());
+        // specks on the border that are too sparse to survive downsampling
+        cm.insert_test_chunk(specks_pos, |access| {
+            for ls in Chunk::BOUNDING_BOX.cartesian_iter() {
+                if ls.x == 0 && (ls.y + ls.z) % 3 == 0 {
+                    access
+                        .set(ls, ChunkAccessInput::new(solid.clone()))
+                        .unwrap();
+                }
+            }
+        });
+
+        // both chunks are meshed in low quality, so neither stitches its border
+        let mut mesher = GreedyMesher::new();
+        let solid_mesh = mesh_chunk_downsampled(
+            &cm,
+            &registries,
+            solid_pos,
+            &mut mesher,
+            2,
+            NeighborSet::EMPTY,
+        )
+        .unwrap();
+        let specks_mesh = mesh_chunk_downsampled(
+            &cm,
+            &registries,
+            specks_pos,
+            &mut mesher,
+            2,
+            NeighborSet::EMPTY,
+        )
+        .unwrap();
+
+        let border = |face: Face, magnitude: i32, mesh: &ChunkMeshData, pos: IVec2| {
+            mesh.quad_buffer.iter().any(|quad| {
+                quad.bitfields.get_face() == face
+                    && quad.magnitude == magnitude
+                    && quad.min.cmple(pos.as_vec2()).all()
+                    && quad.max.cmpge(pos.as_vec2() + 1.0).all()
+            })
+        };
+
+        // the specks are gone in the downsampled neighbor, so the whole border of the solid chunk is exposed
+        for y in 0..Chunk::SIZE {
+            for z in 0..Chunk::SIZE {
+                let plane = Face::North.to_plane_coords(ivec3(0, y, z));
+                assert!(
+                    border(
+                        Face::North,
+                        Chunk::SUBDIVIDED_CHUNK_SIZE,
+                        &solid_mesh,
+                        plane
+                    ),
+                    "at {plane}"
+                );
+                assert!(!border(Face::South, 0, &specks_mesh, plane), "at {plane}");
+            }
+        }
+
+        // stitching the border facing the solid chunk keeps the specks, but only on that side
+        let stitched = [Face::South.normal()].into_iter().collect();
+        let original = cm
+            .get_loaded_chunk(specks_pos, false)
+            .unwrap()
+            .read_snapshot();
+        let varreg = registries.get_registry::<BlockVariantRegistry>().unwrap();
+        let expanded = downsample(&original.read_access(), 2, &varreg)
+            .unwrap()
+            .expand_stitched(&original.read_access(), stitched)
+            .unwrap();
+        let expanded = expanded.read_access();
+
+        for ls in Chunk::BOUNDING_BOX.cartesian_iter() {
+            let is_solid = matches!(
+                expanded.get(ls).unwrap().block,
+                CaoBlock::Full(block) if block.id == BlockVariantRegistry::FULL
+            );
+            let speck = ls.x == 0 && (ls.y + ls.z) % 3 == 0;
+            assert_eq!(speck, is_solid, "at {ls}");
+        }
+    }
+
+    #[test]
+    fn concurrent_meshing_of_shared_chunk() {
+        let registries = mock_registries();
+
+        let cm = ChunkManager::new_test();
+        for x in -1..=1 {
+            cm.insert_test_chunk(ChunkPos::new(x, 0, 0), |access| {
+                for pos in Chunk::BOUNDING_BOX.cartesian_iter() {
+                    if (pos.x + pos.y * 3 + pos.z * 7) % 5 == 0 {
+                        let block = BlockVoxel::new_full(BlockVariantRegistry::FULL);
+                        access.set(pos, ChunkAccessInput::new(block)).unwrap();
+                    }
+                }
+            });
+        }
+
+        let pos = ChunkPos::new(0, 0, 0);
+        let expected = mesh_chunk(&cm, &registries, pos, &mut GreedyMesher::new())
+            .unwrap()
+            .quad_buffer;
+        let done = AtomicBool::new(false);
+
+        std::thread::scope(|scope| {
+            // keeps writing the voxels the chunk already has, which copies the storage out from under
+            // the snapshots being meshed without changing the mesh
+            scope.spawn(|| {
+                while !done.load(Ordering::Relaxed) {
+                    let cref = cm.get_loaded_chunk(pos, false).unwrap();
+                    cref.with_access(true, |mut access| {
+                        let block = BlockVoxel::new_full(BlockVariantRegistry::FULL);
+                        access
+                            .set(IVec3::ZERO, ChunkAccessInput::new(block))
+                            .unwrap();
+                    })
+                    .unwrap();
+                    std::thread::yield_now();
+                }
+            });
+
+            let workers = (0..3)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut mesher = GreedyMesher::new();
+                        for _ in 0..3 {
+                            let mesh = mesh_chunk(&cm, &registries, pos, &mut mesher).unwrap();
+                            assert_eq!(expected, mesh.quad_buffer);
+                        }
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            let results = workers
+                .into_iter()
+                .map(|worker| worker.join())
+                .collect::<Vec<_>>();
+            // stop the writer before a failed worker panics here, or the scope would wait on it forever
+            done.store(true, Ordering::Relaxed);
+
+            for result in results {
+                result.unwrap();
+            }
+        });
+    }
+
+    #[test]
+    fn mesh_chunk_gathers_neighbors_in_radius() {
+        let cm = loaded_chunk_manager();
+        let registries = Registries::new();
+
+        for (radius, expected) in [(0, 0), (1, 6), (2, 18), (3, 26)] {
+            let mut mesher = NeighborCountingMesher {
+                radius,
+                gathered: None,
+                read: None,
+            };
+
+            mesh_chunk(&cm, &registries, ChunkPos::new(0, 0, 0), &mut mesher).unwrap();
+            assert_eq!(Some(expected), mesher.gathered);
+        }
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "outside of the gathered radius")]
+    fn reading_outside_neighbor_radius_panics() {
+        let cm = loaded_chunk_manager();
+        let registries = Registries::new();
+
+        let mut mesher = NeighborCountingMesher {
+            radius: 0,
+            gathered: None,
+            read: Some(IVec3::splat(-1)),
+        };
+
+        let _ = mesh_chunk(&cm, &registries, ChunkPos::new(0, 0, 0), &mut mesher);
+    }
+
+    #[test]
+    fn pending_chunks_until_drained() {
+        let registries = mock_registries();
+
+        let task_pool = TaskPoolBuilder::new().num_threads(2).build();
+        let mut builder = MeshBuilder::new(
+            MeshBuilderSettings {
+                workers: 2,
+                ..MeshBuilderSettings::new_test()
+            },
+            &task_pool,
+            registries,
+            Arc::new(loaded_chunk_manager()),
+        );
+
+        let positions = [
+            ChunkPos::new(0, 0, 0),
+            ChunkPos::new(1, 0, 0),
+            ChunkPos::new(0, -1, 0),
+        ];
+
+        builder.queue_jobs(positions.into_iter().map(|pos| MeshCommand {
+            pos,
+            priority: RemeshPriority::HIGHEST,
+            generation: 0,
+            quality: MeshQuality::High,
+            stitched: NeighborSet::EMPTY,
+        }));
+
+        // nothing counts as done until it's drained, no matter how quickly the workers are
+        assert_eq!(3, builder.pending_count());
+        let mut pending = builder.pending_positions();
+        pending.sort_by_key(|pos| pos.as_ivec3().to_array());
+        let mut expected = positions.to_vec();
+        expected.sort_by_key(|pos| pos.as_ivec3().to_array());
+        assert_eq!(expected, pending);
+
+        let start = Instant::now();
+        let mut finished = 0;
+        while builder.pending_count() > 0 {
+            assert!(
+                start.elapsed() < Duration::from_secs(30),
+                "timed out waiting for chunks to mesh"
+            );
+
+            // keep sending the commands that didn't fit in the channel
+            builder.queue_jobs(std::iter::empty());
+            finished += builder.get_finished_meshes().len();
+            thread::sleep(Duration::from_millis(5));
+        }
+
+        assert_eq!(3, finished);
+        assert!(builder.pending_positions().is_empty());
+
+        builder.shutdown();
+    }
+
+    #[test]
+    fn reap_lost_tasks() {
+        // there are no workers to receive the command, so it's sent but never finishes
+        let registries = mock_registries();
+
+        let task_pool = TaskPoolBuilder::new().num_threads(1).build();
+        let mut builder = MeshBuilder::new(
+            MeshBuilderSettings {
+                workers: 0,
+                ..MeshBuilderSettings::new_test()
+            },
+            &task_pool,
+            registries,
+            Arc::new(loaded_chunk_manager()),
+        );
+
+        let pos = ChunkPos::new(0, 0, 0);
+        builder.queue_jobs(std::iter::once(MeshCommand {
+            pos,
+            priority: RemeshPriority::HIGHEST,
+            generation: 4,
+            quality: MeshQuality::High,
+            stitched: NeighborSet::EMPTY,
+        }));
+
+        assert!(builder.reap_stale_tasks(Duration::from_secs(60)).is_empty());
+        assert_eq!(1, builder.pending_count());
+
+        thread::sleep(Duration::from_millis(100));
+        assert!(builder.get_finished_meshes().is_empty());
+
+        let stale = builder.reap_stale_tasks(Duration::from_millis(10));
+        assert_eq!(1, stale.len());
+        assert_eq!(pos, stale[0].pos);
+        assert_eq!(4, stale[0].generation);
+        assert_eq!(0, builder.pending_count());
+
+        builder.shutdown();
+    }
+
+    #[test]
+    fn commands_waiting_on_neighbors_are_requeued() {
+        let registries = mock_registries();
+
+        let cm = Arc::new(loaded_chunk_manager());
+        let neighbor = cm.get_loaded_chunk(ChunkPos::new(1, 0, 0), false).unwrap();
+        neighbor.update_flags(|flags| flags.insert(ChunkFlags::GENERATING));
+
+        let task_pool = TaskPoolBuilder::new().num_threads(1).build();
+        let mut builder = MeshBuilder::new(
+            MeshBuilderSettings {
+                backend: MeshBackend::MainThread {
+                    budget: MeshBackend::DEFAULT_MAIN_THREAD_BUDGET,
+                },
+                ..MeshBuilderSettings::new_test()
+            },
+            &task_pool,
+            registries,
+            cm.clone(),
+        );
+
+        let pos = ChunkPos::new(0, 0, 0);
+        builder.queue_jobs(std::iter::once(MeshCommand {
+            pos,
+            priority: RemeshPriority::HIGHEST,
+            generation: 0,
+            quality: MeshQuality::High,
+            stitched: NeighborSet::EMPTY,
+        }));
+
+        // the chunk is still waiting to be meshed, but it isn't sent anywhere so it can't go stale
+        for _ in 0..3 {
+            assert!(builder.get_finished_meshes().is_empty());
+            assert_eq!(vec![pos], builder.pending_positions());
+            assert!(builder.reap_stale_tasks(Duration::ZERO).is_empty());
+        }
+
+        neighbor.update_flags(|flags| flags.remove(ChunkFlags::GENERATING));
+        let finished = builder.get_finished_meshes();
+        assert_eq!(1, finished.len());
+        assert_eq!(pos, finished[0].pos);
+        assert_eq!(0, builder.pending_count());
+
+        builder.shutdown();
+    }
+
+    #[test]
+    fn failed_commands_are_not_pending() {
+        let registries = mock_registries();
+
+        let task_pool = TaskPoolBuilder::new().num_threads(1).build();
+        let mut builder = MeshBuilder::new(
+            MeshBuilderSettings {
+                backend: MeshBackend::MainThread {
+                    budget: MeshBackend::DEFAULT_MAIN_THREAD_BUDGET,
+                },
+                ..MeshBuilderSettings::new_test()
+            },
+            &task_pool,
+            registries,
+            Arc::new(loaded_chunk_manager()),
+        );
+
+        // the chunk isn't loaded, so meshing it fails
+        builder.queue_jobs(std::iter::once(MeshCommand {
+            pos: ChunkPos::new(100, 100, 100),
+            priority: RemeshPriority::HIGHEST,
+            generation: 0,
+            quality: MeshQuality::High,
+            stitched: NeighborSet::EMPTY,
+        }));
+        assert_eq!(1, builder.pending_count());
+
+        assert!(builder.get_finished_meshes().is_empty());
+        assert_eq!(0, builder.pending_count());
+
+        builder.shutdown();
+    }
+
+    fn mesh_with_backend(
+        backend: MeshBackend,
+        cm: Arc<ChunkManager>,
+        pos: ChunkPos,
+    ) -> ChunkMeshData {
+        let registries = mock_registries();
+
+        let task_pool = TaskPoolBuilder::new().num_threads(2).build();
+        let mut builder = MeshBuilder::new(
+            MeshBuilderSettings {
+                workers: 2,
+                backend,
+                lighting: LightingMode::Smooth,
+                ..MeshBuilderSettings::new_test()
+            },
+            &task_pool,
+            registries,
+            cm,
+        );
+
+        builder.queue_jobs(std::iter::once(MeshCommand {
+            pos,
+            priority: RemeshPriority::HIGHEST,
+            generation: 0,
+            quality: MeshQuality::High,
+            stitched: NeighborSet::EMPTY,
+        }));
+
+        let start = Instant::now();
+        loop {
+            assert!(
+                start.elapsed() < Duration::from_secs(30),
+                "timed out waiting for {backend:?} to mesh"
+            );
+
+            if let Some(finished) = builder.get_finished_meshes().pop() {
+                builder.shutdown();
+                return finished.data;
+            }
+
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn backends_build_identical_meshes() {
+        AsyncComputeTaskPool::get_or_init(TaskPool::new);
+
+        let cm = Arc::new(loaded_chunk_manager());
+        let stone = ChunkAccessInput::new(BlockVoxel::new_full(BlockVariantRegistry::FULL));
+        for ws in [
+            ivec3(0, 0, 0),
+            ivec3(1, 0, 0),
+            ivec3(1, 1, 0),
+            ivec3(5, 3, 7),
+        ] {
+            cm.set_voxel(ws, stone.clone()).unwrap();
+        }
+
+        let pos = ChunkPos::new(0, 0, 0);
+        let workers = mesh_with_backend(MeshBackend::Workers, cm.clone(), pos);
+        let tasks = mesh_with_backend(MeshBackend::AsyncCompute, cm.clone(), pos);
+        let main_thread = mesh_with_backend(
+            MeshBackend::MainThread {
+                budget: MeshBackend::DEFAULT_MAIN_THREAD_BUDGET,
+            },
+            cm,
+            pos,
+        );
+
+        assert!(!workers.is_empty());
+        assert_eq!(workers.index_buffer, tasks.index_buffer);
+        assert_eq!(workers.quad_buffer, tasks.quad_buffer);
+        assert_eq!(workers.index_buffer, main_thread.index_buffer);
+        assert_eq!(workers.quad_buffer, main_thread.quad_buffer);
+    }
+
+    #[test]
+    fn async_compute_tasks_dont_wait_for_the_global_lock() {
+        AsyncComputeTaskPool::get_or_init(TaskPool::new);
+
+        let registries = mock_registries();
+
+        let cm = Arc::new(loaded_chunk_manager());
+        let task_pool = TaskPoolBuilder::new().num_threads(1).build();
+        let mut builder = MeshBuilder::new(
+            MeshBuilderSettings {
+                backend: MeshBackend::AsyncCompute,
+                ..MeshBuilderSettings::new_test()
+            },
+            &task_pool,
+            registries,
+            cm.clone(),
+        );
+
+        thread::scope(|scope| {
+            // the chunk manager is globally locked while a forced global lock waits for this chunk to be released.
+            // the chunk is dropped if anything below panics, so the scope can't hang
+            let held = cm.get_loaded_chunk(ChunkPos::new(1, 1, 1), false).unwrap();
+            scope.spawn(|| cm.with_global_lock(None, true, |_| ()));
+
+            let start = Instant::now();
+            while cm.global_lock_state() != GlobalLockState::Locked {
+                assert!(start.elapsed() < Duration::from_secs(30));
+                thread::yield_now();
+            }
+
+            builder.queue_jobs(std::iter::once(MeshCommand {
+                pos: ChunkPos::new(0, 0, 0),
+                priority: RemeshPriority::HIGHEST,
+                generation: 0,
+                quality: MeshQuality::High,
+                stitched: NeighborSet::EMPTY,
+            }));
+
+            // the task gives its command back instead of holding on to a thread of the pool until it's unlocked
+            while !builder.tasks.iter().all(Task::is_finished) {
+                assert!(
+                    start.elapsed() < Duration::from_secs(30),
+                    "timed out waiting for the task to give up"
+                );
+                thread::sleep(Duration::from_millis(5));
+            }
+
+            assert!(builder.get_finished_meshes().is_empty());
+            assert_eq!(1, builder.pending_count());
+            drop(held);
+        });
+
+        let start = Instant::now();
+        while builder.get_finished_meshes().is_empty() {
+            assert!(
+                start.elapsed() < Duration::from_secs(30),
+                "timed out waiting for the retried command"
+            );
+            thread::sleep(Duration::from_millis(5));
+        }
+
+        assert_eq!(0, builder.pending_count());
+        builder.shutdown();
+    }
+
+    #[test]
+    fn main_thread_backend_respects_budget() {
+        let registries = mock_registries();
+
+        let task_pool = TaskPoolBuilder::new().num_threads(1).build();
+        let mut builder = MeshBuilder::new(
+            MeshBuilderSettings {
+                job_channel_capacity: 1,
+                worker_mesh_backlog_capacity: 1,
+                // a zero budget is used up by the first mesh of every frame
+                backend: MeshBackend::MainThread {
+                    budget: Duration::ZERO,
+                },
+                ..MeshBuilderSettings::new_test()
+            },
+            &task_pool,
+            registries,
+            Arc::new(loaded_chunk_manager()),
+        );
+
+        builder.queue_jobs((-1..=1).map(|x| MeshCommand {
+            pos: ChunkPos::new(x, 0, 0),
+            priority: RemeshPriority::HIGHEST,
+            generation: 0,
+            quality: MeshQuality::High,
+            stitched: NeighborSet::EMPTY,
+        }));
+
+        // nothing is built until finished meshes are requested
+        assert_eq!(3, builder.pending_count());
+
+        for remaining in [2, 1, 0] {
+            assert_eq!(1, builder.get_finished_meshes().len());
+            assert_eq!(remaining, builder.pending_count());
+        }
+
+        assert!(builder.get_finished_meshes().is_empty());
+        builder.shutdown();
+    }
 }
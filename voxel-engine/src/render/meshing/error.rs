@@ -10,6 +10,8 @@ pub enum ChunkMeshingError {
     MesherError(#[from] MesherError),
     #[error(transparent)]
     ChunkManagerError(#[from] ChunkManagerError),
+    #[error("Neighbors of the chunk are still being generated")]
+    NeighborsGenerating,
 }
 
 #[derive(te::Error, Debug)]
@@ -0,0 +1,332 @@
+use bevy::math::{ivec2, ivec3, vec3, IVec3, Vec2, Vec3};
+
+use crate::{
+    data::{
+        registries::{block::BlockVariantRegistry, Registry},
+        tile::Face,
+        voxel::VoxelModel,
+    },
+    render::{
+        meshing::lighting::{corner_index, corner_light, LightingMode},
+        quad::{GpuQuad, GpuQuadBitfields},
+    },
+    topo::{
+        access::ReadAccess,
+        block::{FullBlock, SubdividedBlock},
+        light::ChunkLight,
+        neighbors::Neighbors,
+        world::{CaoBlock, Chunk, Crra},
+    },
+};
+
+/// The top surface of a fluid voxel. Unlike regular quads the corners of a fluid surface can be at different
+/// heights, so the surface slopes towards neighboring fluids with lower levels.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FluidQuad {
+    /// The localspace position of the fluid voxel this surface belongs to.
+    pub pos: IVec3,
+    /// The fluid block at the position.
+    pub block: FullBlock,
+    /// The height of each corner above the bottom of the voxel, between 0 and 1.
+    /// Indexed by [`corner_index`], with the X and Z axes as the facespace X and Y axes.
+    pub heights: [f32; 4],
+}
+
+impl FluidQuad {
+    pub fn face(&self) -> Face {
+        Face::Top
+    }
+
+    /// The localspace position of each corner, indexed like [`FluidQuad::heights`].
+    pub fn positions(&self) -> [Vec3; 4] {
+        let min = self.pos.as_vec3();
+
+        [(false, false), (true, false), (false, true), (true, true)].map(|(max_x, max_z)| {
+            let height = self.heights[corner_index(max_x, max_z)];
+            min + vec3(max_x as u8 as f32, height, max_z as u8 as f32)
+        })
+    }
+
+    /// The surface as a quad on the top face of the voxel, with its corners lowered to their heights
+    /// (see [`GpuQuadBitfields::with_corner_depths`]). Returns `None` if the fluid has no block model
+    /// to take the texture from.
+    pub fn gpu_quad(
+        &self,
+        registry: &BlockVariantRegistry,
+        light: &ChunkLight,
+        mode: LightingMode,
+    ) -> Option<GpuQuad> {
+        const SUBDIVS: i32 = SubdividedBlock::SUBDIVISIONS;
+
+        let model = registry.get_by_id(self.block.id).model?;
+        let submodel = self
+            .block
+            .rotation
+            .map(|r| model.rotated_submodel(r))
+            .unwrap_or(model.default_submodel());
+        let texture = submodel.texture(Face::Top);
+
+        let min = ivec2(self.pos.x, self.pos.z);
+        // the top layer of microblocks in the voxel
+        let magnitude = self.pos.y * SUBDIVS + SUBDIVS - 1;
+
+        Some(GpuQuad {
+            texture_id: texture.id.as_u32(),
+            bitfields: GpuQuadBitfields::new()
+                .with_rotation(texture.rotation)
+                .with_face(Face::Top)
+                .with_corner_depths(self.heights.map(|height| 1.0 - height)),
+            min: min.as_vec2(),
+            max: min.as_vec2() + Vec2::ONE,
+            magnitude: magnitude + 1,
+            light: corner_light(
+                light,
+                Face::Top,
+                magnitude,
+                min * SUBDIVS,
+                min * SUBDIVS + (SUBDIVS - 1),
+                mode,
+            ),
+        })
+    }
+}
+
+/// The height of the surface of a fluid with the given level, relative to the bottom of the voxel.
+pub fn fluid_height(level: u8) -> f32 {
+    level.min(VoxelModel::MAX_FLUID_LEVEL) as f32 / VoxelModel::MAX_FLUID_LEVEL as f32
+}
+
+/// Builds the sloped top surfaces of the fluids in a chunk. Only variants with a [`VoxelModel::Fluid`] model
+/// (see [`BlockOptions::fluid_level`](crate::data::registries::block::BlockOptions)) are considered fluids.
+pub struct FluidSurfaces<'a, 'chunk> {
+    access: &'a Crra<'chunk>,
+    neighbors: &'a Neighbors<'chunk>,
+    registry: &'a BlockVariantRegistry,
+}
+
+impl<'a, 'chunk> FluidSurfaces<'a, 'chunk> {
+    pub fn new(
+        access: &'a Crra<'chunk>,
+        neighbors: &'a Neighbors<'chunk>,
+        registry: &'a BlockVariantRegistry,
+    ) -> Self {
+        Self {
+            access,
+            neighbors,
+            registry,
+        }
+    }
+
+    /// The block at the given localspace position, which may be in a neighboring chunk.
+    fn block(&self, pos: IVec3) -> Option<CaoBlock<'_>> {
+        if Chunk::BOUNDING_BOX.contains(pos) {
+            Some(self.access.get(pos).ok()?.block)
+        } else {
+            Some(self.neighbors.get_3d(pos).ok()?.block)
+        }
+    }
+
+    /// The fluid level of the voxel at the given localspace position, which may be in a neighboring chunk.
+    fn level(&self, pos: IVec3) -> Option<u8> {
+        match self.block(pos)? {
+            CaoBlock::Full(block) if !self.registry.is_air(block.id) => self
+                .registry
+                .get_by_id(block.id)
+                .voxel_model()?
+                .fluid_level(),
+            _ => None,
+        }
+    }
+
+    /// Whether the surface of a fluid at the given position is hidden by an opaque block above it.
+    fn covered(&self, pos: IVec3) -> bool {
+        match self.block(pos + IVec3::Y) {
+            Some(CaoBlock::Full(block)) => {
+                !self.registry.is_air(block.id)
+                    && self
+                        .registry
+                        .get_by_id(block.id)
+                        .options
+                        .transparency
+                        .is_opaque()
+            }
+            _ => false,
+        }
+    }
+
+    /// The height of the fluid surface at a corner of the top face of the voxel at `pos`. The corner is shared
+    /// by 4 columns of voxels, and its height is the average height of the fluids in those columns. If any of
+    /// the columns has fluid above it the corner is raised all the way up, so the surface meets the fluid above.
+    fn corner_height(&self, pos: IVec3, max_x: bool, max_z: bool) -> f32 {
+        let mut total = 0.0;
+        let mut fluids = 0;
+
+        for dx in [max_x as i32 - 1, max_x as i32] {
+            for dz in [max_z as i32 - 1, max_z as i32] {
+                let column = pos + ivec3(dx, 0, dz);
+
+                if self.level(column + IVec3::Y).is_some() {
+                    return 1.0;
+                }
+
+                if let Some(level) = self.level(column) {
+                    total += fluid_height(level);
+                    fluids += 1;
+                }
+            }
+        }
+
+        if fluids == 0 {
+            0.0
+        } else {
+            total / fluids as f32
+        }
+    }
+
+    /// The top surface of the fluid at the given localspace position. Returns `None` if there's no fluid
+    /// at the position, or if the fluid is covered by more fluid or an opaque block.
+    pub fn top_quad(&self, pos: IVec3) -> Option<FluidQuad> {
+        self.level(pos)?;
+
+        let Some(CaoBlock::Full(block)) = self.block(pos) else {
+            return None;
+        };
+
+        if self.level(pos + IVec3::Y).is_some() || self.covered(pos) {
+            return None;
+        }
+
+        let mut heights = [0.0; 4];
+        for (max_x, max_z) in [(false, false), (true, false), (false, true), (true, true)] {
+            heights[corner_index(max_x, max_z)] = self.corner_height(pos, max_x, max_z);
+        }
+
+        Some(FluidQuad {
+            pos,
+            block,
+            heights,
+        })
+    }
+
+    /// The top surfaces of all the fluids in the chunk.
+    pub fn build(&self) -> Vec<FluidQuad> {
+        Chunk::BOUNDING_BOX
+            .cartesian_iter()
+            .filter_map(|pos| self.top_quad(pos))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::vec2;
+
+    use crate::{
+        data::registries::texture::TextureRegistry,
+        testing_utils::MockChunk,
+        topo::{
+            access::WriteAccess, block::BlockVoxel, neighbors::NeighborsBuilder,
+            world::ChunkAccessInput,
+        },
+    };
+
+    use super::*;
+
+    #[test]
+    fn fluid_slopes_towards_lower_neighbor() {
+        let texreg = TextureRegistry::new_mock();
+        let varreg = BlockVariantRegistry::new_mock(&texreg);
+
+        let chunk = MockChunk::new(BlockVoxel::new_full(BlockVariantRegistry::VOID));
+        let mut access = chunk.access();
+
+        let source = ivec3(4, 4, 4);
+        let source_block = BlockVoxel::new_full(BlockVariantRegistry::WATER);
+        let lower_block = BlockVoxel::new_full(BlockVariantRegistry::SHALLOW_WATER);
+
+        access
+            .set(source, ChunkAccessInput::new(source_block))
+            .unwrap();
+        access
+            .set(source + IVec3::X, ChunkAccessInput::new(lower_block))
+            .unwrap();
+        drop(access);
+
+        let read_access = chunk.read_access();
+        let neighbors =
+            NeighborsBuilder::new(BlockVoxel::new_full(BlockVariantRegistry::VOID)).build();
+        let surfaces = FluidSurfaces::new(&read_access, &neighbors, &varreg);
+
+        let quad = surfaces.top_quad(source).unwrap();
+
+        // the corners shared with the lower fluid are halfway between the two fluid heights
+        assert_eq!(1.0, quad.heights[corner_index(false, false)]);
+        assert_eq!(0.75, quad.heights[corner_index(true, false)]);
+        assert_eq!(1.0, quad.heights[corner_index(false, true)]);
+        assert_eq!(0.75, quad.heights[corner_index(true, true)]);
+
+        let positions = quad.positions();
+        assert_eq!(vec3(4.0, 5.0, 4.0), positions[corner_index(false, false)]);
+        assert_eq!(vec3(5.0, 4.75, 5.0), positions[corner_index(true, true)]);
+
+        // the lower fluid slopes up towards the source
+        let lower = surfaces.top_quad(source + IVec3::X).unwrap();
+        assert_eq!(0.75, lower.heights[corner_index(false, false)]);
+        assert_eq!(0.5, lower.heights[corner_index(true, false)]);
+
+        assert_eq!(2, surfaces.build().len());
+    }
+
+    #[test]
+    fn covered_fluid_has_no_surface() {
+        let texreg = TextureRegistry::new_mock();
+        let varreg = BlockVariantRegistry::new_mock(&texreg);
+
+        let chunk = MockChunk::new(BlockVoxel::new_full(BlockVariantRegistry::VOID));
+        let mut access = chunk.access();
+
+        let water = || ChunkAccessInput::new(BlockVoxel::new_full(BlockVariantRegistry::WATER));
+        let full = ChunkAccessInput::new(BlockVoxel::new_full(BlockVariantRegistry::FULL));
+
+        // a column of 2 fluids, and a fluid under an opaque block
+        access.set(ivec3(2, 2, 2), water()).unwrap();
+        access.set(ivec3(2, 3, 2), water()).unwrap();
+        access.set(ivec3(8, 2, 8), water()).unwrap();
+        access.set(ivec3(8, 3, 8), full).unwrap();
+        drop(access);
+
+        let read_access = chunk.read_access();
+        let neighbors =
+            NeighborsBuilder::new(BlockVoxel::new_full(BlockVariantRegistry::VOID)).build();
+        let surfaces = FluidSurfaces::new(&read_access, &neighbors, &varreg);
+
+        let quads = surfaces.build();
+        assert_eq!(1, quads.len());
+        assert_eq!(ivec3(2, 3, 2), quads[0].pos);
+    }
+
+    #[test]
+    fn gpu_quad_lowers_corners() {
+        let texreg = TextureRegistry::new_mock();
+        let varreg = BlockVariantRegistry::new_mock(&texreg);
+        let light = ChunkLight::new();
+
+        let quad = FluidQuad {
+            pos: ivec3(3, 5, 7),
+            block: FullBlock::new(BlockVariantRegistry::WATER),
+            heights: [1.0, 0.75, 1.0, 0.5],
+        };
+
+        let gpu_quad = quad.gpu_quad(&varreg, &light, LightingMode::Flat).unwrap();
+
+        assert_eq!(Face::Top, gpu_quad.bitfields.get_face());
+        assert_eq!(vec2(3.0, 7.0), gpu_quad.min);
+        assert_eq!(vec2(4.0, 8.0), gpu_quad.max);
+        // the top of the voxel, in microblocks
+        assert_eq!(6 * SubdividedBlock::SUBDIVISIONS, gpu_quad.magnitude);
+
+        for (corner, height) in quad.heights.into_iter().enumerate() {
+            assert_eq!(1.0 - height, gpu_quad.bitfields.get_corner_depth(corner));
+        }
+    }
+}
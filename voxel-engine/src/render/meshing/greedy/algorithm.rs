@@ -3,17 +3,23 @@ use bevy::math::ivec2;
 use bevy::math::IVec2;
 use bevy::math::Vec2;
 
-use itertools::Itertools;
-
 use crate::data::registries::block::BlockVariantRegistry;
 
 use crate::data::registries::Registry;
+use crate::data::registries::RegistryRef;
 use crate::data::tile::Face;
 
 use crate::render::meshing::controller::ChunkMeshData;
 use crate::render::meshing::error::MesherResult;
+use crate::render::meshing::fluid::FluidSurfaces;
+use crate::render::meshing::lighting::quad_corner_light;
+use crate::render::meshing::lighting::LightingMode;
+use crate::render::meshing::ChunkModels;
 use crate::render::meshing::Context;
+use crate::render::meshing::MeshSidedness;
+use crate::render::meshing::Mesher;
 
+use crate::render::quad::data::DataQuad;
 use crate::render::quad::isometric::IsometrizedQuad;
 use crate::render::quad::isometric::PositionedQuad;
 
@@ -21,6 +27,8 @@ use crate::render::quad::GpuQuad;
 use crate::render::quad::GpuQuadBitfields;
 
 use crate::topo::block::SubdividedBlock;
+use crate::topo::light::ChunkLight;
+use crate::topo::neighbors::Neighbors;
 use crate::topo::world::CaoBlock;
 use crate::topo::world::Chunk;
 use crate::topo::world::Crra;
@@ -35,7 +43,10 @@ fn widen_quad<'reg, 'chunk>(
     quad: &mut PositionedQuad,
     cqs: &ChunkQuadSlice<'reg, 'chunk>,
     mask: &ChunkSliceMask,
+    merge_borders: bool,
 ) -> CqsResult<()> {
+    let light = cqs.corner_light_mb(fpos);
+
     let mut widen_by = 0;
     for dx in 1..(Chunk::SUBDIVIDED_CHUNK_SIZE - fpos.x) {
         let candidate_pos = fpos + ivec2(dx, 0);
@@ -44,19 +55,39 @@ fn widen_quad<'reg, 'chunk>(
             break;
         }
 
+        if cqs.corner_light_mb(candidate_pos) != light {
+            break;
+        }
+
         match cqs.get_quad_mb(candidate_pos)? {
             Some(merge_candidate) if merge_candidate == quad.dataquad => widen_by = dx,
             _ => break,
         }
 
         let candidate_quad = cqs.get_quad_mb(candidate_pos)?;
-        if matches!(candidate_quad, None)
+        if candidate_quad.is_none()
             || matches!(candidate_quad, Some(q) if q.texture != quad.dataquad.texture)
         {
             break;
         }
     }
 
+    // if the quad reaches the chunk border we can continue the run into the first column of blocks
+    // in the neighboring chunk, which will skip these quads when it's meshed
+    if merge_borders && fpos.x + widen_by == Chunk::SUBDIVIDED_CHUNK_SIZE - 1 {
+        for x in Chunk::SUBDIVIDED_CHUNK_SIZE..BORDER_RUN_END {
+            let candidate_pos = ivec2(x, fpos.y);
+            if cqs.corner_light_mb(candidate_pos) != light {
+                break;
+            }
+
+            match cqs.auto_neighboring_get_quad_mb(candidate_pos)? {
+                Some(merge_candidate) if merge_candidate == quad.dataquad => widen_by = x - fpos.x,
+                _ => break,
+            }
+        }
+    }
+
     quad.widen(widen_by).unwrap();
     Ok(())
 }
@@ -66,7 +97,10 @@ fn heighten_quad<'reg, 'chunk>(
     quad: &mut PositionedQuad,
     cqs: &ChunkQuadSlice<'reg, 'chunk>,
     mask: &ChunkSliceMask,
+    merge_borders: bool,
 ) -> CqsResult<()> {
+    let light = cqs.corner_light_mb(fpos);
+
     let mut heighten_by = 0;
     'heighten: for dy in 1..(Chunk::SUBDIVIDED_CHUNK_SIZE - fpos.y) {
         // sweep the width of the quad to test if all quads at this Y are the same
@@ -75,18 +109,51 @@ fn heighten_quad<'reg, 'chunk>(
         for hx in (quad.min().x)..=(quad.max().x) {
             let candidate_pos = ivec2(hx, dy + fpos.y);
 
+            if cqs.corner_light_mb(candidate_pos) != light {
+                break 'heighten;
+            }
+
+            if hx >= Chunk::SUBDIVIDED_CHUNK_SIZE {
+                // this part of the quad is in the neighboring chunk
+                let candidate_quad = cqs.auto_neighboring_get_quad_mb(candidate_pos)?;
+                if candidate_quad.map(|q| q.texture) != Some(quad.dataquad.texture) {
+                    break 'heighten;
+                }
+
+                continue;
+            }
+
             if mask.is_masked_mb(candidate_pos).unwrap() {
                 break 'heighten;
             }
 
+            if merge_borders && cqs.continues_neighbor_run(candidate_pos)? {
+                break 'heighten;
+            }
+
             let candidate_quad = cqs.get_quad_mb(candidate_pos)?;
-            if matches!(candidate_quad, None)
+            if candidate_quad.is_none()
                 || matches!(candidate_quad, Some(q) if q.texture != quad.dataquad.texture)
             {
                 break 'heighten;
             }
         }
 
+        // a quad reaching into the neighboring chunk must cover the entire run at this Y, otherwise
+        // the rest of the run would be skipped by both chunks
+        if merge_borders
+            && quad.max().x >= Chunk::SUBDIVIDED_CHUNK_SIZE - 1
+            && quad.max().x + 1 < BORDER_RUN_END
+        {
+            let next = ivec2(quad.max().x + 1, dy + fpos.y);
+            let next_quad = cqs.auto_neighboring_get_quad_mb(next)?;
+            if next_quad.map(|q| q.texture) == Some(quad.dataquad.texture)
+                && cqs.corner_light_mb(next) == light
+            {
+                break 'heighten;
+            }
+        }
+
         // if we reach this line, the sweep loop was successful and all quads at this Y
         // equaled the current quad, so we can heighten by at least this amount
         heighten_by = dy;
@@ -96,15 +163,207 @@ fn heighten_quad<'reg, 'chunk>(
     Ok(())
 }
 
+/// Like [`widen_quad`] but for quads that have already been heightened, so every row of the quad
+/// has to match for the quad to be widened.
+fn widen_tall_quad<'reg, 'chunk>(
+    fpos: IVec2,
+    quad: &mut PositionedQuad,
+    cqs: &ChunkQuadSlice<'reg, 'chunk>,
+    mask: &ChunkSliceMask,
+) -> CqsResult<()> {
+    let light = cqs.corner_light_mb(fpos);
+
+    let mut widen_by = 0;
+    'widen: for dx in 1..(Chunk::SUBDIVIDED_CHUNK_SIZE - fpos.x) {
+        for hy in (quad.min().y)..=(quad.max().y) {
+            let candidate_pos = ivec2(dx + fpos.x, hy);
+
+            if mask.is_masked_mb(candidate_pos).unwrap() {
+                break 'widen;
+            }
+
+            if cqs.corner_light_mb(candidate_pos) != light {
+                break 'widen;
+            }
+
+            let candidate_quad = cqs.get_quad_mb(candidate_pos)?;
+            if candidate_quad.is_none()
+                || matches!(candidate_quad, Some(q) if q.texture != quad.dataquad.texture)
+            {
+                break 'widen;
+            }
+        }
+
+        widen_by = dx;
+    }
+
+    quad.widen(widen_by).unwrap();
+    Ok(())
+}
+
+/// The order in which the greedy mesher extends quads. Depending on the textures used, quads
+/// that are stretched in one direction may look better than quads stretched in the other.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Default)]
+pub enum MergeAxisPolicy {
+    /// Extend quads along the X axis (in facespace) first, then along the Y axis.
+    #[default]
+    PreferWidth,
+    /// Extend quads along the Y axis (in facespace) first, then along the X axis.
+    PreferHeight,
+    /// Try both orders and keep the quad that covers the most area, which usually leaves fewer
+    /// quads to be meshed afterwards. This is the slowest policy.
+    MinimizeQuads,
+}
+
+/// Which diagonal the two triangles of a quad are split along. Both diagonals of a quad are equally long,
+/// so this doesn't change the shape of the triangles, only which way the diagonal runs across the quad.
+/// Some (mostly tile-based) GPUs rasterize long quads faster with the diagonal running a certain way.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Default)]
+pub enum QuadTriangulation {
+    /// Always split quads along the diagonal between their min and max corners.
+    #[default]
+    Fixed,
+    /// Split wide quads along the diagonal between their min and max corners, and tall quads along
+    /// the other diagonal. Square quads are split like wide quads.
+    ByShape,
+}
+
+impl QuadTriangulation {
+    /// The vertex indices of a quad split along the diagonal between vertex 1 and 2 (the max and min corners),
+    /// see [`PositionedQuad::vertex_pos`] for the layout of the vertices.
+    pub const MIN_MAX_DIAGONAL: [u32; 6] = [0, 1, 2, 2, 1, 3];
+    /// The vertex indices of a quad split along the diagonal between vertex 0 and 3. The triangles have the
+    /// same winding as the ones in [`QuadTriangulation::MIN_MAX_DIAGONAL`].
+    pub const OTHER_DIAGONAL: [u32; 6] = [0, 1, 3, 0, 3, 2];
+
+    /// The vertex indices of the two triangles covering the given quad.
+    pub fn indices(self, quad: &PositionedQuad) -> [u32; 6] {
+        match self {
+            Self::ByShape if quad.height() > quad.width() => Self::OTHER_DIAGONAL,
+            _ => Self::MIN_MAX_DIAGONAL,
+        }
+    }
+}
+
+/// The (exclusive) end of runs that continue into the neighboring chunk when merging across chunk borders.
+/// Runs can extend over the first column of blocks in the neighboring chunk.
+const BORDER_RUN_END: i32 = Chunk::SUBDIVIDED_CHUNK_SIZE + SubdividedBlock::SUBDIVISIONS;
+
 #[derive(Clone)]
 pub struct GreedyMesher {
     quad_buffer_scratch: Vec<IsometrizedQuad>,
+    lighting: LightingMode,
+    merge_policy: MergeAxisPolicy,
+    merge_borders: bool,
+    sidedness: MeshSidedness,
+    cubes_only: bool,
+    triangulation: QuadTriangulation,
+}
+
+impl Default for GreedyMesher {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl GreedyMesher {
     pub fn new() -> Self {
         Self {
             quad_buffer_scratch: Vec::with_capacity(1024),
+            lighting: LightingMode::default(),
+            merge_policy: MergeAxisPolicy::default(),
+            merge_borders: false,
+            sidedness: MeshSidedness::default(),
+            cubes_only: false,
+            triangulation: QuadTriangulation::default(),
+        }
+    }
+
+    pub fn with_lighting(mut self, lighting: LightingMode) -> Self {
+        self.lighting = lighting;
+        self
+    }
+
+    /// Merge quads on the border of the chunk with quads in the first column of blocks of the neighboring
+    /// chunk (on the positive X side in facespace). This prevents tiny quads being emitted on chunk edges,
+    /// at the cost of quads that reach outside of the chunk. Chunks meshed in this mode must all be meshed
+    /// in this mode, or there will be holes and overlapping quads on chunk borders.
+    pub fn with_border_merging(mut self, merge_borders: bool) -> Self {
+        self.merge_borders = merge_borders;
+        self
+    }
+
+    pub fn with_merge_policy(mut self, merge_policy: MergeAxisPolicy) -> Self {
+        self.merge_policy = merge_policy;
+        self
+    }
+
+    pub fn with_triangulation(mut self, triangulation: QuadTriangulation) -> Self {
+        self.triangulation = triangulation;
+        self
+    }
+
+    pub fn with_sidedness(mut self, sidedness: MeshSidedness) -> Self {
+        self.sidedness = sidedness;
+        self
+    }
+
+    /// Only support chunks without subdivided blocks. Faces of full blocks are always on the edges of
+    /// blocks, so this mesher only walks the slices on block edges, which is about 4 times faster.
+    pub fn with_cubes_only(mut self, cubes_only: bool) -> Self {
+        self.cubes_only = cubes_only;
+        self
+    }
+
+    /// Grow a quad at `fpos` as much as possible, in the order given by the merge policy.
+    fn grow_quad(
+        &self,
+        fpos: IVec2,
+        dataquad: DataQuad,
+        cqs: &ChunkQuadSlice<'_, '_>,
+        mask: &ChunkSliceMask,
+    ) -> CqsResult<PositionedQuad> {
+        let width_first = || -> CqsResult<PositionedQuad> {
+            let mut quad = PositionedQuad::new(fpos, dataquad);
+
+            // First we try to extend the quad perpendicular to the direction we are iterating...
+            widen_quad(fpos, &mut quad, cqs, mask, self.merge_borders)?;
+            // Then we extend it in the same direction we are iterating.
+            // This supposedly leads to a higher quality mesh? I'm not sure where I read it but
+            // it doesn't hurt to do it this way so why not.
+            heighten_quad(fpos, &mut quad, cqs, mask, self.merge_borders)?;
+
+            Ok(quad)
+        };
+
+        let height_first = || -> CqsResult<PositionedQuad> {
+            let mut quad = PositionedQuad::new(fpos, dataquad);
+
+            heighten_quad(fpos, &mut quad, cqs, mask, self.merge_borders)?;
+            widen_tall_quad(fpos, &mut quad, cqs, mask)?;
+
+            Ok(quad)
+        };
+
+        // quads that are merged across chunk borders have to be widened first, otherwise they
+        // can't cover the runs they take over from the neighboring chunk
+        if self.merge_borders {
+            return width_first();
+        }
+
+        match self.merge_policy {
+            MergeAxisPolicy::PreferWidth => width_first(),
+            MergeAxisPolicy::PreferHeight => height_first(),
+            MergeAxisPolicy::MinimizeQuads => {
+                let wide = width_first()?;
+                let tall = height_first()?;
+
+                if tall.width() * tall.height() > wide.width() * wide.height() {
+                    Ok(tall)
+                } else {
+                    Ok(wide)
+                }
+            }
         }
     }
 
@@ -118,7 +377,9 @@ impl GreedyMesher {
                 let block = cqs.get(cs_pos)?.block;
 
                 if let CaoBlock::Full(block) = block {
-                    if cqs.registry.get_by_id(block.id).model.is_none() {
+                    if cqs.registry.is_air(block.id)
+                        || cqs.registry.get_by_id(block.id).model.is_none()
+                    {
                         continue;
                     }
                 }
@@ -126,12 +387,13 @@ impl GreedyMesher {
                 if cqs.mag_at_block_edge() {
                     let above = cqs.get_above(cs_pos)?.block;
                     if let CaoBlock::Full(above) = above {
-                        if cqs
-                            .registry
-                            .get_by_id(above.id)
-                            .options
-                            .transparency
-                            .is_opaque()
+                        if !cqs.registry.is_air(above.id)
+                            && cqs
+                                .registry
+                                .get_by_id(above.id)
+                                .options
+                                .transparency
+                                .is_opaque()
                         {
                             continue;
                         }
@@ -154,24 +416,22 @@ impl GreedyMesher {
                             continue;
                         };
 
-                        let mut current = PositionedQuad::new(fpos, dataquad);
-                        debug_assert!(current.height() > 0);
-                        debug_assert!(current.width() > 0);
-
-                        // First we try to extend the quad perpendicular to the direction we are iterating...
-                        widen_quad(fpos, &mut current, cqs, &mask)?;
-                        debug_assert!(current.width() > 0);
+                        if self.merge_borders && cqs.continues_neighbor_run(fpos)? {
+                            continue;
+                        }
 
-                        // Then we extend it in the same direction we are iterating.
-                        // This supposedly leads to a higher quality mesh? I'm not sure where I read it but
-                        // it doesn't hurt to do it this way so why not.
-                        heighten_quad(fpos, &mut current, cqs, &mask)?;
+                        let current = self.grow_quad(fpos, dataquad, cqs, &mask)?;
                         debug_assert!(current.height() > 0);
+                        debug_assert!(current.width() > 0);
 
                         // mask_region will return false if any of the positions provided are outside of the
                         // chunk bounds, so we do a little debug mode sanity check here to make sure thats
                         // not the case, and catch the error early
-                        let result = mask.mask_mb_region_inclusive(current.min(), current.max());
+                        // quads merged across the chunk border only need to be masked within this chunk
+                        let max = current
+                            .max()
+                            .min(IVec2::splat(Chunk::SUBDIVIDED_CHUNK_SIZE - 1));
+                        let result = mask.mask_mb_region_inclusive(current.min(), max);
                         debug_assert!(result);
 
                         let isoquad = cqs.isometrize(current);
@@ -185,51 +445,118 @@ impl GreedyMesher {
         Ok(())
     }
 
-    fn drain_quads(&mut self) -> (Vec<u32>, Vec<GpuQuad>) {
-        const VERTEX_INDICES: [u32; 6] = [0, 1, 2, 2, 1, 3];
+    fn calculate_chunk_quads<'chunk>(
+        &mut self,
+        access: &Crra<'chunk>,
+        neighbors: &Neighbors<'chunk>,
+        varreg: &RegistryRef<'_, BlockVariantRegistry>,
+        light: &ChunkLight,
+    ) -> CqsResult<()> {
+        let uniform = access.is_uniform();
+
+        if let Some(block) = uniform {
+            // nothing to mesh in chunks filled with air or blocks without models
+            if varreg.is_air(block.id) || varreg.get_by_id(block.id).model.is_none() {
+                return Ok(());
+            }
+        }
+
+        // every face inside a chunk that's filled with the same opaque block is hidden, so only the
+        // outer faces of the chunk need to be meshed
+        let solid = uniform
+            .is_some_and(|block| varreg.get_by_id(block.id).options.transparency.is_opaque());
+
+        let mut cqs = ChunkQuadSlice::new(Face::North, 0, access, neighbors, varreg)
+            .unwrap()
+            .with_light(light, self.lighting);
+
+        for face in Face::FACES {
+            for layer in 0..Chunk::SUBDIVIDED_CHUNK_SIZE {
+                cqs.reposition(face, layer).unwrap();
 
+                if solid && !cqs.faces_chunk_border() {
+                    continue;
+                }
+
+                if self.cubes_only && !cqs.mag_at_block_edge() {
+                    continue;
+                }
+
+                self.calculate_slice_quads(&cqs)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn drain_quads(&mut self, light: &ChunkLight) -> (Vec<u32>, Vec<GpuQuad>) {
         let quads = self.quad_buffer_scratch.len();
+        let lighting = self.lighting;
+        let triangulation = self.triangulation;
         let capacity_before = self.quad_buffer_scratch.capacity();
 
         let mut indices = Vec::<u32>::with_capacity(quads * 6);
-        let mut current_idx: u32 = 0;
+        let mut gpu_quads = Vec::<GpuQuad>::with_capacity(quads);
 
-        let quads = self
-            .quad_buffer_scratch
-            .drain(..)
-            .map(|quad| {
-                indices.extend_from_slice(&VERTEX_INDICES.map(|idx| idx + current_idx));
-                current_idx += 4;
+        for quad in self.quad_buffer_scratch.drain(..) {
+            let bitfields = GpuQuadBitfields::new()
+                .with_rotation(quad.quad.dataquad.texture.rotation)
+                .with_face(quad.isometry.face);
 
-                let bitfields = GpuQuadBitfields::new()
-                    .with_rotation(quad.quad.dataquad.texture.rotation)
-                    .with_face(quad.isometry.face);
+            let magnitude = if quad.isometry.face.axis_direction() > 0 {
+                quad.isometry.magnitude() + 1
+            } else {
+                quad.isometry.magnitude()
+            };
 
-                let magnitude = if quad.isometry.face.axis_direction() > 0 {
-                    quad.isometry.magnitude() + 1
-                } else {
-                    quad.isometry.magnitude()
-                };
+            let gpu_quad = GpuQuad {
+                // TODO: get rid of these magic numbers
+                min: quad.min_2d().as_vec2() * 0.25,
+                max: (quad.max_2d().as_vec2() + Vec2::ONE) * 0.25,
+                texture_id: quad.quad.dataquad.texture.id.as_u32(),
+                bitfields,
+                magnitude,
+                light: quad_corner_light(&quad, light, lighting),
+            };
 
-                GpuQuad {
-                    // TODO: get rid of these magic numbers
-                    min: quad.min_2d().as_vec2() * 0.25,
-                    max: (quad.max_2d().as_vec2() + Vec2::ONE) * 0.25,
-                    texture_id: quad.quad.dataquad.texture.id.as_u32(),
-                    bitfields,
-                    magnitude,
-                }
-            })
-            .collect_vec();
+            push_quad(
+                gpu_quad,
+                triangulation.indices(&quad.quad),
+                &mut indices,
+                &mut gpu_quads,
+            );
+        }
 
         if capacity_before != self.quad_buffer_scratch.capacity() {
             panic!("Failed sanity check of quad buffer scratch memory capacity");
         }
 
-        (indices, quads)
+        (indices, gpu_quads)
+    }
+}
+
+/// Push a quad and the indices of its triangles (relative to the first vertex of the quad) to the buffers
+/// of a mesh. Degenerate quads (see [`GpuQuad::is_degenerate`]) are skipped, they can only come from bugs
+/// in quad merging and would push invalid geometry into the mesh. Returns whether the quad was pushed.
+fn push_quad(
+    quad: GpuQuad,
+    triangles: [u32; 6],
+    indices: &mut Vec<u32>,
+    quads: &mut Vec<GpuQuad>,
+) -> bool {
+    if quad.is_degenerate() {
+        return false;
     }
 
-    pub fn build<'reg, 'chunk>(
+    let first_vertex = quads.len() as u32 * 4;
+    indices.extend_from_slice(&triangles.map(|idx| idx + first_vertex));
+    quads.push(quad);
+
+    true
+}
+
+impl Mesher for GreedyMesher {
+    fn build<'reg, 'chunk>(
         &mut self,
         access: Crra<'chunk>,
         cx: Context<'reg, 'chunk>,
@@ -239,24 +566,638 @@ impl GreedyMesher {
             .get_registry::<BlockVariantRegistry>()
             .unwrap();
 
-        let mut cqs = ChunkQuadSlice::new(Face::North, 0, &access, &cx.neighbors, &varreg).unwrap();
+        self.calculate_chunk_quads(&access, &cx.neighbors, &varreg, cx.light)?;
 
-        for face in Face::FACES {
-            for layer in 0..Chunk::SUBDIVIDED_CHUNK_SIZE {
-                cqs.reposition(face, layer).unwrap();
+        let (mut idx_buf, mut quad_buf) = self.drain_quads(cx.light);
 
-                self.calculate_slice_quads(&cqs)?;
+        // the tops of fluids are skipped by the greedy mesher, they're sloped so they get a quad per voxel
+        for fluid in FluidSurfaces::new(&access, &cx.neighbors, &varreg).build() {
+            if let Some(quad) = fluid.gpu_quad(&varreg, cx.light, self.lighting) {
+                // fluid quads are square, so they're always split along the same diagonal
+                push_quad(
+                    quad,
+                    QuadTriangulation::MIN_MAX_DIAGONAL,
+                    &mut idx_buf,
+                    &mut quad_buf,
+                );
             }
         }
 
-        let (idx_buf, quad_buf) = self.drain_quads();
-
         Ok(ChunkMeshData {
             index_buffer: idx_buf,
             quad_buffer: quad_buf,
         })
     }
+
+    /// Border merging reads the chunks diagonal to the slice being meshed, otherwise only the
+    /// face neighbors are read.
+    fn neighbor_radius(&self) -> u8 {
+        if self.merge_borders {
+            2
+        } else {
+            1
+        }
+    }
+
+    fn sidedness(&self) -> MeshSidedness {
+        self.sidedness
+    }
+
+    fn supports(&self, models: ChunkModels) -> bool {
+        !self.cubes_only || !models.contains(ChunkModels::SUBDIVIDED)
+    }
 }
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use bevy::{
+        math::{ivec3, uvec2, uvec3, vec2, IVec3},
+        render::render_resource::encase::StorageBuffer,
+    };
+    use itertools::Itertools;
+    use parking_lot::{RwLock, RwLockReadGuard};
+
+    use crate::{
+        data::{
+            registries::texture::TextureRegistry, texture::FaceTexture,
+            voxel::rotations::BlockModelRotation,
+        },
+        render::quad::anon::Quad,
+        testing_utils::MockChunk,
+        topo::{
+            access::WriteAccess,
+            block::{BlockVoxel, FullBlock, Microblock},
+            light::LightLevel,
+            neighbors::NeighborsBuilder,
+            world::ChunkAccessInput,
+        },
+    };
+
+    use super::*;
+
+    fn floor_chunk(width: i32) -> MockChunk {
+        let chunk = MockChunk::new(BlockVoxel::new_full(BlockVariantRegistry::VOID));
+        let mut access = chunk.access();
+
+        for x in 0..width {
+            for z in 0..Chunk::SIZE {
+                access
+                    .set(
+                        ivec3(x, 0, z),
+                        ChunkAccessInput::new(BlockVoxel::new_full(BlockVariantRegistry::FULL)),
+                    )
+                    .unwrap();
+            }
+        }
+
+        drop(access);
+        chunk
+    }
+
+    fn count_quads(
+        chunk: &MockChunk,
+        neighbor: &MockChunk,
+        neighbor_pos: IVec3,
+        merge_borders: bool,
+    ) -> usize {
+        let texreg = TextureRegistry::new_mock();
+        let varreg = RwLock::new(BlockVariantRegistry::new_mock(&texreg));
+        let guard = RwLockReadGuard::map(varreg.read(), |g| g);
+
+        let mut builder = NeighborsBuilder::new(BlockVoxel::new_full(BlockVariantRegistry::VOID));
+        builder
+            .set_neighbor(neighbor_pos, neighbor.read_access())
+            .unwrap();
+        let neighbors = builder.build();
+
+        let access = chunk.read_access();
+        let mut cqs = ChunkQuadSlice::new(Face::North, 0, &access, &neighbors, &guard).unwrap();
+        let mut mesher = GreedyMesher::new().with_border_merging(merge_borders);
+
+        for face in Face::FACES {
+            for layer in 0..Chunk::SUBDIVIDED_CHUNK_SIZE {
+                cqs.reposition(face, layer).unwrap();
+                mesher.calculate_slice_quads(&cqs).unwrap();
+            }
+        }
+
+        mesher.quad_buffer_scratch.len()
+    }
+
+    #[test]
+    fn border_merging_emits_fewer_quads() {
+        // a full floor next to a chunk with a floor that's only 1 block wide
+        let left = floor_chunk(Chunk::SIZE);
+        let right = floor_chunk(1);
+
+        let independent = count_quads(&left, &right, IVec3::X, false)
+            + count_quads(&right, &left, IVec3::NEG_X, false);
+        let merged = count_quads(&left, &right, IVec3::X, true)
+            + count_quads(&right, &left, IVec3::NEG_X, true);
+
+        assert!(merged < independent);
+        // only the face of the right chunk's floor that faces away from the left chunk isn't merged
+        // into the quads of the left chunk
+        assert_eq!(independent - 4, merged);
+    }
+
+    #[test]
+    fn designated_air_is_skipped() {
+        // lamps are opaque and have a model, so they'd normally be meshed and hide the faces next to them
+        let air = BlockVariantRegistry::LAMP;
+
+        let texreg = TextureRegistry::new_mock();
+        let varreg = RwLock::new(BlockVariantRegistry::new_mock(&texreg).with_air(air));
+        let guard = RwLockReadGuard::map(varreg.read(), |g| g);
+
+        // out of bounds is air too
+        let neighbors = NeighborsBuilder::new(BlockVoxel::new_full(air)).build();
+
+        let count = |chunk: &MockChunk| {
+            let access = chunk.read_access();
+            let mut cqs = ChunkQuadSlice::new(Face::North, 0, &access, &neighbors, &guard).unwrap();
+            let mut mesher = GreedyMesher::new();
+
+            for face in Face::FACES {
+                for layer in 0..Chunk::SUBDIVIDED_CHUNK_SIZE {
+                    cqs.reposition(face, layer).unwrap();
+                    mesher.calculate_slice_quads(&cqs).unwrap();
+                }
+            }
+
+            mesher.quad_buffer_scratch.len()
+        };
+
+        let chunk = MockChunk::new(BlockVoxel::new_full(air));
+        assert_eq!(0, count(&chunk));
+
+        // a solid block surrounded by air has all of its faces, including the ones on the chunk border
+        for pos in [ivec3(8, 8, 8), ivec3(0, 0, 0)] {
+            let chunk = MockChunk::new(BlockVoxel::new_full(air));
+            chunk
+                .access()
+                .set(
+                    pos,
+                    ChunkAccessInput::new(BlockVoxel::new_full(BlockVariantRegistry::FULL)),
+                )
+                .unwrap();
+
+            assert_eq!(6, count(&chunk), "solid block at {pos}");
+        }
+    }
+
+    #[test]
+    fn solid_uniform_chunk_fast_path() {
+        let texreg = TextureRegistry::new_mock();
+        let varreg = RwLock::new(BlockVariantRegistry::new_mock(&texreg));
+        let guard = RwLockReadGuard::map(varreg.read(), |g| g);
+
+        let neighbors =
+            NeighborsBuilder::new(BlockVoxel::new_full(BlockVariantRegistry::VOID)).build();
+
+        let chunk = MockChunk::new(BlockVoxel::new_full(BlockVariantRegistry::FULL));
+        let access = chunk.read_access();
+        assert!(access.is_uniform().is_some());
+
+        let mut mesher = GreedyMesher::new();
+        mesher
+            .calculate_chunk_quads(&access, &neighbors, &guard, &ChunkLight::default())
+            .unwrap();
+        let (_, fast) = mesher.drain_quads(&ChunkLight::default());
+
+        // one quad covering each side of the chunk
+        assert_eq!(6, fast.len());
+
+        // the general path walks every slice and ends up with the same quads
+        let mut cqs = ChunkQuadSlice::new(Face::North, 0, &access, &neighbors, &guard).unwrap();
+        for face in Face::FACES {
+            for layer in 0..Chunk::SUBDIVIDED_CHUNK_SIZE {
+                cqs.reposition(face, layer).unwrap();
+                mesher.calculate_slice_quads(&cqs).unwrap();
+            }
+        }
+        let (_, general) = mesher.drain_quads(&ChunkLight::default());
+
+        assert_eq!(general, fast);
+
+        // a uniform chunk of void has nothing to mesh
+        let chunk = MockChunk::new(BlockVoxel::new_full(BlockVariantRegistry::VOID));
+        mesher
+            .calculate_chunk_quads(
+                &chunk.read_access(),
+                &neighbors,
+                &guard,
+                &ChunkLight::default(),
+            )
+            .unwrap();
+        assert!(mesher.quad_buffer_scratch.is_empty());
+    }
+
+    #[test]
+    fn light_breaks_merging() {
+        let texreg = TextureRegistry::new_mock();
+        let varreg = RwLock::new(BlockVariantRegistry::new_mock(&texreg));
+        let guard = RwLockReadGuard::map(varreg.read(), |g| g);
+
+        let neighbors =
+            NeighborsBuilder::new(BlockVoxel::new_full(BlockVariantRegistry::VOID)).build();
+
+        let chunk = floor_chunk(Chunk::SIZE);
+        let access = chunk.read_access();
+
+        let top_quads = |light: &ChunkLight| {
+            let mut mesher = GreedyMesher::new();
+            mesher
+                .calculate_chunk_quads(&access, &neighbors, &guard, light)
+                .unwrap();
+
+            mesher
+                .quad_buffer_scratch
+                .iter()
+                .filter(|quad| quad.isometry.face == Face::Top)
+                .map(|quad| (quad.quad.min(), quad.quad.max()))
+                .collect_vec()
+        };
+
+        // the whole floor is one quad if it's evenly lit
+        assert_eq!(1, top_quads(&ChunkLight::default()).len());
+
+        // a light right above the middle of the floor gets its own quad
+        let mut light = ChunkLight::default();
+        light.set(ivec3(8, 1, 8), LightLevel::new(10, 0)).unwrap();
+
+        let lit = top_quads(&light);
+        assert!(lit.len() > 1);
+        assert!(lit.contains(&(IVec2::splat(8 * 4), IVec2::splat(8 * 4 + 3))));
+    }
+
+    #[test]
+    fn smooth_light_breaks_merging_at_corners() {
+        let texreg = TextureRegistry::new_mock();
+        let varreg = RwLock::new(BlockVariantRegistry::new_mock(&texreg));
+        let guard = RwLockReadGuard::map(varreg.read(), |g| g);
+
+        let neighbors =
+            NeighborsBuilder::new(BlockVoxel::new_full(BlockVariantRegistry::VOID)).build();
+
+        let chunk = floor_chunk(Chunk::SIZE);
+        let access = chunk.read_access();
+
+        // the half of the floor past x = 8 is lit, so the blocks at x = 8 are dark in front of their faces
+        // but their corners on the max X side are lit with smooth lighting
+        let mut light = ChunkLight::default();
+        for x in 9..Chunk::SIZE {
+            for z in 0..Chunk::SIZE {
+                light.set(ivec3(x, 1, z), LightLevel::new(12, 0)).unwrap();
+            }
+        }
+
+        let spans_x7_and_x8 = |mode: LightingMode| {
+            let mut mesher = GreedyMesher::new().with_lighting(mode);
+            mesher
+                .calculate_chunk_quads(&access, &neighbors, &guard, &light)
+                .unwrap();
+
+            mesher
+                .quad_buffer_scratch
+                .iter()
+                .filter(|quad| quad.isometry.face == Face::Top)
+                .any(|quad| quad.quad.min().x <= 7 * 4 && quad.quad.max().x >= 8 * 4 + 3)
+        };
+
+        assert!(spans_x7_and_x8(LightingMode::Flat));
+        assert!(!spans_x7_and_x8(LightingMode::Smooth));
+    }
+
+    #[test]
+    fn rotation_overrides_break_merging() {
+        let texreg = TextureRegistry::new_mock();
+        let varreg = RwLock::new(BlockVariantRegistry::new_mock(&texreg));
+        let guard = RwLockReadGuard::map(varreg.read(), |g| g);
+
+        let neighbors =
+            NeighborsBuilder::new(BlockVoxel::new_full(BlockVariantRegistry::VOID)).build();
+
+        let count = |rotation: Option<BlockModelRotation>| {
+            let chunk = MockChunk::new(BlockVoxel::new_full(BlockVariantRegistry::VOID));
+            let mut access = chunk.access();
+            for (pos, rotation) in [(ivec3(0, 0, 0), None), (ivec3(0, 0, 1), rotation)] {
+                let block = FullBlock {
+                    rotation,
+                    id: BlockVariantRegistry::FULL,
+                };
+                access
+                    .set(pos, ChunkAccessInput::new(BlockVoxel::Full(block)))
+                    .unwrap();
+            }
+            drop(access);
+
+            let access = chunk.read_access();
+            let mut cqs = ChunkQuadSlice::new(Face::North, 0, &access, &neighbors, &guard).unwrap();
+            let mut mesher = GreedyMesher::new();
+
+            for face in Face::FACES {
+                for layer in 0..Chunk::SUBDIVIDED_CHUNK_SIZE {
+                    cqs.reposition(face, layer).unwrap();
+                    mesher.calculate_slice_quads(&cqs).unwrap();
+                }
+            }
+
+            mesher.quad_buffer_scratch.len()
+        };
+
+        // the same block with the same orientation is merged into one quad on each side of the pair
+        assert_eq!(6, count(None));
+        assert_eq!(6, count(Some(BlockModelRotation::DEFAULT)));
+
+        // turning the second block rotates its top and bottom textures, so those faces can't be merged
+        let rotation = BlockModelRotation::new(Face::East, Face::Top).unwrap();
+        assert_eq!(8, count(Some(rotation)));
+    }
+
+    #[test]
+    fn cubes_only_mode() {
+        let texreg = TextureRegistry::new_mock();
+        let varreg = RwLock::new(BlockVariantRegistry::new_mock(&texreg));
+        let guard = RwLockReadGuard::map(varreg.read(), |g| g);
+
+        let neighbors =
+            NeighborsBuilder::new(BlockVoxel::new_full(BlockVariantRegistry::VOID)).build();
+
+        let chunk = floor_chunk(5);
+        chunk
+            .access()
+            .set(
+                ivec3(8, 4, 8),
+                ChunkAccessInput::new(BlockVoxel::new_full(BlockVariantRegistry::GLASS)),
+            )
+            .unwrap();
+        let access = chunk.read_access();
+
+        let quads = |mut mesher: GreedyMesher| {
+            mesher
+                .calculate_chunk_quads(&access, &neighbors, &guard, &ChunkLight::default())
+                .unwrap();
+            mesher.drain_quads(&ChunkLight::default()).1
+        };
+
+        let general = quads(GreedyMesher::new());
+        assert!(!general.is_empty());
+        assert_eq!(general, quads(GreedyMesher::new().with_cubes_only(true)));
+
+        assert!(GreedyMesher::new().supports(ChunkModels::all()));
+        assert!(GreedyMesher::new()
+            .with_cubes_only(true)
+            .supports(ChunkModels::CUBES));
+        assert!(!GreedyMesher::new()
+            .with_cubes_only(true)
+            .supports(ChunkModels::CUBES | ChunkModels::SUBDIVIDED));
+    }
+
+    /// A chunk with a bit of everything in it: several variants, rotated blocks and subdivided blocks.
+    /// The blocks are written in an order depending on `seed`, which changes the internal layout
+    /// of the chunk's storage but not the blocks in it.
+    fn messy_chunk(seed: u64) -> MockChunk {
+        let chunk = MockChunk::new(BlockVoxel::new_full(BlockVariantRegistry::VOID));
+        let mut access = chunk.access();
+
+        let variants = [
+            BlockVariantRegistry::FULL,
+            BlockVariantRegistry::SUBDIV,
+            BlockVariantRegistry::LAMP,
+            BlockVariantRegistry::GLASS,
+        ];
+        let rotation = BlockModelRotation::new(Face::East, Face::Top).unwrap();
+
+        let mut positions = (0..Chunk::USIZE.pow(3) as i32)
+            .map(|i| {
+                ivec3(
+                    i % Chunk::SIZE,
+                    (i / Chunk::SIZE) % Chunk::SIZE,
+                    i / Chunk::SIZE.pow(2),
+                )
+            })
+            .collect_vec();
+        // simple LCG shuffle, we only need the order to differ between seeds
+        let mut state = seed;
+        for i in (1..positions.len()).rev() {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            positions.swap(i, (state >> 33) as usize % (i + 1));
+        }
+
+        for pos in positions {
+            let hash = (pos.x * 7 + pos.y * 13 + pos.z * 31) as usize;
+            let block = match hash % 9 {
+                0..=3 => continue,
+                4 => BlockVoxel::Full(FullBlock::new(variants[hash % 4]).with_rotation(rotation)),
+                5 => {
+                    let mut subdiv =
+                        SubdividedBlock::new(Microblock::new(BlockVariantRegistry::SUBDIV));
+                    subdiv
+                        .set(uvec3(1, 2, 3), Microblock::new(BlockVariantRegistry::VOID))
+                        .unwrap();
+                    subdiv
+                        .set(uvec3(0, 0, 0), Microblock::new(BlockVariantRegistry::GLASS))
+                        .unwrap();
+                    BlockVoxel::Subdivided(subdiv)
+                }
+                _ => BlockVoxel::new_full(variants[hash % 4]),
+            };
+
+            access.set(pos, ChunkAccessInput::new(block)).unwrap();
+        }
+
+        drop(access);
+        chunk
+    }
+
+    #[test]
+    fn meshing_is_deterministic() {
+        let mesh = |seed: u64| -> Vec<u8> {
+            // fresh registries and a fresh mesher every time, so no hashers are shared between runs
+            let texreg = TextureRegistry::new_mock();
+            let varreg = RwLock::new(BlockVariantRegistry::new_mock(&texreg));
+            let guard = RwLockReadGuard::map(varreg.read(), |g| g);
+
+            let neighbors =
+                NeighborsBuilder::new(BlockVoxel::new_full(BlockVariantRegistry::VOID)).build();
+
+            let chunk = messy_chunk(seed);
+            let mut mesher = GreedyMesher::new();
+            mesher
+                .calculate_chunk_quads(
+                    &chunk.read_access(),
+                    &neighbors,
+                    &guard,
+                    &ChunkLight::default(),
+                )
+                .unwrap();
+            let (_, quads) = mesher.drain_quads(&ChunkLight::default());
+
+            let mut bytes = StorageBuffer::new(Vec::<u8>::new());
+            bytes.write(&quads).unwrap();
+            bytes.into_inner()
+        };
+
+        let expected = mesh(0);
+        assert!(!expected.is_empty());
+
+        for seed in 1..3 {
+            assert!(expected == mesh(seed), "mesh differs for seed {seed}");
+        }
+    }
+
+    fn top_quads(chunk: &MockChunk, policy: MergeAxisPolicy) -> Vec<(IVec2, IVec2)> {
+        let texreg = TextureRegistry::new_mock();
+        let varreg = RwLock::new(BlockVariantRegistry::new_mock(&texreg));
+        let guard = RwLockReadGuard::map(varreg.read(), |g| g);
+
+        let neighbors =
+            NeighborsBuilder::new(BlockVoxel::new_full(BlockVariantRegistry::VOID)).build();
+
+        let access = chunk.read_access();
+        let cqs = ChunkQuadSlice::new(Face::Top, 3, &access, &neighbors, &guard).unwrap();
+        let mut mesher = GreedyMesher::new().with_merge_policy(policy);
+
+        mesher.calculate_slice_quads(&cqs).unwrap();
+
+        mesher
+            .quad_buffer_scratch
+            .iter()
+            .map(|quad| (quad.min_2d(), quad.max_2d()))
+            .collect()
+    }
+
+    #[test]
+    fn merge_policy_l_shape() {
+        // an L made of a column that's 4 blocks long along Z, and a single block next to its end
+        let chunk = MockChunk::new(BlockVoxel::new_full(BlockVariantRegistry::VOID));
+        let mut access = chunk.access();
+        for pos in [
+            ivec3(0, 0, 0),
+            ivec3(0, 0, 1),
+            ivec3(0, 0, 2),
+            ivec3(0, 0, 3),
+            ivec3(1, 0, 0),
+        ] {
+            access
+                .set(
+                    pos,
+                    ChunkAccessInput::new(BlockVoxel::new_full(BlockVariantRegistry::FULL)),
+                )
+                .unwrap();
+        }
+        drop(access);
+
+        let column = (ivec2(0, 0), ivec2(3, 15));
+        let foot = (ivec2(4, 0), ivec2(7, 3));
+
+        // the L can't be covered by less than 2 quads, and the minimizing policy picks the
+        // larger of the 2 ways to start covering it
+        assert_eq!(
+            vec![column, foot],
+            top_quads(&chunk, MergeAxisPolicy::MinimizeQuads)
+        );
+        assert_eq!(
+            vec![column, foot],
+            top_quads(&chunk, MergeAxisPolicy::PreferHeight)
+        );
+        assert_eq!(
+            vec![(ivec2(0, 0), ivec2(7, 3)), (ivec2(0, 4), ivec2(3, 15))],
+            top_quads(&chunk, MergeAxisPolicy::PreferWidth)
+        );
+    }
+
+    #[test]
+    fn triangulation_by_shape() {
+        let quad = |width: i32, height: i32| {
+            let mut quad = PositionedQuad::new(
+                ivec2(2, 3),
+                DataQuad::new(Quad::ONE, FaceTexture::new(TextureRegistry::TEX1)),
+            );
+            quad.widen(width - 1).unwrap();
+            quad.heighten(height - 1).unwrap();
+            quad
+        };
+
+        let wide = quad(8, 2);
+        let tall = quad(2, 8);
+
+        assert_eq!(
+            QuadTriangulation::MIN_MAX_DIAGONAL,
+            QuadTriangulation::ByShape.indices(&wide)
+        );
+        assert_eq!(
+            QuadTriangulation::OTHER_DIAGONAL,
+            QuadTriangulation::ByShape.indices(&tall)
+        );
+        assert_eq!(
+            QuadTriangulation::MIN_MAX_DIAGONAL,
+            QuadTriangulation::Fixed.indices(&tall)
+        );
+
+        for quad in [wide, tall] {
+            // the max corner of a quad is inclusive, so the corners of the covered area are one further out
+            let (min, max) = (quad.min(), quad.max() + IVec2::ONE);
+            let corners = [ivec2(min.x, max.y), max, min, ivec2(max.x, min.y)];
+            // twice the signed area of a triangle, the sign is the winding of the triangle
+            let area = |triangle: &[u32]| {
+                let [a, b, c] = [0, 1, 2].map(|i| corners[triangle[i] as usize]);
+                (b - a).perp_dot(c - a)
+            };
+
+            let indices = QuadTriangulation::ByShape.indices(&quad);
+            let (first, second) = indices.split_at(3);
+
+            // the triangles use every vertex and share exactly one edge, the diagonal
+            assert!((0..4).all(|vertex| indices.contains(&vertex)));
+            assert_eq!(2, first.iter().filter(|i| second.contains(i)).count());
+
+            // and they don't overlap, cover the whole quad, and wind like the default triangulation
+            let winding = area(&QuadTriangulation::MIN_MAX_DIAGONAL[..3]).signum();
+            assert_eq!(winding, area(first).signum());
+            assert_eq!(winding, area(second).signum());
+            assert_eq!(
+                2 * quad.width() * quad.height(),
+                area(first).abs() + area(second).abs()
+            );
+        }
+    }
+
+    #[test]
+    fn degenerate_quads_are_skipped() {
+        assert_eq!(15, Quad::new(uvec2(3, 5)).unwrap().area());
+
+        let quad = |min: Vec2, max: Vec2| GpuQuad {
+            texture_id: 0,
+            bitfields: GpuQuadBitfields::new().with_face(Face::Top),
+            min,
+            max,
+            magnitude: 0,
+            light: 0,
+        };
+
+        let valid = quad(vec2(0.25, 0.5), vec2(1.0, 2.0));
+        assert_eq!(0.75 * 1.5, valid.area());
+        assert!(!valid.is_degenerate());
+
+        let flat = quad(vec2(0.0, 1.0), vec2(2.0, 1.0));
+        assert_eq!(0.0, flat.area());
+        assert!(flat.is_degenerate());
+        assert!(quad(vec2(0.0, 0.0), vec2(f32::NAN, 1.0)).is_degenerate());
+
+        let mut indices = Vec::new();
+        let mut quads = Vec::new();
+        let triangles = QuadTriangulation::MIN_MAX_DIAGONAL;
+
+        assert!(push_quad(valid, triangles, &mut indices, &mut quads));
+        assert!(!push_quad(flat, triangles, &mut indices, &mut quads));
+        assert!(push_quad(valid, triangles, &mut indices, &mut quads));
+
+        // the skipped quad doesn't leave a gap in the vertex indices
+        assert_eq!(2, quads.len());
+        assert_eq!(vec![0, 1, 2, 2, 1, 3, 4, 5, 6, 6, 5, 7], indices);
+    }
+}
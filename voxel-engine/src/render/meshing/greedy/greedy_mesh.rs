@@ -25,6 +25,7 @@ impl ChunkSliceMask {
         Self::contains(pos.div_euclid(IVec2::splat(SubdividedBlock::SUBDIVISIONS)))
     }
 
+    #[allow(dead_code)]
     pub fn mask_region_inclusive(&mut self, pos1: IVec2, pos2: IVec2) -> bool {
         if !Self::contains(pos1) || !Self::contains(pos2) {
             return false;
@@ -24,10 +24,10 @@ pub struct GreedyMeshMaterial {
 
 impl GreedyMeshMaterial {
     pub const TEXTURE_MESH_ATTR: MeshVertexAttribute =
-        MeshVertexAttribute::new("Greedy_Texture", 4099_1, VertexFormat::Uint32);
+        MeshVertexAttribute::new("Greedy_Texture", 40_991, VertexFormat::Uint32);
 
     pub const MISC_DATA_ATTR: MeshVertexAttribute =
-        MeshVertexAttribute::new("Misc_Data", 4099_2, VertexFormat::Uint32);
+        MeshVertexAttribute::new("Misc_Data", 40_992, VertexFormat::Uint32);
 }
 
 macro_rules! uint_shader_def {
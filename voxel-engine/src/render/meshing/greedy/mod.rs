@@ -1,21 +1,25 @@
-use bevy::math::{IVec2, IVec3};
+use bevy::math::{ivec2, IVec2, IVec3};
 
 use crate::{
     data::{
         registries::{block::BlockVariantRegistry, Registry, RegistryRef},
         texture::FaceTexture,
-        tile::Face,
+        tile::{Face, Transparency},
         voxel::rotations::BlockModelRotation,
     },
-    render::quad::{
-        anon::Quad,
-        data::DataQuad,
-        isometric::{IsometrizedQuad, PositionedQuad, QuadIsometry},
+    render::{
+        meshing::lighting::{corner_light, LightingMode},
+        quad::{
+            anon::Quad,
+            data::DataQuad,
+            isometric::{IsometrizedQuad, PositionedQuad, QuadIsometry},
+        },
     },
     topo::{
         access::ReadAccess,
         block::{Microblock, SubdividedBlock},
         ivec_project_to_2d, ivec_project_to_3d,
+        light::ChunkLight,
         neighbors::{self, Neighbors},
         storage::error::OutOfBounds,
         world::{CaoBlock, Chunk, ChunkAccessOutput, Crra},
@@ -41,6 +45,7 @@ pub struct ChunkQuadSlice<'a, 'chunk> {
     access: &'a Crra<'chunk>,
     neighbors: &'a Neighbors<'chunk>,
     registry: &'a RegistryRef<'a, BlockVariantRegistry>,
+    light: Option<(&'a ChunkLight, LightingMode)>,
 }
 
 pub const MAX: IVec2 = IVec2::splat(Chunk::SIZE);
@@ -56,7 +61,7 @@ impl<'a, 'chunk> ChunkQuadSlice<'a, 'chunk> {
         neighbors: &'a Neighbors<'chunk>,
         registry: &'a RegistryRef<'a, BlockVariantRegistry>,
     ) -> Result<Self, OutOfBounds> {
-        if 0 > magnitude && magnitude > Chunk::SUBDIVIDED_CHUNK_SIZE {
+        if !(0..Chunk::SUBDIVIDED_CHUNK_SIZE).contains(&magnitude) {
             return Err(OutOfBounds);
         }
 
@@ -66,9 +71,33 @@ impl<'a, 'chunk> ChunkQuadSlice<'a, 'chunk> {
             access,
             neighbors,
             registry,
+            light: None,
         })
     }
 
+    /// Merge quads only with faces that have the same [light](ChunkQuadSlice::corner_light_mb), sampled
+    /// with the given lighting mode.
+    pub fn with_light(mut self, light: &'a ChunkLight, mode: LightingMode) -> Self {
+        self.light = Some((light, mode));
+        self
+    }
+
+    /// The light at the corners of the block face that the face of the microblock at `pos_mb` is part of,
+    /// packed like [`corner_light`]. Quads only merge with faces that have the same corner light as them,
+    /// since the light of a quad is only sampled at its corners and the light of the faces in between would
+    /// be lost. With smooth lighting this keeps quads from merging across a change in light, even if the
+    /// faces have the same light in the middle. Every face has the same light if the slice wasn't given any
+    /// light.
+    pub fn corner_light_mb(&self, pos_mb: IVec2) -> u32 {
+        let Some((light, mode)) = self.light else {
+            return 0;
+        };
+
+        let subdivs = SubdividedBlock::SUBDIVISIONS;
+        let min = pos_mb.div_euclid(IVec2::splat(subdivs)) * subdivs;
+        corner_light(light, self.face, self.mag, min, min + (subdivs - 1), mode)
+    }
+
     fn face_texture_for_variant(
         &self,
         variant_id: <BlockVariantRegistry as Registry>::Id,
@@ -76,14 +105,14 @@ impl<'a, 'chunk> ChunkQuadSlice<'a, 'chunk> {
     ) -> Option<FaceTexture> {
         let model = self.registry.get_by_id(variant_id).model?;
         let submodel = rotation
-            .map(|r| model.submodel(r.front()))
+            .map(|r| model.rotated_submodel(r))
             .unwrap_or(model.default_submodel());
 
         Some(submodel.texture(self.face))
     }
 
     pub fn reposition(&mut self, face: Face, magnitude: i32) -> Result<(), OutOfBounds> {
-        if 0 > magnitude && magnitude > Chunk::SIZE {
+        if !(0..Chunk::SUBDIVIDED_CHUNK_SIZE).contains(&magnitude) {
             return Err(OutOfBounds);
         }
 
@@ -100,6 +129,13 @@ impl<'a, 'chunk> ChunkQuadSlice<'a, 'chunk> {
         ) == SubdividedBlock::SUBDIVISIONS - 1
     }
 
+    /// Whether the faces in this slice are on the outside of the chunk, i.e. the blocks above them are
+    /// in the neighboring chunk.
+    pub fn faces_chunk_border(&self) -> bool {
+        self.mag_at_block_edge()
+            && !Self::contains_3d(self.pos_3d(IVec2::ZERO) + self.face.normal())
+    }
+
     pub fn contains_mb(pos: IVec2) -> bool {
         Self::contains(microblock_to_full_block(pos))
     }
@@ -149,8 +185,8 @@ impl<'a, 'chunk> ChunkQuadSlice<'a, 'chunk> {
     }
 
     #[inline]
-    pub fn get_3d(&self, pos: IVec3) -> CqsResult<ChunkAccessOutput> {
-        self.access.get(pos).map_err(|e| CqsError::AccessError(e))
+    pub fn get_3d(&self, pos: IVec3) -> CqsResult<ChunkAccessOutput<'_>> {
+        self.access.get(pos).map_err(CqsError::AccessError)
     }
 
     #[inline]
@@ -172,13 +208,13 @@ impl<'a, 'chunk> ChunkQuadSlice<'a, 'chunk> {
     /// `pos` is in localspace and can exceed the regular chunk bounds by 1 for any component of the vector.
     /// In this case the `ChunkAccessOutput` is taken from a neighboring chunk.
     #[inline]
-    pub fn auto_neighboring_get(&self, pos: IVec3) -> CqsResult<ChunkAccessOutput> {
+    pub fn auto_neighboring_get(&self, pos: IVec3) -> CqsResult<ChunkAccessOutput<'_>> {
         if Self::contains_3d(pos) && !neighbors::is_in_bounds_3d(pos) {
             self.get_3d(pos)
         } else if !Self::contains_3d(pos) && neighbors::is_in_bounds_3d(pos) {
             Ok(self.neighbors.get_3d(pos)?)
         } else {
-            return Err(CqsError::OutOfBounds);
+            Err(CqsError::OutOfBounds)
         }
     }
 
@@ -202,12 +238,12 @@ impl<'a, 'chunk> ChunkQuadSlice<'a, 'chunk> {
                 }
             })
         } else {
-            return Err(CqsError::OutOfBounds);
+            Err(CqsError::OutOfBounds)
         }
     }
 
     #[inline]
-    pub fn get(&self, pos: IVec2) -> CqsResult<ChunkAccessOutput> {
+    pub fn get(&self, pos: IVec2) -> CqsResult<ChunkAccessOutput<'_>> {
         if !Self::contains(pos) {
             return Err(CqsError::OutOfBounds);
         }
@@ -227,7 +263,7 @@ impl<'a, 'chunk> ChunkQuadSlice<'a, 'chunk> {
     }
 
     #[inline]
-    pub fn get_above(&self, pos: IVec2) -> CqsResult<ChunkAccessOutput> {
+    pub fn get_above(&self, pos: IVec2) -> CqsResult<ChunkAccessOutput<'_>> {
         if !Self::contains(pos) {
             return Err(CqsError::OutOfBounds);
         }
@@ -270,27 +306,83 @@ impl<'a, 'chunk> ChunkQuadSlice<'a, 'chunk> {
         let microblock = self.get_mb(pos_mb)?;
         let microblock_above = self.get_mb_above(pos_mb)?;
 
+        Ok(self.quad_between(microblock, microblock_above))
+    }
+
+    /// Like [`ChunkQuadSlice::get_quad_mb`], but `pos_mb` can be up to 1 block outside of the chunk.
+    /// In this case the quad is taken from a neighboring chunk.
+    #[inline]
+    pub fn auto_neighboring_get_quad_mb(&self, pos_mb: IVec2) -> CqsResult<Option<DataQuad>> {
+        let pos_mb_3d = self.pos_3d_mb(pos_mb);
+
+        let microblock = self.auto_neighboring_get_mb(pos_mb_3d)?;
+        let microblock_above = self.auto_neighboring_get_mb(pos_mb_3d + self.face.normal())?;
+
+        Ok(self.quad_between(microblock, microblock_above))
+    }
+
+    /// Returns `true` if the quad at `pos_mb` continues a run of identical quads coming from the chunk
+    /// on the negative X side of this one (in facespace). Only quads in the first column of blocks can
+    /// continue such a run. When merging quads across chunk borders, the neighboring chunk extends its
+    /// quads over these positions, so they should not be meshed by this chunk.
+    pub fn continues_neighbor_run(&self, pos_mb: IVec2) -> CqsResult<bool> {
+        if !Self::contains_mb(pos_mb) || pos_mb.x >= SubdividedBlock::SUBDIVISIONS {
+            return Ok(false);
+        }
+
+        let Some(quad) = self.auto_neighboring_get_quad_mb(ivec2(-1, pos_mb.y))? else {
+            return Ok(false);
+        };
+        let light = self.corner_light_mb(ivec2(-1, pos_mb.y));
+
+        for x in 0..=pos_mb.x {
+            let pos = ivec2(x, pos_mb.y);
+            if self.get_quad_mb(pos)? != Some(quad) || self.corner_light_mb(pos) != light {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
+    fn quad_between(
+        &self,
+        microblock: Microblock,
+        microblock_above: Microblock,
+    ) -> Option<DataQuad> {
+        if self.registry.is_air(microblock.id) {
+            return None;
+        }
+
         let entry = self.registry.get_by_id(microblock.id);
-        let entry_above = self.registry.get_by_id(microblock_above.id);
 
-        if entry.options.transparency.is_transparent()
-            || entry_above.options.transparency.is_opaque()
-        {
-            return Ok(None);
+        // the tops of fluids are sloped, so they're meshed separately (see crate::render::meshing::fluid)
+        if self.face == Face::Top && entry.options.fluid_level.is_some() {
+            return None;
         }
 
-        let Some(model) = entry.model else {
-            return Ok(None);
+        let transparency_above = if self.registry.is_air(microblock_above.id) {
+            Transparency::Transparent
+        } else {
+            self.registry
+                .get_by_id(microblock_above.id)
+                .options
+                .transparency
         };
 
-        let submodel = microblock
-            .rotation
-            .map(|r| model.submodel(r.front()))
-            .unwrap_or(model.default_submodel());
+        if !entry
+            .options
+            .transparency
+            .face_visible(transparency_above, microblock.id == microblock_above.id)
+        {
+            return None;
+        }
 
-        let texture = submodel.texture(self.face);
+        // the rotation of the microblock overrides the model's default orientation, so microblocks with
+        // different rotations can end up with different textures and won't be merged
+        let texture = self.face_texture_for_variant(microblock.id, microblock.rotation)?;
 
-        Ok(Some(DataQuad::new(Quad::ONE, texture)))
+        Some(DataQuad::new(Quad::ONE, texture))
     }
 }
 
@@ -435,6 +527,30 @@ pub mod tests {
         assert!(!cqs.mag_at_block_edge());
     }
 
+    #[test]
+    fn cqs_magnitude_out_of_bounds() {
+        let texreg = TextureRegistry::new_mock();
+        let varreg = RwLock::new(BlockVariantRegistry::new_mock(&texreg));
+        let neighbor_chunk = MockChunk::new(BlockVoxel::new_full(BlockVariantRegistry::VOID));
+        let chunk = testing_chunk();
+        let neighbors = testing_neighbors(&neighbor_chunk);
+
+        let access = chunk.read_access();
+        let guard = RwLockReadGuard::map(varreg.read(), |g| g);
+
+        for mag in [-1, Chunk::SUBDIVIDED_CHUNK_SIZE] {
+            assert!(ChunkQuadSlice::new(Face::Top, mag, &access, &neighbors, &guard).is_err());
+        }
+
+        let mut cqs = ChunkQuadSlice::new(Face::Top, 0, &access, &neighbors, &guard).unwrap();
+        cqs.reposition(Face::Top, Chunk::SUBDIVIDED_CHUNK_SIZE - 1)
+            .unwrap();
+        assert!(cqs.reposition(Face::Top, -1).is_err());
+        assert!(cqs
+            .reposition(Face::Top, Chunk::SUBDIVIDED_CHUNK_SIZE)
+            .is_err());
+    }
+
     #[test]
     fn cqs_can_read() {
         let texreg = TextureRegistry::new_mock();
@@ -497,6 +613,61 @@ pub mod tests {
         assert_eq!(Ok(None), cqs.get_quad_mb(ivec2(17, 12)));
     }
 
+    #[test]
+    fn self_culling_faces() {
+        let texreg = TextureRegistry::new_mock();
+        let varreg = RwLock::new(BlockVariantRegistry::new_mock(&texreg));
+        let neighbor_chunk = MockChunk::new(BlockVoxel::new_full(BlockVariantRegistry::VOID));
+        let neighbors = testing_neighbors(&neighbor_chunk);
+
+        let glass = BlockVoxel::new_full(BlockVariantRegistry::GLASS);
+        let full = BlockVoxel::new_full(BlockVariantRegistry::FULL);
+
+        let chunk = MockChunk::new(BlockVoxel::new_full(BlockVariantRegistry::VOID));
+        let mut access = chunk.access();
+        // glass on top of glass, and an opaque block on top of glass
+        for (pos, block) in [
+            (ivec3(1, 0, 1), &glass),
+            (ivec3(1, 1, 1), &glass),
+            (ivec3(3, 0, 1), &glass),
+            (ivec3(3, 1, 1), &full),
+        ] {
+            access
+                .set(pos, ChunkAccessInput::new(block.clone()))
+                .unwrap();
+        }
+        drop(access);
+
+        let access = chunk.read_access();
+        let guard = RwLockReadGuard::map(varreg.read(), |g| g);
+
+        let glass_quad = Some(DataQuad::new(
+            Quad::ONE,
+            FaceTexture::new(TextureRegistry::TEX2),
+        ));
+        let full_quad = Some(DataQuad::new(
+            Quad::ONE,
+            FaceTexture::new(TextureRegistry::TEX1),
+        ));
+
+        // the top layer of microblocks in the bottom blocks
+        let mut cqs = ChunkQuadSlice::new(Face::Top, 3, &access, &neighbors, &guard).unwrap();
+        // the face between two glass blocks is culled
+        assert_eq!(Ok(None), cqs.get_quad_mb(ivec2(4, 4)));
+        // glass covered by an opaque block is culled
+        assert_eq!(Ok(None), cqs.get_quad_mb(ivec2(12, 4)));
+
+        // glass next to air is visible
+        cqs.reposition(Face::Top, 7).unwrap();
+        assert_eq!(Ok(glass_quad), cqs.get_quad_mb(ivec2(4, 4)));
+
+        // an opaque block next to glass is visible
+        cqs.reposition(Face::Bottom, 4).unwrap();
+        assert_eq!(Ok(full_quad), cqs.get_quad_mb(ivec2(12, 4)));
+        // but the bottom of glass on top of glass isn't
+        assert_eq!(Ok(None), cqs.get_quad_mb(ivec2(4, 4)));
+    }
+
     #[test]
     fn cqs_get_quad_mb_across_chunks() {
         let texreg = TextureRegistry::new_mock();
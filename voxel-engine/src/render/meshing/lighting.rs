@@ -0,0 +1,217 @@
+use bevy::math::{ivec2, IVec2, IVec3};
+
+use crate::{
+    data::tile::Face,
+    render::quad::IsometrizedQuad,
+    topo::{
+        block::SubdividedBlock,
+        ivec_project_to_3d,
+        light::{ChunkLight, LightLevel},
+    },
+};
+
+/// The index of a quad's corner, based on which side of the quad the corner is on in facespace.
+/// The corner on the max X side has bit 0 set, and the corner on the max Y side has bit 1 set.
+/// The shader finds the corner of a vertex the same way, so the index doesn't depend on the vertex order.
+pub fn corner_index(max_x: bool, max_y: bool) -> usize {
+    (max_x as usize) | ((max_y as usize) << 1)
+}
+
+/// How the light at the corners of quads is sampled.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Default)]
+pub enum LightingMode {
+    /// Every corner gets the light of the block in front of the microblock at that corner.
+    /// Cheaper than smooth lighting, but the light changes abruptly between blocks.
+    Flat,
+    /// Every corner gets the average light of the blocks in front of the quad that touch that corner,
+    /// so the light is smoothly interpolated across faces.
+    #[default]
+    Smooth,
+}
+
+/// Sample the light at the corners of a quad and pack it into a `u32` with 8 bits per corner.
+pub fn quad_corner_light(quad: &IsometrizedQuad, light: &ChunkLight, mode: LightingMode) -> u32 {
+    corner_light(
+        light,
+        quad.isometry.face,
+        quad.isometry.magnitude(),
+        quad.min_2d(),
+        quad.max_2d(),
+        mode,
+    )
+}
+
+/// Sample the light at the corners of the microblocks from `min` to `max` (inclusive, in facespace) in the
+/// layer `magnitude`, packed like [`quad_corner_light`].
+pub fn corner_light(
+    light: &ChunkLight,
+    face: Face,
+    magnitude: i32,
+    min: IVec2,
+    max: IVec2,
+    mode: LightingMode,
+) -> u32 {
+    // the layer of microblocks in front of the quad
+    let front = magnitude + face.axis_direction();
+    let front_block = front.div_euclid(SubdividedBlock::SUBDIVISIONS);
+
+    let mut packed = 0;
+    for (max_x, max_y) in [(false, false), (true, false), (false, true), (true, true)] {
+        let level = match mode {
+            LightingMode::Flat => {
+                let corner = ivec2(
+                    if max_x { max.x } else { min.x },
+                    if max_y { max.y } else { min.y },
+                );
+
+                face_light(light, face, magnitude, corner)
+            }
+            LightingMode::Smooth => {
+                // the corner itself, rather than the microblock at the corner
+                let corner = ivec2(
+                    if max_x { max.x + 1 } else { min.x },
+                    if max_y { max.y + 1 } else { min.y },
+                );
+
+                smooth_light(light, face, front_block, corner)
+            }
+        };
+
+        packed |= (level.as_u8() as u32) << (corner_index(max_x, max_y) * 8);
+    }
+
+    packed
+}
+
+/// The light of the block in front of the face of the microblock at `pos` (in facespace) in the layer
+/// `magnitude`. This is the light [flat lighting](LightingMode::Flat) gives the corners of quads.
+pub fn face_light(light: &ChunkLight, face: Face, magnitude: i32, pos: IVec2) -> LightLevel {
+    let front = magnitude + face.axis_direction();
+    let block = ivec_project_to_3d(pos, face, front)
+        .div_euclid(IVec3::splat(SubdividedBlock::SUBDIVISIONS));
+
+    light.sample(block)
+}
+
+/// The blocks along one axis that touch a point at the given microblock position.
+/// If the point is on the border between two blocks it touches both of them.
+fn touching_blocks(mb: i32) -> impl Iterator<Item = i32> {
+    let block = mb.div_euclid(SubdividedBlock::SUBDIVISIONS);
+
+    if mb.rem_euclid(SubdividedBlock::SUBDIVISIONS) == 0 {
+        (block - 1)..=block
+    } else {
+        block..=block
+    }
+}
+
+/// The average light of the blocks in the layer `magnitude` that touch the corner at the given
+/// microblock facespace position. This is usually 4 blocks, but corners in the middle of a block
+/// (from subdivided blocks) touch less.
+fn smooth_light(light: &ChunkLight, face: Face, magnitude: i32, corner: IVec2) -> LightLevel {
+    let mut count = 0u32;
+    let mut block_sum = 0u32;
+    let mut sky_sum = 0u32;
+
+    for x in touching_blocks(corner.x) {
+        for y in touching_blocks(corner.y) {
+            let pos = ivec_project_to_3d(ivec2(x, y), face, magnitude);
+            let level = light.sample(pos);
+
+            count += 1;
+            block_sum += level.block() as u32;
+            sky_sum += level.sky() as u32;
+        }
+    }
+
+    let average = |sum: u32| ((sum + (count / 2)) / count) as u8;
+    LightLevel::new(average(block_sum), average(sky_sum))
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::ivec3;
+
+    use crate::{
+        data::{registries::texture::TextureRegistry, texture::FaceTexture},
+        render::quad::{DataQuad, PositionedQuad, Quad, QuadIsometry},
+    };
+
+    use super::*;
+
+    fn unpack(packed: u32, max_x: bool, max_y: bool) -> u8 {
+        (packed >> (corner_index(max_x, max_y) * 8)) as u8
+    }
+
+    #[test]
+    fn flat_corner_light() {
+        let mut light = ChunkLight::new();
+        light.set(ivec3(2, 4, 3), LightLevel::new(10, 0)).unwrap();
+        light.set(ivec3(3, 4, 3), LightLevel::new(4, 15)).unwrap();
+
+        // top face of the blocks at y = 3, spanning x = 2..=3 and z = 3
+        let mut quad = PositionedQuad::new(
+            IVec2::new(8, 12),
+            DataQuad::new(Quad::ONE, FaceTexture::new(TextureRegistry::TEX1)),
+        );
+        quad.widen(7).unwrap();
+        quad.heighten(3).unwrap();
+
+        let isoquad = IsometrizedQuad::new(QuadIsometry::new(quad.pos(), 15, Face::Top), quad);
+        let packed = quad_corner_light(&isoquad, &light, LightingMode::Flat);
+
+        assert_eq!(LightLevel::new(10, 0).as_u8(), unpack(packed, false, false));
+        assert_eq!(LightLevel::new(10, 0).as_u8(), unpack(packed, false, true));
+        assert_eq!(LightLevel::new(4, 15).as_u8(), unpack(packed, true, false));
+        assert_eq!(LightLevel::new(4, 15).as_u8(), unpack(packed, true, true));
+    }
+
+    #[test]
+    fn smooth_corner_light() {
+        let mut light = ChunkLight::new();
+        for x in 0..16 {
+            for z in 0..16 {
+                light.set(ivec3(x, 4, z), LightLevel::new(0, 12)).unwrap();
+            }
+        }
+
+        // one dark block next to the min corner
+        light.set(ivec3(1, 4, 2), LightLevel::DARK).unwrap();
+
+        // top face of the block at [2, 3, 3]
+        let mut quad = PositionedQuad::new(
+            IVec2::new(8, 12),
+            DataQuad::new(Quad::ONE, FaceTexture::new(TextureRegistry::TEX1)),
+        );
+        quad.widen(3).unwrap();
+        quad.heighten(3).unwrap();
+
+        let isoquad = IsometrizedQuad::new(QuadIsometry::new(quad.pos(), 15, Face::Top), quad);
+
+        let smooth = quad_corner_light(&isoquad, &light, LightingMode::Smooth);
+        // (0 + 12 + 12 + 12) / 4
+        assert_eq!(LightLevel::new(0, 9).as_u8(), unpack(smooth, false, false));
+        assert_eq!(LightLevel::new(0, 12).as_u8(), unpack(smooth, true, true));
+
+        // flat lighting only looks at the block in front of the quad
+        let flat = quad_corner_light(&isoquad, &light, LightingMode::Flat);
+        assert_eq!(LightLevel::new(0, 12).as_u8(), unpack(flat, false, false));
+    }
+
+    #[test]
+    fn smooth_light_inside_block() {
+        let mut light = ChunkLight::new();
+        light.set(ivec3(2, 4, 3), LightLevel::new(8, 0)).unwrap();
+
+        // a corner in the middle of a block only touches that block
+        assert_eq!(
+            LightLevel::new(8, 0),
+            smooth_light(&light, Face::Top, 4, ivec2(10, 14))
+        );
+        // a corner on an edge between two blocks touches both
+        assert_eq!(
+            LightLevel::new(4, 0),
+            smooth_light(&light, Face::Top, 4, ivec2(12, 14))
+        );
+    }
+}
@@ -1,12 +1,109 @@
 // pub mod ecs;
 pub mod controller;
 pub mod error;
+pub mod fluid;
 pub mod greedy;
 pub mod immediate;
+pub mod lighting;
+pub mod selector;
+#[cfg(any(test, debug_assertions))]
+pub mod validation;
+pub mod weld;
 
-use crate::{data::registries::Registries, topo::neighbors::Neighbors};
+use bevy::{
+    ecs::system::Resource,
+    render::{extract_resource::ExtractResource, render_resource::Face},
+};
+use bitflags::bitflags;
+
+use crate::{
+    data::registries::{block::BlockVariantRegistry, Registries, Registry},
+    topo::{block::BlockVoxel, light::ChunkLight, neighbors::Neighbors, world::chunk_ref::Crra},
+};
+
+use self::error::MesherResult;
 
 pub struct Context<'reg, 'chunk> {
     pub neighbors: Neighbors<'chunk>,
     pub registries: &'reg Registries,
+    pub light: &'chunk ChunkLight,
+}
+
+pub trait Mesher {
+    fn build<'reg, 'chunk>(
+        &mut self,
+        access: Crra<'chunk>,
+        cx: Context<'reg, 'chunk>,
+    ) -> MesherResult;
+
+    /// How far away the neighboring chunks this mesher reads from may be, measured in the number of axes
+    /// the neighbor is offset along (see [`neighbors::neighbor_radius`](crate::topo::neighbors::neighbor_radius)).
+    /// A radius of 0 means no neighbors, 1 means the 6 face neighbors, 2 includes the 12 edge neighbors,
+    /// and 3 includes the 8 corner neighbors. Only the neighbors within this radius are gathered for the mesher.
+    fn neighbor_radius(&self) -> u8 {
+        1
+    }
+
+    /// Whether the faces of the geometry built by this mesher are visible from both sides. Double-sided geometry
+    /// is rendered without backface culling, for models like foliage or glass seen from the inside.
+    fn sidedness(&self) -> MeshSidedness {
+        MeshSidedness::Single
+    }
+
+    /// Whether this mesher can build the mesh of a chunk containing the given kinds of models.
+    /// Used by [`MesherSelector`](selector::MesherSelector) to pick the cheapest mesher for a chunk.
+    fn supports(&self, _models: ChunkModels) -> bool {
+        true
+    }
+}
+
+bitflags! {
+    /// The kinds of models in a chunk, see [`Mesher::supports`].
+    #[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+    pub struct ChunkModels: u8 {
+        /// Full blocks with a model.
+        const CUBES = 0b1 << 0;
+        /// Subdivided blocks, which can have faces anywhere inside the block (like slabs and stairs).
+        const SUBDIVIDED = 0b1 << 1;
+    }
+}
+
+impl ChunkModels {
+    /// The kinds of models in the chunk. Only the chunk's palette is checked, so this is cheap, but
+    /// it may include models that were overwritten since the chunk's storage was last optimized.
+    pub fn of_chunk(access: &Crra<'_>, registry: &BlockVariantRegistry) -> Self {
+        access
+            .palette()
+            .iter()
+            .fold(Self::empty(), |models, block| match block {
+                BlockVoxel::Full(full)
+                    if registry.is_air(full.id) || registry.get_by_id(full.id).model.is_none() =>
+                {
+                    models
+                }
+                BlockVoxel::Full(_) => models | Self::CUBES,
+                BlockVoxel::Subdivided(_) => models | Self::SUBDIVIDED,
+            })
+    }
+}
+
+/// Which sides of the faces in chunk meshes are visible, see [`Mesher::sidedness`].
+/// Available as a resource describing the mesher that chunks are meshed with.
+#[derive(Resource, ExtractResource, Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum MeshSidedness {
+    /// Only the front of faces is visible, so back faces are culled.
+    #[default]
+    Single,
+    /// Both sides of faces are visible.
+    Double,
+}
+
+impl MeshSidedness {
+    /// The face culling mode for rendering geometry with this sidedness.
+    pub fn cull_mode(self) -> Option<Face> {
+        match self {
+            Self::Single => Some(Face::Back),
+            Self::Double => None,
+        }
+    }
 }
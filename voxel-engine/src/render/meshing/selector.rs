@@ -0,0 +1,131 @@
+use crate::{data::registries::block::BlockVariantRegistry, topo::world::chunk_ref::Crra};
+
+use super::{error::MesherResult, ChunkModels, Context, MeshSidedness, Mesher};
+
+/// Meshes every chunk with the cheapest mesher that can handle the models in it (see [`Mesher::supports`]).
+/// The meshers are ordered from cheapest to most general, and the last one is used for chunks that none
+/// of them support. All the meshers should have the same sidedness.
+#[derive(Clone)]
+pub struct MesherSelector<M> {
+    meshers: Vec<M>,
+}
+
+impl<M: Mesher> MesherSelector<M> {
+    /// Panics if `meshers` is empty.
+    pub fn new(meshers: Vec<M>) -> Self {
+        assert!(
+            !meshers.is_empty(),
+            "mesher selector needs at least one mesher"
+        );
+
+        Self { meshers }
+    }
+
+    /// The index of the mesher that chunks with the given models are meshed with.
+    pub fn select(&self, models: ChunkModels) -> usize {
+        self.meshers
+            .iter()
+            .position(|mesher| mesher.supports(models))
+            .unwrap_or(self.meshers.len() - 1)
+    }
+
+    pub fn meshers(&self) -> &[M] {
+        &self.meshers
+    }
+}
+
+impl<M: Mesher> Mesher for MesherSelector<M> {
+    fn build<'reg, 'chunk>(
+        &mut self,
+        access: Crra<'chunk>,
+        cx: Context<'reg, 'chunk>,
+    ) -> MesherResult {
+        let models = {
+            let varreg = cx
+                .registries
+                .get_registry::<BlockVariantRegistry>()
+                .unwrap();
+            ChunkModels::of_chunk(&access, &varreg)
+        };
+
+        let index = self.select(models);
+        self.meshers[index].build(access, cx)
+    }
+
+    /// The neighbors are gathered before the mesher is selected, so this is the largest radius of all the meshers.
+    fn neighbor_radius(&self) -> u8 {
+        self.meshers
+            .iter()
+            .map(Mesher::neighbor_radius)
+            .max()
+            .unwrap()
+    }
+
+    fn sidedness(&self) -> MeshSidedness {
+        self.meshers[0].sidedness()
+    }
+
+    fn supports(&self, models: ChunkModels) -> bool {
+        self.meshers.iter().any(|mesher| mesher.supports(models))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::ivec3;
+
+    use crate::{
+        data::registries::texture::TextureRegistry,
+        render::meshing::greedy::algorithm::GreedyMesher,
+        testing_utils::MockChunk,
+        topo::{
+            access::WriteAccess,
+            block::{BlockVoxel, Microblock, SubdividedBlock},
+            world::ChunkAccessInput,
+        },
+    };
+
+    use super::*;
+
+    #[test]
+    fn select_by_models() {
+        let texreg = TextureRegistry::new_mock();
+        let varreg = BlockVariantRegistry::new_mock(&texreg);
+
+        let selector = MesherSelector::new(vec![
+            GreedyMesher::new().with_cubes_only(true),
+            GreedyMesher::new(),
+        ]);
+
+        let chunk = MockChunk::new(BlockVoxel::new_full(BlockVariantRegistry::VOID));
+        assert_eq!(
+            ChunkModels::empty(),
+            ChunkModels::of_chunk(&chunk.read_access(), &varreg)
+        );
+
+        chunk
+            .access()
+            .set(
+                ivec3(1, 2, 3),
+                ChunkAccessInput::new(BlockVoxel::new_full(BlockVariantRegistry::FULL)),
+            )
+            .unwrap();
+        let models = ChunkModels::of_chunk(&chunk.read_access(), &varreg);
+        assert_eq!(ChunkModels::CUBES, models);
+        assert_eq!(0, selector.select(models));
+
+        chunk
+            .access()
+            .set(
+                ivec3(4, 5, 6),
+                ChunkAccessInput::new(BlockVoxel::Subdivided(SubdividedBlock::new(
+                    Microblock::new(BlockVariantRegistry::SUBDIV),
+                ))),
+            )
+            .unwrap();
+        let models = ChunkModels::of_chunk(&chunk.read_access(), &varreg);
+        assert_eq!(ChunkModels::CUBES | ChunkModels::SUBDIVIDED, models);
+        assert_eq!(1, selector.select(models));
+        assert!(selector.supports(models));
+    }
+}
@@ -0,0 +1,237 @@
+//! Sanity checks for the output of meshers. Bugs in quad merging can produce quads that overlap (causing
+//! z-fighting) or T-junctions (causing lighting seams and cracks between quads), which are easy to miss
+//! by looking at the world. These checks are too slow to run on every mesh, so they're only compiled
+//! into tests and debug builds.
+
+use bevy::math::{IVec2, Vec2};
+
+use crate::{data::tile::Face, render::quad::GpuQuad};
+
+use super::controller::ChunkMeshData;
+
+/// The number of units per block that quad coordinates are converted to for validation.
+/// Quads are aligned to microblocks so this makes all the coordinates integers.
+const UNITS_PER_BLOCK: f32 = 4.0;
+
+/// A vertex of one quad that lies inside the edge of another quad on the same plane.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TJunction {
+    /// The index of the quad whose vertex is on the edge of the other quad.
+    pub vertex_quad: usize,
+    /// The index of the quad whose edge the vertex is on.
+    pub edge_quad: usize,
+    /// The position of the vertex in facespace, in blocks.
+    pub vertex: Vec2,
+}
+
+/// Issues found in a mesh by [`validate_quads`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MeshValidation {
+    /// Pairs of indices of quads that face the same way on the same plane and overlap.
+    pub overlaps: Vec<(usize, usize)>,
+    pub t_junctions: Vec<TJunction>,
+}
+
+impl MeshValidation {
+    pub fn is_clean(&self) -> bool {
+        self.overlaps.is_empty() && self.t_junctions.is_empty()
+    }
+}
+
+/// A quad in integer facespace coordinates. `max` is exclusive.
+#[derive(Copy, Clone, Debug)]
+struct PlaneQuad {
+    face: Face,
+    magnitude: i32,
+    min: IVec2,
+    max: IVec2,
+}
+
+impl PlaneQuad {
+    fn new(quad: &GpuQuad) -> Self {
+        Self {
+            face: quad.bitfields.get_face(),
+            magnitude: quad.magnitude,
+            min: (quad.min * UNITS_PER_BLOCK).round().as_ivec2(),
+            max: (quad.max * UNITS_PER_BLOCK).round().as_ivec2(),
+        }
+    }
+
+    fn coplanar(&self, other: &Self) -> bool {
+        self.face == other.face && self.magnitude == other.magnitude
+    }
+
+    fn overlaps(&self, other: &Self) -> bool {
+        self.min.cmplt(other.max).all() && other.min.cmplt(self.max).all()
+    }
+
+    fn vertices(&self) -> [IVec2; 4] {
+        [
+            self.min,
+            IVec2::new(self.max.x, self.min.y),
+            IVec2::new(self.min.x, self.max.y),
+            self.max,
+        ]
+    }
+
+    /// Whether the point lies on an edge of this quad, excluding the corners.
+    fn edge_contains(&self, point: IVec2) -> bool {
+        let inside_x = self.min.x < point.x && point.x < self.max.x;
+        let inside_y = self.min.y < point.y && point.y < self.max.y;
+        let on_x = point.x == self.min.x || point.x == self.max.x;
+        let on_y = point.y == self.min.y || point.y == self.max.y;
+
+        (inside_x && on_y) || (inside_y && on_x)
+    }
+}
+
+/// Check the quads for overlaps and T-junctions. Only quads facing the same way on the same plane are
+/// compared, since those are the only quads that can z-fight or share edges.
+pub fn validate_quads(quads: &[GpuQuad]) -> MeshValidation {
+    let quads = quads.iter().map(PlaneQuad::new).collect::<Vec<_>>();
+    let mut validation = MeshValidation::default();
+
+    for (i, a) in quads.iter().enumerate() {
+        for (j, b) in quads.iter().enumerate() {
+            if i == j || !a.coplanar(b) {
+                continue;
+            }
+
+            if i < j && a.overlaps(b) {
+                validation.overlaps.push((i, j));
+            }
+
+            for vertex in a.vertices() {
+                if b.edge_contains(vertex) {
+                    validation.t_junctions.push(TJunction {
+                        vertex_quad: i,
+                        edge_quad: j,
+                        vertex: vertex.as_vec2() / UNITS_PER_BLOCK,
+                    });
+                }
+            }
+        }
+    }
+
+    validation
+}
+
+/// Check the quads of a chunk mesh, see [`validate_quads`].
+pub fn validate_mesh(mesh: &ChunkMeshData) -> MeshValidation {
+    validate_quads(&mesh.quad_buffer)
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::vec2;
+
+    use crate::{
+        data::registries::block::BlockVariantRegistry,
+        render::{
+            meshing::{greedy::algorithm::GreedyMesher, Context, Mesher},
+            quad::GpuQuadBitfields,
+        },
+        testing_utils::{mock_registries, MockChunk},
+        topo::{
+            block::BlockVoxel, light::ChunkLight, neighbors::NeighborsBuilder, world::ChunkPos,
+            worldgen::generator::Generator,
+        },
+    };
+
+    use super::*;
+
+    fn quad(face: Face, magnitude: i32, min: Vec2, max: Vec2) -> GpuQuad {
+        GpuQuad {
+            texture_id: 0,
+            bitfields: GpuQuadBitfields::new().with_face(face),
+            min,
+            max,
+            magnitude,
+            light: 0,
+        }
+    }
+
+    #[test]
+    fn overlapping_quads_are_flagged() {
+        let quads = [
+            quad(Face::Top, 4, vec2(0.0, 0.0), vec2(2.0, 2.0)),
+            quad(Face::Top, 4, vec2(1.0, 1.0), vec2(3.0, 3.0)),
+            // same area but on another plane or facing the other way, so it doesn't overlap
+            quad(Face::Top, 8, vec2(0.0, 0.0), vec2(2.0, 2.0)),
+            quad(Face::Bottom, 4, vec2(0.0, 0.0), vec2(2.0, 2.0)),
+        ];
+
+        let validation = validate_quads(&quads);
+        assert_eq!(vec![(0, 1)], validation.overlaps);
+        assert!(!validation.is_clean());
+    }
+
+    #[test]
+    fn t_junctions_are_flagged() {
+        // a wide quad next to two quads that are half as wide, the vertex between the narrow
+        // quads lies on the edge of the wide quad
+        let quads = [
+            quad(Face::Top, 4, vec2(0.0, 0.0), vec2(2.0, 1.0)),
+            quad(Face::Top, 4, vec2(0.0, 1.0), vec2(1.0, 2.0)),
+            quad(Face::Top, 4, vec2(1.0, 1.0), vec2(2.0, 2.0)),
+        ];
+
+        let validation = validate_quads(&quads);
+        assert!(validation.overlaps.is_empty());
+        assert_eq!(2, validation.t_junctions.len());
+        assert!(validation
+            .t_junctions
+            .iter()
+            .all(|tj| tj.edge_quad == 0 && tj.vertex == vec2(1.0, 1.0)));
+    }
+
+    #[test]
+    fn clean_mesh_passes() {
+        let quads = [
+            quad(Face::Top, 4, vec2(0.0, 0.0), vec2(1.0, 2.0)),
+            quad(Face::Top, 4, vec2(1.0, 0.0), vec2(2.0, 2.0)),
+            quad(Face::North, 0, vec2(0.0, 0.0), vec2(2.0, 2.0)),
+        ];
+
+        assert!(validate_quads(&quads).is_clean());
+    }
+
+    #[test]
+    fn generated_chunks_have_no_overlapping_quads() {
+        let registries = mock_registries();
+
+        let generator = Generator::new(0, &registries);
+        let light = ChunkLight::new();
+
+        for pos in [
+            ChunkPos::new(0, 0, 0),
+            ChunkPos::new(1, 0, -1),
+            ChunkPos::new(-2, 1, 3),
+        ] {
+            let chunk = MockChunk::new(BlockVoxel::new_full(BlockVariantRegistry::VOID));
+            generator.write_to_chunk(pos, &mut chunk.access()).unwrap();
+
+            let neighbors =
+                NeighborsBuilder::new(BlockVoxel::new_full(BlockVariantRegistry::VOID)).build();
+            let mesh = GreedyMesher::new()
+                .build(
+                    chunk.read_access(),
+                    Context {
+                        neighbors,
+                        registries: &registries,
+                        light: &light,
+                    },
+                )
+                .unwrap();
+
+            // greedy meshing produces T-junctions by design wherever quads of different sizes meet,
+            // but quads should never overlap
+            let validation = validate_mesh(&mesh);
+            assert_eq!(
+                Vec::<(usize, usize)>::new(),
+                validation.overlaps,
+                "overlapping quads in generated chunk {pos}"
+            );
+        }
+    }
+}
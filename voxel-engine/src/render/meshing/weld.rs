@@ -0,0 +1,287 @@
+//! Welding of T-junctions in chunk meshes. Greedy meshing places quads of different sizes next to each other,
+//! so the vertex between two small quads can lie on the edge of a big quad next to them. Rasterization can
+//! leave tiny cracks along such edges, since the big quad doesn't have a vertex there.
+//! See [`validation`](super::validation) for detecting T-junctions.
+
+use std::collections::BTreeSet;
+
+use bevy::math::{ivec2, IVec2};
+
+use crate::{data::tile::Face, render::quad::GpuQuad, topo::light::LightLevel};
+
+use super::{controller::ChunkMeshData, lighting::corner_index};
+
+/// The number of units per block that quad coordinates are converted to for welding.
+/// Quads are aligned to microblocks so this makes all the coordinates integers.
+const UNITS_PER_BLOCK: f32 = 4.0;
+
+/// Where to split a quad in two, in units.
+#[derive(Copy, Clone, Debug)]
+enum Cut {
+    X(i32),
+    Y(i32),
+}
+
+fn to_units(quad: &GpuQuad) -> (IVec2, IVec2) {
+    (
+        (quad.min * UNITS_PER_BLOCK).round().as_ivec2(),
+        (quad.max * UNITS_PER_BLOCK).round().as_ivec2(),
+    )
+}
+
+/// Split quads at the vertices of other quads on the same plane that lie on their edges, so that quads
+/// sharing an edge also share all the vertices on it. Quads are rectangles so a vertex can't be inserted
+/// into an edge, instead the quad is split in two at the vertex. The light at the new corners is interpolated
+/// from the corners of the original quad. Returns the number of splits.
+///
+/// Sloped quads (with [corner depths](crate::render::quad::GpuQuadBitfields::with_corner_depths), like fluid surfaces) don't lie
+/// on the plane of their magnitude, so they're left out of welding entirely.
+///
+/// Expects 6 indices per quad in the index buffer, in the same order as the quads (like the greedy mesher
+/// builds them). The new quads are triangulated like the quads they were split from.
+pub fn weld_t_junctions(mesh: &mut ChunkMeshData) -> usize {
+    debug_assert_eq!(mesh.quad_buffer.len() * 6, mesh.index_buffer.len());
+
+    let mut planes = hb::HashMap::<(Face, i32), Vec<usize>>::new();
+    for (i, quad) in mesh.quad_buffer.iter().enumerate() {
+        if is_sloped(quad) {
+            continue;
+        }
+
+        planes
+            .entry((quad.bitfields.get_face(), quad.magnitude))
+            .or_default()
+            .push(i);
+    }
+
+    let mut splits = 0;
+
+    for mut plane in planes.into_values() {
+        // splitting a quad adds vertices to the edge opposite of the T-junction, which can make new
+        // T-junctions with the quads on that side, so keep going until there are none left
+        loop {
+            let cuts = find_cuts(&mesh.quad_buffer, &plane);
+            if cuts.is_empty() {
+                break;
+            }
+
+            for (quad, cut) in cuts {
+                plane.push(split_quad(mesh, quad, cut));
+                splits += 1;
+            }
+        }
+    }
+
+    splits
+}
+
+fn is_sloped(quad: &GpuQuad) -> bool {
+    (0..4).any(|corner| quad.bitfields.get_corner_depth(corner) != 0.0)
+}
+
+/// Find at most one cut for every quad in the plane that has another quad's vertex on one of its edges.
+fn find_cuts(quads: &[GpuQuad], plane: &[usize]) -> Vec<(usize, Cut)> {
+    // the X positions of the vertices in every row, and the Y positions of the vertices in every column
+    let mut rows = hb::HashMap::<i32, BTreeSet<i32>>::new();
+    let mut columns = hb::HashMap::<i32, BTreeSet<i32>>::new();
+
+    for &i in plane {
+        let (min, max) = to_units(&quads[i]);
+
+        for vertex in [min, max, ivec2(min.x, max.y), ivec2(max.x, min.y)] {
+            rows.entry(vertex.y).or_default().insert(vertex.x);
+            columns.entry(vertex.x).or_default().insert(vertex.y);
+        }
+    }
+
+    // the first vertex strictly between `from` and `to` on any of the lines
+    let first_between = |lines: &hb::HashMap<i32, BTreeSet<i32>>, at: [i32; 2], from, to| {
+        at.iter()
+            .filter_map(|line| lines.get(line)?.range(from + 1..to).next().copied())
+            .min()
+    };
+
+    plane
+        .iter()
+        .filter_map(|&i| {
+            let (min, max) = to_units(&quads[i]);
+
+            let cut = first_between(&rows, [min.y, max.y], min.x, max.x)
+                .map(Cut::X)
+                .or_else(|| first_between(&columns, [min.x, max.x], min.y, max.y).map(Cut::Y))?;
+
+            Some((i, cut))
+        })
+        .collect()
+}
+
+/// Linearly interpolate between two light levels, per channel.
+fn lerp_light(a: u8, b: u8, t: f32) -> u8 {
+    let (a, b) = (LightLevel::from_u8(a), LightLevel::from_u8(b));
+    let lerp = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+
+    LightLevel::new(lerp(a.block(), b.block()), lerp(a.sky(), b.sky())).as_u8()
+}
+
+/// Split the packed corner light of a quad (see [`quad_corner_light`](super::lighting::quad_corner_light))
+/// at `t` along the X axis (or the Y axis if `along_x` is false), into the light of the min and max side.
+fn split_light(light: u32, t: f32, along_x: bool) -> (u32, u32) {
+    let corner = |max_x, max_y| (light >> (corner_index(max_x, max_y) * 8)) as u8;
+    let (mut min_side, mut max_side) = (light, light);
+
+    for other in [false, true] {
+        let (low, high) = match along_x {
+            true => ((false, other), (true, other)),
+            false => ((other, false), (other, true)),
+        };
+
+        let middle = lerp_light(corner(low.0, low.1), corner(high.0, high.1), t) as u32;
+        let high_shift = corner_index(high.0, high.1) * 8;
+        let low_shift = corner_index(low.0, low.1) * 8;
+
+        min_side = (min_side & !(0xff << high_shift)) | (middle << high_shift);
+        max_side = (max_side & !(0xff << low_shift)) | (middle << low_shift);
+    }
+
+    (min_side, max_side)
+}
+
+/// Split a quad in two, the quad keeps the min side of the cut and the max side is pushed as a new quad.
+/// Returns the index of the new quad.
+fn split_quad(mesh: &mut ChunkMeshData, i: usize, cut: Cut) -> usize {
+    let quad = mesh.quad_buffer[i];
+    let (min, max) = to_units(&quad);
+    let (mut min_side, mut max_side) = (quad, quad);
+
+    match cut {
+        Cut::X(x) => {
+            let t = (x - min.x) as f32 / (max.x - min.x) as f32;
+            min_side.max.x = x as f32 / UNITS_PER_BLOCK;
+            max_side.min.x = x as f32 / UNITS_PER_BLOCK;
+            (min_side.light, max_side.light) = split_light(quad.light, t, true);
+        }
+        Cut::Y(y) => {
+            let t = (y - min.y) as f32 / (max.y - min.y) as f32;
+            min_side.max.y = y as f32 / UNITS_PER_BLOCK;
+            max_side.min.y = y as f32 / UNITS_PER_BLOCK;
+            (min_side.light, max_side.light) = split_light(quad.light, t, false);
+        }
+    }
+
+    let new = mesh.quad_buffer.len();
+    let first_vertex = |quad: usize| quad as u32 * 4;
+    let triangles: [u32; 6] =
+        std::array::from_fn(|k| mesh.index_buffer[i * 6 + k] - first_vertex(i));
+
+    mesh.quad_buffer[i] = min_side;
+    mesh.quad_buffer.push(max_side);
+    mesh.index_buffer
+        .extend(triangles.map(|idx| idx + first_vertex(new)));
+
+    new
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::{vec2, Vec2};
+
+    use crate::render::{meshing::validation::validate_quads, quad::GpuQuadBitfields};
+
+    use super::*;
+
+    fn quad(min: Vec2, max: Vec2, light: u32) -> GpuQuad {
+        GpuQuad {
+            texture_id: 0,
+            bitfields: GpuQuadBitfields::new().with_face(Face::Top),
+            min,
+            max,
+            magnitude: 4,
+            light,
+        }
+    }
+
+    fn mesh(quads: Vec<GpuQuad>) -> ChunkMeshData {
+        ChunkMeshData {
+            index_buffer: (0..quads.len() as u32)
+                .flat_map(|q| [0, 1, 2, 2, 1, 3].map(|i| q * 4 + i))
+                .collect(),
+            quad_buffer: quads,
+        }
+    }
+
+    #[test]
+    fn t_junction_is_welded() {
+        // skylight 0 on the min X side of the wide quad and 14 on the max X side
+        let bright = LightLevel::new(0, 14).as_u8() as u32;
+        let light = (bright << (corner_index(true, false) * 8))
+            | (bright << (corner_index(true, true) * 8));
+
+        // the vertex between the narrow quads lies on the top edge of the wide quad
+        let mut mesh = mesh(vec![
+            quad(vec2(0.0, 0.0), vec2(2.0, 1.0), light),
+            quad(vec2(0.0, 1.0), vec2(1.0, 2.0), 0),
+            quad(vec2(1.0, 1.0), vec2(2.0, 2.0), 0),
+        ]);
+        assert!(!validate_quads(&mesh.quad_buffer).is_clean());
+
+        assert_eq!(1, weld_t_junctions(&mut mesh));
+        assert!(validate_quads(&mesh.quad_buffer).is_clean());
+        assert_eq!(4, mesh.quad_buffer.len());
+        assert_eq!(24, mesh.index_buffer.len());
+        assert_eq!(&[12, 13, 14, 14, 13, 15], &mesh.index_buffer[18..]);
+
+        // the wide quad now has a vertex at the T-junction
+        let (left, right) = (mesh.quad_buffer[0], mesh.quad_buffer[3]);
+        assert_eq!((vec2(0.0, 0.0), vec2(1.0, 1.0)), (left.min, left.max));
+        assert_eq!((vec2(1.0, 0.0), vec2(2.0, 1.0)), (right.min, right.max));
+
+        // and the light in the middle of it is interpolated
+        let middle = LightLevel::new(0, 7).as_u8() as u32;
+        assert_eq!(
+            (middle << (corner_index(true, false) * 8))
+                | (middle << (corner_index(true, true) * 8)),
+            left.light
+        );
+        assert_eq!(
+            middle
+                | (middle << (corner_index(false, true) * 8))
+                | (bright << (corner_index(true, false) * 8))
+                | (bright << (corner_index(true, true) * 8)),
+            right.light
+        );
+    }
+
+    #[test]
+    fn sloped_quads_are_not_welded() {
+        let mut mesh = mesh(vec![
+            quad(vec2(0.0, 0.0), vec2(2.0, 1.0), 0),
+            quad(vec2(0.0, 1.0), vec2(1.0, 2.0), 0),
+            quad(vec2(1.0, 1.0), vec2(2.0, 2.0), 0),
+        ]);
+        // a fluid surface sloping down towards the max X side
+        mesh.quad_buffer[0].bitfields = mesh.quad_buffer[0]
+            .bitfields
+            .with_corner_depths([0.0, 0.5, 0.0, 0.5]);
+
+        let expected = mesh.clone();
+        assert_eq!(0, weld_t_junctions(&mut mesh));
+        assert_eq!(expected.quad_buffer, mesh.quad_buffer);
+        assert_eq!(expected.index_buffer, mesh.index_buffer);
+    }
+
+    #[test]
+    fn clean_mesh_is_unchanged() {
+        let mut clean = mesh(vec![
+            quad(vec2(0.0, 0.0), vec2(1.0, 2.0), 0),
+            quad(vec2(1.0, 0.0), vec2(2.0, 2.0), 0),
+            // would make a T-junction with the quads above, but it's on another plane
+            quad(vec2(0.5, 2.0), vec2(1.5, 3.0), 0),
+        ]);
+        clean.quad_buffer[2].magnitude = 8;
+
+        let expected = clean.clone();
+        assert_eq!(0, weld_t_junctions(&mut clean));
+        assert_eq!(expected.quad_buffer, clean.quad_buffer);
+        assert_eq!(expected.index_buffer, clean.index_buffer);
+    }
+}
@@ -1,11 +1,27 @@
 use std::{mem::size_of, num::NonZeroU8};
 
-use bevy::{ecs::component::Component, math::IVec3};
+use bevy::{
+    ecs::component::Component,
+    math::{ivec3, uvec3, IVec3, UVec3},
+    render::render_resource::ShaderDefVal,
+};
 
 use crate::{
-    data::tile::Face,
+    data::{
+        registries::{
+            block::{BlockVariantId, BlockVariantRegistry},
+            Registry,
+        },
+        tile::Face,
+    },
+    render::core::u32_shader_def,
     topo::{
-        access::HasBounds, bounding_box::BoundingBox, storage::error::OutOfBounds, world::Chunk,
+        access::{ChunkAccess, HasBounds},
+        block::SubdividedBlock,
+        bounding_box::BoundingBox,
+        neighbors::Neighbors,
+        storage::error::OutOfBounds,
+        world::{CaoBlock, Chunk},
     },
     util::ivec3_to_1d,
 };
@@ -39,19 +55,65 @@ impl BlockOcclusion {
 
     pub fn is_occluded(&self, face: Face) -> bool {
         match self.0 {
-            Some(v) => u8::from(v) & (face.as_usize() as u8) != 0,
+            Some(v) => u8::from(v) & (1u8 << face.as_usize()) != 0,
             None => false,
         }
     }
 
     pub fn as_byte(self) -> u8 {
-        self.0.map(|b| u8::from(b)).unwrap_or(0)
+        self.0.map(u8::from).unwrap_or(0)
     }
 
     pub fn from_byte(mut byte: u8) -> Self {
         byte &= 0b00111111;
         Self(NonZeroU8::new(byte))
     }
+
+    /// Calculate the occlusion of a block. A face of a block is occluded if it's entirely covered by opaque
+    /// (micro)blocks, so a full opaque block occludes all its faces while a transparent block occludes none.
+    /// Air never occludes anything, whatever its transparency.
+    pub fn from_block(block: CaoBlock<'_>, registry: &BlockVariantRegistry) -> Self {
+        let is_opaque = |id: BlockVariantId| {
+            !registry.is_air(id) && registry.get_by_id(id).options.transparency.is_opaque()
+        };
+
+        match block {
+            CaoBlock::Full(full) => {
+                if is_opaque(full.id) {
+                    Self::filled()
+                } else {
+                    Self::empty()
+                }
+            }
+            CaoBlock::Subdivided(subdiv) => {
+                let faces = Face::FACES
+                    .into_iter()
+                    .filter(|&face| {
+                        subdivided_face_layer(face)
+                            .all(|pos| is_opaque(subdiv.get(pos).unwrap().id))
+                    })
+                    .collect::<Vec<_>>();
+
+                Self::new(&faces)
+            }
+        }
+    }
+}
+
+/// The positions of the microblocks on the given face of a subdivided block.
+fn subdivided_face_layer(face: Face) -> impl Iterator<Item = UVec3> {
+    const MAX: u32 = SubdividedBlock::SUBDIVISIONS as u32 - 1;
+
+    (0..=MAX).flat_map(move |a| {
+        (0..=MAX).map(move |b| match face {
+            Face::Top => uvec3(a, MAX, b),
+            Face::Bottom => uvec3(a, 0, b),
+            Face::North => uvec3(MAX, a, b),
+            Face::South => uvec3(0, a, b),
+            Face::East => uvec3(a, b, MAX),
+            Face::West => uvec3(a, b, 0),
+        })
+    })
 }
 
 #[derive(Clone, Debug, Component)]
@@ -60,6 +122,12 @@ pub struct ChunkOcclusionMap([BlockOcclusion; Self::BUFFER_SIZE]);
 // we need to be able to reinterperet the whole buffer as a buffer of u32s
 static_assertions::const_assert_eq!(0, ChunkOcclusionMap::BUFFER_SIZE % size_of::<u32>());
 
+impl Default for ChunkOcclusionMap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ChunkOcclusionMap {
     pub const USIZE: usize = Chunk::USIZE + 2;
     pub const SIZE: i32 = Self::USIZE as i32;
@@ -78,6 +146,60 @@ impl ChunkOcclusionMap {
         Self([BlockOcclusion::default(); Self::BUFFER_SIZE])
     }
 
+    /// Build an occlusion map by calling `f` for every position in [`ChunkOcclusionMap::BOUNDS`].
+    pub fn from_fn<F>(mut f: F) -> Self
+    where
+        F: FnMut(IVec3) -> BlockOcclusion,
+    {
+        let mut map = Self::new();
+
+        for x in Self::BOUNDS.min.x..Self::BOUNDS.max.x {
+            for y in Self::BOUNDS.min.y..Self::BOUNDS.max.y {
+                for z in Self::BOUNDS.min.z..Self::BOUNDS.max.z {
+                    let pos = ivec3(x, y, z);
+                    map.set(pos, f(pos)).unwrap();
+                }
+            }
+        }
+
+        map
+    }
+
+    /// Build the occlusion map for a chunk, the border of the map is read from the neighboring chunks.
+    /// Blocks that can't be read are treated as not occluding anything.
+    pub fn build<'a, A>(
+        access: &'a A,
+        neighbors: &Neighbors,
+        registry: &BlockVariantRegistry,
+    ) -> Self
+    where
+        A: ChunkAccess<'a>,
+    {
+        Self::from_fn(|pos| {
+            let block = if Chunk::BOUNDING_BOX.contains(pos) {
+                access.get(pos).ok().map(|output| output.block)
+            } else {
+                neighbors.get_3d(pos).ok().map(|output| output.block)
+            };
+
+            block
+                .map(|block| BlockOcclusion::from_block(block, registry))
+                .unwrap_or_default()
+        })
+    }
+
+    pub fn shader_defs() -> Vec<ShaderDefVal> {
+        vec![
+            u32_shader_def("CHUNK_OCCLUSION_BUFFER_SIZE", Self::GPU_BUFFER_SIZE),
+            u32_shader_def(
+                "CHUNK_OCCLUSION_BUFFER_DIMENSIONS",
+                Self::GPU_BUFFER_DIMENSIONS,
+            ),
+        ]
+    }
+
+    /// Serialize the map into the layout of the GPU buffer. The returned buffer always has a length of
+    /// [`ChunkOcclusionMap::GPU_BUFFER_SIZE`].
     pub fn as_buffer(self) -> Vec<[u8; size_of::<u32>()]> {
         let mut buffer = vec![[0; size_of::<u32>()]; Self::BUFFER_SIZE / size_of::<u32>()];
 
@@ -124,14 +246,23 @@ impl HasBounds for ChunkOcclusionMap {
 
 #[cfg(test)]
 mod tests {
-    use bevy::math::ivec3;
+    use crate::{
+        data::registries::texture::TextureRegistry,
+        testing_utils::MockChunk,
+        topo::{
+            access::WriteAccess,
+            block::{BlockVoxel, FullBlock, Microblock},
+            neighbors::NeighborsBuilder,
+            world::ChunkAccessInput,
+        },
+    };
 
     use super::*;
 
     #[test]
     fn divisible_by_u32_size() {
         let v = ChunkOcclusionMap::BUFFER_SIZE;
-        assert!(v % size_of::<u32>() == 0);
+        assert!(v.is_multiple_of(size_of::<u32>()));
     }
 
     #[test]
@@ -215,4 +346,94 @@ mod tests {
         f(ivec3(0, 5, 0), BlockOcclusion::new(&[Face::West]));
         f(ivec3(10, 2, 14), BlockOcclusion::new(&[Face::South]));
     }
+
+    #[test]
+    fn test_is_occluded() {
+        let occlusion = BlockOcclusion::new(&[Face::Top, Face::West]);
+
+        assert!(occlusion.is_occluded(Face::Top));
+        assert!(occlusion.is_occluded(Face::West));
+        assert!(!occlusion.is_occluded(Face::Bottom));
+        assert!(!occlusion.is_occluded(Face::North));
+    }
+
+    #[test]
+    fn test_build_occlusion_map() {
+        let texreg = TextureRegistry::new_mock();
+        let registry = BlockVariantRegistry::new_mock(&texreg);
+
+        let chunk = MockChunk::new(BlockVoxel::new_full(BlockVariantRegistry::VOID));
+        let mut access = chunk.access();
+
+        access
+            .set(
+                ivec3(3, 3, 3),
+                ChunkAccessInput::new(BlockVoxel::new_full(BlockVariantRegistry::FULL)),
+            )
+            .unwrap();
+
+        // subdivided block with only its bottom layer filled
+        let mut subdiv = SubdividedBlock::new(Microblock::new(BlockVariantRegistry::VOID));
+        for x in 0..4 {
+            for z in 0..4 {
+                subdiv
+                    .set(
+                        uvec3(x, 0, z),
+                        Microblock::new(BlockVariantRegistry::SUBDIV),
+                    )
+                    .unwrap();
+            }
+        }
+
+        access
+            .set(
+                ivec3(7, 0, 7),
+                ChunkAccessInput::new(BlockVoxel::Subdivided(subdiv)),
+            )
+            .unwrap();
+
+        drop(access);
+
+        let neighbors =
+            NeighborsBuilder::new(BlockVoxel::new_full(BlockVariantRegistry::FULL)).build();
+        let map = ChunkOcclusionMap::build(&chunk.read_access(), &neighbors, &registry);
+
+        assert_eq!(
+            ChunkOcclusionMap::GPU_BUFFER_SIZE as usize,
+            map.clone().as_buffer().len()
+        );
+
+        assert_eq!(BlockOcclusion::filled(), map.get(ivec3(3, 3, 3)).unwrap());
+        assert_eq!(BlockOcclusion::empty(), map.get(ivec3(3, 4, 3)).unwrap());
+
+        let subdiv_occlusion = map.get(ivec3(7, 0, 7)).unwrap();
+        assert_eq!(BlockOcclusion::new(&[Face::Bottom]), subdiv_occlusion);
+        assert!(subdiv_occlusion.is_occluded(Face::Bottom));
+        assert!(!subdiv_occlusion.is_occluded(Face::Top));
+
+        // the border is read from the neighbors, which are all filled with opaque blocks
+        assert_eq!(BlockOcclusion::filled(), map.get(ivec3(-1, 5, 5)).unwrap());
+        assert_eq!(
+            BlockOcclusion::filled(),
+            map.get(ivec3(16, 16, 16)).unwrap()
+        );
+    }
+
+    #[test]
+    fn air_variants_dont_occlude() {
+        let texreg = TextureRegistry::new_mock();
+        let registry = BlockVariantRegistry::new_mock(&texreg);
+        let full = CaoBlock::Full(FullBlock::new(BlockVariantRegistry::FULL));
+
+        assert_eq!(
+            BlockOcclusion::filled(),
+            BlockOcclusion::from_block(full, &registry)
+        );
+
+        let registry = registry.with_air(BlockVariantRegistry::FULL);
+        assert_eq!(
+            BlockOcclusion::empty(),
+            BlockOcclusion::from_block(full, &registry)
+        );
+    }
 }
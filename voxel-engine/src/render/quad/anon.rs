@@ -13,13 +13,13 @@ pub struct Quad {
 impl Quad {
     pub const ONE: Self = Self {
         // SAFETY: the u32 literal is not zero
-        x: unsafe { NonZeroU32::new_unchecked(1) },
-        y: unsafe { NonZeroU32::new_unchecked(1) },
+        x: NonZeroU32::new(1).unwrap(),
+        y: NonZeroU32::new(1).unwrap(),
     };
 
     #[inline]
     pub fn new(dims: UVec2) -> Result<Self, QuadError> {
-        if dims.x <= 0 || dims.y <= 0 {
+        if dims.x == 0 || dims.y == 0 {
             return Err(QuadError::InvalidDimensions);
         }
 
@@ -60,6 +60,12 @@ impl Quad {
         ivec2(self.x(), self.y())
     }
 
+    /// The number of cells covered by this quad. Never zero, since both dimensions are at least 1.
+    #[inline]
+    pub fn area(self) -> i32 {
+        self.x() * self.y()
+    }
+
     #[inline]
     pub fn x(self) -> i32 {
         u32::from(self.x) as i32
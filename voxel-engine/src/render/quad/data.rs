@@ -8,10 +8,16 @@ pub struct QVertexData;
 #[derive(Copy, Clone, Hash, Debug, PartialEq, Eq)]
 pub struct QData([QVertexData; 4]);
 
+impl Default for QData {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl QData {
     #[inline]
     pub fn new() -> Self {
-        Self([QVertexData::default(); 4])
+        Self([QVertexData; 4])
     }
 
     #[inline]
@@ -172,6 +172,7 @@ impl PositionedQuad {
     }
 }
 
+/// Float version of [`Face::project_to_3d`], with the same conventions.
 #[inline]
 pub fn project_to_3d(pos: Vec2, face: Face, mag: f32) -> Vec3 {
     match face.axis() {
@@ -181,6 +182,7 @@ pub fn project_to_3d(pos: Vec2, face: Face, mag: f32) -> Vec3 {
     }
 }
 
+/// Float version of [`Face::to_plane_coords`], the inverse of [`project_to_3d`].
 #[inline]
 pub fn project_to_2d(pos: Vec3, face: Face) -> Vec2 {
     match face.axis() {
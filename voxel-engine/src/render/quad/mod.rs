@@ -2,17 +2,25 @@ pub mod anon;
 pub mod data;
 pub mod error;
 pub mod isometric;
+pub mod tangent;
 
 use std::{fmt::Debug, mem::size_of};
 
 pub use anon::*;
-use bevy::{math::Vec2, render::render_resource::ShaderType};
+use bevy::{
+    math::Vec2,
+    render::render_resource::{ShaderDefVal, ShaderType},
+};
 pub use data::*;
 pub use error::*;
 pub use isometric::*;
 use num_traits::FromPrimitive;
+pub use tangent::*;
 
-use crate::data::{texture::FaceTextureRotation, tile::Face};
+use crate::{
+    data::{texture::FaceTextureRotation, tile::Face},
+    render::core::u32_shader_def,
+};
 
 #[rustfmt::skip]
 pub mod consts {
@@ -22,22 +30,55 @@ pub mod consts {
     pub const OCCLUSION: u32     = 0b00000000_00000000_00000000_00010000;
 }
 
-#[derive(Copy, Clone, Debug, ShaderType, PartialEq)]
-pub struct GpuQuad {
-    pub texture_id: u32,
-    pub bitfields: GpuQuadBitfields,
-    pub min: Vec2,
-    pub max: Vec2,
-    pub magnitude: i32,
+pub use shader_types::{GpuQuad, GpuQuadBitfields};
+
+// the `ShaderType` derive emits field checks next to the struct that are never called,
+// an allow on the struct itself doesn't reach them
+#[allow(dead_code)]
+mod shader_types {
+    use super::*;
+
+    #[derive(Copy, Clone, Debug, ShaderType, PartialEq)]
+    pub struct GpuQuad {
+        pub texture_id: u32,
+        pub bitfields: GpuQuadBitfields,
+        pub min: Vec2,
+        pub max: Vec2,
+        pub magnitude: i32,
+        /// The light level at each corner of the quad, 8 bits per corner laid out like a
+        /// [`LightLevel`](crate::topo::light::LightLevel).
+        /// See [`crate::render::meshing::lighting`] for how the corners are indexed.
+        pub light: u32,
+    }
+
+    #[derive(Copy, Clone, Debug, ShaderType, PartialEq, Eq)]
+    pub struct GpuQuadBitfields {
+        pub(super) value: u32,
+    }
+}
+
+impl GpuQuad {
+    /// The area of the quad in facespace, in blocks. Zero for degenerate quads.
+    pub fn area(&self) -> f32 {
+        let size = (self.max - self.min).max(Vec2::ZERO);
+        size.x * size.y
+    }
+
+    /// Whether the quad covers no area (or a negative one), or has coordinates that aren't finite.
+    /// Degenerate quads can't be rendered and should never be emitted by meshers.
+    pub fn is_degenerate(&self) -> bool {
+        !(self.min.is_finite() && self.max.is_finite()) || self.max.cmple(self.min).any()
+    }
 }
 
-#[derive(Copy, Clone, Debug, ShaderType, PartialEq, Eq)]
-pub struct GpuQuadBitfields {
-    value: u32,
+impl Default for GpuQuadBitfields {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl GpuQuadBitfields {
-    pub const ROTATION_MASK: u32 = 0b11 << 0;
+    pub const ROTATION_MASK: u32 = 0b11;
     pub const ROTATION_SHIFT: u32 = 0;
     pub const FACE_MASK: u32 = 0b111 << 2;
     pub const FACE_SHIFT: u32 = 2;
@@ -45,10 +86,36 @@ impl GpuQuadBitfields {
     pub const FLIP_UV_X_BIT: u32 = 5;
     pub const FLIP_UV_Y_BIT: u32 = 6;
 
+    /// How far each corner of the quad is pushed back along the face normal, in
+    /// 1/[`Self::CORNER_DEPTH_STEPS`] of a block. Used for the sloped surfaces of fluids, regular quads
+    /// have a depth of 0 at every corner. The corners are indexed like the corner light, see
+    /// [`crate::render::meshing::lighting`].
+    pub const CORNER_DEPTH_SHIFT: u32 = 7;
+    pub const CORNER_DEPTH_BITS: u32 = 5;
+    pub const CORNER_DEPTH_MASK: u32 = (1 << Self::CORNER_DEPTH_BITS) - 1;
+    pub const CORNER_DEPTH_STEPS: u32 = 16;
+
     pub fn new() -> Self {
         Self { value: 0 }
     }
 
+    /// Shader definitions for the bitfield layout, so shaders can decode the bitfields
+    /// using the same constants as we use to encode them.
+    pub fn shader_defs() -> Vec<ShaderDefVal> {
+        vec![
+            u32_shader_def("ROTATION_MASK", Self::ROTATION_MASK),
+            u32_shader_def("ROTATION_SHIFT", Self::ROTATION_SHIFT),
+            u32_shader_def("FACE_MASK", Self::FACE_MASK),
+            u32_shader_def("FACE_SHIFT", Self::FACE_SHIFT),
+            u32_shader_def("FLIP_UV_X_BIT", Self::FLIP_UV_X_BIT),
+            u32_shader_def("FLIP_UV_Y_BIT", Self::FLIP_UV_Y_BIT),
+            u32_shader_def("CORNER_DEPTH_SHIFT", Self::CORNER_DEPTH_SHIFT),
+            u32_shader_def("CORNER_DEPTH_BITS", Self::CORNER_DEPTH_BITS),
+            u32_shader_def("CORNER_DEPTH_MASK", Self::CORNER_DEPTH_MASK),
+            u32_shader_def("CORNER_DEPTH_STEPS", Self::CORNER_DEPTH_STEPS),
+        ]
+    }
+
     pub fn get_face(self) -> Face {
         let raw = (self.value & Self::FACE_MASK) >> Self::FACE_SHIFT;
         FromPrimitive::from_u32(raw).unwrap()
@@ -77,6 +144,26 @@ impl GpuQuadBitfields {
         }
         self
     }
+
+    /// Set the depth of every corner in blocks, clamped between 0 and 1 and rounded to the
+    /// nearest step. See [`GpuQuadBitfields::CORNER_DEPTH_SHIFT`].
+    pub fn with_corner_depths(mut self, depths: [f32; 4]) -> Self {
+        for (corner, depth) in depths.into_iter().enumerate() {
+            let steps = (depth.clamp(0.0, 1.0) * Self::CORNER_DEPTH_STEPS as f32).round() as u32;
+            let shift = Self::CORNER_DEPTH_SHIFT + corner as u32 * Self::CORNER_DEPTH_BITS;
+
+            self.value &= !(Self::CORNER_DEPTH_MASK << shift);
+            self.value |= steps << shift;
+        }
+        self
+    }
+
+    /// The depth of a corner in blocks, see [`GpuQuadBitfields::with_corner_depths`].
+    pub fn get_corner_depth(self, corner: usize) -> f32 {
+        let shift = Self::CORNER_DEPTH_SHIFT + corner as u32 * Self::CORNER_DEPTH_BITS;
+        let steps = (self.value >> shift) & Self::CORNER_DEPTH_MASK;
+        steps as f32 / Self::CORNER_DEPTH_STEPS as f32
+    }
 }
 
 #[derive(Clone)]
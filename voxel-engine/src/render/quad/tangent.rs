@@ -0,0 +1,105 @@
+use bevy::math::{Mat3, Vec3};
+
+use crate::{
+    data::{texture::FaceTextureRotation, tile::Face},
+    util::Axis3D,
+};
+
+/// The world space tangent basis of a quad, derived from its face and texture rotation.
+/// Used to transform tangent space normals (from normal maps) into world space.
+/// This must match `quad_world_normal` in the shaders.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TangentBasis {
+    pub tangent: Vec3,
+    pub bitangent: Vec3,
+    pub normal: Vec3,
+}
+
+impl TangentBasis {
+    pub fn new(face: Face, rotation: FaceTextureRotation) -> Self {
+        let normal = face.normal().as_vec3();
+        let tangent = rotation_around_axis(rotation, face.axis()) * face.tangent().as_vec3();
+
+        Self {
+            tangent,
+            bitangent: normal.cross(tangent),
+            normal,
+        }
+    }
+
+    /// Transform a tangent space normal into world space.
+    pub fn to_world(&self, tangent_space_normal: Vec3) -> Vec3 {
+        let n = tangent_space_normal;
+        (n.x * self.tangent + n.y * self.bitangent + n.z * self.normal).normalize()
+    }
+}
+
+/// The texture rotation matrix around the given axis, same as `tex_rotation_matrix_around_axis` in the shaders.
+fn rotation_around_axis(rotation: FaceTextureRotation, axis: Axis3D) -> Mat3 {
+    let (sin, cos) = rotation.radians().sin_cos();
+
+    // WGSL matrices are constructed column by column, just like these
+    #[rustfmt::skip]
+    let cols = match axis {
+        Axis3D::X => [
+            1.0, 0.0, 0.0,
+            0.0, cos, -sin,
+            0.0, sin, cos,
+        ],
+        Axis3D::Y => [
+            cos, 0.0, -sin,
+            0.0, 1.0, 0.0,
+            sin, 0.0, cos,
+        ],
+        Axis3D::Z => [
+            cos, -sin, 0.0,
+            sin, cos, 0.0,
+            0.0, 0.0, 1.0,
+        ],
+    };
+
+    Mat3::from_cols_array(&cols)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 0.0001;
+
+    #[test]
+    fn flat_normal_matches_face() {
+        for face in Face::FACES {
+            for rotation in 0..FaceTextureRotation::TOTAL_ROTATIONS {
+                let basis = TangentBasis::new(face, FaceTextureRotation::new(rotation));
+
+                // a flat normal map should give us the normal of the face, no matter the rotation
+                let normal = basis.to_world(Vec3::Z);
+                assert!(normal.abs_diff_eq(face.normal().as_vec3(), EPSILON));
+
+                assert!(basis.tangent.dot(basis.normal).abs() < EPSILON);
+                assert!(basis.bitangent.dot(basis.normal).abs() < EPSILON);
+                assert!((basis.tangent.length() - 1.0).abs() < EPSILON);
+            }
+        }
+    }
+
+    #[test]
+    fn rotated_tangent() {
+        let basis = TangentBasis::new(Face::Top, FaceTextureRotation::new(0));
+        assert!(basis.tangent.abs_diff_eq(Vec3::X, EPSILON));
+
+        // rotating the texture rotates the tangent around the face's normal
+        let rotated = TangentBasis::new(Face::Top, FaceTextureRotation::new(1));
+        assert!(rotated.tangent.abs_diff_eq(Vec3::NEG_Z, EPSILON));
+
+        // a normal tilted along the tangent should follow the texture rotation
+        let tilted = Vec3::new(1.0, 0.0, 1.0).normalize();
+        assert!(basis
+            .to_world(tilted)
+            .abs_diff_eq(Vec3::new(1.0, 1.0, 0.0).normalize(), EPSILON));
+        assert!(rotated
+            .to_world(tilted)
+            .abs_diff_eq(Vec3::new(0.0, 1.0, -1.0).normalize(), EPSILON));
+    }
+}
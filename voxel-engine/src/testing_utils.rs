@@ -1,9 +1,21 @@
-use crate::topo::{
-    block::BlockVoxel,
-    storage::containers::data_storage::SyncIndexedChunkContainer,
-    world::{Crra, Crwa},
+use crate::{
+    data::registries::{block::BlockVariantRegistry, texture::TextureRegistry, Registries},
+    topo::{
+        block::BlockVoxel,
+        storage::containers::data_storage::SyncIndexedChunkContainer,
+        world::{Crra, Crwa},
+    },
 };
 
+/// Registries with the mock texture and block variant registries.
+pub fn mock_registries() -> Registries {
+    let registries = Registries::new();
+    let texreg = TextureRegistry::new_mock();
+    registries.add_registry(BlockVariantRegistry::new_mock(&texreg));
+    registries.add_registry(texreg);
+    registries
+}
+
 pub struct MockChunk {
     pub variants: SyncIndexedChunkContainer<BlockVoxel>,
 }
@@ -17,7 +29,8 @@ impl MockChunk {
 
     pub fn access(&self) -> Crwa<'_> {
         Crwa {
-            wrote_to_edge: None,
+            touched_neighbors: None,
+            edits: None,
             block_variants: self.variants.access(),
         }
     }
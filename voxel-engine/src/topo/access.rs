@@ -3,6 +3,7 @@ use std::error::Error;
 
 use super::{
     bounding_box::BoundingBox,
+    storage::error::OutOfBounds,
     world::{chunk_ref::ChunkAccessOutput, Chunk},
 };
 
@@ -45,3 +46,212 @@ impl<T: ChunkBounds> HasBounds for T {
         Chunk::BOUNDING_BOX
     }
 }
+
+/// A [`WriteAccess`] wrapper that keeps track of the region that was written to, so that
+/// only the affected region of a chunk needs to be remeshed.
+pub struct TrackedWriteAccess<A> {
+    inner: A,
+    min: IVec3,
+    max: IVec3,
+}
+
+impl<A> TrackedWriteAccess<A> {
+    pub fn new(inner: A) -> Self {
+        Self {
+            inner,
+            min: IVec3::MAX,
+            max: IVec3::MIN,
+        }
+    }
+
+    /// The smallest bounding box containing every position that was successfully written to,
+    /// or `None` if nothing was written.
+    pub fn touched(&self) -> Option<BoundingBox> {
+        if self.min.cmpgt(self.max).any() {
+            return None;
+        }
+
+        Some(BoundingBox::from_min_max(self.min, self.max + IVec3::ONE))
+    }
+
+    pub fn inner(&self) -> &A {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> A {
+        self.inner
+    }
+}
+
+impl<A: WriteAccess> WriteAccess for TrackedWriteAccess<A> {
+    type WriteType = A::WriteType;
+    type WriteErr = A::WriteErr;
+
+    fn set(&mut self, pos: IVec3, data: Self::WriteType) -> Result<(), Self::WriteErr> {
+        self.inner.set(pos, data)?;
+
+        self.min = self.min.min(pos);
+        self.max = self.max.max(pos);
+
+        Ok(())
+    }
+}
+
+impl<A: ReadAccess> ReadAccess for TrackedWriteAccess<A> {
+    type ReadType<'a> = A::ReadType<'a> where Self: 'a;
+    type ReadErr = A::ReadErr;
+
+    fn get(&self, pos: IVec3) -> Result<Self::ReadType<'_>, Self::ReadErr> {
+        self.inner.get(pos)
+    }
+}
+
+impl<A: ChunkBounds> ChunkBounds for TrackedWriteAccess<A> {}
+
+/// A chunk-sized voxel volume backed by a hashmap, for building ad-hoc volumes in tests and tooling
+/// without setting up a full chunk. Positions that were never written to read as the default value.
+#[derive(Clone, Debug)]
+pub struct MapAccess<T> {
+    map: hb::HashMap<IVec3, T>,
+    default: T,
+}
+
+impl<T> MapAccess<T> {
+    pub fn new(default: T) -> Self {
+        Self {
+            map: hb::HashMap::new(),
+            default,
+        }
+    }
+
+    /// The number of positions that were written to.
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Iterate over the positions that were written to, and their values.
+    pub fn iter(&self) -> impl Iterator<Item = (IVec3, &T)> + '_ {
+        self.map.iter().map(|(&pos, value)| (pos, value))
+    }
+}
+
+impl<T: Default> Default for MapAccess<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T> WriteAccess for MapAccess<T> {
+    type WriteType = T;
+    type WriteErr = OutOfBounds;
+
+    fn set(&mut self, pos: IVec3, data: Self::WriteType) -> Result<(), Self::WriteErr> {
+        if !self.bounds().contains(pos) {
+            return Err(OutOfBounds);
+        }
+
+        self.map.insert(pos, data);
+        Ok(())
+    }
+}
+
+impl<T> ReadAccess for MapAccess<T> {
+    type ReadType<'a>
+        = &'a T
+    where
+        Self: 'a;
+    type ReadErr = OutOfBounds;
+
+    fn get(&self, pos: IVec3) -> Result<Self::ReadType<'_>, Self::ReadErr> {
+        if !self.bounds().contains(pos) {
+            return Err(OutOfBounds);
+        }
+
+        Ok(self.map.get(&pos).unwrap_or(&self.default))
+    }
+}
+
+impl<T> ChunkBounds for MapAccess<T> {}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        data::registries::block::BlockVariantRegistry,
+        testing_utils::MockChunk,
+        topo::{
+            block::BlockVoxel,
+            world::{CaoBlock, ChunkAccessInput},
+        },
+    };
+
+    use super::*;
+
+    #[test]
+    fn tracked_write_access() {
+        let chunk = MockChunk::new(BlockVoxel::new_full(BlockVariantRegistry::VOID));
+        let mut access = TrackedWriteAccess::new(chunk.access());
+
+        assert_eq!(None, access.touched());
+
+        let block = || ChunkAccessInput::new(BlockVoxel::new_full(BlockVariantRegistry::FULL));
+
+        access.set(IVec3::new(3, 8, 2), block()).unwrap();
+        assert_eq!(
+            Some(BoundingBox::new(IVec3::new(3, 8, 2), IVec3::new(4, 9, 3))),
+            access.touched()
+        );
+
+        access.set(IVec3::new(10, 1, 5), block()).unwrap();
+        access.set(IVec3::new(6, 12, 0), block()).unwrap();
+
+        // out of bounds writes fail, so they don't touch anything
+        assert!(access.set(IVec3::new(16, 20, 3), block()).is_err());
+
+        let touched = access.touched().unwrap();
+        assert_eq!(IVec3::new(3, 1, 0), touched.min());
+        assert_eq!(IVec3::new(11, 13, 6), touched.max());
+
+        let CaoBlock::Full(written) = access.get(IVec3::new(10, 1, 5)).unwrap().block else {
+            panic!("expected a full block");
+        };
+        assert_eq!(BlockVariantRegistry::FULL, written.id);
+    }
+
+    #[test]
+    fn map_access_round_trip() {
+        let mut access = MapAccess::new(BlockVariantRegistry::VOID);
+
+        access
+            .set(IVec3::new(1, 2, 3), BlockVariantRegistry::FULL)
+            .unwrap();
+        access
+            .set(IVec3::new(15, 0, 15), BlockVariantRegistry::LAMP)
+            .unwrap();
+
+        assert_eq!(
+            &BlockVariantRegistry::FULL,
+            access.get(IVec3::new(1, 2, 3)).unwrap()
+        );
+        assert_eq!(
+            &BlockVariantRegistry::LAMP,
+            access.get(IVec3::new(15, 0, 15)).unwrap()
+        );
+        assert_eq!(2, access.len());
+
+        // unset positions read as the default
+        assert_eq!(
+            &BlockVariantRegistry::VOID,
+            access.get(IVec3::new(8, 8, 8)).unwrap()
+        );
+
+        assert!(access.get(IVec3::new(16, 0, 0)).is_err());
+        assert!(access
+            .set(IVec3::new(-1, 0, 0), BlockVariantRegistry::FULL)
+            .is_err());
+        assert_eq!(2, access.len());
+    }
+}
@@ -14,6 +14,8 @@ use crate::{
 
 use super::storage::error::OutOfBounds;
 
+// subdivided blocks are kept inline so building and comparing voxels never allocates
+#[allow(clippy::large_enum_variant)]
 #[derive(Clone, PartialEq, Eq, Hash)]
 pub enum BlockVoxel {
     Full(FullBlock),
@@ -45,6 +47,11 @@ impl FullBlock {
     pub fn new(id: <BlockVariantRegistry as Registry>::Id) -> Self {
         Self { rotation: None, id }
     }
+
+    pub fn with_rotation(mut self, rotation: BlockModelRotation) -> Self {
+        self.rotation = Some(rotation);
+        self
+    }
 }
 
 #[derive(Clone, Hash, PartialEq, Eq)]
@@ -64,6 +71,11 @@ impl Microblock {
         Self { rotation: None, id }
     }
 
+    pub fn with_rotation(mut self, rotation: BlockModelRotation) -> Self {
+        self.rotation = Some(rotation);
+        self
+    }
+
     pub fn as_full_block(&self) -> FullBlock {
         FullBlock {
             rotation: self.rotation,
@@ -48,6 +48,33 @@ impl BoundingBox {
         pos.cmpge(self.min).all() && pos.cmple(self.max).all()
     }
 
+    /// Whether this bounding box and `other` overlap. Boxes that only touch at their edges don't intersect.
+    pub fn intersects(self, other: Self) -> bool {
+        self.min.cmplt(other.max).all() && other.min.cmplt(self.max).all()
+    }
+
+    /// The region where this bounding box and `other` overlap, or `None` if they don't intersect.
+    pub fn intersection(self, other: Self) -> Option<Self> {
+        if !self.intersects(other) {
+            return None;
+        }
+
+        Some(Self {
+            min: self.min.max(other.min),
+            max: self.max.min(other.max),
+        })
+    }
+
+    /// Pin `pos` to the nearest position contained in this bounding box.
+    /// Panics if the bounding box is empty.
+    pub fn clamp(self, pos: IVec3) -> IVec3 {
+        if self.min.cmpge(self.max).any() {
+            panic!("Tried to clamp to an empty {}", type_name::<Self>())
+        }
+
+        pos.clamp(self.min, self.max - IVec3::ONE)
+    }
+
     pub fn to_aabb(self) -> Aabb {
         Aabb::from_min_max(self.min.as_vec3(), self.max.as_vec3())
     }
@@ -63,8 +90,71 @@ impl BoundingBox {
         let [x, y, z] = self.span().max.to_array();
         (x * y * z).unsigned_abs()
     }
+
+    /// Iterate over every position contained in this bounding box. Positions are yielded in order of
+    /// their X component, then their Y component, then their Z component.
+    pub fn cartesian_iter(self) -> CartesianIter {
+        CartesianIter {
+            min: self.min,
+            dims: self.max - self.min,
+            front: 0,
+            back: self.volume(),
+        }
+    }
+}
+
+/// Iterator over all positions in a [`BoundingBox`], see [`BoundingBox::cartesian_iter`].
+#[derive(Clone, Debug)]
+pub struct CartesianIter {
+    min: IVec3,
+    dims: IVec3,
+    front: u32,
+    back: u32,
+}
+
+impl CartesianIter {
+    fn pos(&self, idx: u32) -> IVec3 {
+        let idx = idx as i32;
+        let z = idx % self.dims.z;
+        let y = (idx / self.dims.z) % self.dims.y;
+        let x = idx / (self.dims.z * self.dims.y);
+
+        self.min + IVec3::new(x, y, z)
+    }
+}
+
+impl Iterator for CartesianIter {
+    type Item = IVec3;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.front >= self.back {
+            return None;
+        }
+
+        let pos = self.pos(self.front);
+        self.front += 1;
+        Some(pos)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = (self.back - self.front) as usize;
+        (len, Some(len))
+    }
 }
 
+impl DoubleEndedIterator for CartesianIter {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.front >= self.back {
+            return None;
+        }
+
+        self.back -= 1;
+        Some(self.pos(self.back))
+    }
+}
+
+impl ExactSizeIterator for CartesianIter {}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -79,4 +169,69 @@ mod tests {
         assert!(!bb.contains(IVec3::splat(16)));
         assert!(bb.contains_inclusive(IVec3::splat(16)));
     }
+
+    #[test]
+    fn bounding_box_intersection() {
+        let a = BoundingBox::new(IVec3::splat(0), IVec3::splat(10));
+        let b = BoundingBox::new(IVec3::splat(5), IVec3::splat(15));
+
+        assert!(a.intersects(b));
+        assert!(b.intersects(a));
+        assert_eq!(
+            Some(BoundingBox::new(IVec3::splat(5), IVec3::splat(10))),
+            a.intersection(b)
+        );
+
+        // a box inside another box
+        let inner = BoundingBox::new(IVec3::splat(2), IVec3::splat(4));
+        assert_eq!(Some(inner), a.intersection(inner));
+
+        // disjoint boxes
+        let c = BoundingBox::new(IVec3::new(20, 0, 0), IVec3::new(30, 10, 10));
+        assert!(!a.intersects(c));
+        assert_eq!(None, a.intersection(c));
+
+        // boxes that only touch don't intersect
+        let d = BoundingBox::new(IVec3::new(10, 0, 0), IVec3::new(20, 10, 10));
+        assert!(!a.intersects(d));
+        assert_eq!(None, a.intersection(d));
+    }
+
+    #[test]
+    fn bounding_box_clamp() {
+        let bb = Chunk::BOUNDING_BOX;
+
+        assert_eq!(IVec3::new(4, 5, 6), bb.clamp(IVec3::new(4, 5, 6)));
+        assert_eq!(IVec3::new(0, 15, 3), bb.clamp(IVec3::new(-4, 16, 3)));
+        assert_eq!(IVec3::splat(15), bb.clamp(IVec3::splat(100)));
+        assert!(bb.contains(bb.clamp(IVec3::new(-100, 7, 100))));
+    }
+
+    #[test]
+    fn bounding_box_cartesian_iter() {
+        let bb = BoundingBox::new(IVec3::new(-1, 2, 0), IVec3::new(2, 4, 4));
+        let iter = bb.cartesian_iter();
+        assert_eq!(bb.volume() as usize, iter.len());
+
+        let forward = iter.clone().collect::<Vec<_>>();
+        let mut reversed = iter.rev().collect::<Vec<_>>();
+        reversed.reverse();
+
+        assert_eq!(forward, reversed);
+        assert_eq!(IVec3::new(-1, 2, 0), forward[0]);
+        assert_eq!(IVec3::new(-1, 2, 1), forward[1]);
+        assert_eq!(IVec3::new(1, 3, 3), *forward.last().unwrap());
+        assert!(forward.iter().all(|&pos| bb.contains(pos)));
+
+        // iterating from both ends at once shouldn't yield any position twice
+        let mut iter = bb.cartesian_iter();
+        iter.next();
+        iter.next_back();
+        assert_eq!(bb.volume() as usize - 2, iter.len());
+        assert_eq!(bb.volume() as usize - 2, iter.count());
+
+        let empty = BoundingBox::new(IVec3::ZERO, IVec3::new(0, 5, 5));
+        assert_eq!(0, empty.cartesian_iter().len());
+        assert_eq!(None, empty.cartesian_iter().next());
+    }
 }
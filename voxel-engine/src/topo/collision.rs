@@ -0,0 +1,459 @@
+use bevy::{
+    math::{ivec3, IVec3, Vec3},
+    render::primitives::Aabb,
+};
+
+use crate::data::{
+    registries::{
+        block::{BlockVariantId, BlockVariantRegistry},
+        Registry,
+    },
+    tile::Face,
+};
+
+use super::{
+    block::SubdividedBlock,
+    bounding_box::BoundingBox,
+    world::{CaoBlock, Chunk},
+};
+
+/// The furthest a box can move in one [sweep](crate::topo::world::ChunkManager::sweep), in blocks.
+pub const MAX_SWEEP_DISTANCE: f32 = 16.0 * Chunk::SIZE as f32;
+
+/// The furthest a box moves in a single step of a sweep, in blocks. The solid voxels are only collected around
+/// each step, so the size of the region doesn't depend on how far the box moves.
+pub const SWEEP_STEP: f32 = Chunk::SIZE as f32;
+
+/// The solid microblocks in a region of the world. Used to build the collision boxes of the region.
+/// Positions are in microblock-space, relative to the minimum corner of the region.
+pub(crate) struct SolidGrid {
+    /// The minimum corner of the region in worldspace (in blocks).
+    origin: IVec3,
+    dims: IVec3,
+    solid: Vec<bool>,
+}
+
+impl SolidGrid {
+    /// Create an empty grid covering the given region (in blocks).
+    pub fn new(region: BoundingBox) -> Self {
+        let dims = (region.max() - region.min()) * SubdividedBlock::SUBDIVISIONS;
+
+        Self {
+            origin: region.min(),
+            dims,
+            solid: vec![false; (dims.x * dims.y * dims.z) as usize],
+        }
+    }
+
+    fn index(&self, pos: IVec3) -> Option<usize> {
+        if pos.cmplt(IVec3::ZERO).any() || pos.cmpge(self.dims).any() {
+            return None;
+        }
+
+        Some((pos.z * self.dims.y * self.dims.x + pos.y * self.dims.x + pos.x) as usize)
+    }
+
+    fn is_solid(&self, pos: IVec3) -> bool {
+        self.index(pos).is_some_and(|idx| self.solid[idx])
+    }
+
+    fn set(&mut self, pos: IVec3, solid: bool) {
+        if let Some(idx) = self.index(pos) {
+            self.solid[idx] = solid;
+        }
+    }
+
+    /// Insert the block at the given worldspace position. Blocks with a model (other than fluids) are solid,
+    /// no matter how transparent they are, and subdivided blocks are solid where their microblocks have a model.
+    pub fn insert_block(&mut self, pos: IVec3, block: CaoBlock, registry: &BlockVariantRegistry) {
+        const SUBDIVS: i32 = SubdividedBlock::SUBDIVISIONS;
+
+        let mb_min = (pos - self.origin) * SUBDIVS;
+        let microblocks = BoundingBox::from_min_max(IVec3::ZERO, IVec3::splat(SUBDIVS));
+
+        match block {
+            CaoBlock::Full(full) => {
+                if !is_solid(full.id, registry) {
+                    return;
+                }
+
+                for offset in microblocks.cartesian_iter() {
+                    self.set(mb_min + offset, true);
+                }
+            }
+            CaoBlock::Subdivided(subdiv) => {
+                for offset in microblocks.cartesian_iter() {
+                    let id = subdiv.get(offset.as_uvec3()).unwrap().id;
+
+                    if is_solid(id, registry) {
+                        self.set(mb_min + offset, true);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Greedily merge the solid microblocks into as few boxes as possible. Boxes are first extended along
+    /// the X axis, then the Y axis, then the Z axis. The returned boxes are in worldspace.
+    pub fn merge(mut self) -> Vec<Aabb> {
+        let mut boxes = Vec::new();
+
+        for z in 0..self.dims.z {
+            for y in 0..self.dims.y {
+                for x in 0..self.dims.x {
+                    let min = ivec3(x, y, z);
+                    if !self.is_solid(min) {
+                        continue;
+                    }
+
+                    let mut max = min + IVec3::ONE;
+
+                    while self.is_solid(ivec3(max.x, min.y, min.z)) {
+                        max.x += 1;
+                    }
+
+                    while self.all_solid(
+                        ivec3(min.x, max.y, min.z),
+                        ivec3(max.x, max.y + 1, min.z + 1),
+                    ) {
+                        max.y += 1;
+                    }
+
+                    while self.all_solid(ivec3(min.x, min.y, max.z), ivec3(max.x, max.y, max.z + 1))
+                    {
+                        max.z += 1;
+                    }
+
+                    for pos in BoundingBox::from_min_max(min, max).cartesian_iter() {
+                        self.set(pos, false);
+                    }
+
+                    boxes.push(self.to_worldspace(min, max));
+                }
+            }
+        }
+
+        boxes
+    }
+
+    fn all_solid(&self, min: IVec3, max: IVec3) -> bool {
+        BoundingBox::from_min_max(min, max)
+            .cartesian_iter()
+            .all(|pos| self.is_solid(pos))
+    }
+
+    fn to_worldspace(&self, min: IVec3, max: IVec3) -> Aabb {
+        const SUBDIVS_F32: f32 = SubdividedBlock::SUBDIVISIONS as f32;
+
+        let origin = self.origin.as_vec3();
+        Aabb::from_min_max(
+            origin + min.as_vec3() / SUBDIVS_F32,
+            origin + max.as_vec3() / SUBDIVS_F32,
+        )
+    }
+}
+
+/// Whether the variant collides. Air doesn't, and neither do variants without a model since there's nothing
+/// there to collide with. Fluids can be moved through, so they don't collide either.
+fn is_solid(id: BlockVariantId, registry: &BlockVariantRegistry) -> bool {
+    let entry = registry.get_by_id(id);
+    !registry.is_air(id) && entry.model.is_some() && entry.options.fluid_level.is_none()
+}
+
+/// The result of sweeping a box through the world, see [`sweep_aabb`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SweepResult {
+    /// How far along its velocity the box moved before it hit something, between 0 and 1.
+    /// This is 1 if the box didn't hit anything.
+    pub time: f32,
+    /// The face of the obstacle that the box hit, or `None` if the box didn't hit anything.
+    pub normal: Option<Face>,
+    /// The center of the box at the point where it stopped.
+    pub position: Vec3,
+}
+
+impl SweepResult {
+    pub fn hit(&self) -> bool {
+        self.normal.is_some()
+    }
+}
+
+/// The time of impact and the hit face of a box moving with `velocity` into `obstacle`.
+/// Boxes that are already overlapping the obstacle don't collide with it, so that they can move out of it.
+fn time_of_impact(moving: &Aabb, velocity: Vec3, obstacle: &Aabb) -> Option<(f32, Face)> {
+    // the obstacle grown by the size of the moving box, so that we can treat the moving box as a point
+    let min = Vec3::from(obstacle.min() - moving.half_extents);
+    let max = Vec3::from(obstacle.max() + moving.half_extents);
+    let center = Vec3::from(moving.center);
+
+    let mut entry = f32::NEG_INFINITY;
+    let mut exit = f32::INFINITY;
+    let mut normal = None;
+
+    for axis in 0..3 {
+        if velocity[axis] == 0.0 {
+            if center[axis] <= min[axis] || center[axis] >= max[axis] {
+                return None;
+            }
+
+            continue;
+        }
+
+        let (near, far) = if velocity[axis] > 0.0 {
+            (min[axis], max[axis])
+        } else {
+            (max[axis], min[axis])
+        };
+
+        let axis_entry = (near - center[axis]) / velocity[axis];
+        let axis_exit = (far - center[axis]) / velocity[axis];
+
+        if axis_entry > entry {
+            entry = axis_entry;
+
+            let mut normal_vec = IVec3::ZERO;
+            normal_vec[axis] = -velocity[axis].signum() as i32;
+            normal = Face::from_normal(normal_vec);
+        }
+
+        exit = exit.min(axis_exit);
+    }
+
+    if entry > exit || !(0.0..=1.0).contains(&entry) {
+        return None;
+    }
+
+    normal.map(|normal| (entry, normal))
+}
+
+/// Move a box with the given velocity through the obstacles, stopping at the first obstacle it hits.
+pub fn sweep_aabb(moving: Aabb, velocity: Vec3, obstacles: &[Aabb]) -> SweepResult {
+    let mut result = SweepResult {
+        time: 1.0,
+        normal: None,
+        position: Vec3::from(moving.center) + velocity,
+    };
+
+    for obstacle in obstacles {
+        let Some((time, normal)) = time_of_impact(&moving, velocity, obstacle) else {
+            continue;
+        };
+
+        if time < result.time || result.normal.is_none() && time == result.time {
+            result = SweepResult {
+                time,
+                normal: Some(normal),
+                position: Vec3::from(moving.center) + velocity * time,
+            };
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::{uvec3, Vec3};
+
+    use crate::{
+        data::registries::texture::TextureRegistry,
+        topo::{
+            access::WriteAccess,
+            block::{BlockVoxel, Microblock},
+            world::{ChunkAccessInput, ChunkManager, ChunkPos},
+        },
+    };
+
+    use super::*;
+
+    fn chunk_manager(blocks: &[(IVec3, BlockVoxel)]) -> ChunkManager {
+        let cm = ChunkManager::new_test();
+        cm.insert_test_chunk(ChunkPos::ZERO, |access| {
+            for (pos, block) in blocks {
+                access
+                    .set(*pos, ChunkAccessInput::new(block.clone()))
+                    .unwrap();
+            }
+        });
+
+        cm
+    }
+
+    #[test]
+    fn merge_solid_blocks() {
+        let texreg = TextureRegistry::new_mock();
+        let registry = BlockVariantRegistry::new_mock(&texreg);
+
+        let full = BlockVoxel::new_full(BlockVariantRegistry::FULL);
+        let blocks = BoundingBox::from_min_max(IVec3::ONE, IVec3::splat(3))
+            .cartesian_iter()
+            .map(|pos| (pos, full.clone()))
+            .collect::<Vec<_>>();
+
+        let cm = chunk_manager(&blocks);
+
+        // a 2x2x2 cube of solid blocks is merged into a single box
+        let region = BoundingBox::from_min_max(IVec3::ZERO, IVec3::splat(4));
+        assert_eq!(
+            vec![Aabb::from_min_max(Vec3::ONE, Vec3::splat(3.0))],
+            cm.collision_aabbs_in(region, &registry)
+        );
+
+        // air doesn't collide
+        let region = BoundingBox::from_min_max(ivec3(4, 4, 4), ivec3(8, 8, 8));
+        assert!(cm.collision_aabbs_in(region, &registry).is_empty());
+
+        // boxes are clipped to the region
+        let region = BoundingBox::from_min_max(ivec3(2, 2, 2), ivec3(8, 8, 8));
+        assert_eq!(
+            vec![Aabb::from_min_max(Vec3::splat(2.0), Vec3::splat(3.0))],
+            cm.collision_aabbs_in(region, &registry)
+        );
+    }
+
+    #[test]
+    fn transparent_blocks_are_solid() {
+        let texreg = TextureRegistry::new_mock();
+        let registry = BlockVariantRegistry::new_mock(&texreg);
+
+        let cm = chunk_manager(&[
+            (
+                IVec3::ZERO,
+                BlockVoxel::new_full(BlockVariantRegistry::GLASS),
+            ),
+            (IVec3::X, BlockVoxel::new_full(BlockVariantRegistry::LAMP)),
+        ]);
+
+        let region = BoundingBox::from_min_max(IVec3::ZERO, IVec3::splat(2));
+        assert_eq!(
+            vec![Aabb::from_min_max(Vec3::ZERO, Vec3::new(2.0, 1.0, 1.0))],
+            cm.collision_aabbs_in(region, &registry)
+        );
+
+        // unless they're designated air
+        let registry = BlockVariantRegistry::new_mock(&texreg).with_air(BlockVariantRegistry::LAMP);
+        assert_eq!(
+            vec![Aabb::from_min_max(Vec3::ZERO, Vec3::ONE)],
+            cm.collision_aabbs_in(region, &registry)
+        );
+    }
+
+    #[test]
+    fn sweep_into_wall() {
+        let texreg = TextureRegistry::new_mock();
+        let registry = BlockVariantRegistry::new_mock(&texreg);
+
+        // a wall at x = 8
+        let full = BlockVoxel::new_full(BlockVariantRegistry::FULL);
+        let blocks = BoundingBox::from_min_max(ivec3(8, 0, 0), ivec3(9, 16, 16))
+            .cartesian_iter()
+            .map(|pos| (pos, full.clone()))
+            .collect::<Vec<_>>();
+
+        let cm = chunk_manager(&blocks);
+
+        let aabb = Aabb::from_min_max(Vec3::new(2.0, 4.0, 4.0), Vec3::new(3.0, 6.0, 5.0));
+
+        let result = cm.sweep(aabb, Vec3::new(10.0, 0.0, 0.0), &registry);
+        assert!(result.hit());
+        assert_eq!(Some(Face::South), result.normal);
+        assert!((result.time - 0.5).abs() < 1e-5);
+        // the box ends up flush against the wall
+        assert!((result.position.x + 0.5 - 8.0).abs() < 1e-5);
+
+        // moving along the wall doesn't hit it
+        let result = cm.sweep(aabb, Vec3::new(0.0, 3.0, -4.0), &registry);
+        assert!(!result.hit());
+        assert_eq!(1.0, result.time);
+        assert_eq!(Vec3::new(2.5, 8.0, 0.5), result.position);
+
+        // moving away from the wall doesn't either
+        let result = cm.sweep(aabb, Vec3::new(-2.0, 0.0, 0.0), &registry);
+        assert!(!result.hit());
+        assert_eq!(Vec3::new(0.5, 5.0, 4.5), result.position);
+    }
+
+    #[test]
+    fn fast_sweeps_are_stepped_and_clamped() {
+        let texreg = TextureRegistry::new_mock();
+        let registry = BlockVariantRegistry::new_mock(&texreg);
+
+        // a wall at x = 40, two chunks away from the box
+        let cm = ChunkManager::new_test();
+        for chunk in [
+            ChunkPos::ZERO,
+            ChunkPos::new(1, 0, 0),
+            ChunkPos::new(2, 0, 0),
+        ] {
+            cm.insert_test_chunk(chunk, |access| {
+                if chunk == ChunkPos::new(2, 0, 0) {
+                    let full = BlockVoxel::new_full(BlockVariantRegistry::FULL);
+                    for pos in
+                        BoundingBox::from_min_max(ivec3(8, 0, 0), ivec3(9, 16, 16)).cartesian_iter()
+                    {
+                        access
+                            .set(pos, ChunkAccessInput::new(full.clone()))
+                            .unwrap();
+                    }
+                }
+            });
+        }
+
+        let aabb = Aabb::from_min_max(Vec3::new(2.0, 4.0, 4.0), Vec3::new(3.0, 6.0, 5.0));
+
+        // the wall is hit even though it's far outside the first step
+        let result = cm.sweep(aabb, Vec3::new(74.0, 0.0, 0.0), &registry);
+        assert_eq!(Some(Face::South), result.normal);
+        assert!((result.position.x + 0.5 - 40.0).abs() < 1e-4);
+        assert!((result.time - 0.5).abs() < 1e-5);
+
+        // huge velocities are clamped instead of covering the whole path with one region
+        let result = cm.sweep(aabb, Vec3::new(0.0, 0.0, -1e12), &registry);
+        assert!(!result.hit());
+        assert!((result.position.z - (4.5 - MAX_SWEEP_DISTANCE)).abs() < 1e-3);
+    }
+
+    #[test]
+    fn subdivided_blocks_collide_per_microblock() {
+        let texreg = TextureRegistry::new_mock();
+        let registry = BlockVariantRegistry::new_mock(&texreg);
+
+        // a slab covering the bottom half of the block
+        let mut slab = SubdividedBlock::new(Microblock::new(BlockVariantRegistry::VOID));
+        for pos in BoundingBox::from_min_max(IVec3::ZERO, ivec3(4, 2, 4)).cartesian_iter() {
+            slab.set(
+                pos.as_uvec3(),
+                Microblock::new(BlockVariantRegistry::SUBDIV),
+            )
+            .unwrap();
+        }
+
+        let cm = chunk_manager(&[(IVec3::ZERO, BlockVoxel::Subdivided(slab))]);
+
+        let region = BoundingBox::from_min_max(IVec3::ZERO, IVec3::ONE);
+        assert_eq!(
+            vec![Aabb::from_min_max(Vec3::ZERO, Vec3::new(1.0, 0.5, 1.0))],
+            cm.collision_aabbs_in(region, &registry)
+        );
+
+        // a single microblock
+        let mut single = SubdividedBlock::new(Microblock::new(BlockVariantRegistry::VOID));
+        single
+            .set(
+                uvec3(1, 2, 3),
+                Microblock::new(BlockVariantRegistry::SUBDIV),
+            )
+            .unwrap();
+
+        let cm = chunk_manager(&[(IVec3::ONE, BlockVoxel::Subdivided(single))]);
+
+        let region = BoundingBox::from_min_max(IVec3::ZERO, IVec3::splat(2));
+        assert_eq!(
+            vec![Aabb::from_min_max(
+                Vec3::new(1.25, 1.5, 1.75),
+                Vec3::new(1.5, 1.75, 2.0)
+            )],
+            cm.collision_aabbs_in(region, &registry)
+        );
+    }
+}
@@ -0,0 +1,228 @@
+use bevy::{
+    ecs::entity::{EntityHashMap, EntityHashSet},
+    prelude::*,
+};
+
+use crate::{
+    topo::{bounding_box::BoundingBox, world::ChunkPos},
+    util::{space::world_to_chunk, ChunkMap},
+};
+
+use super::{ChunkObserver, ChunkObserverCrossChunkBorderEvent};
+
+/// Marks an entity that should be tracked by the [`ChunkEntityIndex`]. Chunk observers are always tracked,
+/// so they don't need this.
+#[derive(Copy, Clone, Component, Debug, Default)]
+pub struct ChunkTracked;
+
+/// Spatial index of the chunks that tracked entities (chunk observers and entities with [`ChunkTracked`]) are in,
+/// so systems can look up the entities in a chunk without iterating over every entity.
+/// The chunk of an entity is the chunk containing the translation of its [`Transform`].
+#[derive(Resource, Default)]
+pub struct ChunkEntityIndex {
+    buckets: ChunkMap<EntityHashSet>,
+    chunks: EntityHashMap<ChunkPos>,
+}
+
+impl ChunkEntityIndex {
+    /// Put the entity in the bucket for `pos`, removing it from its previous bucket.
+    /// Returns the chunk the entity was previously in.
+    pub fn insert(&mut self, entity: Entity, pos: ChunkPos) -> Option<ChunkPos> {
+        let old = self.chunks.insert(entity, pos);
+
+        if old == Some(pos) {
+            return old;
+        }
+
+        if let Some(old) = old {
+            self.remove_from_bucket(entity, old);
+        }
+
+        self.buckets.entry(pos).or_default().insert(entity);
+
+        old
+    }
+
+    /// Remove the entity from the index, returning the chunk it was in.
+    pub fn remove(&mut self, entity: Entity) -> Option<ChunkPos> {
+        let old = self.chunks.remove(&entity)?;
+        self.remove_from_bucket(entity, old);
+        Some(old)
+    }
+
+    fn remove_from_bucket(&mut self, entity: Entity, pos: ChunkPos) {
+        if let Some(bucket) = self.buckets.get_mut(pos) {
+            bucket.remove(&entity);
+
+            if bucket.is_empty() {
+                self.buckets.remove(pos);
+            }
+        }
+    }
+
+    /// The chunk the entity is in, or `None` if the entity isn't tracked.
+    pub fn chunk_of(&self, entity: Entity) -> Option<ChunkPos> {
+        self.chunks.get(&entity).copied()
+    }
+
+    pub fn entities_in_chunk(&self, pos: ChunkPos) -> impl Iterator<Item = Entity> + '_ {
+        self.buckets.get(pos).into_iter().flatten().copied()
+    }
+
+    /// The entities in the chunks in `region`, which is in chunkspace.
+    pub fn entities_in_region(&self, region: BoundingBox) -> impl Iterator<Item = Entity> + '_ {
+        region
+            .cartesian_iter()
+            .flat_map(|pos| self.entities_in_chunk(ChunkPos::from(pos)))
+    }
+
+    /// The number of tracked entities.
+    pub fn len(&self) -> usize {
+        self.chunks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+}
+
+/// Keeps the [`ChunkEntityIndex`] up to date. Observers are moved between buckets by the border crossing
+/// events from `dispatch_move_events`, other tracked entities when their transform changes.
+#[allow(clippy::type_complexity)]
+pub fn update_chunk_entity_index(
+    mut index: ResMut<ChunkEntityIndex>,
+    mut border_events: EventReader<ChunkObserverCrossChunkBorderEvent>,
+    tracked: Query<
+        (Entity, &Transform),
+        (
+            With<ChunkTracked>,
+            Without<ChunkObserver>,
+            Changed<Transform>,
+        ),
+    >,
+    mut removed_observers: RemovedComponents<ChunkObserver>,
+    mut removed_tracked: RemovedComponents<ChunkTracked>,
+) {
+    for event in border_events.read() {
+        index.insert(event.entity, event.new_chunk);
+    }
+
+    for (entity, transform) in &tracked {
+        index.insert(entity, world_to_chunk(transform.translation));
+    }
+
+    for entity in removed_observers.read().chain(removed_tracked.read()) {
+        index.remove(entity);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::ivec3;
+
+    use crate::topo::controller::{observer_events::dispatch_move_events, ChunkObserverMoveEvent};
+
+    use super::*;
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.init_resource::<ChunkEntityIndex>()
+            .add_event::<ChunkObserverMoveEvent>()
+            .add_event::<ChunkObserverCrossChunkBorderEvent>()
+            .add_systems(
+                Update,
+                (dispatch_move_events, update_chunk_entity_index).chain(),
+            );
+
+        app
+    }
+
+    fn observer() -> ChunkObserver {
+        ChunkObserver {
+            horizontal_range: 1.0,
+            view_distance_above: 1.0,
+            view_distance_below: 1.0,
+        }
+    }
+
+    fn in_chunk(app: &App, pos: ChunkPos) -> Vec<Entity> {
+        let mut entities = app
+            .world
+            .resource::<ChunkEntityIndex>()
+            .entities_in_chunk(pos)
+            .collect::<Vec<_>>();
+        entities.sort();
+        entities
+    }
+
+    #[test]
+    fn moving_across_border_updates_bucket() {
+        let mut app = app();
+
+        let observer = app
+            .world
+            .spawn((observer(), Transform::from_xyz(15.5, 0.0, 0.0)))
+            .id();
+        let tracked = app
+            .world
+            .spawn((ChunkTracked, Transform::from_xyz(-0.5, 0.0, 0.0)))
+            .id();
+        app.update();
+
+        assert_eq!(vec![observer], in_chunk(&app, ChunkPos::new(0, 0, 0)));
+        assert_eq!(vec![tracked], in_chunk(&app, ChunkPos::new(-1, 0, 0)));
+
+        app.world
+            .entity_mut(observer)
+            .insert(Transform::from_xyz(16.5, 0.0, 0.0));
+        app.world
+            .entity_mut(tracked)
+            .insert(Transform::from_xyz(0.5, 0.0, 0.0));
+        app.update();
+
+        assert_eq!(vec![tracked], in_chunk(&app, ChunkPos::new(0, 0, 0)));
+        assert_eq!(vec![observer], in_chunk(&app, ChunkPos::new(1, 0, 0)));
+        assert!(in_chunk(&app, ChunkPos::new(-1, 0, 0)).is_empty());
+
+        app.world.despawn(tracked);
+        app.update();
+
+        let index = app.world.resource::<ChunkEntityIndex>();
+        assert_eq!(None, index.chunk_of(tracked));
+        assert_eq!(Some(ChunkPos::new(1, 0, 0)), index.chunk_of(observer));
+        assert_eq!(1, index.len());
+    }
+
+    #[test]
+    fn region_query() {
+        let mut index = ChunkEntityIndex::default();
+
+        let positions = [
+            ivec3(0, 0, 0),
+            ivec3(1, 1, 1),
+            ivec3(-1, 0, 0),
+            ivec3(2, 0, 0),
+            ivec3(1, -1, 1),
+        ];
+
+        let entities = positions
+            .iter()
+            .enumerate()
+            .map(|(i, &pos)| {
+                let entity = Entity::from_raw(i as u32);
+                index.insert(entity, ChunkPos::from(pos));
+                entity
+            })
+            .collect::<Vec<_>>();
+
+        let mut found = index
+            .entities_in_region(BoundingBox::new(ivec3(0, 0, 0), ivec3(2, 2, 2)))
+            .collect::<Vec<_>>();
+        found.sort();
+
+        let mut expected = vec![entities[0], entities[1]];
+        expected.sort();
+
+        assert_eq!(expected, found);
+    }
+}
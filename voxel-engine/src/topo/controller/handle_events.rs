@@ -1,33 +1,26 @@
 use std::time::Instant;
 
-use bevy::{
-    prelude::*,
-    render::{primitives::Aabb, view::NoFrustumCulling},
-};
-
-use hb::hash_map::Entry as HashbrownEntry;
+use bevy::{prelude::*, render::primitives::Aabb};
 
 use crate::{
     topo::{
-        controller::{ChunkPermitKey, LoadReasons},
-        world::{
-            chunk_manager::ChunkLoadResult, Chunk, ChunkEntity, ChunkManagerError, ChunkPos,
-            VoxelRealm,
-        },
-        worldgen::generator::GenerateChunk,
+        controller::ChunkPermitKey,
+        world::{chunk_manager::ChunkLoadResult, Chunk, ChunkEntity, ChunkPos, VoxelRealm},
     },
-    util::{ChunkMap, ChunkSet},
+    util::ChunkMap,
 };
 
 use super::{
-    ChunkEcsPermits, LoadChunkEvent, LoadedChunkEvent, MergeEvent, Permit, UnloadChunkEvent,
-    UnloadedChunkEvent, UpdatePermitEvent, WorldControllerSettings,
+    ChunkEcsPermits, LoadChunkEvent, LoadedChunkEvent, MergeEvent, Permit, PermitFlags,
+    UnloadChunkEvent, UnloadedChunkEvent, UpdatePermitEvent, WorldControllerSettings,
 };
 
 #[derive(Bundle)]
 pub struct ChunkEcsBundle {
     pub chunk_pos: ChunkPos,
     pub marker: ChunkEntity,
+    /// The visibility bounds of the chunk in its localspace, used for frustum culling. Chunk meshes have no
+    /// vertex positions, so bevy can't calculate this from the mesh and it has to be inserted on spawn.
     pub aabb: Aabb,
     pub spatial: SpatialBundle,
 }
@@ -55,7 +48,7 @@ pub fn handle_permit_updates(
 ) {
     let then = Instant::now();
 
-    let has_events = permit_events.len() > 0;
+    let has_events = !permit_events.is_empty();
 
     let mut permit_updates = ChunkMap::<UpdatePermitEvent>::with_capacity(permit_events.len());
 
@@ -98,9 +91,9 @@ pub fn handle_permit_updates(
 
         // Remove the permit (and remove the ECS chunk) if the flags ended up being empty.
         if permit.flags.is_empty() {
-            permits
-                .remove(ChunkPermitKey::Chunk(event.chunk_pos))
-                .map(|entry| cmds.entity(entry.entity).despawn());
+            if let Some(entry) = permits.remove(ChunkPermitKey::Chunk(event.chunk_pos)) {
+                cmds.entity(entry.entity).despawn()
+            }
         }
     }
 
@@ -146,6 +139,7 @@ fn update_backlogs<E: Event + Clone + MergeEvent>(
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn handle_chunk_loads_and_unloads(
     // Prelude
     realm: VoxelRealm,
@@ -175,13 +169,10 @@ pub fn handle_chunk_loads_and_unloads(
 
     // If we've stalled for more than our allowed time, we have to load the chunks ASAP, so we force
     // the global lock.
-    let overtime = match *latest_cycle {
-        Some(latest) if now - latest > max_stall => true,
-        _ => false,
-    };
+    let overtime = matches!(*latest_cycle, Some(latest) if now - latest > max_stall);
 
     // Nothing to process, so just return early.
-    if unload_backlog.len() <= 0 || load_backlog.len() <= 0 {
+    if unload_backlog.is_empty() || load_backlog.is_empty() {
         return;
     }
 
@@ -259,3 +250,51 @@ pub fn handle_chunk_loads_and_unloads(
             load_backlog.clear();
         });
 }
+
+/// Unload force loaded chunks whose load tickets have all been dropped, and take away their render permits.
+pub fn release_expired_load_tickets(
+    realm: VoxelRealm,
+    mut unloaded_chunks: EventWriter<UnloadedChunkEvent>,
+    mut update_permits: EventWriter<UpdatePermitEvent>,
+) {
+    let unloaded = realm.cm().release_expired_tickets();
+
+    for chunk_pos in unloaded {
+        unloaded_chunks.send(UnloadedChunkEvent { chunk_pos });
+        update_permits.send(UpdatePermitEvent {
+            chunk_pos,
+            insert_flags: PermitFlags::empty(),
+            remove_flags: PermitFlags::RENDER,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spawned_chunk_has_chunk_aabb() {
+        let mut app = App::new();
+        app.add_event::<UpdatePermitEvent>()
+            .init_resource::<ChunkEcsPermits>()
+            .add_systems(Update, handle_permit_updates);
+
+        let chunk_pos = ChunkPos::new(1, -2, 3);
+        app.world.send_event(UpdatePermitEvent {
+            chunk_pos,
+            insert_flags: PermitFlags::RENDER,
+            remove_flags: PermitFlags::empty(),
+        });
+        app.update();
+
+        let entity = app
+            .world
+            .resource::<ChunkEcsPermits>()
+            .get_entity(chunk_pos)
+            .unwrap();
+
+        let aabb = app.world.get::<Aabb>(entity).unwrap();
+        assert_eq!(Chunk::BOUNDING_BOX.to_aabb(), *aabb);
+    }
+}
@@ -2,25 +2,38 @@ use std::{fmt, time::Duration};
 
 use bevy::prelude::*;
 use bitflags::bitflags;
-use error::EventPosMismatch;
-use handle_events::{handle_chunk_loads_and_unloads, handle_permit_updates};
+use entity_index::update_chunk_entity_index;
+use handle_events::{
+    handle_chunk_loads_and_unloads, handle_permit_updates, release_expired_load_tickets,
+};
 use observer_events::{
-    dispatch_move_events, generate_chunks_with_priority, load_in_range_chunks,
-    unload_out_of_range_chunks,
+    assign_chunk_render_layers, dispatch_move_events, generate_chunks_with_priority,
+    load_in_range_chunks, unload_out_of_range_chunks,
 };
 
 use crate::EngineState;
 
-use super::world::ChunkPos;
+use super::{
+    schematic::{paste_pending_schematics, PendingPastes},
+    store::{save_dirty_chunks, RealmTeardownSystems},
+    world::{edits::dispatch_voxel_edit_events, ChunkPos, VoxelEditEvent},
+};
 
+mod entity_index;
 mod error;
 mod events;
 mod handle_events;
 mod observer_events;
 mod permits;
+mod tickets;
+mod ticking;
+pub use entity_index::{ChunkEntityIndex, ChunkTracked};
 pub use events::*;
+pub(crate) use observer_events::chunks_in_range;
 
 pub use permits::*;
+pub use tickets::*;
+pub use ticking::*;
 
 #[derive(Clone, Component, Debug)]
 pub struct ChunkObserver {
@@ -29,6 +42,11 @@ pub struct ChunkObserver {
     pub view_distance_below: f32,
 }
 
+/// Marks the chunk observer that [`RealmState`](crate::render::meshing::controller::RealmState) tracks.
+/// If no observer is marked, an arbitrary observer is tracked instead.
+#[derive(Copy, Clone, Component, Debug, Default)]
+pub struct PrimaryObserver;
+
 #[derive(Clone, Component, Debug)]
 pub struct LastPosition {
     pub ws_pos: Vec3,
@@ -52,6 +70,9 @@ bitflags! {
         /// This chunk is loaded because it should have collisions, if it passes out of physics distance
         /// then this flag will be removed
         const COLLISION = 1 << 2;
+        /// This chunk is force loaded by a load ticket, this flag is removed once all the tickets
+        /// for the chunk are dropped. See [`crate::topo::world::chunk_manager::LoadTicket`]
+        const TICKET = 1 << 3;
     }
 }
 
@@ -61,6 +82,7 @@ impl fmt::Debug for LoadReasons {
             (Self::MANUAL, "MANUAL"),
             (Self::RENDER, "RENDER"),
             (Self::COLLISION, "COLLISION"),
+            (Self::TICKET, "TICKET"),
         ];
 
         let mut list = f.debug_list();
@@ -97,13 +119,22 @@ pub struct WorldController {
 impl Plugin for WorldController {
     fn build(&self, app: &mut App) {
         app.insert_resource(self.settings)
+            .init_resource::<GenerationBudget>()
+            .init_resource::<TicketedLoads>()
+            .init_resource::<VoxelWorldTick>()
+            .init_resource::<TickSettings>()
+            .init_resource::<BlockTickers>()
+            .init_resource::<ScheduledTicks>()
+            .init_resource::<ChunkEntityIndex>()
+            .init_resource::<PendingPastes>()
             .add_event::<LoadChunkEvent>()
             .add_event::<LoadedChunkEvent>()
             .add_event::<UnloadChunkEvent>()
             .add_event::<UnloadedChunkEvent>()
             .add_event::<UpdatePermitEvent>()
             .add_event::<ChunkObserverMoveEvent>()
-            .add_event::<ChunkObserverCrossChunkBorderEvent>();
+            .add_event::<ChunkObserverCrossChunkBorderEvent>()
+            .add_event::<VoxelEditEvent>();
 
         app.add_systems(
             FixedPostUpdate,
@@ -112,10 +143,24 @@ impl Plugin for WorldController {
                 (unload_out_of_range_chunks, load_in_range_chunks)
                     .chain()
                     .in_set(WorldControllerSystems::ObserverResponses),
-                (handle_chunk_loads_and_unloads, handle_permit_updates)
+                update_chunk_entity_index.in_set(WorldControllerSystems::ObserverResponses),
+                (
+                    handle_chunk_loads_and_unloads,
+                    handle_permit_updates,
+                    release_expired_load_tickets,
+                )
                     .chain()
                     .in_set(WorldControllerSystems::CoreEvents),
                 generate_chunks_with_priority.after(WorldControllerSystems::CoreEvents),
+                assign_chunk_render_layers.after(WorldControllerSystems::CoreEvents),
+                load_ticketed_chunks.after(WorldControllerSystems::CoreEvents),
+                (
+                    tick_voxels,
+                    paste_pending_schematics,
+                    dispatch_voxel_edit_events,
+                )
+                    .chain()
+                    .after(WorldControllerSystems::CoreEvents),
             ),
         );
 
@@ -129,5 +174,14 @@ impl Plugin for WorldController {
                 .chain()
                 .run_if(in_state(EngineState::Finished)),
         );
+
+        app.add_systems(
+            Last,
+            save_dirty_chunks
+                .in_set(RealmTeardownSystems::Save)
+                .run_if(in_state(EngineState::Finished)),
+        );
+
+        RealmTeardownSystems::configure(app);
     }
 }
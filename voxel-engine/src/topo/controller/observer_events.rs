@@ -1,33 +1,23 @@
 use std::time::Instant;
 
-use bevy::{
-    ecs::system::SystemParam,
-    math::{
-        bounding::{Aabb3d, BoundingVolume},
-        ivec2, ivec3,
-    },
-    prelude::*,
-    tasks::ComputeTaskPool,
-};
-use cb::channel;
+use bevy::{ecs::entity::EntityHashMap, math::ivec3, prelude::*, render::view::RenderLayers};
 
 use crate::{
-    render::meshing::controller::MeshGeneration,
     topo::{
-        world::{realm::ChunkManagerResource, Chunk, ChunkEntity, ChunkPos, VoxelRealm},
+        world::{Chunk, ChunkEntity, ChunkPos, VoxelRealm},
         worldgen::{generator::GenerateChunk, GenerationPriority},
     },
-    util::{ws_to_chunk_pos, ChunkMap, ChunkSet},
+    util::{space::world_to_chunk, ChunkMap, ChunkSet},
 };
 
 use super::{
     ChunkObserver, ChunkObserverCrossChunkBorderEvent, ChunkObserverMoveEvent, ChunkPermitKey,
-    Entry, LastPosition, LoadChunkEvent, LoadReasons, LoadedChunkEvent, Permit, PermitFlags,
+    Entry, LastPosition, LoadChunkEvent, LoadReasons, LoadedChunkEvent, PermitFlags,
     UnloadChunkEvent, UpdatePermitEvent,
 };
 
 fn transform_chunk_pos(trans: &Transform) -> ChunkPos {
-    ws_to_chunk_pos(trans.translation.floor().as_ivec3())
+    world_to_chunk(trans.translation)
 }
 
 /// Dispatch movement events for chunk observers.
@@ -126,53 +116,121 @@ fn is_in_range(
     in_horizontal_range && in_vertical_range
 }
 
+/// All the chunks in range of an observer in the given chunk.
+pub(crate) fn chunks_in_range(
+    observer_pos: ChunkPos,
+    observer: &ChunkObserver,
+) -> impl Iterator<Item = ChunkPos> + '_ {
+    let min_y = (-observer.view_distance_below).floor() as i32;
+    let max_y = observer.view_distance_above.ceil() as i32;
+
+    let horizontal_min = IVec2::splat((-observer.horizontal_range).floor() as i32);
+    let horizontal_max = IVec2::splat(observer.horizontal_range.ceil() as i32);
+
+    (min_y..=max_y)
+        .flat_map(move |y| {
+            (horizontal_min.x..=horizontal_max.x).flat_map(move |x| {
+                (horizontal_min.y..=horizontal_max.y).map(move |z| observer_pos + ivec3(x, y, z))
+            })
+        })
+        .filter(move |&cpos| is_in_range(observer_pos, cpos, observer))
+}
+
+/// Gives chunk entities the [`RenderLayers`] of the observers they're in range of, so that each camera only
+/// renders the chunks around its own observer (for split-screen or minimaps). The layers of an observer are
+/// the [`RenderLayers`] of the observer entity, or the default layer if it has none. Chunks in range of
+/// several observers are rendered on all of their layers, and chunks that aren't in range of any observer
+/// keep their layers.
+pub fn assign_chunk_render_layers(
+    mut border_events: EventReader<ChunkObserverCrossChunkBorderEvent>,
+    observers: Query<(&ChunkObserver, &LastPosition, Option<&RenderLayers>)>,
+    changed_layers: Query<(), (With<ChunkObserver>, Changed<RenderLayers>)>,
+    added_chunks: Query<(), Added<ChunkEntity>>,
+    chunks: Query<(Entity, &ChunkPos, Option<&RenderLayers>), With<ChunkEntity>>,
+    mut cmds: Commands,
+) {
+    let moved = border_events.read().count() > 0;
+
+    if !moved && changed_layers.is_empty() && added_chunks.is_empty() {
+        return;
+    }
+
+    for (entity, &chunk_pos, current) in &chunks {
+        let layers = observers
+            .iter()
+            .filter(|(observer, last_pos, _)| is_in_range(last_pos.chunk_pos, chunk_pos, observer))
+            .map(|(_, _, layers)| layers.copied().unwrap_or_default())
+            .reduce(|acc, layers| layers.iter().fold(acc, RenderLayers::with));
+
+        let Some(layers) = layers else {
+            continue;
+        };
+
+        if current.copied().unwrap_or_default() != layers {
+            cmds.entity(entity).insert(layers);
+        }
+    }
+}
+
+/// Whether the chunk is in range of any of the observers. `observers` are the chunk positions of the
+/// observers and their settings.
+fn in_range_of_any(chunk_pos: ChunkPos, observers: &[(ChunkPos, &ChunkObserver)]) -> bool {
+    observers
+        .iter()
+        .any(|&(opos, observer)| is_in_range(opos, chunk_pos, observer))
+}
+
 pub fn unload_out_of_range_chunks(
     realm: VoxelRealm,
     mut border_events: EventReader<ChunkObserverCrossChunkBorderEvent>,
     mut update_permits: EventWriter<UpdatePermitEvent>,
     mut unload_chunks: EventWriter<UnloadChunkEvent>,
-    chunk_observers: Query<&ChunkObserver>,
+    chunk_observers: Query<(Entity, &ChunkObserver, Option<&LastPosition>)>,
 ) {
     let then = Instant::now();
 
-    let mut moved_observers = ChunkMap::<&ChunkObserver>::default();
+    let mut moved_observers = EntityHashMap::<ChunkPos>::default();
     for event in border_events.read() {
         if event.new {
             continue;
         }
 
-        let Ok(observer) = chunk_observers.get(event.entity) else {
+        if !chunk_observers.contains(event.entity) {
             error!("Chunk observer entity described in move event didn't exist in the query");
             continue;
-        };
+        }
 
-        moved_observers.set(event.new_chunk, observer);
+        moved_observers.insert(event.entity, event.new_chunk);
     }
 
     // If there's no non-new border events, we don't do anything
-    if moved_observers.len() <= 0 {
+    if moved_observers.is_empty() {
         return;
     }
 
+    // Chunks are only unloaded if no observer can see them, not just the ones that moved, since chunks
+    // are shared between all the observers they're in range of.
+    let observers = chunk_observers
+        .iter()
+        .filter_map(|(entity, observer, last_pos)| {
+            let pos = moved_observers
+                .get(&entity)
+                .copied()
+                .or(last_pos.map(|last_pos| last_pos.chunk_pos))?;
+
+            Some((pos, observer))
+        })
+        .collect::<Vec<_>>();
+
     let mut removed = ChunkMap::<Entry>::new();
 
     for entry in realm.permits().iter() {
-        let mut visible = false;
-        // This would lead to a bug if we didn't already verify that there are actually events to be handled.
-        // If 'moved_observers' is empty, then 'visible' remains false, and the chunk is unloaded.
-        for (opos, &observer) in moved_observers.iter() {
-            if is_in_range(opos, entry.chunk, observer) {
-                visible = true;
-                break;
-            }
-        }
-
-        if !visible {
+        if !in_range_of_any(entry.chunk, &observers) {
             removed.set(entry.chunk, entry.clone());
         }
     }
 
-    for (chunk_pos, entry) in removed.iter() {
+    for (chunk_pos, _entry) in removed.iter() {
         // TODO: fix unloading
         unload_chunks.send(UnloadChunkEvent {
             chunk_pos,
@@ -188,7 +246,7 @@ pub fn unload_out_of_range_chunks(
     let now = Instant::now();
     let elapsed = now - then;
 
-    if removed.len() > 0 {
+    if !removed.is_empty() {
         info!(
             "Spent {}ms unloading out of range chunks for observers",
             elapsed.as_millis()
@@ -218,33 +276,16 @@ pub fn load_in_range_chunks(
     let mut in_range = ChunkSet::default();
 
     for (opos, &observer) in moved_observers.iter() {
-        let min_y = (-observer.view_distance_below).floor() as i32;
-        let max_y = observer.view_distance_above.ceil() as i32;
-
-        let horizontal_min = IVec2::splat((-observer.horizontal_range).floor() as i32);
-        let horizontal_max = IVec2::splat(observer.horizontal_range.ceil() as i32);
-
-        for y in min_y..=max_y {
-            for x in horizontal_min.x..=horizontal_max.x {
-                for z in horizontal_min.y..=horizontal_max.y {
-                    let pos = ivec3(x, y, z);
-                    let cpos = ChunkPos::from(pos + opos.as_ivec3());
-
-                    if !is_in_range(opos, cpos, observer) {
-                        continue;
-                    }
-
-                    if realm
-                        .permits()
-                        .get(ChunkPermitKey::Chunk(cpos))
-                        .is_some_and(|permit| permit.flags.contains(PermitFlags::RENDER))
-                    {
-                        continue;
-                    }
-
-                    in_range.set(cpos);
-                }
+        for cpos in chunks_in_range(opos, observer) {
+            if realm
+                .permits()
+                .get(ChunkPermitKey::Chunk(cpos))
+                .is_some_and(|permit| permit.flags.contains(PermitFlags::RENDER))
+            {
+                continue;
             }
+
+            in_range.set(cpos);
         }
     }
 
@@ -264,7 +305,7 @@ pub fn load_in_range_chunks(
     let now = Instant::now();
     let elapsed = now - then;
 
-    if in_range.len() > 0 {
+    if !in_range.is_empty() {
         info!(
             "Spent {}ms loading in-range chunks for observers",
             elapsed.as_millis()
@@ -313,3 +354,134 @@ pub fn generate_chunks_with_priority(
         }
     }));
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::topo::{
+        controller::{ChunkEcsPermits, Permit},
+        world::{realm::ChunkManagerResource, ChunkManager},
+    };
+
+    use super::*;
+
+    fn observer() -> ChunkObserver {
+        ChunkObserver {
+            horizontal_range: 2.0,
+            view_distance_above: 1.0,
+            view_distance_below: 1.0,
+        }
+    }
+
+    fn last_pos(chunk_pos: ChunkPos) -> LastPosition {
+        LastPosition {
+            ws_pos: chunk_pos.worldspace_min().as_vec3(),
+            chunk_pos,
+        }
+    }
+
+    #[test]
+    fn chunks_render_to_their_observers_layers() {
+        let mut app = App::new();
+        app.add_event::<ChunkObserverCrossChunkBorderEvent>()
+            .add_systems(Update, assign_chunk_render_layers);
+
+        let a_layers = RenderLayers::layer(1);
+        let b_layers = RenderLayers::layer(2);
+
+        app.world
+            .spawn((observer(), last_pos(ChunkPos::new(0, 0, 0)), a_layers));
+        app.world
+            .spawn((observer(), last_pos(ChunkPos::new(10, 0, 0)), b_layers));
+
+        let only_a = app.world.spawn((ChunkPos::new(-2, 0, 0), ChunkEntity)).id();
+        let only_b = app.world.spawn((ChunkPos::new(12, 0, 0), ChunkEntity)).id();
+        let neither = app.world.spawn((ChunkPos::new(5, 0, 0), ChunkEntity)).id();
+        app.update();
+
+        let layers = |app: &App, entity| app.world.get::<RenderLayers>(entity).copied();
+
+        assert_eq!(Some(a_layers), layers(&app, only_a));
+        assert!(!layers(&app, only_a).unwrap().intersects(&b_layers));
+        assert_eq!(Some(b_layers), layers(&app, only_b));
+        assert_eq!(None, layers(&app, neither));
+
+        // a third observer on the default layer shares a chunk with observer A
+        let c = app
+            .world
+            .spawn((observer(), last_pos(ChunkPos::new(-4, 0, 0))))
+            .id();
+        app.world.send_event(ChunkObserverCrossChunkBorderEvent {
+            new: true,
+            entity: c,
+            old_chunk: ChunkPos::new(-4, 0, 0),
+            new_chunk: ChunkPos::new(-4, 0, 0),
+        });
+        app.update();
+
+        assert_eq!(Some(a_layers.with(0)), layers(&app, only_a));
+    }
+
+    #[test]
+    fn shared_chunk_unloads_with_last_observer() {
+        let mut app = App::new();
+        app.add_event::<ChunkObserverMoveEvent>()
+            .add_event::<ChunkObserverCrossChunkBorderEvent>()
+            .add_event::<UnloadChunkEvent>()
+            .add_event::<UpdatePermitEvent>()
+            .insert_resource(ChunkManagerResource(Arc::new(ChunkManager::new_test())))
+            .init_resource::<ChunkEcsPermits>()
+            .add_systems(
+                Update,
+                (dispatch_move_events, unload_out_of_range_chunks).chain(),
+            );
+
+        // the chunk at x = 5 is in range of both observers
+        let shared = ChunkPos::new(5, 0, 0);
+        app.world.resource_mut::<ChunkEcsPermits>().insert(
+            Entity::PLACEHOLDER,
+            shared,
+            Permit::new(PermitFlags::RENDER),
+        );
+
+        let observer_at = |app: &mut App, chunk_pos: ChunkPos| {
+            let transform = Transform::from_translation(chunk_pos.worldspace_min().as_vec3());
+            app.world
+                .spawn((observer(), transform, last_pos(chunk_pos)))
+                .id()
+        };
+
+        let move_to = |app: &mut App, entity: Entity, chunk_pos: ChunkPos| {
+            app.world
+                .entity_mut(entity)
+                .insert(Transform::from_translation(
+                    chunk_pos.worldspace_min().as_vec3(),
+                ));
+            app.update();
+        };
+
+        let unloaded = |app: &mut App| {
+            let chunks = app
+                .world
+                .resource_mut::<Events<UnloadChunkEvent>>()
+                .drain()
+                .map(|event| event.chunk_pos)
+                .collect::<Vec<_>>();
+            app.world
+                .resource_mut::<Events<UpdatePermitEvent>>()
+                .clear();
+            chunks
+        };
+
+        let a = observer_at(&mut app, ChunkPos::new(4, 0, 0));
+        let b = observer_at(&mut app, ChunkPos::new(6, 0, 0));
+        app.update();
+
+        move_to(&mut app, a, ChunkPos::new(20, 0, 0));
+        assert!(unloaded(&mut app).is_empty());
+
+        move_to(&mut app, b, ChunkPos::new(-20, 0, 0));
+        assert_eq!(vec![shared], unloaded(&mut app));
+    }
+}
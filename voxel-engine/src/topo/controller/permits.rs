@@ -131,10 +131,14 @@ impl ChunkEcsPermits {
         self.data.len()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
     pub fn iter(&self) -> ChunkEcsPermitsIterator<'_> {
         ChunkEcsPermitsIterator {
             current_idx: 0,
-            permits: &self,
+            permits: self,
         }
     }
 }
@@ -148,8 +152,9 @@ impl<'a> Iterator for ChunkEcsPermitsIterator<'a> {
     type Item = &'a Entry;
 
     fn next(&mut self) -> Option<Self::Item> {
+        let entry = self.permits.data.get(self.current_idx)?;
         self.current_idx += 1;
-        self.permits.data.get(self.current_idx)
+        Some(entry)
     }
 }
 
@@ -164,7 +169,7 @@ mod tests {
         for i in 0..100 {
             permits.insert(
                 Entity::from_raw(i as u32),
-                ChunkPos::new(0, i as i32, 0),
+                ChunkPos::new(0, i, 0),
                 Permit::new(PermitFlags::RENDER),
             );
         }
@@ -182,5 +187,7 @@ mod tests {
             let entity = permits.get_entity(cpos).unwrap();
             assert_eq!(entity, Entity::from_raw(i as u32));
         }
+
+        assert_eq!(50, permits.iter().count());
     }
 }
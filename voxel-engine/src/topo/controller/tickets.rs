@@ -0,0 +1,265 @@
+use std::collections::BinaryHeap;
+
+use bevy::prelude::*;
+
+use crate::{
+    topo::{
+        world::{
+            chunk_manager::{ChunkLoadResult, LoadTicket},
+            ChunkManager, ChunkPos, VoxelRealm,
+        },
+        worldgen::{generator::GenerateChunk, GenerationPriority},
+    },
+    util::{Keyed, KeyedOrd},
+};
+
+use super::{LoadedChunkEvent, PermitFlags, UpdatePermitEvent};
+
+/// The maximum number of ticketed chunks that are force loaded (and generated) per tick.
+/// Keeps a burst of force loads from stalling the frame.
+#[derive(Copy, Clone, Resource, Debug, Deref, DerefMut)]
+pub struct GenerationBudget(pub usize);
+
+impl Default for GenerationBudget {
+    fn default() -> Self {
+        Self(8)
+    }
+}
+
+#[derive(Clone, Debug)]
+struct TicketedLoad {
+    pos: ChunkPos,
+    priority: GenerationPriority,
+    ticket: LoadTicket,
+}
+
+impl Keyed<GenerationPriority> for TicketedLoad {
+    type Key = GenerationPriority;
+
+    fn key(&self) -> &Self::Key {
+        &self.priority
+    }
+}
+
+/// A chunk that was force loaded by [`TicketedLoads::process`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ProcessedLoad {
+    pub pos: ChunkPos,
+    pub priority: GenerationPriority,
+    pub result: ChunkLoadResult,
+}
+
+/// Queue of chunks waiting to be force loaded with a ticket. The queue is drained by priority,
+/// up to [`GenerationBudget`] chunks per tick.
+#[derive(Resource, Default)]
+pub struct TicketedLoads {
+    pending: BinaryHeap<KeyedOrd<TicketedLoad, GenerationPriority>>,
+}
+
+impl TicketedLoads {
+    /// Queue the chunk at `pos` to be force loaded with the given ticket. The queue keeps a clone
+    /// of the ticket until the chunk is loaded.
+    pub fn queue(&mut self, pos: ChunkPos, ticket: &LoadTicket) {
+        self.pending.push(KeyedOrd::new(TicketedLoad {
+            pos,
+            priority: ticket.priority(),
+            ticket: ticket.clone(),
+        }));
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Force load up to `budget` of the queued chunks with the highest priority.
+    /// Returns the chunks that were loaded, in the order they were loaded.
+    pub fn process(&mut self, cm: &ChunkManager, budget: usize) -> Vec<ProcessedLoad> {
+        let mut processed = Vec::with_capacity(budget.min(self.pending.len()));
+
+        while processed.len() < budget {
+            let Some(load) = self.pending.pop().map(KeyedOrd::into_inner) else {
+                break;
+            };
+
+            match cm.force_load(load.pos, &load.ticket) {
+                Ok(result) => processed.push(ProcessedLoad {
+                    pos: load.pos,
+                    priority: load.priority,
+                    result,
+                }),
+                Err(error) => {
+                    error!(
+                        "Error force loading chunk at position {}: {error}",
+                        load.pos
+                    );
+                }
+            }
+        }
+
+        processed
+    }
+}
+
+/// Force load queued chunks within the generation budget, and generate the ones that weren't loaded before.
+/// Force loaded chunks get a render permit, and newly loaded chunks are announced with a [`LoadedChunkEvent`]
+/// like chunks loaded by observers, so the rest of the engine treats them the same.
+pub fn load_ticketed_chunks(
+    realm: VoxelRealm,
+    budget: Res<GenerationBudget>,
+    mut loads: ResMut<TicketedLoads>,
+    mut loaded_chunks: EventWriter<LoadedChunkEvent>,
+    mut update_permits: EventWriter<UpdatePermitEvent>,
+    mut generation_events: EventWriter<GenerateChunk>,
+) {
+    if loads.is_empty() {
+        return;
+    }
+
+    let processed = loads.process(realm.cm(), budget.0);
+
+    for load in processed {
+        update_permits.send(UpdatePermitEvent {
+            chunk_pos: load.pos,
+            insert_flags: PermitFlags::RENDER,
+            remove_flags: PermitFlags::empty(),
+        });
+
+        if load.result == ChunkLoadResult::New {
+            // generated here with the priority of the ticket, rather than by distance to the observers
+            loaded_chunks.send(LoadedChunkEvent {
+                chunk_pos: load.pos,
+                auto_generate: false,
+            });
+            generation_events.send(GenerateChunk {
+                pos: load.pos,
+                priority: load.priority,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use bevy::ecs::event::Events;
+
+    use crate::topo::{
+        controller::{
+            handle_events::{handle_permit_updates, release_expired_load_tickets},
+            ChunkEcsPermits, ChunkPermitKey, LoadReasons, UnloadedChunkEvent,
+        },
+        world::realm::ChunkManagerResource,
+    };
+
+    use super::*;
+
+    #[test]
+    fn ticketed_loads_respect_budget_and_priority() {
+        let cm = ChunkManager::new_test();
+        let mut loads = TicketedLoads::default();
+
+        // queue the chunks in a scrambled order, the chunk at x has priority x
+        let tickets = [3, 7, 0, 9, 1, 5, 8, 2, 6, 4].map(|x| {
+            let ticket = LoadTicket::with_priority(GenerationPriority::new(x));
+            loads.queue(ChunkPos::new(x as i32, 0, 0), &ticket);
+            ticket
+        });
+
+        let mut order = Vec::new();
+        for tick in 0..5 {
+            let processed = loads.process(&cm, 2);
+            assert_eq!(2, processed.len());
+            assert_eq!(10 - (tick + 1) * 2, loads.len());
+
+            for load in processed {
+                assert_eq!(ChunkLoadResult::New, load.result);
+                order.push(load.pos.x());
+            }
+        }
+
+        assert!(loads.process(&cm, 2).is_empty());
+
+        // a lower number means a higher priority
+        assert_eq!((0..10).collect::<Vec<_>>(), order);
+
+        for x in 0..10 {
+            let cref = cm.get_loaded_chunk(ChunkPos::new(x, 0, 0), true).unwrap();
+            assert_eq!(LoadReasons::TICKET, cref.load_reasons());
+        }
+
+        drop(tickets);
+        assert_eq!(10, cm.release_expired_tickets().len());
+    }
+
+    #[test]
+    fn ticketed_loads_send_load_events() {
+        let mut app = App::new();
+        app.insert_resource(ChunkManagerResource(Arc::new(ChunkManager::new_test())))
+            .init_resource::<ChunkEcsPermits>()
+            .init_resource::<GenerationBudget>()
+            .init_resource::<TicketedLoads>()
+            .add_event::<LoadedChunkEvent>()
+            .add_event::<UnloadedChunkEvent>()
+            .add_event::<UpdatePermitEvent>()
+            .add_event::<GenerateChunk>()
+            .add_systems(
+                Update,
+                (
+                    load_ticketed_chunks,
+                    handle_permit_updates,
+                    release_expired_load_tickets,
+                )
+                    .chain(),
+            );
+
+        let pos = ChunkPos::new(1, 2, 3);
+        let ticket = LoadTicket::with_priority(GenerationPriority::new(4));
+        app.world
+            .resource_mut::<TicketedLoads>()
+            .queue(pos, &ticket);
+        app.update();
+
+        let loaded = app
+            .world
+            .resource_mut::<Events<LoadedChunkEvent>>()
+            .drain()
+            .map(|event| (event.chunk_pos, event.auto_generate))
+            .collect::<Vec<_>>();
+        assert_eq!(vec![(pos, false)], loaded);
+
+        let generated = app
+            .world
+            .resource_mut::<Events<GenerateChunk>>()
+            .drain()
+            .map(|event| (event.pos, event.priority))
+            .collect::<Vec<_>>();
+        assert_eq!(vec![(pos, GenerationPriority::new(4))], generated);
+
+        let has_render_permit = |app: &App| {
+            app.world
+                .resource::<ChunkEcsPermits>()
+                .get(ChunkPermitKey::Chunk(pos))
+                .is_some_and(|permit| permit.flags.contains(PermitFlags::RENDER))
+        };
+        assert!(has_render_permit(&app));
+
+        // the permit is taken away again once the chunk is unloaded
+        drop(ticket);
+        app.update();
+        app.update();
+
+        let unloaded = app
+            .world
+            .resource_mut::<Events<UnloadedChunkEvent>>()
+            .drain()
+            .map(|event| event.chunk_pos)
+            .collect::<Vec<_>>();
+        assert_eq!(vec![pos], unloaded);
+        assert!(!has_render_permit(&app));
+    }
+}
@@ -0,0 +1,327 @@
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::{
+    data::registries::block::BlockVariantId,
+    topo::{
+        block::{BlockVoxel, FullBlock},
+        world::{
+            chunk::ChunkFlags, Chunk, ChunkAccessInput, ChunkManager, ChunkManagerError, EditCause,
+            VoxelRealm,
+        },
+    },
+};
+
+/// The number of voxel world ticks that have passed. Incremented every time [`tick_voxels`] runs.
+#[derive(Copy, Clone, Resource, Debug, Default, Deref)]
+pub struct VoxelWorldTick(pub u64);
+
+#[derive(Copy, Clone, Resource, Debug)]
+pub struct TickSettings {
+    /// The number of random voxels in every loaded chunk that are ticked every tick.
+    pub random_ticks_per_chunk: usize,
+}
+
+impl Default for TickSettings {
+    fn default() -> Self {
+        Self {
+            random_ticks_per_chunk: 3,
+        }
+    }
+}
+
+/// Why a voxel was ticked.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum TickMode {
+    /// The voxel was picked at random. Used for slow processes like crops growing.
+    Random,
+    /// The voxel was scheduled to be ticked, see [`TickContext::schedule`].
+    Scheduled,
+}
+
+/// The context a block's tick callback is invoked with.
+pub struct TickContext<'a> {
+    /// The worldspace position of the voxel being ticked.
+    pub pos: IVec3,
+    pub block: FullBlock,
+    pub mode: TickMode,
+    pub tick: u64,
+    pub cm: &'a ChunkManager,
+    scheduled: &'a mut ScheduledTicks,
+}
+
+impl<'a> TickContext<'a> {
+    /// Schedule the voxel at `pos` to be ticked `delay` ticks from now. A delay of 0 is treated as 1.
+    pub fn schedule(&mut self, pos: IVec3, delay: u64) {
+        self.scheduled.schedule(pos, self.tick + delay.max(1));
+    }
+
+    /// Write a voxel, queueing remeshes for the chunks affected by the edit. See [`ChunkManager::set_voxel`].
+    pub fn set_voxel(&self, pos: IVec3, block: BlockVoxel) -> Result<(), ChunkManagerError> {
+        self.cm
+            .set_voxel_caused(pos, ChunkAccessInput::new(block), EditCause::Tick)
+    }
+}
+
+pub type BlockTickFn = fn(&mut TickContext<'_>);
+
+/// The tick callbacks of the block variants that tick.
+#[derive(Resource, Default)]
+pub struct BlockTickers {
+    callbacks: hb::HashMap<BlockVariantId, BlockTickFn, fxhash::FxBuildHasher>,
+}
+
+impl BlockTickers {
+    /// Register the tick callback of a block variant, replacing the previous callback if any.
+    pub fn register(&mut self, variant: BlockVariantId, callback: BlockTickFn) {
+        self.callbacks.insert(variant, callback);
+    }
+
+    pub fn get(&self, variant: BlockVariantId) -> Option<BlockTickFn> {
+        self.callbacks.get(&variant).copied()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.callbacks.is_empty()
+    }
+}
+
+/// The voxel positions that are scheduled to be ticked, keyed by the tick they're due on.
+#[derive(Resource, Default)]
+pub struct ScheduledTicks {
+    pending: BTreeMap<u64, Vec<IVec3>>,
+}
+
+impl ScheduledTicks {
+    /// Schedule the voxel at `pos` to be ticked on the given tick. Scheduling the same position for
+    /// the same tick multiple times only ticks it once.
+    pub fn schedule(&mut self, pos: IVec3, tick: u64) {
+        let positions = self.pending.entry(tick).or_default();
+
+        if !positions.contains(&pos) {
+            positions.push(pos);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.values().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Remove and return the positions that are due on or before the given tick.
+    fn take_due(&mut self, tick: u64) -> Vec<IVec3> {
+        let later = self.pending.split_off(&(tick + 1));
+        let due = std::mem::replace(&mut self.pending, later);
+
+        due.into_values().flatten().collect()
+    }
+}
+
+/// Invoke the tick callback of the voxel at `pos`, if it has one. Subdivided voxels don't tick.
+fn tick_voxel(
+    cm: &ChunkManager,
+    tickers: &BlockTickers,
+    scheduled: &mut ScheduledTicks,
+    pos: IVec3,
+    mode: TickMode,
+    tick: u64,
+) -> bool {
+    let Ok(BlockVoxel::Full(block)) = cm.get_voxel(pos) else {
+        return false;
+    };
+
+    let Some(callback) = tickers.get(block.id) else {
+        return false;
+    };
+
+    callback(&mut TickContext {
+        pos,
+        block,
+        mode,
+        tick,
+        cm,
+        scheduled,
+    });
+
+    true
+}
+
+/// Run the scheduled ticks that are due on the given tick, and the random ticks of every loaded chunk.
+/// Returns the number of voxels that were ticked.
+pub fn run_voxel_tick<R: Rng>(
+    cm: &ChunkManager,
+    tickers: &BlockTickers,
+    scheduled: &mut ScheduledTicks,
+    settings: &TickSettings,
+    tick: u64,
+    rng: &mut R,
+) -> usize {
+    let mut ticked = 0;
+
+    for pos in scheduled.take_due(tick) {
+        if tick_voxel(cm, tickers, scheduled, pos, TickMode::Scheduled, tick) {
+            ticked += 1;
+        }
+    }
+
+    if settings.random_ticks_per_chunk == 0 || tickers.is_empty() {
+        return ticked;
+    }
+
+    let chunks = cm
+        .loaded_chunks()
+        .filter(|(_, cref)| !cref.flags().contains(ChunkFlags::PRIMORDIAL))
+        .map(|(pos, _)| pos)
+        .collect::<Vec<_>>();
+
+    for chunk_pos in chunks {
+        for _ in 0..settings.random_ticks_per_chunk {
+            let local = IVec3::from_array(std::array::from_fn(|_| rng.gen_range(0..Chunk::SIZE)));
+            let pos = chunk_pos.worldspace_min() + local;
+
+            if tick_voxel(cm, tickers, scheduled, pos, TickMode::Random, tick) {
+                ticked += 1;
+            }
+        }
+    }
+
+    ticked
+}
+
+/// Advance the voxel world by one tick, ticking scheduled and random voxels. Edits made by tick callbacks
+/// flag the affected chunks for remeshing.
+pub fn tick_voxels(
+    realm: VoxelRealm,
+    settings: Res<TickSettings>,
+    tickers: Res<BlockTickers>,
+    mut tick: ResMut<VoxelWorldTick>,
+    mut scheduled: ResMut<ScheduledTicks>,
+) {
+    tick.0 += 1;
+
+    run_voxel_tick(
+        realm.cm(),
+        &tickers,
+        &mut scheduled,
+        &settings,
+        tick.0,
+        &mut rand::thread_rng(),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::{data::registries::block::BlockVariantRegistry, topo::world::ChunkPos};
+
+    use super::*;
+
+    fn loaded_chunk_manager(filling: BlockVariantId) -> ChunkManager {
+        let cm = ChunkManager::new_test();
+        cm.insert_filled_test_chunk(ChunkPos::ZERO, BlockVoxel::new_full(filling));
+        cm
+    }
+
+    #[test]
+    fn scheduled_tick_runs_once_per_tick_and_edits_remesh() {
+        static TICKS: AtomicUsize = AtomicUsize::new(0);
+
+        // ticks every tick, and places a block above itself the first time it's ticked
+        fn tick_lamp(cx: &mut TickContext<'_>) {
+            assert_eq!(TickMode::Scheduled, cx.mode);
+
+            if TICKS.fetch_add(1, Ordering::Relaxed) == 0 {
+                cx.set_voxel(
+                    cx.pos + IVec3::Y,
+                    BlockVoxel::new_full(BlockVariantRegistry::FULL),
+                )
+                .unwrap();
+            }
+
+            cx.schedule(cx.pos, 1);
+        }
+
+        let cm = loaded_chunk_manager(BlockVariantRegistry::VOID);
+        let pos = IVec3::splat(4);
+        cm.set_voxel(
+            pos,
+            ChunkAccessInput::new(BlockVoxel::new_full(BlockVariantRegistry::LAMP)),
+        )
+        .unwrap();
+
+        let cref = cm.get_loaded_chunk(ChunkPos::ZERO, false).unwrap();
+        cref.update_flags(|flags| flags.remove(ChunkFlags::REMESH));
+
+        let mut tickers = BlockTickers::default();
+        tickers.register(BlockVariantRegistry::LAMP, tick_lamp);
+
+        let settings = TickSettings {
+            random_ticks_per_chunk: 0,
+        };
+        let mut scheduled = ScheduledTicks::default();
+        scheduled.schedule(pos, 1);
+
+        let mut rng = rand::thread_rng();
+
+        // nothing is due yet
+        assert_eq!(
+            0,
+            run_voxel_tick(&cm, &tickers, &mut scheduled, &settings, 0, &mut rng)
+        );
+        assert_eq!(0, TICKS.load(Ordering::Relaxed));
+
+        for tick in 1..=3 {
+            assert_eq!(
+                1,
+                run_voxel_tick(&cm, &tickers, &mut scheduled, &settings, tick, &mut rng)
+            );
+            assert_eq!(tick as usize, TICKS.load(Ordering::Relaxed));
+            assert_eq!(1, scheduled.len());
+        }
+
+        // the edit made by the callback queued a remesh
+        assert!(cref.flags().contains(ChunkFlags::REMESH));
+        assert!(matches!(
+            cm.get_voxel(pos + IVec3::Y),
+            Ok(BlockVoxel::Full(block)) if block.id == BlockVariantRegistry::FULL
+        ));
+    }
+
+    #[test]
+    fn random_ticks_per_chunk() {
+        static TICKS: AtomicUsize = AtomicUsize::new(0);
+
+        fn tick_lamp(cx: &mut TickContext<'_>) {
+            assert_eq!(TickMode::Random, cx.mode);
+            TICKS.fetch_add(1, Ordering::Relaxed);
+        }
+
+        // every voxel in the chunk ticks
+        let cm = loaded_chunk_manager(BlockVariantRegistry::LAMP);
+
+        let mut tickers = BlockTickers::default();
+        tickers.register(BlockVariantRegistry::LAMP, tick_lamp);
+
+        let settings = TickSettings {
+            random_ticks_per_chunk: 5,
+        };
+
+        let ticked = run_voxel_tick(
+            &cm,
+            &tickers,
+            &mut ScheduledTicks::default(),
+            &settings,
+            1,
+            &mut rand::thread_rng(),
+        );
+
+        assert_eq!(5, ticked);
+        assert_eq!(5, TICKS.load(Ordering::Relaxed));
+    }
+}
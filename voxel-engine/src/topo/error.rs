@@ -34,3 +34,13 @@ pub enum NeighborAccessError {
     #[error("Underlying access error: {0}")]
     Internal(#[from] ChunkAccessError),
 }
+
+#[derive(te::Error, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SetNeighborError {
+    /// The position is the center chunk, which can't be its own neighbor.
+    #[error("Attempted to set the center chunk as a neighbor")]
+    CenterChunk,
+    /// The position is outside of the 3x3x3 neighbor shell.
+    #[error("Neighbor position out of range")]
+    OutOfRange,
+}
@@ -0,0 +1,102 @@
+use bevy::math::{ivec2, uvec3, IVec2};
+
+use crate::data::registries::block::{BlockVariantId, BlockVariantRegistry};
+
+use super::{block::SubdividedBlock, world::CaoBlock};
+
+/// The top-most voxel that isn't air in a column of the world.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct HeightMapColumn {
+    /// The voxelspace Y position of the voxel.
+    pub y: i32,
+    /// The variant of the voxel, for subdivided blocks this is the variant of the top-most microblock
+    /// that isn't air.
+    pub block: BlockVariantId,
+}
+
+/// The surface of a region of the world, one [`HeightMapColumn`] per voxel column.
+/// Built by [`ChunkManager::heightmap`](super::world::ChunkManager::heightmap).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HeightMap {
+    min: IVec2,
+    size: IVec2,
+    columns: Vec<Option<HeightMapColumn>>,
+}
+
+impl HeightMap {
+    /// An empty heightmap covering the columns from `min` (inclusive) to `min + size` (exclusive).
+    /// The X and Y components of the vectors are the X and Z components of the columns.
+    pub(crate) fn new(min: IVec2, size: IVec2) -> Self {
+        Self {
+            min,
+            size,
+            columns: vec![None; (size.x * size.y) as usize],
+        }
+    }
+
+    fn index(&self, x: i32, z: i32) -> Option<usize> {
+        let local = ivec2(x, z) - self.min;
+
+        if local.cmplt(IVec2::ZERO).any() || local.cmpge(self.size).any() {
+            return None;
+        }
+
+        Some((local.x * self.size.y + local.y) as usize)
+    }
+
+    /// The minimum X and Z of the columns in this heightmap.
+    pub fn min(&self) -> IVec2 {
+        self.min
+    }
+
+    /// The number of columns along the X and Z axes.
+    pub fn size(&self) -> IVec2 {
+        self.size
+    }
+
+    /// The surface of the column at the given voxelspace X and Z. Returns `None` if the column isn't in this
+    /// heightmap, or if there's nothing but air (or unloaded chunks) in the column.
+    pub fn get(&self, x: i32, z: i32) -> Option<HeightMapColumn> {
+        self.columns[self.index(x, z)?]
+    }
+
+    pub(crate) fn is_set(&self, x: i32, z: i32) -> bool {
+        self.get(x, z).is_some()
+    }
+
+    pub(crate) fn set(&mut self, x: i32, z: i32, column: HeightMapColumn) {
+        if let Some(index) = self.index(x, z) {
+            self.columns[index] = Some(column);
+        }
+    }
+
+    /// Iterate over the columns that have a surface, along with their voxelspace X and Z.
+    pub fn iter(&self) -> impl Iterator<Item = (IVec2, HeightMapColumn)> + '_ {
+        self.columns.iter().enumerate().filter_map(|(i, column)| {
+            let i = i as i32;
+            let pos = self.min + ivec2(i / self.size.y, i % self.size.y);
+            column.map(|column| (pos, column))
+        })
+    }
+}
+
+/// The variant of the block if it isn't air. Subdivided blocks are air if all their microblocks are air,
+/// otherwise the top-most microblock that isn't air is used.
+pub(crate) fn surface_variant(
+    block: CaoBlock<'_>,
+    registry: &BlockVariantRegistry,
+) -> Option<BlockVariantId> {
+    match block {
+        CaoBlock::Full(full) => (!registry.is_air(full.id)).then_some(full.id),
+        CaoBlock::Subdivided(subdiv) => {
+            let size = SubdividedBlock::SUBDIVISIONS as u32;
+
+            (0..size).rev().find_map(|y| {
+                (0..size)
+                    .flat_map(|x| (0..size).map(move |z| uvec3(x, y, z)))
+                    .map(|pos| subdiv.get(pos).unwrap().id)
+                    .find(|&id| !registry.is_air(id))
+            })
+        }
+    }
+}
@@ -0,0 +1,621 @@
+use std::collections::VecDeque;
+
+use bevy::{
+    math::{ivec3, uvec3, IVec3},
+    render::render_resource::ShaderDefVal,
+};
+
+use crate::{
+    data::{
+        registries::{
+            block::{BlockVariantId, BlockVariantRegistry},
+            Registry,
+        },
+        tile::Face,
+    },
+    render::core::u32_shader_def,
+    topo::{
+        access::{ChunkAccess, HasBounds},
+        block::SubdividedBlock,
+        bounding_box::BoundingBox,
+        neighbors::Neighbors,
+        storage::error::OutOfBounds,
+        world::{CaoBlock, Chunk},
+    },
+    util::ivec3_to_1d,
+};
+
+/// The light level of a voxel. Block light (from emissive blocks) and skylight are stored in
+/// separate 4-bit channels, block light in the low nibble and skylight in the high nibble.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Default)]
+pub struct LightLevel(u8);
+
+impl LightLevel {
+    pub const MAX: u8 = 0b1111;
+    pub const DARK: Self = Self(0);
+
+    pub const CHANNEL_MASK: u8 = 0b1111;
+    pub const SKY_SHIFT: u8 = 4;
+
+    /// Create a new light level, the channels are clamped to [`LightLevel::MAX`].
+    pub fn new(block: u8, sky: u8) -> Self {
+        Self(block.min(Self::MAX) | (sky.min(Self::MAX) << Self::SKY_SHIFT))
+    }
+
+    pub fn block(self) -> u8 {
+        self.0 & Self::CHANNEL_MASK
+    }
+
+    pub fn sky(self) -> u8 {
+        (self.0 >> Self::SKY_SHIFT) & Self::CHANNEL_MASK
+    }
+
+    pub fn as_u8(self) -> u8 {
+        self.0
+    }
+
+    /// The inverse of [`LightLevel::as_u8`].
+    pub fn from_u8(raw: u8) -> Self {
+        Self(raw)
+    }
+
+    /// The brightest of each channel of the two light levels.
+    pub fn max(self, other: Self) -> Self {
+        Self::new(self.block().max(other.block()), self.sky().max(other.sky()))
+    }
+
+    pub fn shader_defs() -> Vec<ShaderDefVal> {
+        vec![
+            u32_shader_def("LIGHT_CHANNEL_MASK", Self::CHANNEL_MASK as u32),
+            u32_shader_def("LIGHT_SKY_SHIFT", Self::SKY_SHIFT as u32),
+            u32_shader_def("LIGHT_MAX", Self::MAX as u32),
+        ]
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum LightChannel {
+    Block,
+    Sky,
+}
+
+impl LightChannel {
+    fn get(self, level: LightLevel) -> u8 {
+        match self {
+            Self::Block => level.block(),
+            Self::Sky => level.sky(),
+        }
+    }
+
+    fn with(self, level: LightLevel, value: u8) -> LightLevel {
+        match self {
+            Self::Block => LightLevel::new(value, level.sky()),
+            Self::Sky => LightLevel::new(level.block(), value),
+        }
+    }
+}
+
+/// How a block interacts with light.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct LightProperties {
+    opaque: bool,
+    emission: u8,
+}
+
+impl LightProperties {
+    /// Air is empty space, so it lets light through and doesn't emit any.
+    const AIR: Self = Self {
+        opaque: false,
+        emission: 0,
+    };
+
+    fn from_variant(id: BlockVariantId, registry: &BlockVariantRegistry) -> Self {
+        if registry.is_air(id) {
+            return Self::AIR;
+        }
+
+        let options = registry.get_by_id(id).options;
+        Self {
+            opaque: options.transparency.is_opaque(),
+            emission: options.emission,
+        }
+    }
+
+    /// Subdivided blocks let light through if any of their microblocks are transparent,
+    /// and emit the light of their brightest microblock.
+    fn from_block(block: CaoBlock<'_>, registry: &BlockVariantRegistry) -> Self {
+        match block {
+            CaoBlock::Full(full) => Self::from_variant(full.id, registry),
+            CaoBlock::Subdivided(subdiv) => {
+                const SUBDIVS: u32 = SubdividedBlock::SUBDIVISIONS as u32;
+
+                let mut properties = Self {
+                    opaque: true,
+                    emission: 0,
+                };
+
+                for x in 0..SUBDIVS {
+                    for y in 0..SUBDIVS {
+                        for z in 0..SUBDIVS {
+                            let id = subdiv.get(uvec3(x, y, z)).unwrap().id;
+                            let microblock = Self::from_variant(id, registry);
+
+                            properties.opaque &= microblock.opaque;
+                            properties.emission = properties.emission.max(microblock.emission);
+                        }
+                    }
+                }
+
+                properties
+            }
+        }
+    }
+}
+
+/// The upward directions that are checked for open sky when baking sky access.
+const SKY_ACCESS_RAYS: [IVec3; 9] = [
+    IVec3::new(0, 1, 0),
+    IVec3::new(1, 1, 0),
+    IVec3::new(-1, 1, 0),
+    IVec3::new(0, 1, 1),
+    IVec3::new(0, 1, -1),
+    IVec3::new(1, 1, 1),
+    IVec3::new(1, 1, -1),
+    IVec3::new(-1, 1, 1),
+    IVec3::new(-1, 1, -1),
+];
+
+/// How many voxels the sky access rays travel before they're considered to have reached the sky.
+pub const SKY_ACCESS_RANGE: i32 = 6;
+
+/// The light levels of a chunk, with a 1 voxel border containing the light of the neighboring chunks
+/// at the time the light was calculated. Light is flood-filled from emissive blocks and from the light of the border,
+/// decreasing by 1 for every voxel it travels. Skylight at full strength travels straight down without decreasing.
+///
+/// Along with the light levels every voxel has a baked sky access value (see [`ChunkLight::bake_sky_access`]),
+/// a coarse ambient occlusion term that darkens the skylight of voxels that are closed in by their surroundings.
+#[derive(Clone, Debug)]
+pub struct ChunkLight {
+    levels: Box<[LightLevel]>,
+    sky_access: Box<[u8]>,
+}
+
+impl Default for ChunkLight {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ChunkLight {
+    pub const USIZE: usize = Chunk::USIZE + 2;
+    pub const SIZE: i32 = Self::USIZE as i32;
+
+    pub const BUFFER_SIZE: usize = Self::USIZE.pow(3);
+
+    pub const BOUNDS: BoundingBox = BoundingBox {
+        min: IVec3::splat(-1),
+        max: Chunk::VEC.saturating_add(IVec3::ONE),
+    };
+
+    /// A completely dark chunk, with full sky access everywhere.
+    pub fn new() -> Self {
+        Self {
+            levels: vec![LightLevel::DARK; Self::BUFFER_SIZE].into_boxed_slice(),
+            sky_access: vec![LightLevel::MAX; Self::BUFFER_SIZE].into_boxed_slice(),
+        }
+    }
+
+    pub fn get(&self, pos: IVec3) -> Result<LightLevel, OutOfBounds> {
+        Ok(self.levels[Self::index(pos)?])
+    }
+
+    pub fn set(&mut self, pos: IVec3, level: LightLevel) -> Result<(), OutOfBounds> {
+        self.levels[Self::index(pos)?] = level;
+        Ok(())
+    }
+
+    /// How much of the sky the voxel can see, between 0 (none) and [`LightLevel::MAX`] (all of it).
+    pub fn sky_access(&self, pos: IVec3) -> Result<u8, OutOfBounds> {
+        Ok(self.sky_access[Self::index(pos)?])
+    }
+
+    pub fn set_sky_access(&mut self, pos: IVec3, access: u8) -> Result<(), OutOfBounds> {
+        self.sky_access[Self::index(pos)?] = access.min(LightLevel::MAX);
+        Ok(())
+    }
+
+    /// The light level to render the voxel with: its skylight is scaled by its sky access, so voxels
+    /// that are closed in are darker than the skylight that leaks in would make them. Positions outside
+    /// of the bounds are dark.
+    pub fn sample(&self, pos: IVec3) -> LightLevel {
+        let Ok(index) = Self::index(pos) else {
+            return LightLevel::DARK;
+        };
+
+        let level = self.levels[index];
+        let sky = level.sky() as u32 * self.sky_access[index] as u32;
+        let max = LightLevel::MAX as u32;
+
+        LightLevel::new(level.block(), ((sky + max / 2) / max) as u8)
+    }
+
+    fn index(mut pos: IVec3) -> Result<usize, OutOfBounds> {
+        if !Self::BOUNDS.contains(pos) {
+            return Err(OutOfBounds);
+        }
+
+        // the lowest value pos can be is [-1, -1, -1]
+        pos += IVec3::ONE;
+        ivec3_to_1d(pos, Self::USIZE).map_err(|_| OutOfBounds)
+    }
+
+    /// Calculate the light of a chunk. `border` is called for every position on the border of
+    /// the chunk and should return the light level of that voxel in the neighboring chunk. Blocks in
+    /// the neighboring chunks that emit light will light up this chunk even if `border` returns darkness for them.
+    pub fn propagate<'a, A, F>(
+        access: &'a A,
+        neighbors: &Neighbors,
+        registry: &BlockVariantRegistry,
+        mut border: F,
+    ) -> Self
+    where
+        A: ChunkAccess<'a>,
+        F: FnMut(IVec3) -> LightLevel,
+    {
+        let mut light = Self::new();
+        let mut opaque = vec![false; Chunk::USIZE.pow(3)];
+
+        let mut block_queue = VecDeque::new();
+        let mut sky_queue = VecDeque::new();
+
+        for x in Self::BOUNDS.min.x..Self::BOUNDS.max.x {
+            for y in Self::BOUNDS.min.y..Self::BOUNDS.max.y {
+                for z in Self::BOUNDS.min.z..Self::BOUNDS.max.z {
+                    let pos = ivec3(x, y, z);
+
+                    if access.bounds().contains(pos) {
+                        let Ok(output) = access.get(pos) else {
+                            continue;
+                        };
+
+                        let properties = LightProperties::from_block(output.block, registry);
+                        opaque[ivec3_to_1d(pos, Chunk::USIZE).unwrap()] = properties.opaque;
+
+                        if properties.emission > 0 {
+                            light
+                                .set(pos, LightLevel::new(properties.emission, 0))
+                                .unwrap();
+                            block_queue.push_back(pos);
+                        }
+                    } else {
+                        let mut level = border(pos);
+
+                        if let Ok(output) = neighbors.get_3d(pos) {
+                            let properties = LightProperties::from_block(output.block, registry);
+                            level = level.max(LightLevel::new(properties.emission, 0));
+                        }
+
+                        light.set(pos, level).unwrap();
+
+                        if level.block() > 1 {
+                            block_queue.push_back(pos);
+                        }
+
+                        if level.sky() > 1 {
+                            sky_queue.push_back(pos);
+                        }
+                    }
+                }
+            }
+        }
+
+        light.flood(&opaque, block_queue, LightChannel::Block);
+        light.flood(&opaque, sky_queue, LightChannel::Sky);
+
+        light
+    }
+
+    /// Bake the sky access of every voxel in the chunk and its border. Rays are cast upwards (straight up and
+    /// diagonally) from every voxel that isn't opaque, and the sky access is the fraction of the rays that travel
+    /// [`SKY_ACCESS_RANGE`] voxels without hitting an opaque voxel. Only the chunk and the border provided by
+    /// `neighbors` are sampled, so rays that leave them count as reaching the sky. Opaque voxels have no sky access.
+    ///
+    /// This is baked along with the rest of the light by [`ChunkManager::relight_chunk`], which happens before
+    /// every remesh. Edits flag the edited chunk and the neighbors sharing the edited voxels for remeshing, which
+    /// are exactly the chunks whose sky access can change.
+    ///
+    /// [`ChunkManager::relight_chunk`]: super::world::ChunkManager::relight_chunk
+    pub fn bake_sky_access<'a, A>(
+        &mut self,
+        access: &'a A,
+        neighbors: &Neighbors,
+        registry: &BlockVariantRegistry,
+    ) where
+        A: ChunkAccess<'a>,
+    {
+        let mut opaque = vec![false; Self::BUFFER_SIZE];
+
+        for pos in Self::BOUNDS.cartesian_iter() {
+            let properties = if access.bounds().contains(pos) {
+                access
+                    .get(pos)
+                    .ok()
+                    .map(|output| LightProperties::from_block(output.block, registry))
+            } else {
+                neighbors
+                    .get_3d(pos)
+                    .ok()
+                    .map(|output| LightProperties::from_block(output.block, registry))
+            };
+
+            opaque[Self::index(pos).unwrap()] = properties.is_some_and(|p| p.opaque);
+        }
+
+        for pos in Self::BOUNDS.cartesian_iter() {
+            let index = Self::index(pos).unwrap();
+
+            if opaque[index] {
+                self.sky_access[index] = 0;
+                continue;
+            }
+
+            let open = SKY_ACCESS_RAYS
+                .iter()
+                .filter(|&&direction| {
+                    (1..=SKY_ACCESS_RANGE).all(|step| match Self::index(pos + direction * step) {
+                        Ok(next) => !opaque[next],
+                        Err(_) => true,
+                    })
+                })
+                .count() as u32;
+
+            let rays = SKY_ACCESS_RAYS.len() as u32;
+            self.sky_access[index] = ((open * LightLevel::MAX as u32 + rays / 2) / rays) as u8;
+        }
+    }
+
+    fn flood(&mut self, opaque: &[bool], mut queue: VecDeque<IVec3>, channel: LightChannel) {
+        while let Some(pos) = queue.pop_front() {
+            let level = channel.get(self.get(pos).unwrap());
+
+            for face in Face::FACES {
+                let next = pos + face.normal();
+
+                if !Chunk::BOUNDING_BOX.contains(next)
+                    || opaque[ivec3_to_1d(next, Chunk::USIZE).unwrap()]
+                {
+                    continue;
+                }
+
+                let next_level = if channel == LightChannel::Sky
+                    && face == Face::Bottom
+                    && level == LightLevel::MAX
+                {
+                    LightLevel::MAX
+                } else {
+                    level.saturating_sub(1)
+                };
+
+                let current = self.get(next).unwrap();
+                if next_level > channel.get(current) {
+                    self.set(next, channel.with(current, next_level)).unwrap();
+                    queue.push_back(next);
+                }
+            }
+        }
+    }
+}
+
+impl HasBounds for ChunkLight {
+    fn bounds(&self) -> BoundingBox {
+        Self::BOUNDS
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        data::registries::texture::TextureRegistry,
+        testing_utils::MockChunk,
+        topo::{
+            access::WriteAccess,
+            block::{BlockVoxel, FullBlock},
+            neighbors::NeighborsBuilder,
+            world::ChunkAccessInput,
+        },
+    };
+
+    use super::*;
+
+    fn void_neighbors<'a>() -> Neighbors<'a> {
+        NeighborsBuilder::new(BlockVoxel::new_full(BlockVariantRegistry::VOID)).build()
+    }
+
+    #[test]
+    fn light_level_channels() {
+        let level = LightLevel::new(3, 12);
+        assert_eq!(3, level.block());
+        assert_eq!(12, level.sky());
+
+        let clamped = LightLevel::new(200, 16);
+        assert_eq!(LightLevel::MAX, clamped.block());
+        assert_eq!(LightLevel::MAX, clamped.sky());
+
+        assert_eq!(
+            LightLevel::new(5, 12),
+            LightLevel::new(5, 2).max(LightLevel::new(1, 12))
+        );
+    }
+
+    #[test]
+    fn block_light_decays_with_distance() {
+        let texreg = TextureRegistry::new_mock();
+        let registry = BlockVariantRegistry::new_mock(&texreg);
+
+        let chunk = MockChunk::new(BlockVoxel::new_full(BlockVariantRegistry::VOID));
+        let mut access = chunk.access();
+        access
+            .set(
+                ivec3(8, 8, 8),
+                ChunkAccessInput::new(BlockVoxel::new_full(BlockVariantRegistry::LAMP)),
+            )
+            .unwrap();
+        drop(access);
+
+        let light =
+            ChunkLight::propagate(&chunk.read_access(), &void_neighbors(), &registry, |_| {
+                LightLevel::DARK
+            });
+
+        assert_eq!(15, light.get(ivec3(8, 8, 8)).unwrap().block());
+        assert_eq!(14, light.get(ivec3(9, 8, 8)).unwrap().block());
+        assert_eq!(13, light.get(ivec3(9, 9, 8)).unwrap().block());
+        // manhattan distance of 6
+        assert_eq!(9, light.get(ivec3(10, 6, 10)).unwrap().block());
+        // too far away to be lit
+        assert_eq!(0, light.get(ivec3(0, 0, 0)).unwrap().block());
+        assert_eq!(0, light.get(ivec3(8, 8, 8)).unwrap().sky());
+    }
+
+    #[test]
+    fn opaque_blocks_block_light() {
+        let texreg = TextureRegistry::new_mock();
+        let registry = BlockVariantRegistry::new_mock(&texreg);
+
+        let chunk = MockChunk::new(BlockVoxel::new_full(BlockVariantRegistry::VOID));
+        let mut access = chunk.access();
+        access
+            .set(
+                ivec3(2, 8, 8),
+                ChunkAccessInput::new(BlockVoxel::new_full(BlockVariantRegistry::LAMP)),
+            )
+            .unwrap();
+
+        // a wall at x = 4 that light can't go through
+        for y in 0..16 {
+            for z in 0..16 {
+                access
+                    .set(
+                        ivec3(4, y, z),
+                        ChunkAccessInput::new(BlockVoxel::new_full(BlockVariantRegistry::FULL)),
+                    )
+                    .unwrap();
+            }
+        }
+        drop(access);
+
+        let light =
+            ChunkLight::propagate(&chunk.read_access(), &void_neighbors(), &registry, |_| {
+                LightLevel::DARK
+            });
+
+        assert_eq!(14, light.get(ivec3(3, 8, 8)).unwrap().block());
+        assert_eq!(0, light.get(ivec3(4, 8, 8)).unwrap().block());
+        assert_eq!(0, light.get(ivec3(5, 8, 8)).unwrap().block());
+    }
+
+    #[test]
+    fn skylight_and_neighbor_light() {
+        let texreg = TextureRegistry::new_mock();
+        let registry = BlockVariantRegistry::new_mock(&texreg);
+
+        let chunk = MockChunk::new(BlockVoxel::new_full(BlockVariantRegistry::VOID));
+        let mut access = chunk.access();
+        // a roof over part of the chunk
+        access
+            .set(
+                ivec3(5, 10, 5),
+                ChunkAccessInput::new(BlockVoxel::new_full(BlockVariantRegistry::FULL)),
+            )
+            .unwrap();
+        drop(access);
+
+        // full skylight above the chunk, and a light of 10 coming from the chunk to the west
+        let light =
+            ChunkLight::propagate(&chunk.read_access(), &void_neighbors(), &registry, |pos| {
+                match pos {
+                    pos if pos.y == Chunk::SIZE => LightLevel::new(0, LightLevel::MAX),
+                    pos if pos == ivec3(3, 3, -1) => LightLevel::new(10, 0),
+                    _ => LightLevel::DARK,
+                }
+            });
+
+        // skylight doesn't decay going straight down
+        assert_eq!(15, light.get(ivec3(0, 0, 0)).unwrap().sky());
+        // but it does when it has to go around the roof
+        assert_eq!(14, light.get(ivec3(5, 9, 5)).unwrap().sky());
+        assert_eq!(14, light.get(ivec3(5, 0, 5)).unwrap().sky());
+
+        assert_eq!(9, light.get(ivec3(3, 3, 0)).unwrap().block());
+        assert_eq!(7, light.get(ivec3(3, 3, 2)).unwrap().block());
+    }
+
+    #[test]
+    fn sky_access() {
+        let texreg = TextureRegistry::new_mock();
+        let registry = BlockVariantRegistry::new_mock(&texreg);
+
+        let stone = || ChunkAccessInput::new(BlockVoxel::new_full(BlockVariantRegistry::FULL));
+
+        let chunk = MockChunk::new(BlockVoxel::new_full(BlockVariantRegistry::VOID));
+        let mut access = chunk.access();
+        // a floor at y = 0
+        for x in 0..16 {
+            for z in 0..16 {
+                access.set(ivec3(x, 0, z), stone()).unwrap();
+            }
+        }
+
+        // a hollow box around [4, 4, 4]
+        for pos in BoundingBox::from_min_max(ivec3(3, 3, 3), ivec3(6, 6, 6)).cartesian_iter() {
+            if pos != ivec3(4, 4, 4) {
+                access.set(pos, stone()).unwrap();
+            }
+        }
+
+        // a roof over [12, 1, 12], open on the sides
+        access.set(ivec3(12, 3, 12), stone()).unwrap();
+        drop(access);
+
+        let read_access = chunk.read_access();
+        let mut light = ChunkLight::propagate(&read_access, &void_neighbors(), &registry, |_| {
+            LightLevel::new(0, LightLevel::MAX)
+        });
+        light.bake_sky_access(&read_access, &void_neighbors(), &registry);
+
+        // the surface of the floor is open to the sky
+        assert_eq!(Ok(LightLevel::MAX), light.sky_access(ivec3(10, 1, 2)));
+        // opaque voxels have no sky access
+        assert_eq!(Ok(0), light.sky_access(ivec3(10, 0, 2)));
+        assert_eq!(Ok(0), light.sky_access(ivec3(4, 4, 4)));
+
+        // the roof blocks the ray going straight up, but not the diagonal ones
+        let covered = light.sky_access(ivec3(12, 1, 12)).unwrap();
+        assert!(covered > 0 && covered < LightLevel::MAX);
+
+        // skylight leaks into the box through the flood fill, but sampling it is darkened by its sky access
+        light
+            .set(ivec3(4, 4, 4), LightLevel::new(3, LightLevel::MAX))
+            .unwrap();
+        assert_eq!(LightLevel::new(3, 0), light.sample(ivec3(4, 4, 4)));
+        assert_eq!(
+            LightLevel::new(0, LightLevel::MAX),
+            light.sample(ivec3(10, 1, 2))
+        );
+    }
+
+    #[test]
+    fn air_variants_are_empty_and_dark() {
+        let texreg = TextureRegistry::new_mock();
+        let registry = BlockVariantRegistry::new_mock(&texreg);
+        let lamp = CaoBlock::Full(FullBlock::new(BlockVariantRegistry::LAMP));
+
+        assert_ne!(0, LightProperties::from_block(lamp, &registry).emission);
+
+        let registry = registry.with_air(BlockVariantRegistry::LAMP);
+        assert_eq!(
+            LightProperties::AIR,
+            LightProperties::from_block(lamp, &registry)
+        );
+    }
+}
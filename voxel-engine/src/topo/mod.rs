@@ -1,15 +1,21 @@
-use bevy::math::{ivec2, ivec3, IVec2, IVec3};
+use bevy::math::{ivec2, ivec3, vec3, IVec2, IVec3, Vec2, Vec3};
 
 use crate::{data::tile::Face, util::Axis3D};
 
 pub mod access;
 pub mod block;
 pub mod bounding_box;
+pub mod collision;
 pub mod controller;
 mod ecs;
 pub mod error;
+pub mod heightmap;
+pub mod light;
 pub mod neighbors;
+pub mod schematic;
+pub mod selection;
 pub mod storage;
+pub mod store;
 pub mod util;
 pub mod world;
 pub mod worldgen;
@@ -18,6 +24,7 @@ pub use controller::ChunkObserver;
 
 pub use util::*;
 
+/// Project a position on the plane of a face into 3D, see [`Face::project_to_3d`] for the conventions.
 #[inline]
 pub fn ivec_project_to_3d(pos: IVec2, face: Face, mag: i32) -> IVec3 {
     match face.axis() {
@@ -27,6 +34,16 @@ pub fn ivec_project_to_3d(pos: IVec2, face: Face, mag: i32) -> IVec3 {
     }
 }
 
+#[inline]
+pub fn vec_project_to_3d(pos: Vec2, face: Face, mag: f32) -> Vec3 {
+    match face.axis() {
+        Axis3D::X => vec3(mag, pos.y, pos.x),
+        Axis3D::Y => vec3(pos.x, mag, pos.y),
+        Axis3D::Z => vec3(pos.x, pos.y, mag),
+    }
+}
+
+/// The inverse of [`ivec_project_to_3d`], see [`Face::to_plane_coords`].
 #[inline]
 pub fn ivec_project_to_2d(pos: IVec3, face: Face) -> IVec2 {
     match face.axis() {
@@ -1,17 +1,23 @@
-use bevy::math::{ivec3, IVec2, IVec3};
+#[cfg(debug_assertions)]
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use bevy::math::{ivec2, ivec3, IVec2, IVec3};
 
 use crate::{
-    data::tile::Face,
+    data::{
+        registries::block::BlockVariantRegistry,
+        tile::{Face, Transparency},
+    },
+    render::occlusion::BlockOcclusion,
     topo::{
         access::ReadAccess, bounding_box::BoundingBox, ivec_project_to_3d,
         storage::error::OutOfBounds,
     },
-    util::ivec3_to_1d,
 };
 
 use super::{
     block::BlockVoxel,
-    error::NeighborAccessError,
+    error::{NeighborAccessError, SetNeighborError},
     world::{chunk_ref::Crra, Chunk, ChunkAccessOutput},
 };
 
@@ -35,6 +41,12 @@ fn localspace_to_neighbor_localspace(pos: IVec3) -> IVec3 {
 pub struct Neighbors<'a> {
     chunks: [Option<Crra<'a>>; NEIGHBOR_ARRAY_SIZE],
     default: BlockVoxel,
+    /// The radius the neighbors were gathered in, reading neighbors outside of it is a bug.
+    #[cfg(debug_assertions)]
+    radius: u8,
+    /// Bitmask of the neighboring chunks that were read, indexed like `chunks`.
+    #[cfg(debug_assertions)]
+    accessed: AtomicU32,
 }
 
 /// Test if the provided facespace vector is in bounds
@@ -53,6 +65,37 @@ pub fn is_in_bounds_3d(pos: IVec3) -> bool {
     pos.cmpge(min).all() && pos.cmplt(max).all() && localspace_to_chunk_pos(pos) != IVec3::ZERO
 }
 
+/// The number of axes a neighboring chunk is offset along, which is 1 for face neighbors, 2 for edge neighbors,
+/// and 3 for corner neighbors. `offset` is the position of the neighbor relative to the center chunk.
+pub fn neighbor_radius(offset: IVec3) -> u8 {
+    offset.to_array().into_iter().filter(|&c| c != 0).count() as u8
+}
+
+pub const NEIGHBOR_MAX_RADIUS: u8 = 3;
+
+/// The index of the neighbor at `offset` (relative to the center chunk) in an array of neighbors, same as
+/// `ivec3_to_1d(offset + IVec3::ONE, NEIGHBOR_CUBIC_ARRAY_DIMENSIONS)` but without the conversion error.
+/// Every component of `offset` must be in `-1..=1`, this is only checked in debug builds.
+#[inline]
+pub const fn neighbor_index(offset: IVec3) -> usize {
+    debug_assert!(
+        offset.x >= -1
+            && offset.x <= 1
+            && offset.y >= -1
+            && offset.y <= 1
+            && offset.z >= -1
+            && offset.z <= 1
+    );
+
+    const DIMS: usize = NEIGHBOR_CUBIC_ARRAY_DIMENSIONS;
+
+    let x = (offset.x + 1) as usize;
+    let y = (offset.y + 1) as usize;
+    let z = (offset.z + 1) as usize;
+
+    (z * DIMS * DIMS) + (y * DIMS) + x
+}
+
 pub type NbResult<'a> = Result<ChunkAccessOutput<'a>, NeighborAccessError>;
 
 pub const NEIGHBOR_CUBIC_ARRAY_DIMENSIONS: usize = 3;
@@ -60,7 +103,67 @@ pub const NEIGHBOR_ARRAY_SIZE: usize = NEIGHBOR_CUBIC_ARRAY_DIMENSIONS.pow(3);
 
 impl<'a> Neighbors<'a> {
     pub fn from_raw(chunks: [Option<Crra<'a>>; NEIGHBOR_ARRAY_SIZE], default: BlockVoxel) -> Self {
-        Self { chunks, default }
+        Self {
+            chunks,
+            default,
+            #[cfg(debug_assertions)]
+            radius: NEIGHBOR_MAX_RADIUS,
+            #[cfg(debug_assertions)]
+            accessed: AtomicU32::new(0),
+        }
+    }
+
+    /// Set (or replace) the neighboring chunk at `pos`, relative to the center chunk.
+    pub fn set_neighbor(&mut self, pos: IVec3, access: Crra<'a>) -> Result<(), SetNeighborError> {
+        if pos == IVec3::ZERO {
+            return Err(SetNeighborError::CenterChunk);
+        }
+
+        if !is_valid_neighbor_chunk_pos(pos) {
+            return Err(SetNeighborError::OutOfRange);
+        }
+
+        self.chunks[neighbor_index(pos)] = Some(access);
+
+        Ok(())
+    }
+
+    /// Declare the radius (see [`neighbor_radius`]) these neighbors were gathered in. In debug builds reading a
+    /// neighbor outside of this radius panics, since it would silently read the default block instead.
+    /// In release builds this does nothing.
+    #[allow(unused_mut, unused_variables)]
+    pub fn with_radius(mut self, radius: u8) -> Self {
+        #[cfg(debug_assertions)]
+        {
+            self.radius = radius;
+        }
+
+        self
+    }
+
+    /// The positions (relative to the center chunk) of the neighboring chunks that have been read so far.
+    /// Only tracked in debug builds.
+    #[cfg(debug_assertions)]
+    pub fn accessed(&self) -> Vec<IVec3> {
+        let accessed = self.accessed.load(Ordering::Relaxed);
+
+        (0..NEIGHBOR_ARRAY_SIZE)
+            .filter(|&i| accessed & (1 << i) != 0)
+            .map(|i| {
+                let dims = NEIGHBOR_CUBIC_ARRAY_DIMENSIONS;
+                ivec3(
+                    (i % dims) as i32,
+                    ((i / dims) % dims) as i32,
+                    (i / (dims * dims)) as i32,
+                ) - IVec3::ONE
+            })
+            .collect()
+    }
+
+    /// The number of neighboring chunks that were gathered. Neighbors that weren't gathered are read as
+    /// the default block.
+    pub fn gathered(&self) -> usize {
+        self.chunks.iter().filter(|chunk| chunk.is_some()).count()
     }
 
     /// `pos` is in localspace
@@ -72,14 +175,21 @@ impl<'a> Neighbors<'a> {
             return Err(NeighborAccessError::OutOfBounds);
         }
 
-        let chk_index = ivec3_to_1d(chk_pos + IVec3::ONE, NEIGHBOR_CUBIC_ARRAY_DIMENSIONS)
-            .map_err(|_| NeighborAccessError::OutOfBounds)?;
-        let chk = self
-            .chunks
-            .get(chk_index)
-            .ok_or(NeighborAccessError::OutOfBounds)?;
+        // callers make sure that the position is at most 1 block outside of the center chunk
+        let chk_index = neighbor_index(chk_pos);
 
-        match chk {
+        #[cfg(debug_assertions)]
+        {
+            assert!(
+                neighbor_radius(chk_pos) <= self.radius,
+                "read neighbor {chk_pos} outside of the gathered radius {}",
+                self.radius
+            );
+
+            self.accessed.fetch_or(1 << chk_index, Ordering::Relaxed);
+        }
+
+        match &self.chunks[chk_index] {
             Some(access) => {
                 let neighbor_local = localspace_to_neighbor_localspace(pos);
                 Ok(access.get(neighbor_local)?)
@@ -116,377 +226,446 @@ impl<'a> Neighbors<'a> {
     }
 }
 
-fn is_valid_neighbor_chunk_pos(pos: IVec3) -> bool {
-    const BB: BoundingBox = BoundingBox {
-        min: IVec3::splat(-1),
-        max: IVec3::ONE,
+/// The offsets of the neighboring chunks that share a face, edge, or corner with the voxel at the given
+/// localspace position. Voxels in the interior of a chunk don't touch any neighbors, voxels on a face touch 1,
+/// voxels on an edge touch 3, and voxels on a corner touch 7.
+pub fn touched_neighbors(local: IVec3) -> Vec<IVec3> {
+    let axis_offsets = |c: i32| match c {
+        0 => [0, -1],
+        c if c == Chunk::SIZE - 1 => [0, 1],
+        _ => [0, 0],
     };
 
-    pos != IVec3::ZERO && BB.contains_inclusive(pos)
-}
-
-pub struct NeighborsBuilder<'a>(Neighbors<'a>);
+    let [xs, ys, zs] = local.to_array().map(axis_offsets);
 
-impl<'a> NeighborsBuilder<'a> {
-    pub fn new(default: BlockVoxel) -> Self {
-        Self(Neighbors::from_raw(Default::default(), default))
+    let mut offsets = Vec::new();
+    for x in xs {
+        for y in ys {
+            for z in zs {
+                let offset = ivec3(x, y, z);
+                if offset != IVec3::ZERO && !offsets.contains(&offset) {
+                    offsets.push(offset);
+                }
+            }
+        }
     }
 
-    pub fn set_neighbor(&mut self, pos: IVec3, access: Crra<'a>) -> Result<(), OutOfBounds> {
-        if !is_valid_neighbor_chunk_pos(pos) {
-            return Err(OutOfBounds);
-        }
+    offsets
+}
 
-        let idx = ivec3_to_1d(pos + IVec3::ONE, NEIGHBOR_CUBIC_ARRAY_DIMENSIONS)
-            .map_err(|_| OutOfBounds)?;
+/// A set of neighbor offsets, stored as a bitmask indexed by [`neighbor_index`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct NeighborSet(u32);
 
-        let slot = self.0.chunks.get_mut(idx).ok_or(OutOfBounds)?;
-        *slot = Some(access);
+impl NeighborSet {
+    pub const EMPTY: Self = Self(0);
 
-        Ok(())
+    /// The neighbors sharing a face with the chunk.
+    pub fn faces() -> Self {
+        Face::FACES.map(Face::normal).into_iter().collect()
     }
 
-    pub fn build(self) -> Neighbors<'a> {
-        self.0
+    pub fn from_raw(raw: u32) -> Self {
+        Self(raw)
     }
-}
-
-/* TODO: fix this madness
 
-#[cfg(test)]
-mod tests {
-    use bevy::math::ivec2;
+    pub fn raw(self) -> u32 {
+        self.0
+    }
 
-    use crate::{
-        data::{
-            registries::{block::{BlockVariantId, BlockVariantRegistry}, Registry},
-            tile::Transparency,
-        },
-        topo::{access::{self, HasBounds}, block::FullBlock},
-    };
+    /// Panics in debug builds if `offset` isn't a valid neighbor offset.
+    pub fn insert(&mut self, offset: IVec3) {
+        debug_assert!(is_valid_neighbor_chunk_pos(offset));
+        self.0 |= 1 << neighbor_index(offset);
+    }
 
-    use super::*;
+    pub fn contains(self, offset: IVec3) -> bool {
+        is_valid_neighbor_chunk_pos(offset) && self.0 & (1 << neighbor_index(offset)) != 0
+    }
 
-    fn make_blockvxl(id: u32) -> BlockVoxel {
-        BlockVoxel::Full(FullBlock {
-            rotation: None,
-            block: BlockVariantId::new(id)
-        })
+    pub fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
     }
 
-    struct TestAccess {
-        even: BlockVoxel,
-        odd: BlockVoxel,
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
     }
 
-    impl ChunkBounds for TestAccess {}
-    impl access::ReadAccess for TestAccess {
-        type ReadErr = OutOfBounds;
-        type ReadType = ChunkVoxelOutput<'a>;
+    pub fn len(self) -> usize {
+        self.0.count_ones() as usize
+    }
 
-        fn get(&self, pos: IVec3) -> Result<Self::ReadType, Self::ReadErr> {
-            if !self.bounds().contains(pos) {
-                return Err(OutOfBounds);
-            }
+    /// Iterate over the offsets in this set.
+    pub fn iter(self) -> impl Iterator<Item = IVec3> {
+        BoundingBox::from_min_max(IVec3::splat(-1), IVec3::splat(2))
+            .cartesian_iter()
+            .filter(move |&offset| self.contains(offset))
+    }
+}
 
-            if (pos % 2).cmpeq(IVec3::ZERO).any() {
-                Ok(ChunkVoxelOutput::new(&self.even))
-            } else {
-                Ok(ChunkVoxelOutput::new(&self.odd))
-            }
+impl FromIterator<IVec3> for NeighborSet {
+    fn from_iter<T: IntoIterator<Item = IVec3>>(iter: T) -> Self {
+        let mut set = Self::EMPTY;
+        for offset in iter {
+            set.insert(offset);
         }
-    }
 
-    fn make_test_neighbors<'a>() -> Neighbors<'a, TestAccess> {
-        let mut builder = NeighborsBuilder::<TestAccess>::new(make_blockvxl(0));
+        set
+    }
+}
 
-        // FACES
+fn is_valid_neighbor_chunk_pos(pos: IVec3) -> bool {
+    const BB: BoundingBox = BoundingBox {
+        min: IVec3::splat(-1),
+        max: IVec3::ONE,
+    };
 
-        builder
-            .set_neighbor(ivec3(1, 0, 0), TestAccess { even: 1, odd: 2 })
-            .unwrap();
+    pos != IVec3::ZERO && BB.contains_inclusive(pos)
+}
 
-        builder
-            .set_neighbor(ivec3(-1, 0, 0), TestAccess { even: 3, odd: 4 })
-            .unwrap();
+pub struct NeighborsBuilder<'a>(Neighbors<'a>);
 
-        builder
-            .set_neighbor(ivec3(0, 1, 0), TestAccess { even: 5, odd: 6 })
-            .unwrap();
+impl<'a> NeighborsBuilder<'a> {
+    pub fn new(default: BlockVoxel) -> Self {
+        Self(Neighbors::from_raw(Default::default(), default))
+    }
 
-        builder
-            .set_neighbor(ivec3(0, -1, 0), TestAccess { even: 7, odd: 8 })
-            .unwrap();
+    pub fn set_neighbor(&mut self, pos: IVec3, access: Crra<'a>) -> Result<(), SetNeighborError> {
+        self.0.set_neighbor(pos, access)
+    }
 
-        builder
-            .set_neighbor(ivec3(0, 0, 1), TestAccess { even: 9, odd: 10 })
-            .unwrap();
+    pub fn build(self) -> Neighbors<'a> {
+        self.0
+    }
+}
 
-        builder
-            .set_neighbor(ivec3(0, 0, -1), TestAccess { even: 11, odd: 12 })
-            .unwrap();
+/// The transparency of the blocks in the neighboring chunks that are directly adjacent to each face of a chunk.
+/// Positions are in facespace. A neighboring block is opaque if the side of it that touches the chunk is
+/// entirely covered by opaque (micro)blocks.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AdjacentTransparency([[Transparency; Self::PLANE_SIZE]; Face::FACES.len()]);
 
-        // EDGES
+impl AdjacentTransparency {
+    const PLANE_SIZE: usize = Chunk::USIZE * Chunk::USIZE;
 
-        builder
-            .set_neighbor(ivec3(1, 1, 0), TestAccess { even: 20, odd: 21 })
-            .unwrap();
+    /// Create adjacent transparency where every adjacent block has the given transparency.
+    pub fn new(transparency: Transparency) -> Self {
+        Self([[transparency; Self::PLANE_SIZE]; Face::FACES.len()])
+    }
 
-        builder
-            .set_neighbor(ivec3(-1, 1, 0), TestAccess { even: 22, odd: 23 })
-            .unwrap();
+    /// Derive the adjacent transparency from the border of the neighboring chunks, so that it always
+    /// agrees with the neighbors used for meshing.
+    pub fn from_neighbors(neighbors: &Neighbors, registry: &BlockVariantRegistry) -> Self {
+        let mut adjacent = Self::new(Transparency::Opaque);
+
+        for face in Face::FACES {
+            for x in 0..Chunk::SIZE {
+                for y in 0..Chunk::SIZE {
+                    let pos = ivec2(x, y);
+
+                    let Ok(output) = neighbors.get(face, pos) else {
+                        continue;
+                    };
+
+                    // the side of the neighboring block that touches this chunk
+                    let touching = face.opposite();
+                    let transparency = if BlockOcclusion::from_block(output.block, registry)
+                        .is_occluded(touching)
+                    {
+                        Transparency::Opaque
+                    } else {
+                        Transparency::Transparent
+                    };
+
+                    adjacent.set(face, pos, transparency).unwrap();
+                }
+            }
+        }
 
-        builder
-            .set_neighbor(ivec3(0, 1, 1), TestAccess { even: 24, odd: 25 })
-            .unwrap();
+        adjacent
+    }
 
-        builder
-            .set_neighbor(ivec3(0, 1, -1), TestAccess { even: 26, odd: 27 })
-            .unwrap();
+    fn index(pos: IVec2) -> Result<usize, OutOfBounds> {
+        if pos.cmplt(IVec2::ZERO).any() || pos.cmpge(IVec2::splat(Chunk::SIZE)).any() {
+            return Err(OutOfBounds);
+        }
 
-        // CORNERS
+        Ok((pos.y as usize * Chunk::USIZE) + pos.x as usize)
+    }
 
-        builder
-            .set_neighbor(ivec3(1, 1, 1), TestAccess { even: 30, odd: 31 })
-            .unwrap();
+    /// Get the transparency of the block adjacent to the given face at the given facespace position.
+    pub fn get(&self, face: Face, pos: IVec2) -> Result<Transparency, OutOfBounds> {
+        Ok(self.0[face.as_usize()][Self::index(pos)?])
+    }
 
-        builder
-            .set_neighbor(ivec3(-1, 1, 1), TestAccess { even: 32, odd: 33 })
-            .unwrap();
+    pub fn set(
+        &mut self,
+        face: Face,
+        pos: IVec2,
+        transparency: Transparency,
+    ) -> Result<(), OutOfBounds> {
+        self.0[face.as_usize()][Self::index(pos)?] = transparency;
+        Ok(())
+    }
+}
 
-        builder
-            .set_neighbor(ivec3(1, 1, -1), TestAccess { even: 34, odd: 35 })
-            .unwrap();
+#[cfg(test)]
+mod tests {
+    use bevy::math::uvec3;
 
-        builder
-            .set_neighbor(ivec3(-1, 1, -1), TestAccess { even: 36, odd: 37 })
-            .unwrap();
+    use crate::{
+        data::registries::{block::BlockVariantId, texture::TextureRegistry},
+        testing_utils::MockChunk,
+        topo::{
+            access::WriteAccess,
+            block::{Microblock, SubdividedBlock},
+            world::{chunk_ref::CaoBlock, ChunkAccessInput},
+        },
+    };
 
-        builder.build()
-    }
+    use super::*;
 
     #[test]
-    fn test_builder() {
-        const DUMMY: TestAccess = TestAccess { even: 0, odd: 0 };
-
-        let mut builder = NeighborsBuilder::<TestAccess>::new(make_blockvxl(0));
-
-        assert!(builder.set_neighbor(ivec3(0, 0, 0), DUMMY).is_err());
-        assert!(builder.set_neighbor(ivec3(1, 1, 1), DUMMY).is_ok());
-        assert!(builder.set_neighbor(ivec3(-1, -1, -1), DUMMY).is_ok());
-        assert!(builder.set_neighbor(ivec3(-1, -2, -1), DUMMY).is_err());
+    fn neighbor_index_matches_checked() {
+        for x in -1..=1 {
+            for y in -1..=1 {
+                for z in -1..=1 {
+                    let offset = ivec3(x, y, z);
+
+                    assert_eq!(
+                        crate::util::ivec3_to_1d(
+                            offset + IVec3::ONE,
+                            NEIGHBOR_CUBIC_ARRAY_DIMENSIONS
+                        )
+                        .unwrap(),
+                        neighbor_index(offset)
+                    );
+                }
+            }
+        }
     }
 
     #[test]
-    fn test_neighbors() {
-        let neighbors = make_test_neighbors();
-
-        dbg!(ivec3(0, 0, 0) % 2);
-
-        assert_eq!(
-            0,
-            neighbors
-                .get(Face::Bottom, ivec2(-1, 0))
-                .unwrap()
-                .variant
-                .as_u32()
-        );
+    fn set_neighbor_errors() {
+        let chunk = MockChunk::new(BlockVoxel::new_full(BlockVariantRegistry::VOID));
+        let mut builder = NeighborsBuilder::new(BlockVoxel::new_full(BlockVariantRegistry::VOID));
 
         assert_eq!(
-            7,
-            neighbors
-                .get(Face::Bottom, ivec2(0, 0))
-                .unwrap()
-                .variant
-                .as_u32()
-        );
-        assert_eq!(
-            8,
-            neighbors
-                .get(Face::Bottom, ivec2(1, 1))
-                .unwrap()
-                .variant
-                .as_u32()
+            Err(SetNeighborError::CenterChunk),
+            builder.set_neighbor(IVec3::ZERO, chunk.read_access())
         );
-
         assert_eq!(
-            7,
-            neighbors
-                .get(Face::Bottom, ivec2(6, 10))
-                .unwrap()
-                .variant
-                .as_u32()
+            Err(SetNeighborError::OutOfRange),
+            builder.set_neighbor(ivec3(2, 0, 0), chunk.read_access())
         );
         assert_eq!(
-            8,
-            neighbors
-                .get(Face::Bottom, ivec2(5, 5))
-                .unwrap()
-                .variant
-                .as_u32()
+            Err(SetNeighborError::OutOfRange),
+            builder.set_neighbor(ivec3(-1, -2, -1), chunk.read_access())
         );
-
         assert_eq!(
-            0,
-            neighbors
-                .get(Face::Bottom, ivec2(16, 16))
-                .unwrap()
-                .variant
-                .as_u32()
+            Ok(()),
+            builder.set_neighbor(ivec3(1, 1, 1), chunk.read_access())
         );
+    }
 
-        assert!(neighbors.get(Face::Bottom, ivec2(16, 17)).is_err());
-        assert!(neighbors.get(Face::Bottom, ivec2(-2, 5)).is_err());
-
-        // EDGES
-
-        assert_eq!(
-            5,
-            neighbors
-                .get(Face::Top, ivec2(0, 0))
-                .unwrap()
-                .variant
-                .as_u32()
-        );
+    #[test]
+    fn adjacent_transparency_from_neighbors() {
+        let texreg = TextureRegistry::new_mock();
+        let registry = BlockVariantRegistry::new_mock(&texreg);
+
+        let above = MockChunk::new(BlockVoxel::new_full(BlockVariantRegistry::VOID));
+        let mut access = above.access();
+
+        access
+            .set(
+                ivec3(3, 0, 5),
+                ChunkAccessInput::new(BlockVoxel::new_full(BlockVariantRegistry::FULL)),
+            )
+            .unwrap();
 
-        assert_eq!(
-            20,
-            neighbors
-                .get(Face::Top, ivec2(16, 5))
-                .unwrap()
-                .variant
-                .as_u32()
-        );
+        // subdivided block with an opaque bottom layer, which touches the chunk below
+        let mut bottom_layer = SubdividedBlock::new(Microblock::new(BlockVariantRegistry::VOID));
+        // subdivided block with an opaque top layer, which doesn't
+        let mut top_layer = SubdividedBlock::new(Microblock::new(BlockVariantRegistry::VOID));
+        for x in 0..4 {
+            for z in 0..4 {
+                let mb = Microblock::new(BlockVariantRegistry::SUBDIV);
+                bottom_layer.set(uvec3(x, 0, z), mb).unwrap();
+                top_layer.set(uvec3(x, 3, z), mb).unwrap();
+            }
+        }
 
-        assert_eq!(
-            20,
-            neighbors
-                .get(Face::North, ivec2(6, 16))
-                .unwrap()
-                .variant
-                .as_u32()
-        );
+        access
+            .set(
+                ivec3(4, 0, 5),
+                ChunkAccessInput::new(BlockVoxel::Subdivided(bottom_layer)),
+            )
+            .unwrap();
+        access
+            .set(
+                ivec3(5, 0, 5),
+                ChunkAccessInput::new(BlockVoxel::Subdivided(top_layer)),
+            )
+            .unwrap();
+        drop(access);
+
+        // missing neighbors are filled with the default block, which is opaque here
+        let mut builder = NeighborsBuilder::new(BlockVoxel::new_full(BlockVariantRegistry::FULL));
+        builder.set_neighbor(IVec3::Y, above.read_access()).unwrap();
+        let neighbors = builder.build();
+
+        let mut expected = AdjacentTransparency::new(Transparency::Opaque);
+        for x in 0..Chunk::SIZE {
+            for y in 0..Chunk::SIZE {
+                expected
+                    .set(Face::Top, ivec2(x, y), Transparency::Transparent)
+                    .unwrap();
+            }
+        }
+        expected
+            .set(Face::Top, ivec2(3, 5), Transparency::Opaque)
+            .unwrap();
+        expected
+            .set(Face::Top, ivec2(4, 5), Transparency::Opaque)
+            .unwrap();
 
         assert_eq!(
-            1,
-            neighbors
-                .get(Face::North, ivec2(6, 6))
-                .unwrap()
-                .variant
-                .as_u32()
+            expected,
+            AdjacentTransparency::from_neighbors(&neighbors, &registry)
         );
+    }
 
-        assert_eq!(
-            22,
-            neighbors
-                .get(Face::Top, ivec2(-1, 5))
-                .unwrap()
-                .variant
-                .as_u32()
-        );
+    /// The neighbor offset and neighbor localspace position of a localspace position just outside of the
+    /// center chunk, worked out axis by axis instead of with the euclidean division used by [`Neighbors`].
+    fn expected_neighbor_pos(pos: IVec3) -> (IVec3, IVec3) {
+        let split = |c: i32| {
+            if c < 0 {
+                (-1, c + Chunk::SIZE)
+            } else if c >= Chunk::SIZE {
+                (1, c - Chunk::SIZE)
+            } else {
+                (0, c)
+            }
+        };
 
-        assert_eq!(
-            24,
-            neighbors
-                .get(Face::Top, ivec2(5, 16))
-                .unwrap()
-                .variant
-                .as_u32()
-        );
+        let [(ox, lx), (oy, ly), (oz, lz)] = pos.to_array().map(split);
+        (ivec3(ox, oy, oz), ivec3(lx, ly, lz))
+    }
 
-        assert_eq!(
-            26,
-            neighbors
-                .get(Face::Top, ivec2(5, -1))
-                .unwrap()
-                .variant
-                .as_u32()
-        );
+    /// A unique block ID for every voxel in every neighbor.
+    fn marker(offset: IVec3, local: IVec3) -> BlockVariantId {
+        let size = Chunk::SIZE as u32;
+        let local = local.as_uvec3();
+        let index = neighbor_index(offset) as u32;
 
-        // CORNERS
+        BlockVariantId::new(
+            1 + index * size.pow(3) + local.x * size.pow(2) + local.y * size + local.z,
+        )
+    }
 
-        assert_eq!(
-            30,
-            neighbors
-                .get(Face::Top, ivec2(16, 16))
-                .unwrap()
-                .variant
-                .as_u32()
-        );
+    /// Every localspace position in the layer of voxels surrounding the center chunk.
+    fn shell() -> impl Iterator<Item = IVec3> {
+        let range = -1..=Chunk::SIZE;
 
-        assert_eq!(
-            32,
-            neighbors
-                .get(Face::Top, ivec2(-1, 16))
-                .unwrap()
-                .variant
-                .as_u32()
-        );
+        range
+            .clone()
+            .flat_map(move |x| range.clone().map(move |y| (x, y)))
+            .flat_map(|(x, y)| (-1..=Chunk::SIZE).map(move |z| ivec3(x, y, z)))
+            .filter(|pos| !(pos.cmpge(IVec3::ZERO).all() && pos.cmplt(Chunk::VEC).all()))
+    }
 
-        assert_eq!(
-            34,
-            neighbors
-                .get(Face::Top, ivec2(16, -1))
-                .unwrap()
-                .variant
-                .as_u32()
-        );
+    /// All 26 neighbors, with the voxels touching the center chunk set to their [`marker`].
+    fn marked_neighbor_chunks() -> Vec<(IVec3, MockChunk)> {
+        let chunks = (-1..=1)
+            .flat_map(|x| (-1..=1).flat_map(move |y| (-1..=1).map(move |z| ivec3(x, y, z))))
+            .filter(|&offset| offset != IVec3::ZERO)
+            .map(|offset| {
+                let chunk = MockChunk::new(BlockVoxel::new_full(BlockVariantRegistry::VOID));
+                (offset, chunk)
+            })
+            .collect::<Vec<_>>();
+
+        for pos in shell() {
+            let (offset, local) = expected_neighbor_pos(pos);
+            let (_, chunk) = chunks.iter().find(|(o, _)| *o == offset).unwrap();
+
+            chunk
+                .access()
+                .set(
+                    local,
+                    ChunkAccessInput::new(BlockVoxel::new_full(marker(offset, local))),
+                )
+                .unwrap();
+        }
 
-        assert_eq!(
-            36,
-            neighbors
-                .get(Face::Top, ivec2(-1, -1))
-                .unwrap()
-                .variant
-                .as_u32()
-        );
+        chunks
     }
 
-    #[test]
-    fn test_neighbors_3d() {
-        let neighbors = make_test_neighbors();
+    fn read_id(result: NbResult<'_>) -> BlockVariantId {
+        match result.unwrap().block {
+            CaoBlock::Full(block) => block.id,
+            CaoBlock::Subdivided(_) => panic!("expected a full block"),
+        }
+    }
 
-        assert_eq!(
-            1,
-            neighbors.get_3d(ivec3(16, 5, 5)).unwrap().variant.as_u32()
-        );
-        assert!(neighbors.get_3d(ivec3(17, 5, 5)).is_err());
-        assert!(neighbors.get_3d(ivec3(5, 5, 5)).is_err());
+    fn build_neighbors(chunks: &[(IVec3, MockChunk)]) -> Neighbors<'_> {
+        let mut builder = NeighborsBuilder::new(BlockVoxel::new_full(BlockVariantRegistry::VOID));
+        for (offset, chunk) in chunks {
+            builder.set_neighbor(*offset, chunk.read_access()).unwrap();
+        }
 
-        assert_eq!(
-            4,
-            neighbors.get_3d(ivec3(-1, 5, 5)).unwrap().variant.as_u32()
-        );
+        builder.build()
     }
 
     #[test]
-    fn test_chunkspace_to_chunk_pos() {
-        // for readability's sake
-        fn f(x: i32, y: i32, z: i32) -> IVec3 {
-            localspace_to_chunk_pos(ivec3(x, y, z))
+    fn neighbors_3d_sample_all_neighbors() {
+        let chunks = marked_neighbor_chunks();
+        let neighbors = build_neighbors(&chunks);
+
+        for pos in shell() {
+            let (offset, local) = expected_neighbor_pos(pos);
+            assert_eq!(
+                marker(offset, local),
+                read_id(neighbors.get_3d(pos)),
+                "{pos}"
+            );
         }
 
-        assert_eq!(ivec3(0, 0, 0), f(8, 5, 6));
-        assert_eq!(ivec3(0, 0, 0), f(0, 0, 0));
-        assert_eq!(ivec3(0, 0, 0), f(15, 15, 15));
-        assert_eq!(ivec3(0, 0, 1), f(15, 15, 16));
-        assert_eq!(ivec3(0, -1, 0), f(0, -1, 0));
-        assert_eq!(ivec3(0, -1, 1), f(0, -1, 16));
+        assert!(neighbors.get_3d(ivec3(5, 5, 5)).is_err());
+        assert!(neighbors.get_3d(ivec3(-2, 5, 5)).is_err());
+        assert!(neighbors.get_3d(ivec3(5, Chunk::SIZE + 1, 5)).is_err());
     }
 
     #[test]
-    fn test_move_pos_origin() {
-        // for readability's sake
-        fn f(x: i32, y: i32, z: i32) -> IVec3 {
-            localspace_to_neighbor_localspace(ivec3(x, y, z))
-        }
+    fn neighbors_facespace_sample_all_neighbors() {
+        let chunks = marked_neighbor_chunks();
+        let neighbors = build_neighbors(&chunks);
+
+        for face in Face::FACES {
+            for x in -1..=Chunk::SIZE {
+                for y in -1..=Chunk::SIZE {
+                    // the layer of voxels just outside the face, written out per face so this doesn't
+                    // rely on the projection used by `Neighbors::get`
+                    let pos = match face {
+                        Face::Top => ivec3(x, Chunk::SIZE, y),
+                        Face::Bottom => ivec3(x, -1, y),
+                        Face::North => ivec3(Chunk::SIZE, y, x),
+                        Face::South => ivec3(-1, y, x),
+                        Face::East => ivec3(x, y, Chunk::SIZE),
+                        Face::West => ivec3(x, y, -1),
+                    };
+
+                    let (offset, local) = expected_neighbor_pos(pos);
+                    assert_eq!(
+                        marker(offset, local),
+                        read_id(neighbors.get(face, ivec2(x, y))),
+                        "{face:?} {x} {y}"
+                    );
+                }
+            }
 
-        assert_eq!(ivec3(5, 5, 5), f(5, 5, 5));
-        assert_eq!(ivec3(0, 0, 0), f(0, 0, 0));
-        assert_eq!(ivec3(0, 15, 0), f(0, -1, 0));
-        assert_eq!(ivec3(0, 0, 5), f(0, 16, 5));
+            assert!(neighbors.get(face, ivec2(-2, 0)).is_err());
+            assert!(neighbors.get(face, ivec2(0, Chunk::SIZE + 1)).is_err());
+        }
     }
 }
-
-
-*/
@@ -0,0 +1,551 @@
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use bevy::{
+    ecs::system::{ResMut, Resource},
+    log::error,
+    math::{IVec3, UVec3},
+};
+
+use crate::{
+    data::{
+        registries::{
+            block::{BlockVariantId, BlockVariantRegistry},
+            Registry,
+        },
+        resourcepath::ResourcePath,
+        tile::Face,
+        voxel::rotations::BlockModelRotation,
+    },
+    topo::{
+        block::{BlockVoxel, FullBlock, Microblock, SubdividedBlock},
+        bounding_box::BoundingBox,
+        controller::TicketedLoads,
+        selection::Selection,
+        world::{
+            chunk_manager::LoadTicket, ChunkAccessInput, ChunkManager, ChunkManagerError,
+            VoxelRealm,
+        },
+    },
+    util::{space::voxel_to_chunk, ChunkSet},
+};
+
+#[derive(te::Error, Debug)]
+pub enum SchematicError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Serialization(#[from] serde_json::Error),
+    #[error("Schematic uses block variant '{0}' which isn't registered")]
+    UnknownVariant(ResourcePath),
+    #[error("Schematic palette has no entry {0}")]
+    InvalidPaletteIndex(u32),
+    #[error("Schematic has a block rotation where the front and up faces aren't orthogonal")]
+    InvalidRotation,
+    #[error("Schematic has a subdivided block with {0} microblocks")]
+    InvalidMicroblockCount(usize),
+}
+
+/// A copy of the voxels in a region of the world, that can be saved to a file and pasted elsewhere.
+/// Voxel positions are stored relative to an anchor, which is the position the schematic is pasted at.
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct Schematic {
+    voxels: Vec<(IVec3, BlockVoxel)>,
+}
+
+impl Schematic {
+    /// Copy the voxels in the selection, relative to `anchor`.
+    /// Returns [`ChunkManagerError::Unloaded`] if any chunk in the selection isn't loaded.
+    pub fn copy(
+        cm: &ChunkManager,
+        selection: &Selection,
+        anchor: IVec3,
+    ) -> Result<Self, ChunkManagerError> {
+        let voxels = cm
+            .read_selection(selection)?
+            .into_iter()
+            .map(|(ws, block)| (ws - anchor, block))
+            .collect();
+
+        Ok(Self { voxels })
+    }
+
+    /// The voxels in this schematic, with positions relative to the anchor.
+    pub fn voxels(&self) -> impl Iterator<Item = (IVec3, &BlockVoxel)> + '_ {
+        self.voxels.iter().map(|(pos, block)| (*pos, block))
+    }
+
+    pub fn len(&self) -> usize {
+        self.voxels.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.voxels.is_empty()
+    }
+
+    /// The region covered by this schematic relative to the anchor, or `None` if it's empty.
+    pub fn bounds(&self) -> Option<BoundingBox> {
+        let min = self.voxels.iter().map(|(pos, _)| *pos).reduce(IVec3::min)?;
+        let max = self.voxels.iter().map(|(pos, _)| *pos).reduce(IVec3::max)?;
+
+        Some(BoundingBox::from_min_max(min, max + IVec3::ONE))
+    }
+
+    /// This schematic rotated around its anchor. The rotations of the blocks (and the microblocks in
+    /// subdivided blocks) are rotated along with their positions. Blocks without a rotation are left
+    /// without one, since they look the same from every direction.
+    pub fn rotated(&self, rotation: BlockModelRotation) -> Self {
+        let voxels = self
+            .voxels
+            .iter()
+            .map(|(pos, block)| (rotation.rotate(*pos), rotate_block(block, rotation)))
+            .collect();
+
+        Self { voxels }
+    }
+
+    /// The chunks that pasting this schematic at `origin` writes to.
+    pub fn target_chunks(&self, origin: IVec3, rotation: BlockModelRotation) -> ChunkSet {
+        let mut chunks = ChunkSet::default();
+        for (pos, _) in &self.voxels {
+            chunks.set(voxel_to_chunk(origin + rotation.rotate(*pos)));
+        }
+
+        chunks
+    }
+
+    /// Paste this schematic with its anchor at `origin`, rotated by `rotation`, in a single
+    /// [`ChunkManager::edit_transaction`]. If any of the target chunks isn't loaded nothing is pasted and
+    /// [`ChunkManagerError::Unloaded`] is returned, see [`PendingPastes`] for pasting into unloaded chunks.
+    pub fn paste(
+        &self,
+        cm: &ChunkManager,
+        origin: IVec3,
+        rotation: BlockModelRotation,
+    ) -> Result<(), ChunkManagerError> {
+        cm.edit_transaction(|tx| {
+            for (pos, block) in &self.voxels {
+                tx.set(
+                    origin + rotation.rotate(*pos),
+                    ChunkAccessInput::new(rotate_block(block, rotation)),
+                );
+            }
+        })
+    }
+
+    /// Write this schematic as JSON. Block variants are written by their label (see [`BlockVariantRegistry::label`])
+    /// so the schematic can be read with a registry where the variants have different IDs.
+    pub fn write<W: Write>(
+        &self,
+        writer: W,
+        registry: &BlockVariantRegistry,
+    ) -> Result<(), SchematicError> {
+        let mut palette = Vec::<ResourcePath>::new();
+        let mut indices = hb::HashMap::<BlockVariantId, u32>::new();
+
+        let mut entry = |id: BlockVariantId, rotation: Option<BlockModelRotation>| {
+            let index = *indices.entry(id).or_insert_with(|| {
+                palette.push(registry.label(id).clone());
+                (palette.len() - 1) as u32
+            });
+
+            PaletteEntry {
+                index,
+                rotation: rotation.map(|rotation| [rotation.front(), rotation.up()]),
+            }
+        };
+
+        let voxels = self
+            .voxels
+            .iter()
+            .map(|(pos, block)| {
+                let block = match block {
+                    BlockVoxel::Full(full) => SchematicBlock::Full(entry(full.id, full.rotation)),
+                    BlockVoxel::Subdivided(subdiv) => SchematicBlock::Subdivided(
+                        microblock_positions()
+                            .map(|mb_pos| {
+                                let microblock = subdiv.get(mb_pos).unwrap();
+                                entry(microblock.id, microblock.rotation)
+                            })
+                            .collect(),
+                    ),
+                };
+
+                (*pos, block)
+            })
+            .collect();
+
+        serde_json::to_writer(writer, &SchematicFile { palette, voxels })?;
+        Ok(())
+    }
+
+    /// Read a schematic written by [`Schematic::write`], resolving its block variants with the registry.
+    pub fn read<R: Read>(
+        reader: R,
+        registry: &BlockVariantRegistry,
+    ) -> Result<Self, SchematicError> {
+        let file = serde_json::from_reader::<_, SchematicFile>(reader)?;
+
+        let ids = file
+            .palette
+            .into_iter()
+            .map(|label| {
+                registry
+                    .get_id(&label)
+                    .ok_or(SchematicError::UnknownVariant(label))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let microblock = |entry: PaletteEntry| -> Result<Microblock, SchematicError> {
+            let id = *ids
+                .get(entry.index as usize)
+                .ok_or(SchematicError::InvalidPaletteIndex(entry.index))?;
+            let rotation = entry
+                .rotation
+                .map(|[fwd, up]| {
+                    BlockModelRotation::new(fwd, up).ok_or(SchematicError::InvalidRotation)
+                })
+                .transpose()?;
+
+            Ok(Microblock { rotation, id })
+        };
+
+        let voxels = file
+            .voxels
+            .into_iter()
+            .map(|(pos, block)| {
+                let block = match block {
+                    SchematicBlock::Full(entry) => {
+                        BlockVoxel::Full(microblock(entry)?.as_full_block())
+                    }
+                    SchematicBlock::Subdivided(entries) => {
+                        if entries.len() != SubdividedBlock::SUBDIVISIONS.pow(3) as usize {
+                            return Err(SchematicError::InvalidMicroblockCount(entries.len()));
+                        }
+
+                        let mut subdiv = SubdividedBlock::new(microblock(entries[0])?);
+                        for (mb_pos, entry) in microblock_positions().zip(entries) {
+                            subdiv.set(mb_pos, microblock(entry)?).unwrap();
+                        }
+
+                        BlockVoxel::Subdivided(subdiv)
+                    }
+                };
+
+                Ok((pos, block))
+            })
+            .collect::<Result<Vec<_>, SchematicError>>()?;
+
+        Ok(Self { voxels })
+    }
+
+    /// Save this schematic to a file, see [`Schematic::write`].
+    pub fn save(
+        &self,
+        path: impl AsRef<Path>,
+        registry: &BlockVariantRegistry,
+    ) -> Result<(), SchematicError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write(&mut writer, registry)?;
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    /// Load a schematic from a file, see [`Schematic::read`].
+    pub fn load(
+        path: impl AsRef<Path>,
+        registry: &BlockVariantRegistry,
+    ) -> Result<Self, SchematicError> {
+        Self::read(BufReader::new(File::open(path)?), registry)
+    }
+}
+
+/// The positions of the microblocks in a subdivided block, in the order they're written to schematic files.
+fn microblock_positions() -> impl Iterator<Item = UVec3> {
+    BoundingBox::from_min_max(IVec3::ZERO, SubdividedBlock::SUBDIVS_VEC3)
+        .cartesian_iter()
+        .map(|pos| pos.as_uvec3())
+}
+
+fn rotate_block(block: &BlockVoxel, rotation: BlockModelRotation) -> BlockVoxel {
+    let rotate_full = |full: FullBlock| FullBlock {
+        rotation: full.rotation.map(|r| r.rotated_by(rotation)),
+        id: full.id,
+    };
+
+    match block {
+        BlockVoxel::Full(full) => BlockVoxel::Full(rotate_full(*full)),
+        BlockVoxel::Subdivided(subdiv) => {
+            let mut rotated = subdiv.clone();
+
+            // microblocks are rotated around the center of the block, so we work with positions that are
+            // doubled and centered on the origin to keep everything in integers
+            let max = SubdividedBlock::SUBDIVISIONS - 1;
+            for mb_pos in microblock_positions() {
+                let centered = mb_pos.as_ivec3() * 2 - IVec3::splat(max);
+                let target = ((rotation.rotate(centered) + IVec3::splat(max)) / 2).as_uvec3();
+
+                let microblock = subdiv.get(mb_pos).unwrap();
+                let full = rotate_full(microblock.as_full_block());
+                rotated
+                    .set(
+                        target,
+                        Microblock {
+                            rotation: full.rotation,
+                            id: full.id,
+                        },
+                    )
+                    .unwrap();
+            }
+
+            BlockVoxel::Subdivided(rotated)
+        }
+    }
+}
+
+#[derive(Copy, Clone, serde::Serialize, serde::Deserialize)]
+struct PaletteEntry {
+    index: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    rotation: Option<[Face; 2]>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+enum SchematicBlock {
+    Full(PaletteEntry),
+    /// The microblocks in the order of [`microblock_positions`].
+    Subdivided(Vec<PaletteEntry>),
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct SchematicFile {
+    palette: Vec<ResourcePath>,
+    voxels: Vec<(IVec3, SchematicBlock)>,
+}
+
+struct PendingPaste {
+    schematic: Schematic,
+    origin: IVec3,
+    rotation: BlockModelRotation,
+    /// Keeps the target chunks loaded until the schematic is pasted.
+    _ticket: LoadTicket,
+}
+
+/// Schematics waiting for their target chunks to be loaded and generated before they're pasted.
+/// The target chunks are force loaded with a [`LoadTicket`] that's held until the paste is done.
+#[derive(Resource, Default)]
+pub struct PendingPastes {
+    pending: Vec<PendingPaste>,
+}
+
+impl PendingPastes {
+    /// Queue the schematic to be pasted at `origin` once all of its target chunks are loaded, and queue
+    /// the target chunks to be force loaded.
+    pub fn queue(
+        &mut self,
+        schematic: Schematic,
+        origin: IVec3,
+        rotation: BlockModelRotation,
+        loads: &mut TicketedLoads,
+    ) {
+        let ticket = LoadTicket::new();
+        for pos in schematic.target_chunks(origin, rotation).iter() {
+            loads.queue(pos, &ticket);
+        }
+
+        self.pending.push(PendingPaste {
+            schematic,
+            origin,
+            rotation,
+            _ticket: ticket,
+        });
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Paste the queued schematics whose target chunks are all loaded and generated.
+    /// Returns the number of schematics that were pasted.
+    pub fn process(&mut self, cm: &ChunkManager) -> usize {
+        let before = self.pending.len();
+
+        self.pending.retain(|paste| {
+            match paste.schematic.paste(cm, paste.origin, paste.rotation) {
+                Ok(()) => false,
+                Err(ChunkManagerError::Unloaded | ChunkManagerError::Primordial) => true,
+                Err(error) => {
+                    error!("Error pasting schematic at {}: {error}", paste.origin);
+                    false
+                }
+            }
+        });
+
+        before - self.pending.len()
+    }
+}
+
+/// Paste the pending schematics whose target chunks are ready, see [`PendingPastes`].
+pub fn paste_pending_schematics(realm: VoxelRealm, mut pastes: ResMut<PendingPastes>) {
+    if !pastes.is_empty() {
+        pastes.process(realm.cm());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::{ivec3, uvec3};
+
+    use crate::{
+        data::registries::texture::TextureRegistry,
+        topo::world::{chunk::ChunkFlags, ChunkPos},
+    };
+
+    use super::*;
+
+    const STONE: BlockVariantId = BlockVariantRegistry::FULL;
+    const LAMP: BlockVariantId = BlockVariantRegistry::LAMP;
+
+    fn manager(chunks: impl IntoIterator<Item = ChunkPos>) -> ChunkManager {
+        let cm = ChunkManager::new_test();
+        for pos in chunks {
+            cm.insert_test_chunk(pos, |_| ());
+        }
+
+        cm
+    }
+
+    fn rotation(fwd: Face, up: Face) -> BlockModelRotation {
+        BlockModelRotation::new(fwd, up).unwrap()
+    }
+
+    /// A small structure in the corner of chunk (0, 0, 0): a pillar of stone, a lamp facing west, and a
+    /// subdivided block with a single stone microblock.
+    fn build_structure(cm: &ChunkManager) -> Schematic {
+        let set = |ws: IVec3, block: BlockVoxel| {
+            cm.set_voxel(ws, ChunkAccessInput::new(block)).unwrap();
+        };
+
+        for y in 0..3 {
+            set(ivec3(1, y, 1), BlockVoxel::new_full(STONE));
+        }
+        set(
+            ivec3(2, 0, 1),
+            BlockVoxel::Full(FullBlock::new(LAMP).with_rotation(rotation(Face::West, Face::Top))),
+        );
+
+        let mut subdiv = SubdividedBlock::new(Microblock::new(BlockVariantRegistry::VOID));
+        subdiv.set(UVec3::ZERO, Microblock::new(STONE)).unwrap();
+        set(ivec3(1, 0, 2), BlockVoxel::Subdivided(subdiv));
+
+        let selection = Selection::from(BoundingBox::new(ivec3(0, 0, 0), ivec3(3, 3, 3)));
+        Schematic::copy(cm, &selection, ivec3(1, 0, 1)).unwrap()
+    }
+
+    #[test]
+    fn copy_and_paste() {
+        let cm = manager([ChunkPos::new(0, 0, 0), ChunkPos::new(1, 0, 0)]);
+        let schematic = build_structure(&cm);
+        assert_eq!(27, schematic.len());
+
+        // the paste crosses the border between the two chunks
+        let origin = ivec3(15, 4, 7);
+        assert_eq!(
+            2,
+            schematic
+                .target_chunks(origin, BlockModelRotation::DEFAULT)
+                .len()
+        );
+        schematic
+            .paste(&cm, origin, BlockModelRotation::DEFAULT)
+            .unwrap();
+
+        for (pos, block) in schematic.voxels() {
+            assert!(cm.get_voxel(origin + pos).unwrap() == *block);
+        }
+
+        let registry = BlockVariantRegistry::new_mock(&TextureRegistry::new_mock());
+        let mut buffer = Vec::new();
+        schematic.write(&mut buffer, &registry).unwrap();
+        assert!(Schematic::read(buffer.as_slice(), &registry).unwrap() == schematic);
+    }
+
+    #[test]
+    fn rotated_paste() {
+        let cm = manager([ChunkPos::new(0, 0, 0), ChunkPos::new(-1, 0, 0)]);
+        let schematic = build_structure(&cm);
+
+        // 90 degrees around the Y axis, (x, y, z) becomes (-z, y, x)
+        let quarter_turn = rotation(Face::East, Face::Top);
+        let origin = ivec3(1, 8, 1);
+        schematic.paste(&cm, origin, quarter_turn).unwrap();
+
+        for y in 0..3 {
+            assert!(cm.get_voxel(origin + ivec3(0, y, 0)).unwrap() == BlockVoxel::new_full(STONE));
+        }
+        assert!(
+            cm.get_voxel(origin + ivec3(0, 0, 1)).unwrap()
+                == BlockVoxel::Full(
+                    FullBlock::new(LAMP).with_rotation(rotation(Face::North, Face::Top))
+                )
+        );
+
+        let BlockVoxel::Subdivided(subdiv) = cm.get_voxel(origin + ivec3(-1, 0, 0)).unwrap() else {
+            panic!("expected a subdivided block");
+        };
+        assert_eq!(STONE, subdiv.get(uvec3(3, 0, 0)).unwrap().id);
+        assert_eq!(
+            BlockVariantRegistry::VOID,
+            subdiv.get(UVec3::ZERO).unwrap().id
+        );
+
+        // four quarter turns are a full turn
+        let mut turned = schematic.clone();
+        for _ in 0..4 {
+            turned = turned.rotated(quarter_turn);
+        }
+        assert!(turned == schematic);
+    }
+
+    #[test]
+    fn paste_waits_for_target_chunks() {
+        let cm = manager([ChunkPos::new(0, 0, 0)]);
+        let schematic = build_structure(&cm);
+
+        let mut loads = TicketedLoads::default();
+        let mut pastes = PendingPastes::default();
+        let origin = ivec3(1, 0, 1) + ChunkPos::new(3, 0, 0).worldspace_min();
+        pastes.queue(
+            schematic.clone(),
+            origin,
+            BlockModelRotation::DEFAULT,
+            &mut loads,
+        );
+
+        assert_eq!(
+            Err(ChunkManagerError::Unloaded),
+            schematic.paste(&cm, origin, BlockModelRotation::DEFAULT)
+        );
+        assert_eq!(0, pastes.process(&cm));
+        assert_eq!(1, loads.len());
+
+        // the chunk is loaded but not generated yet
+        loads.process(&cm, 8);
+        assert_eq!(0, pastes.process(&cm));
+
+        cm.get_loaded_chunk(ChunkPos::new(3, 0, 0), true)
+            .unwrap()
+            .update_flags(|flags| flags.remove(ChunkFlags::PRIMORDIAL));
+        assert_eq!(1, pastes.process(&cm));
+        assert!(pastes.is_empty());
+        assert!(cm.get_voxel(origin).unwrap() == BlockVoxel::new_full(STONE));
+    }
+}
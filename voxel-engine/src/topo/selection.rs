@@ -0,0 +1,341 @@
+use bevy::math::IVec3;
+
+use crate::util::{space::voxel_to_chunk, ChunkSet};
+
+use super::{
+    bounding_box::{BoundingBox, CartesianIter},
+    world::{Chunk, ChunkPos},
+};
+
+pub type VoxelSet = hb::HashSet<IVec3, fxhash::FxBuildHasher>;
+
+#[derive(te::Error, Debug, Copy, Clone, PartialEq, Eq)]
+#[error("Selection box has a min corner greater than its max corner")]
+pub struct InvalidSelection;
+
+/// A selection of voxels in the world, in voxelspace. Either a box of voxels, or an arbitrary set of voxels.
+/// Used by tools that operate on regions of the world, see [`ChunkManager::read_selection`] and
+/// [`ChunkManager::fill_selection`].
+///
+/// [`ChunkManager::read_selection`]: super::world::ChunkManager::read_selection
+/// [`ChunkManager::fill_selection`]: super::world::ChunkManager::fill_selection
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(try_from = "SelectionRepr", into = "SelectionRepr")]
+pub enum Selection {
+    /// The voxels in the box, the max corner of the box is exclusive like in [`BoundingBox::contains`].
+    Box(BoundingBox),
+    Set(VoxelSet),
+}
+
+impl Default for Selection {
+    fn default() -> Self {
+        Self::Set(VoxelSet::default())
+    }
+}
+
+impl FromIterator<IVec3> for Selection {
+    fn from_iter<T: IntoIterator<Item = IVec3>>(iter: T) -> Self {
+        Self::Set(iter.into_iter().collect())
+    }
+}
+
+impl From<BoundingBox> for Selection {
+    fn from(bb: BoundingBox) -> Self {
+        Self::Box(bb)
+    }
+}
+
+impl Selection {
+    pub fn contains(&self, pos: IVec3) -> bool {
+        match self {
+            Self::Box(bb) => bb.contains(pos),
+            Self::Set(set) => set.contains(&pos),
+        }
+    }
+
+    /// The number of voxels in this selection.
+    pub fn volume(&self) -> usize {
+        match self {
+            Self::Box(bb) => bb.volume() as usize,
+            Self::Set(set) => set.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.volume() == 0
+    }
+
+    /// Iterate over the voxels in this selection. Boxes are iterated in the order of
+    /// [`BoundingBox::cartesian_iter`], sets are iterated in an arbitrary order.
+    pub fn iter(&self) -> SelectionIter<'_> {
+        match self {
+            Self::Box(bb) => SelectionIter::Box(bb.cartesian_iter()),
+            Self::Set(set) => SelectionIter::Set(set.iter()),
+        }
+    }
+
+    /// The smallest box containing every voxel in this selection, or `None` if it's empty.
+    pub fn bounds(&self) -> Option<BoundingBox> {
+        if self.is_empty() {
+            return None;
+        }
+
+        match self {
+            Self::Box(bb) => Some(*bb),
+            Self::Set(set) => {
+                let min = set.iter().copied().reduce(IVec3::min)?;
+                let max = set.iter().copied().reduce(IVec3::max)?;
+                Some(BoundingBox::from_min_max(min, max + IVec3::ONE))
+            }
+        }
+    }
+
+    /// The chunks containing the voxels in this selection.
+    pub fn chunks(&self) -> ChunkSet {
+        let mut chunks = ChunkSet::default();
+
+        match self {
+            Self::Box(bb) if !self.is_empty() => {
+                let region = BoundingBox::from_min_max(
+                    bb.min().div_euclid(Chunk::VEC),
+                    (bb.max() - IVec3::ONE).div_euclid(Chunk::VEC) + IVec3::ONE,
+                );
+
+                for pos in region.cartesian_iter() {
+                    chunks.set(ChunkPos::from(pos));
+                }
+            }
+            _ => {
+                for pos in self.iter() {
+                    chunks.set(voxel_to_chunk(pos));
+                }
+            }
+        }
+
+        chunks
+    }
+
+    /// This selection moved by `offset`.
+    pub fn translated(&self, offset: IVec3) -> Self {
+        match self {
+            Self::Box(bb) => Self::Box(BoundingBox::from_min_max(
+                bb.min() + offset,
+                bb.max() + offset,
+            )),
+            Self::Set(set) => Self::Set(set.iter().map(|&pos| pos + offset).collect()),
+        }
+    }
+
+    /// The voxels in either this selection or `other`. The union of two boxes is only a box if one of
+    /// them contains the other.
+    pub fn union(&self, other: &Self) -> Self {
+        match (self, other) {
+            (Self::Box(a), Self::Box(b)) if a.intersection(*b) == Some(*b) || b.volume() == 0 => {
+                Self::Box(*a)
+            }
+            (Self::Box(a), Self::Box(b)) if b.intersection(*a) == Some(*a) || a.volume() == 0 => {
+                Self::Box(*b)
+            }
+            _ => self.iter().chain(other.iter()).collect(),
+        }
+    }
+
+    /// The voxels in both this selection and `other`. The intersection of two boxes is always a box.
+    pub fn intersection(&self, other: &Self) -> Self {
+        match (self, other) {
+            (Self::Box(a), Self::Box(b)) => a.intersection(*b).map(Self::Box).unwrap_or_default(),
+            // iterate over the smaller selection
+            _ if self.volume() <= other.volume() => {
+                self.iter().filter(|&pos| other.contains(pos)).collect()
+            }
+            _ => other.iter().filter(|&pos| self.contains(pos)).collect(),
+        }
+    }
+}
+
+/// Iterator over the voxels in a [`Selection`], see [`Selection::iter`].
+pub enum SelectionIter<'a> {
+    Box(CartesianIter),
+    Set(hb::hash_set::Iter<'a, IVec3>),
+}
+
+impl<'a> Iterator for SelectionIter<'a> {
+    type Item = IVec3;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Self::Box(iter) => iter.next(),
+            Self::Set(iter) => iter.next().copied(),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self {
+            Self::Box(iter) => iter.size_hint(),
+            Self::Set(iter) => iter.size_hint(),
+        }
+    }
+}
+
+/// How selections are serialized. Sets are sorted so that equal selections serialize the same.
+#[derive(serde::Serialize, serde::Deserialize)]
+enum SelectionRepr {
+    Box { min: IVec3, max: IVec3 },
+    Set(Vec<IVec3>),
+}
+
+impl From<Selection> for SelectionRepr {
+    fn from(selection: Selection) -> Self {
+        match selection {
+            Selection::Box(bb) => Self::Box {
+                min: bb.min(),
+                max: bb.max(),
+            },
+            Selection::Set(set) => {
+                let mut voxels = set.into_iter().collect::<Vec<_>>();
+                voxels.sort_by_key(|pos| pos.to_array());
+                Self::Set(voxels)
+            }
+        }
+    }
+}
+
+impl TryFrom<SelectionRepr> for Selection {
+    type Error = InvalidSelection;
+
+    fn try_from(repr: SelectionRepr) -> Result<Self, Self::Error> {
+        match repr {
+            SelectionRepr::Box { min, max } if min.cmpgt(max).any() => Err(InvalidSelection),
+            SelectionRepr::Box { min, max } => Ok(Self::Box(BoundingBox::from_min_max(min, max))),
+            SelectionRepr::Set(voxels) => Ok(voxels.into_iter().collect()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::ivec3;
+
+    use crate::{
+        data::registries::block::BlockVariantId,
+        topo::{
+            block::BlockVoxel,
+            world::{ChunkManager, ChunkManagerError},
+        },
+    };
+
+    use super::*;
+
+    #[test]
+    fn box_iteration() {
+        let selection = Selection::from(BoundingBox::new(ivec3(-1, 0, 0), ivec3(1, 2, 1)));
+
+        assert_eq!(4, selection.volume());
+        assert_eq!(
+            vec![
+                ivec3(-1, 0, 0),
+                ivec3(-1, 1, 0),
+                ivec3(0, 0, 0),
+                ivec3(0, 1, 0)
+            ],
+            selection.iter().collect::<Vec<_>>()
+        );
+        assert!(selection.iter().all(|pos| selection.contains(pos)));
+        assert!(!selection.contains(ivec3(1, 0, 0)));
+
+        let chunks = selection.chunks();
+        assert_eq!(2, chunks.len());
+        assert!(chunks.contains(ChunkPos::new(-1, 0, 0)));
+        assert!(chunks.contains(ChunkPos::new(0, 0, 0)));
+    }
+
+    #[test]
+    fn set_operations() {
+        let a = Selection::from(BoundingBox::new(ivec3(0, 0, 0), ivec3(4, 4, 4)));
+        let b = Selection::from(BoundingBox::new(ivec3(2, 2, 2), ivec3(6, 6, 6)));
+
+        assert_eq!(
+            Selection::Box(BoundingBox::new(ivec3(2, 2, 2), ivec3(4, 4, 4))),
+            a.intersection(&b)
+        );
+        assert!(a
+            .intersection(&Selection::from(BoundingBox::new(
+                ivec3(10, 0, 0),
+                ivec3(11, 1, 1)
+            )))
+            .is_empty());
+
+        let union = a.union(&b);
+        assert_eq!(64 + 64 - 8, union.volume());
+        assert_eq!(
+            Some(BoundingBox::new(ivec3(0, 0, 0), ivec3(6, 6, 6))),
+            union.bounds()
+        );
+        assert_eq!(a, a.union(&a.intersection(&b)));
+
+        let set = [ivec3(0, 0, 0), ivec3(5, 5, 5), ivec3(9, 9, 9)]
+            .into_iter()
+            .collect::<Selection>();
+        assert_eq!(
+            [ivec3(0, 0, 0)].into_iter().collect::<Selection>(),
+            set.intersection(&a)
+        );
+        assert_eq!(
+            [ivec3(5, 5, 5)].into_iter().collect::<Selection>(),
+            b.intersection(&set)
+        );
+    }
+
+    #[test]
+    fn serde_round_trip() {
+        let boxed = Selection::from(BoundingBox::new(ivec3(-3, 0, 2), ivec3(5, 1, 7)));
+        let json = serde_json::to_string(&boxed).unwrap();
+        assert_eq!(boxed, serde_json::from_str(&json).unwrap());
+
+        let set = [ivec3(1, 2, 3), ivec3(-4, 5, -6)]
+            .into_iter()
+            .collect::<Selection>();
+        let json = serde_json::to_string(&set).unwrap();
+        assert_eq!(set, serde_json::from_str(&json).unwrap());
+
+        assert!(
+            serde_json::from_str::<Selection>(r#"{"Box":{"min":[1,1,1],"max":[0,0,0]}}"#).is_err()
+        );
+    }
+
+    #[test]
+    fn read_and_fill_selection() {
+        let cm = ChunkManager::new_test();
+        for pos in [ChunkPos::new(0, 0, 0), ChunkPos::new(1, 0, 0)] {
+            cm.insert_test_chunk(pos, |_| ());
+        }
+
+        let selection = Selection::from(BoundingBox::new(ivec3(14, 0, 0), ivec3(18, 2, 2)));
+        let stone = BlockVoxel::new_full(BlockVariantId::new(1));
+        cm.fill_selection(&selection, stone.clone()).unwrap();
+
+        let voxels = cm.read_selection(&selection).unwrap();
+        assert_eq!(selection.iter().collect::<Vec<_>>(), {
+            voxels.iter().map(|(pos, _)| *pos).collect::<Vec<_>>()
+        });
+        assert!(voxels.iter().all(|(_, block)| *block == stone));
+        assert_eq!(
+            BlockVoxel::new_full(BlockVariantId::new(0)),
+            cm.get_voxel(ivec3(13, 0, 0)).unwrap()
+        );
+
+        let unloaded = selection.translated(ivec3(-16, 0, 0));
+        assert_eq!(
+            Err(ChunkManagerError::Unloaded),
+            cm.read_selection(&unloaded)
+        );
+        assert_eq!(
+            Err(ChunkManagerError::Unloaded),
+            cm.fill_selection(&unloaded, stone)
+        );
+        assert_eq!(
+            BlockVoxel::new_full(BlockVariantId::new(0)),
+            cm.get_voxel(ivec3(0, 0, 0)).unwrap()
+        );
+    }
+}
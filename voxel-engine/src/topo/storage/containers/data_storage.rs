@@ -1,4 +1,8 @@
-use std::hash::{self, BuildHasher};
+use std::{
+    hash::{self, BuildHasher},
+    ops::Deref,
+    sync::Arc,
+};
 
 use bevy::math::IVec3;
 
@@ -15,6 +19,12 @@ use parking_lot::{RwLock, RwLockReadGuard, RwLockWriteGuard};
 /// SLCC for short
 pub struct SyncLayeredChunkContainer<T>(RwLock<LayeredChunkStorage<T>>);
 
+impl<T> Default for SyncLayeredChunkContainer<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<T> SyncLayeredChunkContainer<T> {
     pub fn new() -> Self {
         Self(RwLock::new(LayeredChunkStorage::new()))
@@ -62,7 +72,10 @@ impl<'a, T: Copy> WriteAccess for SlccAccess<'a, T> {
     }
 }
 
-pub struct SlccReadAccess<'a, T: Copy>(RwLockReadGuard<'a, LayeredChunkStorage<T>>);
+// reads aren't implemented yet, the guard just holds the lock for now
+pub struct SlccReadAccess<'a, T: Copy>(
+    #[allow(dead_code)] RwLockReadGuard<'a, LayeredChunkStorage<T>>,
+);
 
 impl<'a, T: Copy> ChunkBounds for SlccReadAccess<'a, T> {}
 
@@ -76,13 +89,21 @@ impl<'a, T: Copy> ReadAccess for SlccReadAccess<'a, T> {
 }
 
 // SICC for short
+/// The storage is shared with the [snapshots](SyncIndexedChunkContainer::snapshot) of the container,
+/// and it's only copied when it's written to while a snapshot is alive.
 pub struct SyncIndexedChunkContainer<T: hash::Hash + Eq, S: BuildHasher = ahash::RandomState>(
-    pub(crate) RwLock<IndexedChunkStorage<T, S>>,
+    pub(crate) RwLock<Arc<IndexedChunkStorage<T, S>>>,
 );
 
 /// Avoid writing the huge name of SyncIndexedChunkContainer
 pub type Sicc<T, S> = SyncIndexedChunkContainer<T, S>;
 
+impl<T: hash::Hash + Eq> Default for SyncIndexedChunkContainer<T, ahash::RandomState> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<T: hash::Hash + Eq> SyncIndexedChunkContainer<T, ahash::RandomState> {
     pub fn new() -> Self {
         Self::with_random_state(ahash::RandomState::new())
@@ -96,12 +117,12 @@ impl<T: hash::Hash + Eq> SyncIndexedChunkContainer<T, ahash::RandomState> {
 impl<T: hash::Hash + Eq, S: BuildHasher> SyncIndexedChunkContainer<T, S> {
     pub fn with_random_state(random_state: S) -> Self {
         let storage = IndexedChunkStorage::with_random_state(random_state);
-        Self(RwLock::new(storage))
+        Self(RwLock::new(Arc::new(storage)))
     }
 
     pub fn filled_with_random_state(value: T, random_state: S) -> Self {
         let storage = IndexedChunkStorage::filled_with_random_state(value, random_state);
-        Self(RwLock::new(storage))
+        Self(RwLock::new(Arc::new(storage)))
     }
 
     pub fn access(&self) -> SiccAccess<'_, T, S> {
@@ -109,47 +130,72 @@ impl<T: hash::Hash + Eq, S: BuildHasher> SyncIndexedChunkContainer<T, S> {
     }
 
     pub fn read_access(&self) -> SiccReadAccess<'_, T, S> {
-        SiccReadAccess(self.0.read())
+        SiccReadAccess(SiccReadGuard::Locked(self.0.read()))
+    }
+
+    /// The storage of this container as it is right now, which can be read without holding the lock of the
+    /// container. This doesn't copy anything, the storage is copied by the next write to the container instead.
+    pub fn snapshot(&self) -> Arc<IndexedChunkStorage<T, S>> {
+        self.0.read().clone()
+    }
+
+    /// Whether the storage is shared with a snapshot, in which case the next write will copy it.
+    pub fn is_shared(&self) -> bool {
+        Arc::strong_count(&self.0.read()) > 1
+    }
+
+    /// The value at every position if this container is known to be uniform.
+    pub fn uniform(&self) -> Option<T>
+    where
+        T: Clone,
+    {
+        self.0.read().uniform().cloned()
     }
 }
 
 pub struct SiccAccess<'a, T: hash::Hash + Eq, S: BuildHasher>(
-    RwLockWriteGuard<'a, IndexedChunkStorage<T, S>>,
+    RwLockWriteGuard<'a, Arc<IndexedChunkStorage<T, S>>>,
 );
 
 impl<'a, T: hash::Hash + Eq, S: BuildHasher> SiccAccess<'a, T, S> {
-    pub(crate) fn get_mut(&mut self, pos: IVec3) -> Result<Option<&mut T>, OutOfBounds> {
-        self.0.get_mut(pos)
-    }
-
     pub fn values(&self) -> &[T] {
         self.0.values()
     }
+}
+
+impl<'a, T: hash::Hash + Eq + Clone, S: BuildHasher + Clone> SiccAccess<'a, T, S> {
+    /// The storage for writing, copied first if it's shared with a snapshot.
+    fn storage_mut(&mut self) -> &mut IndexedChunkStorage<T, S> {
+        Arc::make_mut(&mut self.0)
+    }
+
+    #[allow(dead_code)]
+    pub(crate) fn get_mut(&mut self, pos: IVec3) -> Result<Option<&mut T>, OutOfBounds> {
+        self.storage_mut().get_mut(pos)
+    }
 
     pub fn values_mut(&mut self) -> &mut [T] {
-        self.0.values_mut()
+        self.storage_mut().values_mut()
     }
-}
 
-impl<'a, T: hash::Hash + Eq + Clone, S: BuildHasher + Clone> SiccAccess<'a, T, S> {
     pub fn optimize_storage(&mut self) -> usize {
-        self.0.optimize()
+        self.storage_mut().optimize()
     }
 }
 
 impl<'a, T: hash::Hash + Eq, S: BuildHasher> ChunkBounds for SiccAccess<'a, T, S> {}
 
-impl<'a, T: hash::Hash + Eq, S: BuildHasher> WriteAccess for SiccAccess<'a, T, S> {
+impl<'a, T: hash::Hash + Eq + Clone, S: BuildHasher + Clone> WriteAccess for SiccAccess<'a, T, S> {
     type WriteErr = OutOfBounds;
     type WriteType = Option<T>;
 
     fn set(&mut self, pos: IVec3, data: Self::WriteType) -> Result<(), Self::WriteErr> {
         match data {
             Some(v) => {
-                self.0.set(pos, v)?;
+                self.storage_mut().set(pos, v)?;
             }
             None => {
-                self.0.clear(pos)?;
+                self.storage_mut().clear(pos)?;
             }
         }
 
@@ -162,13 +208,45 @@ impl<'a, T: hash::Hash + Eq, S: BuildHasher> ReadAccess for SiccAccess<'a, T, S>
     type ReadType<'b> = Option<&'b T> where 'a: 'b;
 
     fn get(&self, pos: IVec3) -> Result<Self::ReadType<'_>, Self::ReadErr> {
-        Ok(self.0.get(pos)?)
+        self.0.get(pos)
     }
 }
 
-pub struct SiccReadAccess<'a, T: hash::Hash + Eq, S: BuildHasher>(
-    RwLockReadGuard<'a, IndexedChunkStorage<T, S>>,
-);
+enum SiccReadGuard<'a, T: hash::Hash + Eq, S: BuildHasher> {
+    Locked(RwLockReadGuard<'a, Arc<IndexedChunkStorage<T, S>>>),
+    Borrowed(&'a IndexedChunkStorage<T, S>),
+}
+
+impl<'a, T: hash::Hash + Eq, S: BuildHasher> Deref for SiccReadGuard<'a, T, S> {
+    type Target = IndexedChunkStorage<T, S>;
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            Self::Locked(guard) => guard,
+            Self::Borrowed(storage) => storage,
+        }
+    }
+}
+
+pub struct SiccReadAccess<'a, T: hash::Hash + Eq, S: BuildHasher>(SiccReadGuard<'a, T, S>);
+
+impl<'a, T: hash::Hash + Eq, S: BuildHasher> SiccReadAccess<'a, T, S> {
+    /// Read access to storage that isn't in a container, like a [snapshot](SyncIndexedChunkContainer::snapshot).
+    pub fn from_storage(storage: &'a IndexedChunkStorage<T, S>) -> Self {
+        Self(SiccReadGuard::Borrowed(storage))
+    }
+
+    /// The value at every position if this container is known to be uniform.
+    pub fn uniform(&self) -> Option<&T> {
+        self.0.uniform()
+    }
+
+    /// Every distinct value that was written to the storage. Values that have since been overwritten
+    /// everywhere stay in here until the storage is optimized.
+    pub fn values(&self) -> &[T] {
+        self.0.values()
+    }
+}
 
 impl<'a, T: hash::Hash + Eq, S: BuildHasher> ChunkBounds for SiccReadAccess<'a, T, S> {}
 
@@ -177,6 +255,6 @@ impl<'a, T: hash::Hash + Eq, S: BuildHasher> ReadAccess for SiccReadAccess<'a, T
     type ReadType<'b> = Option<&'b T> where Self: 'b;
 
     fn get(&self, pos: IVec3) -> Result<Self::ReadType<'_>, Self::ReadErr> {
-        Ok(self.0.get(pos)?)
+        self.0.get(pos)
     }
 }
@@ -7,7 +7,7 @@ use crate::topo::access::{HasBounds, ReadAccess, WriteAccess};
 use crate::topo::bounding_box::BoundingBox;
 use crate::topo::error::ChunkAccessError;
 use crate::topo::world::Chunk;
-use crate::util;
+use crate::util::cubic::CubicArrayExt;
 
 use super::super::data_structures::DenseChunkStorage;
 
@@ -22,8 +22,10 @@ impl<T> WriteAccess for DenseChunkStorage<T> {
     type WriteErr = ChunkAccessError;
 
     fn set(&mut self, pos: IVec3, data: Self::WriteType) -> Result<(), Self::WriteErr> {
-        let idx = util::try_ivec3_to_usize_arr(pos).map_err(|_| ChunkAccessError::OutOfBounds)?;
-        let slot = self.get_mut(idx).ok_or(ChunkAccessError::OutOfBounds)?;
+        let slot = self
+            .0
+            .at_mut(pos)
+            .map_err(|_| ChunkAccessError::OutOfBounds)?;
 
         *slot = data;
         Ok(())
@@ -102,6 +104,7 @@ impl<T: Copy> DenseChunkContainer<T> {
         }
     }
 
+    #[allow(dead_code)]
     pub(crate) fn internal_set(&mut self, pos: IVec3, data: T) -> Result<(), ChunkAccessError> {
         match self {
             Self::Empty => Err(ChunkAccessError::NotInitialized),
@@ -109,6 +112,7 @@ impl<T: Copy> DenseChunkContainer<T> {
         }
     }
 
+    #[allow(dead_code)]
     pub(crate) fn internal_get(&self, _pos: IVec3) -> Result<T, ChunkAccessError> {
         match self {
             Self::Empty => Err(ChunkAccessError::NotInitialized),
@@ -126,9 +130,14 @@ impl<T: Copy> DenseChunkContainer<T> {
 
 pub struct SyncDenseChunkContainer<T>(pub(crate) RwLock<DenseChunkContainer<T>>);
 
-pub struct SyncDenseContainerAccess<'a, T: Copy>(RwLockWriteGuard<'a, DenseChunkContainer<T>>);
+// accesses aren't implemented yet, the guards just hold the lock for now
+pub struct SyncDenseContainerAccess<'a, T: Copy>(
+    #[allow(dead_code)] RwLockWriteGuard<'a, DenseChunkContainer<T>>,
+);
 
-pub struct SyncDenseContainerReadAccess<'a, T: Copy>(RwLockReadGuard<'a, DenseChunkContainer<T>>);
+pub struct SyncDenseContainerReadAccess<'a, T: Copy>(
+    #[allow(dead_code)] RwLockReadGuard<'a, DenseChunkContainer<T>>,
+);
 
 impl<T: Copy> SyncDenseChunkContainer<T> {
     pub fn empty() -> Self {
@@ -8,7 +8,7 @@ use hashbrown::HashTable;
 
 use crate::{
     topo::world::Chunk,
-    util::{self, CubicArray, SquareArray},
+    util::{self, cubic::CubicArrayExt, CubicArray, SquareArray},
 };
 
 use super::error::OutOfBounds;
@@ -44,6 +44,12 @@ type SqChunkArray<T> = SquareArray<{ Chunk::USIZE }, Option<T>>;
 #[derive(Clone)]
 pub struct LayeredChunkStorage<T: Sized>([Option<Box<SqChunkArray<T>>>; Chunk::USIZE]);
 
+impl<T> Default for LayeredChunkStorage<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<T> LayeredChunkStorage<T> {
     pub fn new() -> Self {
         Self(array::from_fn(|_| None))
@@ -74,18 +80,11 @@ impl<T> LayeredChunkStorage<T> {
         let mut cleared = 0;
 
         for y in 0..Chunk::USIZE {
-            let mut should_clear = false;
-            if let Some(layer) = self.get_layer(y).unwrap().as_deref() {
-                should_clear = true;
-                'outer: for x in 0..Chunk::USIZE {
-                    for z in 0..Chunk::USIZE {
-                        if layer[x][z].is_some() {
-                            should_clear = false;
-                            break 'outer;
-                        }
-                    }
-                }
-            }
+            let should_clear = self
+                .get_layer(y)
+                .unwrap()
+                .as_deref()
+                .is_some_and(|layer| layer.iter().flatten().all(|slot| slot.is_none()));
 
             if should_clear {
                 cleared += 1;
@@ -147,7 +146,9 @@ impl<T> LayeredChunkStorage<T> {
             return Err(OutOfBounds);
         }
 
-        layer.as_deref_mut().map(|inner| inner[x][z] = None);
+        if let Some(inner) = layer.as_deref_mut() {
+            inner[x][z] = None;
+        }
 
         Ok(())
     }
@@ -242,6 +243,9 @@ pub struct IndexedChunkStorage<T: Eq + hash::Hash, S: BuildHasher = ahash::Rando
     values: Vec<T>,
     idx_table: HashTable<usize>,
     random_state: S,
+    /// The index shared by every position in the storage, or `None` if positions have different indices.
+    /// Writes that break uniformity clear this, but it's only restored by [`IndexedChunkStorage::optimize`].
+    uniform: Option<u16>,
 }
 
 fn optimize_by_copying<T: Eq + hash::Hash + Clone, S: BuildHasher + Clone>(
@@ -267,6 +271,12 @@ fn optimize_by_copying<T: Eq + hash::Hash + Clone, S: BuildHasher + Clone>(
     new
 }
 
+impl<T: Eq + hash::Hash> Default for IndexedChunkStorage<T, ahash::RandomState> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<T: Eq + hash::Hash> IndexedChunkStorage<T, ahash::RandomState> {
     pub fn new() -> Self {
         Self::internal_new(ahash::RandomState::new())
@@ -281,8 +291,7 @@ impl<T: Eq + hash::Hash, S: BuildHasher> IndexedChunkStorage<T, S> {
     const EMPTY_VALUE: u16 = 0b1000_0000_0000_0000;
 
     fn get_idx(&self, pos: IVec3) -> Option<usize> {
-        let us = util::try_ivec3_to_usize_arr(pos).unwrap();
-        let idx = *self.indices.get_ref(us).unwrap();
+        let idx = *self.indices.0.at_unchecked(pos);
 
         if idx != Self::EMPTY_VALUE {
             Some(idx as usize)
@@ -292,9 +301,28 @@ impl<T: Eq + hash::Hash, S: BuildHasher> IndexedChunkStorage<T, S> {
     }
 
     fn set_idx(&mut self, pos: IVec3, idx: usize) {
-        let us = util::try_ivec3_to_usize_arr(pos).unwrap();
-        let slot = self.indices.get_mut(us).unwrap();
-        *slot = idx as u16;
+        let idx = idx as u16;
+
+        if self.uniform.is_some_and(|uniform| uniform != idx) {
+            self.uniform = None;
+        }
+
+        let slot = self.indices.0.at_unchecked_mut(pos);
+        *slot = idx;
+    }
+
+    fn recalculate_uniform(&mut self) {
+        let first = *self.indices.get_ref([0, 0, 0]).unwrap();
+
+        let uniform = self
+            .indices
+            .0
+            .iter()
+            .flatten()
+            .flatten()
+            .all(|&idx| idx == first);
+
+        self.uniform = uniform.then_some(first);
     }
 
     fn insert_new_unique_value(&mut self, pos: IVec3, data: T) {
@@ -335,6 +363,7 @@ impl<T: Eq + hash::Hash, S: BuildHasher> IndexedChunkStorage<T, S> {
             values: Vec::new(),
             idx_table: HashTable::new(),
             random_state,
+            uniform: Some(Self::EMPTY_VALUE),
         }
     }
 
@@ -348,6 +377,13 @@ impl<T: Eq + hash::Hash, S: BuildHasher> IndexedChunkStorage<T, S> {
         // all indices point to the first value in the value vector
         new.indices = DenseChunkStorage::new(0);
         new.values = vec![filling];
+        new.uniform = Some(0);
+
+        // the filling needs to be in the index table too, otherwise writing the filling would
+        // insert a duplicate of it
+        let hash = new.random_state.hash_one(&new.values[0]);
+        let hasher = |i: &_| new.random_state.hash_one(&new.values[*i]);
+        new.idx_table.insert_unique(hash, 0, hasher);
 
         new
     }
@@ -389,8 +425,7 @@ impl<T: Eq + hash::Hash, S: BuildHasher> IndexedChunkStorage<T, S> {
             return Err(OutOfBounds);
         }
 
-        let upos = util::try_ivec3_to_usize_arr(pos).unwrap();
-        *self.indices.get_mut(upos).unwrap() = Self::EMPTY_VALUE;
+        self.set_idx(pos, Self::EMPTY_VALUE as usize);
 
         Ok(())
     }
@@ -446,13 +481,23 @@ impl<T: Eq + hash::Hash, S: BuildHasher> IndexedChunkStorage<T, S> {
     pub fn values_len(&self) -> usize {
         self.values().len()
     }
+
+    /// The value at every position if the storage is uniform (all positions have the same value).
+    /// Returns `None` if the storage is uniformly empty, or if it isn't known to be uniform.
+    pub fn uniform(&self) -> Option<&T> {
+        match self.uniform {
+            Some(idx) if idx != Self::EMPTY_VALUE => Some(&self.values[idx as usize]),
+            _ => None,
+        }
+    }
 }
 
 impl<T: hash::Hash + Eq + Clone, S: BuildHasher + Clone> IndexedChunkStorage<T, S> {
     pub fn optimize(&mut self) -> usize {
         let old_values = self.values_len();
 
-        let new = optimize_by_copying(self);
+        let mut new = optimize_by_copying(self);
+        new.recalculate_uniform();
         let new_values = new.values_len();
 
         *self = new;
@@ -465,6 +510,8 @@ impl<T: hash::Hash + Eq + Clone, S: BuildHasher + Clone> IndexedChunkStorage<T,
 mod tests {
     use bevy::math::ivec3;
 
+    use crate::topo::world::ContainerHasher;
+
     use super::*;
 
     #[test]
@@ -569,6 +616,37 @@ mod tests {
         assert_eq!(2, ics.values_len());
     }
 
+    #[test]
+    fn test_ICS_uniform() {
+        let mut ics = IndexedChunkStorage::<u32>::filled(10);
+        assert_eq!(Some(&10), ics.uniform());
+
+        // writing the same value keeps the storage uniform
+        ics.set(ivec3(4, 4, 4), 10).unwrap();
+        assert_eq!(Some(&10), ics.uniform());
+
+        ics.set(ivec3(4, 4, 4), 11).unwrap();
+        assert_eq!(None, ics.uniform());
+
+        // writing the old value back doesn't make it uniform until we optimize
+        ics.set(ivec3(4, 4, 4), 10).unwrap();
+        assert_eq!(None, ics.uniform());
+        ics.optimize();
+        assert_eq!(Some(&10), ics.uniform());
+
+        // a partially filled storage isn't uniform
+        let mut ics = IndexedChunkStorage::<u32>::new();
+        assert_eq!(None, ics.uniform());
+        for x in 0..Chunk::SIZE {
+            for z in 0..Chunk::SIZE {
+                ics.set(ivec3(x, 0, z), 5).unwrap();
+            }
+        }
+        assert_eq!(None, ics.uniform());
+        ics.optimize();
+        assert_eq!(None, ics.uniform());
+    }
+
     #[test]
     fn test_ICS_optimizing() {
         let mut ics = IndexedChunkStorage::<u32>::new();
@@ -719,4 +797,35 @@ mod tests {
         assert_eq!(None, ics.get(ivec3(0, 1, 0)).unwrap());
         assert_eq!(None, ics.get(ivec3(0, 2, 0)).unwrap());
     }
+
+    #[test]
+    fn seeded_containers_iterate_in_same_order() {
+        let build = || {
+            let random_state = ContainerHasher::Seeded(1234).random_state();
+            let mut storage = IndexedChunkStorage::<u32, _>::with_random_state(random_state);
+
+            for i in 0..256 {
+                let pos = ivec3(i % 16, i / 16, 0);
+                storage
+                    .set(pos, (i as u32).wrapping_mul(2654435761))
+                    .unwrap();
+            }
+
+            storage
+        };
+
+        let a = build();
+        let b = build();
+
+        let order = |storage: &IndexedChunkStorage<u32, ahash::RandomState>| {
+            storage
+                .idx_table
+                .iter()
+                .map(|&idx| storage.values[idx])
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(256, order(&a).len());
+        assert_eq!(order(&a), order(&b));
+    }
 }
@@ -0,0 +1,130 @@
+use std::sync::Arc;
+
+use bevy::{
+    app::{App, AppExit, Last},
+    ecs::{
+        schedule::{common_conditions::on_event, IntoSystemSetConfigs, SystemSet},
+        system::{ResMut, Resource},
+    },
+    log::{error, info},
+};
+
+use crate::util::SyncChunkMap;
+
+use super::world::{chunk::ChunkFlags, ChunkManager, ChunkPos, OwnedChunkAccess, VoxelRealm};
+
+#[derive(te::Error, Debug)]
+pub enum ChunkStoreError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+/// Somewhere to persist chunks, like a save file. See [`VoxelStore`].
+pub trait ChunkStore: Send + Sync + 'static {
+    /// Save the voxels of the chunk at `pos`, replacing whatever was saved for it before.
+    fn save(&mut self, pos: ChunkPos, chunk: OwnedChunkAccess) -> Result<(), ChunkStoreError>;
+}
+
+/// A [`ChunkStore`] that keeps the saved chunks in memory. Clones share the same chunks, so a clone
+/// can be kept around to read the chunks saved through a [`VoxelStore`].
+#[derive(Clone, Default)]
+pub struct MemoryChunkStore {
+    chunks: Arc<SyncChunkMap<OwnedChunkAccess>>,
+}
+
+impl MemoryChunkStore {
+    pub fn get(&self, pos: ChunkPos) -> Option<OwnedChunkAccess> {
+        self.chunks.get(pos).map(|chunk| chunk.clone())
+    }
+
+    pub fn len(&self) -> usize {
+        self.chunks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl ChunkStore for MemoryChunkStore {
+    fn save(&mut self, pos: ChunkPos, chunk: OwnedChunkAccess) -> Result<(), ChunkStoreError> {
+        self.chunks.set(pos, chunk);
+        Ok(())
+    }
+}
+
+/// The store that the chunks of the realm are saved to. The engine doesn't insert one of these itself,
+/// without it nothing is saved when the app exits.
+#[derive(Resource)]
+pub struct VoxelStore {
+    store: Box<dyn ChunkStore>,
+    /// The change tick of the chunk manager when chunks were last saved.
+    saved_tick: u64,
+}
+
+impl VoxelStore {
+    pub fn new<S: ChunkStore>(store: S) -> Self {
+        Self {
+            store: Box::new(store),
+            saved_tick: 0,
+        }
+    }
+
+    /// Snapshot and save every loaded chunk that was written to since the last save. Chunks that haven't
+    /// been generated yet are skipped. Returns the number of chunks saved.
+    /// If saving a chunk fails, the next call will save all the chunks of this call again.
+    pub fn save_dirty_chunks(&mut self, cm: &ChunkManager) -> Result<usize, ChunkStoreError> {
+        // chunks written to while saving will have a greater tick, so they're saved again next time
+        let tick = cm.change_tick();
+        let mut saved = 0;
+
+        for (pos, cref) in cm.loaded_chunks() {
+            if cref.changed_tick() <= self.saved_tick
+                || cref
+                    .flags()
+                    .intersects(ChunkFlags::PRIMORDIAL | ChunkFlags::GENERATING)
+            {
+                continue;
+            }
+
+            self.store.save(pos, cref.read_snapshot())?;
+            saved += 1;
+        }
+
+        self.saved_tick = tick;
+        Ok(saved)
+    }
+}
+
+/// System sets for tearing down the realm when the app exits. The sets only run on the frame an
+/// [`AppExit`] event is sent, in the [`Last`] schedule.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq, SystemSet)]
+pub enum RealmTeardownSystems {
+    /// Stop and join the background workers so nothing writes to the realm anymore.
+    StopWorkers,
+    /// Save the dirty chunks, see [`VoxelStore::save_dirty_chunks`].
+    Save,
+}
+
+impl RealmTeardownSystems {
+    pub(crate) fn configure(app: &mut App) {
+        app.configure_sets(
+            Last,
+            (Self::StopWorkers, Self::Save)
+                .chain()
+                .run_if(on_event::<AppExit>()),
+        );
+    }
+}
+
+/// Save the dirty chunks of the realm to the [`VoxelStore`], if there is one.
+pub fn save_dirty_chunks(realm: VoxelRealm, store: Option<ResMut<VoxelStore>>) {
+    let Some(mut store) = store else {
+        return;
+    };
+
+    match store.save_dirty_chunks(realm.cm()) {
+        Ok(saved) => info!("Saved {saved} dirty chunks"),
+        Err(error) => error!("Error saving dirty chunks: {error}"),
+    }
+}
@@ -90,7 +90,7 @@ impl<'reg, 'chunk, 'acc> SubdivAccess<'reg, 'chunk, 'acc> {
     }
 
     pub fn access(&self) -> &ChunkRefAccess<'chunk> {
-        &self.access
+        self.access
     }
 
     pub fn mb_write_behaviour(&self) -> MbWriteBehaviour {
@@ -182,7 +182,11 @@ mod tests {
     use parking_lot::{RwLock, RwLockReadGuard};
 
     use crate::{
-        data::registries::{block::BlockVariantRegistry, texture::TextureRegistry},
+        data::{
+            registries::{block::BlockVariantRegistry, texture::TextureRegistry},
+            tile::Face,
+            voxel::rotations::BlockModelRotation,
+        },
         testing_utils::MockChunk,
         topo::block::BlockVoxel,
     };
@@ -260,4 +264,66 @@ mod tests {
             subdiv.get(uvec3(1, 0, 0)).unwrap()
         );
     }
+
+    #[test]
+    fn test_write_read_rotation() {
+        let texreg = TextureRegistry::new_mock();
+        let varreg = RwLock::new(BlockVariantRegistry::new_mock(&texreg));
+        let guard = varreg.read();
+        let chunk = MockChunk::new(BlockVoxel::new_full(BlockVariantRegistry::VOID));
+
+        let rotation = BlockModelRotation::new(Face::East, Face::North).unwrap();
+        let other_rotation = BlockModelRotation::new(Face::Bottom, Face::West).unwrap();
+
+        let mut access = chunk.access();
+        let mut sd_access = SubdivAccess::new(
+            RwLockReadGuard::map(guard, |g| g),
+            &mut access,
+            MbWriteBehaviour::Ignore,
+            Microblock::new(BlockVariantRegistry::VOID),
+        );
+
+        let rotated = FullBlock::new(BlockVariantRegistry::SUBDIV).with_rotation(rotation);
+        sd_access
+            .set(
+                ivec3(2, 2, 2),
+                ChunkAccessInput::new(BlockVoxel::Full(rotated)),
+            )
+            .unwrap();
+        sd_access
+            .set(
+                ivec3(3, 3, 3),
+                ChunkAccessInput::new(BlockVoxel::Full(rotated)),
+            )
+            .unwrap();
+
+        // subdividing the block should keep the rotation of the untouched microblocks
+        sd_access
+            .set_mb(
+                ivec3(3, 3, 3) * SubdividedBlock::SUBDIVISIONS,
+                Microblock::new(BlockVariantRegistry::FULL).with_rotation(other_rotation),
+            )
+            .unwrap();
+
+        drop(sd_access);
+        drop(access);
+
+        let read_access = chunk.read_access();
+        let sd_access = SubdivReadAccess::new(read_access);
+
+        assert_eq!(
+            CaoBlock::Full(rotated),
+            sd_access.get(ivec3(2, 2, 2)).unwrap().block
+        );
+
+        let CaoBlock::Subdivided(subdiv) = sd_access.get(ivec3(3, 3, 3)).unwrap().block else {
+            panic!("expected block to be subdivided")
+        };
+
+        assert_eq!(
+            Some(other_rotation),
+            subdiv.get(uvec3(0, 0, 0)).unwrap().rotation
+        );
+        assert_eq!(Some(rotation), subdiv.get(uvec3(1, 0, 0)).unwrap().rotation);
+    }
 }
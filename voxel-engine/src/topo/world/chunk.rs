@@ -1,4 +1,6 @@
 use std::fmt;
+use std::ops;
+use std::sync::atomic::{AtomicU32, AtomicU64};
 
 use bevy::math::ivec3;
 use bevy::prelude::*;
@@ -8,10 +10,12 @@ use parking_lot::RwLock;
 
 use crate::data::registries::block::BlockVariantRegistry;
 use crate::data::registries::Registry;
+use crate::data::tile::Face;
 use crate::data::voxel::rotations::BlockModelRotation;
 use crate::topo::block::{BlockVoxel, SubdividedBlock};
 use crate::topo::bounding_box::BoundingBox;
 use crate::topo::controller::LoadReasons;
+use crate::topo::light::ChunkLight;
 use crate::topo::storage::containers::data_storage::SyncIndexedChunkContainer;
 
 #[derive(dm::From, dm::Into, dm::Display, Debug, PartialEq, Eq, Hash, Copy, Clone, Component)]
@@ -24,6 +28,11 @@ impl ChunkPos {
         Self(ivec3(x, y, z))
     }
 
+    /// The position of the chunk containing the given worldspace position.
+    pub fn from_worldspace(pos: IVec3) -> Self {
+        Self(pos.div_euclid(Chunk::VEC))
+    }
+
     pub fn worldspace_max(self) -> IVec3 {
         (self.0 * Chunk::SIZE) + (Chunk::SIZE - 1)
     }
@@ -51,6 +60,40 @@ impl ChunkPos {
     pub fn as_vec3(self) -> Vec3 {
         self.0.as_vec3()
     }
+
+    /// The position of the chunk adjacent to this one on the given face.
+    pub fn neighbor(self, face: Face) -> Self {
+        self.offset(face.normal())
+    }
+
+    pub fn offset(self, delta: IVec3) -> Self {
+        Self(self.0 + delta)
+    }
+}
+
+impl ops::Add<IVec3> for ChunkPos {
+    type Output = Self;
+
+    fn add(self, rhs: IVec3) -> Self::Output {
+        self.offset(rhs)
+    }
+}
+
+impl ops::Sub<IVec3> for ChunkPos {
+    type Output = Self;
+
+    fn sub(self, rhs: IVec3) -> Self::Output {
+        self.offset(-rhs)
+    }
+}
+
+/// The offset from `rhs` to `self`.
+impl ops::Sub<ChunkPos> for ChunkPos {
+    type Output = IVec3;
+
+    fn sub(self, rhs: ChunkPos) -> Self::Output {
+        self.0 - rhs.0
+    }
 }
 
 bitflags! {
@@ -97,6 +140,18 @@ impl fmt::Debug for ChunkFlags {
     }
 }
 
+/// Whether a chunk can be read, see [`ChunkManager::load_state`](super::ChunkManager::load_state).
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ChunkLoadState {
+    /// The chunk isn't loaded, reads of it should use defaults.
+    Unloaded,
+    /// The chunk is loaded but hasn't been generated yet (it's primordial or generating), so its voxels are
+    /// placeholders. Work that depends on the chunk should wait until it's ready.
+    Generating,
+    /// The chunk is loaded and generated.
+    Ready,
+}
+
 #[derive(Copy, Clone, Debug, Component, PartialEq, Eq)]
 pub struct ChunkEntity;
 
@@ -110,6 +165,12 @@ pub struct Chunk {
     pub flags: RwLock<ChunkFlags>,
     pub load_reasons: RwLock<LoadReasons>,
     pub variants: SyncIndexedChunkContainer<BlockVoxel>,
+    /// The change tick of the chunk manager when this chunk was last written to.
+    pub changed_tick: AtomicU64,
+    pub light: RwLock<ChunkLight>,
+    /// The raw [`NeighborSet`](crate::topo::neighbors::NeighborSet) of the neighbors that need to be remeshed
+    /// along with this chunk. Only meaningful while the chunk is flagged with [`ChunkFlags::REMESH_NEIGHBORS`].
+    pub remesh_neighbors: AtomicU32,
 }
 
 const CHUNK_SIZE: usize = 16;
@@ -132,10 +193,29 @@ impl Chunk {
 
     #[inline]
     pub fn new(filling: BlockVoxel, initial_flags: ChunkFlags, load_reasons: LoadReasons) -> Self {
+        Self::with_random_state(
+            filling,
+            initial_flags,
+            load_reasons,
+            ahash::RandomState::new(),
+        )
+    }
+
+    /// Like [`Chunk::new`], but the chunk's containers use the given random state for hashing.
+    #[inline]
+    pub fn with_random_state(
+        filling: BlockVoxel,
+        initial_flags: ChunkFlags,
+        load_reasons: LoadReasons,
+        random_state: ahash::RandomState,
+    ) -> Self {
         Self {
             flags: RwLock::new(initial_flags),
             load_reasons: RwLock::new(load_reasons),
-            variants: SyncIndexedChunkContainer::filled(filling),
+            variants: SyncIndexedChunkContainer::filled_with_random_state(filling, random_state),
+            changed_tick: AtomicU64::new(0),
+            light: RwLock::new(ChunkLight::new()),
+            remesh_neighbors: AtomicU32::new(0),
         }
     }
 }
@@ -166,4 +246,23 @@ mod test {
         test(-1, -16, -1);
         test(-2, -32, -17);
     }
+
+    #[test]
+    fn chunkpos_arithmetic() {
+        let pos = ChunkPos::new(3, -2, 7);
+
+        assert_eq!(ChunkPos::new(3, -1, 7), pos.neighbor(Face::Top));
+        assert_eq!(ChunkPos::new(3, -3, 7), pos.neighbor(Face::Bottom));
+
+        for face in Face::FACES {
+            let opposite = Face::from_normal(-face.normal()).unwrap();
+            assert_eq!(pos, pos.neighbor(face).neighbor(opposite));
+        }
+
+        let delta = ivec3(-5, 4, 1);
+        assert_eq!(ChunkPos::from(pos.as_ivec3() + delta), pos.offset(delta));
+        assert_eq!(pos.offset(delta), pos + delta);
+        assert_eq!(pos, pos + delta - delta);
+        assert_eq!(delta, (pos + delta) - pos);
+    }
 }
@@ -1,30 +1,44 @@
 use std::{
-    ops::{Deref, DerefMut},
+    ops::DerefMut,
     sync::{
-        atomic::{AtomicBool, Ordering},
-        Arc,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Weak,
     },
     time::Duration,
 };
 
 use bevy::{
-    ecs::entity::Entity,
-    math::{ivec3, IVec3},
+    ecs::system::Resource,
+    math::{ivec2, ivec3, IVec3, Vec3},
+    render::primitives::Aabb,
 };
-use dashmap::{mapref::one::Ref, DashSet};
-use parking_lot::{MappedRwLockReadGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use dashmap::DashSet;
+use parking_lot::{MappedRwLockReadGuard, Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
 
 use crate::{
+    data::registries::block::BlockVariantRegistry,
     topo::{
+        access::{ReadAccess, WriteAccess},
         block::{BlockVoxel, FullBlock},
+        bounding_box::BoundingBox,
+        collision::{sweep_aabb, SolidGrid, SweepResult, MAX_SWEEP_DISTANCE, SWEEP_STEP},
         controller::LoadReasons,
-        neighbors::{Neighbors, NEIGHBOR_ARRAY_SIZE, NEIGHBOR_CUBIC_ARRAY_DIMENSIONS},
+        heightmap::{surface_variant, HeightMap, HeightMapColumn},
+        light::{ChunkLight, LightLevel},
+        neighbors::{
+            neighbor_index, neighbor_radius, touched_neighbors, Neighbors, NEIGHBOR_ARRAY_SIZE,
+            NEIGHBOR_MAX_RADIUS,
+        },
+        selection::Selection,
+        worldgen::GenerationPriority,
     },
-    util::{ivec3_to_1d, ChunkMap, ChunkSet, SyncHashMap},
+    util::ChunkMap,
 };
 
 use super::{
-    chunk::ChunkFlags, Chunk, ChunkContainerError, ChunkManagerError, ChunkPos, ChunkRef,
+    chunk::{ChunkFlags, ChunkLoadState},
+    edits::{EditCause, EditLog, EditTransaction},
+    CaoBlock, Chunk, ChunkAccessInput, ChunkContainerError, ChunkManagerError, ChunkPos, ChunkRef,
     ChunkRefReadAccess,
 };
 
@@ -67,6 +81,14 @@ impl LoadedChunkContainer {
         Ok(LccRef(chunk))
     }
 
+    /// Snapshot of the positions of all chunks in this container at the time of calling.
+    pub fn positions(&self) -> Vec<ChunkPos> {
+        let guard = self.map.read();
+        let mut positions = Vec::with_capacity(guard.len());
+        guard.for_each_pos(|pos| positions.push(pos));
+        positions
+    }
+
     /// Get the state of the global lock for this chunk container
     pub fn global_lock_state(&self) -> GlobalLockState {
         if self.force_write.load(Ordering::Relaxed) || self.map.is_locked_exclusive() {
@@ -92,9 +114,8 @@ impl LoadedChunkContainer {
 
         let mut guard = timeout
             .map(|timeout| {
-                self.map.try_write_for(timeout).map(|guard| {
+                self.map.try_write_for(timeout).inspect(|_guard| {
                     self.force_write.store(false, Ordering::Release);
-                    guard
                 })
             })
             .unwrap_or_else(|| {
@@ -133,7 +154,7 @@ impl PendingChunkChanges {
     }
 
     pub fn iter(&self) -> impl Iterator<Item = ChunkPos> + '_ {
-        self.0.iter().map(|r| r.clone())
+        self.0.iter().map(|r| *r)
     }
 }
 
@@ -142,6 +163,8 @@ pub struct ChunkStatuses {
     pub updated: DashSet<ChunkPos, fxhash::FxBuildHasher>,
     pub generating: DashSet<ChunkPos, fxhash::FxBuildHasher>,
     pub fresh: DashSet<ChunkPos, fxhash::FxBuildHasher>,
+    /// Incremented every time a chunk is written to.
+    pub change_tick: AtomicU64,
 }
 
 /// Indicates what happened when we tried to load a chunk
@@ -155,10 +178,38 @@ pub enum ChunkLoadResult {
     Updated(LoadReasons),
 }
 
+/// How the hash tables in the containers of a chunk manager's chunks are seeded. With random seeds the
+/// internal layout of the containers changes from run to run, so anything that depends on it (like
+/// serialization and golden tests) can only be reproduced with a fixed seed.
+#[derive(Resource, Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum ContainerHasher {
+    /// A new random seed for every container.
+    #[default]
+    Random,
+    /// The same seed for every container.
+    Seeded(u64),
+}
+
+impl ContainerHasher {
+    pub fn random_state(self) -> ahash::RandomState {
+        match self {
+            Self::Random => ahash::RandomState::new(),
+            // the other keys are derived from the seed with some digits of pi
+            Self::Seeded(seed) => ahash::RandomState::with_seeds(
+                seed,
+                seed ^ 0x243f_6a88_85a3_08d3,
+                seed ^ 0x1319_8a2e_0370_7344,
+                seed ^ 0xa409_3822_299f_31d0,
+            ),
+        }
+    }
+}
+
 pub struct ChunkManagerAccess<'a> {
     chunks: &'a mut ChunkMap<Chunk>,
     statuses: RwLockWriteGuard<'a, ChunkStatuses>,
     default_block: FullBlock,
+    hasher: ContainerHasher,
 }
 
 impl<'a> ChunkManagerAccess<'a> {
@@ -213,7 +264,7 @@ impl<'a> ChunkManagerAccess<'a> {
                 let mut existing_load_reasons = chunk.load_reasons.write();
 
                 existing_load_reasons.insert(load_reasons);
-                Ok(ChunkLoadResult::Updated(existing_load_reasons.clone()))
+                Ok(ChunkLoadResult::Updated(*existing_load_reasons))
             }
             // Just forward the error to the caller, not much we can do here anyways
             Err(error) => Err(error),
@@ -232,16 +283,52 @@ impl<'a> ChunkManagerAccess<'a> {
             return Err(ChunkManagerError::AlreadyLoaded);
         }
 
-        let chunk = Chunk::new(
+        let chunk = Chunk::with_random_state(
             BlockVoxel::Full(self.default_block),
             ChunkFlags::PRIMORDIAL,
             load_reasons,
+            self.hasher.random_state(),
         );
         self.chunks.set(pos, chunk);
         Ok(())
     }
 }
 
+/// Keeps chunks that were force loaded with [`ChunkManager::force_load`] loaded. A chunk is released
+/// once the ticket and all its clones are dropped, and it's unloaded the next time expired tickets are
+/// released (see [`ChunkManager::release_expired_tickets`]), unless there are other reasons to keep it loaded.
+#[derive(Clone, Debug)]
+pub struct LoadTicket {
+    handle: Arc<()>,
+    priority: GenerationPriority,
+}
+
+impl Default for LoadTicket {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LoadTicket {
+    /// Create a ticket with the lowest priority.
+    pub fn new() -> Self {
+        Self::with_priority(GenerationPriority::LOWEST)
+    }
+
+    /// Create a ticket with the given priority. Chunks loaded with tickets of a higher priority are
+    /// loaded and generated before chunks loaded with tickets of a lower priority.
+    pub fn with_priority(priority: GenerationPriority) -> Self {
+        Self {
+            handle: Arc::new(()),
+            priority,
+        }
+    }
+
+    pub fn priority(&self) -> GenerationPriority {
+        self.priority
+    }
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum GlobalLockState {
     Locked,
@@ -251,18 +338,50 @@ pub enum GlobalLockState {
 pub struct ChunkManager {
     loaded_chunks: LoadedChunkContainer,
     status: RwLock<ChunkStatuses>,
+    tickets: Mutex<ChunkMap<Vec<Weak<()>>>>,
     default_block: FullBlock,
+    hasher: ContainerHasher,
+    edits: EditLog,
 }
 
+// The chunk manager is shared between the mesher workers (and other threads), which read chunks concurrently
+// while the main thread writes to them. All the interior mutability of chunks goes through locks or atomics,
+// so none of this needs unsafe code, but these assertions make sure it stays that way.
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+
+    assert_send_sync::<ChunkManager>();
+    assert_send_sync::<Chunk>();
+    assert_send_sync::<super::chunk_ref::OwnedChunkAccess>();
+};
+
 impl ChunkManager {
     pub fn new(default_block: FullBlock) -> Self {
         Self {
             loaded_chunks: LoadedChunkContainer::default(),
             status: RwLock::new(ChunkStatuses::default()),
+            tickets: Mutex::new(ChunkMap::default()),
             default_block,
+            hasher: ContainerHasher::default(),
+            edits: EditLog::default(),
         }
     }
 
+    /// Use the given hasher for the containers of chunks loaded from now on.
+    pub fn with_container_hasher(mut self, hasher: ContainerHasher) -> Self {
+        self.hasher = hasher;
+        self
+    }
+
+    pub fn container_hasher(&self) -> ContainerHasher {
+        self.hasher
+    }
+
+    /// The voxel edits made to the loaded chunks since the log was last drained.
+    pub fn edit_log(&self) -> &EditLog {
+        &self.edits
+    }
+
     /// Gets the loaded chunk at the given position if it exists, otherwise return an error.
     /// If `get_primordial` is false this function will return an error if the chunk is tagged as primordial.
     pub fn get_loaded_chunk(
@@ -272,10 +391,8 @@ impl ChunkManager {
     ) -> Result<ChunkRef<'_>, ChunkManagerError> {
         let chunk = self.loaded_chunks.get(pos)?;
 
-        if !get_primordial {
-            if chunk.flags.read().contains(ChunkFlags::PRIMORDIAL) {
-                return Err(ChunkManagerError::Primordial);
-            }
+        if !get_primordial && chunk.flags.read().contains(ChunkFlags::PRIMORDIAL) {
+            return Err(ChunkManagerError::Primordial);
         }
 
         Ok(ChunkRef {
@@ -283,9 +400,48 @@ impl ChunkManager {
             stats: self.status.read(),
             pos,
             entity: None,
+            edits: &self.edits,
         })
     }
 
+    /// Like [`ChunkManager::get_loaded_chunk`] without primordial chunks, but returns
+    /// [`ChunkManagerError::Unloaded`] if the chunk doesn't exist.
+    fn get_loaded_chunk_or_unloaded(
+        &self,
+        pos: ChunkPos,
+    ) -> Result<ChunkRef<'_>, ChunkManagerError> {
+        self.get_loaded_chunk(pos, false)
+            .map_err(|error| match error {
+                error if error.is_doesnt_exists() => ChunkManagerError::Unloaded,
+                error => error,
+            })
+    }
+
+    /// Iterate over all loaded chunks (including primordial ones).
+    /// The positions of the loaded chunks are snapshotted when this function is called, chunks that
+    /// are unloaded while iterating are skipped, and chunks loaded while iterating won't be yielded.
+    pub fn loaded_chunks(&self) -> impl Iterator<Item = (ChunkPos, ChunkRef<'_>)> + '_ {
+        self.loaded_chunks
+            .positions()
+            .into_iter()
+            .filter_map(|pos| Some((pos, self.get_loaded_chunk(pos, true).ok()?)))
+    }
+
+    /// The current change tick. Chunks written to after this function was called will have a
+    /// greater change tick than the one returned.
+    pub fn change_tick(&self) -> u64 {
+        self.status.read().change_tick.load(Ordering::Acquire)
+    }
+
+    /// Iterate over the positions of all loaded chunks that were written to after the given change tick.
+    /// Store the result of [`ChunkManager::change_tick`] before calling this function and pass it to the next
+    /// call to only get chunks that were changed in between.
+    pub fn chunks_changed_since(&self, tick: u64) -> impl Iterator<Item = ChunkPos> + '_ {
+        self.loaded_chunks()
+            .filter(move |(_, cref)| cref.changed_tick() > tick)
+            .map(|(pos, _)| pos)
+    }
+
     /// Get the chunk flags for the given chunk position
     pub fn chunk_flags(&self, pos: ChunkPos) -> Option<ChunkFlags> {
         self.get_loaded_chunk(pos, true)
@@ -293,6 +449,28 @@ impl ChunkManager {
             .ok()
     }
 
+    /// Whether the chunk at the given position is unloaded, waiting to be generated, or ready to be read.
+    pub fn load_state(&self, pos: ChunkPos) -> ChunkLoadState {
+        match self.chunk_flags(pos) {
+            None => ChunkLoadState::Unloaded,
+            Some(flags) if flags.intersects(ChunkFlags::PRIMORDIAL | ChunkFlags::GENERATING) => {
+                ChunkLoadState::Generating
+            }
+            Some(_) => ChunkLoadState::Ready,
+        }
+    }
+
+    /// Whether none of the neighbors of the chunk within the given radius (see [`neighbor_radius`]) are
+    /// [generating](ChunkLoadState::Generating). Work that reads the neighbors of a chunk (like meshing)
+    /// should wait until this is true, instead of reading the placeholder voxels of generating neighbors.
+    /// Unloaded neighbors don't count, since they won't be ready any time soon.
+    pub fn neighbors_ready(&self, pos: ChunkPos, radius: u8) -> bool {
+        BoundingBox::from_min_max(IVec3::splat(-1), IVec3::splat(2))
+            .cartesian_iter()
+            .filter(|&offset| offset != IVec3::ZERO && neighbor_radius(offset) <= radius)
+            .all(|offset| self.load_state(pos + offset) != ChunkLoadState::Generating)
+    }
+
     /// Get the state of the global lock
     pub fn global_lock_state(&self) -> GlobalLockState {
         self.loaded_chunks.global_lock_state()
@@ -328,14 +506,28 @@ impl ChunkManager {
                     chunks,
                     statuses,
                     default_block: self.default_block,
+                    hasher: self.hasher,
                 };
 
                 f(access)
             })
     }
 
-    // TODO: test
-    pub fn with_neighbors<F, R>(&self, pos: ChunkPos, mut f: F) -> Result<R, ChunkManagerError>
+    pub fn with_neighbors<F, R>(&self, pos: ChunkPos, f: F) -> Result<R, ChunkManagerError>
+    where
+        F: for<'a> FnMut(Neighbors<'a>) -> R,
+    {
+        self.with_neighbors_in_radius(pos, NEIGHBOR_MAX_RADIUS, f)
+    }
+
+    /// Like [`ChunkManager::with_neighbors`] but only gathers the neighbors within the given radius
+    /// (see [`neighbor_radius`]). Neighbors outside the radius are read as the default block.
+    pub fn with_neighbors_in_radius<F, R>(
+        &self,
+        pos: ChunkPos,
+        radius: u8,
+        mut f: F,
+    ) -> Result<R, ChunkManagerError>
     where
         F: for<'a> FnMut(Neighbors<'a>) -> R,
     {
@@ -347,14 +539,13 @@ impl ChunkManager {
             for y in -1..=1 {
                 for z in -1..=1 {
                     let nbrpos = ivec3(x, y, z);
-                    if nbrpos == IVec3::ZERO {
+                    if nbrpos == IVec3::ZERO || neighbor_radius(nbrpos) > radius {
                         continue;
                     }
 
-                    let nbrpos_ws = ChunkPos::from(nbrpos + IVec3::from(pos));
+                    let nbrpos_ws = pos + nbrpos;
                     if let Ok(chunk_ref) = self.get_loaded_chunk(nbrpos_ws, false) {
-                        refs[ivec3_to_1d(nbrpos + IVec3::ONE, NEIGHBOR_CUBIC_ARRAY_DIMENSIONS)
-                            .unwrap()] = Some(chunk_ref)
+                        refs[neighbor_index(nbrpos)] = Some(chunk_ref)
                     }
                 }
             }
@@ -372,7 +563,8 @@ impl ChunkManager {
             });
         }
 
-        let neighbors = Neighbors::from_raw(accesses, BlockVoxel::Full(self.default_block));
+        let neighbors =
+            Neighbors::from_raw(accesses, BlockVoxel::Full(self.default_block)).with_radius(radius);
         let result = f(neighbors);
 
         drop(refs);
@@ -380,8 +572,460 @@ impl ChunkManager {
         Ok(result)
     }
 
+    /// Recalculate the light of the chunk at the given position. The light of the neighboring chunks is used as the
+    /// light coming into the chunk from outside. Neighbors that aren't loaded are treated as dark, except for
+    /// the ones above the chunk which are treated as open sky. The sky access of the chunk is baked again too,
+    /// see [`ChunkLight::bake_sky_access`].
+    pub fn relight_chunk(
+        &self,
+        pos: ChunkPos,
+        registry: &BlockVariantRegistry,
+    ) -> Result<(), ChunkManagerError> {
+        let mut refs =
+            std::array::from_fn::<Option<ChunkRef>, { NEIGHBOR_ARRAY_SIZE }, _>(|_| None);
+
+        for x in -1..=1 {
+            for y in -1..=1 {
+                for z in -1..=1 {
+                    let nbrpos = ivec3(x, y, z);
+                    if nbrpos == IVec3::ZERO {
+                        continue;
+                    }
+
+                    let nbrpos_ws = pos + nbrpos;
+                    if let Ok(chunk_ref) = self.get_loaded_chunk(nbrpos_ws, false) {
+                        refs[neighbor_index(nbrpos)] = Some(chunk_ref)
+                    }
+                }
+            }
+        }
+
+        let lights = refs
+            .each_ref()
+            .map(|cref| cref.as_ref().map(ChunkRef::light));
+
+        let border = |local: IVec3| {
+            let offset = local.div_euclid(Chunk::VEC);
+            match &lights[neighbor_index(offset)] {
+                Some(light) => light.get(local.rem_euclid(Chunk::VEC)).unwrap_or_default(),
+                None if offset.y > 0 => LightLevel::new(0, LightLevel::MAX),
+                None => LightLevel::DARK,
+            }
+        };
+
+        let chunk = self.get_loaded_chunk(pos, false)?;
+        let light = self.with_neighbors(pos, |neighbors| {
+            chunk.with_read_access(|access| {
+                let mut light = ChunkLight::propagate(&access, &neighbors, registry, border);
+                light.bake_sky_access(&access, &neighbors, registry);
+                light
+            })
+        })??;
+
+        drop(lights);
+        chunk.set_light(light);
+
+        Ok(())
+    }
+
+    /// Read the voxel at the given worldspace position. Returns [`ChunkManagerError::Unloaded`] if the chunk isn't loaded.
+    pub fn get_voxel(&self, ws: IVec3) -> Result<BlockVoxel, ChunkManagerError> {
+        let chunk = self.get_loaded_chunk_or_unloaded(ChunkPos::from_worldspace(ws))?;
+
+        let block = chunk.with_read_access(|access| {
+            access
+                .get(ws.rem_euclid(Chunk::VEC))
+                .map(|output| match output.block {
+                    CaoBlock::Full(full) => BlockVoxel::Full(full),
+                    CaoBlock::Subdivided(subdiv) => BlockVoxel::Subdivided(subdiv.clone()),
+                })
+        })??;
+
+        Ok(block)
+    }
+
+    /// Write a voxel at the given worldspace position. The chunk is flagged for remeshing, and so are the loaded
+    /// neighboring chunks that share a face, edge, or corner with the voxel, since their meshes depend on the voxels
+    /// along their border. Returns [`ChunkManagerError::Unloaded`] if the chunk isn't loaded.
+    pub fn set_voxel(&self, ws: IVec3, input: ChunkAccessInput) -> Result<(), ChunkManagerError> {
+        self.set_voxel_caused(ws, input, EditCause::Direct)
+    }
+
+    /// Like [`ChunkManager::set_voxel`], but the edit is reported with the given cause.
+    pub fn set_voxel_caused(
+        &self,
+        ws: IVec3,
+        input: ChunkAccessInput,
+        cause: EditCause,
+    ) -> Result<(), ChunkManagerError> {
+        let chunk_pos = ChunkPos::from_worldspace(ws);
+        let local = ws.rem_euclid(Chunk::VEC);
+
+        let chunk = self.get_loaded_chunk_or_unloaded(chunk_pos)?;
+
+        chunk.with_access_caused(true, cause, |mut access| access.set(local, input))??;
+        chunk.update_flags(|flags| flags.insert(ChunkFlags::REMESH));
+
+        for offset in touched_neighbors(local) {
+            let Ok(neighbor) = self.get_loaded_chunk(chunk_pos + offset, false) else {
+                continue;
+            };
+
+            neighbor.update_flags(|flags| flags.insert(ChunkFlags::REMESH));
+        }
+
+        Ok(())
+    }
+
+    /// Flag the chunk at `pos` for remeshing even though nothing in it changed, like after changing a
+    /// render setting that affects how meshes are built. The chunk is remeshed like any other updated chunk.
+    /// Returns [`ChunkManagerError::Unloaded`] if the chunk isn't loaded.
+    pub fn request_remesh(&self, pos: ChunkPos) -> Result<(), ChunkManagerError> {
+        let chunk = self.get_loaded_chunk_or_unloaded(pos)?;
+
+        chunk.update_flags(|flags| flags.insert(ChunkFlags::REMESH));
+        Ok(())
+    }
+
+    /// Make many voxel edits at once. The writes made to the transaction in the closure are committed when it
+    /// returns, and every affected chunk is written to, marked as changed, and flagged for remeshing only once
+    /// (along with the neighbors touched by the writes). The edits are reported as [`EditCause::Direct`] edits.
+    /// If any of the affected chunks isn't loaded nothing is written and [`ChunkManagerError::Unloaded`] is returned.
+    pub fn edit_transaction<F, U>(&self, f: F) -> Result<U, ChunkManagerError>
+    where
+        F: FnOnce(&mut EditTransaction) -> U,
+    {
+        let mut tx = EditTransaction::default();
+        let result = f(&mut tx);
+
+        // get all the chunks before writing anything, so we don't partially apply the transaction
+        let mut chunks = ChunkMap::with_capacity(tx.chunks().count());
+        for pos in tx.chunks() {
+            let chunk = self.get_loaded_chunk_or_unloaded(pos)?;
+
+            chunks.set(pos, chunk);
+        }
+
+        for (pos, writes) in tx.into_writes() {
+            let Some(chunk) = chunks.get(pos) else {
+                continue;
+            };
+
+            chunk.with_access_caused(false, EditCause::Direct, |mut access| {
+                writes
+                    .into_iter()
+                    .try_for_each(|(local, input)| access.set(local, input))
+            })??;
+        }
+
+        Ok(result)
+    }
+
+    /// Read every voxel in the selection, in the order of [`Selection::iter`]. Each chunk is only locked once, to
+    /// take a snapshot of it. Returns [`ChunkManagerError::Unloaded`] if any chunk in the selection isn't loaded.
+    pub fn read_selection(
+        &self,
+        selection: &Selection,
+    ) -> Result<Vec<(IVec3, BlockVoxel)>, ChunkManagerError> {
+        let positions = selection.chunks();
+        let mut snapshots = ChunkMap::with_capacity(positions.len());
+        for pos in positions.iter() {
+            let chunk = self.get_loaded_chunk_or_unloaded(pos)?;
+
+            snapshots.set(pos, chunk.read_snapshot());
+        }
+
+        let mut voxels = Vec::with_capacity(selection.volume());
+        for ws in selection.iter() {
+            let Some(snapshot) = snapshots.get(ChunkPos::from_worldspace(ws)) else {
+                return Err(ChunkManagerError::Unloaded);
+            };
+
+            let block = match snapshot.get(ws.rem_euclid(Chunk::VEC))?.block {
+                CaoBlock::Full(full) => BlockVoxel::Full(full),
+                CaoBlock::Subdivided(subdiv) => BlockVoxel::Subdivided(subdiv.clone()),
+            };
+
+            voxels.push((ws, block));
+        }
+
+        Ok(voxels)
+    }
+
+    /// Write the block to every voxel in the selection in a single [`ChunkManager::edit_transaction`].
+    pub fn fill_selection(
+        &self,
+        selection: &Selection,
+        block: BlockVoxel,
+    ) -> Result<(), ChunkManagerError> {
+        self.edit_transaction(|tx| {
+            for ws in selection.iter() {
+                tx.set(ws, ChunkAccessInput::new(block.clone()));
+            }
+        })
+    }
+
+    /// The boxes of solid voxels in the given region (in blocks), for use in physics. Blocks with a model are solid,
+    /// and subdivided blocks are solid where their microblocks have a model. Adjacent solid voxels are greedily merged
+    /// into larger boxes, and boxes are clipped to the region. Chunks that aren't loaded (or are primordial)
+    /// don't contribute any boxes.
+    pub fn collision_aabbs_in(
+        &self,
+        region: BoundingBox,
+        registry: &BlockVariantRegistry,
+    ) -> Vec<Aabb> {
+        if region.volume() == 0 {
+            return Vec::new();
+        }
+
+        let mut grid = SolidGrid::new(region);
+
+        let chunks = BoundingBox::from_min_max(
+            region.min().div_euclid(Chunk::VEC),
+            (region.max() - IVec3::ONE).div_euclid(Chunk::VEC) + IVec3::ONE,
+        );
+
+        for chunk_pos in chunks.cartesian_iter().map(ChunkPos::from) {
+            let Ok(chunk) = self.get_loaded_chunk(chunk_pos, false) else {
+                continue;
+            };
+
+            let chunk_min = chunk_pos.worldspace_min();
+            let Some(overlap) =
+                region.intersection(BoundingBox::from_min_max(chunk_min, chunk_min + Chunk::VEC))
+            else {
+                continue;
+            };
+
+            let _ = chunk.with_read_access(|access| {
+                for pos in overlap.cartesian_iter() {
+                    if let Ok(output) = access.get(pos - chunk_min) {
+                        grid.insert_block(pos, output.block, registry);
+                    }
+                }
+            });
+        }
+
+        grid.merge()
+    }
+
+    /// The surface of the columns in the given region (in chunkspace), for things like minimaps. Chunks are scanned
+    /// from the top down, and the surface of a column is the top-most voxel that isn't air. Chunks that aren't loaded
+    /// (or are primordial) are skipped, so columns with only air or unloaded chunks have no surface. Fails if a loaded
+    /// chunk can't be read, rather than leaving its columns out.
+    pub fn heightmap(
+        &self,
+        chunks: BoundingBox,
+        registry: &BlockVariantRegistry,
+    ) -> Result<HeightMap, ChunkManagerError> {
+        let min = chunks.min() * Chunk::SIZE;
+        let size = (chunks.max() - chunks.min()) * Chunk::SIZE;
+        let mut heightmap = HeightMap::new(ivec2(min.x, min.z), ivec2(size.x, size.z));
+
+        for cx in chunks.min().x..chunks.max().x {
+            for cz in chunks.min().z..chunks.max().z {
+                for cy in (chunks.min().y..chunks.max().y).rev() {
+                    let chunk_pos = ChunkPos::new(cx, cy, cz);
+                    let Ok(chunk) = self.get_loaded_chunk(chunk_pos, false) else {
+                        continue;
+                    };
+
+                    let chunk_min = chunk_pos.worldspace_min();
+
+                    let found_all = chunk.with_read_access(|access| {
+                        let mut found_all = true;
+
+                        for x in 0..Chunk::SIZE {
+                            for z in 0..Chunk::SIZE {
+                                let (ws_x, ws_z) = (chunk_min.x + x, chunk_min.z + z);
+                                if heightmap.is_set(ws_x, ws_z) {
+                                    continue;
+                                }
+
+                                let mut surface = None;
+                                for y in (0..Chunk::SIZE).rev() {
+                                    let output = access.get(ivec3(x, y, z))?;
+                                    if let Some(block) = surface_variant(output.block, registry) {
+                                        surface = Some(HeightMapColumn {
+                                            y: chunk_min.y + y,
+                                            block,
+                                        });
+                                        break;
+                                    }
+                                }
+
+                                match surface {
+                                    Some(column) => heightmap.set(ws_x, ws_z, column),
+                                    None => found_all = false,
+                                }
+                            }
+                        }
+
+                        Ok::<_, ChunkManagerError>(found_all)
+                    })??;
+
+                    // no need to look further down if every column in this chunk has a surface
+                    if found_all {
+                        break;
+                    }
+                }
+            }
+        }
+
+        Ok(heightmap)
+    }
+
+    /// Move a box with the given velocity through the solid voxels of the loaded chunks, stopping at the first voxel
+    /// it hits. See [`ChunkManager::collision_aabbs_in`] for which voxels are solid.
+    ///
+    /// The box is moved in steps of at most [`SWEEP_STEP`] blocks, so only the voxels around each step are collected.
+    /// Velocities longer than [`MAX_SWEEP_DISTANCE`] are clamped to it, and the result is relative to the clamped
+    /// velocity.
+    pub fn sweep(
+        &self,
+        aabb: Aabb,
+        velocity: Vec3,
+        registry: &BlockVariantRegistry,
+    ) -> SweepResult {
+        let velocity = velocity.clamp_length_max(MAX_SWEEP_DISTANCE);
+        let steps = (velocity.length() / SWEEP_STEP).ceil().max(1.0) as u32;
+        let step = velocity / steps as f32;
+
+        let mut moving = aabb;
+        for i in 0..steps {
+            let result = self.sweep_step(moving, step, registry);
+            if result.hit() {
+                return SweepResult {
+                    time: (i as f32 + result.time) / steps as f32,
+                    ..result
+                };
+            }
+
+            moving.center = result.position.into();
+        }
+
+        SweepResult {
+            time: 1.0,
+            normal: None,
+            position: Vec3::from(aabb.center) + velocity,
+        }
+    }
+
+    fn sweep_step(
+        &self,
+        aabb: Aabb,
+        velocity: Vec3,
+        registry: &BlockVariantRegistry,
+    ) -> SweepResult {
+        let start_min = Vec3::from(aabb.min());
+        let start_max = Vec3::from(aabb.max());
+
+        // every voxel the box could touch on its way
+        let region = BoundingBox::from_min_max(
+            start_min.min(start_min + velocity).floor().as_ivec3() - IVec3::ONE,
+            start_max.max(start_max + velocity).ceil().as_ivec3() + IVec3::ONE,
+        );
+
+        let obstacles = self.collision_aabbs_in(region, registry);
+        sweep_aabb(aabb, velocity, &obstacles)
+    }
+
+    /// Load the chunk at the given position and keep it loaded until `ticket` (and all its clones) are
+    /// dropped, regardless of any observers. The same ticket can be used to force load multiple chunks, and a
+    /// chunk can be force loaded by multiple tickets. The chunk is loaded with the [`LoadReasons::TICKET`] reason.
+    /// Newly loaded chunks are not generated, the caller should generate the chunk if the result is [`ChunkLoadResult::New`].
+    pub fn force_load(
+        &self,
+        pos: ChunkPos,
+        ticket: &LoadTicket,
+    ) -> Result<ChunkLoadResult, ChunkManagerError> {
+        // the tickets are locked while loading the chunk so that we can't race with
+        // release_expired_tickets removing the load reason after we've loaded the chunk
+        let mut tickets = self.tickets.lock();
+
+        let result = self
+            .with_global_lock(None, false, |mut access| {
+                access.load_chunk(pos, LoadReasons::TICKET)
+            })
+            .unwrap()?;
+
+        tickets
+            .entry(pos)
+            .or_default()
+            .push(Arc::downgrade(&ticket.handle));
+
+        Ok(result)
+    }
+
+    /// Remove the [`LoadReasons::TICKET`] load reason from force loaded chunks whose tickets have all been dropped.
+    /// Returns the positions of the chunks that were unloaded because they had no load reasons left.
+    pub fn release_expired_tickets(&self) -> Vec<ChunkPos> {
+        let mut tickets = self.tickets.lock();
+
+        let mut expired = Vec::new();
+        tickets.for_each_entry_mut(|pos, chunk_tickets| {
+            chunk_tickets.retain(|ticket| ticket.strong_count() > 0);
+
+            if chunk_tickets.is_empty() {
+                expired.push(pos);
+            }
+        });
+
+        if expired.is_empty() {
+            return Vec::new();
+        }
+
+        for &pos in &expired {
+            tickets.remove(pos);
+        }
+
+        self.with_global_lock(None, false, |mut access| {
+            expired
+                .into_iter()
+                .filter(|&pos| access.unload_chunk(pos, LoadReasons::TICKET) == Ok(true))
+                .collect()
+        })
+        .unwrap()
+    }
+
     pub fn updated_chunks(&self) -> UpdatedChunks<'_> {
-        UpdatedChunks { manager: &self }
+        UpdatedChunks { manager: self }
+    }
+}
+
+/// Helpers for building small realms with known contents in tests, without worldgen or a Bevy app.
+#[cfg(test)]
+impl ChunkManager {
+    /// An empty chunk manager where chunks are filled with void when they're loaded.
+    pub fn new_test() -> Self {
+        Self::new(FullBlock::new(BlockVariantRegistry::VOID))
+    }
+
+    /// Load the chunk at `pos` as if it was generated by calling `f` with access to it. The chunk is
+    /// ready to be used by the time this returns, so it isn't primordial and no edits are recorded for it.
+    pub fn insert_test_chunk<F>(&self, pos: ChunkPos, f: F)
+    where
+        F: for<'a> FnOnce(&mut super::Crwa<'a>),
+    {
+        self.with_global_lock(None, false, |mut access| {
+            access.load_chunk(pos, LoadReasons::RENDER).unwrap();
+        })
+        .unwrap();
+
+        let cref = self.get_loaded_chunk(pos, true).unwrap();
+        cref.with_access(true, |mut access| f(&mut access)).unwrap();
+        cref.update_flags(|flags| flags.remove(ChunkFlags::PRIMORDIAL));
+    }
+
+    /// Like [`ChunkManager::insert_test_chunk`], but every voxel in the chunk is `block`.
+    pub fn insert_filled_test_chunk(&self, pos: ChunkPos, block: BlockVoxel) {
+        self.insert_test_chunk(pos, |access| {
+            for ls in Chunk::BOUNDING_BOX.cartesian_iter() {
+                access
+                    .set(ls, ChunkAccessInput::new(block.clone()))
+                    .unwrap();
+            }
+        });
     }
 }
 
@@ -414,3 +1058,517 @@ impl<'a> UpdatedChunks<'a> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        data::registries::{block::BlockVariantId, texture::TextureRegistry},
+        topo::neighbors::NeighborSet,
+        util::ChunkSet,
+    };
+
+    use super::*;
+
+    #[test]
+    fn loaded_chunks() {
+        let manager = ChunkManager::new_test();
+
+        let positions = [
+            ChunkPos::new(0, 0, 0),
+            ChunkPos::new(1, -2, 3),
+            ChunkPos::new(-5, 0, 0),
+            ChunkPos::new(2, 2, 2),
+        ];
+
+        manager
+            .with_global_lock(None, false, |mut access| {
+                for pos in positions {
+                    access.load_chunk(pos, LoadReasons::RENDER).unwrap();
+                }
+            })
+            .unwrap();
+
+        let mut loaded = ChunkSet::default();
+        for (pos, cref) in manager.loaded_chunks() {
+            assert_eq!(pos, cref.pos());
+            assert!(loaded.set(pos));
+        }
+
+        assert_eq!(positions.len(), loaded.len());
+        for pos in positions {
+            assert!(loaded.contains(pos));
+        }
+    }
+
+    #[test]
+    fn force_load() {
+        let manager = ChunkManager::new_test();
+        let pos = ChunkPos::new(3, 0, -1);
+
+        // the chunk is loaded because an observer is in range of it
+        manager
+            .with_global_lock(None, false, |mut access| {
+                access.load_chunk(pos, LoadReasons::RENDER).unwrap();
+            })
+            .unwrap();
+
+        let ticket = LoadTicket::new();
+        let clone = ticket.clone();
+        assert_eq!(
+            ChunkLoadResult::Updated(LoadReasons::RENDER | LoadReasons::TICKET),
+            manager.force_load(pos, &ticket).unwrap()
+        );
+
+        // the observer moves out of range, but the ticket keeps the chunk loaded
+        manager
+            .with_global_lock(None, false, |mut access| {
+                assert!(!access.unload_chunk(pos, LoadReasons::RENDER).unwrap());
+            })
+            .unwrap();
+
+        assert!(manager.release_expired_tickets().is_empty());
+        assert!(manager.get_loaded_chunk(pos, true).is_ok());
+
+        // the chunk stays loaded until every clone of the ticket is dropped
+        drop(ticket);
+        assert!(manager.release_expired_tickets().is_empty());
+        assert!(manager.get_loaded_chunk(pos, true).is_ok());
+
+        drop(clone);
+        assert_eq!(vec![pos], manager.release_expired_tickets());
+        assert!(manager
+            .get_loaded_chunk(pos, true)
+            .is_err_and(|err| err.is_doesnt_exists()));
+
+        // chunks that are force loaded from scratch are new
+        let ticket = LoadTicket::new();
+        assert_eq!(
+            ChunkLoadResult::New,
+            manager.force_load(pos, &ticket).unwrap()
+        );
+    }
+
+    #[test]
+    fn chunks_changed_since() {
+        let manager = ChunkManager::new_test();
+
+        let a = ChunkPos::new(0, 0, 0);
+        let b = ChunkPos::new(1, 0, 0);
+
+        manager
+            .with_global_lock(None, false, |mut access| {
+                access.load_chunk(a, LoadReasons::RENDER).unwrap();
+                access.load_chunk(b, LoadReasons::RENDER).unwrap();
+            })
+            .unwrap();
+
+        let start = manager.change_tick();
+        assert_eq!(0, manager.chunks_changed_since(start).count());
+
+        let edit = |pos: ChunkPos| {
+            let cref = manager.get_loaded_chunk(pos, true).unwrap();
+            let before = cref.changed_tick();
+            cref.with_access(true, |_| ()).unwrap();
+            assert!(cref.changed_tick() > before);
+        };
+
+        edit(a);
+        edit(a);
+
+        let changed = manager.chunks_changed_since(start).collect::<Vec<_>>();
+        assert_eq!(vec![a], changed);
+
+        let next = manager.change_tick();
+        assert_eq!(0, manager.chunks_changed_since(next).count());
+
+        edit(b);
+
+        let changed = manager.chunks_changed_since(next).collect::<Vec<_>>();
+        assert_eq!(vec![b], changed);
+        assert_eq!(2, manager.chunks_changed_since(start).count());
+    }
+
+    #[test]
+    fn set_voxel_flags_touched_neighbors() {
+        let manager = ChunkManager::new_test();
+        for x in -1..=1 {
+            for y in -1..=1 {
+                manager.insert_test_chunk(ChunkPos::new(x, y, 0), |_| ());
+            }
+        }
+
+        for (pos, _) in manager.loaded_chunks() {
+            assert!(!manager
+                .chunk_flags(pos)
+                .unwrap()
+                .contains(ChunkFlags::REMESH));
+        }
+
+        let stone = ChunkAccessInput::new(BlockVoxel::new_full(BlockVariantId::new(1)));
+        let remeshing = || {
+            let mut positions = manager
+                .loaded_chunks()
+                .filter(|(_, cref)| cref.flags().contains(ChunkFlags::REMESH))
+                .map(|(pos, _)| pos)
+                .collect::<Vec<_>>();
+            positions.sort_by_key(|pos| pos.as_ivec3().to_array());
+            positions
+        };
+
+        // a voxel in the interior of the chunk only affects its own chunk
+        manager.set_voxel(ivec3(5, 5, 5), stone.clone()).unwrap();
+        assert_eq!(vec![ChunkPos::ZERO], remeshing());
+
+        // a voxel on the face of the chunk also affects the neighbor across that face
+        manager.set_voxel(ivec3(15, 5, 5), stone.clone()).unwrap();
+        assert_eq!(vec![ChunkPos::ZERO, ChunkPos::new(1, 0, 0)], remeshing());
+
+        // the chunk is written to
+        let cref = manager.get_loaded_chunk(ChunkPos::ZERO, false).unwrap();
+        cref.with_read_access(|access| {
+            assert_eq!(
+                CaoBlock::Full(FullBlock::new(BlockVariantId::new(1))),
+                access.get(ivec3(15, 5, 5)).unwrap().block
+            );
+        })
+        .unwrap();
+        drop(cref);
+
+        for (_, cref) in manager.loaded_chunks() {
+            cref.update_flags(|flags| flags.remove(ChunkFlags::REMESH));
+        }
+
+        // a voxel on an edge affects the 3 chunks sharing that edge, neighbors that aren't loaded are ignored
+        manager.set_voxel(ivec3(-1, -1, 0), stone.clone()).unwrap();
+        assert_eq!(
+            vec![
+                ChunkPos::new(-1, -1, 0),
+                ChunkPos::new(-1, 0, 0),
+                ChunkPos::new(0, -1, 0),
+                ChunkPos::new(0, 0, 0),
+            ],
+            remeshing()
+        );
+
+        assert_eq!(
+            Err(ChunkManagerError::Unloaded),
+            manager.set_voxel(ivec3(0, 0, 100), stone)
+        );
+    }
+
+    #[test]
+    fn border_edits_remesh_touching_neighbors() {
+        let manager = ChunkManager::new_test();
+        let pos = ChunkPos::ZERO;
+
+        manager
+            .with_global_lock(None, false, |mut access| {
+                access.load_chunk(pos, LoadReasons::RENDER).unwrap();
+            })
+            .unwrap();
+
+        let cref = manager.get_loaded_chunk(pos, true).unwrap();
+        let stone = ChunkAccessInput::new(BlockVoxel::new_full(BlockVariantId::new(1)));
+
+        // edits in the interior of the chunk don't remesh any neighbors
+        cref.with_access(false, |mut access| {
+            access.set(IVec3::splat(8), stone.clone())
+        })
+        .unwrap()
+        .unwrap();
+        assert!(!cref.flags().contains(ChunkFlags::REMESH_NEIGHBORS));
+        assert!(cref.neighbors_to_remesh().is_empty());
+
+        // the 6 faces, 12 edges, and 8 corners of the chunk
+        for direction in BoundingBox::from_min_max(IVec3::splat(-1), IVec3::splat(2))
+            .cartesian_iter()
+            .filter(|&direction| direction != IVec3::ZERO)
+        {
+            let local = direction.to_array().map(|c| match c {
+                -1 => 0,
+                0 => 8,
+                _ => Chunk::SIZE - 1,
+            });
+
+            cref.update_flags(|flags| flags.remove(ChunkFlags::REMESH_NEIGHBORS));
+            cref.with_access(false, |mut access| {
+                access.set(IVec3::from_array(local), stone.clone())
+            })
+            .unwrap()
+            .unwrap();
+
+            // every neighbor whose offset only points in the directions of the edited border
+            let expected = BoundingBox::from_min_max(IVec3::splat(-1), IVec3::splat(2))
+                .cartesian_iter()
+                .filter(|&offset| offset != IVec3::ZERO)
+                .filter(|&offset| {
+                    (0..3).all(|axis| offset[axis] == 0 || offset[axis] == direction[axis])
+                })
+                .collect::<NeighborSet>();
+
+            let touched = cref.neighbors_to_remesh();
+            assert!(cref.flags().contains(ChunkFlags::REMESH_NEIGHBORS));
+            assert_eq!(expected, touched, "editing border {direction}");
+            // 1 neighbor for faces, 3 for edges, and 7 for corners
+            let nonzero = direction.to_array().iter().filter(|&&c| c != 0).count() as u32;
+            assert_eq!(2usize.pow(nonzero) - 1, touched.len());
+        }
+
+        // removing the flag clears the neighbors
+        cref.update_flags(|flags| flags.remove(ChunkFlags::REMESH_NEIGHBORS));
+        assert!(cref.neighbors_to_remesh().is_empty());
+    }
+
+    #[test]
+    fn uniform_chunks() {
+        let void = FullBlock::new(BlockVariantRegistry::VOID);
+        let manager = ChunkManager::new_test();
+        let pos = ChunkPos::new(0, 0, 0);
+
+        manager
+            .with_global_lock(None, false, |mut access| {
+                access.load_chunk(pos, LoadReasons::RENDER).unwrap();
+            })
+            .unwrap();
+
+        let cref = manager.get_loaded_chunk(pos, true).unwrap();
+        assert_eq!(Some(void), cref.is_uniform());
+
+        let stone = BlockVoxel::Full(FullBlock::new(BlockVariantId::new(1)));
+
+        // fill the bottom half of the chunk
+        cref.with_access(true, |mut access| {
+            for x in 0..Chunk::SIZE {
+                for y in 0..(Chunk::SIZE / 2) {
+                    for z in 0..Chunk::SIZE {
+                        access
+                            .set(ivec3(x, y, z), ChunkAccessInput::new(stone.clone()))
+                            .unwrap();
+                    }
+                }
+            }
+        })
+        .unwrap();
+
+        assert_eq!(None, cref.is_uniform());
+
+        // fill the top half too, the chunk should be uniform once it's optimized
+        cref.with_access(true, |mut access| {
+            for x in 0..Chunk::SIZE {
+                for y in (Chunk::SIZE / 2)..Chunk::SIZE {
+                    for z in 0..Chunk::SIZE {
+                        access
+                            .set(ivec3(x, y, z), ChunkAccessInput::new(stone.clone()))
+                            .unwrap();
+                    }
+                }
+            }
+
+            access.optimize_internal_storage();
+        })
+        .unwrap();
+
+        assert_eq!(
+            Some(FullBlock::new(BlockVariantId::new(1))),
+            cref.is_uniform()
+        );
+
+        // a single differing block breaks uniformity
+        cref.with_access(true, |mut access| {
+            access
+                .set(
+                    ivec3(3, 3, 3),
+                    ChunkAccessInput::new(BlockVoxel::Full(void)),
+                )
+                .unwrap();
+        })
+        .unwrap();
+
+        assert_eq!(None, cref.is_uniform());
+    }
+
+    #[test]
+    fn edit_transaction_marks_chunks_changed_once() {
+        let manager = ChunkManager::new_test();
+        let chunks = BoundingBox::from_min_max(IVec3::splat(-2), IVec3::splat(2));
+        for pos in chunks.cartesian_iter() {
+            manager.insert_test_chunk(ChunkPos::from(pos), |_| ());
+        }
+
+        // a 10x10x10 region around the origin, which is in the 8 chunks around the origin
+        let region = BoundingBox::from_min_max(IVec3::splat(-5), IVec3::splat(5));
+        let stone = BlockVoxel::new_full(BlockVariantId::new(1));
+
+        let tick = manager.change_tick();
+        let written = manager
+            .edit_transaction(|tx| {
+                tx.fill(region, stone.clone());
+                tx.len()
+            })
+            .unwrap();
+
+        assert_eq!(1000, written);
+        assert_eq!(tick + 8, manager.change_tick());
+
+        let mut changed = manager.chunks_changed_since(tick).collect::<Vec<_>>();
+        changed.sort_by_key(|pos| pos.as_ivec3().to_array());
+        let mut expected = BoundingBox::from_min_max(IVec3::splat(-1), IVec3::splat(1))
+            .cartesian_iter()
+            .map(ChunkPos::from)
+            .collect::<Vec<_>>();
+        expected.sort_by_key(|pos| pos.as_ivec3().to_array());
+        assert_eq!(expected, changed);
+
+        for &pos in &expected {
+            assert!(manager
+                .chunk_flags(pos)
+                .unwrap()
+                .contains(ChunkFlags::REMESH));
+        }
+
+        assert_eq!(stone, manager.get_voxel(IVec3::splat(-5)).unwrap());
+        assert_eq!(stone, manager.get_voxel(IVec3::splat(4)).unwrap());
+        assert_ne!(stone, manager.get_voxel(IVec3::splat(5)).unwrap());
+        assert_eq!(1000, manager.edit_log().drain().len());
+
+        // nothing is written if a chunk isn't loaded
+        let dirt = BlockVoxel::new_full(BlockVariantId::new(2));
+        let result = manager.edit_transaction(|tx| {
+            tx.set(ivec3(0, 0, 0), ChunkAccessInput::new(dirt.clone()));
+            tx.set(ivec3(100, 0, 0), ChunkAccessInput::new(dirt.clone()));
+        });
+        assert_eq!(Err(ChunkManagerError::Unloaded), result);
+        assert_eq!(stone, manager.get_voxel(ivec3(0, 0, 0)).unwrap());
+        assert!(manager.edit_log().is_empty());
+    }
+
+    #[test]
+    fn snapshot_doesnt_see_later_writes() {
+        let manager = ChunkManager::new_test();
+        manager.insert_test_chunk(ChunkPos::ZERO, |_| ());
+
+        let cref = manager.get_loaded_chunk(ChunkPos::ZERO, false).unwrap();
+
+        let stone = BlockVoxel::new_full(BlockVariantId::new(1));
+        manager
+            .set_voxel(ivec3(1, 2, 3), ChunkAccessInput::new(stone.clone()))
+            .unwrap();
+
+        let snapshot = cref.read_snapshot();
+
+        // the chunk can be written to while the snapshot is alive
+        let dirt = BlockVoxel::new_full(BlockVariantId::new(2));
+        manager
+            .set_voxel(ivec3(1, 2, 3), ChunkAccessInput::new(dirt.clone()))
+            .unwrap();
+        manager
+            .set_voxel(ivec3(4, 5, 6), ChunkAccessInput::new(dirt.clone()))
+            .unwrap();
+
+        let read = |pos| match snapshot.read_access().get(pos).unwrap().block {
+            CaoBlock::Full(full) => BlockVoxel::Full(full),
+            CaoBlock::Subdivided(subdiv) => BlockVoxel::Subdivided(subdiv.clone()),
+        };
+
+        assert_eq!(stone, read(ivec3(1, 2, 3)));
+        assert_eq!(
+            BlockVoxel::new_full(BlockVariantId::new(0)),
+            read(ivec3(4, 5, 6))
+        );
+        assert_eq!(
+            snapshot.get(ivec3(1, 2, 3)).unwrap(),
+            snapshot.read_access().get(ivec3(1, 2, 3)).unwrap()
+        );
+
+        assert_eq!(dirt, manager.get_voxel(ivec3(1, 2, 3)).unwrap());
+        assert_eq!(dirt, manager.get_voxel(ivec3(4, 5, 6)).unwrap());
+    }
+
+    #[test]
+    fn snapshot_is_copy_on_write() {
+        let manager = ChunkManager::new_test();
+        manager.insert_test_chunk(ChunkPos::ZERO, |_| ());
+
+        let cref = manager.get_loaded_chunk(ChunkPos::ZERO, false).unwrap();
+        assert!(!cref.chunk.variants.is_shared());
+
+        // taking a snapshot shares the storage instead of copying it
+        let snapshot = cref.read_snapshot();
+        assert!(cref.chunk.variants.is_shared());
+
+        // reading doesn't copy either
+        cref.with_read_access(|access| access.get(IVec3::ZERO).is_ok())
+            .unwrap();
+        assert!(cref.chunk.variants.is_shared());
+
+        let stone = BlockVoxel::new_full(BlockVariantId::new(1));
+        manager
+            .set_voxel(IVec3::ZERO, ChunkAccessInput::new(stone.clone()))
+            .unwrap();
+        assert!(!cref.chunk.variants.is_shared());
+
+        assert_eq!(
+            Some(FullBlock::new(BlockVariantId::new(0))),
+            snapshot.is_uniform()
+        );
+        assert_eq!(stone, manager.get_voxel(IVec3::ZERO).unwrap());
+
+        // the storage isn't copied again once it's unshared
+        drop(snapshot);
+        manager
+            .set_voxel(IVec3::ONE, ChunkAccessInput::new(stone))
+            .unwrap();
+        assert!(!cref.chunk.variants.is_shared());
+    }
+
+    #[test]
+    fn heightmap_surface() {
+        let manager = ChunkManager::new_test();
+        let chunks = BoundingBox::from_min_max(ivec3(0, -1, 0), ivec3(2, 2, 1));
+        for pos in chunks.cartesian_iter() {
+            manager.insert_test_chunk(ChunkPos::from(pos), |_| ());
+        }
+
+        let registry = BlockVariantRegistry::new_mock(&TextureRegistry::new_mock());
+        let stone = BlockVariantRegistry::FULL;
+        let lamp = BlockVariantRegistry::LAMP;
+
+        // flat terrain with its surface at y = 4
+        let terrain = BoundingBox::from_min_max(ivec3(0, -16, 0), ivec3(32, 5, 16));
+        manager
+            .fill_selection(&Selection::from(terrain), BlockVoxel::new_full(stone))
+            .unwrap();
+
+        let heightmap = manager.heightmap(chunks, &registry).unwrap();
+        assert_eq!(ivec2(32, 16), heightmap.size());
+        assert_eq!(32 * 16, heightmap.iter().count());
+        assert!(heightmap
+            .iter()
+            .all(|(_, column)| column == HeightMapColumn { y: 4, block: stone }));
+
+        // a pillar reaching into the top layer of chunks
+        let pillar = BoundingBox::from_min_max(ivec3(20, 5, 3), ivec3(21, 21, 4));
+        manager
+            .fill_selection(&Selection::from(pillar), BlockVoxel::new_full(lamp))
+            .unwrap();
+
+        // the column of chunks at x = 2 isn't loaded
+        let heightmap = manager
+            .heightmap(
+                BoundingBox::from_min_max(ivec3(0, -1, 0), ivec3(3, 2, 1)),
+                &registry,
+            )
+            .unwrap();
+        assert_eq!(
+            Some(HeightMapColumn { y: 20, block: lamp }),
+            heightmap.get(20, 3)
+        );
+        assert_eq!(
+            Some(HeightMapColumn { y: 4, block: stone }),
+            heightmap.get(21, 3)
+        );
+        assert_eq!(None, heightmap.get(32, 0));
+        assert_eq!(None, heightmap.get(-1, 0));
+        assert_eq!(32 * 16, heightmap.iter().count());
+    }
+}
@@ -1,15 +1,22 @@
-use std::hash::BuildHasher;
+use std::{
+    hash::BuildHasher,
+    sync::{atomic::Ordering, Arc},
+};
 
 use bevy::{ecs::entity::Entity, math::UVec3, prelude::IVec3};
 use parking_lot::RwLockReadGuard;
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
 
 use crate::topo::{
     access::{ChunkBounds, ReadAccess, WriteAccess},
     block::{BlockVoxel, FullBlock, Microblock, SubdividedBlock},
     controller::LoadReasons,
     error::ChunkAccessError,
+    light::ChunkLight,
+    neighbors::{touched_neighbors, NeighborSet},
     storage::{
-        containers::data_storage::{SiccAccess, SiccReadAccess},
+        containers::data_storage::{SiccAccess, SiccReadAccess, SyncIndexedChunkContainer},
+        data_structures::IndexedChunkStorage,
         error::OutOfBounds,
     },
 };
@@ -17,6 +24,7 @@ use crate::topo::{
 use super::{
     chunk::{Chunk, ChunkFlags, ChunkPos},
     chunk_manager::{ChunkStatuses, LccRef},
+    edits::{EditCause, EditLog, LocalEdit},
     ChunkManagerError,
 };
 
@@ -30,6 +38,7 @@ pub struct ChunkRef<'a> {
     pub(super) stats: RwLockReadGuard<'a, ChunkStatuses>,
     pub(super) pos: ChunkPos,
     pub(super) entity: Option<Entity>,
+    pub(super) edits: &'a EditLog,
 }
 
 impl<'a> ChunkRef<'a> {
@@ -76,9 +85,60 @@ impl<'a> ChunkRef<'a> {
             self.stats.updated.remove(&self.pos);
         }
 
+        if !new_flags.contains(ChunkFlags::REMESH_NEIGHBORS) {
+            self.chunk.remesh_neighbors.store(0, Ordering::Release);
+        }
+
         self.set_flags(new_flags);
     }
 
+    /// The neighbors that need to be remeshed along with this chunk, see [`ChunkRef::remesh_neighbors`].
+    pub fn neighbors_to_remesh(&self) -> NeighborSet {
+        NeighborSet::from_raw(self.chunk.remesh_neighbors.load(Ordering::Acquire))
+    }
+
+    /// Flag the given neighbors to be remeshed when this chunk is remeshed. Neighbors that were already
+    /// flagged stay flagged until this chunk's [`ChunkFlags::REMESH_NEIGHBORS`] flag is removed.
+    pub fn remesh_neighbors(&self, neighbors: NeighborSet) {
+        if neighbors.is_empty() {
+            return;
+        }
+
+        self.update_flags(|flags| flags.insert(ChunkFlags::REMESH_NEIGHBORS));
+        self.chunk
+            .remesh_neighbors
+            .fetch_or(neighbors.raw(), Ordering::AcqRel);
+    }
+
+    /// The change tick of the chunk manager when this chunk was last written to.
+    pub fn changed_tick(&self) -> u64 {
+        self.chunk.changed_tick.load(Ordering::Acquire)
+    }
+
+    fn mark_changed(&self) {
+        let tick = self.stats.change_tick.fetch_add(1, Ordering::AcqRel) + 1;
+        self.chunk.changed_tick.fetch_max(tick, Ordering::AcqRel);
+    }
+
+    /// If every block in this chunk is the same full block, returns that block. Meshing and serialization
+    /// can use this to skip uniform chunks. May return `None` for some uniform chunks if they were made
+    /// uniform by writes, until the chunk's storage is optimized.
+    pub fn is_uniform(&self) -> Option<FullBlock> {
+        match self.chunk.variants.uniform()? {
+            BlockVoxel::Full(block) => Some(block),
+            BlockVoxel::Subdivided(_) => None,
+        }
+    }
+
+    /// The light of this chunk as of the last time it was calculated.
+    pub fn light(&self) -> RwLockReadGuard<'_, ChunkLight> {
+        self.chunk.light.read()
+    }
+
+    pub fn set_light(&self, light: ChunkLight) {
+        *self.chunk.light.write() = light;
+    }
+
     pub fn load_reasons(&self) -> LoadReasons {
         *self.chunk.load_reasons.read()
     }
@@ -105,27 +165,56 @@ impl<'a> ChunkRef<'a> {
     where
         F: for<'access> FnOnce(ChunkRefAccess<'access, ahash::RandomState>) -> U,
     {
+        self.with_access_caused(manual_update_ctrl, EditCause::Direct, f)
+    }
+
+    /// Like [`ChunkRef::with_access`], but the edits made through the access are reported with the given cause.
+    /// Edits aren't reported if the chunk is still being generated.
+    pub fn with_access_caused<F, U>(
+        &self,
+        manual_update_ctrl: bool,
+        cause: EditCause,
+        f: F,
+    ) -> Result<U, ChunkManagerError>
+    where
+        F: for<'access> FnOnce(ChunkRefAccess<'access, ahash::RandomState>) -> U,
+    {
+        let record_edits = self.edits.is_enabled()
+            && !self
+                .flags()
+                .intersects(ChunkFlags::PRIMORDIAL | ChunkFlags::GENERATING);
+
         let variant_access = self.chunk.variants.access();
 
-        let mut wrote_to_edge = false;
+        let mut touched = NeighborSet::EMPTY;
+        let mut edits = Vec::new();
         let result = Ok(f(ChunkRefAccess {
-            wrote_to_edge: Some(&mut wrote_to_edge),
+            touched_neighbors: Some(&mut touched),
+            edits: record_edits.then_some(&mut edits),
             block_variants: variant_access,
         }));
 
-        if !manual_update_ctrl {
-            self.update_flags(|flags| {
-                flags.insert(ChunkFlags::REMESH);
+        self.mark_changed();
+        self.edits.record(self.pos, cause, edits);
 
-                if wrote_to_edge {
-                    flags.insert(ChunkFlags::REMESH_NEIGHBORS);
-                }
-            });
+        if !manual_update_ctrl {
+            self.update_flags(|flags| flags.insert(ChunkFlags::REMESH));
+            self.remesh_neighbors(touched);
         }
 
         result
     }
 
+    /// Take an owned snapshot of the voxels of this chunk, which can be read from without holding the
+    /// lock of the chunk. Use this for long reads (like building a mesh) so writers aren't blocked
+    /// while reading. Writes to the chunk after the snapshot was taken aren't visible in the snapshot.
+    /// Taking a snapshot is cheap, the voxels are only copied if the chunk is written to while the snapshot is alive.
+    pub fn read_snapshot(&self) -> OwnedChunkAccess {
+        OwnedChunkAccess {
+            block_variants: self.chunk.variants.snapshot(),
+        }
+    }
+
     #[allow(clippy::let_and_return)] // We need do to this little crime so the borrowchecker doesn't yell at us
     pub fn with_read_access<F, U>(&self, f: F) -> Result<U, ChunkManagerError>
     where
@@ -144,6 +233,52 @@ pub struct ChunkRefReadAccess<'a, S: BuildHasher = ahash::RandomState> {
     pub(crate) block_variants: SiccReadAccess<'a, BlockVoxel, S>,
 }
 
+impl<'a, S: BuildHasher> ChunkRefReadAccess<'a, S> {
+    /// Same as [`ChunkRef::is_uniform`], but for the chunk being read.
+    pub fn is_uniform(&self) -> Option<FullBlock> {
+        match self.block_variants.uniform()? {
+            BlockVoxel::Full(block) => Some(*block),
+            BlockVoxel::Subdivided(_) => None,
+        }
+    }
+
+    /// Every distinct voxel in the chunk being read, along with voxels that were overwritten since the
+    /// chunk's storage was last optimized. Much cheaper than reading every voxel in the chunk.
+    pub fn palette(&self) -> &[BlockVoxel] {
+        self.block_variants.values()
+    }
+}
+
+/// An owned copy of the voxels of a chunk, see [`ChunkRef::read_snapshot`].
+#[derive(Clone)]
+pub struct OwnedChunkAccess {
+    block_variants: Arc<IndexedChunkStorage<BlockVoxel, ahash::RandomState>>,
+}
+
+impl OwnedChunkAccess {
+    /// Take ownership of the voxels in the container.
+    pub(crate) fn from_container(container: SyncIndexedChunkContainer<BlockVoxel>) -> Self {
+        Self {
+            block_variants: container.snapshot(),
+        }
+    }
+
+    /// Read access to the snapshot, for code that works with [`ChunkRefReadAccess`]es (like meshers).
+    pub fn read_access(&self) -> ChunkRefReadAccess<'_> {
+        ChunkRefReadAccess {
+            block_variants: SiccReadAccess::from_storage(&self.block_variants),
+        }
+    }
+
+    /// Same as [`ChunkRef::is_uniform`], but for the snapshot.
+    pub fn is_uniform(&self) -> Option<FullBlock> {
+        match self.block_variants.uniform()? {
+            BlockVoxel::Full(block) => Some(*block),
+            BlockVoxel::Subdivided(_) => None,
+        }
+    }
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum CaoBlock<'a> {
     Full(FullBlock),
@@ -199,11 +334,15 @@ pub struct MutChunkAccOutput<'a> {
 }
 
 pub struct ChunkRefAccess<'a, S: BuildHasher = ahash::RandomState> {
-    pub(crate) wrote_to_edge: Option<&'a mut bool>,
+    /// The neighbors sharing a face, edge, or corner with the voxels written to through this access.
+    pub(crate) touched_neighbors: Option<&'a mut NeighborSet>,
+    /// The voxels changed through this access, recorded if present.
+    pub(crate) edits: Option<&'a mut Vec<LocalEdit>>,
     pub(crate) block_variants: SiccAccess<'a, BlockVoxel, S>,
 }
 
 impl<'a, S: BuildHasher + Clone> ChunkRefAccess<'a, S> {
+    #[allow(dead_code)]
     pub(crate) fn get_mutable_output(
         &mut self,
         pos: IVec3,
@@ -239,17 +378,75 @@ impl<'a, S: BuildHasher + Clone> ChunkRefAccess<'a, S> {
     pub fn optimize_internal_storage(&mut self) -> usize {
         self.block_variants.optimize_storage()
     }
+
+    /// Call `f` with every voxel in the chunk in parallel, replacing the voxel with the block `f` returns
+    /// (if any). Meant for expensive per-voxel work like sampling noise during generation.
+    ///
+    /// The chunk is split into slabs along the X axis which are processed on the rayon thread pool. The
+    /// storage can't be written to from several threads, so the replacements are collected first and then
+    /// written sequentially through [`WriteAccess::set`], which keeps edit and neighbor tracking intact.
+    pub fn par_for_each<F>(&mut self, f: F) -> Result<(), ChunkAccessError>
+    where
+        F: Fn(IVec3, &BlockVoxel) -> Option<BlockVoxel> + Sync,
+        S: Send + Sync,
+    {
+        let block_variants = &self.block_variants;
+
+        let replacements = (0..Chunk::SIZE)
+            .into_par_iter()
+            .map(|x| {
+                let mut slab = Vec::new();
+
+                for y in 0..Chunk::SIZE {
+                    for z in 0..Chunk::SIZE {
+                        let pos = IVec3::new(x, y, z);
+                        let block = block_variants
+                            .get(pos)?
+                            .ok_or(ChunkAccessError::NotInitialized)?;
+
+                        if let Some(new) = f(pos, block) {
+                            slab.push((pos, new));
+                        }
+                    }
+                }
+
+                Ok(slab)
+            })
+            .collect::<Result<Vec<_>, ChunkAccessError>>()?;
+
+        for (pos, block) in replacements.into_iter().flatten() {
+            self.set(pos, ChunkAccessInput::new(block))?;
+        }
+
+        Ok(())
+    }
 }
 
-impl<'a, S: BuildHasher> WriteAccess for ChunkRefAccess<'a, S> {
+impl<'a, S: BuildHasher + Clone> WriteAccess for ChunkRefAccess<'a, S> {
     type WriteErr = ChunkAccessError;
     type WriteType = ChunkAccessInput;
 
     fn set(&mut self, pos: IVec3, data: Self::WriteType) -> Result<(), Self::WriteErr> {
+        if let Some(edits) = self.edits.as_deref_mut() {
+            if let Some(old) = self.block_variants.get(pos)? {
+                if *old != data.block {
+                    edits.push(LocalEdit {
+                        pos,
+                        old: old.clone(),
+                        new: data.block.clone(),
+                    });
+                }
+            }
+        }
+
         self.block_variants.set(pos, Some(data.block))?;
 
         if pos.cmple(IVec3::ZERO).any() || pos.cmpge(Chunk::VEC - IVec3::ONE).any() {
-            self.wrote_to_edge.as_deref_mut().map(|v| *v = true);
+            if let Some(touched) = self.touched_neighbors.as_deref_mut() {
+                for offset in touched_neighbors(pos) {
+                    touched.insert(offset);
+                }
+            }
         }
 
         Ok(())
@@ -287,3 +484,65 @@ impl<'a, S: BuildHasher> ReadAccess for ChunkRefReadAccess<'a, S> {
 }
 
 impl<'a, S: BuildHasher> ChunkBounds for ChunkRefReadAccess<'a, S> {}
+
+impl ReadAccess for OwnedChunkAccess {
+    type ReadErr = ChunkAccessError;
+    type ReadType<'b> = ChunkAccessOutput<'b>;
+
+    fn get(&self, pos: IVec3) -> Result<Self::ReadType<'_>, Self::ReadErr> {
+        let block = self
+            .block_variants
+            .get(pos)?
+            .ok_or(ChunkAccessError::NotInitialized)?;
+
+        Ok(ChunkAccessOutput::new(block))
+    }
+}
+
+impl ChunkBounds for OwnedChunkAccess {}
+
+#[cfg(test)]
+mod tests {
+    use noise::{NoiseFn, Perlin};
+
+    use crate::{data::registries::block::BlockVariantRegistry, testing_utils::MockChunk};
+
+    use super::*;
+
+    #[test]
+    fn parallel_noise_fill() {
+        let noise = Perlin::new(5);
+        let stone = |pos: IVec3| {
+            (noise.get((pos.as_dvec3() * 0.2).to_array()) > 0.0)
+                .then(|| BlockVoxel::new_full(BlockVariantRegistry::FULL))
+        };
+
+        let sequential = MockChunk::new(BlockVoxel::new_full(BlockVariantRegistry::VOID));
+        let mut access = sequential.access();
+        for pos in Chunk::BOUNDING_BOX.cartesian_iter() {
+            if let Some(block) = stone(pos) {
+                access.set(pos, ChunkAccessInput::new(block)).unwrap();
+            }
+        }
+        drop(access);
+
+        let parallel = MockChunk::new(BlockVoxel::new_full(BlockVariantRegistry::VOID));
+        parallel
+            .access()
+            .par_for_each(|pos, _| stone(pos))
+            .unwrap();
+
+        let (sequential, parallel) = (sequential.read_access(), parallel.read_access());
+        let mut filled = 0;
+        for pos in Chunk::BOUNDING_BOX.cartesian_iter() {
+            let block = parallel.get(pos).unwrap().block;
+            assert!(sequential.get(pos).unwrap().block == block, "{pos}");
+
+            if matches!(block, CaoBlock::Full(full) if full.id == BlockVariantRegistry::FULL) {
+                filled += 1;
+            }
+        }
+
+        assert!(filled > 0 && filled < Chunk::USIZE.pow(3));
+    }
+}
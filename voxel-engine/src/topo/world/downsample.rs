@@ -0,0 +1,375 @@
+//! Downsampling of chunk voxels for distant, low detail meshes. Every cell of `factor`³ voxels is replaced
+//! by the one voxel that best represents it, so the greedy mesher can merge much larger quads.
+
+use bevy::math::IVec3;
+
+use crate::{
+    data::registries::block::BlockVariantRegistry,
+    topo::{
+        access::{ReadAccess, WriteAccess},
+        block::{BlockVoxel, FullBlock, SubdividedBlock},
+        bounding_box::BoundingBox,
+        error::ChunkAccessError,
+        light::{ChunkLight, LightLevel},
+        neighbors::NeighborSet,
+        storage::containers::data_storage::SyncIndexedChunkContainer,
+    },
+};
+
+use super::{CaoBlock, Chunk, Crra, OwnedChunkAccess};
+
+#[derive(te::Error, Debug, PartialEq, Eq)]
+pub enum DownsampleError {
+    #[error("Downsampling factor {0} isn't a power of two between 1 and the chunk size")]
+    InvalidFactor(i32),
+    #[error(transparent)]
+    Access(#[from] ChunkAccessError),
+}
+
+/// The voxels of a chunk at a lower resolution, see [`downsample`].
+#[derive(Clone, Debug)]
+pub struct DownsampledChunk {
+    factor: i32,
+    voxels: Vec<FullBlock>,
+}
+
+impl DownsampledChunk {
+    /// How many voxels along each axis were merged into one.
+    pub fn factor(&self) -> i32 {
+        self.factor
+    }
+
+    /// The number of voxels along each axis.
+    pub fn size(&self) -> i32 {
+        Chunk::SIZE / self.factor
+    }
+
+    fn index(&self, pos: IVec3) -> Option<usize> {
+        let size = self.size();
+        if pos.cmplt(IVec3::ZERO).any() || pos.cmpge(IVec3::splat(size)).any() {
+            return None;
+        }
+
+        Some(((pos.x * size + pos.y) * size + pos.z) as usize)
+    }
+
+    /// The voxel at `pos`, in downsampled coordinates.
+    pub fn get(&self, pos: IVec3) -> Option<FullBlock> {
+        self.index(pos).map(|i| self.voxels[i])
+    }
+
+    /// Expand back to the full resolution of a chunk, where every cell is filled with its downsampled voxel.
+    /// The result can be meshed like any other chunk.
+    pub fn expand(&self) -> OwnedChunkAccess {
+        let container = SyncIndexedChunkContainer::filled(BlockVoxel::Full(self.voxels[0]));
+
+        {
+            let mut access = container.access();
+            for pos in Chunk::BOUNDING_BOX.cartesian_iter() {
+                let block = self.get(pos / self.factor).unwrap();
+                access.set(pos, Some(BlockVoxel::Full(block))).unwrap();
+            }
+        }
+
+        OwnedChunkAccess::from_container(container)
+    }
+
+    /// Like [`expand`](Self::expand), but the outermost layer of voxels on the sides of the chunk facing the
+    /// `stitched` neighbors (offsets of face neighbors) is copied from the `original` voxels that were downsampled.
+    ///
+    /// Neighbors meshed at a higher quality cull their border faces against the original voxels of this chunk.
+    /// Where a downsampled cell is air but the original voxel on the border was solid, the neighbor culls its
+    /// face and this chunk doesn't build one either, leaving a hole between the chunks. The original layer
+    /// stitches the levels of detail together, since both sides of the border agree on its voxels. Neighbors
+    /// meshed at the same quality cull against the downsampled voxels instead, so their sides aren't stitched.
+    pub fn expand_stitched(
+        &self,
+        original: &Crra,
+        stitched: NeighborSet,
+    ) -> Result<OwnedChunkAccess, DownsampleError> {
+        let container = SyncIndexedChunkContainer::filled(BlockVoxel::Full(self.voxels[0]));
+
+        {
+            let mut access = container.access();
+            for pos in Chunk::BOUNDING_BOX.cartesian_iter() {
+                let on_stitched_border = stitched.iter().any(|offset| {
+                    let border = pos.cmpeq(IVec3::ZERO) & offset.cmplt(IVec3::ZERO)
+                        | pos.cmpeq(IVec3::splat(Chunk::SIZE - 1)) & offset.cmpgt(IVec3::ZERO);
+                    border.any()
+                });
+
+                let voxel = match on_stitched_border {
+                    true => match original.get(pos)?.block {
+                        CaoBlock::Full(block) => BlockVoxel::Full(block),
+                        CaoBlock::Subdivided(subdiv) => BlockVoxel::Subdivided(subdiv.clone()),
+                    },
+                    false => BlockVoxel::Full(self.get(pos / self.factor).unwrap()),
+                };
+
+                access.set(pos, Some(voxel)).unwrap();
+            }
+        }
+
+        Ok(OwnedChunkAccess::from_container(container))
+    }
+
+    /// The light to mesh the downsampled voxels with. Every voxel of a cell gets the brightest light in the
+    /// cell, which is the light on the air side of the original surface in it. Cells that became solid have
+    /// faces where the original voxels in front of them were solid too, and those voxels are dark. The border
+    /// of the light (from the neighboring chunks) is kept as it is.
+    pub fn light(&self, original: &ChunkLight) -> ChunkLight {
+        let mut light = original.clone();
+
+        for cell in
+            BoundingBox::from_min_max(IVec3::ZERO, IVec3::splat(self.size())).cartesian_iter()
+        {
+            let voxels = BoundingBox::from_min_max(cell * self.factor, (cell + 1) * self.factor);
+
+            let mut level = LightLevel::DARK;
+            let mut sky_access = 0;
+            for pos in voxels.cartesian_iter() {
+                level = level.max(original.get(pos).unwrap());
+                sky_access = sky_access.max(original.sky_access(pos).unwrap());
+            }
+
+            for pos in voxels.cartesian_iter() {
+                light.set(pos, level).unwrap();
+                light.set_sky_access(pos, sky_access).unwrap();
+            }
+        }
+
+        light
+    }
+}
+
+/// Tally of the voxels of a cell, weighted by how much of the cell they cover.
+#[derive(Default)]
+struct Votes {
+    /// The candidates in the order they were first seen, so ties go to the first one.
+    candidates: Vec<(FullBlock, u32)>,
+    solid: u32,
+    total: u32,
+}
+
+impl Votes {
+    fn add(&mut self, block: FullBlock, weight: u32, registry: &BlockVariantRegistry) {
+        if !registry.is_air(block.id) {
+            self.solid += weight;
+        }
+        self.total += weight;
+
+        match self.candidates.iter_mut().find(|(c, _)| c.id == block.id) {
+            Some((_, votes)) => *votes += weight,
+            None => self.candidates.push((block, weight)),
+        }
+    }
+
+    /// The cell is solid if at least half of it is, so thin walls and floors don't disappear.
+    /// Solid cells are the most common solid variant, other cells the most common air variant.
+    fn winner(&self, registry: &BlockVariantRegistry) -> FullBlock {
+        let solid = self.solid * 2 >= self.total;
+
+        let mut winner = None::<(FullBlock, u32)>;
+        for &(block, votes) in &self.candidates {
+            if registry.is_air(block.id) == solid {
+                continue;
+            }
+
+            if winner.is_none_or(|(_, most)| votes > most) {
+                winner = Some((block, votes));
+            }
+        }
+
+        winner.map(|(block, _)| block).unwrap()
+    }
+}
+
+/// Whether `factor` can be used to [`downsample`] a chunk, it has to evenly divide the chunk size.
+pub fn valid_factor(factor: i32) -> bool {
+    factor > 0 && factor <= Chunk::SIZE && (factor as u32).is_power_of_two()
+}
+
+/// Downsample the voxels of a chunk by `factor` along every axis, by a vote in every cell of `factor`³ voxels.
+/// A cell is solid if at least half of its volume is solid, and takes the most common variant (and its
+/// rotation) of the side that won. Subdivided blocks vote with each of their microblocks.
+pub fn downsample(
+    access: &Crra,
+    factor: i32,
+    registry: &BlockVariantRegistry,
+) -> Result<DownsampledChunk, DownsampleError> {
+    if !valid_factor(factor) {
+        return Err(DownsampleError::InvalidFactor(factor));
+    }
+
+    let size = Chunk::SIZE / factor;
+    let microblocks = SubdividedBlock::SUBDIVISIONS.pow(3) as u32;
+    let mut voxels = Vec::with_capacity(size.pow(3) as usize);
+
+    for cell in BoundingBox::from_min_max(IVec3::ZERO, IVec3::splat(size)).cartesian_iter() {
+        let min = cell * factor;
+        let mut votes = Votes::default();
+
+        for pos in BoundingBox::from_min_max(min, min + factor).cartesian_iter() {
+            match access.get(pos)?.block {
+                CaoBlock::Full(block) => votes.add(block, microblocks, registry),
+                CaoBlock::Subdivided(subdiv) => {
+                    let subdivs = SubdividedBlock::SUBDIVS_VEC3;
+                    for mpos in BoundingBox::from_min_max(IVec3::ZERO, subdivs).cartesian_iter() {
+                        let microblock = subdiv.get(mpos.as_uvec3()).unwrap();
+                        votes.add(microblock.as_full_block(), 1, registry);
+                    }
+                }
+            }
+        }
+
+        voxels.push(votes.winner(registry));
+    }
+
+    Ok(DownsampledChunk { factor, voxels })
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::ivec3;
+
+    use crate::{
+        data::registries::{block::BlockVariantId, texture::TextureRegistry},
+        testing_utils::MockChunk,
+        topo::world::ChunkAccessInput,
+    };
+
+    use super::*;
+
+    /// The most common variant in the cell, the source chunk only has full blocks.
+    fn most_common_in_cell(access: &Crra, cell: IVec3, factor: i32) -> BlockVariantId {
+        let mut counts = Vec::<(BlockVariantId, u32)>::new();
+        for pos in BoundingBox::from_min_max(cell * factor, cell * factor + factor).cartesian_iter()
+        {
+            let CaoBlock::Full(block) = access.get(pos).unwrap().block else {
+                panic!("test chunks only have full blocks");
+            };
+
+            match counts.iter_mut().find(|(id, _)| *id == block.id) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((block.id, 1)),
+            }
+        }
+
+        counts.iter().max_by_key(|(_, count)| *count).unwrap().0
+    }
+
+    #[test]
+    fn downsampled_cells_match_their_dominant_voxel() {
+        let texreg = TextureRegistry::new_mock();
+        let varreg = BlockVariantRegistry::new_mock(&texreg);
+
+        let chunk = MockChunk::new(BlockVoxel::new_full(BlockVariantRegistry::VOID));
+        {
+            let mut access = chunk.access();
+            for pos in Chunk::BOUNDING_BOX.cartesian_iter() {
+                let cell = pos / 2;
+                // every cell gets a majority variant, with a different minority variant in 3 of its 8 voxels
+                let (major, minor) = match (cell.x + cell.y + cell.z) % 3 {
+                    0 => (BlockVariantRegistry::FULL, BlockVariantRegistry::VOID),
+                    1 => (BlockVariantRegistry::VOID, BlockVariantRegistry::GLASS),
+                    _ => (BlockVariantRegistry::GLASS, BlockVariantRegistry::FULL),
+                };
+
+                let local = pos % 2;
+                let id = match local.x + local.y * 2 + local.z * 4 {
+                    1 | 2 | 4 => minor,
+                    _ => major,
+                };
+
+                access
+                    .set(pos, ChunkAccessInput::new(BlockVoxel::new_full(id)))
+                    .unwrap();
+            }
+        }
+
+        let access = chunk.read_access();
+        let downsampled = downsample(&access, 2, &varreg).unwrap();
+        assert_eq!(8, downsampled.size());
+
+        for cell in BoundingBox::from_min_max(IVec3::ZERO, IVec3::splat(8)).cartesian_iter() {
+            let expected = most_common_in_cell(&access, cell, 2);
+            assert_eq!(expected, downsampled.get(cell).unwrap().id);
+        }
+        assert_eq!(None, downsampled.get(IVec3::splat(8)));
+
+        // the expanded chunk has the downsampled voxel in every voxel of the cell
+        let expanded = downsampled.expand();
+        for pos in Chunk::BOUNDING_BOX.cartesian_iter() {
+            let block = expanded.get(pos).unwrap().block;
+            assert_eq!(CaoBlock::Full(downsampled.get(pos / 2).unwrap()), block);
+        }
+    }
+
+    #[test]
+    fn invalid_factors_are_rejected() {
+        let texreg = TextureRegistry::new_mock();
+        let varreg = BlockVariantRegistry::new_mock(&texreg);
+        let chunk = MockChunk::new(BlockVoxel::new_full(BlockVariantRegistry::FULL));
+
+        for factor in [0, 3, -2, 32] {
+            assert_eq!(
+                DownsampleError::InvalidFactor(factor),
+                downsample(&chunk.read_access(), factor, &varreg).unwrap_err()
+            );
+        }
+    }
+
+    #[test]
+    fn half_solid_cells_are_solid() {
+        let texreg = TextureRegistry::new_mock();
+        let varreg = BlockVariantRegistry::new_mock(&texreg);
+        let chunk = MockChunk::new(BlockVoxel::new_full(BlockVariantRegistry::VOID));
+
+        {
+            let mut access = chunk.access();
+            for pos in Chunk::BOUNDING_BOX.cartesian_iter() {
+                if pos.y < Chunk::SIZE / 2 {
+                    let block = BlockVoxel::new_full(BlockVariantRegistry::GLASS);
+                    access.set(pos, ChunkAccessInput::new(block)).unwrap();
+                }
+            }
+        }
+
+        let downsampled = downsample(&chunk.read_access(), Chunk::SIZE, &varreg).unwrap();
+        assert_eq!(1, downsampled.size());
+        assert_eq!(
+            BlockVariantRegistry::GLASS,
+            downsampled.get(IVec3::ZERO).unwrap().id
+        );
+    }
+
+    #[test]
+    fn cells_are_lit_by_their_brightest_voxel() {
+        let texreg = TextureRegistry::new_mock();
+        let varreg = BlockVariantRegistry::new_mock(&texreg);
+        let chunk = MockChunk::new(BlockVoxel::new_full(BlockVariantRegistry::FULL));
+        let downsampled = downsample(&chunk.read_access(), 2, &varreg).unwrap();
+
+        // the surface runs through the cell at the origin, only its top voxels are lit
+        let mut light = ChunkLight::new();
+        light.set(ivec3(0, 1, 1), LightLevel::new(3, 12)).unwrap();
+        light.set(ivec3(1, 1, 0), LightLevel::new(9, 0)).unwrap();
+        light.set(ivec3(0, -1, 0), LightLevel::new(5, 5)).unwrap();
+
+        let cell_light = downsampled.light(&light);
+        for pos in BoundingBox::from_min_max(IVec3::ZERO, IVec3::splat(2)).cartesian_iter() {
+            assert_eq!(
+                LightLevel::new(9, 12),
+                cell_light.get(pos).unwrap(),
+                "at {pos}"
+            );
+        }
+        assert_eq!(LightLevel::DARK, cell_light.get(ivec3(2, 0, 0)).unwrap());
+
+        // the border from the neighboring chunks isn't part of any cell
+        assert_eq!(
+            LightLevel::new(5, 5),
+            cell_light.get(ivec3(0, -1, 0)).unwrap()
+        );
+    }
+}
@@ -0,0 +1,297 @@
+use std::{
+    collections::VecDeque,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use bevy::{
+    ecs::{event::Event, system::Res},
+    math::IVec3,
+    prelude::EventWriter,
+};
+use parking_lot::Mutex;
+
+use crate::{
+    topo::{block::BlockVoxel, bounding_box::BoundingBox},
+    util::ChunkMap,
+};
+
+use super::{realm::ChunkManagerResource, Chunk, ChunkAccessInput, ChunkPos};
+
+/// What caused a voxel edit.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Default)]
+pub enum EditCause {
+    /// The voxel was written directly, for example by a player breaking or placing a block.
+    #[default]
+    Direct,
+    /// The voxel was written by a block's tick callback, see [`TickContext`](crate::topo::controller::TickContext).
+    Tick,
+}
+
+/// A voxel was changed from `old` to `new`. Only writes that actually change a voxel are reported,
+/// and writes to chunks that are still being generated aren't reported at all.
+#[derive(Event, Clone, Debug, PartialEq)]
+pub struct VoxelEditEvent {
+    /// The worldspace position of the voxel.
+    pub pos: IVec3,
+    pub old: BlockVoxel,
+    pub new: BlockVoxel,
+    pub cause: EditCause,
+}
+
+/// A voxel edit in the localspace of the chunk it was made in, recorded by a
+/// [`ChunkRefAccess`](super::ChunkRefAccess).
+pub(crate) struct LocalEdit {
+    pub pos: IVec3,
+    pub old: BlockVoxel,
+    pub new: BlockVoxel,
+}
+
+/// The voxel edits made since the last time the log was drained. Edits are batched here and sent as
+/// [`VoxelEditEvent`]s once per tick by [`dispatch_voxel_edit_events`]. Only the most recent
+/// [`EditLog::CAPACITY`] edits are kept, so the log doesn't grow forever in apps that never drain it.
+pub struct EditLog {
+    enabled: AtomicBool,
+    edits: Mutex<VecDeque<VoxelEditEvent>>,
+}
+
+impl Default for EditLog {
+    fn default() -> Self {
+        Self {
+            enabled: AtomicBool::new(true),
+            edits: Mutex::new(VecDeque::new()),
+        }
+    }
+}
+
+impl EditLog {
+    /// The most edits the log holds, older edits are dropped to make room for new ones.
+    pub const CAPACITY: usize = 1 << 16;
+
+    /// Whether edits are being recorded.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Start or stop recording edits, for example to suppress events during large edits that
+    /// nothing should react to.
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record(&self, chunk: ChunkPos, cause: EditCause, edits: Vec<LocalEdit>) {
+        if edits.is_empty() {
+            return;
+        }
+
+        let min = chunk.worldspace_min();
+        let mut log = self.edits.lock();
+        log.extend(edits.into_iter().map(|edit| VoxelEditEvent {
+            pos: min + edit.pos,
+            old: edit.old,
+            new: edit.new,
+            cause,
+        }));
+
+        let overflow = log.len().saturating_sub(Self::CAPACITY);
+        log.drain(..overflow);
+    }
+
+    /// Remove and return the recorded edits in the order they were made.
+    pub fn drain(&self) -> Vec<VoxelEditEvent> {
+        std::mem::take(&mut *self.edits.lock()).into()
+    }
+
+    pub fn len(&self) -> usize {
+        self.edits.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.edits.lock().is_empty()
+    }
+}
+
+/// A batch of voxel writes made with [`ChunkManager::edit_transaction`](super::ChunkManager::edit_transaction).
+/// Nothing is written until the transaction is committed, and writing the same voxel multiple times only
+/// keeps the last write.
+#[derive(Default)]
+pub struct EditTransaction {
+    writes: ChunkMap<hb::HashMap<IVec3, ChunkAccessInput, fxhash::FxBuildHasher>>,
+}
+
+impl EditTransaction {
+    /// Write a voxel at the given worldspace position.
+    pub fn set(&mut self, ws: IVec3, input: ChunkAccessInput) {
+        self.writes
+            .entry(ChunkPos::from_worldspace(ws))
+            .or_default()
+            .insert(ws.rem_euclid(Chunk::VEC), input);
+    }
+
+    /// Write the block to every voxel in the worldspace region.
+    pub fn fill(&mut self, region: BoundingBox, block: BlockVoxel) {
+        for ws in region.cartesian_iter() {
+            self.set(ws, ChunkAccessInput::new(block.clone()));
+        }
+    }
+
+    /// The chunks written to by this transaction.
+    pub fn chunks(&self) -> impl Iterator<Item = ChunkPos> + '_ {
+        self.writes.iter().map(|(pos, _)| pos)
+    }
+
+    /// The number of voxels written to by this transaction.
+    pub fn len(&self) -> usize {
+        self.writes.iter().map(|(_, writes)| writes.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.writes.len() == 0
+    }
+
+    /// The writes of this transaction grouped by chunk, with positions in the localspace of the chunk.
+    pub(crate) fn into_writes(
+        self,
+    ) -> impl Iterator<Item = (ChunkPos, impl Iterator<Item = (IVec3, ChunkAccessInput)>)> {
+        self.writes
+            .into_iter()
+            .map(|(pos, writes)| (pos, writes.into_iter()))
+    }
+}
+
+/// Send the voxel edits made since this system last ran as [`VoxelEditEvent`]s.
+pub fn dispatch_voxel_edit_events(
+    cm: Res<ChunkManagerResource>,
+    mut events: EventWriter<VoxelEditEvent>,
+) {
+    let edits = cm.0.edit_log().drain();
+
+    if !edits.is_empty() {
+        events.send_batch(edits);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use bevy::{
+        app::{App, Update},
+        ecs::event::Events,
+        math::ivec3,
+    };
+
+    use crate::{
+        data::registries::block::BlockVariantRegistry,
+        topo::{
+            access::WriteAccess,
+            world::{ChunkAccessInput, ChunkManager},
+        },
+    };
+
+    use super::*;
+
+    fn stone() -> BlockVoxel {
+        BlockVoxel::new_full(BlockVariantRegistry::FULL)
+    }
+
+    /// An app with a loaded chunk, which was generated with a stone at `(-1, 2, 3)`.
+    fn app_with_loaded_chunk() -> App {
+        let cm = ChunkManager::new_test();
+        cm.insert_test_chunk(ChunkPos::new(-1, 0, 0), |access| {
+            access
+                .set(ivec3(15, 2, 3), ChunkAccessInput::new(stone()))
+                .unwrap();
+        });
+
+        let mut app = App::new();
+        app.insert_resource(ChunkManagerResource(Arc::new(cm)))
+            .add_event::<VoxelEditEvent>()
+            .add_systems(Update, dispatch_voxel_edit_events);
+
+        app
+    }
+
+    fn cm(app: &App) -> Arc<ChunkManager> {
+        app.world.resource::<ChunkManagerResource>().0.clone()
+    }
+
+    fn sent_events(app: &mut App) -> Vec<VoxelEditEvent> {
+        app.world
+            .resource_mut::<Events<VoxelEditEvent>>()
+            .drain()
+            .collect()
+    }
+
+    #[test]
+    fn edit_emits_event() {
+        let mut app = app_with_loaded_chunk();
+        let cm = cm(&app);
+
+        // writes made while the chunk was being generated aren't reported
+        assert!(cm.edit_log().is_empty());
+
+        let dirt = BlockVoxel::new_full(BlockVariantRegistry::GLASS);
+        cm.set_voxel(ivec3(-1, 2, 3), ChunkAccessInput::new(dirt.clone()))
+            .unwrap();
+        app.update();
+
+        assert_eq!(
+            vec![VoxelEditEvent {
+                pos: ivec3(-1, 2, 3),
+                old: stone(),
+                new: dirt,
+                cause: EditCause::Direct,
+            }],
+            sent_events(&mut app)
+        );
+        assert!(cm.edit_log().is_empty());
+    }
+
+    #[test]
+    fn no_op_edit_emits_nothing() {
+        let mut app = app_with_loaded_chunk();
+        let cm = cm(&app);
+
+        let void = BlockVoxel::new_full(BlockVariantRegistry::VOID);
+        cm.set_voxel(ivec3(-5, 0, 0), ChunkAccessInput::new(void))
+            .unwrap();
+
+        cm.edit_log().set_enabled(false);
+        cm.set_voxel(ivec3(-5, 0, 0), ChunkAccessInput::new(stone()))
+            .unwrap();
+        app.update();
+
+        assert!(sent_events(&mut app).is_empty());
+    }
+
+    #[test]
+    fn log_is_capped() {
+        let log = EditLog::default();
+        let edit = |x: i32| LocalEdit {
+            pos: ivec3(x, 0, 0),
+            old: stone(),
+            new: stone(),
+        };
+
+        log.record(
+            ChunkPos::ZERO,
+            EditCause::Direct,
+            (0..10).map(edit).collect(),
+        );
+        log.record(
+            ChunkPos::ZERO,
+            EditCause::Direct,
+            (10..EditLog::CAPACITY as i32 + 10).map(edit).collect(),
+        );
+        assert_eq!(EditLog::CAPACITY, log.len());
+
+        // the oldest edits were dropped
+        let edits = log.drain();
+        assert_eq!(ivec3(10, 0, 0), edits[0].pos);
+        assert_eq!(
+            ivec3(EditLog::CAPACITY as i32 + 9, 0, 0),
+            edits.last().unwrap().pos
+        );
+        assert!(log.is_empty());
+    }
+}
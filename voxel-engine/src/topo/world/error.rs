@@ -1,3 +1,5 @@
+use crate::topo::error::ChunkAccessError;
+
 #[derive(te::Error, Debug, PartialEq, Eq, Clone)]
 pub enum ChunkManagerError {
     #[error("Chunk not loaded")]
@@ -12,6 +14,8 @@ pub enum ChunkManagerError {
     MissingEntity,
     #[error("Chunk position is out of bounds")]
     OutOfBounds,
+    #[error(transparent)]
+    AccessError(#[from] ChunkAccessError),
 }
 
 impl ChunkManagerError {
@@ -1,18 +1,22 @@
 pub mod chunk;
 pub mod chunk_manager;
 pub mod chunk_ref;
+pub mod downsample;
+pub mod edits;
 pub mod error;
 pub mod realm;
 
 pub use error::*;
 
-pub use chunk_manager::ChunkManager;
+pub use chunk_manager::{ChunkManager, ContainerHasher};
 
-pub use chunk::{Chunk, ChunkEntity, ChunkPos};
+pub use chunk::{Chunk, ChunkEntity, ChunkLoadState, ChunkPos};
+
+pub use edits::{EditCause, EditTransaction, VoxelEditEvent};
 
 pub use chunk_ref::{
     CaoBlock, ChunkAccessInput, ChunkAccessOutput, ChunkRef, ChunkRefAccess, ChunkRefReadAccess,
-    Crra, Crwa,
+    Crra, Crwa, OwnedChunkAccess,
 };
 
 pub use realm::VoxelRealm;
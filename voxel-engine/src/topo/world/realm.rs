@@ -1,26 +1,16 @@
 use std::sync::Arc;
 
 use bevy::{
-    ecs::{
-        entity::Entity,
-        system::{Res, SystemParam},
-    },
-    math::{ivec3, IVec3},
+    ecs::system::{Res, SystemParam},
     prelude::Resource,
 };
-use dashmap::{mapref::one::Ref, DashSet};
-
-use crate::{
-    topo::{
-        block::{BlockVoxel, FullBlock},
-        controller::{ChunkEcsPermits, ChunkPermitKey, PermitFlags},
-        neighbors::{Neighbors, NEIGHBOR_ARRAY_SIZE, NEIGHBOR_CUBIC_ARRAY_DIMENSIONS},
-        world::chunk::ChunkFlags,
-    },
-    util::{ivec3_to_1d, SyncHashMap},
-};
 
-use super::{chunk_manager::ChunkManager, ChunkPos};
+use crate::topo::controller::{ChunkEcsPermits, ChunkPermitKey, PermitFlags};
+
+use super::{
+    chunk_manager::{ChunkLoadResult, ChunkManager, LoadTicket},
+    ChunkManagerError, ChunkPos,
+};
 
 #[derive(Resource)]
 pub struct ChunkManagerResource(pub(crate) Arc<ChunkManager>);
@@ -40,6 +30,15 @@ impl<'w> VoxelRealm<'w> {
         self.chunk_manager.0.clone()
     }
 
+    /// Force load a chunk, see [`ChunkManager::force_load`].
+    pub fn force_load(
+        &self,
+        pos: ChunkPos,
+        ticket: &LoadTicket,
+    ) -> Result<ChunkLoadResult, ChunkManagerError> {
+        self.cm().force_load(pos, ticket)
+    }
+
     pub fn permits(&self) -> &ChunkEcsPermits {
         &self.permits
     }
@@ -11,7 +11,7 @@ use crate::{
     util::ChunkMap,
 };
 
-use super::{generator::GenerateChunk, GeneratorCommand, GeneratorWorkerPool};
+use super::{generator::GenerateChunk, GeneratedChunk, GeneratorCommand, GeneratorWorkerPool};
 
 #[derive(Resource, Deref, Clone)]
 pub struct GeneratorSeed(pub u32);
@@ -47,6 +47,17 @@ pub fn setup_terrain_generator_workers(
     cmds.insert_resource(GeneratorWorkerTaskPool(task_pool));
 }
 
+/// Stop and join the terrain generator workers, then drop their task pool. Chunks that were being generated
+/// are left as they are.
+pub fn shutdown_terrain_generator_workers(world: &mut World) {
+    if let Some(workers) = world.remove_resource::<GeneratorWorkerPool>() {
+        info!("Shutting down terrain generator workers");
+        workers.shutdown();
+    }
+
+    world.remove_resource::<GeneratorWorkerTaskPool>();
+}
+
 pub fn generate_chunks_from_events(
     mut reader: EventReader<GenerateChunk>,
     mut workers: ResMut<GeneratorWorkerPool>,
@@ -76,3 +87,11 @@ pub fn generate_chunks_from_events(
         debug!("Queued {} generation jobs from events", total);
     }
 }
+
+/// Send events for the chunks that the generator workers finished generating.
+pub fn send_generated_chunk_events(
+    workers: Res<GeneratorWorkerPool>,
+    mut writer: EventWriter<GeneratedChunk>,
+) {
+    writer.send_batch(workers.get_finished_chunks());
+}
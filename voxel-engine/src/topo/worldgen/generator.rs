@@ -8,8 +8,6 @@ use crate::{
     data::{
         registries::{block::BlockVariantRegistry, Registries, Registry},
         resourcepath::rpath,
-        tile::Face,
-        voxel::rotations::BlockModelRotation,
     },
     topo::{
         block::{BlockVoxel, Microblock, SubdividedBlock},
@@ -45,7 +43,6 @@ struct GeneratorPalette {
 pub struct Generator {
     registries: Registries,
     palette: GeneratorPalette,
-    default_rotation: BlockModelRotation,
     noise: Perlin,
     scale: f64,
 }
@@ -56,7 +53,7 @@ impl Generator {
         let variants = registries.get_registry::<BlockVariantRegistry>().unwrap();
 
         let palette = GeneratorPalette {
-            void: variants.get_id(&rpath("void")).unwrap(),
+            void: variants.air(),
             debug: variants.get_id(&rpath("debug")).unwrap(),
             stone: variants.get_id(&rpath("stone")).unwrap(),
             water: variants.get_id(&rpath("water")).unwrap(),
@@ -65,7 +62,6 @@ impl Generator {
         Self {
             registries: registries.clone(),
             palette,
-            default_rotation: BlockModelRotation::new(Face::North, Face::Top).unwrap(),
             noise: Perlin::new(seed),
             scale: 0.1,
         }
@@ -10,11 +10,10 @@ use std::{
 };
 
 use bevy::{
-    ecs::system::Resource,
+    ecs::{event::Event, system::Resource},
     log::{error, warn},
-    tasks::{block_on, futures_lite::future, Task, TaskPool},
+    tasks::{block_on, Task, TaskPool},
 };
-use bhp::KeyComparator;
 use crossbeam::channel::{self, Receiver, RecvTimeoutError, Sender, TrySendError};
 
 use crate::{
@@ -24,7 +23,8 @@ use crate::{
 
 use self::generator::Generator;
 
-use super::world::{chunk::ChunkFlags, chunk_manager::GlobalLockState, ChunkManager, ChunkPos};
+use super::neighbors::NeighborSet;
+use super::world::{chunk::ChunkFlags, ChunkManager, ChunkPos};
 
 pub mod ecs;
 pub mod error;
@@ -33,7 +33,6 @@ pub mod generator;
 pub struct Worker {
     task: Task<()>,
     interrupt: Arc<AtomicBool>,
-    label: String,
 }
 
 #[derive(Clone)]
@@ -41,10 +40,11 @@ pub struct WorkerParams {
     pub registries: Registries,
     pub chunk_manager: Arc<ChunkManager>,
     pub cmds: Receiver<GeneratorCommand>,
+    pub finished: Sender<GeneratedChunk>,
     pub timeout: Duration,
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Ord)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub struct GenerationPriority(u32);
 
 impl GenerationPriority {
@@ -56,9 +56,16 @@ impl GenerationPriority {
     }
 }
 
+// lower raw values are higher priorities
+impl Ord for GenerationPriority {
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        other.0.cmp(&self.0)
+    }
+}
+
 impl PartialOrd for GenerationPriority {
     fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
-        other.0.partial_cmp(&self.0)
+        Some(self.cmp(other))
     }
 }
 
@@ -79,6 +86,14 @@ impl Keyed<GenerationPriority> for GeneratorCommand {
     }
 }
 
+/// A chunk that a generator worker finished generating. The chunk's data is already written into
+/// the chunk manager by the time this is received.
+#[derive(Event, Copy, Clone, Debug, PartialEq, Eq)]
+pub struct GeneratedChunk {
+    pub pos: ChunkPos,
+    pub priority: GenerationPriority,
+}
+
 async fn internal_worker_task(
     generator: Generator,
     params: WorkerParams,
@@ -168,10 +183,19 @@ async fn internal_worker_task(
         // We also set the remesh flags here so that the mesh is built.
         cref.update_flags(|flags| {
             flags.remove(ChunkFlags::GENERATING | ChunkFlags::PRIMORDIAL);
-            flags.insert(
-                ChunkFlags::FRESHLY_GENERATED | ChunkFlags::REMESH_NEIGHBORS | ChunkFlags::REMESH,
-            );
+            flags.insert(ChunkFlags::FRESHLY_GENERATED | ChunkFlags::REMESH);
         });
+        cref.remesh_neighbors(NeighborSet::faces());
+
+        let generated = GeneratedChunk {
+            pos: cpos,
+            priority: cmd.priority,
+        };
+
+        if params.finished.send(generated).is_err() {
+            warn!("Channel disconnected for generator worker '{label}', shutting down.");
+            return;
+        }
     }
 }
 
@@ -181,25 +205,34 @@ impl Worker {
 
         let atomic_interrupt = Arc::new(AtomicBool::new(false));
 
-        let task_label = label.clone();
         let task_interrupt = atomic_interrupt.clone();
         let task = pool.spawn(internal_worker_task(
             generator,
             params,
             task_interrupt,
-            task_label,
+            label,
         ));
 
         Self {
             task,
             interrupt: atomic_interrupt,
-            label,
         }
     }
 
-    pub async fn stop(self) {
+    /// Tell the worker to stop after the command it's currently working on.
+    pub fn interrupt(&self) {
         self.interrupt.store(true, Ordering::Relaxed);
-        future::poll_once(self.task.cancel()).await;
+    }
+
+    /// Wait for the worker to stop. The worker must be [interrupted](Self::interrupt) first, otherwise
+    /// this will wait forever.
+    pub async fn join(self) {
+        self.task.await
+    }
+
+    pub async fn stop(self) {
+        self.interrupt();
+        self.join().await;
     }
 }
 
@@ -214,6 +247,7 @@ pub struct GeneratorWorkerPool {
     workers: Vec<Worker>,
     cmds: Sender<GeneratorCommand>,
     pending: BinaryHeap<KeyedOrd<GeneratorCommand, GenerationPriority>>,
+    finished: Receiver<GeneratedChunk>,
 }
 
 impl GeneratorWorkerPool {
@@ -226,6 +260,7 @@ impl GeneratorWorkerPool {
     ) -> Self {
         let (cmd_sender, cmd_recver) =
             channel::bounded::<GeneratorCommand>(settings.job_channel_capacity);
+        let (finished_sender, finished_recver) = channel::unbounded::<GeneratedChunk>();
         let mut workers = Vec::<Worker>::with_capacity(settings.workers);
 
         let default_channel_timeout_duration = Duration::from_millis(50);
@@ -234,6 +269,7 @@ impl GeneratorWorkerPool {
             registries,
             chunk_manager: cm,
             cmds: cmd_recver,
+            finished: finished_sender,
             timeout: default_channel_timeout_duration,
         };
 
@@ -252,13 +288,31 @@ impl GeneratorWorkerPool {
             workers,
             cmds: cmd_sender,
             pending: BinaryHeap::new(),
+            finished: finished_recver,
         }
     }
 
+    /// Stop all workers and wait for them to finish. All workers are interrupted before any of them
+    /// are joined so that they can wind down in parallel.
     pub fn shutdown(self) {
+        for worker in &self.workers {
+            worker.interrupt();
+        }
+
         for worker in self.workers.into_iter() {
-            block_on(worker.stop());
+            block_on(worker.join());
+        }
+    }
+
+    /// Drain the chunks that were generated since the last call.
+    pub fn get_finished_chunks(&self) -> Vec<GeneratedChunk> {
+        let mut vec = Vec::with_capacity(self.finished.len());
+
+        while let Ok(finished) = self.finished.try_recv() {
+            vec.push(finished);
         }
+
+        vec
     }
 
     pub fn queue_jobs<I: Iterator<Item = GeneratorCommand>>(&mut self, cmds: I) {
@@ -283,3 +337,148 @@ impl GeneratorWorkerPool {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use bevy::{
+        app::{App, Update},
+        ecs::event::Events,
+        tasks::TaskPoolBuilder,
+    };
+
+    use crate::{testing_utils::mock_registries, topo::controller::LoadReasons, util::ChunkSet};
+
+    use super::*;
+
+    fn worker_pool(cm: Arc<ChunkManager>, task_pool: &TaskPool) -> GeneratorWorkerPool {
+        let registries = mock_registries();
+
+        GeneratorWorkerPool::new(
+            GeneratorPoolSettings {
+                workers: 2,
+                job_channel_capacity: 2,
+            },
+            0,
+            task_pool,
+            registries,
+            cm,
+        )
+    }
+
+    #[test]
+    fn generator_pool_generates_queued_chunks() {
+        let cm = Arc::new(ChunkManager::new_test());
+        let positions = [
+            ChunkPos::new(0, 0, 0),
+            ChunkPos::new(1, 0, 0),
+            ChunkPos::new(0, -1, 0),
+            ChunkPos::new(0, 0, 1),
+        ];
+
+        cm.with_global_lock(None, false, |mut access| {
+            for pos in positions {
+                access.load_chunk(pos, LoadReasons::RENDER).unwrap();
+            }
+        })
+        .unwrap();
+
+        let task_pool = TaskPoolBuilder::new().num_threads(2).build();
+        let mut pool = worker_pool(cm.clone(), &task_pool);
+
+        let mut generated = ChunkSet::default();
+        let start = Instant::now();
+
+        pool.queue_jobs(positions.into_iter().map(|pos| GeneratorCommand {
+            pos,
+            priority: GenerationPriority::HIGHEST,
+        }));
+
+        while generated.len() < positions.len() {
+            assert!(
+                start.elapsed() < Duration::from_secs(30),
+                "timed out waiting for chunks to generate"
+            );
+
+            // the channel only fits 2 commands, so the rest are queued once there's room
+            pool.queue_jobs(std::iter::empty());
+
+            for chunk in pool.get_finished_chunks() {
+                generated.set(chunk.pos);
+            }
+
+            thread::sleep(Duration::from_millis(5));
+        }
+
+        for pos in positions {
+            assert!(generated.contains(pos));
+
+            let flags = cm.chunk_flags(pos).unwrap();
+            assert!(!flags.intersects(ChunkFlags::PRIMORDIAL | ChunkFlags::GENERATING));
+            assert!(flags.contains(ChunkFlags::FRESHLY_GENERATED));
+        }
+
+        pool.shutdown();
+
+        // joining the workers drops their handles to the chunk manager
+        assert_eq!(1, Arc::strong_count(&cm));
+    }
+
+    #[test]
+    fn generated_chunks_are_sent_as_events() {
+        let cm = Arc::new(ChunkManager::new_test());
+        let pos = ChunkPos::new(0, 2, 0);
+        cm.with_global_lock(None, false, |mut access| {
+            access.load_chunk(pos, LoadReasons::RENDER).unwrap();
+        })
+        .unwrap();
+
+        let task_pool = TaskPoolBuilder::new().num_threads(2).build();
+        let mut app = App::new();
+        app.insert_resource(worker_pool(cm, &task_pool))
+            .add_event::<GeneratedChunk>()
+            .add_systems(Update, ecs::send_generated_chunk_events);
+
+        app.world
+            .resource_mut::<GeneratorWorkerPool>()
+            .queue_jobs(std::iter::once(GeneratorCommand {
+                pos,
+                priority: GenerationPriority::new(3),
+            }));
+
+        let start = Instant::now();
+        let events = loop {
+            assert!(
+                start.elapsed() < Duration::from_secs(30),
+                "timed out waiting for the chunk to generate"
+            );
+
+            app.update();
+            let events = app
+                .world
+                .resource_mut::<Events<GeneratedChunk>>()
+                .drain()
+                .collect::<Vec<_>>();
+
+            if !events.is_empty() {
+                break events;
+            }
+
+            thread::sleep(Duration::from_millis(5));
+        };
+
+        assert_eq!(
+            vec![GeneratedChunk {
+                pos,
+                priority: GenerationPriority::new(3),
+            }],
+            events
+        );
+
+        app.world
+            .remove_resource::<GeneratorWorkerPool>()
+            .unwrap()
+            .shutdown();
+    }
+}
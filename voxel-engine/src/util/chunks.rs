@@ -1,21 +1,99 @@
+use std::hash::{BuildHasher, Hasher};
+
 use dashmap::{
     mapref::{entry::Entry as DashMapEntry, one::Ref as DashMapRef},
     DashMap,
 };
-use fxhash::FxBuildHasher;
-use hb::hash_map::{Drain, Entry as HashbrownEntry};
+use hb::hash_map::{Drain, Entry as HashbrownEntry, IntoIter};
 use itertools::Itertools;
 
 use crate::topo::world::ChunkPos;
 
+/// Hashes [`ChunkPos`]es by interleaving the bits of their coordinates (a Morton or Z-order code), so nearby
+/// chunks get similar codes, and then scrambling the code with a single multiplication. This is a lot cheaper than
+/// a general purpose hash of three integers, and chunk maps are hashed constantly.
+#[derive(Copy, Clone, Default, Debug)]
+pub struct ChunkPosBuildHasher;
+
+impl BuildHasher for ChunkPosBuildHasher {
+    type Hasher = ChunkPosHasher;
+
+    fn build_hasher(&self) -> Self::Hasher {
+        ChunkPosHasher::default()
+    }
+}
+
+/// The hasher built by [`ChunkPosBuildHasher`]. Only meant for hashing [`ChunkPos`]es, other data is hashed
+/// correctly but slowly.
+#[derive(Copy, Clone, Default, Debug)]
+pub struct ChunkPosHasher {
+    coords: [u32; 3],
+    written: usize,
+    /// Anything written after the 3 coordinates of a chunk position.
+    extra: u64,
+}
+
+impl ChunkPosHasher {
+    const MULTIPLIER: u64 = 0x9e37_79b9_7f4a_7c15;
+
+    /// Spread the low 21 bits of `v` out so there are 2 zero bits between each of them.
+    fn spread(v: u32) -> u64 {
+        let mut v = v as u64 & 0x1f_ffff;
+        v = (v | v << 32) & 0x001f_0000_0000_ffff;
+        v = (v | v << 16) & 0x001f_0000_ff00_00ff;
+        v = (v | v << 8) & 0x100f_00f0_0f00_f00f;
+        v = (v | v << 4) & 0x10c3_0c30_c30c_30c3;
+        v = (v | v << 2) & 0x1249_2492_4924_9249;
+        v
+    }
+
+    /// The Morton code of the (lowest 21 bits of the) coordinates written so far.
+    pub fn morton(&self) -> u64 {
+        let [x, y, z] = self.coords;
+        Self::spread(x) | Self::spread(y) << 1 | Self::spread(z) << 2
+    }
+}
+
+impl Hasher for ChunkPosHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.extra = (self.extra.rotate_left(5) ^ byte as u64).wrapping_mul(Self::MULTIPLIER);
+        }
+    }
+
+    #[inline]
+    fn write_i32(&mut self, i: i32) {
+        match self.coords.get_mut(self.written) {
+            Some(coord) => {
+                *coord = i as u32;
+                self.written += 1;
+            }
+            None => self.write(&i.to_ne_bytes()),
+        }
+    }
+
+    #[inline]
+    fn finish(&self) -> u64 {
+        // coordinates above 21 bits wrap around in the morton code, so they're mixed in here as well
+        let [x, y, z] = self.coords.map(|c| (c >> 21) as u64);
+        let high = x | y << 11 | z << 22;
+
+        let hash =
+            (self.morton() ^ high.rotate_left(33) ^ self.extra).wrapping_mul(Self::MULTIPLIER);
+        // hashbrown uses the top bits of the hash, which are the best mixed bits of the product,
+        // and the bottom bits for the bucket index, so fold the top bits into the bottom ones
+        hash ^ (hash >> 32)
+    }
+}
+
 #[derive(Clone, Default, Debug)]
-pub struct ChunkSet(hb::HashSet<ChunkPos, wyhash2::WyHash>);
+pub struct ChunkSet(hb::HashSet<ChunkPos, ChunkPosBuildHasher>);
 
 impl ChunkSet {
     pub fn with_capacity(capacity: usize) -> Self {
         Self(hb::HashSet::with_capacity_and_hasher(
             capacity,
-            wyhash2::WyHash::default(),
+            ChunkPosBuildHasher,
         ))
     }
 
@@ -31,6 +109,10 @@ impl ChunkSet {
         self.0.len()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
     pub fn remove(&mut self, pos: ChunkPos) -> bool {
         self.0.remove(&pos)
     }
@@ -45,7 +127,7 @@ impl ChunkSet {
 }
 
 #[derive(Clone)]
-pub struct SyncChunkMap<T>(DashMap<ChunkPos, T, wyhash2::WyHash>);
+pub struct SyncChunkMap<T>(DashMap<ChunkPos, T, ChunkPosBuildHasher>);
 
 impl<T> Default for SyncChunkMap<T> {
     fn default() -> Self {
@@ -55,14 +137,14 @@ impl<T> Default for SyncChunkMap<T> {
 
 impl<T> SyncChunkMap<T> {
     pub fn new() -> Self {
-        Self(DashMap::with_hasher(wyhash2::WyHash::default()))
+        Self(DashMap::with_hasher(ChunkPosBuildHasher))
     }
 
     pub fn set(&self, pos: ChunkPos, data: T) -> Option<T> {
         self.0.insert(pos, data)
     }
 
-    pub fn get(&self, pos: ChunkPos) -> Option<DashMapRef<ChunkPos, T, wyhash2::WyHash>> {
+    pub fn get(&self, pos: ChunkPos) -> Option<DashMapRef<'_, ChunkPos, T, ChunkPosBuildHasher>> {
         self.0.get(&pos)
     }
 
@@ -74,7 +156,7 @@ impl<T> SyncChunkMap<T> {
         self.0.contains_key(&pos)
     }
 
-    pub fn entry(&self, pos: ChunkPos) -> DashMapEntry<'_, ChunkPos, T, wyhash2::WyHash> {
+    pub fn entry(&self, pos: ChunkPos) -> DashMapEntry<'_, ChunkPos, T, ChunkPosBuildHasher> {
         self.0.entry(pos)
     }
 
@@ -82,7 +164,7 @@ impl<T> SyncChunkMap<T> {
     where
         F: FnMut(ChunkPos),
     {
-        for entry in self.0.iter().map(|e| e) {
+        for entry in self.0.iter() {
             f(*entry.key())
         }
     }
@@ -95,6 +177,10 @@ impl<T> SyncChunkMap<T> {
         self.0.len()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
     pub fn capacity(&self) -> usize {
         self.0.capacity()
     }
@@ -105,7 +191,7 @@ impl<T> SyncChunkMap<T> {
 }
 
 #[derive(Clone)]
-pub struct ChunkMap<T>(hb::HashMap<ChunkPos, T, wyhash2::WyHash>);
+pub struct ChunkMap<T>(hb::HashMap<ChunkPos, T, ChunkPosBuildHasher>);
 
 impl<T> Default for ChunkMap<T> {
     fn default() -> Self {
@@ -115,13 +201,13 @@ impl<T> Default for ChunkMap<T> {
 
 impl<T> ChunkMap<T> {
     pub fn new() -> Self {
-        Self(hb::HashMap::with_hasher(wyhash2::WyHash::default()))
+        Self(hb::HashMap::with_hasher(ChunkPosBuildHasher))
     }
 
     pub fn with_capacity(capacity: usize) -> Self {
         Self(hb::HashMap::with_capacity_and_hasher(
             capacity,
-            wyhash2::WyHash::default(),
+            ChunkPosBuildHasher,
         ))
     }
 
@@ -145,7 +231,7 @@ impl<T> ChunkMap<T> {
         self.0.contains_key(&pos)
     }
 
-    pub fn entry(&mut self, pos: ChunkPos) -> HashbrownEntry<'_, ChunkPos, T, wyhash2::WyHash> {
+    pub fn entry(&mut self, pos: ChunkPos) -> HashbrownEntry<'_, ChunkPos, T, ChunkPosBuildHasher> {
         self.0.entry(pos)
     }
 
@@ -180,6 +266,10 @@ impl<T> ChunkMap<T> {
         self.0.len()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
     pub fn capacity(&self) -> usize {
         self.0.capacity()
     }
@@ -192,10 +282,6 @@ impl<T> ChunkMap<T> {
         self.0.iter().map(|(&pos, data)| (pos, data))
     }
 
-    pub fn into_iter(self) -> impl Iterator<Item = (ChunkPos, T)> {
-        self.0.into_iter()
-    }
-
     pub fn clear(&mut self) {
         self.0.clear()
     }
@@ -204,3 +290,69 @@ impl<T> ChunkMap<T> {
         self.0.drain()
     }
 }
+
+impl<T> IntoIterator for ChunkMap<T> {
+    type Item = (ChunkPos, T);
+    type IntoIter = IntoIter<ChunkPos, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::IVec3;
+
+    use crate::topo::bounding_box::BoundingBox;
+
+    use super::*;
+
+    fn hasher_for(pos: ChunkPos) -> ChunkPosHasher {
+        let mut hasher = ChunkPosBuildHasher.build_hasher();
+        std::hash::Hash::hash(&pos, &mut hasher);
+        hasher
+    }
+
+    #[test]
+    fn chunk_pos_morton_code() {
+        assert_eq!(0, hasher_for(ChunkPos::new(0, 0, 0)).morton());
+        assert_eq!(0b001, hasher_for(ChunkPos::new(1, 0, 0)).morton());
+        assert_eq!(0b010, hasher_for(ChunkPos::new(0, 1, 0)).morton());
+        assert_eq!(0b100, hasher_for(ChunkPos::new(0, 0, 1)).morton());
+        assert_eq!(0b111_111, hasher_for(ChunkPos::new(3, 3, 3)).morton());
+        assert_eq!(0b100_000, hasher_for(ChunkPos::new(0, 0, 2)).morton());
+    }
+
+    #[test]
+    fn chunk_pos_hash_collisions() {
+        let region = BoundingBox::new(IVec3::splat(-32), IVec3::splat(31));
+
+        let hashes = region
+            .cartesian_iter()
+            .map(|pos| ChunkPosBuildHasher.hash_one(ChunkPos::from(pos)))
+            .collect::<hb::HashSet<u64>>();
+        assert_eq!(region.volume() as usize, hashes.len());
+
+        // far away chunks don't collide with the chunks near the origin
+        let far = [
+            ChunkPos::new(1 << 21, 0, 0),
+            ChunkPos::new(0, -(1 << 22), 0),
+            ChunkPos::new(i32::MAX, i32::MIN, 7),
+        ];
+        for pos in far {
+            assert!(
+                !hashes.contains(&ChunkPosBuildHasher.hash_one(pos)),
+                "{pos}"
+            );
+        }
+
+        let mut map = ChunkMap::new();
+        for pos in region.cartesian_iter().chain(far.map(ChunkPos::as_ivec3)) {
+            map.set(ChunkPos::from(pos), pos);
+        }
+        for pos in region.cartesian_iter() {
+            assert_eq!(Some(&pos), map.get(ChunkPos::from(pos)));
+        }
+    }
+}
@@ -1,12 +1,67 @@
 use std::array;
 
-use bevy::math::UVec3;
+use bevy::math::{IVec3, UVec3};
 use slice_of_array::SliceFlatExt;
 
 use crate::topo::storage::error::OutOfBounds;
 
 use super::{uvec_to_usize_arr, CubicArray};
 
+/// Bounds-checked indexing of a [`CubicArray`] by position, so callers don't need to convert positions
+/// to indices themselves. Positions are indexed as `array[x][y][z]`.
+pub trait CubicArrayExt<T> {
+    /// Returns `true` if `pos` is within the bounds of the array.
+    fn contains_pos(&self, pos: IVec3) -> bool;
+
+    fn at(&self, pos: IVec3) -> Result<&T, OutOfBounds>;
+
+    fn at_mut(&mut self, pos: IVec3) -> Result<&mut T, OutOfBounds>;
+
+    /// Like [`CubicArrayExt::at`] but without the bounds check, for hot paths where the position is known to
+    /// be in bounds. Out of bounds positions are caught by a debug assertion, and panic in release builds.
+    fn at_unchecked(&self, pos: IVec3) -> &T;
+
+    /// Like [`CubicArrayExt::at_mut`] but without the bounds check, see [`CubicArrayExt::at_unchecked`].
+    fn at_unchecked_mut(&mut self, pos: IVec3) -> &mut T;
+}
+
+impl<const S: usize, T> CubicArrayExt<T> for CubicArray<S, T> {
+    #[inline]
+    fn contains_pos(&self, pos: IVec3) -> bool {
+        pos.cmpge(IVec3::ZERO).all() && pos.cmplt(IVec3::splat(S as i32)).all()
+    }
+
+    #[inline]
+    fn at(&self, pos: IVec3) -> Result<&T, OutOfBounds> {
+        if !self.contains_pos(pos) {
+            return Err(OutOfBounds);
+        }
+
+        Ok(self.at_unchecked(pos))
+    }
+
+    #[inline]
+    fn at_mut(&mut self, pos: IVec3) -> Result<&mut T, OutOfBounds> {
+        if !self.contains_pos(pos) {
+            return Err(OutOfBounds);
+        }
+
+        Ok(self.at_unchecked_mut(pos))
+    }
+
+    #[inline]
+    fn at_unchecked(&self, pos: IVec3) -> &T {
+        debug_assert!(self.contains_pos(pos), "position {pos} out of bounds");
+        &self[pos.x as usize][pos.y as usize][pos.z as usize]
+    }
+
+    #[inline]
+    fn at_unchecked_mut(&mut self, pos: IVec3) -> &mut T {
+        debug_assert!(self.contains_pos(pos), "position {pos} out of bounds");
+        &mut self[pos.x as usize][pos.y as usize][pos.z as usize]
+    }
+}
+
 #[derive(Clone, PartialEq, Eq, Hash)]
 pub struct Cubic<const S: usize, T>(CubicArray<S, T>);
 
@@ -65,3 +120,31 @@ impl<const S: usize, T> Cubic<S, Option<T>> {
         }))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::ivec3;
+
+    use super::*;
+
+    #[test]
+    fn cubic_array_indexing() {
+        let mut arr: CubicArray<4, u32> = [[[0; 4]; 4]; 4];
+
+        *arr.at_mut(ivec3(1, 2, 3)).unwrap() = 5;
+        assert_eq!(Ok(&5), arr.at(ivec3(1, 2, 3)));
+        assert_eq!(5, arr[1][2][3]);
+        assert_eq!(&5, arr.at_unchecked(ivec3(1, 2, 3)));
+
+        assert_eq!(Ok(&0), arr.at(ivec3(0, 0, 0)));
+        assert_eq!(Ok(&0), arr.at(ivec3(3, 3, 3)));
+
+        for axis in [IVec3::X, IVec3::Y, IVec3::Z] {
+            for pos in [axis * -1, axis * 4, axis * 100 + IVec3::ONE] {
+                assert_eq!(Err(OutOfBounds), arr.at(pos), "{pos}");
+                assert_eq!(Err(OutOfBounds), arr.at_mut(pos), "{pos}");
+                assert!(!arr.contains_pos(pos));
+            }
+        }
+    }
+}
@@ -8,7 +8,7 @@ use crate::topo::{
 #[inline]
 pub const fn rem_euclid_2_pow_n(x: i32, n: u32) -> i32 {
     let pow = 0b1 << n;
-    x & ((pow - 1) as i32)
+    x & (pow - 1)
 }
 
 #[inline]
@@ -21,7 +21,7 @@ impl<T: Keyed<K>, K> KeyedOrd<T, K> {
     pub fn new(data: T) -> Self {
         Self {
             data,
-            _k: PhantomData::default(),
+            _k: PhantomData,
         }
     }
 
@@ -54,7 +54,7 @@ impl<T: Keyed<K>, K> Eq for KeyedOrd<T, K> {}
 
 impl<T: Keyed<K>, K> PartialOrd for KeyedOrd<T, K> {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        self.data.key().partial_cmp(other.data.key())
+        Some(self.cmp(other))
     }
 }
 
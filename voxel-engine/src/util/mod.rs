@@ -2,6 +2,7 @@ pub mod cubic;
 pub mod intdiv;
 pub mod notnan;
 pub mod result;
+pub mod space;
 pub use intdiv::*;
 
 pub mod chunks;
@@ -39,7 +40,7 @@ pub fn ivec3_to_1d(v: IVec3, max: usize) -> Result<usize, ConversionError> {
 }
 
 pub fn to_1d(x: usize, y: usize, z: usize, max: usize) -> usize {
-    return (z * max * max) + (y * max) + x;
+    (z * max * max) + (y * max) + x
 }
 
 pub fn try_ivec3_to_usize_arr(ivec: IVec3) -> Result<[usize; 3], ConversionError> {
@@ -154,6 +155,11 @@ impl<T> FaceMap<T> {
         Self(Face::FACES.map(f))
     }
 
+    /// Create a filled map where the value of each face is given by `f`.
+    pub fn filled_with<F: FnMut(Face) -> T>(mut f: F) -> Self {
+        Self(Face::FACES.map(|face| Some(f(face))))
+    }
+
     pub fn iter(&self) -> FaceMapIterator<'_, T> {
         FaceMapIterator {
             map: self,
@@ -219,6 +225,10 @@ impl<T> FaceMap<T> {
         self.0.iter().filter(|&v| v.is_some()).count()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.0.iter().all(|v| v.is_none())
+    }
+
     pub fn is_filled(&self) -> bool {
         self.len() == 6
     }
@@ -321,6 +331,8 @@ impl<T: Copy, const SIZE: usize> ArrayExt for [T; SIZE] {
 
 #[cfg(test)]
 mod tests {
+    use itertools::Itertools;
+
     use super::*;
 
     #[test]
@@ -378,5 +390,17 @@ mod tests {
         assert_eq!(expected_map, map);
     }
 
+    #[test]
+    fn test_facemap_filled_with() {
+        let map = FaceMap::filled_with(|face| face as u8);
+
+        assert!(map.is_filled());
+        assert_eq!(
+            Face::FACES.map(|face| face as u8).to_vec(),
+            map.iter().map(|(_, v)| *v.unwrap()).collect::<Vec<_>>()
+        );
+        assert!(map.iter().map(|(_, v)| v).all_unique());
+    }
+
     // TODO: FaceMap serialization test
 }
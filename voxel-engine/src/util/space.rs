@@ -0,0 +1,137 @@
+//! Conversions between the coordinate spaces of the world.
+//!
+//! - Worldspace: continuous positions in the world, one unit is one voxel.
+//! - Voxelspace: integer positions of voxels in the world, the voxel at `v` spans `v..v + 1` in worldspace.
+//! - Chunkspace: integer positions of chunks, see [`ChunkPos`].
+//! - Localspace: voxel positions relative to the minimum corner of their chunk, in `0..Chunk::SIZE`.
+//!
+//! Voxel positions are always floored into chunks (with `div_euclid` and `rem_euclid`), so negative
+//! positions belong to the chunk below them. For example worldspace `x = -1` is in chunk `-1` at local `x = 15`.
+
+use bevy::math::{IVec3, Vec3};
+
+use crate::topo::world::{Chunk, ChunkPos};
+
+/// The voxel containing the worldspace position.
+#[inline]
+pub fn world_to_voxel(ws: Vec3) -> IVec3 {
+    ws.floor().as_ivec3()
+}
+
+/// The worldspace position of the minimum corner of the voxel.
+#[inline]
+pub fn voxel_to_world(voxel: IVec3) -> Vec3 {
+    voxel.as_vec3()
+}
+
+/// The chunk containing the voxel.
+#[inline]
+pub fn voxel_to_chunk(voxel: IVec3) -> ChunkPos {
+    ChunkPos::from(voxel.div_euclid(Chunk::VEC))
+}
+
+/// The localspace position of the voxel in its chunk.
+#[inline]
+pub fn voxel_to_local(voxel: IVec3) -> IVec3 {
+    voxel.rem_euclid(Chunk::VEC)
+}
+
+/// The chunk containing the voxel, and the localspace position of the voxel in that chunk.
+#[inline]
+pub fn voxel_to_chunk_local(voxel: IVec3) -> (ChunkPos, IVec3) {
+    (voxel_to_chunk(voxel), voxel_to_local(voxel))
+}
+
+/// The voxel at the localspace position in the chunk. Inverse of [`voxel_to_chunk_local`].
+#[inline]
+pub fn chunk_local_to_voxel(chunk: ChunkPos, local: IVec3) -> IVec3 {
+    chunk.worldspace_min() + local
+}
+
+/// The chunk containing the worldspace position.
+#[inline]
+pub fn world_to_chunk(ws: Vec3) -> ChunkPos {
+    voxel_to_chunk(world_to_voxel(ws))
+}
+
+/// The worldspace position of the minimum corner of the chunk.
+#[inline]
+pub fn chunk_to_world_min(chunk: ChunkPos) -> Vec3 {
+    voxel_to_world(chunk.worldspace_min())
+}
+
+/// The worldspace position of the center of the chunk.
+#[inline]
+pub fn chunk_to_world_center(chunk: ChunkPos) -> Vec3 {
+    chunk_to_world_min(chunk) + Vec3::splat(Chunk::SIZE as f32 / 2.0)
+}
+
+/// The worldspace position of the minimum corner of the voxel at the localspace position in the chunk.
+#[inline]
+pub fn chunk_local_to_world(chunk: ChunkPos, local: IVec3) -> Vec3 {
+    voxel_to_world(chunk_local_to_voxel(chunk, local))
+}
+
+/// The chunk containing the worldspace position, and the localspace position of the voxel containing it.
+/// Inverse of [`chunk_local_to_world`] for positions in the minimum corner of a voxel.
+#[inline]
+pub fn world_to_chunk_local(ws: Vec3) -> (ChunkPos, IVec3) {
+    voxel_to_chunk_local(world_to_voxel(ws))
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::{ivec3, vec3};
+
+    use super::*;
+
+    #[test]
+    fn negative_coordinates() {
+        assert_eq!(
+            (ChunkPos::new(-1, 0, -1), ivec3(15, 0, 0)),
+            voxel_to_chunk_local(ivec3(-1, 0, -16))
+        );
+        assert_eq!(
+            (ChunkPos::new(-2, -1, 0), ivec3(15, 0, 15)),
+            voxel_to_chunk_local(ivec3(-17, -16, 15))
+        );
+
+        // worldspace positions between -1 and 0 are in voxel -1, not voxel 0
+        assert_eq!(ivec3(-1, -1, 0), world_to_voxel(vec3(-0.5, -0.01, 0.5)));
+        assert_eq!(
+            (ChunkPos::new(-1, -1, 0), ivec3(15, 15, 0)),
+            world_to_chunk_local(vec3(-0.5, -0.01, 0.5))
+        );
+
+        assert_eq!(
+            vec3(-16.0, 0.0, 16.0),
+            chunk_to_world_min(ChunkPos::new(-1, 0, 1))
+        );
+        assert_eq!(
+            vec3(-8.0, 8.0, 24.0),
+            chunk_to_world_center(ChunkPos::new(-1, 0, 1))
+        );
+    }
+
+    #[test]
+    fn conversions_round_trip() {
+        for voxel in [
+            ivec3(0, 0, 0),
+            ivec3(-1, -1, -1),
+            ivec3(-16, 15, 16),
+            ivec3(-17, -33, 31),
+            ivec3(100, -100, 7),
+        ] {
+            let (chunk, local) = voxel_to_chunk_local(voxel);
+
+            assert!(local.cmpge(IVec3::ZERO).all() && local.cmplt(Chunk::VEC).all());
+            assert_eq!(voxel, chunk_local_to_voxel(chunk, local));
+            assert_eq!(ChunkPos::from_worldspace(voxel), chunk);
+            assert_eq!(crate::util::ws_to_chunk_pos(voxel), chunk);
+
+            let ws = chunk_local_to_world(chunk, local);
+            assert_eq!((chunk, local), world_to_chunk_local(ws));
+            assert_eq!((chunk, local), world_to_chunk_local(ws + Vec3::splat(0.99)));
+        }
+    }
+}